@@ -0,0 +1,60 @@
+//! Resources built into the binaries, replaceable by files on disk.
+//!
+//! Apps embed their defaults with `include_bytes!` so they run wherever the
+//! executable is copied to. A file of the same name in `--assets <dir>`, or
+//! in `res/` next to the executable, is read instead, to swap a sample
+//! without rebuilding.
+
+use crate::cli;
+use std::{borrow::Cow, fs, io, path::PathBuf};
+
+/// the directory next to the executable overrides are looked up in
+const DIR: &str = "res";
+
+#[derive(Clone, Copy, Debug)]
+pub struct Asset {
+    pub name: &'static str,
+    embedded: &'static [u8],
+}
+
+impl Asset {
+    /// `name` is the file name overrides go by
+    pub const fn new(name: &'static str, embedded: &'static [u8]) -> Self {
+        Self { name, embedded }
+    }
+
+    /// the file replacing the embedded copy, if there is one
+    pub fn override_path(&self) -> Option<PathBuf> {
+        dirs()
+            .into_iter()
+            .map(|dir| dir.join(self.name))
+            .find(|path| path.is_file())
+    }
+
+    /// the override's contents, else the embedded copy
+    pub fn bytes(&self) -> io::Result<Cow<'static, [u8]>> {
+        match self.override_path() {
+            Some(path) => fs::read(path).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(self.embedded)),
+        }
+    }
+
+    /// where `bytes` came from, for error messages
+    pub fn source(&self) -> PathBuf {
+        self.override_path()
+            .unwrap_or_else(|| PathBuf::from(format!("<embedded {}>", self.name)))
+    }
+}
+
+/// where overrides are looked up, `--assets` first
+pub fn dirs() -> Vec<PathBuf> {
+    let beside_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(DIR)));
+    cli::args()
+        .assets
+        .clone()
+        .into_iter()
+        .chain(beside_exe)
+        .collect()
+}
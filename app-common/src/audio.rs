@@ -0,0 +1,668 @@
+use crate::diagnostics::CallbackStats;
+use crate::jack::{Command, JackConfig, JackOutput};
+use crate::plugin::{Insert, Plugin, Processor};
+use crate::render::{self, Render};
+#[cfg(not(feature = "audio"))]
+use dsp_common::denormal::DenormalGuard;
+#[cfg(feature = "audio")]
+use nannou_audio as audio;
+#[cfg(feature = "audio")]
+use nannou_audio::Buffer;
+use ringbuf::Consumer;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+#[cfg(not(feature = "audio"))]
+use std::{sync::atomic::AtomicBool, thread};
+
+const SCAN_INTERVAL: Duration = Duration::from_millis(1000);
+const STALL_TIMEOUT: Duration = Duration::from_millis(500);
+/// what an insert is first activated at when nothing asks for a rate
+const DEFAULT_RATE: u32 = 48_000;
+/// how long overloads are counted over before the count starts again
+const OVERLOAD_WINDOW: Duration = Duration::from_secs(5);
+/// overloads in a window for it to count as overrun
+const OVERRUN: usize = 3;
+/// windows overrun in a row before the buffer grows, a passing spike
+/// doesn't cost latency for good
+const SUSTAINED: usize = 3;
+/// the buffer grows no further, about 170ms at 48kHz
+const MAX_FRAMES: usize = 8192;
+/// grown from when neither the config nor a callback says otherwise
+const DEFAULT_FRAMES: usize = 512;
+
+/// `None` leaves the choice to the device
+#[derive(Clone, Debug, Default)]
+pub struct StreamConfig {
+    /// audio API by name, see `hosts`, the platform default when `None`
+    pub host: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub frames_per_buffer: Option<usize>,
+    pub channels: Option<usize>,
+    /// follow the system default when `None`
+    pub device: Option<String>,
+    /// run on a JACK server instead of an output device
+    pub jack: Option<JackConfig>,
+    /// CLAP effect over the engine's output, see `plugin`
+    pub insert: Option<PathBuf>,
+    /// double `frames_per_buffer` after sustained overloads, see
+    /// `Supervisor::poll`
+    pub adaptive: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    NoDevice,
+    Build(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoDevice => write!(f, "no audio output device available"),
+            Error::Build(e) => write!(f, "failed to build audio stream: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// names of the audio APIs available on this platform, e.g. ALSA and JACK,
+/// none without the `audio` feature
+pub fn hosts() -> Vec<String> {
+    #[cfg(feature = "audio")]
+    {
+        audio::cpal::available_hosts()
+            .into_iter()
+            .map(|id| id.name().to_string())
+            .collect()
+    }
+    #[cfg(not(feature = "audio"))]
+    Vec::new()
+}
+
+/// the host called `name`, the platform default without one or if it
+/// can't be opened
+#[cfg(feature = "audio")]
+pub(crate) fn host(name: Option<&str>) -> audio::Host {
+    let id = name.and_then(|name| {
+        audio::cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name() == name)
+    });
+    match id.map(audio::cpal::host_from_id) {
+        Some(Ok(host)) => audio::Host::from_host(host),
+        Some(Err(e)) => {
+            eprintln!("falling back to the default audio host: {}", e);
+            audio::Host::new()
+        }
+        None => audio::Host::new(),
+    }
+}
+
+/// names of the output devices of the host called `host`, see `hosts`
+pub fn output_devices(host: Option<&str>) -> Vec<String> {
+    #[cfg(feature = "audio")]
+    {
+        self::host(host)
+            .output_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+    #[cfg(not(feature = "audio"))]
+    {
+        let _ = host;
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// the stream was rebuilt on another device, engine state preserved
+    Rebuilt { device: String },
+    /// the device went away and no other one is available yet
+    Lost,
+    /// overloads kept up, the stream was rebuilt with a bigger buffer
+    Enlarged { frames_per_buffer: usize },
+}
+
+/// What actually lives on the audio thread.
+///
+/// The engine sits behind a mutex the audio thread only ever `try_lock`s,
+/// the UI thread only locks it while no stream is running, so it survives
+/// the stream being torn down and rebuilt. A callback that misses the lock
+/// leaves its buffer silent, and the commands sent meanwhile wait in the
+/// channel for the next one that gets it.
+#[cfg(feature = "audio")]
+pub struct Monitored<M> {
+    engine: Arc<Mutex<M>>,
+    heartbeat: Arc<AtomicUsize>,
+    stats: Arc<CallbackStats>,
+    commands: mpsc::Receiver<Command<M>>,
+}
+
+#[cfg(feature = "audio")]
+fn render<M: Render>(monitored: &mut Monitored<M>, buffer: &mut Buffer) {
+    let start = Instant::now();
+    if let Ok(mut engine) = monitored.engine.try_lock() {
+        let engine = &mut *engine;
+        for command in monitored.commands.try_iter() {
+            render::contain(
+                &monitored.stats,
+                || command(engine),
+                || String::from("running a command"),
+            );
+        }
+        render::callback(engine, buffer, &monitored.stats);
+    }
+    monitored.heartbeat.fetch_add(1, Ordering::Relaxed);
+    monitored
+        .stats
+        .record(start, buffer.len_frames(), buffer.sample_rate());
+}
+
+/// Renders into nothing at the stream's rate, for builds without the `audio`
+/// feature so the visuals still follow the engine.
+#[cfg(not(feature = "audio"))]
+struct Silent<M: 'static + Send> {
+    commands: mpsc::Sender<Command<M>>,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[cfg(not(feature = "audio"))]
+impl<M: Render + 'static + Send> Silent<M> {
+    const NAME: &'static str = "silent";
+
+    fn start(
+        engine: Arc<Mutex<M>>,
+        heartbeat: Arc<AtomicUsize>,
+        stats: Arc<CallbackStats>,
+        config: &StreamConfig,
+    ) -> Self {
+        let sample_rate = config.sample_rate.unwrap_or(48_000);
+        let frames = config.frames_per_buffer.unwrap_or(512);
+        let channels = config.channels.unwrap_or(2);
+        let period = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+        let (commands, receiver) = mpsc::channel::<Command<M>>();
+        let running = Arc::new(AtomicBool::new(true));
+        let keep_running = running.clone();
+        let thread = thread::spawn(move || {
+            let _denormals = DenormalGuard::new();
+            let mut scratch = vec![0.0; frames * channels];
+            let mut next = Instant::now();
+            while keep_running.load(Ordering::Relaxed) {
+                let start = Instant::now();
+                if let Ok(mut engine) = engine.try_lock() {
+                    let engine = &mut *engine;
+                    for command in receiver.try_iter() {
+                        render::contain(
+                            &stats,
+                            || command(engine),
+                            || String::from("running a command"),
+                        );
+                    }
+                    scratch.iter_mut().for_each(|sample| *sample = 0.0);
+                    render::render_contained(engine, &mut scratch, channels, sample_rate, &stats);
+                }
+                heartbeat.fetch_add(1, Ordering::Relaxed);
+                stats.record(start, frames, sample_rate);
+                // on a schedule rather than a sleep per buffer, so the clock
+                // doesn't drift by however long rendering took
+                next += period;
+                match next.checked_duration_since(Instant::now()) {
+                    Some(wait) => thread::sleep(wait),
+                    None => next = Instant::now(),
+                }
+            }
+        });
+        Self {
+            commands,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, f: Command<M>) {
+        let _ = self.commands.send(f);
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl<M: 'static + Send> Drop for Silent<M> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+enum Output<M: 'static + Send> {
+    /// and the commands its callback runs before rendering
+    #[cfg(feature = "audio")]
+    Device(audio::Stream<Monitored<M>>, mpsc::Sender<Command<M>>),
+    #[cfg(not(feature = "audio"))]
+    Silent(Silent<M>),
+    Jack(JackOutput<M>),
+}
+
+/// Owns an output stream and rebuilds it when the device disappears,
+/// the default device changes or the callback stops being called.
+///
+/// Without the `audio` feature there are no devices, it renders on a timer
+/// thread instead and reports the device as "silent".
+pub struct Supervisor<M: 'static + Send> {
+    #[cfg(feature = "audio")]
+    host: audio::Host,
+    config: StreamConfig,
+    engine: Arc<Mutex<Insert<M>>>,
+    stream: Option<Output<Insert<M>>>,
+    device: Option<String>,
+    heartbeat: Arc<AtomicUsize>,
+    stats: Arc<CallbackStats>,
+    last_beat: (usize, Instant),
+    last_scan: Instant,
+    overruns: Overruns,
+    /// activated again with every stream, its processor in the engine
+    insert: Option<Plugin>,
+    /// processors the engine let go of, dropped on this thread
+    retired: Consumer<Processor>,
+}
+
+/// Overloads counted a window at a time, for `Supervisor::adapt`.
+struct Overruns {
+    /// when the window started and the overload count then
+    start: (Instant, usize),
+    /// windows overrun in a row
+    windows: usize,
+    /// the buffer a device refused, it isn't asked for again
+    ceiling: usize,
+}
+
+impl Overruns {
+    fn new(overloads: usize) -> Self {
+        Self {
+            start: (Instant::now(), overloads),
+            windows: 0,
+            ceiling: MAX_FRAMES * 2,
+        }
+    }
+}
+
+/// `path`'s effect, or why not on stderr
+fn load_insert(path: &Path) -> Option<Plugin> {
+    match Plugin::load(path) {
+        Ok(plugin) => {
+            println!("inserted {} from {}", plugin.name(), path.display());
+            Some(plugin)
+        }
+        Err(e) => {
+            eprintln!("no insert: {}", e);
+            None
+        }
+    }
+}
+
+impl<M: Render + 'static + Send> Supervisor<M> {
+    pub fn new(engine: M, config: StreamConfig) -> Result<Self, Error> {
+        let mut supervisor = Self::idle(engine, config);
+        supervisor.rebuild()?;
+        Ok(supervisor)
+    }
+
+    /// without a stream yet, `rebuild` or `poll` starts one and the engine
+    /// is kept either way
+    pub fn idle(engine: M, config: StreamConfig) -> Self {
+        let (engine, retired) = Insert::new(engine);
+        Self {
+            #[cfg(feature = "audio")]
+            host: host(config.host.as_deref()),
+            insert: config.insert.as_deref().and_then(load_insert),
+            config,
+            engine: Arc::new(Mutex::new(engine)),
+            stream: None,
+            device: None,
+            heartbeat: Arc::new(AtomicUsize::new(0)),
+            stats: Arc::new(CallbackStats::default()),
+            last_beat: (0, Instant::now()),
+            last_scan: Instant::now(),
+            overruns: Overruns::new(0),
+            retired,
+        }
+    }
+
+    pub fn config(&self) -> &StreamConfig {
+        &self.config
+    }
+
+    /// name of the device or JACK client currently rendering, if any
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// callback timing, kept across rebuilds
+    pub fn stats(&self) -> Arc<CallbackStats> {
+        self.stats.clone()
+    }
+
+    /// the effect over the engine's output, if one loaded
+    pub fn insert(&self) -> Option<&Plugin> {
+        self.insert.as_ref()
+    }
+
+    /// for its editor, see `Plugin::key_pressed`
+    pub fn insert_mut(&mut self) -> Option<&mut Plugin> {
+        self.insert.as_mut()
+    }
+
+    /// run `f` on the audio thread against the engine, before the next
+    /// buffer that gets the engine's lock
+    pub fn send<F>(&self, f: F)
+    where
+        F: FnOnce(&mut M) + Send + 'static,
+    {
+        match &self.stream {
+            #[cfg(feature = "audio")]
+            Some(Output::Device(_, commands)) => {
+                let _ = commands.send(Box::new(move |engine: &mut Insert<M>| {
+                    f(engine.engine_mut())
+                }));
+            }
+            #[cfg(not(feature = "audio"))]
+            Some(Output::Silent(silent)) => silent.send(Box::new(move |engine: &mut Insert<M>| {
+                f(engine.engine_mut())
+            })),
+            Some(Output::Jack(jack)) => jack.send(Box::new(move |engine: &mut Insert<M>| {
+                f(engine.engine_mut())
+            })),
+            None => {}
+        }
+    }
+
+    /// host, device, rate and buffer size at once, e.g. from the setup screen
+    pub fn set_config(&mut self, config: StreamConfig) -> Result<(), Error> {
+        if config.host != self.config.host {
+            // the old host's stream has to go before the new host opens
+            self.stream = None;
+            #[cfg(feature = "audio")]
+            {
+                self.host = host(config.host.as_deref());
+            }
+        }
+        if config.insert != self.config.insert {
+            // its processor has to go before it does
+            self.stream = None;
+            self.take_processor();
+            self.insert = config.insert.as_deref().and_then(load_insert);
+        }
+        if let Some(insert) = &self.insert {
+            // activated at the new rate rather than the old stream's
+            insert.forget_stream_rate();
+        }
+        self.config = config;
+        self.overruns = Overruns::new(self.stats.overloads());
+        self.rebuild()
+    }
+
+    /// pin the stream to a device, or follow the default with `None`
+    pub fn set_device(&mut self, device: Option<String>) -> Result<(), Error> {
+        self.config.device = device;
+        self.rebuild()
+    }
+
+    /// for engines that render extra channels, see `cv`
+    pub fn set_channels(&mut self, channels: Option<usize>) -> Result<(), Error> {
+        self.config.channels = channels;
+        self.rebuild()
+    }
+
+    /// move to a JACK server, or back to devices with `None`
+    pub fn set_jack(&mut self, jack: Option<JackConfig>) -> Result<(), Error> {
+        self.config.jack = jack;
+        self.rebuild()
+    }
+
+    /// called at frame rate, with `adaptive` set overloads through a few
+    /// windows in a row double the buffer
+    pub fn poll(&mut self) -> Option<Event> {
+        self.drop_retired();
+        let beats = self.heartbeat.load(Ordering::Relaxed);
+        if beats != self.last_beat.0 {
+            self.last_beat = (beats, Instant::now());
+        }
+        let stalled = self.stream.is_some() && self.last_beat.1.elapsed() > STALL_TIMEOUT;
+        // restarted, or the stream isn't at the rate it was activated at
+        let reactivate = self.insert.as_mut().map_or(false, Plugin::poll);
+
+        // JACK has no default device to follow
+        let moved = self.config.jack.is_none() && self.last_scan.elapsed() > SCAN_INTERVAL && {
+            self.last_scan = Instant::now();
+            self.wanted_device_name() != self.device
+        };
+
+        if let Some((from, to)) = self.adapt() {
+            self.config.frames_per_buffer = Some(to);
+            match self.rebuild() {
+                Ok(()) => {
+                    eprintln!("audio kept overloading, the buffer grew to {} frames", to);
+                    self.stats.record_enlarged(to);
+                    return Some(Event::Enlarged {
+                        frames_per_buffer: to,
+                    });
+                }
+                Err(e) => {
+                    eprintln!("cannot grow the buffer to {} frames: {}", to, e);
+                    self.overruns.ceiling = to;
+                    self.config.frames_per_buffer = from;
+                }
+            }
+            return Some(match self.rebuild() {
+                Ok(()) => Event::Rebuilt {
+                    device: self.device.clone().unwrap_or_default(),
+                },
+                Err(_) => Event::Lost,
+            });
+        }
+
+        if !stalled && !moved && !reactivate {
+            return None;
+        }
+
+        Some(match self.rebuild() {
+            Ok(()) => Event::Rebuilt {
+                device: self.device.clone().unwrap_or_default(),
+            },
+            Err(_) => Event::Lost,
+        })
+    }
+
+    /// the buffer size before and after, once a window closes that makes
+    /// `SUSTAINED` overrun in a row, JACK's is the server's to choose
+    fn adapt(&mut self) -> Option<(Option<usize>, usize)> {
+        if !self.config.adaptive || self.config.jack.is_some() || self.stream.is_none() {
+            return None;
+        }
+        let (start, counted) = self.overruns.start;
+        if start.elapsed() < OVERLOAD_WINDOW {
+            return None;
+        }
+        let overloads = self.stats.overloads();
+        self.overruns.start = (Instant::now(), overloads);
+        if overloads - counted < OVERRUN {
+            self.overruns.windows = 0;
+            return None;
+        }
+        self.overruns.windows += 1;
+        if self.overruns.windows < SUSTAINED {
+            return None;
+        }
+        self.overruns.windows = 0;
+        let from = self.config.frames_per_buffer;
+        let frames = from
+            .or_else(|| self.stats.frames())
+            .unwrap_or(DEFAULT_FRAMES);
+        let to = (frames * 2).min(MAX_FRAMES);
+        if to <= frames || to >= self.overruns.ceiling {
+            return None;
+        }
+        Some((from, to))
+    }
+
+    /// tear down the current stream, if any, and start a new one
+    pub fn rebuild(&mut self) -> Result<(), Error> {
+        // dropping the stream joins the callback, the engine is free after this
+        self.stream = None;
+        self.device = None;
+        self.activate_insert();
+
+        if let Some(jack) = &self.config.jack {
+            let channels = match (jack.ports.len(), self.config.channels) {
+                (0, channels) => channels.unwrap_or(2),
+                (ports, channels) => channels.map_or(ports, |channels| channels.max(ports)),
+            };
+            let output = JackOutput::start(
+                jack,
+                channels,
+                self.engine.clone(),
+                self.heartbeat.clone(),
+                self.stats.clone(),
+            )
+            .map_err(|e| Error::Build(e.to_string()))?;
+            if let Some(rate) = self
+                .config
+                .sample_rate
+                .filter(|&r| r != output.sample_rate())
+            {
+                eprintln!(
+                    "jack runs at {}Hz, the engine expects {}Hz",
+                    output.sample_rate(),
+                    rate
+                );
+            }
+            self.device = Some(output.name().to_string());
+            self.stream = Some(Output::Jack(output));
+            self.last_beat = (self.heartbeat.load(Ordering::Relaxed), Instant::now());
+            return Ok(());
+        }
+
+        self.start_device()
+    }
+
+    /// the engine's processor back, only while no stream is running
+    fn take_processor(&mut self) {
+        if let Ok(mut engine) = self.engine.lock() {
+            engine.set_processor(None);
+        }
+        self.drop_retired();
+    }
+
+    fn drop_retired(&mut self) {
+        while self.retired.pop().is_some() {}
+    }
+
+    /// while no stream is running, at the rate the last one ran at or the
+    /// next one is asked for
+    fn activate_insert(&mut self) {
+        self.take_processor();
+        let insert = match &mut self.insert {
+            Some(insert) => insert,
+            None => return,
+        };
+        insert.deactivate();
+        let sample_rate = insert
+            .stream_rate()
+            .or(self.config.sample_rate)
+            .unwrap_or(DEFAULT_RATE);
+        match insert.activate(sample_rate) {
+            Ok(processor) => {
+                if let Ok(mut engine) = self.engine.lock() {
+                    engine.set_processor(Some(processor));
+                }
+            }
+            Err(e) => eprintln!("insert left out: {}", e),
+        }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn start_device(&mut self) -> Result<(), Error> {
+        let silent = Silent::start(
+            self.engine.clone(),
+            self.heartbeat.clone(),
+            self.stats.clone(),
+            &self.config,
+        );
+        self.stream = Some(Output::Silent(silent));
+        self.device = Some(String::from(Silent::<M>::NAME));
+        self.last_beat = (self.heartbeat.load(Ordering::Relaxed), Instant::now());
+        Ok(())
+    }
+
+    #[cfg(feature = "audio")]
+    fn start_device(&mut self) -> Result<(), Error> {
+        let device = self.find_device().ok_or(Error::NoDevice)?;
+        let name = device.name().unwrap_or_default();
+
+        let (commands, receiver) = mpsc::channel();
+        let monitored = Monitored {
+            engine: self.engine.clone(),
+            heartbeat: self.heartbeat.clone(),
+            stats: self.stats.clone(),
+            commands: receiver,
+        };
+
+        let mut builder = self
+            .host
+            .new_output_stream(monitored)
+            .render(render)
+            .device(device);
+        if let Some(sample_rate) = self.config.sample_rate {
+            builder = builder.sample_rate(sample_rate);
+        }
+        if let Some(frames) = self.config.frames_per_buffer {
+            builder = builder.frames_per_buffer(frames);
+        }
+        if let Some(channels) = self.config.channels {
+            builder = builder.channels(channels);
+        }
+        let stream = builder
+            .build()
+            .map_err(|e| Error::Build(format!("{:?}", e)))?;
+
+        self.stream = Some(Output::Device(stream, commands));
+        self.device = Some(name);
+        self.last_beat = (self.heartbeat.load(Ordering::Relaxed), Instant::now());
+        Ok(())
+    }
+
+    #[cfg(feature = "audio")]
+    fn find_device(&self) -> Option<audio::Device> {
+        let pinned = self.config.device.as_ref().and_then(|name| {
+            self.host
+                .output_devices()
+                .ok()?
+                .find(|d| d.name().ok().as_ref() == Some(name))
+        });
+        pinned.or_else(|| self.host.default_output_device())
+    }
+
+    #[cfg(feature = "audio")]
+    fn wanted_device_name(&self) -> Option<String> {
+        self.find_device().and_then(|d| d.name().ok())
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn wanted_device_name(&self) -> Option<String> {
+        Some(String::from(Silent::<M>::NAME))
+    }
+}
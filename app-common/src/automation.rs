@@ -0,0 +1,330 @@
+//! Recording parameter moves and playing them back.
+//!
+//! The `Recorder` watches `Params` from the UI thread, so moves from
+//! sliders, MIDI, OSC or controllers are all picked up, and stamps them with
+//! the engine's `Clock`. Playback runs inside `Automated` on the audio
+//! thread, which splits its blocks at each change so it lands on its sample.
+
+use crate::capture::timestamp;
+use crate::preset::{self, Error};
+use crate::recorder::RecorderInput;
+use crate::render::Render;
+use dsp_common::automation::{Event, Player};
+use dsp_common::param::Params;
+use nannou::prelude::Key;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// starts and stops recording
+pub const RECORD: Key = Key::A;
+/// replays the last recording
+pub const PLAY: Key = Key::S;
+
+const EXTENSION: &str = "automation";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    /// frames since the recording started
+    pub frame: u64,
+    pub param: String,
+    pub value: f32,
+}
+
+/// Something the app did that isn't a parameter, like lissa's jumps.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    /// frames since the recording started
+    pub frame: u64,
+    pub name: String,
+}
+
+/// A recording, stored as JSON next to the app's presets.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Automation {
+    pub sample_rate: u32,
+    pub points: Vec<Point>,
+    /// empty for recordings made before there were any
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// frames from start to stop, 0 for recordings older than the field
+    #[serde(default)]
+    pub frames: u64,
+}
+
+impl Automation {
+    pub fn path(app: &str, name: &str) -> PathBuf {
+        preset::dir(app).join(format!("{}.{}", name, EXTENSION))
+    }
+
+    pub fn save(&self, app: &str, name: &str) -> Result<PathBuf, Error> {
+        let path = Self::path(app, name);
+        fs::create_dir_all(preset::dir(app))?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// saved under the current time, so `latest` finds it
+    pub fn save_new(&self, app: &str) -> Result<PathBuf, Error> {
+        self.save(app, &timestamp())
+    }
+
+    pub fn load(app: &str, name: &str) -> Result<Self, Error> {
+        let path = Self::path(app, name);
+        if !path.exists() {
+            return Err(Error::NotFound(name.into()));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// sorted names of the recordings saved for `app`
+    pub fn list(app: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(preset::dir(app))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// the most recent `save_new`
+    pub fn latest(app: &str) -> Option<Self> {
+        Self::list(app)
+            .last()
+            .and_then(|name| Self::load(app, name).ok())
+    }
+
+    /// resolved against `params` and rescaled to `sample_rate`, unknown
+    /// parameter names are dropped and 0 keeps the recorded rate
+    pub fn player(&self, params: &Params, sample_rate: u32) -> Player {
+        let ratio = match (sample_rate, self.sample_rate) {
+            (0, _) | (_, 0) => 1.0,
+            (to, from) => to as f64 / from as f64,
+        };
+        let events = self
+            .points
+            .iter()
+            .filter_map(|point| {
+                Some(Event {
+                    frame: (point.frame as f64 * ratio).round() as u64,
+                    index: params.index_of(&point.param)?,
+                    value: point.value,
+                })
+            })
+            .collect();
+        Player::new(events)
+    }
+
+    /// the triggers rescaled to `sample_rate` like `player`'s events
+    pub fn triggers(&self, sample_rate: u32) -> Triggers {
+        let ratio = match (sample_rate, self.sample_rate) {
+            (0, _) | (_, 0) => 1.0,
+            (to, from) => to as f64 / from as f64,
+        };
+        Triggers {
+            triggers: self
+                .triggers
+                .iter()
+                .map(|trigger| Trigger {
+                    frame: (trigger.frame as f64 * ratio).round() as u64,
+                    name: trigger.name.clone(),
+                })
+                .collect(),
+            next: 0,
+        }
+    }
+}
+
+/// Recorded triggers handed out in order as playback reaches them.
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    next: usize,
+}
+
+impl Triggers {
+    /// the names of those before `frame` not handed out yet
+    pub fn until(&mut self, frame: u64) -> impl Iterator<Item = &str> {
+        let start = self.next;
+        let due = self.triggers[start..]
+            .iter()
+            .take_while(|trigger| trigger.frame < frame)
+            .count();
+        self.next += due;
+        self.triggers[start..start + due]
+            .iter()
+            .map(|trigger| trigger.name.as_str())
+    }
+
+    /// where the next one not handed out is
+    pub fn next_frame(&self) -> Option<u64> {
+        self.triggers.get(self.next).map(|trigger| trigger.frame)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.triggers.len()
+    }
+}
+
+/// Frames the engine has rendered, shared with the UI.
+#[derive(Clone, Default)]
+pub struct Clock {
+    position: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+}
+
+impl Clock {
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    /// 0 until the engine has rendered once
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+}
+
+/// Polled at frame rate, so moves are stamped to within a frame.
+pub struct Recorder {
+    start: u64,
+    last: Vec<f32>,
+    automation: Automation,
+}
+
+impl Recorder {
+    /// the current values are the recording's first points
+    pub fn start(params: &Params, clock: &Clock) -> Self {
+        let mut recorder = Self {
+            start: clock.position(),
+            last: vec![f32::NAN; params.len()],
+            automation: Automation {
+                sample_rate: clock.sample_rate(),
+                ..Automation::default()
+            },
+        };
+        recorder.poll(params, clock);
+        recorder
+    }
+
+    pub fn poll(&mut self, params: &Params, clock: &Clock) {
+        let frame = clock.position().saturating_sub(self.start);
+        for (i, last) in self.last.iter_mut().enumerate() {
+            let value = params.get(i);
+            if value.to_bits() != last.to_bits() {
+                *last = value;
+                self.automation.points.push(Point {
+                    frame,
+                    param: params.specs()[i].name.to_string(),
+                    value,
+                });
+            }
+        }
+    }
+
+    /// stamped like parameter moves, to within a frame
+    pub fn trigger(&mut self, name: &str, clock: &Clock) {
+        self.automation.triggers.push(Trigger {
+            frame: clock.position().saturating_sub(self.start),
+            name: name.to_string(),
+        });
+    }
+
+    pub fn stop(mut self, clock: &Clock) -> Automation {
+        self.automation.frames = clock.position().saturating_sub(self.start);
+        self.automation
+    }
+}
+
+/// Wraps an app's engine to keep the `Clock`, play automation and tap its
+/// output for a `performance` take.
+pub struct Automated<R> {
+    engine: R,
+    params: Params,
+    clock: Clock,
+    player: Option<Player>,
+    /// clock position playback started at
+    origin: u64,
+    tap: Option<RecorderInput>,
+}
+
+impl<R: Render> Automated<R> {
+    pub fn new(engine: R, params: Params) -> Self {
+        Self {
+            engine,
+            params,
+            clock: Clock::default(),
+            player: None,
+            origin: 0,
+            tap: None,
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    pub fn engine_mut(&mut self) -> &mut R {
+        &mut self.engine
+    }
+
+    /// from the next block on, replacing whatever was playing
+    pub fn play(&mut self, mut player: Player) {
+        player.seek(0);
+        self.origin = self.clock.position();
+        self.player = Some(player);
+    }
+
+    pub fn stop(&mut self) {
+        self.player = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(&self.player, Some(player) if !player.is_finished())
+    }
+
+    /// writes everything rendered from the next block on into `tap`, `None`
+    /// stops, dropping the old one
+    pub fn record(&mut self, tap: Option<RecorderInput>) {
+        self.tap = tap;
+    }
+}
+
+impl<R: Render> Render for Automated<R> {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        self.clock.sample_rate.store(sample_rate, Ordering::Relaxed);
+        let frames = out.len() / channels;
+        let position = self.clock.position();
+
+        let mut start = 0;
+        while start < frames {
+            let mut len = frames - start;
+            if let Some(player) = &mut self.player {
+                let now = position + start as u64 - self.origin;
+                player.apply(now, &self.params);
+                if let Some(next) = player.next_frame() {
+                    len = len.min((next - now) as usize);
+                }
+            }
+            self.engine.render(
+                &mut out[start * channels..(start + len) * channels],
+                channels,
+                sample_rate,
+            );
+            start += len;
+        }
+        if let Some(tap) = &mut self.tap {
+            tap.write(out);
+        }
+        self.clock
+            .position
+            .store(position + frames as u64, Ordering::Relaxed);
+    }
+}
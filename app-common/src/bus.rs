@@ -0,0 +1,102 @@
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[derive(Default)]
+struct Counters {
+    commands_dropped: AtomicUsize,
+    events_dropped: AtomicUsize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BusStats {
+    pub commands_queued: usize,
+    pub commands_capacity: usize,
+    pub commands_dropped: usize,
+    pub events_queued: usize,
+    pub events_capacity: usize,
+    pub events_dropped: usize,
+}
+
+/// UI end: sends commands down, receives events and snapshots up.
+pub struct UiEnd<C, E> {
+    commands: Producer<C>,
+    events: Consumer<E>,
+    counters: Arc<Counters>,
+}
+
+/// Audio end: drains commands, publishes events. Never blocks or allocates.
+pub struct AudioEnd<C, E> {
+    commands: Consumer<C>,
+    events: Producer<E>,
+    counters: Arc<Counters>,
+}
+
+/// Pair of wait-free SPSC queues between the UI and audio threads.
+pub fn bus<C, E>(command_capacity: usize, event_capacity: usize) -> (UiEnd<C, E>, AudioEnd<C, E>) {
+    let (command_producer, command_consumer) = RingBuffer::new(command_capacity).split();
+    let (event_producer, event_consumer) = RingBuffer::new(event_capacity).split();
+    let counters = Arc::new(Counters::default());
+    (
+        UiEnd {
+            commands: command_producer,
+            events: event_consumer,
+            counters: counters.clone(),
+        },
+        AudioEnd {
+            commands: command_consumer,
+            events: event_producer,
+            counters,
+        },
+    )
+}
+
+impl<C, E> UiEnd<C, E> {
+    /// hands the command back if the queue is full
+    pub fn send(&mut self, command: C) -> Result<(), C> {
+        let sent = self.commands.push(command);
+        if sent.is_err() {
+            self.counters
+                .commands_dropped
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    pub fn events(&mut self) -> impl Iterator<Item = E> + '_ {
+        std::iter::from_fn(move || self.events.pop())
+    }
+
+    /// drain everything and keep only the newest, for snapshot style events
+    pub fn latest(&mut self) -> Option<E> {
+        self.events().last()
+    }
+
+    pub fn stats(&self) -> BusStats {
+        BusStats {
+            commands_queued: self.commands.len(),
+            commands_capacity: self.commands.capacity(),
+            commands_dropped: self.counters.commands_dropped.load(Ordering::Relaxed),
+            events_queued: self.events.len(),
+            events_capacity: self.events.capacity(),
+            events_dropped: self.counters.events_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<C, E> AudioEnd<C, E> {
+    pub fn commands(&mut self) -> impl Iterator<Item = C> + '_ {
+        std::iter::from_fn(move || self.commands.pop())
+    }
+
+    /// drops the event if the UI isn't keeping up, returns whether it was sent
+    pub fn publish(&mut self, event: E) -> bool {
+        let sent = self.events.push(event).is_ok();
+        if !sent {
+            self.counters.events_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+}
@@ -0,0 +1,302 @@
+//! A camera watching the room, read as how bright it is and which way the
+//! people in front of it are moving.
+//!
+//! Frames are captured and analysed on a thread of their own, the window
+//! polls for the newest reading. Each frame is shrunk to a small grey
+//! picture, its mean is the brightness and the one shift that best explains
+//! the change from the frame before is the motion, a global Lucas-Kanade
+//! step. Needs the `camera` feature, without it `Camera::open` fails with
+//! `Error::Unavailable`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// the grey picture frames are shrunk to, enough for where things move
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+/// readings waiting for a poll, newer ones are dropped until it comes
+const READINGS: usize = 8;
+/// below this the frame has too little texture to tell motion by
+const MIN_DETERMINANT: f32 = 1e-6;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// the system's numbering, 0 is usually the built-in camera
+    pub device: u32,
+    /// asked of the camera, it picks the nearest it has
+    pub size: [u32; 2],
+    /// seconds the readings take to follow a change
+    pub smoothing: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            device: 0,
+            size: [320, 240],
+            smoothing: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `camera` feature
+    Unavailable,
+    Device(String),
+    /// the camera stopped sending frames
+    Stopped(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `camera` feature"),
+            Error::Device(e) => write!(f, "camera: {}", e),
+            Error::Stopped(e) => write!(f, "camera stopped: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What the camera sees, smoothed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Reading {
+    /// the frame's mean, from 0 for black to 1
+    pub brightness: f32,
+    /// right and up, in frame widths a second
+    pub motion: [f32; 2],
+}
+
+impl Reading {
+    /// how fast, whichever way
+    pub fn speed(&self) -> f32 {
+        self.motion[0].hypot(self.motion[1])
+    }
+
+    /// which way, in radians anticlockwise from the right
+    pub fn direction(&self) -> f32 {
+        self.motion[1].atan2(self.motion[0])
+    }
+}
+
+/// Brightness and motion from one grey frame to the next.
+pub struct Analysis {
+    previous: Option<Vec<f32>>,
+    reading: Reading,
+    /// seconds, see `CameraConfig::smoothing`
+    smoothing: f32,
+}
+
+impl Analysis {
+    pub fn new(smoothing: f32) -> Self {
+        Self {
+            previous: None,
+            reading: Reading::default(),
+            smoothing,
+        }
+    }
+
+    /// `frame` as `WIDTH` by `HEIGHT` grey levels from 0 to 1, row by row
+    /// from the top, `seconds` after the one before
+    pub fn push(&mut self, frame: Vec<f32>, seconds: f32) -> Reading {
+        let brightness = frame.iter().sum::<f32>() / frame.len().max(1) as f32;
+        let motion = match &self.previous {
+            Some(previous) if seconds > 0.0 => {
+                let [x, y] = shift(previous, &frame);
+                // pixels a frame to widths a second, up the right way round
+                [x / WIDTH as f32 / seconds, -y / WIDTH as f32 / seconds]
+            }
+            _ => [0.0; 2],
+        };
+        self.previous = Some(frame);
+
+        let follow = if self.smoothing > 0.0 {
+            1.0 - (-seconds / self.smoothing).exp()
+        } else {
+            1.0
+        };
+        let reading = &mut self.reading;
+        reading.brightness += (brightness - reading.brightness) * follow;
+        for (smoothed, raw) in reading.motion.iter_mut().zip(motion) {
+            *smoothed += (raw - *smoothed) * follow;
+        }
+        self.reading
+    }
+}
+
+/// the shift in pixels, right and down, that best turns `from` into `to`
+fn shift(from: &[f32], to: &[f32]) -> [f32; 2] {
+    let at = |frame: &[f32], x: usize, y: usize| frame[y * WIDTH + x];
+    let (mut xx, mut xy, mut yy, mut xt, mut yt) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for y in 1..HEIGHT - 1 {
+        for x in 1..WIDTH - 1 {
+            // the gradient of both frames, so neither is favoured
+            let dx = (at(from, x + 1, y) - at(from, x - 1, y) + at(to, x + 1, y)
+                - at(to, x - 1, y))
+                * 0.25;
+            let dy = (at(from, x, y + 1) - at(from, x, y - 1) + at(to, x, y + 1)
+                - at(to, x, y - 1))
+                * 0.25;
+            let dt = at(to, x, y) - at(from, x, y);
+            xx += dx * dx;
+            xy += dx * dy;
+            yy += dy * dy;
+            xt += dx * dt;
+            yt += dy * dt;
+        }
+    }
+    let determinant = xx * yy - xy * xy;
+    if determinant < MIN_DETERMINANT * (WIDTH * HEIGHT) as f32 {
+        return [0.0; 2];
+    }
+    [
+        (xy * yt - yy * xt) / determinant,
+        (xy * xt - xx * yt) / determinant,
+    ]
+}
+
+/// `rgb` bytes of a `width` by `height` image averaged down to the grey
+/// picture `Analysis` takes
+pub fn shrink(rgb: &[u8], width: usize, height: usize) -> Vec<f32> {
+    let mut grey = vec![0.0; WIDTH * HEIGHT];
+    let mut counts = vec![0u32; WIDTH * HEIGHT];
+    for (i, pixel) in rgb.chunks_exact(3).take(width * height).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let cell = (y * HEIGHT / height) * WIDTH + x * WIDTH / width;
+        // Rec. 709 luma
+        grey[cell] +=
+            (0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32)
+                / 255.0;
+        counts[cell] += 1;
+    }
+    for (level, count) in grey.iter_mut().zip(counts) {
+        *level /= count.max(1) as f32;
+    }
+    grey
+}
+
+/// A camera captured on its own thread, stopped on drop.
+pub struct Camera {
+    readings: Receiver<Result<Reading, String>>,
+    reading: Reading,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Camera {
+    /// waits for the camera to start sending frames
+    pub fn open(app_name: &str, config: &CameraConfig) -> Result<Self, Error> {
+        let (opened, started) = mpsc::sync_channel(1);
+        let (readings, receiver) = mpsc::sync_channel(READINGS);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (config, stop) = (config.clone(), stop.clone());
+            thread::Builder::new()
+                .name(format!("{} camera", app_name))
+                .spawn(move || capture(&config, &stop, &opened, &readings))
+                .map_err(|e| Error::Device(e.to_string()))?
+        };
+        match started.recv() {
+            Ok(Ok(())) => Ok(Self {
+                readings: receiver,
+                reading: Reading::default(),
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(Error::Device("capture thread panicked".to_string())),
+        }
+    }
+
+    /// takes in the readings since the last poll, called at frame rate
+    pub fn poll(&mut self) -> Result<Reading, Error> {
+        loop {
+            match self.readings.try_recv() {
+                Ok(Ok(reading)) => self.reading = reading,
+                Ok(Err(e)) => return Err(Error::Stopped(e)),
+                Err(TryRecvError::Empty) => return Ok(self.reading),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(Error::Stopped("capture thread ended".to_string()))
+                }
+            }
+        }
+    }
+
+    /// the newest reading
+    pub fn reading(&self) -> Reading {
+        self.reading
+    }
+}
+
+impl Drop for Camera {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+type Opened = mpsc::SyncSender<Result<(), Error>>;
+type Readings = mpsc::SyncSender<Result<Reading, String>>;
+
+/// the thread's loop, frames until `stop` or the camera gives out
+#[cfg(feature = "camera")]
+fn capture(config: &CameraConfig, stop: &AtomicBool, opened: &Opened, readings: &Readings) {
+    use nokhwa::pixel_format::RgbFormat;
+    use nokhwa::utils::{
+        CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType, Resolution,
+    };
+    use std::time::Instant;
+
+    let [width, height] = config.size;
+    let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Closest(
+        CameraFormat::new(Resolution::new(width, height), FrameFormat::MJPEG, 30),
+    ));
+    let camera = nokhwa::Camera::new(CameraIndex::Index(config.device), format)
+        .and_then(|mut camera| camera.open_stream().map(|_| camera));
+    let mut camera = match camera {
+        Ok(camera) => {
+            let _ = opened.send(Ok(()));
+            camera
+        }
+        Err(e) => {
+            let _ = opened.send(Err(Error::Device(e.to_string())));
+            return;
+        }
+    };
+
+    let mut analysis = Analysis::new(config.smoothing);
+    let mut last = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        let image = match camera
+            .frame()
+            .and_then(|frame| frame.decode_image::<RgbFormat>())
+        {
+            Ok(image) => image,
+            Err(e) => {
+                let _ = readings.send(Err(e.to_string()));
+                return;
+            }
+        };
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let grey = shrink(image.as_raw(), width, height);
+        let reading = analysis.push(grey, last.elapsed().as_secs_f32());
+        last = Instant::now();
+        // dropped while the window is behind, the next carries on from it
+        let _ = readings.try_send(Ok(reading));
+    }
+    let _ = camera.stop_stream();
+}
+
+#[cfg(not(feature = "camera"))]
+fn capture(_config: &CameraConfig, _stop: &AtomicBool, opened: &Opened, _readings: &Readings) {
+    let _ = opened.send(Err(Error::Unavailable));
+}
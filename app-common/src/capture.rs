@@ -0,0 +1,396 @@
+use crate::automation::Clock;
+use nannou::prelude::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+pub const HOTKEY: Key = Key::V;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    /// keep the numbered png sequence
+    Images,
+    /// encode the sequence with ffmpeg once recording stops
+    Video { ffmpeg: PathBuf, extension: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct CaptureSettings {
+    pub dir: PathBuf,
+    pub fps: f64,
+    /// video is scaled to this size, images are always at window size
+    pub resolution: Option<[u32; 2]>,
+    pub output: Output,
+}
+
+impl CaptureSettings {
+    pub fn new(app_name: &str) -> Self {
+        Self {
+            dir: PathBuf::from("captures").join(app_name),
+            fps: 30.0,
+            resolution: None,
+            output: Output::Video {
+                ffmpeg: PathBuf::from("ffmpeg"),
+                extension: String::from("mp4"),
+            },
+        }
+    }
+}
+
+struct Session {
+    frames_dir: PathBuf,
+    started: Instant,
+    frame: u64,
+    /// frames timed by the audio rather than the wall clock
+    synced: Option<Synced>,
+}
+
+impl Session {
+    /// the frame due now, numbered by its time since the start, unless it's
+    /// been captured already
+    fn due(&mut self, fps: f64) -> Option<u64> {
+        let seconds = match &self.synced {
+            Some(synced) => synced.seconds(),
+            None => self.started.elapsed().as_secs_f64(),
+        };
+        let due = (seconds * fps) as u64;
+        if due < self.frame {
+            return None;
+        }
+        self.frame = due + 1;
+        Some(due)
+    }
+}
+
+/// A capture following the engine's `Clock`, with the recording to put
+/// under it.
+struct Synced {
+    clock: Clock,
+    /// the clock's position when it started
+    start: u64,
+    audio: PathBuf,
+}
+
+impl Synced {
+    fn seconds(&self) -> f64 {
+        let frames = self.clock.position().saturating_sub(self.start);
+        frames as f64 / self.clock.sample_rate().max(1) as f64
+    }
+}
+
+/// Records the main window at a fixed frame rate, independent of the
+/// app's loop rate: frames are captured whenever the wall clock says
+/// the next one is due. A skipped frame is the one before held over, so
+/// frame `n` is always `n / fps` seconds in and slow frames never stretch
+/// the video.
+///
+/// `start_synced` goes by the audio rendered instead, for a video of a
+/// recording started alongside, and the video gets the recording as its
+/// sound.
+pub struct FrameRecorder {
+    settings: CaptureSettings,
+    session: Option<Session>,
+    encoders: Vec<JoinHandle<()>>,
+}
+
+impl FrameRecorder {
+    pub fn new(settings: CaptureSettings) -> Self {
+        Self {
+            settings,
+            session: None,
+            encoders: Vec::new(),
+        }
+    }
+
+    pub fn settings_mut(&mut self) -> &mut CaptureSettings {
+        &mut self.settings
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    pub fn key_pressed(&mut self, app: &App, key: Key) {
+        if key == HOTKEY {
+            self.toggle(app);
+        }
+    }
+
+    pub fn toggle(&mut self, app: &App) {
+        if self.is_recording() {
+            self.stop(app);
+        } else {
+            self.start();
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.begin(None);
+    }
+
+    /// frames timed by `clock` from now on, with `audio` under them once
+    /// it's encoded, the recording has to be finished before `stop`
+    pub fn start_synced(&mut self, clock: &Clock, audio: &Path) {
+        self.begin(Some(Synced {
+            clock: clock.clone(),
+            start: clock.position(),
+            audio: audio.to_path_buf(),
+        }));
+    }
+
+    fn begin(&mut self, synced: Option<Synced>) {
+        let stamp = timestamp();
+        let frames_dir = self.settings.dir.join(&stamp);
+        if let Err(e) = fs::create_dir_all(&frames_dir) {
+            eprintln!(
+                "cannot create capture directory {}: {}",
+                frames_dir.display(),
+                e
+            );
+            return;
+        }
+        self.session = Some(Session {
+            frames_dir,
+            started: Instant::now(),
+            frame: 0,
+            synced,
+        });
+    }
+
+    /// called at frame rate, queues the next frame if one is due
+    pub fn update(&mut self, app: &App) {
+        let fps = self.settings.fps;
+        if let Some(session) = &mut self.session {
+            if let Some(due) = session.due(fps) {
+                let path = frame_path(&session.frames_dir, due);
+                app.main_window().capture_frame(path);
+            }
+        }
+    }
+
+    pub fn stop(&mut self, app: &App) {
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => return,
+        };
+        // frames are written by nannou's capture threads, wait for the stragglers
+        let _ = app.main_window().await_capture_frame_jobs();
+        let frames = session.frames_dir;
+        let count = session.frame;
+        let audio = session.synced.map(|synced| synced.audio);
+        let video = match &self.settings.output {
+            Output::Video { ffmpeg, extension } => {
+                Some((ffmpeg.clone(), frames.with_extension(extension)))
+            }
+            Output::Images => None,
+        };
+        let (fps, resolution) = (self.settings.fps, self.settings.resolution);
+        // holding frames over copies one file per skipped frame, so it's
+        // done beside the encode rather than on the UI thread
+        self.encoders.push(thread::spawn(move || {
+            hold_skipped(&frames, count);
+            if let Some((ffmpeg, output)) = video {
+                encode(&ffmpeg, &frames, audio.as_deref(), &output, fps, resolution);
+            }
+        }));
+    }
+
+    /// stop and block until every pending fill and encode is done, for app
+    /// exit
+    pub fn finish(&mut self, app: &App) {
+        self.stop(app);
+        for encoder in self.encoders.drain(..) {
+            let _ = encoder.join();
+        }
+    }
+}
+
+fn frame_path(frames: &Path, frame: u64) -> PathBuf {
+    frames.join(format!("{:06}.png", frame))
+}
+
+/// the frame before copied into each one skipped of the first `count`, so
+/// the sequence keeps time and has no gap for ffmpeg to stop at, the ones
+/// before the first frame written are that frame
+fn hold_skipped(frames: &Path, count: u64) {
+    let first = match (0..count).find(|&frame| frame_path(frames, frame).exists()) {
+        Some(first) => first,
+        None => return,
+    };
+    for frame in 0..count {
+        let path = frame_path(frames, frame);
+        if path.exists() {
+            continue;
+        }
+        let held = if frame < first { first } else { frame - 1 };
+        if let Err(e) = fs::copy(frame_path(frames, held), &path) {
+            eprintln!("cannot hold frame {} over: {}", held, e);
+            return;
+        }
+    }
+}
+
+fn encode(
+    ffmpeg: &Path,
+    frames: &Path,
+    audio: Option<&Path>,
+    output: &Path,
+    fps: f64,
+    resolution: Option<[u32; 2]>,
+) {
+    let mut command = Command::new(ffmpeg);
+    command
+        .args(&["-y", "-loglevel", "error", "-framerate"])
+        .arg(fps.to_string())
+        .arg("-i")
+        .arg(frames.join("%06d.png"));
+    if let Some(audio) = audio {
+        command
+            .arg("-i")
+            .arg(audio)
+            .args(&["-c:a", "aac", "-b:a", "320k", "-shortest"]);
+    }
+    command.args(&["-c:v", "libx264", "-pix_fmt", "yuv420p"]);
+    if let Some([w, h]) = resolution {
+        command.arg("-vf").arg(format!("scale={}:{}", w, h));
+    }
+    let status = command.arg(output).stdin(Stdio::null()).status();
+
+    match status {
+        Ok(status) if status.success() => {
+            let _ = fs::remove_dir_all(frames);
+        }
+        Ok(status) => eprintln!(
+            "ffmpeg exited with {}, frames kept in {}",
+            status,
+            frames.display()
+        ),
+        Err(e) => eprintln!(
+            "could not run ffmpeg ({}), frames kept in {}",
+            e,
+            frames.display()
+        ),
+    }
+}
+
+/// seconds since the epoch, good enough to keep sessions apart and sorted
+pub fn timestamp() -> String {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs().to_string())
+        .unwrap_or_else(|_| String::from("capture"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::Automated;
+    use crate::param::Params;
+    use crate::render::Render;
+    use std::time::Duration;
+
+    const FPS: f64 = 30.0;
+    const SAMPLE_RATE: u32 = 48_000;
+
+    struct Silence;
+
+    impl Render for Silence {
+        fn render(&mut self, _out: &mut [f32], _channels: usize, _sample_rate: u32) {}
+    }
+
+    fn frames_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("capture-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// writes what nannou would for the frame due, if there is one
+    fn capture(session: &mut Session, contents: &str) -> Option<u64> {
+        let due = session.due(FPS)?;
+        fs::write(frame_path(&session.frames_dir, due), contents).unwrap();
+        Some(due)
+    }
+
+    fn advance(engine: &mut Automated<Silence>, seconds: f64) {
+        let mut out = vec![0.0; (seconds * SAMPLE_RATE as f64).round() as usize];
+        engine.render(&mut out, 1, SAMPLE_RATE);
+    }
+
+    fn read(frames: &Path, frame: u64) -> String {
+        fs::read_to_string(frame_path(frames, frame)).unwrap()
+    }
+
+    #[test]
+    fn synced_frames_are_numbered_by_the_audio_rendered() {
+        let mut engine = Automated::new(Silence, Params::new(&[]));
+        advance(&mut engine, 0.5);
+        let clock = engine.clock();
+        let mut session = Session {
+            frames_dir: frames_dir("synced"),
+            started: Instant::now(),
+            frame: 0,
+            synced: Some(Synced {
+                start: clock.position(),
+                clock,
+                audio: PathBuf::from("take.wav"),
+            }),
+        };
+
+        assert_eq!(capture(&mut session, "first"), Some(0));
+        // nothing rendered, nothing new is due however long the UI waits
+        assert_eq!(capture(&mut session, "again"), None);
+        advance(&mut engine, 1.0);
+        assert_eq!(capture(&mut session, "second"), Some(30));
+        advance(&mut engine, 3.0 / FPS);
+        assert_eq!(capture(&mut session, "third"), Some(33));
+
+        hold_skipped(&session.frames_dir, session.frame);
+        let frames = &session.frames_dir;
+        assert_eq!(read(frames, 0), "first");
+        assert!((1..30).all(|frame| read(frames, frame) == "first"));
+        assert_eq!(read(frames, 30), "second");
+        assert_eq!(read(frames, 31), "second");
+        assert_eq!(read(frames, 32), "second");
+        assert_eq!(read(frames, 33), "third");
+        assert!(!frame_path(frames, 34).exists());
+        fs::remove_dir_all(frames).unwrap();
+    }
+
+    #[test]
+    fn plain_frames_are_numbered_by_the_wall_clock() {
+        let second = Duration::from_secs(1);
+        let mut session = Session {
+            frames_dir: frames_dir("plain"),
+            started: Instant::now() - second,
+            frame: 0,
+            synced: None,
+        };
+
+        // the first frame lands a second in, the ones before it are gaps
+        let first = capture(&mut session, "first").unwrap();
+        assert!(first >= 30);
+        session.started -= second;
+        let next = capture(&mut session, "next").unwrap();
+        assert!(next >= first + 30);
+
+        hold_skipped(&session.frames_dir, session.frame);
+        let frames = &session.frames_dir;
+        assert!((0..first).all(|frame| read(frames, frame) == "first"));
+        assert!((first..next).all(|frame| read(frames, frame) == "first"));
+        assert_eq!(read(frames, next), "next");
+        assert!(!frame_path(frames, next + 1).exists());
+        fs::remove_dir_all(frames).unwrap();
+    }
+
+    #[test]
+    fn frames_are_named_as_ffmpeg_reads_them() {
+        let frames = Path::new("frames");
+        assert_eq!(frame_path(frames, 0), frames.join("000000.png"));
+        assert_eq!(frame_path(frames, 1234), frames.join("001234.png"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Command line flags every app understands.
+//!
+//! `main` calls `init` before `nannou::app` starts, `model` and everything
+//! after reads them back through `args`. Flags override the config for the
+//! run, what the app saves on exit still comes from its state.
+
+use crate::audio::StreamConfig;
+use crate::capture::timestamp;
+use crate::config::Config;
+use crate::recorder::{FileFormat, DEFAULT_KBPS};
+use crate::render::{Normalization, Request};
+use clap::{Arg, ArgMatches};
+use once_cell::sync::OnceCell;
+use std::path::PathBuf;
+
+static ARGS: OnceCell<Args> = OnceCell::new();
+
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    /// audio output device, by name
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// frames per audio callback
+    pub buffer_size: Option<usize>,
+    pub fullscreen: bool,
+    /// unattended running, see `kiosk`
+    pub kiosk: bool,
+    /// preset name, or a path to a preset file
+    pub preset: Option<String>,
+    /// where the app's randomness starts, for apps that have any
+    pub seed: Option<u64>,
+    /// threads rendering voices in offline renders, for apps that can split
+    /// them
+    pub threads: Option<usize>,
+    /// audio only, no window
+    pub headless: bool,
+    /// open the audio settings before playing, see `setup`
+    pub setup: bool,
+    pub config: Option<PathBuf>,
+    /// bundle to install before the config is loaded, see `session`
+    pub session: Option<PathBuf>,
+    /// files replacing the embedded resources, see `assets`
+    pub assets: Option<PathBuf>,
+    /// CLAP effect on the output, see `plugin`
+    pub insert: Option<PathBuf>,
+    /// offline render instead of running
+    pub render: Option<Request>,
+    /// loudness to bring renders and takes to, over the config's
+    pub normalize: Option<Normalization>,
+    /// take folder to render again instead of running, see `performance`
+    pub replay: Option<PathBuf>,
+}
+
+/// the flags, for binaries that add their own on top
+pub fn command(app: &'static str) -> clap::App<'static, 'static> {
+    let value = |name: &'static str, value_name: &'static str, help: &'static str| {
+        Arg::with_name(name)
+            .long(name)
+            .takes_value(true)
+            .value_name(value_name)
+            .help(help)
+    };
+    let flag = |name: &'static str, help: &'static str| Arg::with_name(name).long(name).help(help);
+
+    clap::App::new(app)
+        .arg(value("device", "NAME", "audio output device"))
+        .arg(value("sample-rate", "HZ", "audio sample rate"))
+        .arg(value("buffer-size", "FRAMES", "frames per audio callback"))
+        .arg(flag("fullscreen", "start fullscreen"))
+        .arg(flag(
+            "kiosk",
+            "run unattended, fullscreen and restarting if it stalls",
+        ))
+        .arg(value("preset", "NAME", "preset name or file to start from"))
+        .arg(value("seed", "N", "seed for the app's randomness"))
+        .arg(value(
+            "threads",
+            "N",
+            "threads rendering voices, for offline renders",
+        ))
+        .arg(flag("headless", "play the audio without a window"))
+        .arg(flag("setup", "choose the audio settings before playing"))
+        .arg(value(
+            "config",
+            "PATH",
+            "config file instead of the default one",
+        ))
+        .arg(value("session", "PATH", "session bundle to install"))
+        .arg(value(
+            "assets",
+            "DIR",
+            "directory of resources replacing the built-in ones",
+        ))
+        .arg(value("insert", "PATH", "CLAP effect to run on the output"))
+        .arg(
+            Arg::with_name("render")
+                .long("render")
+                .min_values(1)
+                .max_values(2)
+                .value_name("SECONDS [PATH]")
+                .conflicts_with("headless")
+                .help("render SECONDS to a wav, aiff, flac or opus file and exit"),
+        )
+        .arg(value("bitrate", "KBPS", "opus bitrate for the render").requires("render"))
+        .arg(
+            value("replay", "DIR", "render a recorded take again and exit")
+                .conflicts_with_all(&["headless", "render"]),
+        )
+        .arg(value(
+            "normalize",
+            "LUFS",
+            "bring renders and takes to an integrated loudness",
+        ))
+        .arg(
+            value(
+                "true-peak",
+                "DB",
+                "ceiling for the true peak when normalizing, -1 by default",
+            )
+            .requires("normalize"),
+        )
+}
+
+impl Args {
+    pub fn from_matches(app: &str, matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let optional = |name: &str| matches.value_of(name).map(String::from);
+        let ceiling: Option<f32> = optional_number(matches, "true-peak")?;
+        let normalize = optional_number(matches, "normalize")?.map(|target| Normalization {
+            target,
+            ceiling: ceiling.unwrap_or_else(|| Normalization::default().ceiling),
+        });
+        let render = match matches.values_of("render") {
+            Some(mut values) => {
+                let seconds = values
+                    .next()
+                    .and_then(|text| text.parse().ok())
+                    .filter(|&seconds: &f64| seconds > 0.0)
+                    .ok_or_else(|| {
+                        clap::Error::value_validation_auto(
+                            "--render takes a positive number of seconds".into(),
+                        )
+                    })?;
+                let path = values
+                    .next()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from(format!("{}-{}.wav", app, timestamp())));
+                let kbps = optional_number(matches, "bitrate")?.unwrap_or(DEFAULT_KBPS);
+                let format = FileFormat::from_path(&path, kbps).unwrap_or(FileFormat::Wav);
+                Some(Request {
+                    seconds,
+                    path,
+                    format,
+                    normalize,
+                })
+            }
+            None => None,
+        };
+        Ok(Self {
+            device: optional("device"),
+            sample_rate: optional_number(matches, "sample-rate")?,
+            buffer_size: optional_number(matches, "buffer-size")?,
+            fullscreen: matches.is_present("fullscreen"),
+            kiosk: matches.is_present("kiosk"),
+            preset: optional("preset"),
+            seed: optional_number(matches, "seed")?,
+            threads: optional_number(matches, "threads")?,
+            headless: matches.is_present("headless"),
+            setup: matches.is_present("setup"),
+            config: matches.value_of("config").map(PathBuf::from),
+            session: matches.value_of("session").map(PathBuf::from),
+            assets: matches.value_of("assets").map(PathBuf::from),
+            insert: matches.value_of("insert").map(PathBuf::from),
+            render,
+            replay: matches.value_of("replay").map(PathBuf::from),
+            normalize,
+        })
+    }
+
+    /// the device, window and loudness flags over `config`
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(device) = &self.device {
+            config.audio_device = Some(device.clone());
+        }
+        if self.normalize.is_some() {
+            config.normalize = self.normalize;
+        }
+        if self.fullscreen {
+            config.ui.fullscreen = true;
+        }
+    }
+
+    /// the sample rate, buffer size and insert flags over the app's defaults
+    pub fn stream_config(&self, config: StreamConfig) -> StreamConfig {
+        StreamConfig {
+            sample_rate: self.sample_rate.or(config.sample_rate),
+            frames_per_buffer: self.buffer_size.or(config.frames_per_buffer),
+            insert: self.insert.clone().or(config.insert),
+            ..config
+        }
+    }
+}
+
+fn optional_number<T: std::str::FromStr>(
+    matches: &ArgMatches,
+    name: &str,
+) -> Result<Option<T>, clap::Error> {
+    match matches.value_of(name) {
+        Some(text) => text.parse().map(Some).map_err(|_| {
+            clap::Error::value_validation_auto(format!("--{} takes a number, not {}", name, text))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// parses the process's arguments once, exits with usage on bad ones
+pub fn init(app: &'static str) -> &'static Args {
+    ARGS.get_or_init(|| {
+        let matches = command(app).get_matches();
+        Args::from_matches(app, &matches).unwrap_or_else(|e| e.exit())
+    })
+}
+
+/// for binaries that built their own `command`
+pub fn init_from(app: &str, matches: &ArgMatches) -> &'static Args {
+    ARGS.get_or_init(|| Args::from_matches(app, matches).unwrap_or_else(|e| e.exit()))
+}
+
+/// what `init` parsed, no flags at all if it wasn't called
+pub fn args() -> &'static Args {
+    ARGS.get_or_init(Args::default)
+}
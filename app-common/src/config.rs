@@ -0,0 +1,292 @@
+use crate::audio::StreamConfig;
+use crate::camera::CameraConfig;
+use crate::cli;
+use crate::cv::CvConfig;
+use crate::dmx::DmxConfig;
+use crate::jack::JackConfig;
+use crate::kiosk::{self, KioskConfig};
+use crate::macros::Macro;
+use crate::midi::MidiOutConfig;
+use crate::mirror::MirrorConfig;
+use crate::osc;
+use crate::oscquery::OscQueryConfig;
+use crate::output::OutputConfig;
+use crate::param::ParamSnapshot;
+use crate::recorder::FileFormat;
+use crate::remote::RemoteConfig;
+use crate::render::Normalization;
+use crate::serial::SerialConfig;
+use crate::speakers::SpeakerConfig;
+use crate::timecode::TimecodeConfig;
+use crate::watch::FileWatcher;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_SIZE: [u32; 2] = [1024, 768];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// inner size in points
+    pub size: Option<[u32; 2]>,
+    /// outer position in pixels
+    pub position: Option<[i32; 2]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    pub show_controls: bool,
+    pub fullscreen: bool,
+    /// palette name, see `theme::Themes`
+    pub theme: Option<String>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            show_controls: true,
+            fullscreen: false,
+            theme: None,
+        }
+    }
+}
+
+/// What an app remembers between runs, missing keys fall back to defaults
+/// so older files keep loading as fields are added.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // plain values before tables, toml can't serialize them the other way round
+    /// audio API by name, see `audio::hosts`
+    pub audio_host: Option<String>,
+    pub audio_device: Option<String>,
+    pub input_device: Option<String>,
+    /// over the app's preferred rate and buffer size when set
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<usize>,
+    /// the buffer doubles after sustained overloads unless this is set
+    pub fixed_buffer: bool,
+    pub midi_device: Option<String>,
+    pub sample_path: Option<PathBuf>,
+    /// a WAV of a recorded space, for apps with a convolution reverb
+    pub impulse_path: Option<PathBuf>,
+    /// the output limiter is on unless this is set
+    pub bypass_limiter: bool,
+    /// run the audio on a JACK server, needs the `jack` feature
+    pub jack: bool,
+    /// stream the scene as an NDI source, needs the `ndi` feature
+    pub ndi: bool,
+    /// publish the scene to Syphon on macOS or Spout on Windows, needs the
+    /// `texture-share` feature
+    pub texture_share: bool,
+    /// CLAP effect on the output, see `plugin`
+    pub insert: Option<PathBuf>,
+    pub window: WindowConfig,
+    pub ui: UiConfig,
+    /// borderless projection window, off when absent
+    pub output: Option<OutputConfig>,
+    /// parameter values by name, for apps that have any
+    pub params: ParamSnapshot,
+    /// knobs over several parameters, for apps that have them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub macros: Vec<Macro>,
+    /// lighting output, off when absent
+    pub dmx: Option<DmxConfig>,
+    /// parameter control, and feedback with `send_to`, off when absent
+    pub osc: Option<osc::Config>,
+    /// parameter server for browsers and scripts, off when absent
+    pub remote: Option<RemoteConfig>,
+    /// parameter discovery for OSC controllers, off when absent
+    pub oscquery: Option<OscQueryConfig>,
+    /// leading or following other instances, off when absent
+    pub mirror: Option<MirrorConfig>,
+    /// control voltages after the audio channels, off when absent
+    pub cv: Option<CvConfig>,
+    /// generated notes for external synths, off when absent
+    pub midi_out: Option<MidiOutConfig>,
+    /// loudness renders and takes are brought to, off when absent
+    pub normalize: Option<Normalization>,
+    /// what takes and exports are written as, wav when absent
+    pub recording: Option<FileFormat>,
+    /// surround output for apps that pan round the room, stereo when absent
+    pub speakers: Option<SpeakerConfig>,
+    /// show control timecode the transport chases, off when absent
+    pub timecode: Option<TimecodeConfig>,
+    /// unattended running, see `kiosk`, off when absent unless `--kiosk`
+    pub kiosk: Option<KioskConfig>,
+    /// a camera for apps that follow the room, needs the `camera` feature,
+    /// off when absent
+    pub camera: Option<CameraConfig>,
+    /// sensors on a microcontroller driving parameters, needs the `serial`
+    /// feature, off when absent
+    pub serial: Option<SerialConfig>,
+}
+
+/// `config.toml` in the platform config directory for `app`
+pub fn default_path(app: &str) -> PathBuf {
+    directories::ProjectDirs::from("", "", app)
+        .map(|dirs| dirs.config_dir().join("config.toml"))
+        .unwrap_or_else(|| PathBuf::from(format!("{}.toml", app)))
+}
+
+/// `--config <path>` if given on the command line, else the default path
+pub fn path(app: &str) -> PathBuf {
+    cli::args()
+        .config
+        .clone()
+        .unwrap_or_else(|| default_path(app))
+}
+
+impl Config {
+    /// the saved audio settings over the app's `preferred` stream, and the
+    /// command line flags over both
+    pub fn stream_config(&self, preferred: StreamConfig) -> StreamConfig {
+        cli::args().stream_config(StreamConfig {
+            host: self.audio_host.clone().or(preferred.host),
+            device: self.audio_device.clone().or(preferred.device),
+            sample_rate: self.sample_rate.or(preferred.sample_rate),
+            frames_per_buffer: self.buffer_size.or(preferred.frames_per_buffer),
+            insert: self.insert.clone().or(preferred.insert),
+            adaptive: !self.fixed_buffer,
+            ..preferred
+        })
+    }
+
+    /// a JACK client named after `app` when `jack` is set
+    pub fn jack_client(&self, app: &str, ports: &'static [&'static str]) -> Option<JackConfig> {
+        if self.jack {
+            Some(JackConfig::new(app, ports))
+        } else {
+            None
+        }
+    }
+
+    /// never fails, a missing or malformed file gives the defaults
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("ignoring malformed config {}: {}", path.display(), e);
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// the main window, sized and placed as it was last time
+    pub fn build_window<M: 'static>(&self, app: &App, view: nannou::window::ViewFn<M>) -> WindowId {
+        let [w, h] = self.window.size.unwrap_or(DEFAULT_SIZE);
+        let id = app.new_window().size(w, h).view(view).build().unwrap();
+        let window = app.window(id).unwrap();
+        if let Some([x, y]) = self.window.position {
+            window.set_outer_position_pixels(x, y);
+        }
+        window.set_fullscreen(self.ui.fullscreen);
+        id
+    }
+
+    /// move, resize or toggle fullscreen where the file differs from `previous`
+    pub fn apply_window(&self, previous: &Config, app: &App) {
+        let window = app.main_window();
+        if self.window.size != previous.window.size {
+            if let Some([w, h]) = self.window.size {
+                window.set_inner_size_points(w as f32, h as f32);
+            }
+        }
+        if self.window.position != previous.window.position {
+            if let Some([x, y]) = self.window.position {
+                window.set_outer_position_pixels(x, y);
+            }
+        }
+        if self.ui.fullscreen != previous.ui.fullscreen {
+            window.set_fullscreen(self.ui.fullscreen);
+        }
+    }
+
+    /// remember the main window's current size and position, kept as it
+    /// was in kiosk mode so the desk layout survives a gallery run
+    pub fn capture_window(&mut self, app: &App) {
+        if kiosk::settings(self).is_some() {
+            return;
+        }
+        let window = app.main_window();
+        let (w, h) = window.inner_size_points();
+        self.window.size = Some([w as u32, h as u32]);
+        self.window.position = window.outer_position_pixels().ok().map(|(x, y)| [x, y]);
+        self.ui.fullscreen = window.is_fullscreen();
+    }
+}
+
+/// Rereads the config file whenever it is edited on disk.
+///
+/// Unlike `Config::load` a malformed file is ignored rather than replaced
+/// by the defaults, so a half-typed edit doesn't reset a running app.
+pub struct LiveConfig {
+    path: PathBuf,
+    watcher: Option<FileWatcher>,
+}
+
+impl LiveConfig {
+    /// hot-reload is skipped with a warning if the file can't be watched
+    pub fn new(path: &Path) -> Self {
+        let watcher = FileWatcher::new().and_then(|mut watcher| {
+            watcher.watch(path)?;
+            Ok(watcher)
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("not watching {}: {}", path.display(), e);
+                None
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            watcher,
+        }
+    }
+
+    /// the new config after an edit
+    pub fn poll(&mut self) -> Option<Config> {
+        let watcher = self.watcher.as_mut()?;
+        if watcher.poll().is_empty() {
+            return None;
+        }
+        let text = fs::read_to_string(&self.path).ok()?;
+        match toml::from_str(&text) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("not reloading {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// After a reload, `slot` opened again from `current` if it differs from
+/// `previous`. The old value goes first so it lets go of any port or device
+/// the new one needs.
+pub fn reopen<S: PartialEq, T>(
+    previous: &S,
+    current: &S,
+    slot: &mut Option<T>,
+    open: impl FnOnce() -> Option<T>,
+) {
+    if previous != current {
+        *slot = None;
+        *slot = open();
+    }
+}
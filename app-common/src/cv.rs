@@ -0,0 +1,138 @@
+//! Control voltages on extra channels of a DC-coupled interface, so the
+//! apps can drive modular synths.
+//!
+//! The app declares its `CvSignal`s, sets their voltages on `CvTargets`
+//! from `update` and wraps its engine in `WithCv`, which renders them next
+//! to the audio. Signals take consecutive channels from
+//! `CvConfig::first_channel` on, open the stream with `CvConfig::channels`.
+
+use crate::render::Render;
+use serde::{Deserialize, Serialize};
+
+pub use dsp_common::cv::{CvRenderer, CvScale, CvSignal, CvTargets};
+
+/// volts for levels at full, the usual modulation range
+pub const UNIPOLAR: f32 = 5.0;
+/// volts for an open gate or trigger
+pub const GATE: f32 = 5.0;
+
+/// frames the engine renders per call, longer buffers take several
+const MAX_BLOCK: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CvConfig {
+    /// 0-based channel of the first signal, right after the audio when
+    /// `None` or when it would overlap it
+    pub first_channel: Option<usize>,
+    /// volts at full scale, 10 for Expert Sleepers modules
+    pub full_scale: f32,
+    /// the pitch at 0V
+    pub zero_volt_hz: f32,
+}
+
+impl Default for CvConfig {
+    fn default() -> Self {
+        let scale = CvScale::default();
+        Self {
+            first_channel: None,
+            full_scale: scale.full_scale,
+            zero_volt_hz: scale.zero_volt_hz,
+        }
+    }
+}
+
+impl CvConfig {
+    pub fn scale(&self) -> CvScale {
+        CvScale {
+            full_scale: self.full_scale,
+            zero_volt_hz: self.zero_volt_hz,
+        }
+    }
+
+    pub fn first_channel(&self, audio_channels: usize) -> usize {
+        self.first_channel
+            .unwrap_or(audio_channels)
+            .max(audio_channels)
+    }
+
+    /// what the stream needs for the audio and every signal
+    pub fn channels(&self, audio_channels: usize, signals: usize) -> usize {
+        self.first_channel(audio_channels) + signals
+    }
+}
+
+/// The signals and where they go, built on the UI thread.
+pub struct CvOutput {
+    renderer: CvRenderer,
+    first_channel: usize,
+}
+
+impl CvOutput {
+    pub fn new(targets: CvTargets, config: &CvConfig, audio_channels: usize) -> Self {
+        Self {
+            renderer: CvRenderer::new(targets, config.scale()),
+            first_channel: config.first_channel(audio_channels),
+        }
+    }
+}
+
+/// An engine rendering a fixed number of audio channels, whatever the
+/// stream has, with the CV signals after them.
+pub struct WithCv<R> {
+    engine: R,
+    audio_channels: usize,
+    cv: Option<CvOutput>,
+    scratch: Vec<f32>,
+}
+
+impl<R: Render> WithCv<R> {
+    pub fn new(engine: R, audio_channels: usize, cv: Option<CvOutput>) -> Self {
+        Self {
+            engine,
+            audio_channels,
+            cv,
+            scratch: vec![0.0; MAX_BLOCK * audio_channels],
+        }
+    }
+
+    pub fn engine_mut(&mut self) -> &mut R {
+        &mut self.engine
+    }
+
+    /// from the next block on, open the stream with the new channel count
+    /// first
+    pub fn set_output(&mut self, cv: Option<CvOutput>) {
+        self.cv = cv;
+    }
+}
+
+impl<R: Render> Render for WithCv<R> {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if channels == self.audio_channels && self.cv.is_none() {
+            self.engine.render(out, channels, sample_rate);
+            return;
+        }
+
+        let audio_channels = self.audio_channels;
+        for chunk in out.chunks_mut(MAX_BLOCK * channels) {
+            let frames = chunk.len() / channels;
+            let block = &mut self.scratch[..frames * audio_channels];
+            block.iter_mut().for_each(|sample| *sample = 0.0);
+            self.engine.render(block, audio_channels, sample_rate);
+            for (frame, audio) in chunk
+                .chunks_exact_mut(channels)
+                .zip(block.chunks_exact(audio_channels))
+            {
+                frame
+                    .iter_mut()
+                    .zip(audio.iter())
+                    .for_each(|(out, sample)| *out = *sample);
+            }
+            if let Some(cv) = &mut self.cv {
+                cv.renderer
+                    .render(chunk, channels, cv.first_channel, sample_rate);
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Audio callback timing and a HUD to show it, for tuning buffer sizes.
+//!
+//! `CallbackStats` is filled in by the stream the `Supervisor` runs, device
+//! or JACK, without locking or allocating. Xruns can't be read back from
+//! every backend, so they are inferred: a callback that takes longer than
+//! its buffer lasts is an overload, one that starts later than the previous
+//! buffer ran out is a gap.
+//!
+//! Panics the engine contained, see `render::contain`, are counted there too
+//! and the HUD shows a banner for a while after each, visible or not. So
+//! does the `Supervisor` growing the buffer after sustained overloads.
+
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// shows and hides the HUD
+pub const HOTKEY: Key = Key::H;
+
+/// histogram buckets per doubling of the callback duration
+const STEPS_PER_OCTAVE: f32 = 4.0;
+/// up to ~16s in microseconds
+const BUCKETS: usize = 96;
+/// how often the HUD recomputes percentiles
+const REFRESH: Duration = Duration::from_millis(500);
+const FRAME_HISTORY: usize = 120;
+/// how long the panic banner stays up
+const BANNER: Duration = Duration::from_secs(10);
+
+fn bucket(micros: f32) -> usize {
+    ((micros + 1.0).log2() * STEPS_PER_OCTAVE).max(0.0) as usize
+}
+
+/// the longest duration, in microseconds, that lands in `bucket`
+fn bucket_limit(bucket: usize) -> f32 {
+    2f32.powf((bucket + 1) as f32 / STEPS_PER_OCTAVE) - 1.0
+}
+
+/// Written by the audio callback, read by the HUD.
+pub struct CallbackStats {
+    epoch: Instant,
+    buckets: Vec<AtomicUsize>,
+    callbacks: AtomicUsize,
+    overloads: AtomicUsize,
+    gaps: AtomicUsize,
+    /// nanoseconds since `epoch`, 0 before the first callback
+    last_start: AtomicU64,
+    /// how long the previous buffer lasted, in nanoseconds
+    last_budget: AtomicU64,
+    /// the previous buffer's length, 0 before the first callback
+    frames: AtomicUsize,
+    /// what the buffer last grew to, 0 until it has
+    enlarged: AtomicUsize,
+    panics: AtomicUsize,
+    /// only ever `try_lock`ed by the audio thread
+    last_panic: Mutex<String>,
+}
+
+impl Default for CallbackStats {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            buckets: (0..BUCKETS).map(|_| AtomicUsize::new(0)).collect(),
+            callbacks: AtomicUsize::new(0),
+            overloads: AtomicUsize::new(0),
+            gaps: AtomicUsize::new(0),
+            last_start: AtomicU64::new(0),
+            last_budget: AtomicU64::new(0),
+            frames: AtomicUsize::new(0),
+            enlarged: AtomicUsize::new(0),
+            panics: AtomicUsize::new(0),
+            last_panic: Mutex::new(String::new()),
+        }
+    }
+}
+
+impl CallbackStats {
+    /// called with the callback's start time once it has rendered `frames`
+    pub fn record(&self, start: Instant, frames: usize, sample_rate: u32) {
+        let elapsed = start.elapsed();
+        let budget = (frames as u64 * 1_000_000_000) / sample_rate.max(1) as u64;
+        let since_epoch = start.duration_since(self.epoch).as_nanos() as u64;
+
+        let previous = self.last_start.swap(since_epoch.max(1), Ordering::Relaxed);
+        let previous_budget = self.last_budget.swap(budget, Ordering::Relaxed);
+        self.frames.store(frames, Ordering::Relaxed);
+        // half a buffer of jitter is normal for most drivers
+        if previous != 0 && since_epoch.saturating_sub(previous) > previous_budget * 3 / 2 {
+            self.gaps.fetch_add(1, Ordering::Relaxed);
+        }
+        if elapsed.as_nanos() as u64 > budget {
+            self.overloads.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let index = bucket(elapsed.as_secs_f32() * 1e6).min(BUCKETS - 1);
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn callbacks(&self) -> usize {
+        self.callbacks.load(Ordering::Relaxed)
+    }
+
+    pub fn overloads(&self) -> usize {
+        self.overloads.load(Ordering::Relaxed)
+    }
+
+    pub fn gaps(&self) -> usize {
+        self.gaps.load(Ordering::Relaxed)
+    }
+
+    /// the last buffer's length in frames, `None` before the first
+    pub fn frames(&self) -> Option<usize> {
+        Some(self.frames.load(Ordering::Relaxed)).filter(|&frames| frames > 0)
+    }
+
+    /// the buffer grew to `frames` to keep up, see `Supervisor::poll`
+    pub fn record_enlarged(&self, frames: usize) {
+        self.enlarged.store(frames, Ordering::Relaxed);
+    }
+
+    /// what the buffer last grew to, 0 until it has
+    pub fn enlarged(&self) -> usize {
+        self.enlarged.load(Ordering::Relaxed)
+    }
+
+    /// returns how many panics there have been, this one included
+    pub fn record_panic(&self, message: &str) -> usize {
+        if let Ok(mut last) = self.last_panic.try_lock() {
+            last.clear();
+            last.push_str(message);
+        }
+        self.panics.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn panics(&self) -> usize {
+        self.panics.load(Ordering::Relaxed)
+    }
+
+    pub fn last_panic(&self) -> String {
+        self.last_panic
+            .lock()
+            .map(|last| last.clone())
+            .unwrap_or_default()
+    }
+
+    fn histogram(&self) -> Vec<usize> {
+        self.buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+/// Fill level of one of the app's queues.
+#[derive(Clone, Debug)]
+pub struct QueueStats {
+    pub name: &'static str,
+    pub queued: usize,
+    pub capacity: usize,
+    pub dropped: usize,
+}
+
+/// Callback duration percentiles over the last refresh, in microseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Percentiles {
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Percentiles {
+    fn from_histogram(counts: &[usize]) -> Option<Self> {
+        let total: usize = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let at = |fraction: f64| {
+            let rank = (total as f64 * fraction).ceil().max(1.0) as usize;
+            let mut seen = 0;
+            for (i, count) in counts.iter().enumerate() {
+                seen += count;
+                if seen >= rank {
+                    return bucket_limit(i);
+                }
+            }
+            bucket_limit(counts.len() - 1)
+        };
+        Some(Self {
+            p50: at(0.5),
+            p95: at(0.95),
+            p99: at(0.99),
+            max: at(1.0),
+        })
+    }
+}
+
+/// Overlay with callback timing, xruns, frame times and queue levels.
+pub struct Hud {
+    visible: bool,
+    stats: Arc<CallbackStats>,
+    previous: Vec<usize>,
+    last_refresh: Instant,
+    percentiles: Percentiles,
+    frame_times: VecDeque<f32>,
+    queues: Vec<QueueStats>,
+    panics: usize,
+    enlarged: usize,
+    /// the latest panic or growth and when to stop showing it
+    banner: Option<(String, Instant)>,
+}
+
+impl Hud {
+    pub fn new(stats: Arc<CallbackStats>) -> Self {
+        Self {
+            visible: false,
+            previous: stats.histogram(),
+            stats,
+            last_refresh: Instant::now(),
+            percentiles: Percentiles::default(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            queues: Vec::new(),
+            panics: stats.panics(),
+            enlarged: stats.enlarged(),
+            banner: None,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        if key == HOTKEY {
+            self.visible = !self.visible;
+        }
+    }
+
+    /// called every frame with `Update::since_last`
+    pub fn update(&mut self, since_last: Duration) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(since_last.as_secs_f32() * 1e3);
+        self.queues.clear();
+
+        let panics = self.stats.panics();
+        if panics != self.panics {
+            self.panics = panics;
+            let message = format!(
+                "audio engine panicked, its buffer was silenced ({} so far): {}",
+                panics,
+                self.stats.last_panic()
+            );
+            self.banner = Some((message, Instant::now() + BANNER));
+        }
+        let enlarged = self.stats.enlarged();
+        if enlarged != self.enlarged {
+            self.enlarged = enlarged;
+            let message = format!(
+                "audio kept overloading, the buffer grew to {} frames",
+                enlarged
+            );
+            self.banner = Some((message, Instant::now() + BANNER));
+        }
+        if matches!(&self.banner, Some((_, until)) if Instant::now() > *until) {
+            self.banner = None;
+        }
+
+        if self.last_refresh.elapsed() < REFRESH {
+            return;
+        }
+        self.last_refresh = Instant::now();
+        let histogram = self.stats.histogram();
+        let recent: Vec<usize> = histogram
+            .iter()
+            .zip(self.previous.iter())
+            .map(|(now, before)| now - before)
+            .collect();
+        if let Some(percentiles) = Percentiles::from_histogram(&recent) {
+            self.percentiles = percentiles;
+        }
+        self.previous = histogram;
+    }
+
+    /// reported every frame, after `update`
+    pub fn queue(&mut self, name: &'static str, queued: usize, capacity: usize, dropped: usize) {
+        self.queues.push(QueueStats {
+            name,
+            queued,
+            capacity,
+            dropped,
+        });
+    }
+
+    pub fn percentiles(&self) -> Percentiles {
+        self.percentiles
+    }
+
+    pub fn draw(&self, draw: &Draw, rect: Rect, palette: &Palette) {
+        const LINE: f32 = 18.0;
+        const WIDTH: f32 = 320.0;
+
+        if let Some((message, _)) = &self.banner {
+            let [r, g, b] = palette.meter.over;
+            draw.rect()
+                .x_y(0.0, rect.top() - LINE)
+                .w_h(rect.w(), 2.0 * LINE)
+                .color(rgba(r, g, b, 0.85));
+            draw.text(message)
+                .x_y(0.0, rect.top() - LINE)
+                .w_h(rect.w() - 2.0 * LINE, LINE)
+                .font_size(12)
+                .color(theme::color(palette.background));
+        }
+        if !self.visible {
+            return;
+        }
+        let lines = 6 + self.queues.len();
+        let height = (lines as f32 + 1.0) * LINE;
+        let [r, g, b] = palette.background;
+        let top_left = pt2(rect.left() + 20.0, rect.bottom() + 20.0 + height);
+        draw.rect()
+            .x_y(top_left.x + WIDTH / 2.0, top_left.y - height / 2.0)
+            .w_h(WIDTH, height)
+            .color(rgba(r, g, b, 0.85));
+
+        let text = theme::color(palette.line);
+        let mut y = top_left.y - LINE;
+        let mut line = |message: &str| {
+            draw.text(message)
+                .x_y(top_left.x + WIDTH / 2.0, y)
+                .w_h(WIDTH - LINE, LINE)
+                .left_justify()
+                .font_size(12)
+                .color(text);
+            y -= LINE;
+        };
+
+        let p = self.percentiles;
+        line(&format!(
+            "callback us  p50 {:.0}  p95 {:.0}  p99 {:.0}  max {:.0}",
+            p.p50, p.p95, p.p99, p.max
+        ));
+        line(&format!("callbacks    {}", self.stats.callbacks()));
+        line(&format!(
+            "xruns        {} overloads, {} gaps",
+            self.stats.overloads(),
+            self.stats.gaps()
+        ));
+        let count = self.frame_times.len().max(1) as f32;
+        let mean = self.frame_times.iter().sum::<f32>() / count;
+        let worst = self.frame_times.iter().cloned().fold(0.0, f32::max);
+        line(&format!(
+            "frame ms     mean {:.1}  worst {:.1}",
+            mean, worst
+        ));
+        line(&format!("fps          {:.0}", 1e3 / mean.max(f32::EPSILON)));
+        line("");
+        for queue in self.queues.iter() {
+            line(&format!(
+                "{:<12} {}/{}  {} dropped",
+                queue.name, queue.queued, queue.capacity, queue.dropped
+            ));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! DMX lighting over Art-Net or sACN (E1.31), driven by the apps' analysis.
+//!
+//! The app sets numbered levels (band energy, voice activity...) and fires
+//! numbered flashes (grain events, figure jumps...), the config maps those
+//! to channels of one universe.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+pub const CHANNELS: usize = 512;
+const ART_NET_PORT: u16 = 6454;
+const SACN_PORT: u16 = 5568;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Protocol {
+    ArtNet,
+    Sacn,
+}
+
+/// `source = "Flash"` and `index = 0` in a mapping
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", content = "index")]
+pub enum Source {
+    /// follows `set_level(index, ..)`
+    Level(usize),
+    /// jumps to full on `flash(index)` and decays
+    Flash(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mapping {
+    /// 1 to 512, as printed on fixtures
+    pub channel: u16,
+    #[serde(default)]
+    pub min: u8,
+    #[serde(default = "full")]
+    pub max: u8,
+    /// seconds for a flash to fade out
+    #[serde(default = "default_decay")]
+    pub decay: f32,
+    #[serde(flatten)]
+    pub source: Source,
+}
+
+fn full() -> u8 {
+    255
+}
+
+fn default_decay() -> f32 {
+    0.25
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DmxConfig {
+    pub protocol: Protocol,
+    /// node address, broadcast for Art-Net and the universe's multicast
+    /// group for sACN when `None`
+    pub target: Option<IpAddr>,
+    pub universe: u16,
+    /// packets per second, sent even when nothing changes
+    pub rate: f32,
+    pub mappings: Vec<Mapping>,
+}
+
+impl Default for DmxConfig {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::ArtNet,
+            target: None,
+            universe: 0,
+            rate: 40.0,
+            mappings: Vec::new(),
+        }
+    }
+}
+
+impl DmxConfig {
+    pub fn destination(&self) -> SocketAddr {
+        match self.protocol {
+            Protocol::ArtNet => SocketAddr::new(
+                self.target.unwrap_or(IpAddr::V4(Ipv4Addr::BROADCAST)),
+                ART_NET_PORT,
+            ),
+            Protocol::Sacn => {
+                let [hi, lo] = self.universe.to_be_bytes();
+                SocketAddr::new(
+                    self.target
+                        .unwrap_or(IpAddr::V4(Ipv4Addr::new(239, 255, hi, lo))),
+                    SACN_PORT,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// a mapping points outside the universe
+    Channel(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "dmx io error: {}", e),
+            Error::Channel(c) => write!(f, "dmx channel {} is outside 1-{}", c, CHANNELS),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// ArtDmx packet for one universe, `universe` is the 15 bit port address
+pub fn art_dmx(universe: u16, sequence: u8, data: &[u8; CHANNELS]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(18 + CHANNELS);
+    packet.extend_from_slice(b"Art-Net\0");
+    packet.extend_from_slice(&0x5000u16.to_le_bytes());
+    packet.extend_from_slice(&14u16.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.extend_from_slice(&(universe & 0x7fff).to_le_bytes());
+    packet.extend_from_slice(&(CHANNELS as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// E1.31 data packet, `cid` identifies this sender across restarts
+pub fn sacn(
+    universe: u16,
+    sequence: u8,
+    cid: &[u8; 16],
+    source_name: &str,
+    data: &[u8; CHANNELS],
+) -> Vec<u8> {
+    const LENGTH: usize = 126 + CHANNELS;
+    let pdu = |offset: usize| (0x7000 | (LENGTH - offset) as u16).to_be_bytes();
+
+    let mut packet = Vec::with_capacity(LENGTH);
+    // root layer
+    packet.extend_from_slice(&0x0010u16.to_be_bytes());
+    packet.extend_from_slice(&0u16.to_be_bytes());
+    packet.extend_from_slice(b"ASC-E1.17\0\0\0");
+    packet.extend_from_slice(&pdu(16));
+    packet.extend_from_slice(&4u32.to_be_bytes());
+    packet.extend_from_slice(cid);
+    // framing layer
+    packet.extend_from_slice(&pdu(38));
+    packet.extend_from_slice(&2u32.to_be_bytes());
+    let mut name = [0u8; 64];
+    let len = source_name.len().min(63);
+    name[..len].copy_from_slice(&source_name.as_bytes()[..len]);
+    packet.extend_from_slice(&name);
+    packet.push(100);
+    packet.extend_from_slice(&0u16.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.extend_from_slice(&universe.to_be_bytes());
+    // DMP layer, start code then the channels
+    packet.extend_from_slice(&pdu(115));
+    packet.push(0x02);
+    packet.push(0xa1);
+    packet.extend_from_slice(&0u16.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    packet.extend_from_slice(&(CHANNELS as u16 + 1).to_be_bytes());
+    packet.push(0);
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// A stable sender id derived from the app name.
+fn cid(name: &str) -> [u8; 16] {
+    let mut cid = [0u8; 16];
+    for (i, byte) in name.bytes().enumerate() {
+        cid[i % 16] = cid[i % 16].wrapping_mul(31).wrapping_add(byte);
+    }
+    cid
+}
+
+/// Sends one universe at a steady rate, called from `update`.
+pub struct DmxOutput {
+    config: DmxConfig,
+    socket: UdpSocket,
+    destination: SocketAddr,
+    app_name: String,
+    cid: [u8; 16],
+    data: [u8; CHANNELS],
+    levels: Vec<f32>,
+    /// one per mapping, so each fades at its own rate
+    flashes: Vec<f32>,
+    sequence: u8,
+    last_update: Instant,
+    last_send: Option<Instant>,
+}
+
+impl DmxOutput {
+    pub fn open(app_name: &str, config: DmxConfig) -> Result<Self, Error> {
+        if let Some(mapping) = config
+            .mappings
+            .iter()
+            .find(|m| m.channel == 0 || m.channel as usize > CHANNELS)
+        {
+            return Err(Error::Channel(mapping.channel));
+        }
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        let levels = config
+            .mappings
+            .iter()
+            .filter_map(|m| match m.source {
+                Source::Level(i) => Some(i + 1),
+                Source::Flash(_) => None,
+            })
+            .max()
+            .unwrap_or(0);
+        Ok(Self {
+            destination: config.destination(),
+            levels: vec![0.0; levels],
+            flashes: vec![0.0; config.mappings.len()],
+            config,
+            socket,
+            app_name: app_name.to_string(),
+            cid: cid(app_name),
+            data: [0; CHANNELS],
+            sequence: 0,
+            last_update: Instant::now(),
+            last_send: None,
+        })
+    }
+
+    pub fn config(&self) -> &DmxConfig {
+        &self.config
+    }
+
+    /// `value` in [0, 1], ignored when nothing is mapped to `index`
+    pub fn set_level(&mut self, index: usize, value: f32) {
+        if let Some(level) = self.levels.get_mut(index) {
+            *level = value.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn flash(&mut self, index: usize) {
+        for (mapping, flash) in self.config.mappings.iter().zip(self.flashes.iter_mut()) {
+            if mapping.source == Source::Flash(index) {
+                *flash = 1.0;
+            }
+        }
+    }
+
+    /// the channel values last sent
+    pub fn data(&self) -> &[u8; CHANNELS] {
+        &self.data
+    }
+
+    /// called at frame rate, fades flashes and sends when a packet is due
+    pub fn update(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        for (mapping, flash) in self.config.mappings.iter().zip(self.flashes.iter_mut()) {
+            let value = match mapping.source {
+                Source::Level(i) => self.levels[i],
+                Source::Flash(_) => *flash,
+            };
+            let (min, max) = (mapping.min as f32, mapping.max as f32);
+            self.data[mapping.channel as usize - 1] = (min + value * (max - min)).round() as u8;
+            *flash = (*flash - elapsed / mapping.decay.max(f32::EPSILON)).max(0.0);
+        }
+
+        let interval = Duration::from_secs_f32(1.0 / self.config.rate.max(1.0));
+        if matches!(self.last_send, Some(sent) if now.duration_since(sent) < interval) {
+            return Ok(());
+        }
+        self.last_send = Some(now);
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+
+        let packet = match self.config.protocol {
+            Protocol::ArtNet => art_dmx(self.config.universe, self.sequence, &self.data),
+            Protocol::Sacn => sacn(
+                self.config.universe,
+                self.sequence,
+                &self.cid,
+                &self.app_name,
+                &self.data,
+            ),
+        };
+        match self.socket.send_to(&packet, self.destination) {
+            Err(e) if e.kind() != io::ErrorKind::WouldBlock => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,501 @@
+//! Game controllers driving parameters.
+//!
+//! A `GamepadMap` binds buttons and axes, by their gilrs names, to
+//! parameter names and lives next to the app's config so it can be edited
+//! by hand. `Gamepads` follows controllers being plugged in and out and
+//! needs the `gamepad` feature, `GamepadEditor` binds by example.
+
+use crate::param::Params;
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// toggles the mapping editor
+pub const HOTKEY: Key = Key::G;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ButtonAction {
+    /// flips between the ends of the range on every press
+    Toggle,
+    /// the top of the range while held, analog triggers sweep it
+    Momentary,
+    /// back to the parameter's default on press
+    Reset,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ButtonBinding {
+    /// gilrs `Button` name, e.g. `South` or `LeftTrigger2`
+    pub button: String,
+    pub param: String,
+    #[serde(default = "toggle")]
+    pub action: ButtonAction,
+}
+
+fn toggle() -> ButtonAction {
+    ButtonAction::Toggle
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    /// gilrs `Axis` name, e.g. `LeftStickX`
+    pub axis: String,
+    pub param: String,
+    #[serde(default)]
+    pub invert: bool,
+    /// deflection ignored around the centre
+    #[serde(default = "default_deadzone")]
+    pub deadzone: f32,
+    /// when set the stick nudges the parameter by up to this much of its
+    /// range per second instead of setting it, so it stays put on release
+    #[serde(default)]
+    pub rate: Option<f32>,
+}
+
+fn default_deadzone() -> f32 {
+    0.1
+}
+
+impl AxisBinding {
+    /// the deadzone cut out and the rest rescaled to [-1, 1]
+    fn shape(&self, value: f32) -> f32 {
+        let value = if self.invert { -value } else { value };
+        let deadzone = self.deadzone.clamp(0.0, 0.99);
+        if value.abs() <= deadzone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+        }
+    }
+}
+
+/// One physical control, what the editor learns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    Button(String),
+    Axis(String),
+}
+
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Control::Button(name) => write!(f, "button {}", name),
+            Control::Axis(name) => write!(f, "axis {}", name),
+        }
+    }
+}
+
+/// Controller input as the map sees it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Pressed(String),
+    Released(String),
+    /// analog buttons such as triggers, 0 to 1
+    Button(String, f32),
+    /// -1 to 1
+    Axis(String, f32),
+}
+
+/// `[[button]]` and `[[axis]]` tables in `gamepad.toml`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadMap {
+    #[serde(rename = "button")]
+    pub buttons: Vec<ButtonBinding>,
+    #[serde(rename = "axis")]
+    pub axes: Vec<AxisBinding>,
+}
+
+impl GamepadMap {
+    /// `gamepad.toml` next to the app's config
+    pub fn path(config_path: &Path) -> PathBuf {
+        config_path.with_file_name("gamepad.toml")
+    }
+
+    /// never fails, a missing or malformed file gives an empty map
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("ignoring malformed gamepad map {}: {}", path.display(), e);
+                GamepadMap::default()
+            }),
+            Err(_) => GamepadMap::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// `control` drives `param` and nothing else from now on
+    pub fn bind(&mut self, control: &Control, param: &str) {
+        match control {
+            Control::Button(name) => {
+                self.buttons.retain(|b| b.button != *name);
+                self.buttons.push(ButtonBinding {
+                    button: name.clone(),
+                    param: param.into(),
+                    action: ButtonAction::Toggle,
+                });
+            }
+            Control::Axis(name) => {
+                self.axes.retain(|a| a.axis != *name);
+                self.axes.push(AxisBinding {
+                    axis: name.clone(),
+                    param: param.into(),
+                    invert: false,
+                    deadzone: default_deadzone(),
+                    rate: None,
+                });
+            }
+        }
+    }
+
+    pub fn unbind(&mut self, param: &str) {
+        self.buttons.retain(|b| b.param != param);
+        self.axes.retain(|a| a.param != param);
+    }
+
+    /// the controls bound to `param`
+    pub fn controls(&self, param: &str) -> Vec<Control> {
+        let buttons = self
+            .buttons
+            .iter()
+            .filter(|b| b.param == param)
+            .map(|b| Control::Button(b.button.clone()));
+        let axes = self
+            .axes
+            .iter()
+            .filter(|a| a.param == param)
+            .map(|a| Control::Axis(a.axis.clone()));
+        buttons.chain(axes).collect()
+    }
+
+    /// true if a parameter changed, relative axes move in `apply_held`
+    pub fn apply(&self, params: &Params, input: &Input) -> bool {
+        let mut applied = false;
+        match input {
+            Input::Pressed(name) | Input::Released(name) | Input::Button(name, _) => {
+                for binding in self.buttons.iter().filter(|b| b.button == *name) {
+                    let i = match params.index_of(&binding.param) {
+                        Some(i) => i,
+                        None => continue,
+                    };
+                    match (binding.action, input) {
+                        (ButtonAction::Toggle, Input::Pressed(_)) => {
+                            let on = params.get_normalized(i) < 0.5;
+                            params.set_normalized(i, if on { 1.0 } else { 0.0 });
+                        }
+                        (ButtonAction::Momentary, Input::Pressed(_)) => {
+                            params.set_normalized(i, 1.0)
+                        }
+                        (ButtonAction::Momentary, Input::Released(_)) => {
+                            params.set_normalized(i, 0.0)
+                        }
+                        (ButtonAction::Momentary, Input::Button(_, value)) => {
+                            params.set_normalized(i, *value)
+                        }
+                        (ButtonAction::Reset, Input::Pressed(_)) => {
+                            params.set(i, params.specs()[i].default)
+                        }
+                        _ => continue,
+                    }
+                    applied = true;
+                }
+            }
+            Input::Axis(name, value) => {
+                for binding in self
+                    .axes
+                    .iter()
+                    .filter(|a| a.axis == *name && a.rate.is_none())
+                {
+                    if let Some(i) = params.index_of(&binding.param) {
+                        params.set_normalized(i, 0.5 + 0.5 * binding.shape(*value));
+                        applied = true;
+                    }
+                }
+            }
+        }
+        applied
+    }
+
+    /// moves the parameters of relative axes by `seconds` worth of the
+    /// current deflection in `axes`
+    pub fn apply_held(&self, params: &Params, axes: &BTreeMap<String, f32>, seconds: f32) -> bool {
+        let mut applied = false;
+        for binding in self.axes.iter() {
+            let (rate, value) = match (binding.rate, axes.get(&binding.axis)) {
+                (Some(rate), Some(value)) => (rate, binding.shape(*value)),
+                _ => continue,
+            };
+            if value == 0.0 {
+                continue;
+            }
+            if let Some(i) = params.index_of(&binding.param) {
+                let normalized = params.get_normalized(i) + value * rate * seconds;
+                params.set_normalized(i, normalized.clamp(0.0, 1.0));
+                applied = true;
+            }
+        }
+        applied
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `gamepad` feature
+    Unavailable,
+    Gilrs(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `gamepad` feature"),
+            Error::Gilrs(e) => write!(f, "gamepad: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Every connected controller, polled from `update`.
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: gilrs::Gilrs,
+    connected: Vec<String>,
+    /// latest deflection per axis, for relative bindings
+    axes: BTreeMap<String, f32>,
+    last: Option<Control>,
+    last_poll: Instant,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Self, Error> {
+        #[cfg(feature = "gamepad")]
+        {
+            let gilrs = gilrs::Gilrs::new().map_err(|e| Error::Gilrs(e.to_string()))?;
+            let connected = gilrs
+                .gamepads()
+                .map(|(_, pad)| pad.name().to_string())
+                .collect();
+            Ok(Self {
+                gilrs,
+                connected,
+                axes: BTreeMap::new(),
+                last: None,
+                last_poll: Instant::now(),
+            })
+        }
+        #[cfg(not(feature = "gamepad"))]
+        Err(Error::Unavailable)
+    }
+
+    /// names of the controllers plugged in
+    pub fn connected(&self) -> &[String] {
+        &self.connected
+    }
+
+    /// the control last pressed or pushed past half way, once
+    pub fn take_last(&mut self) -> Option<Control> {
+        self.last.take()
+    }
+
+    /// called at frame rate, true if a parameter changed
+    pub fn poll(&mut self, map: &GamepadMap, params: &Params) -> bool {
+        let seconds = self.last_poll.elapsed().as_secs_f32();
+        self.last_poll = Instant::now();
+
+        let mut applied = false;
+        while let Some(input) = self.next_input() {
+            match &input {
+                Input::Pressed(name) => self.last = Some(Control::Button(name.clone())),
+                Input::Axis(name, value) => {
+                    if value.abs() > 0.5 {
+                        self.last = Some(Control::Axis(name.clone()));
+                    }
+                    self.axes.insert(name.clone(), *value);
+                }
+                _ => {}
+            }
+            applied |= map.apply(params, &input);
+        }
+        map.apply_held(params, &self.axes, seconds) || applied
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn next_input(&mut self) -> Option<Input> {
+        use gilrs::EventType;
+
+        while let Some(event) = self.gilrs.next_event() {
+            let input = match event.event {
+                EventType::ButtonPressed(button, _) => Input::Pressed(format!("{:?}", button)),
+                EventType::ButtonReleased(button, _) => Input::Released(format!("{:?}", button)),
+                EventType::ButtonChanged(button, value, _) => {
+                    Input::Button(format!("{:?}", button), value)
+                }
+                EventType::AxisChanged(axis, value, _) => Input::Axis(format!("{:?}", axis), value),
+                EventType::Connected | EventType::Disconnected => {
+                    self.connected = self
+                        .gilrs
+                        .gamepads()
+                        .map(|(_, pad)| pad.name().to_string())
+                        .collect();
+                    // a stick unplugged mid-push would keep nudging
+                    self.axes.clear();
+                    continue;
+                }
+                _ => continue,
+            };
+            return Some(input);
+        }
+        None
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    fn next_input(&mut self) -> Option<Input> {
+        None
+    }
+}
+
+/// Overlay listing each parameter's controls, binds by example.
+///
+/// Up/down pick a parameter, return waits for the next control touched on
+/// any controller and binds it, backspace clears the parameter's bindings.
+#[derive(Default)]
+pub struct GamepadEditor {
+    open: bool,
+    selected: usize,
+    learning: bool,
+}
+
+impl GamepadEditor {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// true if the key was for the editor, the map may have changed
+    pub fn key_pressed(&mut self, key: Key, params: &Params, map: &mut GamepadMap) -> bool {
+        if key == HOTKEY {
+            self.open = !self.open;
+            self.learning = false;
+            return true;
+        }
+        if !self.open || params.is_empty() {
+            return false;
+        }
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(params.len() - 1),
+            Key::Return => self.learning = true,
+            Key::Back | Key::Delete => map.unbind(params.specs()[self.selected].name),
+            Key::Escape => {
+                self.learning = false;
+                self.open = false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// called after `Gamepads::poll`, true when a binding was learnt
+    pub fn update(
+        &mut self,
+        gamepads: &mut Gamepads,
+        params: &Params,
+        map: &mut GamepadMap,
+    ) -> bool {
+        let control = gamepads.take_last();
+        if !(self.open && self.learning) {
+            return false;
+        }
+        match (control, params.specs().get(self.selected)) {
+            (Some(control), Some(spec)) => {
+                map.bind(&control, spec.name);
+                self.learning = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        draw: &Draw,
+        rect: Rect,
+        palette: &Palette,
+        params: &Params,
+        map: &GamepadMap,
+        connected: &[String],
+    ) {
+        const LINE: f32 = 22.0;
+
+        if !self.open {
+            return;
+        }
+        // the scene stays faintly visible behind the list
+        let [r, g, b] = palette.background;
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgba(r, g, b, 0.85));
+        let text = theme::color(palette.line);
+        let accent = theme::color(palette.accent(0));
+        let mut y = rect.top() - 2.0 * LINE;
+        let mut line = |message: &str, color: Rgb, size: u32| {
+            draw.text(message)
+                .x_y(0.0, y)
+                .w_h(rect.w() - 4.0 * LINE, LINE)
+                .left_justify()
+                .font_size(size)
+                .color(color);
+            y -= LINE;
+        };
+
+        if connected.is_empty() {
+            line("no controller connected", text, 14);
+        } else {
+            line(&format!("controllers: {}", connected.join(", ")), text, 14);
+        }
+        line("", text, 14);
+        for (i, spec) in params.specs().iter().enumerate() {
+            let controls = map
+                .controls(spec.name)
+                .iter()
+                .map(Control::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let controls = if i == self.selected && self.learning {
+                "touch a control...".to_string()
+            } else if controls.is_empty() {
+                "-".to_string()
+            } else {
+                controls
+            };
+            let marker = if i == self.selected { ">" } else { " " };
+            let color = if i == self.selected { accent } else { text };
+            line(
+                &format!("{} {}: {}", marker, spec.name, controls),
+                color,
+                14,
+            );
+        }
+        line("", text, 14);
+        line(
+            "up/down pick a parameter, return learns a control, backspace clears, g closes",
+            text,
+            12,
+        );
+    }
+}
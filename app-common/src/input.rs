@@ -0,0 +1,237 @@
+//! Mic/line input into a lock-free ring any engine or visualizer can drain.
+
+#[cfg(feature = "audio")]
+use nannou_audio as audio;
+#[cfg(feature = "audio")]
+use nannou_audio::Buffer;
+use ringbuf::Consumer;
+#[cfg(feature = "audio")]
+use ringbuf::{Producer, RingBuffer};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[derive(Clone, Debug)]
+pub struct InputConfig {
+    /// audio API by name, see `audio::hosts`
+    pub host: Option<String>,
+    /// the system default when `None`
+    pub device: Option<String>,
+    /// device channel feeding each ring channel, `[1, 0]` swaps a stereo pair
+    pub channels: Vec<usize>,
+    pub sample_rate: Option<u32>,
+    pub frames_per_buffer: Option<usize>,
+    /// seconds of audio the ring holds before the input starts dropping
+    pub ring_seconds: f32,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            device: None,
+            channels: vec![0, 1],
+            sample_rate: None,
+            frames_per_buffer: None,
+            ring_seconds: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoDevice,
+    NoChannels,
+    Build(String),
+    Unavailable,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoDevice => write!(f, "no audio input device available"),
+            Error::NoChannels => write!(f, "the input channel map is empty"),
+            Error::Build(e) => write!(f, "failed to build input stream: {}", e),
+            Error::Unavailable => write!(f, "built without the `audio` feature"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// names of the input devices of the host called `host`, for a
+/// `DevicePicker`
+pub fn devices(host: Option<&str>) -> Vec<String> {
+    #[cfg(feature = "audio")]
+    {
+        crate::audio::host(host)
+            .input_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+    #[cfg(not(feature = "audio"))]
+    {
+        let _ = host;
+        Vec::new()
+    }
+}
+
+/// Lives on the input thread.
+#[cfg(feature = "audio")]
+pub struct Capture {
+    producer: Producer<f32>,
+    map: Vec<usize>,
+    overruns: Arc<AtomicUsize>,
+    sample_rate: Arc<AtomicU32>,
+}
+
+#[cfg(feature = "audio")]
+fn capture(capture: &mut Capture, buffer: &Buffer) {
+    capture
+        .sample_rate
+        .store(buffer.sample_rate(), Ordering::Relaxed);
+    let frames = buffer.len_frames();
+    // all or nothing so channels never get out of step in the ring
+    if capture.producer.remaining() < frames * capture.map.len() {
+        capture.overruns.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    for frame in buffer.frames() {
+        for &channel in capture.map.iter() {
+            let _ = capture
+                .producer
+                .push(frame.get(channel).copied().unwrap_or(0.0));
+        }
+    }
+}
+
+/// Reading end of the input, `Send` so it can move to the audio thread.
+/// Never blocks or allocates.
+pub struct InputReader {
+    consumer: Consumer<f32>,
+    channels: usize,
+    sample_rate: Arc<AtomicU32>,
+}
+
+impl InputReader {
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// the requested rate until the first buffer arrives
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// whole frames waiting in the ring
+    pub fn available(&self) -> usize {
+        self.consumer.len() / self.channels
+    }
+
+    /// fills `out` with the oldest interleaved frames, returns how many
+    pub fn read(&mut self, out: &mut [f32]) -> usize {
+        let whole = out.len() - out.len() % self.channels;
+        self.consumer.pop_slice(&mut out[..whole]) / self.channels
+    }
+
+    /// the newest frames that fit in `out`, dropping anything older,
+    /// for visualizers that only care about now
+    pub fn latest(&mut self, out: &mut [f32]) -> usize {
+        let wanted = out.len() / self.channels;
+        let stale = self.available().saturating_sub(wanted);
+        self.consumer.discard(stale * self.channels);
+        self.read(out)
+    }
+}
+
+/// An open input stream, capture stops when it is dropped.
+pub struct Input {
+    #[cfg(feature = "audio")]
+    _stream: audio::Stream<Capture>,
+    device: String,
+    overruns: Arc<AtomicUsize>,
+}
+
+impl Input {
+    pub fn start(config: &InputConfig) -> Result<(Input, InputReader), Error> {
+        if config.channels.is_empty() {
+            return Err(Error::NoChannels);
+        }
+        #[cfg(feature = "audio")]
+        {
+            Self::open(config)
+        }
+        #[cfg(not(feature = "audio"))]
+        Err(Error::Unavailable)
+    }
+
+    #[cfg(feature = "audio")]
+    fn open(config: &InputConfig) -> Result<(Input, InputReader), Error> {
+        let host = crate::audio::host(config.host.as_deref());
+        let pinned = config.device.as_ref().and_then(|name| {
+            host.input_devices()
+                .ok()?
+                .find(|d| d.name().ok().as_ref() == Some(name))
+        });
+        let device = pinned
+            .or_else(|| host.default_input_device())
+            .ok_or(Error::NoDevice)?;
+        let name = device.name().unwrap_or_default();
+
+        // only sizes the ring when the device picks the rate
+        let sample_rate = config.sample_rate.unwrap_or(48_000);
+        let ring_channels = config.channels.len();
+        let capacity = (config.ring_seconds * sample_rate as f32) as usize * ring_channels;
+        let (producer, consumer) = RingBuffer::new(capacity.max(ring_channels)).split();
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let shared_rate = Arc::new(AtomicU32::new(sample_rate));
+
+        let capture_state = Capture {
+            producer,
+            map: config.channels.clone(),
+            overruns: overruns.clone(),
+            sample_rate: shared_rate.clone(),
+        };
+        let device_channels = config.channels.iter().max().unwrap() + 1;
+        let mut builder = host
+            .new_input_stream(capture_state)
+            .capture(capture)
+            .device(device)
+            .channels(device_channels);
+        if let Some(rate) = config.sample_rate {
+            builder = builder.sample_rate(rate);
+        }
+        if let Some(frames) = config.frames_per_buffer {
+            builder = builder.frames_per_buffer(frames);
+        }
+        let stream = builder
+            .build()
+            .map_err(|e| Error::Build(format!("{:?}", e)))?;
+
+        Ok((
+            Input {
+                _stream: stream,
+                device: name,
+                overruns,
+            },
+            InputReader {
+                consumer,
+                channels: ring_channels,
+                sample_rate: shared_rate,
+            },
+        ))
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// input buffers dropped because the reader fell behind
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,231 @@
+//! JACK output under a named client with labelled ports.
+//!
+//! Needs the `jack` feature and a running server, the server is never
+//! started on demand. The engine renders at whatever rate the server runs.
+
+use crate::diagnostics::CallbackStats;
+use crate::render::Render;
+#[cfg(feature = "jack")]
+use dsp_common::denormal::DenormalGuard;
+use std::fmt;
+use std::sync::{atomic::AtomicUsize, mpsc, Arc, Mutex};
+
+#[cfg(feature = "jack")]
+use std::{sync::atomic::Ordering, time::Instant};
+
+/// frames rendered per `Render` call, longer periods take several calls
+#[cfg(feature = "jack")]
+const MAX_BLOCK: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JackConfig {
+    pub client_name: String,
+    /// one output port per label, channels past the labels are `out_<n>`
+    pub ports: &'static [&'static str],
+    /// connect to the physical playback ports once running
+    pub autoconnect: bool,
+}
+
+impl JackConfig {
+    pub fn new(client_name: &str, ports: &'static [&'static str]) -> Self {
+        Self {
+            client_name: client_name.to_string(),
+            ports,
+            autoconnect: true,
+        }
+    }
+
+    #[cfg(feature = "jack")]
+    fn port_names(&self, channels: usize) -> Vec<String> {
+        (0..channels.max(self.ports.len()))
+            .map(|i| match self.ports.get(i) {
+                Some(label) => label.to_string(),
+                None => format!("out_{}", i + 1),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `jack` feature
+    Unavailable,
+    Jack(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `jack` feature"),
+            Error::Jack(e) => write!(f, "jack: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "jack")]
+impl From<jack::Error> for Error {
+    fn from(e: jack::Error) -> Self {
+        Error::Jack(format!("{:?}", e))
+    }
+}
+
+pub type Command<M> = Box<dyn FnOnce(&mut M) + Send>;
+
+#[cfg(feature = "jack")]
+struct Process<M> {
+    engine: Arc<Mutex<M>>,
+    heartbeat: Arc<AtomicUsize>,
+    stats: Arc<CallbackStats>,
+    commands: mpsc::Receiver<Command<M>>,
+    ports: Vec<jack::Port<jack::AudioOut>>,
+    scratch: Vec<f32>,
+    sample_rate: u32,
+}
+
+#[cfg(feature = "jack")]
+impl<M: Render + Send> jack::ProcessHandler for Process<M> {
+    fn process(&mut self, _: &jack::Client, scope: &jack::ProcessScope) -> jack::Control {
+        let start = Instant::now();
+        let _denormals = DenormalGuard::new();
+        self.heartbeat.fetch_add(1, Ordering::Relaxed);
+        let channels = self.ports.len();
+        let frames = scope.n_frames() as usize;
+        self.render(scope, channels, frames);
+        self.stats.record(start, frames, self.sample_rate);
+        jack::Control::Continue
+    }
+}
+
+#[cfg(feature = "jack")]
+impl<M: Render + Send> Process<M> {
+    fn render(&mut self, scope: &jack::ProcessScope, channels: usize, frames: usize) {
+        // same rule as the device stream, never wait on the UI thread
+        let mut engine = match self.engine.try_lock() {
+            Ok(engine) => engine,
+            Err(_) => {
+                for port in self.ports.iter_mut() {
+                    port.as_mut_slice(scope).iter_mut().for_each(|s| *s = 0.0);
+                }
+                return;
+            }
+        };
+        while let Ok(command) = self.commands.try_recv() {
+            let engine = &mut *engine;
+            crate::render::contain(
+                &self.stats,
+                || command(engine),
+                || String::from("running a command"),
+            );
+        }
+
+        let mut start = 0;
+        while start < frames {
+            let len = (frames - start).min(MAX_BLOCK);
+            let block = &mut self.scratch[..len * channels];
+            block.iter_mut().for_each(|s| *s = 0.0);
+            crate::render::render_contained(
+                &mut *engine,
+                block,
+                channels,
+                self.sample_rate,
+                &self.stats,
+            );
+            for (channel, port) in self.ports.iter_mut().enumerate() {
+                let out = &mut port.as_mut_slice(scope)[start..start + len];
+                for (frame, sample) in out.iter_mut().enumerate() {
+                    *sample = block[frame * channels + channel];
+                }
+            }
+            start += len;
+        }
+    }
+}
+
+/// An active JACK client rendering the engine, deactivated on drop.
+pub struct JackOutput<M: 'static + Send> {
+    #[cfg(feature = "jack")]
+    _client: jack::AsyncClient<(), Process<M>>,
+    commands: mpsc::Sender<Command<M>>,
+    name: String,
+    sample_rate: u32,
+}
+
+impl<M: Render + 'static + Send> JackOutput<M> {
+    /// `engine` is shared the same way as with `audio::Supervisor`
+    pub fn start(
+        config: &JackConfig,
+        channels: usize,
+        engine: Arc<Mutex<M>>,
+        heartbeat: Arc<AtomicUsize>,
+        stats: Arc<CallbackStats>,
+    ) -> Result<Self, Error> {
+        #[cfg(feature = "jack")]
+        {
+            let (client, _status) =
+                jack::Client::new(&config.client_name, jack::ClientOptions::NO_START_SERVER)?;
+            let name = client.name().to_string();
+            let sample_rate = client.sample_rate() as u32;
+
+            let ports = config
+                .port_names(channels)
+                .iter()
+                .map(|label| client.register_port(label, jack::AudioOut))
+                .collect::<Result<Vec<_>, _>>()?;
+            let port_names = ports
+                .iter()
+                .map(|port| port.name())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let (commands, receiver) = mpsc::channel();
+            let process = Process {
+                engine,
+                heartbeat,
+                stats,
+                commands: receiver,
+                scratch: vec![0.0; MAX_BLOCK * ports.len()],
+                ports,
+                sample_rate,
+            };
+            let client = client.activate_async((), process)?;
+
+            if config.autoconnect {
+                let playback = client.as_client().ports(
+                    None,
+                    Some(jack::jack_sys::FLOAT_MONO_AUDIO),
+                    jack::PortFlags::IS_INPUT | jack::PortFlags::IS_PHYSICAL,
+                );
+                for (port, destination) in port_names.iter().zip(playback.iter()) {
+                    let _ = client.as_client().connect_ports_by_name(port, destination);
+                }
+            }
+
+            Ok(Self {
+                _client: client,
+                commands,
+                name,
+                sample_rate,
+            })
+        }
+        #[cfg(not(feature = "jack"))]
+        {
+            let _ = (config, channels, engine, heartbeat, stats);
+            Err(Error::Unavailable)
+        }
+    }
+
+    /// client name, the server may have made it unique
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// run `f` on the JACK thread against the engine
+    pub fn send(&self, f: Command<M>) {
+        let _ = self.commands.send(f);
+    }
+}
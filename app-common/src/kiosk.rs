@@ -0,0 +1,263 @@
+//! Unattended running, for installations.
+//!
+//! `--kiosk` or a `[kiosk]` table puts the main window fullscreen on the
+//! chosen monitor with the cursor hidden and escape no longer quitting. A
+//! watchdog thread watches the frames and the audio callbacks: the
+//! `Supervisor` restarts a stalled stream on its own, if either stays
+//! stalled past `stall_seconds` the whole app is started again with the
+//! same flags. Uptime since the first start is appended to `kiosk.log` in
+//! the app's data directory. Changes take effect on restart.
+
+use crate::capture::timestamp;
+use crate::cli;
+use crate::config::Config;
+use crate::diagnostics::CallbackStats;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process::{self, Command},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// how often the watchdog looks
+const CHECK: Duration = Duration::from_secs(1);
+/// restarts so far, passed on to the next start
+const RESTARTS: &str = "NANNOU_APPS_RESTARTS";
+/// seconds since the epoch of the first start, passed on likewise
+const SINCE: &str = "NANNOU_APPS_SINCE";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KioskConfig {
+    /// monitor name, wherever the window opened when `None`
+    pub monitor: Option<String>,
+    /// how long the frames or the audio can stop before the app restarts
+    pub stall_seconds: f32,
+    /// minutes between uptime lines in the log
+    pub log_minutes: f32,
+}
+
+impl Default for KioskConfig {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            stall_seconds: 10.0,
+            log_minutes: 15.0,
+        }
+    }
+}
+
+/// the config's `[kiosk]`, the defaults for `--kiosk` without one
+pub fn settings(config: &Config) -> Option<KioskConfig> {
+    match &config.kiosk {
+        Some(kiosk) => Some(kiosk.clone()),
+        None if cli::args().kiosk => Some(KioskConfig::default()),
+        None => None,
+    }
+}
+
+/// `Kiosk::start` on the `settings` there are, once the main window is
+/// built
+pub fn open(app: &App, name: &str, config: &Config, stats: Arc<CallbackStats>) -> Option<Kiosk> {
+    settings(config).map(|settings| Kiosk::start(app, name, &settings, stats))
+}
+
+/// `kiosk.log` in the platform data directory for `app`
+pub fn log_path(app: &str) -> PathBuf {
+    directories::ProjectDirs::from("", "", app)
+        .map(|dirs| dirs.data_dir().join("kiosk.log"))
+        .unwrap_or_else(|| PathBuf::from(format!("{}-kiosk.log", app)))
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Appends timestamped lines to the log, echoed to stderr.
+struct Log {
+    app: String,
+    path: PathBuf,
+}
+
+impl Log {
+    fn write(&self, message: &str) {
+        eprintln!("{}: {}", self.app, message);
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path);
+        if let Ok(mut file) = file {
+            let _ = writeln!(file, "{} {}", timestamp(), message);
+        }
+    }
+}
+
+/// The main window set up for unattended running and the watchdog over
+/// it, stopped on drop.
+pub struct Kiosk {
+    frames: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Kiosk {
+    /// call once the main window is built, `stats` is the stream's
+    pub fn start(app: &App, name: &str, config: &KioskConfig, stats: Arc<CallbackStats>) -> Self {
+        let log = Log {
+            app: name.to_string(),
+            path: log_path(name),
+        };
+        let restarts: usize = env::var(RESTARTS)
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0);
+        let since = env::var(SINCE)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(epoch_seconds);
+        log.write(&format!("kiosk started, {} restarts", restarts));
+
+        app.set_exit_on_escape(false);
+        let window = app.main_window();
+        if let Some(name) = &config.monitor {
+            match app
+                .available_monitors()
+                .into_iter()
+                .find(|m| m.name().as_deref() == Some(name.as_str()))
+            {
+                Some(monitor) => {
+                    let position = monitor.position();
+                    window.set_outer_position_pixels(position.x, position.y);
+                }
+                None => log.write(&format!("no monitor named {}, staying put", name)),
+            }
+        }
+        window.set_fullscreen(true);
+        window.set_cursor_visible(false);
+
+        let frames = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let watchdog = Watchdog {
+            frames: frames.clone(),
+            stats,
+            stop: stop.clone(),
+            stall: Duration::from_secs_f32(config.stall_seconds.max(1.0)),
+            log_every: Duration::from_secs_f32(config.log_minutes.max(1.0) * 60.0),
+            restarts,
+            since,
+            log,
+        };
+        let spawned = thread::Builder::new()
+            .name("watchdog".into())
+            .spawn(move || watchdog.run());
+        if let Err(e) = spawned {
+            eprintln!("{}: no watchdog: {}", name, e);
+        }
+        Self { frames, stop }
+    }
+
+    /// called every frame, proof the window is still drawing
+    pub fn update(&self) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Kiosk {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Watchdog {
+    frames: Arc<AtomicUsize>,
+    stats: Arc<CallbackStats>,
+    stop: Arc<AtomicBool>,
+    stall: Duration,
+    log_every: Duration,
+    restarts: usize,
+    since: u64,
+    log: Log,
+}
+
+impl Watchdog {
+    fn run(self) {
+        // each count and when it last moved
+        let mut frames = (self.frames.load(Ordering::Relaxed), Instant::now());
+        let mut callbacks = (self.stats.callbacks(), Instant::now());
+        let mut logged = Instant::now();
+        while !self.stop.load(Ordering::Relaxed) {
+            thread::sleep(CHECK);
+            let now = (self.frames.load(Ordering::Relaxed), Instant::now());
+            if now.0 != frames.0 {
+                frames = now;
+            }
+            let now = (self.stats.callbacks(), Instant::now());
+            if now.0 != callbacks.0 {
+                callbacks = now;
+            }
+            if frames.1.elapsed() > self.stall {
+                self.restart("frames");
+            }
+            // a stream that never started is the error screen's to show,
+            // restarting wouldn't find a device either
+            if callbacks.0 > 0 && callbacks.1.elapsed() > self.stall {
+                self.restart("audio callbacks");
+            }
+            if logged.elapsed() > self.log_every {
+                logged = Instant::now();
+                self.log.write(&format!(
+                    "up {}, {} restarts, {} audio overloads",
+                    self.uptime(),
+                    self.restarts,
+                    self.stats.overloads()
+                ));
+            }
+        }
+    }
+
+    /// since the first start, as `1d 2h 3m`
+    fn uptime(&self) -> String {
+        let minutes = epoch_seconds().saturating_sub(self.since) / 60;
+        format!(
+            "{}d {}h {}m",
+            minutes / (24 * 60),
+            minutes / 60 % 24,
+            minutes % 60
+        )
+    }
+
+    /// starts the app again with the same flags and ends this one, which
+    /// may well be stuck
+    fn restart(&self, stalled: &str) -> ! {
+        self.log.write(&format!(
+            "{} stalled for {:.0}s after {}, restarting",
+            stalled,
+            self.stall.as_secs_f32(),
+            self.uptime()
+        ));
+        let spawned = env::current_exe().and_then(|exe| {
+            Command::new(exe)
+                .args(env::args_os().skip(1))
+                .env(RESTARTS, (self.restarts + 1).to_string())
+                .env(SINCE, self.since.to_string())
+                .spawn()
+        });
+        if let Err(e) = spawned {
+            self.log.write(&format!("cannot restart: {}", e));
+        }
+        process::exit(1);
+    }
+}
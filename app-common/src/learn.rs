@@ -0,0 +1,250 @@
+//! MIDI-learn for parameter sliders.
+//!
+//! Right click, or alt click, a slider from `param::sliders` to arm it, the
+//! next CC from the current device is bound to it. Bindings are kept per
+//! device in `midi.toml` next to the app's config, so each controller keeps
+//! its own layout.
+
+use crate::midi::MidiMessage;
+use crate::param::{self, CcBinding, Params};
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use nannou::ui::input::{keyboard::ModifierKey, MouseButton};
+use nannou::ui::prelude::{widget, UiCell};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// toggles the bindings editor
+pub const HOTKEY: Key = Key::M;
+
+/// `[[devices."<port name>"]]` tables in `midi.toml`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiMap {
+    pub devices: BTreeMap<String, Vec<CcBinding>>,
+}
+
+impl MidiMap {
+    /// `midi.toml` next to the app's config
+    pub fn path(config_path: &Path) -> PathBuf {
+        config_path.with_file_name("midi.toml")
+    }
+
+    /// never fails, a missing or malformed file gives an empty map
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("ignoring malformed midi map {}: {}", path.display(), e);
+                MidiMap::default()
+            }),
+            Err(_) => MidiMap::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn bindings(&self, device: &str) -> &[CcBinding] {
+        self.devices.get(device).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// the controller drives `param` only, and `param` only that controller
+    pub fn bind(&mut self, device: &str, channel: u8, controller: u8, param: &str) {
+        let bindings = self.devices.entry(device.to_string()).or_default();
+        bindings.retain(|b| {
+            b.param != param && !(b.controller == controller && b.channel == Some(channel))
+        });
+        bindings.push(CcBinding {
+            channel: Some(channel),
+            controller,
+            param: param.into(),
+        });
+    }
+
+    pub fn unbind(&mut self, device: &str, param: &str) {
+        if let Some(bindings) = self.devices.get_mut(device) {
+            bindings.retain(|b| b.param != param);
+            if bindings.is_empty() {
+                self.devices.remove(device);
+            }
+        }
+    }
+}
+
+/// Arms sliders, learns CCs and shows the bindings of the current device.
+///
+/// In the editor up/down pick a parameter, return arms it and backspace
+/// clears its binding.
+pub struct MidiLearn {
+    map: MidiMap,
+    path: PathBuf,
+    armed: Option<usize>,
+    editor: bool,
+    selected: usize,
+}
+
+impl MidiLearn {
+    pub fn load(config_path: &Path) -> Self {
+        let path = MidiMap::path(config_path);
+        Self {
+            map: MidiMap::load(&path),
+            path,
+            armed: None,
+            editor: false,
+            selected: 0,
+        }
+    }
+
+    pub fn map(&self) -> &MidiMap {
+        &self.map
+    }
+
+    /// the parameter waiting for a CC
+    pub fn armed(&self) -> Option<usize> {
+        self.armed
+    }
+
+    pub fn arm(&mut self, index: usize) {
+        self.armed = Some(index);
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = None;
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.map.save(&self.path) {
+            eprintln!("cannot save {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// called after `param::sliders` with the same ids
+    pub fn watch(&mut self, ids: &widget::id::List, ui: &UiCell) {
+        for (i, &id) in ids.iter().enumerate() {
+            let armed = ui
+                .widget_input(id)
+                .clicks()
+                .any(|c| c.button == MouseButton::Right || c.modifiers.contains(ModifierKey::ALT));
+            if armed {
+                self.armed = Some(i);
+            }
+        }
+    }
+
+    /// binds the armed parameter or applies the device's bindings, true if
+    /// a parameter moved
+    pub fn midi(&mut self, device: &str, message: &MidiMessage, params: &Params) -> bool {
+        if let (
+            Some(i),
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                ..
+            },
+        ) = (self.armed, *message)
+        {
+            if let Some(spec) = params.specs().get(i) {
+                self.map.bind(device, channel, controller, spec.name);
+                self.save();
+            }
+            self.armed = None;
+        }
+        param::apply_cc(self.map.bindings(device), params, message)
+    }
+
+    /// true if the key was for the editor
+    pub fn key_pressed(&mut self, key: Key, device: &str, params: &Params) -> bool {
+        if key == HOTKEY {
+            self.editor = !self.editor;
+            return true;
+        }
+        if key == Key::Escape && self.armed.is_some() {
+            self.armed = None;
+            return true;
+        }
+        if !self.editor || params.is_empty() {
+            return false;
+        }
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(params.len() - 1),
+            Key::Return => self.armed = Some(self.selected),
+            Key::Back | Key::Delete => {
+                self.map.unbind(device, params.specs()[self.selected].name);
+                self.save();
+            }
+            Key::Escape => self.editor = false,
+            _ => return false,
+        }
+        true
+    }
+
+    /// the learn prompt while armed and the editor when open
+    pub fn draw(&self, draw: &Draw, rect: Rect, palette: &Palette, params: &Params, device: &str) {
+        const LINE: f32 = 22.0;
+
+        let text = theme::color(palette.line);
+        let accent = theme::color(palette.accent(0));
+        if let Some(spec) = self.armed.and_then(|i| params.specs().get(i)) {
+            draw.text(&format!("{}: move a controller on {}", spec.name, device))
+                .x_y(0.0, rect.bottom() + LINE)
+                .w_h(rect.w(), LINE)
+                .font_size(14)
+                .color(accent);
+        }
+        if !self.editor {
+            return;
+        }
+
+        let [r, g, b] = palette.background;
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgba(r, g, b, 0.85));
+        let mut y = rect.top() - 2.0 * LINE;
+        let mut line = |message: &str, color: Rgb, size: u32| {
+            draw.text(message)
+                .x_y(0.0, y)
+                .w_h(rect.w() - 4.0 * LINE, LINE)
+                .left_justify()
+                .font_size(size)
+                .color(color);
+            y -= LINE;
+        };
+
+        line(&format!("midi bindings for {}", device), text, 14);
+        line("", text, 14);
+        let bindings = self.map.bindings(device);
+        for (i, spec) in params.specs().iter().enumerate() {
+            let binding = match bindings.iter().find(|b| b.param == spec.name) {
+                Some(CcBinding {
+                    channel: Some(channel),
+                    controller,
+                    ..
+                }) => format!("cc {} on channel {}", controller, channel + 1),
+                Some(binding) => format!("cc {}", binding.controller),
+                None if self.armed == Some(i) => "move a controller...".to_string(),
+                None => "-".to_string(),
+            };
+            let marker = if i == self.selected { ">" } else { " " };
+            let color = if i == self.selected { accent } else { text };
+            line(&format!("{} {}: {}", marker, spec.name, binding), color, 14);
+        }
+        line("", text, 14);
+        line(
+            "right click a slider or pick one and press return to learn, backspace clears, m closes",
+            text,
+            12,
+        );
+    }
+}
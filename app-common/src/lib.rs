@@ -0,0 +1,106 @@
+/// counts allocations for `render`'s check, see `dsp_common::allocation`
+#[cfg(feature = "alloc-check")]
+#[global_allocator]
+static ALLOCATOR: dsp_common::allocation::Checked = dsp_common::allocation::Checked;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod assets;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod automation;
+pub mod bus;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod camera;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod capture;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cv;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod diagnostics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dmx;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gamepad;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod input;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod jack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod kiosk;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod learn;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod link;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod macros;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mirror;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ndi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod osc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod oscquery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod param;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod performance;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod preset;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recorder;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod remote;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod render;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod scope;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod screenshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serial;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod session;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod setup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod share;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shell;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod speakers;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod spectrum;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod spout;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod startup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod syphon;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tasks;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod theme;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timecode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod touchosc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod widget;
@@ -0,0 +1,195 @@
+use crate::theme::{Palette, Themed};
+use nannou::ui::prelude::*;
+
+#[cfg(feature = "link")]
+use rusty_link::{AblLink, SessionState};
+#[cfg(feature = "link")]
+use std::sync::Arc;
+
+/// Ableton Link session, app thread side.
+///
+/// Without the `link` feature this is inert: never enabled, no peers,
+/// and clocks report no beat so callers fall back to their own timing.
+pub struct Link {
+    #[cfg(feature = "link")]
+    link: Arc<AblLink>,
+    #[cfg(feature = "link")]
+    state: SessionState,
+    quantum: f64,
+}
+
+impl Link {
+    pub fn new(bpm: f64, quantum: f64) -> Self {
+        #[cfg(not(feature = "link"))]
+        let _ = bpm;
+        Self {
+            #[cfg(feature = "link")]
+            link: Arc::new(AblLink::new(bpm)),
+            #[cfg(feature = "link")]
+            state: SessionState::new(),
+            quantum,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        cfg!(feature = "link")
+    }
+
+    pub fn enable(&mut self, enabled: bool) {
+        #[cfg(feature = "link")]
+        self.link.enable(enabled);
+        #[cfg(not(feature = "link"))]
+        let _ = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "link")]
+        return self.link.is_enabled();
+        #[cfg(not(feature = "link"))]
+        false
+    }
+
+    pub fn peers(&self) -> usize {
+        #[cfg(feature = "link")]
+        return self.link.num_peers() as usize;
+        #[cfg(not(feature = "link"))]
+        0
+    }
+
+    pub fn quantum(&self) -> f64 {
+        self.quantum
+    }
+
+    /// session beat right now, `None` while disabled
+    pub fn beat(&mut self) -> Option<f64> {
+        #[cfg(feature = "link")]
+        {
+            if !self.link.is_enabled() {
+                return None;
+            }
+            self.link.capture_app_session_state(&mut self.state);
+            Some(
+                self.state
+                    .beat_at_time(self.link.clock_micros(), self.quantum),
+            )
+        }
+        #[cfg(not(feature = "link"))]
+        None
+    }
+
+    pub fn tempo(&mut self) -> Option<f64> {
+        #[cfg(feature = "link")]
+        {
+            self.link.capture_app_session_state(&mut self.state);
+            Some(self.state.tempo())
+        }
+        #[cfg(not(feature = "link"))]
+        None
+    }
+
+    pub fn set_tempo(&mut self, bpm: f64) {
+        #[cfg(feature = "link")]
+        {
+            self.link.capture_app_session_state(&mut self.state);
+            self.state.set_tempo(bpm, self.link.clock_micros());
+            self.link.commit_app_session_state(&self.state);
+        }
+        #[cfg(not(feature = "link"))]
+        let _ = bpm;
+    }
+
+    /// handle for reading the session from the audio thread
+    pub fn clock(&self) -> LinkClock {
+        LinkClock {
+            #[cfg(feature = "link")]
+            link: self.link.clone(),
+            #[cfg(feature = "link")]
+            state: SessionState::new(),
+            quantum: self.quantum,
+        }
+    }
+
+    /// enable toggle with the peer count, laid out below the previous widget
+    pub fn panel(&mut self, toggle: widget::Id, palette: &Palette, ui: &mut UiCell) {
+        if !self.is_available() {
+            return;
+        }
+        let enabled = self.is_enabled();
+        let label = if enabled {
+            format!("link: {} peers", self.peers())
+        } else {
+            String::from("link: off")
+        };
+        for value in widget::Toggle::new(enabled)
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(&label)
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(toggle, ui)
+        {
+            self.enable(value);
+        }
+    }
+}
+
+/// Audio thread side, allocation free once constructed.
+pub struct LinkClock {
+    #[cfg(feature = "link")]
+    link: Arc<AblLink>,
+    #[cfg(feature = "link")]
+    state: SessionState,
+    quantum: f64,
+}
+
+impl LinkClock {
+    /// session beat at the moment this buffer will be heard, `None` while disabled
+    pub fn beat(&mut self, latency_micros: i64) -> Option<f64> {
+        #[cfg(feature = "link")]
+        {
+            if !self.link.is_enabled() {
+                return None;
+            }
+            self.link.capture_audio_session_state(&mut self.state);
+            Some(
+                self.state
+                    .beat_at_time(self.link.clock_micros() + latency_micros, self.quantum),
+            )
+        }
+        #[cfg(not(feature = "link"))]
+        {
+            let _ = latency_micros;
+            None
+        }
+    }
+
+    pub fn quantum(&self) -> f64 {
+        self.quantum
+    }
+}
+
+/// Reports when a beat position crosses a grid line, e.g. every bar.
+#[derive(Clone, Copy, Debug)]
+pub struct BeatGrid {
+    every: f64,
+    last: Option<f64>,
+}
+
+impl BeatGrid {
+    pub fn new(every: f64) -> Self {
+        Self { every, last: None }
+    }
+
+    pub fn crossed(&mut self, beat: f64) -> bool {
+        let line = (beat / self.every).floor();
+        let crossed = matches!(self.last, Some(last) if line > last);
+        self.last = Some(line);
+        crossed
+    }
+
+    /// forget the last position, e.g. when the clock is disabled
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
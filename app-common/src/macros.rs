@@ -0,0 +1,90 @@
+//! One knob moving several parameters at once.
+//!
+//! Each `Macro` sweeps its targets between their own `from` and `to`
+//! values as it turns from 0 to 1, bent by a curve per target, so one hand
+//! can raise the density while the slope steepens and the release shortens.
+//! Targets only follow a macro while it turns, in between they can be set
+//! on their own. Macros are edited as `[[macros]]` tables in the config or
+//! a parameter preset, each with its `[[macros.targets]]`.
+
+use crate::theme::Palette;
+use crate::widget::Knob;
+use dsp_common::env::bend;
+use dsp_common::param::Params;
+use nannou::ui::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// pixels across each knob, its label underneath included
+const KNOB_SIZE: f64 = 60.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MacroTarget {
+    pub param: String,
+    /// the parameter's value with the macro at 0, in its own units
+    pub from: f32,
+    /// and at 1, below `from` to turn the parameter down
+    pub to: f32,
+    /// see `env::bend`, straight at 0
+    #[serde(default)]
+    pub curve: f32,
+}
+
+impl MacroTarget {
+    /// the parameter's value with the macro at `value`
+    pub fn at(&self, value: f32) -> f32 {
+        self.from + (self.to - self.from) * bend(value.clamp(0.0, 1.0), self.curve)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    /// where the knob was left, in [0, 1]
+    #[serde(default)]
+    pub value: f32,
+    #[serde(default)]
+    pub targets: Vec<MacroTarget>,
+}
+
+impl Macro {
+    /// turns the knob to `value` and its targets with it, unknown
+    /// parameters are skipped
+    pub fn set(&mut self, value: f32, params: &Params) {
+        self.value = value.clamp(0.0, 1.0);
+        for target in self.targets.iter() {
+            params.set_by_name(&target.param, target.at(self.value));
+        }
+    }
+}
+
+/// A knob per macro in a row below the previously set widget. True if one
+/// was turned, for the app to store the new positions.
+pub fn knobs(
+    macros: &mut [Macro],
+    params: &Params,
+    ids: &mut widget::id::List,
+    palette: &Palette,
+    ui: &mut UiCell,
+) -> bool {
+    if ids.len() != macros.len() {
+        ids.resize(macros.len(), &mut ui.widget_id_generator());
+    }
+
+    let mut turned = false;
+    for (i, m) in macros.iter_mut().enumerate() {
+        let knob = Knob::new(m.value, 0.0, 1.0)
+            .label(&m.name)
+            .with_style(palette.control_style())
+            .w_h(KNOB_SIZE, KNOB_SIZE);
+        let knob = if i == 0 {
+            knob.down(20.0)
+        } else {
+            knob.right(10.0)
+        };
+        if let Some(value) = knob.set(ids[i], ui) {
+            m.set(value, params);
+            turned = true;
+        }
+    }
+    turned
+}
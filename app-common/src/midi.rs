@@ -0,0 +1,442 @@
+use dsp_common::tuning;
+use ringbuf::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    PitchBend {
+        channel: u8,
+        value: i16,
+    },
+}
+
+impl MidiMessage {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        let channel = status & 0x0F;
+        let data = |i: usize| bytes.get(i).map(|b| b & 0x7F);
+        Some(match status & 0xF0 {
+            // note on with velocity 0 is a note off by convention
+            0x90 if data(2)? > 0 => MidiMessage::NoteOn {
+                channel,
+                note: data(1)?,
+                velocity: data(2)?,
+            },
+            0x80 | 0x90 => MidiMessage::NoteOff {
+                channel,
+                note: data(1)?,
+                velocity: data(2)?,
+            },
+            0xB0 => MidiMessage::ControlChange {
+                channel,
+                controller: data(1)?,
+                value: data(2)?,
+            },
+            0xE0 => MidiMessage::PitchBend {
+                channel,
+                value: ((data(2)? as i16) << 7 | data(1)? as i16) - 8192,
+            },
+            _ => return None,
+        })
+    }
+
+    /// the wire format, the inverse of `parse`
+    pub fn to_bytes(&self) -> [u8; 3] {
+        match *self {
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => [0x90 | channel & 0x0F, note & 0x7F, velocity & 0x7F],
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => [0x80 | channel & 0x0F, note & 0x7F, velocity & 0x7F],
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => [0xB0 | channel & 0x0F, controller & 0x7F, value & 0x7F],
+            MidiMessage::PitchBend { channel, value } => {
+                let value = (value.clamp(-8192, 8191) + 8192) as u16;
+                [
+                    0xE0 | channel & 0x0F,
+                    (value & 0x7F) as u8,
+                    (value >> 7) as u8,
+                ]
+            }
+        }
+    }
+}
+
+/// the nearest note to `freq`, `None` outside the midi range
+pub fn note(freq: f32) -> Option<u8> {
+    let note = tuning::freq_to_midi(freq).round();
+    if (0.0..=127.0).contains(&note) {
+        Some(note as u8)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MidiEvent {
+    /// microseconds, relative to an arbitrary point set by the backend
+    pub timestamp: u64,
+    pub message: MidiMessage,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Init(midir::InitError),
+    /// nothing to send to
+    NoPorts,
+    Connect(String),
+    NoSuchPort(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Init(e) => write!(f, "midi init error: {}", e),
+            Error::NoPorts => write!(f, "no midi output ports"),
+            Error::Connect(e) => write!(f, "midi connect error: {}", e),
+            Error::NoSuchPort(name) => write!(f, "no midi port named {}", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<midir::InitError> for Error {
+    fn from(e: midir::InitError) -> Self {
+        Error::Init(e)
+    }
+}
+
+/// Engine end of the midi queue, `Send` so it can live on the audio thread.
+pub struct MidiReceiver {
+    consumer: Consumer<MidiEvent>,
+}
+
+impl MidiReceiver {
+    pub fn pop(&mut self) -> Option<MidiEvent> {
+        self.consumer.pop()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = MidiEvent> + '_ {
+        std::iter::from_fn(move || self.consumer.pop())
+    }
+}
+
+type SharedProducer = Arc<Mutex<Producer<MidiEvent>>>;
+
+/// UI end, owns the port connection and follows the selected device
+/// through unplug/replug as long as `poll` is called regularly.
+pub struct MidiInput {
+    client_name: String,
+    scanner: midir::MidiInput,
+    ports: Vec<String>,
+    selected: Option<String>,
+    connection: Option<midir::MidiInputConnection<()>>,
+    // only ever locked by the midi backend thread, the engine side is lock-free
+    producer: SharedProducer,
+    last_scan: Instant,
+}
+
+impl MidiInput {
+    pub fn new(client_name: &str, capacity: usize) -> Result<(Self, MidiReceiver), Error> {
+        let (producer, consumer) = RingBuffer::new(capacity).split();
+        let scanner = midir::MidiInput::new(&format!("{} scanner", client_name))?;
+        let mut input = Self {
+            client_name: client_name.into(),
+            scanner,
+            ports: Vec::new(),
+            selected: None,
+            connection: None,
+            producer: Arc::new(Mutex::new(producer)),
+            last_scan: Instant::now(),
+        };
+        input.scan();
+        Ok((input, MidiReceiver { consumer }))
+    }
+
+    pub fn ports(&self) -> &[String] {
+        &self.ports
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// remember `port` and connect to it, reconnects happen in `poll`
+    pub fn select(&mut self, port: &str) -> Result<(), Error> {
+        self.disconnect();
+        self.selected = Some(port.into());
+        self.connect(port)
+    }
+
+    pub fn deselect(&mut self) {
+        self.disconnect();
+        self.selected = None;
+    }
+
+    /// called at frame rate, rescans ports and handles hot-plugging
+    pub fn poll(&mut self) {
+        if self.last_scan.elapsed() < SCAN_INTERVAL {
+            return;
+        }
+        self.scan();
+
+        let selected = match self.selected.clone() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let present = self.ports.contains(&selected);
+        if self.is_connected() && !present {
+            self.disconnect();
+        } else if !self.is_connected() && present {
+            let _ = self.connect(&selected);
+        }
+    }
+
+    fn scan(&mut self) {
+        self.last_scan = Instant::now();
+        self.ports = self
+            .scanner
+            .ports()
+            .iter()
+            .filter_map(|port| self.scanner.port_name(port).ok())
+            .collect();
+    }
+
+    fn connect(&mut self, name: &str) -> Result<(), Error> {
+        let input = midir::MidiInput::new(&self.client_name)?;
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).ok().as_deref() == Some(name))
+            .ok_or_else(|| Error::NoSuchPort(name.into()))?;
+        let producer = self.producer.clone();
+
+        let connection = input
+            .connect(
+                &port,
+                &self.client_name,
+                move |timestamp, bytes, _| {
+                    if let (Some(message), Ok(mut producer)) =
+                        (MidiMessage::parse(bytes), producer.lock())
+                    {
+                        let _ = producer.push(MidiEvent { timestamp, message });
+                    }
+                },
+                (),
+            )
+            .map_err(|e| Error::Connect(e.to_string()))?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+    }
+}
+
+/// Where the generated notes go, off when absent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiOutConfig {
+    /// port name, the first port when absent
+    pub device: Option<String>,
+    /// 0-based, 9 is drums on most synths
+    pub channel: u8,
+    pub velocity: u8,
+}
+
+impl Default for MidiOutConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            channel: 0,
+            velocity: 100,
+        }
+    }
+}
+
+/// Notes and controllers for external synths, following the port through
+/// unplug/replug like `MidiInput`. Held notes are released on drop so
+/// nothing drones on after the app is gone.
+pub struct MidiOutput {
+    client_name: String,
+    config: MidiOutConfig,
+    port: String,
+    connection: Option<midir::MidiOutputConnection>,
+    held: Vec<u8>,
+    /// last value sent per controller, repeats are skipped
+    controllers: [Option<u8>; 128],
+    last_scan: Instant,
+}
+
+impl MidiOutput {
+    pub fn open(client_name: &str, config: MidiOutConfig) -> Result<Self, Error> {
+        let port = match &config.device {
+            Some(device) => device.clone(),
+            None => {
+                let scanner = midir::MidiOutput::new(&format!("{} scanner", client_name))?;
+                let ports = scanner.ports();
+                let first = ports.first().ok_or(Error::NoPorts)?;
+                scanner
+                    .port_name(first)
+                    .map_err(|e| Error::Connect(e.to_string()))?
+            }
+        };
+        let mut output = Self {
+            client_name: client_name.into(),
+            config,
+            port,
+            connection: None,
+            held: Vec::new(),
+            controllers: [None; 128],
+            last_scan: Instant::now(),
+        };
+        output.connect()?;
+        Ok(output)
+    }
+
+    pub fn config(&self) -> &MidiOutConfig {
+        &self.config
+    }
+
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// called at frame rate, reconnects once the port is back
+    pub fn poll(&mut self) {
+        if self.is_connected() || self.last_scan.elapsed() < SCAN_INTERVAL {
+            return;
+        }
+        self.last_scan = Instant::now();
+        let _ = self.connect();
+    }
+
+    /// dropped while disconnected, a failed send disconnects
+    pub fn send(&mut self, message: MidiMessage) {
+        if let Some(connection) = &mut self.connection {
+            if connection.send(&message.to_bytes()).is_err() {
+                self.connection = None;
+                self.held.clear();
+            }
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8) {
+        if self.held.contains(&note) {
+            self.note_off(note);
+        }
+        self.send(MidiMessage::NoteOn {
+            channel: self.config.channel,
+            note,
+            velocity: self.config.velocity.max(1),
+        });
+        if self.is_connected() {
+            self.held.push(note);
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        self.held.retain(|&held| held != note);
+        self.send(MidiMessage::NoteOff {
+            channel: self.config.channel,
+            note,
+            velocity: 0,
+        });
+    }
+
+    /// releases what is held, then strikes `notes`
+    pub fn play(&mut self, notes: &[u8]) {
+        self.release_all();
+        for &note in notes {
+            self.note_on(note);
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        for note in std::mem::take(&mut self.held) {
+            self.note_off(note);
+        }
+    }
+
+    /// `value` in [0, 1]
+    pub fn control_change(&mut self, controller: u8, value: f32) {
+        let controller = controller & 0x7F;
+        let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+        if self.controllers[controller as usize] == Some(value) {
+            return;
+        }
+        self.send(MidiMessage::ControlChange {
+            channel: self.config.channel,
+            controller,
+            value,
+        });
+        if self.is_connected() {
+            self.controllers[controller as usize] = Some(value);
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), Error> {
+        let output = midir::MidiOutput::new(&self.client_name)?;
+        let port = output
+            .ports()
+            .into_iter()
+            .find(|port| output.port_name(port).ok().as_deref() == Some(&self.port))
+            .ok_or_else(|| Error::NoSuchPort(self.port.clone()))?;
+        let connection = output
+            .connect(&port, &self.client_name)
+            .map_err(|e| Error::Connect(e.to_string()))?;
+        self.connection = Some(connection);
+        // whatever the synth had from us is gone or stale
+        self.controllers = [None; 128];
+        Ok(())
+    }
+}
+
+impl Drop for MidiOutput {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
@@ -0,0 +1,208 @@
+//! Leader/follower mirroring between instances of one app over UDP, for
+//! multi-screen and multi-room installations of the same piece.
+//!
+//! The leader broadcasts its whole `State` whenever it changes and at a
+//! steady rate otherwise, so followers that start late or drop packets
+//! catch up on the next one. Followers run no randomness of their own,
+//! they replay the leader's from its seed and step count.
+
+use crate::param::ParamSnapshot;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// a state with every parameter fits well under this
+const MAX_PACKET: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Role {
+    Leader,
+    Follower,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    pub role: Role,
+    pub port: u16,
+    /// where the leader sends, the local broadcast address when `None`
+    pub target: Option<IpAddr>,
+    /// packets per second when nothing changes
+    pub rate: f32,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            role: Role::Follower,
+            port: 7400,
+            target: None,
+            rate: 4.0,
+        }
+    }
+}
+
+/// What followers mirror.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    /// the leader's transport in seconds, as of the packet, followers add
+    /// `Mirror::silence` for the current one
+    pub position: f64,
+    /// where the mirrored randomness starts
+    pub seed: u64,
+    /// random steps taken from `seed` so far
+    pub steps: u64,
+    pub params: ParamSnapshot,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Packet {
+    app: String,
+    /// tells a restarted leader from a reordered packet
+    leader: u64,
+    sequence: u64,
+    state: State,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// only a leader publishes
+    NotLeader,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "mirror io error: {}", e),
+            Error::NotLeader => write!(f, "only the leader publishes"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// One end of the mirror, called from `update`.
+pub struct Mirror {
+    config: MirrorConfig,
+    app_name: String,
+    socket: UdpSocket,
+    /// this leader's id, or the followed one's
+    leader: u64,
+    sequence: u64,
+    sent: Option<(Instant, State)>,
+    /// when the last packet from the leader arrived
+    heard: Option<Instant>,
+}
+
+impl Mirror {
+    pub fn open(app_name: &str, config: MirrorConfig) -> Result<Self, Error> {
+        let socket = match config.role {
+            Role::Leader => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.set_broadcast(true)?;
+                socket
+            }
+            Role::Follower => UdpSocket::bind(("0.0.0.0", config.port))?,
+        };
+        socket.set_nonblocking(true)?;
+        let leader = match config.role {
+            Role::Leader => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_nanos() as u64)
+                .unwrap_or(1),
+            Role::Follower => 0,
+        };
+        Ok(Self {
+            config,
+            app_name: app_name.to_string(),
+            socket,
+            leader,
+            sequence: 0,
+            sent: None,
+            heard: None,
+        })
+    }
+
+    pub fn config(&self) -> &MirrorConfig {
+        &self.config
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.config.role == Role::Leader
+    }
+
+    /// sends `state` if it changed or a packet is due anyway
+    pub fn publish(&mut self, state: &State) -> Result<(), Error> {
+        if !self.is_leader() {
+            return Err(Error::NotLeader);
+        }
+        let now = Instant::now();
+        let interval = Duration::from_secs_f32(1.0 / self.config.rate.max(0.1));
+        if let Some((sent, last)) = &self.sent {
+            // the position moves every frame, it alone doesn't make a packet due
+            let changed =
+                last.seed != state.seed || last.steps != state.steps || last.params != state.params;
+            if !changed && now.duration_since(*sent) < interval {
+                return Ok(());
+            }
+        }
+        self.sequence += 1;
+        let packet = Packet {
+            app: self.app_name.clone(),
+            leader: self.leader,
+            sequence: self.sequence,
+            state: state.clone(),
+        };
+        let bytes = serde_json::to_vec(&packet).map_err(io::Error::from)?;
+        let target = self
+            .config
+            .target
+            .unwrap_or(IpAddr::V4(Ipv4Addr::BROADCAST));
+        self.sent = Some((now, state.clone()));
+        match self
+            .socket
+            .send_to(&bytes, SocketAddr::new(target, self.config.port))
+        {
+            Err(e) if e.kind() != io::ErrorKind::WouldBlock => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// the leader's newest state since the last call, for followers
+    pub fn poll(&mut self) -> Option<State> {
+        let mut newest = None;
+        let mut buffer = [0; MAX_PACKET];
+        while let Ok(len) = self.socket.recv(&mut buffer) {
+            let packet: Packet = match serde_json::from_slice(&buffer[..len]) {
+                Ok(packet) => packet,
+                Err(_) => continue,
+            };
+            if packet.app != self.app_name {
+                continue;
+            }
+            // a new leader is followed from its first packet on
+            if packet.leader != self.leader || packet.sequence > self.sequence {
+                self.leader = packet.leader;
+                self.sequence = packet.sequence;
+                self.heard = Some(Instant::now());
+                newest = Some(packet.state);
+            }
+        }
+        newest
+    }
+
+    /// time since the leader was last heard from, for followers
+    pub fn silence(&self) -> Option<Duration> {
+        self.heard.map(|heard| heard.elapsed())
+    }
+}
@@ -0,0 +1,221 @@
+//! NDI video output, a `share::Server` streaming frames over the network.
+//!
+//! Needs the `ndi` feature and the NDI runtime installed on the machine, the
+//! runtime library is loaded when the sender is created rather than linked.
+
+use crate::share::{Server, SharedFrame};
+use std::fmt;
+
+#[cfg(feature = "ndi")]
+use std::{
+    ffi::{c_void, CString},
+    os::raw::{c_char, c_float, c_int},
+    ptr,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `ndi` feature
+    Unavailable,
+    /// the runtime library is missing or incomplete
+    Runtime(String),
+    /// the runtime refused to create the sender
+    Sender,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `ndi` feature"),
+            Error::Runtime(e) => write!(f, "ndi runtime: {}", e),
+            Error::Sender => write!(f, "ndi sender could not be created"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "ndi")]
+#[repr(C)]
+struct SendCreate {
+    name: *const c_char,
+    groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+#[cfg(feature = "ndi")]
+#[repr(C)]
+struct VideoFrame {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    aspect_ratio: c_float,
+    frame_format: c_int,
+    timecode: i64,
+    data: *const u8,
+    line_stride: c_int,
+    metadata: *const c_char,
+    timestamp: i64,
+}
+
+#[cfg(feature = "ndi")]
+const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+#[cfg(feature = "ndi")]
+const PROGRESSIVE: c_int = 1;
+/// let the runtime stamp frames as they are sent
+#[cfg(feature = "ndi")]
+const SYNTHESIZE_TIMECODE: i64 = i64::MAX;
+
+#[cfg(feature = "ndi")]
+struct Runtime {
+    initialize: unsafe extern "C" fn() -> bool,
+    destroy: unsafe extern "C" fn(),
+    send_create: unsafe extern "C" fn(*const SendCreate) -> *mut c_void,
+    send_destroy: unsafe extern "C" fn(*mut c_void),
+    send_video: unsafe extern "C" fn(*mut c_void, *const VideoFrame),
+    // keeps the functions above loaded
+    _library: libloading::Library,
+}
+
+#[cfg(feature = "ndi")]
+impl Runtime {
+    fn candidates() -> Vec<std::path::PathBuf> {
+        let mut paths = Vec::new();
+        if cfg!(target_os = "windows") {
+            if let Some(dir) = std::env::var_os("NDI_RUNTIME_DIR_V5") {
+                paths.push(std::path::Path::new(&dir).join("Processing.NDI.Lib.x64.dll"));
+            }
+            paths.push("Processing.NDI.Lib.x64.dll".into());
+        } else if cfg!(target_os = "macos") {
+            paths.push("libndi.dylib".into());
+            paths.push("/usr/local/lib/libndi.dylib".into());
+            paths.push("/Library/NDI SDK for Apple/lib/macOS/libndi.dylib".into());
+        } else {
+            paths.push("libndi.so.5".into());
+            paths.push("libndi.so".into());
+        }
+        paths
+    }
+
+    fn load() -> Result<Self, Error> {
+        let mut last_error = String::from("no library to load");
+        for path in Self::candidates() {
+            // the runtime runs no code of its own on load
+            match unsafe { libloading::Library::new(&path) } {
+                Ok(library) => return unsafe { Self::bind(library) },
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(Error::Runtime(last_error))
+    }
+
+    unsafe fn bind(library: libloading::Library) -> Result<Self, Error> {
+        let missing = |e: libloading::Error| Error::Runtime(e.to_string());
+        Ok(Self {
+            initialize: *library.get(b"NDIlib_initialize\0").map_err(missing)?,
+            destroy: *library.get(b"NDIlib_destroy\0").map_err(missing)?,
+            send_create: *library.get(b"NDIlib_send_create\0").map_err(missing)?,
+            send_destroy: *library.get(b"NDIlib_send_destroy\0").map_err(missing)?,
+            send_video: *library
+                .get(b"NDIlib_send_send_video_v2\0")
+                .map_err(missing)?,
+            _library: library,
+        })
+    }
+}
+
+/// An NDI source other machines on the network can receive by name.
+pub struct NdiSender {
+    name: String,
+    #[cfg(feature = "ndi")]
+    runtime: Runtime,
+    #[cfg(feature = "ndi")]
+    instance: *mut c_void,
+}
+
+// the runtime allows a sender to be used from any one thread at a time
+#[cfg(feature = "ndi")]
+unsafe impl Send for NdiSender {}
+
+impl NdiSender {
+    /// receivers see the source as `<machine> (<name>)`
+    pub fn new(name: &str) -> Result<Self, Error> {
+        #[cfg(feature = "ndi")]
+        {
+            let runtime = Runtime::load()?;
+            if !unsafe { (runtime.initialize)() } {
+                return Err(Error::Runtime("unsupported CPU".into()));
+            }
+            let c_name = CString::new(name).map_err(|e| Error::Runtime(e.to_string()))?;
+            let settings = SendCreate {
+                name: c_name.as_ptr(),
+                groups: ptr::null(),
+                // frames go out as fast as the app renders them
+                clock_video: false,
+                clock_audio: false,
+            };
+            let instance = unsafe { (runtime.send_create)(&settings) };
+            if instance.is_null() {
+                unsafe { (runtime.destroy)() };
+                return Err(Error::Sender);
+            }
+            Ok(Self {
+                name: name.to_string(),
+                runtime,
+                instance,
+            })
+        }
+        #[cfg(not(feature = "ndi"))]
+        {
+            let _ = name;
+            Err(Error::Unavailable)
+        }
+    }
+}
+
+impl Server for NdiSender {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn publish(&mut self, frame: SharedFrame) {
+        #[cfg(feature = "ndi")]
+        {
+            let stride = frame.width as usize * 4;
+            if frame.rgba.len() < stride * frame.height as usize {
+                return;
+            }
+            let video = VideoFrame {
+                xres: frame.width as c_int,
+                yres: frame.height as c_int,
+                four_cc: FOURCC_RGBA,
+                frame_rate_n: 60,
+                frame_rate_d: 1,
+                aspect_ratio: frame.width as f32 / frame.height.max(1) as f32,
+                frame_format: PROGRESSIVE,
+                timecode: SYNTHESIZE_TIMECODE,
+                data: frame.rgba.as_ptr(),
+                line_stride: stride as c_int,
+                metadata: ptr::null(),
+                timestamp: 0,
+            };
+            // synchronous, the runtime is done with `frame` when this returns
+            unsafe { (self.runtime.send_video)(self.instance, &video) };
+        }
+        #[cfg(not(feature = "ndi"))]
+        let _ = frame;
+    }
+}
+
+#[cfg(feature = "ndi")]
+impl Drop for NdiSender {
+    fn drop(&mut self) {
+        unsafe {
+            (self.runtime.send_destroy)(self.instance);
+            (self.runtime.destroy)();
+        }
+    }
+}
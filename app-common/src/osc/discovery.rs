@@ -0,0 +1,26 @@
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+/// Keeps an mDNS record alive until dropped.
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertisement {
+    /// `service` is the full type, e.g. `_osc._udp.local.`
+    pub fn new(service: &str, name: &str, port: u16) -> Result<Self, mdns_sd::Error> {
+        let daemon = ServiceDaemon::new()?;
+        let host = format!("{}.local.", name.replace(' ', "-"));
+        let info = ServiceInfo::new(service, name, &host, "", port, None)?.enable_addr_auto();
+        let fullname = info.get_fullname().to_owned();
+        daemon.register(info)?;
+        Ok(Self { daemon, fullname })
+    }
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        let _ = self.daemon.unregister(&self.fullname);
+        let _ = self.daemon.shutdown();
+    }
+}
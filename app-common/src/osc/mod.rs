@@ -0,0 +1,357 @@
+use nannou_osc as osc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, io, net::SocketAddr};
+
+#[cfg(feature = "mdns")]
+pub(crate) mod discovery;
+
+#[cfg(feature = "mdns")]
+const SERVICE_TYPE: &str = "_osc._udp.local.";
+/// packets read a frame at most, a socket that keeps failing can't hold
+/// the frame up
+const MAX_PACKETS: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    Float,
+    Int,
+    Bool,
+    String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    /// lenient where controllers commonly disagree, e.g. toggles sent as floats
+    fn decode(arg: &osc::Type, expected: ArgType) -> Option<Self> {
+        Some(match (expected, arg) {
+            (ArgType::Float, Type::Float(f)) => Value::Float(*f),
+            (ArgType::Float, Type::Double(f)) => Value::Float(*f as f32),
+            (ArgType::Float, Type::Int(i)) => Value::Float(*i as f32),
+            (ArgType::Int, Type::Int(i)) => Value::Int(*i),
+            (ArgType::Int, Type::Long(i)) => Value::Int(*i as i32),
+            (ArgType::Bool, Type::Bool(b)) => Value::Bool(*b),
+            (ArgType::Bool, Type::Int(i)) => Value::Bool(*i != 0),
+            (ArgType::Bool, Type::Float(f)) => Value::Bool(*f >= 0.5),
+            (ArgType::String, Type::String(s)) => Value::String(s.clone()),
+            _ => return None,
+        })
+    }
+
+    fn encode(&self) -> osc::Type {
+        match self {
+            Value::Float(f) => osc::Type::Float(*f),
+            Value::Int(i) => osc::Type::Int(*i),
+            Value::Bool(b) => osc::Type::Bool(*b),
+            Value::String(s) => osc::Type::String(s.clone()),
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Float(f) => Some(*f),
+            Value::Int(i) => Some(*i as f32),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::String(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub addr: String,
+    pub args: Vec<Value>,
+    pub from: SocketAddr,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Communication(String),
+    #[cfg(feature = "mdns")]
+    Discovery(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "osc io error: {}", e),
+            Error::Communication(e) => write!(f, "osc communication error: {}", e),
+            #[cfg(feature = "mdns")]
+            Error::Discovery(e) => write!(f, "osc discovery error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub listen_port: u16,
+    /// where replies and feedback go, `None` for receive only
+    pub send_to: Option<SocketAddr>,
+}
+
+impl Default for Config {
+    /// TouchOSC's default outgoing port
+    fn default() -> Self {
+        Self {
+            listen_port: 8000,
+            send_to: None,
+        }
+    }
+}
+
+/// Registered OSC address space with type-checked decoding.
+///
+/// Only messages whose address was registered and whose arguments
+/// decode to the declared types make it out of `poll`.
+pub struct Osc {
+    port: u16,
+    receiver: osc::Receiver,
+    sender: Option<osc::Sender<osc::Connected>>,
+    addresses: HashMap<String, Vec<ArgType>>,
+    rejected: usize,
+    #[cfg(feature = "mdns")]
+    advertisement: Option<discovery::Advertisement>,
+}
+
+impl Osc {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let sender = match config.send_to {
+            Some(addr) => Some(osc::sender()?.connect(addr)?),
+            None => None,
+        };
+        Ok(Self {
+            port: config.listen_port,
+            receiver: osc::receiver(config.listen_port)?,
+            sender,
+            addresses: HashMap::new(),
+            rejected: 0,
+            #[cfg(feature = "mdns")]
+            advertisement: None,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn register(&mut self, addr: &str, args: &[ArgType]) {
+        self.addresses.insert(addr.into(), args.to_vec());
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = (&str, &[ArgType])> {
+        self.addresses
+            .iter()
+            .map(|(addr, args)| (addr.as_str(), args.as_slice()))
+    }
+
+    /// messages dropped for an unknown address or mismatched arguments, and
+    /// packets that wouldn't decode
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// called at frame rate, drains everything received since the last call
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for _ in 0..MAX_PACKETS {
+            let (packet, from) = match self.receiver.try_recv() {
+                Ok(Some(received)) => received,
+                Ok(None) => break,
+                // malformed or truncated, the packets behind it still count
+                Err(_) => {
+                    self.rejected += 1;
+                    continue;
+                }
+            };
+            for msg in packet.into_msgs() {
+                match self.decode(msg, from) {
+                    Some(message) => messages.push(message),
+                    None => self.rejected += 1,
+                }
+            }
+        }
+        messages
+    }
+
+    fn decode(&self, msg: osc::Message, from: SocketAddr) -> Option<Message> {
+        let expected = self.addresses.get(&msg.addr)?;
+        let raw = msg.args.unwrap_or_default();
+        if raw.len() != expected.len() {
+            return None;
+        }
+        let args = raw
+            .iter()
+            .zip(expected.iter())
+            .map(|(arg, &ty)| Value::decode(arg, ty))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Message {
+            addr: msg.addr,
+            args,
+            from,
+        })
+    }
+
+    pub fn send(&self, addr: &str, args: &[Value]) -> Result<(), Error> {
+        if let Some(sender) = &self.sender {
+            sender
+                .send((addr, args.iter().map(Value::encode).collect::<Vec<_>>()))
+                .map_err(|e| Error::Communication(format!("{:?}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// advertise the listening port as `_osc._udp` over mDNS
+    #[cfg(feature = "mdns")]
+    pub fn advertise(&mut self, name: &str) -> Result<(), Error> {
+        self.advertisement = Some(
+            discovery::Advertisement::new(SERVICE_TYPE, name, self.port)
+                .map_err(|e| Error::Discovery(e.to_string()))?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// listening on a free port of its own
+    fn listening() -> Osc {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .unwrap()
+            .port();
+        let mut osc = Osc::new(&Config {
+            listen_port: port,
+            send_to: None,
+        })
+        .unwrap();
+        osc.register("/synth/cutoff", &[ArgType::Float]);
+        osc.register("/synth/note", &[ArgType::Int, ArgType::Bool]);
+        osc.register("/synth/name", &[ArgType::String]);
+        osc
+    }
+
+    fn message(addr: &str, args: Vec<osc::Type>) -> osc::Message {
+        osc::Message {
+            addr: addr.into(),
+            args: Some(args),
+        }
+    }
+
+    fn from() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 9000))
+    }
+
+    /// `text` null terminated and padded to four bytes, as OSC strings are
+    fn padded(text: &str) -> Vec<u8> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize((text.len() / 4 + 1) * 4, 0);
+        bytes
+    }
+
+    #[test]
+    fn only_registered_addresses_come_through() {
+        let osc = listening();
+        let args = vec![osc::Type::Float(0.5)];
+        let decoded = osc.decode(message("/synth/cutoff", args.clone()), from());
+        assert_eq!(decoded.unwrap().args, vec![Value::Float(0.5)]);
+        assert!(osc
+            .decode(message("/synth/cutof", args.clone()), from())
+            .is_none());
+        assert!(osc
+            .decode(message("/synth/cutoff/", args.clone()), from())
+            .is_none());
+        assert!(osc.decode(message("/synth", args), from()).is_none());
+    }
+
+    #[test]
+    fn type_tags_decode_leniently_but_not_loosely() {
+        use osc::Type;
+        let osc = listening();
+        let decode = |addr: &str, args: Vec<Type>| {
+            osc.decode(message(addr, args), from())
+                .map(|message| message.args)
+        };
+        assert_eq!(
+            decode("/synth/cutoff", vec![Type::Int(2)]),
+            Some(vec![Value::Float(2.0)])
+        );
+        assert_eq!(
+            decode("/synth/cutoff", vec![Type::Double(0.25)]),
+            Some(vec![Value::Float(0.25)])
+        );
+        assert_eq!(
+            decode("/synth/note", vec![Type::Long(60), Type::Float(1.0)]),
+            Some(vec![Value::Int(60), Value::Bool(true)])
+        );
+        assert_eq!(
+            decode("/synth/note", vec![Type::Int(60), Type::Int(0)]),
+            Some(vec![Value::Int(60), Value::Bool(false)])
+        );
+        assert_eq!(
+            decode("/synth/name", vec![Type::String("pad".into())]),
+            Some(vec![Value::String("pad".into())])
+        );
+        // the wrong type, too few and too many
+        assert_eq!(
+            decode("/synth/note", vec![Type::Float(60.0), Type::Int(1)]),
+            None
+        );
+        assert_eq!(
+            decode("/synth/cutoff", vec![Type::String("1".into())]),
+            None
+        );
+        assert_eq!(decode("/synth/note", vec![Type::Int(60)]), None);
+        assert_eq!(decode("/synth/cutoff", vec![]), None);
+        assert_eq!(
+            decode("/synth/cutoff", vec![Type::Float(1.0), Type::Float(2.0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn truncated_packets_are_rejected_and_the_rest_still_read() {
+        let mut osc = listening();
+        let mut packet = padded("/synth/cutoff");
+        packet.extend(padded(",f"));
+        packet.extend(&0.5f32.to_be_bytes());
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let to = ("127.0.0.1", osc.port());
+        // cut in the middle of the argument, then of the type tags
+        socket.send_to(&packet[..packet.len() - 2], to).unwrap();
+        socket.send_to(&packet[..18], to).unwrap();
+        socket.send_to(&packet, to).unwrap();
+
+        let mut messages = Vec::new();
+        let start = Instant::now();
+        while messages.is_empty() && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+            messages.extend(osc.poll());
+        }
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].addr, "/synth/cutoff");
+        assert_eq!(messages[0].args, vec![Value::Float(0.5)]);
+        assert_eq!(osc.rejected(), 2);
+    }
+}
@@ -0,0 +1,448 @@
+//! The parameters as an OSCQuery namespace, so controllers like Vezér or
+//! Open Stage Control find every address, its range and unit without being
+//! set up by hand.
+//!
+//! Every parameter is a float at `/<app>/<param name>`, the address
+//! `param::Bindings` gives it. One port serves all of it:
+//!
+//! - HTTP `GET /` answers with the whole namespace, `GET /<app>/<name>` with
+//!   one node and `GET /<app>/<name>?VALUE` with one attribute of it
+//! - `GET /?HOST_INFO` names the app and what it supports
+//! - OSC messages over UDP set the parameters, plain values as over `osc`
+//! - a WebSocket takes `{"COMMAND": "LISTEN", "DATA": "<address>"}` and
+//!   `IGNORE`, then pushes each change of a listened address as a binary OSC
+//!   message, from any source. Binary OSC messages sent to it set values
+//!
+//! Needs the `remote` feature, and `mdns` to be advertised as
+//! `_oscjson._tcp`.
+
+use crate::config::{reopen, Config};
+use crate::param::Params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    convert::TryInto,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr},
+};
+
+#[cfg(feature = "remote")]
+mod server;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscQueryConfig {
+    /// this machine only by default, `0.0.0.0` for controllers on other
+    /// devices
+    pub address: IpAddr,
+    /// HTTP, WebSocket and OSC over UDP all on this one
+    pub port: u16,
+}
+
+impl Default for OscQueryConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 9002,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `remote` feature
+    Unavailable,
+    Io(io::Error),
+    WebSocket(String),
+    #[cfg(feature = "mdns")]
+    Discovery(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `remote` feature"),
+            Error::Io(e) => write!(f, "oscquery io error: {}", e),
+            Error::WebSocket(e) => write!(f, "oscquery websocket error: {}", e),
+            #[cfg(feature = "mdns")]
+            Error::Discovery(e) => write!(f, "oscquery discovery error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// read and written, in OSCQuery's `ACCESS`
+const READ_WRITE: u8 = 3;
+
+/// The addresses under `/<app>`, independent of the transport.
+#[derive(Clone)]
+pub struct Namespace {
+    app: String,
+    params: Params,
+}
+
+impl Namespace {
+    pub fn new(app: &str, params: Params) -> Self {
+        Self {
+            app: app.into(),
+            params,
+        }
+    }
+
+    pub fn address(&self, index: usize) -> String {
+        format!("/{}/{}", self.app, self.params.specs()[index].name)
+    }
+
+    /// the parameter at `address`
+    pub fn index_of(&self, address: &str) -> Option<usize> {
+        let name = address
+            .strip_prefix('/')?
+            .strip_prefix(self.app.as_str())?
+            .strip_prefix('/')?;
+        self.params.index_of(name)
+    }
+
+    /// sets the parameter at `address`, false if there's none
+    pub fn set(&self, address: &str, value: f32) -> bool {
+        match self.index_of(address) {
+            Some(i) => {
+                self.params.set(i, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn value(&self, index: usize) -> f32 {
+        self.params.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// what `GET /?HOST_INFO` answers with, OSC arriving on `port`
+    pub fn host_info(&self, port: u16) -> String {
+        json!({
+            "NAME": self.app,
+            "OSC_PORT": port,
+            "OSC_TRANSPORT": "UDP",
+            "EXTENSIONS": {
+                "ACCESS": true,
+                "VALUE": true,
+                "RANGE": true,
+                "UNIT": true,
+                "CLIPMODE": true,
+                "DESCRIPTION": true,
+                "LISTEN": true,
+                "PATH_CHANGED": false,
+            },
+        })
+        .to_string()
+    }
+
+    /// the JSON for an HTTP request target, a path and maybe `?ATTRIBUTE`,
+    /// `None` for nothing there
+    pub fn query(&self, target: &str, port: u16) -> Option<String> {
+        let (path, attribute) = match target.find('?') {
+            Some(at) => (&target[..at], Some(&target[at + 1..])),
+            None => (target, None),
+        };
+        if attribute == Some("HOST_INFO") {
+            return Some(self.host_info(port));
+        }
+        let path = percent_decode(path)?;
+        let path = path.trim_end_matches('/');
+        let node = if path.is_empty() {
+            self.root()
+        } else if path == format!("/{}", self.app) {
+            self.container()
+        } else {
+            self.param(self.index_of(path)?)
+        };
+        match attribute {
+            None | Some("") => Some(node.to_string()),
+            Some(attribute) => {
+                let value = node.get(attribute)?;
+                let mut only = Map::new();
+                only.insert(attribute.into(), value.clone());
+                Some(Value::Object(only).to_string())
+            }
+        }
+    }
+
+    fn root(&self) -> Value {
+        json!({
+            "FULL_PATH": "/",
+            "ACCESS": 0,
+            "CONTENTS": { self.app.clone(): self.container() },
+        })
+    }
+
+    fn container(&self) -> Value {
+        let contents: Map<String, Value> = (0..self.params.len())
+            .map(|i| (self.params.specs()[i].name.to_string(), self.param(i)))
+            .collect();
+        json!({
+            "FULL_PATH": format!("/{}", self.app),
+            "ACCESS": 0,
+            "CONTENTS": contents,
+        })
+    }
+
+    fn param(&self, index: usize) -> Value {
+        let spec = &self.params.specs()[index];
+        let mut node = json!({
+            "FULL_PATH": self.address(index),
+            "TYPE": "f",
+            "ACCESS": READ_WRITE,
+            "VALUE": [number(self.params.get(index))],
+            "RANGE": [{
+                "MIN": number(spec.min.min(spec.max)),
+                "MAX": number(spec.max.max(spec.min)),
+            }],
+            "CLIPMODE": ["both"],
+            "DESCRIPTION": spec.name,
+        });
+        if !spec.unit.is_empty() {
+            node["UNIT"] = json!([spec.unit]);
+        }
+        node
+    }
+}
+
+/// as written rather than widened, 0.1 and not 0.10000000149011612
+fn number(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or_else(|_| value.into())
+}
+
+/// `%xx` escapes back to bytes, `None` if they aren't UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], bytes.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "COMMAND", content = "DATA", rename_all = "UPPERCASE")]
+enum Command {
+    Listen(String),
+    Ignore(String),
+}
+
+/// One WebSocket client's side of the protocol.
+pub struct Connection {
+    namespace: Namespace,
+    listening: Vec<bool>,
+    /// values as of the last push, to find what changed
+    last: Vec<f32>,
+}
+
+impl Connection {
+    pub fn new(namespace: Namespace) -> Self {
+        Self {
+            listening: vec![false; namespace.len()],
+            last: values(&namespace),
+            namespace,
+        }
+    }
+
+    /// a `LISTEN` or `IGNORE`, anything else is ignored as the spec asks
+    pub fn command(&mut self, text: &str) {
+        let (address, listen) = match serde_json::from_str(text) {
+            Ok(Command::Listen(address)) => (address, true),
+            Ok(Command::Ignore(address)) => (address, false),
+            Err(_) => return,
+        };
+        if let Some(i) = self.namespace.index_of(&address) {
+            self.listening[i] = listen;
+            self.last[i] = self.namespace.value(i);
+        }
+    }
+
+    /// binary frames are OSC packets
+    pub fn packet(&self, packet: &[u8]) {
+        for (address, value) in decode(packet) {
+            self.namespace.set(&address, value);
+        }
+    }
+
+    /// an OSC message for each listened value that changed since the last
+    /// call
+    pub fn changes(&mut self) -> Vec<Vec<u8>> {
+        let mut changes = Vec::new();
+        for i in 0..self.namespace.len() {
+            let value = self.namespace.value(i);
+            if self.listening[i] && value.to_bits() != self.last[i].to_bits() {
+                changes.push(encode(&self.namespace.address(i), value));
+            }
+            self.last[i] = value;
+        }
+        changes
+    }
+}
+
+fn values(namespace: &Namespace) -> Vec<f32> {
+    (0..namespace.len()).map(|i| namespace.value(i)).collect()
+}
+
+/// an OSC message with one float
+pub fn encode(address: &str, value: f32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(address.len() + 12);
+    push_string(&mut packet, address);
+    push_string(&mut packet, ",f");
+    packet.extend_from_slice(&value.to_be_bytes());
+    packet
+}
+
+fn push_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend_from_slice(text.as_bytes());
+    let padding = 4 - text.len() % 4;
+    packet.resize(packet.len() + padding, 0);
+}
+
+/// every message in an OSC packet whose first argument reads as a number,
+/// leniently as `osc` does, bundles included
+pub fn decode(packet: &[u8]) -> Vec<(String, f32)> {
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages);
+    messages
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<(String, f32)>) {
+    if let Some(mut elements) = packet.strip_prefix(b"#bundle\0") {
+        // past the time tag, then a size before each element
+        elements = elements.get(8..).unwrap_or_default();
+        while let Some(size) = elements.get(..4) {
+            let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+            match elements.get(4..4 + size) {
+                Some(element) => decode_into(element, messages),
+                None => return,
+            }
+            elements = &elements[4 + size..];
+        }
+    } else if let Some(message) = decode_message(packet) {
+        messages.push(message);
+    }
+}
+
+fn decode_message(packet: &[u8]) -> Option<(String, f32)> {
+    let (address, rest) = read_string(packet)?;
+    let (tags, args) = read_string(rest)?;
+    let tag = *tags.strip_prefix(',')?.as_bytes().first()?;
+    let value = match tag {
+        b'f' => f32::from_be_bytes(args.get(..4)?.try_into().ok()?),
+        b'i' => i32::from_be_bytes(args.get(..4)?.try_into().ok()?) as f32,
+        b'd' => f64::from_be_bytes(args.get(..8)?.try_into().ok()?) as f32,
+        b'h' => i64::from_be_bytes(args.get(..8)?.try_into().ok()?) as f32,
+        b'T' => 1.0,
+        b'F' => 0.0,
+        _ => return None,
+    };
+    Some((address.to_string(), value))
+}
+
+/// a padded OSC string and what follows it
+fn read_string(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let end = bytes.iter().position(|&b| b == 0)?;
+    let text = std::str::from_utf8(&bytes[..end]).ok()?;
+    let next = (end / 4 + 1) * 4;
+    Some((text, bytes.get(next..).unwrap_or_default()))
+}
+
+/// Serves the namespace until dropped.
+pub struct OscQueryServer {
+    #[cfg(feature = "remote")]
+    _server: server::Server,
+    port: u16,
+}
+
+impl OscQueryServer {
+    pub fn start(config: &OscQueryConfig, namespace: Namespace) -> Result<Self, Error> {
+        #[cfg(feature = "remote")]
+        {
+            let server = server::Server::start(config, namespace)?;
+            Ok(Self {
+                port: server.port(),
+                _server: server,
+            })
+        }
+        #[cfg(not(feature = "remote"))]
+        {
+            let _ = (config, namespace);
+            Err(Error::Unavailable)
+        }
+    }
+
+    /// the bound port, differs from the config's when that was 0
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// `app`'s parameters served while `config.oscquery` is set, restarted
+/// when a reload changes it. Why a server couldn't start goes to stderr.
+pub struct Service {
+    app: String,
+    config: Option<OscQueryConfig>,
+    server: Option<OscQueryServer>,
+}
+
+impl Service {
+    pub fn from_config(app: &str, config: &Config, params: &Params) -> Self {
+        Self {
+            app: app.to_owned(),
+            config: config.oscquery.clone(),
+            server: open(app, config, params),
+        }
+    }
+
+    /// restarts the server if `config.oscquery` isn't the one it runs
+    pub fn reload(&mut self, config: &Config, params: &Params) {
+        let app = &self.app;
+        reopen(&self.config, &config.oscquery, &mut self.server, || {
+            open(app, config, params)
+        });
+        self.config = config.oscquery.clone();
+    }
+
+    pub fn server(&self) -> Option<&OscQueryServer> {
+        self.server.as_ref()
+    }
+}
+
+fn open(app: &str, config: &Config, params: &Params) -> Option<OscQueryServer> {
+    let namespace = Namespace::new(app, params.clone());
+    let server = OscQueryServer::start(config.oscquery.as_ref()?, namespace);
+    server.map_err(|e| eprintln!("{}: {}", app, e)).ok()
+}
@@ -0,0 +1,219 @@
+use super::{decode, Connection, Error, Namespace, OscQueryConfig};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tungstenite::Message;
+
+/// how often idle threads check for changes and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+/// clients that don't finish their request by then are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEAD: usize = 8192;
+/// larger than any message a controller sends
+const MAX_PACKET: usize = 65_507;
+#[cfg(feature = "mdns")]
+const SERVICE_TYPE: &str = "_oscjson._tcp.local.";
+
+pub struct Server {
+    running: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+    receiver: Option<JoinHandle<()>>,
+    port: u16,
+    #[cfg(feature = "mdns")]
+    _advertisement: crate::osc::discovery::Advertisement,
+}
+
+impl Server {
+    pub fn start(config: &OscQueryConfig, namespace: Namespace) -> Result<Self, Error> {
+        let listener = TcpListener::bind((config.address, config.port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        // OSC arrives on the same port number, over UDP
+        let socket = UdpSocket::bind((config.address, port))?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        #[cfg(feature = "mdns")]
+        let advertisement =
+            crate::osc::discovery::Advertisement::new(SERVICE_TYPE, &namespace.app, port)
+                .map_err(|e| Error::Discovery(e.to_string()))?;
+        let running = Arc::new(AtomicBool::new(true));
+
+        let receiving = running.clone();
+        let osc = namespace.clone();
+        let receiver = thread::Builder::new()
+            .name("oscquery osc".into())
+            .spawn(move || {
+                let mut packet = vec![0; MAX_PACKET];
+                while receiving.load(Ordering::Relaxed) {
+                    match socket.recv(&mut packet) {
+                        Ok(len) => {
+                            for (address, value) in decode(&packet[..len]) {
+                                osc.set(&address, value);
+                            }
+                        }
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                            ) => {}
+                        Err(e) => eprintln!("oscquery: {}", e),
+                    }
+                }
+            })?;
+
+        let accepting = running.clone();
+        let acceptor = thread::Builder::new()
+            .name("oscquery".into())
+            .spawn(move || {
+                while accepting.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, from)) => {
+                            let namespace = namespace.clone();
+                            let running = accepting.clone();
+                            let _ = thread::Builder::new()
+                                .name(format!("oscquery {}", from))
+                                .spawn(move || {
+                                    if let Err(e) = serve(stream, namespace, port, &running) {
+                                        eprintln!("oscquery: {}: {}", from, e);
+                                    }
+                                });
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL)
+                        }
+                        Err(e) => eprintln!("oscquery: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            running,
+            acceptor: Some(acceptor),
+            receiver: Some(receiver),
+            port,
+            #[cfg(feature = "mdns")]
+            _advertisement: advertisement,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for Server {
+    /// waits for the sockets to close, so the port can be bound again
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+/// the request head, without consuming it
+fn peek_head(stream: &TcpStream) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0; MAX_HEAD];
+    loop {
+        let len = stream.peek(&mut buffer)?;
+        let head = &buffer[..len];
+        if len == 0 || len == MAX_HEAD || head.windows(4).any(|w| w == b"\r\n\r\n") {
+            buffer.truncate(len);
+            return Ok(buffer);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn is_upgrade(head: &str) -> bool {
+    head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("upgrade:") && line.contains("websocket")
+    })
+}
+
+fn serve(
+    stream: TcpStream,
+    namespace: Namespace,
+    port: u16,
+    running: &AtomicBool,
+) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let head = peek_head(&stream)?;
+    let text = String::from_utf8_lossy(&head);
+    if !is_upgrade(&text) {
+        return http(stream, head.len(), &text, &namespace, port);
+    }
+
+    let mut socket = tungstenite::accept(stream).map_err(|e| Error::WebSocket(e.to_string()))?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut connection = Connection::new(namespace);
+    let websocket = |e: tungstenite::Error| Error::WebSocket(e.to_string());
+
+    while running.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(text)) => connection.command(&text),
+            Ok(Message::Binary(packet)) => connection.packet(&packet),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(websocket(e)),
+        }
+        for change in connection.changes() {
+            socket.send(Message::Binary(change)).map_err(websocket)?;
+        }
+    }
+    let _ = socket.close(None);
+    Ok(())
+}
+
+/// `GET` queries of the namespace
+fn http(
+    mut stream: TcpStream,
+    peeked: usize,
+    head: &str,
+    namespace: &Namespace,
+    port: u16,
+) -> Result<(), Error> {
+    // consume what was peeked so closing doesn't reset the connection
+    let mut consumed = vec![0; peeked];
+    let _ = stream.read_exact(&mut consumed);
+
+    let target = head.lines().next().unwrap_or("");
+    let found = match target.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _] => namespace.query(target, port),
+        _ => None,
+    };
+    let (status, body) = match found {
+        Some(body) => ("200 OK", body),
+        None => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
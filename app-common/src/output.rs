@@ -0,0 +1,165 @@
+//! A second, borderless window for a projector or LED wall.
+//!
+//! The UI stays on the main window, the output window only draws the scene,
+//! either mirrored and scaled to fit or extending it past the main window's
+//! right edge. Note `App::main_window` is whichever window has focus, so
+//! clicking the output window briefly makes it the main one.
+
+use nannou::prelude::*;
+use nannou::winit::monitor::MonitorHandle;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Layout {
+    /// the main window's view, scaled to fit
+    Mirror,
+    /// carries on to the right of the main window at the same scale
+    Extend,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// monitor name, the first one not showing the main window when `None`
+    pub monitor: Option<String>,
+    /// in pixels, the whole monitor when `None`
+    pub size: Option<[u32; 2]>,
+    pub layout: Layout,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            size: None,
+            layout: Layout::Mirror,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// no monitor by that name is connected
+    NoMonitor(String),
+    Build(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoMonitor(name) => write!(f, "no monitor named {}", name),
+            Error::Build(e) => write!(f, "cannot open the output window: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// names of the connected monitors, for picking one in the config
+pub fn monitors(app: &App) -> Vec<String> {
+    app.available_monitors()
+        .iter()
+        .filter_map(MonitorHandle::name)
+        .collect()
+}
+
+fn contains(monitor: &MonitorHandle, [x, y]: [i32; 2]) -> bool {
+    let position = monitor.position();
+    let size = monitor.size();
+    x >= position.x
+        && y >= position.y
+        && x < position.x + size.width as i32
+        && y < position.y + size.height as i32
+}
+
+fn pick_monitor(app: &App, main: WindowId, name: Option<&str>) -> Result<MonitorHandle, Error> {
+    let monitors = app.available_monitors();
+    if let Some(name) = name {
+        return monitors
+            .into_iter()
+            .find(|m| m.name().as_deref() == Some(name))
+            .ok_or_else(|| Error::NoMonitor(name.to_string()));
+    }
+    let main_position = app
+        .window(main)
+        .and_then(|window| window.outer_position_pixels().ok())
+        .map(|(x, y)| [x, y]);
+    let other = monitors
+        .iter()
+        .find(|m| !matches!(main_position, Some(p) if contains(m, p)));
+    // a single screen still gets the window, handy for rehearsing
+    other
+        .or_else(|| monitors.first())
+        .cloned()
+        .ok_or_else(|| Error::NoMonitor("any".to_string()))
+}
+
+/// The projection window, drawn by its own view function.
+pub struct OutputWindow {
+    id: WindowId,
+    main: WindowId,
+    layout: Layout,
+}
+
+impl OutputWindow {
+    /// `main` is the window from `Config::build_window`
+    pub fn open<M: 'static>(
+        app: &App,
+        main: WindowId,
+        config: &OutputConfig,
+        view: nannou::window::ViewFn<M>,
+    ) -> Result<Self, Error> {
+        let monitor = pick_monitor(app, main, config.monitor.as_deref())?;
+        let [w, h] = config
+            .size
+            .unwrap_or([monitor.size().width, monitor.size().height]);
+        let id = app
+            .new_window()
+            .title("output")
+            .decorations(false)
+            .size_pixels(w, h)
+            .view(view)
+            .build()
+            .map_err(|e| Error::Build(format!("{:?}", e)))?;
+        if let Some(window) = app.window(id) {
+            let position = monitor.position();
+            window.set_outer_position_pixels(position.x, position.y);
+        }
+        Ok(Self {
+            id,
+            main,
+            layout: config.layout,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// a draw for this window's frame on which the scene can be drawn in
+    /// the main window's coordinates
+    pub fn draw(&self, app: &App) -> Draw {
+        let draw = app.draw();
+        let (main, output) = match (app.window(self.main), app.window(self.id)) {
+            (Some(main), Some(output)) => (main.rect(), output.rect()),
+            _ => return draw,
+        };
+        match self.layout {
+            Layout::Mirror => {
+                let scale = (output.w() / main.w()).min(output.h() / main.h());
+                draw.scale(scale)
+            }
+            Layout::Extend => {
+                let scale = output.h() / main.h();
+                // the output's left edge meets the main window's right edge
+                let offset = main.right() + 0.5 * output.w() / scale;
+                draw.scale(scale).x(-offset)
+            }
+        }
+    }
+}
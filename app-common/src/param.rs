@@ -0,0 +1,308 @@
+use crate::macros::Macro;
+use crate::preset::{self, Format, Preset};
+use crate::theme::{Palette, Themed};
+use crate::{midi::MidiMessage, osc};
+use nannou::ui::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub use dsp_common::param::{Curve, ParamSpec, Params, Smoothed, SmoothedParams};
+
+/// Parameter values by name, what ends up inside presets.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamSnapshot(pub BTreeMap<String, f32>);
+
+impl ParamSnapshot {
+    pub fn capture(params: &Params) -> Self {
+        Self(
+            params
+                .snapshot()
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// unknown names are ignored, missing ones keep their current value
+    pub fn apply(&self, params: &Params) {
+        for (name, value) in self.0.iter() {
+            params.set_by_name(name, *value);
+        }
+    }
+
+    /// for apps whose presets are their parameters, see `ParamPreset::load`
+    pub fn load_preset(app: &str, name: &str) -> Result<Self, preset::Error> {
+        Ok(ParamPreset::load(app, name)?.params)
+    }
+}
+
+/// A preset for apps whose presets are their parameters, with the macros
+/// over them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamPreset {
+    pub params: ParamSnapshot,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub macros: Vec<Macro>,
+    /// what the app keeps outside its parameters by name, where a figure
+    /// has got to, ignored by apps that don't know the names
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub state: BTreeMap<String, f32>,
+}
+
+impl ParamPreset {
+    /// `name` in `app`'s preset directory or the path to a preset file
+    pub fn load(app: &str, name: &str) -> Result<Self, preset::Error> {
+        let path = Path::new(name);
+        if Format::from_path(path).is_some() && path.exists() {
+            return preset::load_path(path);
+        }
+        let path = Format::ALL
+            .iter()
+            .map(|format| preset::dir(app).join(format!("{}.{}", name, format.extension())))
+            .find(|path| path.exists())
+            .ok_or_else(|| preset::Error::NotFound(name.into()))?;
+        preset::load_path(&path)
+    }
+
+    /// as `name` in `app`'s preset directory, where `load` finds it
+    pub fn save(&self, app: &str, name: &str) -> Result<PathBuf, preset::Error> {
+        preset::check_name(name)?;
+        let dir = preset::dir(app);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.{}", name, Format::Toml.extension()));
+        fs::write(&path, preset::to_string(self, Format::Toml)?)?;
+        Ok(path)
+    }
+}
+
+impl Preset for ParamPreset {
+    /// unused, `load` is told which app's directory to look in
+    const APP: &'static str = "";
+    const VERSION: u32 = 2;
+
+    /// version 1 was the values alone
+    fn migrate(version: u32, value: &mut Value) -> Result<(), preset::Error> {
+        match version {
+            1 => {
+                let params = value.take();
+                *value = Value::Object(std::iter::once(("params".into(), params)).collect());
+                Ok(())
+            }
+            _ => Err(preset::Error::Version {
+                found: version,
+                supported: Self::VERSION,
+            }),
+        }
+    }
+}
+
+/// One slider per parameter, the first placed at the top left of the
+/// window and the rest stacked below it. Sliders move in normalized
+/// space so the parameter's curve applies.
+pub fn sliders(params: &Params, ids: &mut widget::id::List, palette: &Palette, ui: &mut UiCell) {
+    if ids.len() != params.len() {
+        ids.resize(params.len(), &mut ui.widget_id_generator());
+    }
+
+    for (i, spec) in params.specs().iter().enumerate() {
+        let slider = widget::Slider::new(params.get_normalized(i), 0.0, 1.0)
+            .w_h(200.0, 30.0)
+            .label(&spec.format(params.get(i)))
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0);
+        let slider = if i == 0 {
+            slider.top_left_with_margin(20.0)
+        } else {
+            slider.down(20.0)
+        };
+        for value in slider.set(ids[i], ui) {
+            params.set_normalized(i, value);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CcBinding {
+    /// any channel when `None`
+    pub channel: Option<u8>,
+    pub controller: u8,
+    pub param: String,
+}
+
+/// sets the parameters `message` is bound to, true if there were any
+pub fn apply_cc(bindings: &[CcBinding], params: &Params, message: &MidiMessage) -> bool {
+    if let MidiMessage::ControlChange {
+        channel,
+        controller,
+        value,
+    } = *message
+    {
+        let mut applied = false;
+        for binding in bindings.iter().filter(|b| {
+            b.controller == controller && (b.channel.is_none() || b.channel == Some(channel))
+        }) {
+            if let Some(i) = params.index_of(&binding.param) {
+                params.set_normalized(i, value as f32 / 127.0);
+                applied = true;
+            }
+        }
+        return applied;
+    }
+    false
+}
+
+/// Controller addresses for an app's parameters.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Bindings {
+    pub cc: Vec<CcBinding>,
+    /// OSC addresses are `/<prefix>/<param name>`
+    pub osc_prefix: String,
+}
+
+impl Bindings {
+    pub fn new(osc_prefix: &str) -> Self {
+        Self {
+            cc: Vec::new(),
+            osc_prefix: osc_prefix.into(),
+        }
+    }
+
+    pub fn bind_cc(&mut self, channel: Option<u8>, controller: u8, param: &str) {
+        self.cc
+            .retain(|b| !(b.channel == channel && b.controller == controller));
+        self.cc.push(CcBinding {
+            channel,
+            controller,
+            param: param.into(),
+        });
+    }
+
+    pub fn unbind(&mut self, param: &str) {
+        self.cc.retain(|b| b.param != param);
+    }
+
+    /// CC values sweep the whole normalized range
+    pub fn apply_midi(&self, params: &Params, message: &MidiMessage) -> bool {
+        apply_cc(&self.cc, params, message)
+    }
+
+    pub fn osc_address(&self, name: &str) -> String {
+        format!("/{}/{}", self.osc_prefix, name)
+    }
+
+    pub fn register_osc(&self, params: &Params, osc: &mut osc::Osc) {
+        for spec in params.specs() {
+            osc.register(&self.osc_address(spec.name), &[osc::ArgType::Float]);
+        }
+    }
+
+    /// the parameter name `message` is addressed to
+    pub fn osc_param<'a>(&self, message: &'a osc::Message) -> Option<&'a str> {
+        message.addr.strip_prefix(&format!("/{}/", self.osc_prefix))
+    }
+
+    /// OSC carries plain parameter values, not normalized ones
+    pub fn apply_osc(&self, params: &Params, message: &osc::Message) -> bool {
+        let name = match self.osc_param(message) {
+            Some(name) => name,
+            None => return false,
+        };
+        match message.args.first().and_then(osc::Value::as_f32) {
+            Some(value) => params.set_by_name(name, value),
+            None => false,
+        }
+    }
+}
+
+/// Sends parameter changes back to `Config::send_to`, so faders on a
+/// controller follow the mouse, MIDI and automation.
+pub struct OscFeedback {
+    /// the last value sent per parameter, NaN to send it regardless
+    sent: Vec<f32>,
+}
+
+impl OscFeedback {
+    /// the first `send_changes` sends every value
+    pub fn new(params: &Params) -> Self {
+        Self {
+            sent: vec![f32::NAN; params.len()],
+        }
+    }
+
+    /// `Bindings::apply_osc`, without echoing the value back to the sender
+    pub fn apply(&mut self, bindings: &Bindings, params: &Params, message: &osc::Message) -> bool {
+        if !bindings.apply_osc(params, message) {
+            return false;
+        }
+        if let Some(i) = bindings
+            .osc_param(message)
+            .and_then(|name| params.index_of(name))
+        {
+            self.sent[i] = params.get(i);
+        }
+        true
+    }
+
+    /// called at frame rate, after the incoming messages are applied
+    pub fn send_changes(
+        &mut self,
+        bindings: &Bindings,
+        params: &Params,
+        osc: &osc::Osc,
+    ) -> Result<(), osc::Error> {
+        for (i, spec) in params.specs().iter().enumerate() {
+            let value = params.get(i);
+            if value.to_bits() != self.sent[i].to_bits() {
+                osc.send(
+                    &bindings.osc_address(spec.name),
+                    &[osc::Value::Float(value)],
+                )?;
+                self.sent[i] = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// sends every value again on the next `send_changes`, for a controller
+    /// that has just connected
+    pub fn resend(&mut self) {
+        self.sent.iter_mut().for_each(|value| *value = f32::NAN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_names_cannot_leave_the_preset_directory() {
+        let names = [
+            "../outside",
+            "..",
+            ".",
+            "",
+            "nested/name",
+            "trailing/",
+            "/etc/passwd",
+            "back\\slash",
+        ];
+        for name in &names {
+            // refused before anything is written
+            let saved = ParamPreset::default().save("param-tests", name);
+            assert!(matches!(saved, Err(preset::Error::Name(_))), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn plain_preset_names_are_kept() {
+        for name in &["warm pad", "v2.1", "..dotted", "über"] {
+            assert!(preset::check_name(name).is_ok(), "{:?}", name);
+        }
+    }
+}
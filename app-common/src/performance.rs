@@ -0,0 +1,278 @@
+//! Performance takes: the output as heard along with every parameter move
+//! and trigger, recorded side by side into one folder.
+//!
+//! `<timestamp>.take/` is a `session` bundle, so the config and the seed
+//! the take started from, with `audio.wav` and `events.json`, an
+//! `Automation` carrying the app's triggers, next to it. `--replay <folder>`
+//! plays the events back offline into `replay.wav`, the same take again for
+//! an engine whose only randomness comes from the seed and the triggers.
+//! With `normalize` in the config the take also gets `audio-normalized.wav`
+//! and the replay is normalized, `--normalize` overrides either. The audio
+//! is written as the config's `recording` format, only a take being
+//! normalized keeps its wav to measure and gets the format for its copy.
+
+use crate::automation::{self, Automated, Automation, Clock, Triggers};
+use crate::capture::timestamp;
+use crate::cli;
+use crate::recorder::{self, FileFormat, Recorder, RecorderInput, Spec};
+use crate::render::{self, Normalization, Render, Request};
+use crate::session::{self, Session};
+use dsp_common::param::Params;
+use nannou::prelude::Key;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// starts and stops a take
+pub const RECORD: Key = Key::F6;
+
+const AUDIO: &str = "audio";
+const NORMALIZED: &str = "audio-normalized";
+const EVENTS: &str = "events.json";
+const REPLAY: &str = "replay";
+const EXTENSION: &str = "take";
+/// how far the audio thread can get ahead of the writer
+const BUFFER_SECONDS: f32 = 4.0;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Session(session::Error),
+    Audio(recorder::Error),
+    Parse(String),
+    /// the sample rate isn't known before the engine has rendered
+    NotRunning,
+    /// a take stopped before `events.json` was written
+    NoEvents(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "take io error: {}", e),
+            Error::Session(e) => write!(f, "take {}", e),
+            Error::Audio(e) => write!(f, "take {}", e),
+            Error::Parse(e) => write!(f, "malformed take events: {}", e),
+            Error::NotRunning => write!(f, "no audio is playing to record"),
+            Error::NoEvents(path) => write!(f, "{} has no events", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<session::Error> for Error {
+    fn from(e: session::Error) -> Self {
+        Error::Session(e)
+    }
+}
+
+impl From<recorder::Error> for Error {
+    fn from(e: recorder::Error) -> Self {
+        Error::Audio(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+fn file_name(stem: &str, format: FileFormat) -> String {
+    format!("{}.{}", stem, format.extension())
+}
+
+/// per-app take directory inside the platform data directory
+pub fn dir(app: &str) -> PathBuf {
+    directories::ProjectDirs::from("", "", app)
+        .map(|dirs| dirs.data_dir().join("takes"))
+        .unwrap_or_else(|| PathBuf::from("takes"))
+}
+
+/// A take being recorded, polled from the UI thread like an automation
+/// `Recorder`.
+pub struct Take {
+    path: PathBuf,
+    audio: Recorder,
+    events: automation::Recorder,
+    /// the audio's, a normalized copy's if there's one
+    format: FileFormat,
+    normalize: Option<Normalization>,
+}
+
+impl Take {
+    /// bundles `session` under a new folder and starts the audio file, the
+    /// input goes to the engine's `Automated::record`
+    pub fn start(
+        session: &Session,
+        params: &Params,
+        clock: &Clock,
+        channels: usize,
+    ) -> Result<(Self, RecorderInput), Error> {
+        let sample_rate = clock.sample_rate();
+        if sample_rate == 0 {
+            return Err(Error::NotRunning);
+        }
+        let path = dir(&session.app).join(format!("{}.{}", timestamp(), EXTENSION));
+        session.save(&path)?;
+        let format = session.config.recording.unwrap_or(FileFormat::Wav);
+        let normalize = session.config.normalize;
+        let recorded = if normalize.is_some() {
+            FileFormat::Wav
+        } else {
+            format
+        };
+        let (audio, input) = Recorder::start(
+            &path.join(file_name(AUDIO, recorded)),
+            Spec {
+                channels: channels as u16,
+                sample_rate,
+                format: recorded,
+                buffer_seconds: BUFFER_SECONDS,
+            },
+        )?;
+        let take = Self {
+            path,
+            audio,
+            events: automation::Recorder::start(params, clock),
+            format,
+            normalize,
+        };
+        Ok((take, input))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// buffers the audio thread had to drop so far
+    pub fn overruns(&self) -> usize {
+        self.audio.overruns()
+    }
+
+    pub fn poll(&mut self, params: &Params, clock: &Clock) {
+        self.events.poll(params, clock);
+    }
+
+    pub fn trigger(&mut self, name: &str, clock: &Clock) {
+        self.events.trigger(name, clock);
+    }
+
+    /// writes the events and finishes the audio file, normalizing a copy
+    /// if the config asks for it, stop the engine's tap first so nothing is
+    /// pushed after it
+    pub fn stop(self, clock: &Clock) -> Result<PathBuf, Error> {
+        let events = self.events.stop(clock);
+        fs::write(
+            self.path.join(EVENTS),
+            serde_json::to_string_pretty(&events)?,
+        )?;
+        self.audio.stop()?;
+        if let Some(normalize) = &self.normalize {
+            render::normalize_wav(
+                &self.path.join(file_name(AUDIO, FileFormat::Wav)),
+                &self.path.join(file_name(NORMALIZED, self.format)),
+                self.format,
+                normalize,
+            )?;
+        }
+        Ok(self.path)
+    }
+}
+
+/// A recorded take, loaded to be rendered again.
+pub struct Replay {
+    pub session: Session,
+    pub events: Automation,
+    path: PathBuf,
+}
+
+impl Replay {
+    pub fn load(app: &str, path: &Path) -> Result<Self, Error> {
+        let session = Session::load(app, path)?;
+        let events = path.join(EVENTS);
+        if !events.exists() {
+            return Err(Error::NoEvents(path.to_path_buf()));
+        }
+        Ok(Self {
+            session,
+            events: serde_json::from_str(&fs::read_to_string(events)?)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// renders the take through `engine` into `replay` in the recording
+    /// format with progress on stderr, `trigger` gets each trigger's name on
+    /// the frame it was recorded at
+    pub fn run<R, F>(
+        &self,
+        engine: R,
+        params: &Params,
+        channels: usize,
+        frames_per_buffer: usize,
+        trigger: F,
+    ) where
+        R: Render,
+        F: FnMut(&mut R, &str),
+    {
+        let sample_rate = self.events.sample_rate;
+        let mut engine = Automated::new(engine, params.clone());
+        engine.play(self.events.player(params, sample_rate));
+        let mut replaying = Replaying {
+            engine,
+            triggers: self.events.triggers(sample_rate),
+            frame: 0,
+            trigger,
+        };
+        let format = self.session.config.recording.unwrap_or(FileFormat::Wav);
+        let request = Request {
+            seconds: self.events.frames as f64 / sample_rate.max(1) as f64,
+            path: self.path.join(file_name(REPLAY, format)),
+            format,
+            normalize: cli::args().normalize.or(self.session.config.normalize),
+        };
+        request.run(&mut replaying, sample_rate, channels, frames_per_buffer);
+    }
+}
+
+/// Splits its blocks at each trigger like `Automated` does at parameter
+/// changes.
+struct Replaying<R, F> {
+    engine: Automated<R>,
+    triggers: Triggers,
+    /// since the start of the take
+    frame: u64,
+    trigger: F,
+}
+
+impl<R: Render, F: FnMut(&mut R, &str)> Render for Replaying<R, F> {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        let frames = out.len() / channels;
+        let mut start = 0;
+        while start < frames {
+            let now = self.frame + start as u64;
+            for name in self.triggers.until(now + 1) {
+                (self.trigger)(self.engine.engine_mut(), name);
+            }
+            let len = match self.triggers.next_frame() {
+                Some(next) => ((next - now) as usize).min(frames - start),
+                None => frames - start,
+            };
+            self.engine.render(
+                &mut out[start * channels..(start + len) * channels],
+                channels,
+                sample_rate,
+            );
+            start += len;
+        }
+        self.frame += frames as u64;
+    }
+}
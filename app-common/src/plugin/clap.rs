@@ -0,0 +1,201 @@
+//! The parts of the CLAP 1.x C ABI an effect host needs, from `clap/*.h`.
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+
+pub const NAME_SIZE: usize = 256;
+pub const PATH_SIZE: usize = 1024;
+
+pub const PLUGIN_FACTORY_ID: &[u8] = b"clap.plugin-factory\0";
+pub const EXT_PARAMS: &[u8] = b"clap.params\0";
+pub const FEATURE_AUDIO_EFFECT: &[u8] = b"audio-effect";
+
+pub const CORE_EVENT_SPACE_ID: u16 = 0;
+pub const EVENT_PARAM_VALUE: u16 = 5;
+
+pub const PARAM_IS_STEPPED: u32 = 1 << 0;
+pub const PARAM_IS_HIDDEN: u32 = 1 << 2;
+pub const PARAM_IS_READONLY: u32 = 1 << 3;
+
+pub const PROCESS_ERROR: i32 = 0;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub revision: u32,
+}
+
+pub const VERSION: Version = Version {
+    major: 1,
+    minor: 1,
+    revision: 0,
+};
+
+#[repr(C)]
+pub struct PluginEntry {
+    pub clap_version: Version,
+    pub init: unsafe extern "C" fn(plugin_path: *const c_char) -> bool,
+    pub deinit: unsafe extern "C" fn(),
+    pub get_factory: unsafe extern "C" fn(factory_id: *const c_char) -> *const c_void,
+}
+
+#[repr(C)]
+pub struct PluginFactory {
+    pub get_plugin_count: unsafe extern "C" fn(factory: *const PluginFactory) -> u32,
+    pub get_plugin_descriptor:
+        unsafe extern "C" fn(factory: *const PluginFactory, index: u32) -> *const PluginDescriptor,
+    pub create_plugin: unsafe extern "C" fn(
+        factory: *const PluginFactory,
+        host: *const Host,
+        plugin_id: *const c_char,
+    ) -> *const Plugin,
+}
+
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub clap_version: Version,
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub manual_url: *const c_char,
+    pub support_url: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    /// null terminated
+    pub features: *const *const c_char,
+}
+
+#[repr(C)]
+pub struct Host {
+    pub clap_version: Version,
+    pub host_data: *mut c_void,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub version: *const c_char,
+    pub get_extension: unsafe extern "C" fn(host: *const Host, id: *const c_char) -> *const c_void,
+    pub request_restart: unsafe extern "C" fn(host: *const Host),
+    pub request_process: unsafe extern "C" fn(host: *const Host),
+    pub request_callback: unsafe extern "C" fn(host: *const Host),
+}
+
+#[repr(C)]
+pub struct Plugin {
+    pub desc: *const PluginDescriptor,
+    pub plugin_data: *mut c_void,
+    pub init: unsafe extern "C" fn(plugin: *const Plugin) -> bool,
+    pub destroy: unsafe extern "C" fn(plugin: *const Plugin),
+    pub activate: unsafe extern "C" fn(
+        plugin: *const Plugin,
+        sample_rate: f64,
+        min_frames: u32,
+        max_frames: u32,
+    ) -> bool,
+    pub deactivate: unsafe extern "C" fn(plugin: *const Plugin),
+    pub start_processing: unsafe extern "C" fn(plugin: *const Plugin) -> bool,
+    pub stop_processing: unsafe extern "C" fn(plugin: *const Plugin),
+    pub reset: unsafe extern "C" fn(plugin: *const Plugin),
+    pub process: unsafe extern "C" fn(plugin: *const Plugin, process: *const Process) -> i32,
+    pub get_extension:
+        unsafe extern "C" fn(plugin: *const Plugin, id: *const c_char) -> *const c_void,
+    pub on_main_thread: unsafe extern "C" fn(plugin: *const Plugin),
+}
+
+#[repr(C)]
+pub struct AudioBuffer {
+    pub data32: *mut *mut f32,
+    pub data64: *mut *mut f64,
+    pub channel_count: u32,
+    pub latency: u32,
+    pub constant_mask: u64,
+}
+
+#[repr(C)]
+pub struct EventHeader {
+    pub size: u32,
+    pub time: u32,
+    pub space_id: u16,
+    pub event_type: u16,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct ParamValueEvent {
+    pub header: EventHeader,
+    pub param_id: u32,
+    pub cookie: *mut c_void,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    pub value: f64,
+}
+
+#[repr(C)]
+pub struct InputEvents {
+    pub ctx: *mut c_void,
+    pub size: unsafe extern "C" fn(list: *const InputEvents) -> u32,
+    pub get: unsafe extern "C" fn(list: *const InputEvents, index: u32) -> *const EventHeader,
+}
+
+#[repr(C)]
+pub struct OutputEvents {
+    pub ctx: *mut c_void,
+    pub try_push:
+        unsafe extern "C" fn(list: *const OutputEvents, event: *const EventHeader) -> bool,
+}
+
+#[repr(C)]
+pub struct Process {
+    pub steady_time: i64,
+    pub frames_count: u32,
+    /// null, there's no transport to follow
+    pub transport: *const c_void,
+    pub audio_inputs: *const AudioBuffer,
+    pub audio_outputs: *mut AudioBuffer,
+    pub audio_inputs_count: u32,
+    pub audio_outputs_count: u32,
+    pub in_events: *const InputEvents,
+    pub out_events: *const OutputEvents,
+}
+
+#[repr(C)]
+pub struct ParamInfo {
+    pub id: u32,
+    pub flags: u32,
+    pub cookie: *mut c_void,
+    pub name: [c_char; NAME_SIZE],
+    pub module: [c_char; PATH_SIZE],
+    pub min_value: f64,
+    pub max_value: f64,
+    pub default_value: f64,
+}
+
+#[repr(C)]
+pub struct PluginParams {
+    pub count: unsafe extern "C" fn(plugin: *const Plugin) -> u32,
+    pub get_info:
+        unsafe extern "C" fn(plugin: *const Plugin, index: u32, info: *mut ParamInfo) -> bool,
+    pub get_value: unsafe extern "C" fn(plugin: *const Plugin, id: u32, value: *mut f64) -> bool,
+    pub value_to_text: unsafe extern "C" fn(
+        plugin: *const Plugin,
+        id: u32,
+        value: f64,
+        out: *mut c_char,
+        size: u32,
+    ) -> bool,
+    pub text_to_value: unsafe extern "C" fn(
+        plugin: *const Plugin,
+        id: u32,
+        text: *const c_char,
+        value: *mut f64,
+    ) -> bool,
+    pub flush: unsafe extern "C" fn(
+        plugin: *const Plugin,
+        in_events: *const InputEvents,
+        out_events: *const OutputEvents,
+    ),
+}
@@ -0,0 +1,764 @@
+//! A CLAP effect inserted on the output, after the engine and before the
+//! device.
+//!
+//! `insert = "<path>.clap"` in the config, or `--insert <path>`, loads the
+//! first effect in the file. `audio::Supervisor` activates it at the stream's
+//! rate whenever it builds a stream and runs it over whatever the engine
+//! renders, so every app can have one. `HOTKEY` opens an editor listing the
+//! effect's parameters.
+//!
+//! Only `--render` goes without, it doesn't run a stream.
+
+use crate::render::Render;
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::{
+    ffi::{c_void, CStr, CString},
+    fmt, mem,
+    os::raw::c_char,
+    path::{Path, PathBuf},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+mod clap;
+
+/// toggles the parameter editor
+pub const HOTKEY: Key = Key::I;
+
+/// frames the effect is given at a time
+const BLOCK: usize = 512;
+/// a stereo effect, mono engines are doubled into it and mixed back down
+const CHANNELS: usize = 2;
+/// parameter changes passed each way between buffers
+const EVENTS: usize = 64;
+/// of a parameter's range, a press of left or right
+const STEP: f64 = 0.01;
+/// processors on their way back to the main thread, only ever one at a time
+/// while it keeps up
+const RETIRED: usize = 4;
+
+const HOST_NAME: &[u8] = b"nannou-apps\0";
+const EMPTY: &[u8] = b"\0";
+const HOST_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+#[derive(Debug)]
+pub enum Error {
+    /// the file couldn't be loaded or isn't a CLAP plugin
+    Library(String),
+    /// built for a CLAP major version this host doesn't know
+    Version(u32),
+    /// it has no plugins, or the one picked wouldn't start
+    Plugin(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Library(e) => write!(f, "cannot load plugin: {}", e),
+            Error::Version(major) => write!(f, "plugin is for CLAP {}.x", major),
+            Error::Plugin(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A parameter as the plugin describes it.
+#[derive(Clone, Debug)]
+pub struct ParamInfo {
+    pub id: u32,
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    /// whole numbers only
+    pub stepped: bool,
+    /// shown but not editable
+    pub read_only: bool,
+}
+
+/// What the plugin asked of the host, from any thread.
+#[derive(Default)]
+struct Requests {
+    callback: AtomicBool,
+    restart: AtomicBool,
+}
+
+/// The library and the plugin made from it, destroyed with the last of
+/// `Plugin` and `Processor`.
+struct Instance {
+    plugin: *const clap::Plugin,
+    /// null if it has no parameters
+    params: *const clap::PluginParams,
+    entry: *const clap::PluginEntry,
+    /// the plugin keeps pointers to both
+    host: Box<clap::Host>,
+    requests: Box<Requests>,
+    active: AtomicBool,
+    // keeps everything above loaded
+    _library: libloading::Library,
+}
+
+// each function is only called from the thread CLAP says it may be
+unsafe impl Send for Instance {}
+unsafe impl Sync for Instance {}
+
+/// the library inside a macOS bundle, or `path` itself elsewhere
+fn binary(path: &Path) -> PathBuf {
+    match path.file_stem() {
+        Some(stem) if path.is_dir() => path.join("Contents").join("MacOS").join(stem),
+        _ => path.to_path_buf(),
+    }
+}
+
+unsafe fn text(s: *const c_char) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+/// a fixed size C string, up to its first nul
+fn array_text(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// the first plugin describing itself as an audio effect, else the first
+unsafe fn pick(factory: *const clap::PluginFactory) -> Option<*const c_char> {
+    let count = ((*factory).get_plugin_count)(factory);
+    let descriptors: Vec<&clap::PluginDescriptor> = (0..count)
+        .filter_map(|i| ((*factory).get_plugin_descriptor)(factory, i).as_ref())
+        .collect();
+    let is_effect = |descriptor: &&clap::PluginDescriptor| {
+        let mut feature = descriptor.features;
+        while !feature.is_null() && !(*feature).is_null() {
+            if CStr::from_ptr(*feature).to_bytes() == clap::FEATURE_AUDIO_EFFECT {
+                return true;
+            }
+            feature = feature.add(1);
+        }
+        false
+    };
+    let effect = descriptors.iter().copied().find(is_effect);
+    effect
+        .or_else(|| descriptors.first().copied())
+        .map(|descriptor| descriptor.id)
+}
+
+unsafe extern "C" fn get_extension(_host: *const clap::Host, _id: *const c_char) -> *const c_void {
+    ptr::null()
+}
+
+unsafe fn requests<'a>(host: *const clap::Host) -> &'a Requests {
+    &*((*host).host_data as *const Requests)
+}
+
+unsafe extern "C" fn request_restart(host: *const clap::Host) {
+    requests(host).restart.store(true, Ordering::Relaxed);
+}
+
+/// it's processed every buffer anyway
+unsafe extern "C" fn request_process(_host: *const clap::Host) {}
+
+unsafe extern "C" fn request_callback(host: *const clap::Host) {
+    requests(host).callback.store(true, Ordering::Relaxed);
+}
+
+impl Instance {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let failed = |e: String| Error::Library(format!("{}: {}", path.display(), e));
+        // the plugin runs its own code from here on, it's trusted like the app
+        let library =
+            unsafe { libloading::Library::new(binary(path)) }.map_err(|e| failed(e.to_string()))?;
+        unsafe {
+            let entry = *library
+                .get::<*const clap::PluginEntry>(b"clap_entry\0")
+                .map_err(|e| failed(e.to_string()))?;
+            let major = (*entry).clap_version.major;
+            if major != clap::VERSION.major {
+                return Err(Error::Version(major));
+            }
+            let c_path = CString::new(path.to_string_lossy().as_bytes())
+                .map_err(|e| failed(e.to_string()))?;
+            if !((*entry).init)(c_path.as_ptr()) {
+                return Err(failed("it wouldn't initialise".into()));
+            }
+
+            let requests = Box::new(Requests::default());
+            let host = Box::new(clap::Host {
+                clap_version: clap::VERSION,
+                host_data: &*requests as *const Requests as *mut c_void,
+                name: HOST_NAME.as_ptr().cast(),
+                vendor: EMPTY.as_ptr().cast(),
+                url: EMPTY.as_ptr().cast(),
+                version: HOST_VERSION.as_ptr().cast(),
+                get_extension,
+                request_restart,
+                request_process,
+                request_callback,
+            });
+            // deinitialised when dropped from here on, whatever fails
+            let mut instance = Self {
+                plugin: ptr::null(),
+                params: ptr::null(),
+                entry,
+                host,
+                requests,
+                active: AtomicBool::new(false),
+                _library: library,
+            };
+
+            let factory = ((*entry).get_factory)(clap::PLUGIN_FACTORY_ID.as_ptr().cast())
+                as *const clap::PluginFactory;
+            if factory.is_null() {
+                return Err(failed("no plugin factory".into()));
+            }
+            let id = pick(factory).ok_or_else(|| failed("no plugins in it".into()))?;
+            let plugin = ((*factory).create_plugin)(factory, &*instance.host, id);
+            if plugin.is_null() {
+                return Err(failed(format!("cannot create {}", text(id))));
+            }
+            instance.plugin = plugin;
+            if !((*plugin).init)(plugin) {
+                return Err(failed(format!("{} wouldn't initialise", text(id))));
+            }
+            instance.params = ((*plugin).get_extension)(plugin, clap::EXT_PARAMS.as_ptr().cast())
+                as *const clap::PluginParams;
+            Ok(instance)
+        }
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.plugin.is_null() {
+                if self.active.load(Ordering::Relaxed) {
+                    ((*self.plugin).deactivate)(self.plugin);
+                }
+                ((*self.plugin).destroy)(self.plugin);
+            }
+            ((*self.entry).deinit)();
+        }
+    }
+}
+
+fn param_value(id: u32, value: f64) -> clap::ParamValueEvent {
+    clap::ParamValueEvent {
+        header: clap::EventHeader {
+            size: mem::size_of::<clap::ParamValueEvent>() as u32,
+            time: 0,
+            space_id: clap::CORE_EVENT_SPACE_ID,
+            event_type: clap::EVENT_PARAM_VALUE,
+            flags: 0,
+        },
+        param_id: id,
+        cookie: ptr::null_mut(),
+        // any note, port, channel and key
+        note_id: -1,
+        port_index: -1,
+        channel: -1,
+        key: -1,
+        value,
+    }
+}
+
+/// `InputEvents` over a `Vec<clap::ParamValueEvent>` as its `ctx`
+fn input_events(events: &mut Vec<clap::ParamValueEvent>) -> clap::InputEvents {
+    unsafe extern "C" fn size(list: *const clap::InputEvents) -> u32 {
+        (*((*list).ctx as *const Vec<clap::ParamValueEvent>)).len() as u32
+    }
+    unsafe extern "C" fn get(
+        list: *const clap::InputEvents,
+        index: u32,
+    ) -> *const clap::EventHeader {
+        let events = &*((*list).ctx as *const Vec<clap::ParamValueEvent>);
+        events
+            .get(index as usize)
+            .map_or(ptr::null(), |event| &event.header)
+    }
+    clap::InputEvents {
+        ctx: events as *mut Vec<clap::ParamValueEvent> as *mut c_void,
+        size,
+        get,
+    }
+}
+
+/// `OutputEvents` passing parameter changes on to a `Producer` as its
+/// `ctx`, or dropping everything with a null one
+fn output_events(to_ui: Option<&mut Producer<(u32, f64)>>) -> clap::OutputEvents {
+    unsafe extern "C" fn try_push(
+        list: *const clap::OutputEvents,
+        event: *const clap::EventHeader,
+    ) -> bool {
+        let to_ui = (*list).ctx as *mut Producer<(u32, f64)>;
+        let header = &*event;
+        if !to_ui.is_null()
+            && header.space_id == clap::CORE_EVENT_SPACE_ID
+            && header.event_type == clap::EVENT_PARAM_VALUE
+        {
+            let event = &*(event as *const clap::ParamValueEvent);
+            // the editor catches up with the next one if it's behind
+            let _ = (*to_ui).push((event.param_id, event.value));
+        }
+        true
+    }
+    clap::OutputEvents {
+        ctx: to_ui.map_or(ptr::null_mut(), |to_ui| to_ui as *mut _ as *mut c_void),
+        try_push,
+    }
+}
+
+/// A loaded effect on the main thread, its parameters as last heard and
+/// the editor's state.
+pub struct Plugin {
+    instance: Arc<Instance>,
+    name: String,
+    path: PathBuf,
+    params: Vec<ParamInfo>,
+    values: Vec<f64>,
+    /// `None` while inactive
+    to_audio: Option<Producer<(u32, f64)>>,
+    from_audio: Option<Consumer<(u32, f64)>>,
+    /// what it's active at
+    sample_rate: Option<u32>,
+    /// what the stream runs at as the processor found it, 0 before that
+    stream_rate: Arc<AtomicU32>,
+    editor: bool,
+    selected: usize,
+}
+
+impl Plugin {
+    /// the first effect in the `.clap` file at `path`, inactive
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let instance = Instance::load(path)?;
+        let plugin = instance.plugin;
+        let name = unsafe { text((*(*plugin).desc).name) };
+        let mut params = Vec::new();
+        let mut values = Vec::new();
+        if let Some(ext) = unsafe { instance.params.as_ref() } {
+            for index in 0..unsafe { (ext.count)(plugin) } {
+                let mut info: clap::ParamInfo = unsafe { mem::zeroed() };
+                if !unsafe { (ext.get_info)(plugin, index, &mut info) }
+                    || info.flags & clap::PARAM_IS_HIDDEN != 0
+                {
+                    continue;
+                }
+                let mut value = info.default_value;
+                unsafe { (ext.get_value)(plugin, info.id, &mut value) };
+                params.push(ParamInfo {
+                    id: info.id,
+                    name: array_text(&info.name),
+                    min: info.min_value,
+                    max: info.max_value,
+                    default: info.default_value,
+                    stepped: info.flags & clap::PARAM_IS_STEPPED != 0,
+                    read_only: info.flags & clap::PARAM_IS_READONLY != 0,
+                });
+                values.push(value);
+            }
+        }
+        Ok(Self {
+            instance: Arc::new(instance),
+            name,
+            path: path.to_path_buf(),
+            params,
+            values,
+            to_audio: None,
+            from_audio: None,
+            sample_rate: None,
+            stream_rate: Arc::new(AtomicU32::new(0)),
+            editor: false,
+            selected: 0,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    pub fn value(&self, index: usize) -> f64 {
+        self.values[index]
+    }
+
+    /// the value the way the plugin writes it, with its unit
+    pub fn text(&self, index: usize) -> String {
+        let (id, value) = (self.params[index].id, self.values[index]);
+        let mut out = [0 as c_char; 64];
+        let written = unsafe { self.instance.params.as_ref() }.map_or(false, |ext| unsafe {
+            (ext.value_to_text)(self.instance.plugin, id, value, out.as_mut_ptr(), 64)
+        });
+        if written {
+            array_text(&out)
+        } else {
+            format!("{:.3}", value)
+        }
+    }
+
+    /// clamped to the parameter's range, read only ones are left alone
+    pub fn set(&mut self, index: usize, value: f64) {
+        let info = match self.params.get(index) {
+            Some(info) if !info.read_only => info,
+            _ => return,
+        };
+        let mut value = value.max(info.min).min(info.max);
+        if info.stepped {
+            value = value.round();
+        }
+        self.values[index] = value;
+        match &mut self.to_audio {
+            Some(to_audio) => {
+                let _ = to_audio.push((info.id, value));
+            }
+            // an inactive plugin takes changes on the main thread
+            None => {
+                if let Some(ext) = unsafe { self.instance.params.as_ref() } {
+                    let mut events = vec![param_value(info.id, value)];
+                    let (input, output) = (input_events(&mut events), output_events(None));
+                    unsafe { (ext.flush)(self.instance.plugin, &input, &output) };
+                }
+            }
+        }
+    }
+
+    /// on the main thread while no processor is running
+    pub(crate) fn activate(&mut self, sample_rate: u32) -> Result<Processor, Error> {
+        let plugin = self.instance.plugin;
+        if !unsafe { ((*plugin).activate)(plugin, f64::from(sample_rate), 1, BLOCK as u32) } {
+            return Err(Error::Plugin(format!(
+                "{} wouldn't start at {}Hz",
+                self.name, sample_rate
+            )));
+        }
+        self.instance.active.store(true, Ordering::Relaxed);
+        let (to_audio, from_ui) = RingBuffer::new(EVENTS).split();
+        let (to_ui, from_audio) = RingBuffer::new(EVENTS).split();
+        self.to_audio = Some(to_audio);
+        self.from_audio = Some(from_audio);
+        self.sample_rate = Some(sample_rate);
+        Ok(Processor {
+            instance: self.instance.clone(),
+            sample_rate,
+            stream_rate: self.stream_rate.clone(),
+            started: false,
+            from_ui,
+            to_ui,
+            events: Vec::with_capacity(EVENTS),
+            input: vec![0.0; BLOCK * CHANNELS],
+            output: vec![0.0; BLOCK * CHANNELS],
+            steady_time: 0,
+        })
+    }
+
+    /// once its processor is dropped
+    pub(crate) fn deactivate(&mut self) {
+        if self.instance.active.swap(false, Ordering::Relaxed) {
+            unsafe { ((*self.instance.plugin).deactivate)(self.instance.plugin) };
+        }
+        self.to_audio = None;
+        self.from_audio = None;
+        self.sample_rate = None;
+    }
+
+    /// the rate to activate at, the stream's if it has run
+    pub(crate) fn stream_rate(&self) -> Option<u32> {
+        match self.stream_rate.load(Ordering::Relaxed) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+
+    /// for a stream at a rate of its own, see `audio::Supervisor::set_config`
+    pub(crate) fn forget_stream_rate(&self) {
+        self.stream_rate.store(0, Ordering::Relaxed);
+    }
+
+    /// called at frame rate, true if it needs activating again
+    pub(crate) fn poll(&mut self) -> bool {
+        let plugin = self.instance.plugin;
+        if self
+            .instance
+            .requests
+            .callback
+            .swap(false, Ordering::Relaxed)
+        {
+            unsafe { ((*plugin).on_main_thread)(plugin) };
+        }
+        if let Some(from_audio) = &mut self.from_audio {
+            while let Some((id, value)) = from_audio.pop() {
+                if let Some(index) = self.params.iter().position(|info| info.id == id) {
+                    self.values[index] = value;
+                }
+            }
+        }
+        let restart = self
+            .instance
+            .requests
+            .restart
+            .swap(false, Ordering::Relaxed);
+        let moved = match (self.sample_rate, self.stream_rate()) {
+            (Some(active), Some(stream)) => active != stream,
+            _ => false,
+        };
+        restart || moved
+    }
+
+    /// true if the key was for the editor
+    pub fn key_pressed(&mut self, key: Key) -> bool {
+        if key == HOTKEY {
+            self.editor = !self.editor;
+            return true;
+        }
+        if !self.editor || self.params.is_empty() {
+            return false;
+        }
+        let index = self.selected;
+        let info = &self.params[index];
+        let default = info.default;
+        let step = if info.stepped {
+            1.0
+        } else {
+            (info.max - info.min) * STEP
+        };
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.params.len() - 1),
+            Key::Left => self.set(index, self.values[index] - step),
+            Key::Right => self.set(index, self.values[index] + step),
+            Key::Back | Key::Delete => self.set(index, default),
+            Key::Escape => self.editor = false,
+            _ => return false,
+        }
+        true
+    }
+
+    /// the editor when open, as many parameters as fit around the selected
+    /// one
+    pub fn draw(&self, draw: &Draw, rect: Rect, palette: &Palette) {
+        const LINE: f32 = 22.0;
+
+        if !self.editor {
+            return;
+        }
+        let text = theme::color(palette.line);
+        let accent = theme::color(palette.accent(0));
+        let [r, g, b] = palette.background;
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgba(r, g, b, 0.85));
+        let mut y = rect.top() - 2.0 * LINE;
+        let mut line = |message: &str, color: Rgb, size: u32| {
+            draw.text(message)
+                .x_y(0.0, y)
+                .w_h(rect.w() - 4.0 * LINE, LINE)
+                .left_justify()
+                .font_size(size)
+                .color(color);
+            y -= LINE;
+        };
+
+        line(&format!("{} on the output", self.name), text, 14);
+        line("", text, 14);
+        if self.params.is_empty() {
+            line("it has no parameters", text, 14);
+        }
+        let fits = ((rect.h() / LINE) as usize).saturating_sub(6).max(1);
+        let first = self
+            .selected
+            .saturating_sub(fits / 2)
+            .min(self.params.len().saturating_sub(fits));
+        for (i, info) in self.params.iter().enumerate().skip(first).take(fits) {
+            let marker = if i == self.selected { ">" } else { " " };
+            let color = if i == self.selected { accent } else { text };
+            let locked = if info.read_only { " (read only)" } else { "" };
+            line(
+                &format!("{} {}: {}{}", marker, info.name, self.text(i), locked),
+                color,
+                14,
+            );
+        }
+        line("", text, 14);
+        line(
+            "up and down pick a parameter, left and right change it, backspace resets, i closes",
+            text,
+            12,
+        );
+    }
+}
+
+/// The effect on the audio thread.
+pub struct Processor {
+    instance: Arc<Instance>,
+    sample_rate: u32,
+    stream_rate: Arc<AtomicU32>,
+    started: bool,
+    from_ui: Consumer<(u32, f64)>,
+    to_ui: Producer<(u32, f64)>,
+    /// this buffer's parameter changes, never more than `EVENTS`
+    events: Vec<clap::ParamValueEvent>,
+    /// a channel after the other
+    input: Vec<f32>,
+    output: Vec<f32>,
+    steady_time: i64,
+}
+
+// the events' cookies are always null, the instance is shared by design
+unsafe impl Send for Processor {}
+
+impl Processor {
+    /// on the thread it processed on, before it's dropped anywhere else
+    fn stop(&mut self) {
+        if self.started {
+            unsafe { ((*self.instance.plugin).stop_processing)(self.instance.plugin) };
+            self.started = false;
+        }
+    }
+
+    /// over `out` in place, left dry if the plugin fails or the stream isn't
+    /// at the rate it was activated at
+    fn process(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            // `Supervisor` activates it again at this rate
+            self.stream_rate.store(sample_rate, Ordering::Relaxed);
+            return;
+        }
+        self.stream_rate.store(sample_rate, Ordering::Relaxed);
+        let plugin = self.instance.plugin;
+        if !self.started {
+            self.started = unsafe { ((*plugin).start_processing)(plugin) };
+            if !self.started {
+                return;
+            }
+        }
+
+        self.events.clear();
+        while self.events.len() < EVENTS {
+            match self.from_ui.pop() {
+                Some((id, value)) => self.events.push(param_value(id, value)),
+                None => break,
+            }
+        }
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let frames = chunk.len() / channels;
+            let (left, right) = self.input.split_at_mut(BLOCK);
+            for (i, frame) in chunk.chunks_exact(channels).enumerate() {
+                left[i] = frame[0];
+                right[i] = frame[1.min(channels - 1)];
+            }
+            let mut inputs = [left.as_mut_ptr(), right.as_mut_ptr()];
+            let (left, right) = self.output.split_at_mut(BLOCK);
+            let mut outputs = [left.as_mut_ptr(), right.as_mut_ptr()];
+            let input = clap::AudioBuffer {
+                data32: inputs.as_mut_ptr(),
+                data64: ptr::null_mut(),
+                channel_count: CHANNELS as u32,
+                latency: 0,
+                constant_mask: 0,
+            };
+            let mut output = clap::AudioBuffer {
+                data32: outputs.as_mut_ptr(),
+                ..input
+            };
+            let in_events = input_events(&mut self.events);
+            let out_events = output_events(Some(&mut self.to_ui));
+            let process = clap::Process {
+                steady_time: self.steady_time,
+                frames_count: frames as u32,
+                transport: ptr::null(),
+                audio_inputs: &input,
+                audio_outputs: &mut output,
+                audio_inputs_count: 1,
+                audio_outputs_count: 1,
+                in_events: &in_events,
+                out_events: &out_events,
+            };
+            let status = unsafe { ((*plugin).process)(plugin, &process) };
+            // the changes went with the first block
+            self.events.clear();
+            self.steady_time += frames as i64;
+            if status == clap::PROCESS_ERROR {
+                continue;
+            }
+            let (left, right) = self.output.split_at(BLOCK);
+            for (i, frame) in chunk.chunks_exact_mut(channels).enumerate() {
+                if channels == 1 {
+                    frame[0] = 0.5 * (left[i] + right[i]);
+                } else {
+                    frame[0] = left[i];
+                    frame[1] = right[i];
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Processor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// An engine with the effect, if any, over what it renders.
+pub(crate) struct Insert<R> {
+    engine: R,
+    processor: Option<Processor>,
+    /// processors taken out, for the main thread to drop
+    retired: Producer<Processor>,
+}
+
+impl<R: Render> Insert<R> {
+    /// and where the processors it lets go of turn up, see `set_processor`
+    pub(crate) fn new(engine: R) -> (Self, Consumer<Processor>) {
+        let (retired, from_audio) = RingBuffer::new(RETIRED).split();
+        let insert = Self {
+            engine,
+            processor: None,
+            retired,
+        };
+        (insert, from_audio)
+    }
+
+    pub(crate) fn engine_mut(&mut self) -> &mut R {
+        &mut self.engine
+    }
+
+    /// The old one stops processing here and is passed on to be dropped on
+    /// the main thread, where the plugin may be deactivated and destroyed
+    /// with it.
+    pub(crate) fn set_processor(&mut self, processor: Option<Processor>) {
+        if let Some(mut old) = mem::replace(&mut self.processor, processor) {
+            old.stop();
+            // full only if nothing has been draining it, then it goes here
+            let _ = self.retired.push(old);
+        }
+    }
+}
+
+impl<R: Render> Render for Insert<R> {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        self.engine.render(out, channels, sample_rate);
+        if let Some(processor) = &mut self.processor {
+            processor.process(out, channels, sample_rate);
+        }
+    }
+}
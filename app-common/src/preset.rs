@@ -0,0 +1,291 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A named, versioned snapshot of an app's state.
+///
+/// Presets are stored as `{ version, preset }` so that older files can be
+/// upgraded through `migrate` before being deserialized into the current type.
+pub trait Preset: Serialize + DeserializeOwned {
+    /// directory name the presets are stored under
+    const APP: &'static str;
+    /// bump whenever the serialized fields change
+    const VERSION: u32;
+
+    /// upgrade `value` in place from `version` to `version + 1`
+    fn migrate(version: u32, _value: &mut Value) -> Result<(), Error> {
+        Err(Error::Version {
+            found: version,
+            supported: Self::VERSION,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    Version {
+        found: u32,
+        supported: u32,
+    },
+    NotFound(String),
+    /// would land outside the preset directory, or is no file name at all
+    Name(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "preset io error: {}", e),
+            Error::Parse(e) => write!(f, "malformed preset: {}", e),
+            Error::Version { found, supported } => write!(
+                f,
+                "preset version {} cannot be read by version {}",
+                found, supported
+            ),
+            Error::NotFound(name) => write!(f, "no preset named {}", name),
+            Error::Name(name) => write!(f, "{:?} cannot be a preset name", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(e: toml::ser::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Toml, Format::Json];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Format::ALL.iter().copied().find(|f| f.extension() == ext)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    preset: T,
+}
+
+/// per-app preset directory inside the platform data directory
+pub fn dir(app: &str) -> PathBuf {
+    directories::ProjectDirs::from("", "", app)
+        .map(|dirs| dirs.data_dir().join("presets"))
+        .unwrap_or_else(|| PathBuf::from("presets"))
+}
+
+pub fn path<P: Preset>(name: &str, format: Format) -> PathBuf {
+    dir(P::APP).join(format!("{}.{}", name, format.extension()))
+}
+
+pub fn to_string<P: Preset>(preset: &P, format: Format) -> Result<String, Error> {
+    let envelope = Envelope {
+        version: P::VERSION,
+        preset,
+    };
+    Ok(match format {
+        // going through `toml::Value` orders plain values before tables
+        Format::Toml => toml::to_string_pretty(&toml::Value::try_from(&envelope)?)?,
+        Format::Json => serde_json::to_string_pretty(&envelope)?,
+    })
+}
+
+pub fn from_str<P: Preset>(text: &str, format: Format) -> Result<P, Error> {
+    let mut value: Value = match format {
+        Format::Toml => toml::from_str(text)?,
+        Format::Json => serde_json::from_str(text)?,
+    };
+
+    let mut version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| Error::Parse("missing version".into()))? as u32;
+
+    if version > P::VERSION {
+        return Err(Error::Version {
+            found: version,
+            supported: P::VERSION,
+        });
+    }
+
+    let preset = value
+        .get_mut("preset")
+        .ok_or_else(|| Error::Parse("missing preset table".into()))?;
+
+    while version < P::VERSION {
+        P::migrate(version, preset)?;
+        version += 1;
+    }
+
+    Ok(serde_json::from_value(preset.take())?)
+}
+
+/// `name` as a single file name, with no separators or `..` to step out
+/// of the preset directory
+pub fn check_name(name: &str) -> Result<(), Error> {
+    let mut components = Path::new(name).components();
+    let single =
+        matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none();
+    // backslashes are separators on Windows and suspect everywhere else
+    if single && !name.contains(['/', '\\']) {
+        Ok(())
+    } else {
+        Err(Error::Name(name.into()))
+    }
+}
+
+pub fn save<P: Preset>(name: &str, preset: &P, format: Format) -> Result<PathBuf, Error> {
+    check_name(name)?;
+    let path = path::<P>(name, format);
+    fs::create_dir_all(dir(P::APP))?;
+    fs::write(&path, to_string(preset, format)?)?;
+    Ok(path)
+}
+
+pub fn load_path<P: Preset>(path: &Path) -> Result<P, Error> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| Error::Parse(format!("unknown preset format {}", path.display())))?;
+    from_str(&fs::read_to_string(path)?, format)
+}
+
+/// load by name, whichever format it was saved in
+pub fn load<P: Preset>(name: &str) -> Result<P, Error> {
+    Format::ALL
+        .iter()
+        .map(|&format| path::<P>(name, format))
+        .find(|path| path.exists())
+        .ok_or_else(|| Error::NotFound(name.into()))
+        .and_then(|path| load_path(&path))
+}
+
+/// sorted preset names available for `app`
+pub fn list(app: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir(app))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| Format::from_path(path).is_some())
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// version 1 called the cutoff `freq` and had no drive
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Filter {
+        cutoff: f32,
+        resonance: f32,
+        drive: f32,
+    }
+
+    impl Preset for Filter {
+        const APP: &'static str = "preset-tests";
+        const VERSION: u32 = 3;
+
+        fn migrate(version: u32, value: &mut Value) -> Result<(), Error> {
+            let table = value
+                .as_object_mut()
+                .ok_or_else(|| Error::Parse("not a table".into()))?;
+            match version {
+                1 => {
+                    let freq = table.remove("freq").unwrap_or(Value::from(1000.0));
+                    table.insert("cutoff".into(), freq);
+                }
+                2 => {
+                    table.insert("drive".into(), Value::from(0.0));
+                }
+                _ => unreachable!(),
+            }
+            Ok(())
+        }
+    }
+
+    const FILTER: Filter = Filter {
+        cutoff: 440.0,
+        resonance: 0.5,
+        drive: 0.0,
+    };
+
+    #[test]
+    fn old_versions_are_migrated_on_load() {
+        let text = "
+            version = 1
+
+            [preset]
+            freq = 440.0
+            resonance = 0.5
+        ";
+        assert_eq!(from_str::<Filter>(text, Format::Toml).unwrap(), FILTER);
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        for &format in &Format::ALL {
+            let text = to_string(&FILTER, format).unwrap();
+            assert_eq!(from_str::<Filter>(&text, format).unwrap(), FILTER);
+        }
+    }
+
+    #[test]
+    fn newer_or_unversioned_presets_are_refused() {
+        let newer = "version = 4\n[preset]\ncutoff = 440.0\n";
+        assert!(matches!(
+            from_str::<Filter>(newer, Format::Toml),
+            Err(Error::Version {
+                found: 4,
+                supported: 3
+            })
+        ));
+        let unversioned = "[preset]\ncutoff = 440.0\n";
+        assert!(matches!(
+            from_str::<Filter>(unversioned, Format::Toml),
+            Err(Error::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+const HEADER_LEN: u64 = 54;
+const BYTES_PER_SAMPLE: u32 = 3;
+
+/// Minimal 24-bit PCM AIFF writer, sizes are patched in on `finalize`.
+pub struct AiffWriter<W: Write + Seek> {
+    out: W,
+    channels: u16,
+    sample_rate: u32,
+    samples: u32,
+}
+
+impl<W: Write + Seek> AiffWriter<W> {
+    pub fn new(mut out: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        out.write_all(&[0; HEADER_LEN as usize])?;
+        Ok(Self {
+            out,
+            channels,
+            sample_rate,
+            samples: 0,
+        })
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let s = (sample.clamp(-1.0, 1.0) * 8_388_607.0) as i32;
+        self.out
+            .write_all(&[(s >> 16) as u8, (s >> 8) as u8, s as u8])?;
+        self.samples += 1;
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> io::Result<()> {
+        let data_len = self.samples * BYTES_PER_SAMPLE;
+        if data_len % 2 == 1 {
+            self.out.write_all(&[0])?;
+        }
+        let padded_len = data_len + data_len % 2;
+        let frames = self.samples / self.channels as u32;
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"FORM");
+        header.extend_from_slice(&(4 + 26 + 16 + padded_len).to_be_bytes());
+        header.extend_from_slice(b"AIFF");
+        header.extend_from_slice(b"COMM");
+        header.extend_from_slice(&18u32.to_be_bytes());
+        header.extend_from_slice(&self.channels.to_be_bytes());
+        header.extend_from_slice(&frames.to_be_bytes());
+        header.extend_from_slice(&(BYTES_PER_SAMPLE as u16 * 8).to_be_bytes());
+        header.extend_from_slice(&extended(self.sample_rate));
+        header.extend_from_slice(b"SSND");
+        header.extend_from_slice(&(8 + data_len).to_be_bytes());
+        header.extend_from_slice(&0u32.to_be_bytes());
+        header.extend_from_slice(&0u32.to_be_bytes());
+
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header)?;
+        self.out.flush()
+    }
+}
+
+/// 80-bit IEEE 754 extended precision, as AIFF wants its sample rate
+fn extended(value: u32) -> [u8; 10] {
+    let mut bytes = [0; 10];
+    if value == 0 {
+        return bytes;
+    }
+    let exponent = 31 - value.leading_zeros();
+    let mantissa = (value as u64) << (63 - exponent);
+    bytes[..2].copy_from_slice(&(16383 + exponent as u16).to_be_bytes());
+    bytes[2..].copy_from_slice(&mantissa.to_be_bytes());
+    bytes
+}
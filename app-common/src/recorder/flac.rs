@@ -0,0 +1,343 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// frames per FLAC frame, every one but the last
+const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 24;
+/// where the sample rate, channels, depth and length start in STREAMINFO
+const INFO_OFFSET: u64 = 18;
+/// fixed predictors up to this order are tried on each channel
+const MAX_ORDER: usize = 4;
+/// residual partitions up to 2^this are tried
+const MAX_PARTITION_ORDER: u32 = 4;
+/// the highest Rice parameter with 4 bits for it, 15 escapes
+const MAX_RICE: u32 = 14;
+
+/// Minimal 24-bit FLAC writer: fixed predictors and Rice coded residuals on
+/// each channel on its own, about what `flac -3` gets. The length is
+/// patched in on `finalize`, the MD5 is left unset as the format allows.
+pub struct FlacWriter<W: Write + Seek> {
+    out: W,
+    channels: usize,
+    sample_rate: u32,
+    /// interleaved samples of the frame being filled
+    block: Vec<i32>,
+    /// one channel of `block` at a time
+    channel: Vec<i64>,
+    residual: Vec<i64>,
+    bits: BitWriter,
+    frames: u64,
+    /// FLAC frames written, numbered in their headers
+    blocks: u64,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut out: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let channels = channels.max(1) as usize;
+        let mut header = Vec::with_capacity(42);
+        header.extend_from_slice(b"fLaC");
+        // the only metadata block, so flagged as the last
+        header.extend_from_slice(&[0x80, 0, 0, 34]);
+        header.extend_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+        header.extend_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+        // frame sizes unknown
+        header.extend_from_slice(&[0; 6]);
+        header.extend_from_slice(&stream_info(sample_rate, channels, 0));
+        header.extend_from_slice(&[0; 16]);
+        out.write_all(&header)?;
+        Ok(Self {
+            out,
+            channels,
+            sample_rate,
+            block: Vec::with_capacity(BLOCK_SIZE * channels),
+            channel: Vec::with_capacity(BLOCK_SIZE),
+            residual: Vec::with_capacity(BLOCK_SIZE),
+            bits: BitWriter::default(),
+            frames: 0,
+            blocks: 0,
+        })
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        self.block
+            .push((sample.clamp(-1.0, 1.0) * 8_388_607.0) as i32);
+        if self.block.len() == BLOCK_SIZE * self.channels {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> io::Result<()> {
+        // a frame cut short in the middle is dropped, like the other writers do
+        self.block
+            .truncate(self.block.len() / self.channels * self.channels);
+        if !self.block.is_empty() {
+            self.write_block()?;
+        }
+        self.out.seek(SeekFrom::Start(INFO_OFFSET))?;
+        self.out
+            .write_all(&stream_info(self.sample_rate, self.channels, self.frames))?;
+        self.out.flush()
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let frames = self.block.len() / self.channels;
+        let bits = &mut self.bits;
+        bits.clear();
+        bits.write(0b1111_1111_1111_1000, 16);
+        // block size from the end of the header, rate from STREAMINFO
+        bits.write(0b0111_0000, 8);
+        bits.write((self.channels as u64 - 1) << 4 | 0b110 << 1, 8);
+        bits.write_utf8(self.blocks);
+        bits.write(frames as u64 - 1, 16);
+        let crc = crc8(bits.bytes());
+        bits.write(u64::from(crc), 8);
+
+        for c in 0..self.channels {
+            self.channel.clear();
+            self.channel.extend(
+                self.block
+                    .iter()
+                    .skip(c)
+                    .step_by(self.channels)
+                    .map(|&s| i64::from(s)),
+            );
+            subframe(&self.channel, &mut self.residual, bits);
+        }
+        bits.align();
+        let crc = crc16(bits.bytes());
+        bits.write(u64::from(crc), 16);
+
+        self.out.write_all(bits.bytes())?;
+        self.frames += frames as u64;
+        self.blocks += 1;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+/// STREAMINFO's rate, channels, depth and length, packed into 64 bits
+fn stream_info(sample_rate: u32, channels: usize, frames: u64) -> [u8; 8] {
+    let packed = u64::from(sample_rate) << 44
+        | (channels as u64 - 1) << 41
+        | u64::from(BITS_PER_SAMPLE - 1) << 36
+        | frames & 0xf_ffff_ffff;
+    packed.to_be_bytes()
+}
+
+/// the cheapest of a constant, a fixed predictor or the samples as they are
+fn subframe(samples: &[i64], residual: &mut Vec<i64>, bits: &mut BitWriter) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        bits.write(0, 8);
+        bits.write_signed(samples[0], BITS_PER_SAMPLE);
+        return;
+    }
+
+    let verbatim = samples.len() as u64 * u64::from(BITS_PER_SAMPLE);
+    let best = (0..=MAX_ORDER.min(samples.len() - 1))
+        .map(|order| {
+            predict(samples, order, residual);
+            let (cost, _) = partitions(residual, order, samples.len());
+            (order, cost + order as u64 * u64::from(BITS_PER_SAMPLE))
+        })
+        .min_by_key(|&(_, cost)| cost)
+        .filter(|&(_, cost)| cost < verbatim);
+
+    match best {
+        Some((order, _)) => {
+            bits.write(0b0001_0000 | (order as u64) << 1, 8);
+            for &s in &samples[..order] {
+                bits.write_signed(s, BITS_PER_SAMPLE);
+            }
+            predict(samples, order, residual);
+            let (_, partition_order) = partitions(residual, order, samples.len());
+            write_residual(residual, order, samples.len(), partition_order, bits);
+        }
+        None => {
+            bits.write(0b0000_0010, 8);
+            for &s in samples {
+                bits.write_signed(s, BITS_PER_SAMPLE);
+            }
+        }
+    }
+}
+
+/// what's left of `samples` after the fixed predictor of `order`
+fn predict(samples: &[i64], order: usize, residual: &mut Vec<i64>) {
+    residual.clear();
+    residual.extend((order..samples.len()).map(|i| {
+        let s = |back: usize| samples[i - back];
+        match order {
+            0 => s(0),
+            1 => s(0) - s(1),
+            2 => s(0) - 2 * s(1) + s(2),
+            3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+            _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+        }
+    }));
+}
+
+/// the residual's partition of `index` in `count`, the first one is short
+/// of the predictor's warm-up samples
+fn partition(residual: &[i64], order: usize, block: usize, count: usize, index: usize) -> &[i64] {
+    let size = block / count;
+    let start = (index * size).saturating_sub(order);
+    let end = ((index + 1) * size - order).min(residual.len());
+    &residual[start..end]
+}
+
+/// the fewest bits the residual codes in and the partition order for them
+fn partitions(residual: &[i64], order: usize, block: usize) -> (u64, u32) {
+    (0..=MAX_PARTITION_ORDER)
+        .take_while(|&p| p <= block.trailing_zeros() && block >> p > order)
+        .map(|p| {
+            let count = 1 << p;
+            let cost: u64 = (0..count)
+                .map(|i| 4 + rice(partition(residual, order, block, count, i)).1)
+                .sum();
+            (cost + 6, p)
+        })
+        .min_by_key(|&(cost, _)| cost)
+        .unwrap_or((u64::MAX, 0))
+}
+
+/// zigzag, so small negatives code as small as small positives
+fn fold(r: i64) -> u64 {
+    ((r << 1) ^ (r >> 63)) as u64
+}
+
+/// the best Rice parameter for `residual` and the bits it takes
+fn rice(residual: &[i64]) -> (u32, u64) {
+    let cost = |k: u32| {
+        residual
+            .iter()
+            .map(|&r| (fold(r) >> k) + 1 + u64::from(k))
+            .sum::<u64>()
+    };
+    let sum: u64 = residual.iter().map(|&r| fold(r)).sum();
+    let mean = sum / residual.len().max(1) as u64;
+    // the parameter near log2 of the mean is the best within one either way
+    let guess = (64 - mean.leading_zeros()).min(MAX_RICE);
+    (guess.saturating_sub(1)..=(guess + 1).min(MAX_RICE))
+        .map(|k| (k, cost(k)))
+        .min_by_key(|&(_, cost)| cost)
+        .unwrap_or((0, 0))
+}
+
+fn write_residual(
+    residual: &[i64],
+    order: usize,
+    block: usize,
+    partition_order: u32,
+    bits: &mut BitWriter,
+) {
+    bits.write(0, 2);
+    bits.write(u64::from(partition_order), 4);
+    let count = 1 << partition_order;
+    for i in 0..count {
+        let part = partition(residual, order, block, count, i);
+        let (k, _) = rice(part);
+        bits.write(u64::from(k), 4);
+        for &r in part {
+            let folded = fold(r);
+            bits.write_zeros(folded >> k);
+            bits.write(1, 1);
+            bits.write(folded & ((1 << k) - 1), k);
+        }
+    }
+}
+
+/// Big-endian bits into bytes, for one frame at a time.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    /// bits waiting in `acc`, under 8 between writes
+    len: u32,
+}
+
+impl BitWriter {
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.acc = 0;
+        self.len = 0;
+    }
+
+    /// the lowest `count` bits of `value`, up to 32 at a time
+    fn write(&mut self, value: u64, count: u32) {
+        if count > 32 {
+            self.write(value >> 32, count - 32);
+            return self.write(value & 0xffff_ffff, 32);
+        }
+        if count == 0 {
+            return;
+        }
+        self.acc = self.acc << count | value & ((1 << count) - 1);
+        self.len += count;
+        while self.len >= 8 {
+            self.len -= 8;
+            self.bytes.push((self.acc >> self.len) as u8);
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, count: u32) {
+        self.write(value as u64, count);
+    }
+
+    fn write_zeros(&mut self, mut count: u64) {
+        while count > 0 {
+            let run = count.min(32);
+            self.write(0, run as u32);
+            count -= run;
+        }
+    }
+
+    /// FLAC's frame numbers, coded the way UTF-8 codes characters
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            return self.write(value, 8);
+        }
+        let bits = 64 - value.leading_zeros();
+        // continuation bytes carry 6 bits each, the first 7 - bytes
+        let extra = (1..6).find(|&n| bits <= 6 - n + 6 * n).unwrap_or(6);
+        let lead = (0xff00u64 >> (extra + 1)) & 0xff;
+        self.write(lead | value >> (6 * extra), 8);
+        for n in (0..extra).rev() {
+            self.write(0x80 | (value >> (6 * n)) & 0x3f, 8);
+        }
+    }
+
+    /// pads the last byte with zeros
+    fn align(&mut self) {
+        if self.len > 0 {
+            self.write(0, 8 - self.len);
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ u16::from(byte) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
@@ -0,0 +1,336 @@
+//! Audio into files off the audio thread, and the writers behind them.
+//!
+//! Lossless formats are written by hand, Opus goes through libopus into an
+//! Ogg stream and needs the `opus` feature. Whatever the format, samples
+//! are encoded on the writer thread, the audio thread only fills the ring.
+
+use ringbuf::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+mod aiff;
+mod flac;
+#[cfg(feature = "opus")]
+mod opus;
+
+use aiff::AiffWriter;
+use flac::FlacWriter;
+#[cfg(feature = "opus")]
+use opus::OpusWriter;
+
+/// Opus bitrate unless one is asked for, transparent for most material
+pub const DEFAULT_KBPS: u32 = 160;
+
+fn default_kbps() -> u32 {
+    DEFAULT_KBPS
+}
+
+/// How recordings and renders are written, `format = "flac"` in a config
+/// table, with `kbps` for Opus.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum FileFormat {
+    /// 32-bit float
+    Wav,
+    /// 24-bit integer
+    Aiff,
+    /// 24-bit lossless, around half the size of the others
+    Flac,
+    /// lossy in an Ogg stream, for long captures, needs the `opus` feature
+    Opus {
+        #[serde(default = "default_kbps")]
+        kbps: u32,
+    },
+}
+
+impl FileFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Wav => "wav",
+            FileFormat::Aiff => "aiff",
+            FileFormat::Flac => "flac",
+            FileFormat::Opus { .. } => "opus",
+        }
+    }
+
+    /// by extension, Opus at `kbps`
+    pub fn from_path(path: &Path, kbps: u32) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "wav" => Some(FileFormat::Wav),
+            "aif" | "aiff" => Some(FileFormat::Aiff),
+            "flac" => Some(FileFormat::Flac),
+            "opus" | "ogg" => Some(FileFormat::Opus { kbps }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Spec {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub format: FileFormat,
+    /// seconds of audio the ring can hold before the audio thread drops buffers
+    pub buffer_seconds: f32,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Wav(hound::Error),
+    /// the encoder failed or can't take the stream's channels or rate
+    Encoder(String),
+    WriterPanicked,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "recorder io error: {}", e),
+            Error::Wav(e) => write!(f, "recorder wav error: {}", e),
+            Error::Encoder(e) => write!(f, "recorder encoder error: {}", e),
+            Error::WriterPanicked => write!(f, "recorder writer thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(e: hound::Error) -> Self {
+        Error::Wav(e)
+    }
+}
+
+#[cfg(feature = "opus")]
+impl From<audiopus::Error> for Error {
+    fn from(e: audiopus::Error) -> Self {
+        Error::Encoder(e.to_string())
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    running: AtomicBool,
+    overruns: AtomicUsize,
+    dropped_frames: AtomicUsize,
+    written_frames: AtomicUsize,
+}
+
+/// Audio thread end of a recording, never blocks or allocates.
+pub struct RecorderInput {
+    producer: Producer<f32>,
+    channels: usize,
+    stats: Arc<Stats>,
+}
+
+impl RecorderInput {
+    /// push interleaved samples, the whole buffer is dropped if it doesn't fit
+    /// so that channels never get out of step in the file
+    pub fn write(&mut self, interleaved: &[f32]) {
+        if !self.stats.running.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.producer.remaining() < interleaved.len() {
+            self.stats.overruns.fetch_add(1, Ordering::Relaxed);
+            self.stats
+                .dropped_frames
+                .fetch_add(interleaved.len() / self.channels, Ordering::Relaxed);
+            return;
+        }
+        self.producer.push_slice(interleaved);
+    }
+}
+
+/// UI end of a recording, owns the writer thread.
+pub struct Recorder {
+    stats: Arc<Stats>,
+    writer: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl Recorder {
+    pub fn start(path: &Path, spec: Spec) -> Result<(Recorder, RecorderInput), Error> {
+        let capacity =
+            (spec.buffer_seconds * spec.sample_rate as f32) as usize * spec.channels as usize;
+        let (producer, consumer) = RingBuffer::new(capacity).split();
+        let sink = Sink::create(path, &spec)?;
+        let stats = Arc::new(Stats::default());
+        stats.running.store(true, Ordering::Release);
+
+        let writer = {
+            let stats = stats.clone();
+            thread::Builder::new()
+                .name("recorder".into())
+                .spawn(move || drain(consumer, sink, spec.channels as usize, &stats))?
+        };
+
+        Ok((
+            Recorder {
+                stats: stats.clone(),
+                writer: Some(writer),
+            },
+            RecorderInput {
+                producer,
+                channels: spec.channels as usize,
+                stats,
+            },
+        ))
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stats.running.load(Ordering::Relaxed)
+    }
+
+    /// number of buffers the audio thread had to drop
+    pub fn overruns(&self) -> usize {
+        self.stats.overruns.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_frames(&self) -> usize {
+        self.stats.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn written_frames(&self) -> usize {
+        self.stats.written_frames.load(Ordering::Relaxed)
+    }
+
+    /// flushes whatever is left in the ring and finalizes the file
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.stats.running.store(false, Ordering::Release);
+        match self.writer.take() {
+            Some(writer) => writer.join().map_err(|_| Error::WriterPanicked)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+pub(crate) enum Sink {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Aiff(AiffWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+    #[cfg(feature = "opus")]
+    Opus(OpusWriter<BufWriter<File>>),
+}
+
+impl Sink {
+    pub(crate) fn create(path: &Path, spec: &Spec) -> Result<Self, Error> {
+        Ok(match spec.format {
+            FileFormat::Wav => Sink::Wav(hound::WavWriter::create(
+                path,
+                hound::WavSpec {
+                    channels: spec.channels,
+                    sample_rate: spec.sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                },
+            )?),
+            FileFormat::Aiff => Sink::Aiff(AiffWriter::new(
+                BufWriter::new(File::create(path)?),
+                spec.channels,
+                spec.sample_rate,
+            )?),
+            FileFormat::Flac => Sink::Flac(FlacWriter::new(
+                BufWriter::new(File::create(path)?),
+                spec.channels,
+                spec.sample_rate,
+            )?),
+            #[cfg(feature = "opus")]
+            FileFormat::Opus { kbps } => Sink::Opus(OpusWriter::new(
+                BufWriter::new(File::create(path)?),
+                spec.channels,
+                spec.sample_rate,
+                kbps,
+            )?),
+            #[cfg(not(feature = "opus"))]
+            FileFormat::Opus { .. } => {
+                return Err(Error::Encoder("opus needs the `opus` feature".into()))
+            }
+        })
+    }
+
+    pub(crate) fn write(&mut self, samples: &[f32]) -> Result<(), Error> {
+        match self {
+            Sink::Wav(w) => {
+                for &s in samples {
+                    w.write_sample(s)?;
+                }
+            }
+            Sink::Aiff(w) => {
+                for &s in samples {
+                    w.write_sample(s)?;
+                }
+            }
+            Sink::Flac(w) => {
+                for &s in samples {
+                    w.write_sample(s)?;
+                }
+            }
+            #[cfg(feature = "opus")]
+            Sink::Opus(w) => w.write(samples)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finalize(self) -> Result<(), Error> {
+        match self {
+            Sink::Wav(w) => w.finalize()?,
+            Sink::Aiff(w) => w.finalize()?,
+            Sink::Flac(w) => w.finalize()?,
+            #[cfg(feature = "opus")]
+            Sink::Opus(w) => w.finalize()?,
+        }
+        Ok(())
+    }
+}
+
+fn drain(
+    mut consumer: Consumer<f32>,
+    mut sink: Sink,
+    channels: usize,
+    stats: &Stats,
+) -> Result<(), Error> {
+    let mut scratch = vec![0.0; 4096 * channels];
+    loop {
+        // read the flag first so nothing pushed before `stop` is missed
+        let running = stats.running.load(Ordering::Acquire);
+        let read = consumer.pop_slice(&mut scratch);
+        if read > 0 {
+            sink.write(&scratch[..read])?;
+            stats
+                .written_frames
+                .fetch_add(read / channels, Ordering::Relaxed);
+        } else if !running {
+            break;
+        } else {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+    sink.finalize()
+}
@@ -0,0 +1,143 @@
+use super::Error;
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::convert::TryFrom;
+use std::io::Write;
+
+/// Ogg Opus counts its granule positions at this rate, whatever the input's
+const GRANULE_RATE: u32 = 48_000;
+/// packets a second, 20ms each
+const PACKETS_PER_SECOND: u32 = 50;
+/// the most a packet can take, from the Opus spec
+const MAX_PACKET: usize = 1275 * 3 + 7;
+const SERIAL: u32 = 0x6e61_6e6e;
+const VENDOR: &[u8] = b"nannou-apps";
+
+/// Ogg Opus writer, 20ms packets at a constant bitrate. Mono and stereo
+/// only, at one of the rates Opus takes, 48kHz as the apps run at.
+pub struct OpusWriter<W: Write> {
+    packets: PacketWriter<W>,
+    encoder: Encoder,
+    channels: usize,
+    /// interleaved samples of the packet being filled
+    block: Vec<f32>,
+    /// frames per packet
+    packet_frames: usize,
+    /// granule positions per frame
+    scale: u64,
+    /// granule positions the decoder drops from the start, the encoder's delay
+    pre_skip: u64,
+    /// frames written in
+    frames: u64,
+    /// frames encoded, the last packet's padding included
+    encoded: u64,
+    scratch: Vec<u8>,
+    /// the last packet and its granule position, held back until it's
+    /// known whether it ends the stream
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl<W: Write> OpusWriter<W> {
+    pub fn new(out: W, channels: u16, sample_rate: u32, kbps: u32) -> Result<Self, Error> {
+        let layout = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => {
+                return Err(Error::Encoder(format!(
+                    "opus takes mono or stereo, not {} channels",
+                    channels
+                )))
+            }
+        };
+        let rate = SampleRate::try_from(sample_rate as i32).map_err(|_| {
+            Error::Encoder(format!(
+                "opus takes 8, 12, 16, 24 or 48kHz, not {}Hz",
+                sample_rate
+            ))
+        })?;
+        let mut encoder = Encoder::new(rate, layout, Application::Audio)?;
+        encoder.set_bitrate(Bitrate::BitsPerSecond(kbps as i32 * 1000))?;
+        let scale = u64::from(GRANULE_RATE / sample_rate);
+        let pre_skip = u64::from(encoder.lookahead()?) * scale;
+
+        let mut packets = PacketWriter::new(out);
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1);
+        head.push(channels as u8);
+        head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+        head.extend_from_slice(&sample_rate.to_le_bytes());
+        // no output gain, mono or stereo mapping
+        head.extend_from_slice(&[0, 0, 0]);
+        packets.write_packet(head.into(), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+        let mut tags = Vec::with_capacity(24);
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+        tags.extend_from_slice(VENDOR);
+        tags.extend_from_slice(&0u32.to_le_bytes());
+        packets.write_packet(tags.into(), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+        let packet_frames = (sample_rate / PACKETS_PER_SECOND) as usize;
+        Ok(Self {
+            packets,
+            encoder,
+            channels: channels as usize,
+            block: Vec::with_capacity(packet_frames * channels as usize),
+            packet_frames,
+            scale,
+            pre_skip,
+            frames: 0,
+            encoded: 0,
+            scratch: vec![0; MAX_PACKET],
+            pending: None,
+        })
+    }
+
+    pub fn write(&mut self, samples: &[f32]) -> Result<(), Error> {
+        for &sample in samples {
+            self.block.push(sample);
+            if self.block.len() == self.packet_frames * self.channels {
+                self.frames += self.packet_frames as u64;
+                self.encode()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// pads the last packet and flushes the encoder's delay, the stream's
+    /// last granule position trims both off again
+    pub fn finalize(mut self) -> Result<(), Error> {
+        let frames = self.block.len() / self.channels;
+        self.block.truncate(frames * self.channels);
+        self.frames += frames as u64;
+        let end = self.pre_skip + self.frames * self.scale;
+        while !self.block.is_empty() || self.encoded * self.scale < end {
+            self.block.resize(self.packet_frames * self.channels, 0.0);
+            self.encode()?;
+        }
+        if let Some((packet, _)) = self.pending.take() {
+            self.packets
+                .write_packet(packet.into(), SERIAL, PacketWriteEndInfo::EndStream, end)?;
+        }
+        self.packets.inner_mut().flush()?;
+        Ok(())
+    }
+
+    fn encode(&mut self) -> Result<(), Error> {
+        let len = self.encoder.encode_float(&self.block, &mut self.scratch)?;
+        self.block.clear();
+        self.encoded += self.packet_frames as u64;
+        let packet = self.scratch[..len].to_vec();
+        if let Some((previous, granule)) = self.pending.replace((packet, self.encoded * self.scale))
+        {
+            self.packets.write_packet(
+                previous.into(),
+                SERIAL,
+                PacketWriteEndInfo::NormalPacket,
+                granule,
+            )?;
+        }
+        Ok(())
+    }
+}
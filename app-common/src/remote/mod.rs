@@ -0,0 +1,238 @@
+//! Parameters as JSON over WebSocket and HTTP, for browser control panels
+//! and scripted installations.
+//!
+//! A plain `GET /params` answers with every parameter, a connection that
+//! upgrades to a WebSocket exchanges one JSON object per message:
+//!
+//! - `{"op": "list"}` replies with every parameter, as `GET /params` does
+//! - `{"op": "get", "name": "δ"}` replies `{"op": "value", "name": "δ", "value": 3.14}`
+//! - `{"op": "set", "name": "δ", "value": 1.0}` replies with the clamped value
+//! - `{"op": "subscribe"}` replies with every parameter, then pushes a
+//!   `value` message whenever one changes, from any source
+//! - `{"op": "unsubscribe"}` stops the pushes
+//!
+//! Values are plain rather than normalized, as over OSC. Needs the `remote`
+//! feature, every connection gets a thread of its own talking to `Params`
+//! directly.
+
+use crate::param::Params;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr},
+};
+
+#[cfg(feature = "remote")]
+mod server;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// this machine only by default, `0.0.0.0` for panels on other devices
+    pub address: IpAddr,
+    pub port: u16,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 9001,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `remote` feature
+    Unavailable,
+    Io(io::Error),
+    WebSocket(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `remote` feature"),
+            Error::Io(e) => write!(f, "remote io error: {}", e),
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    List,
+    Get { name: String },
+    Set { name: String, value: f32 },
+    Subscribe,
+    Unsubscribe,
+}
+
+#[derive(Debug, Serialize)]
+struct ParamInfo {
+    name: &'static str,
+    min: f32,
+    max: f32,
+    default: f32,
+    unit: &'static str,
+    value: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Reply {
+    Params { params: Vec<ParamInfo> },
+    Value { name: &'static str, value: f32 },
+    Error { message: String },
+}
+
+impl Reply {
+    fn params(params: &Params) -> Self {
+        Reply::Params {
+            params: params
+                .specs()
+                .iter()
+                .enumerate()
+                .map(|(i, spec)| ParamInfo {
+                    name: spec.name,
+                    min: spec.min,
+                    max: spec.max,
+                    default: spec.default,
+                    unit: spec.unit,
+                    value: params.get(i),
+                })
+                .collect(),
+        }
+    }
+
+    fn value(params: &Params, index: usize) -> Self {
+        Reply::Value {
+            name: params.specs()[index].name,
+            value: params.get(index),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// every parameter as JSON, what `GET /params` answers with
+pub fn params_json(params: &Params) -> String {
+    Reply::params(params).to_json()
+}
+
+/// One client's side of the protocol, independent of the transport.
+pub struct Connection {
+    params: Params,
+    subscribed: bool,
+    /// values as of the last push, to find what changed
+    last: Vec<f32>,
+}
+
+impl Connection {
+    pub fn new(params: Params) -> Self {
+        Self {
+            last: values(&params),
+            params,
+            subscribed: false,
+        }
+    }
+
+    /// the reply to one message, if it has one
+    pub fn handle(&mut self, text: &str) -> Option<String> {
+        let request = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(e) => {
+                let message = format!("malformed request: {}", e);
+                return Some(Reply::Error { message }.to_json());
+            }
+        };
+        let reply = match request {
+            Request::List => Reply::params(&self.params),
+            Request::Get { name } => self.find(&name, Reply::value),
+            Request::Set { name, value } => self.find(&name, |params, i| {
+                params.set(i, value);
+                Reply::value(params, i)
+            }),
+            Request::Subscribe => {
+                self.subscribed = true;
+                self.last = values(&self.params);
+                Reply::params(&self.params)
+            }
+            Request::Unsubscribe => {
+                self.subscribed = false;
+                return None;
+            }
+        };
+        Some(reply.to_json())
+    }
+
+    fn find(&self, name: &str, reply: impl FnOnce(&Params, usize) -> Reply) -> Reply {
+        match self.params.index_of(name) {
+            Some(i) => reply(&self.params, i),
+            None => Reply::Error {
+                message: format!("no parameter named {}", name),
+            },
+        }
+    }
+
+    /// `value` messages for whatever changed since the last call, while
+    /// subscribed
+    pub fn changes(&mut self) -> Vec<String> {
+        if !self.subscribed {
+            return Vec::new();
+        }
+        let current = values(&self.params);
+        let changes = (0..current.len())
+            .filter(|&i| current[i].to_bits() != self.last[i].to_bits())
+            .map(|i| Reply::value(&self.params, i).to_json())
+            .collect();
+        self.last = current;
+        changes
+    }
+}
+
+fn values(params: &Params) -> Vec<f32> {
+    (0..params.len()).map(|i| params.get(i)).collect()
+}
+
+/// Serves the parameters until dropped.
+pub struct RemoteServer {
+    #[cfg(feature = "remote")]
+    _server: server::Server,
+    port: u16,
+}
+
+impl RemoteServer {
+    pub fn start(config: &RemoteConfig, params: Params) -> Result<Self, Error> {
+        #[cfg(feature = "remote")]
+        {
+            let server = server::Server::start(config, params)?;
+            Ok(Self {
+                port: server.port(),
+                _server: server,
+            })
+        }
+        #[cfg(not(feature = "remote"))]
+        {
+            let _ = (config, params);
+            Err(Error::Unavailable)
+        }
+    }
+
+    /// the bound port, differs from the config's when that was 0
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
@@ -0,0 +1,168 @@
+use super::{params_json, Connection, Error, RemoteConfig};
+use crate::param::Params;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tungstenite::Message;
+
+/// how often idle threads check for changes and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+/// clients that don't finish their request by then are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEAD: usize = 8192;
+
+pub struct Server {
+    running: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+    port: u16,
+}
+
+impl Server {
+    pub fn start(config: &RemoteConfig, params: Params) -> Result<Self, Error> {
+        let listener = TcpListener::bind((config.address, config.port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let accepting = running.clone();
+        let acceptor = thread::Builder::new()
+            .name("remote".into())
+            .spawn(move || {
+                while accepting.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, from)) => {
+                            let params = params.clone();
+                            let running = accepting.clone();
+                            let _ = thread::Builder::new()
+                                .name(format!("remote {}", from))
+                                .spawn(move || {
+                                    if let Err(e) = serve(stream, params, &running) {
+                                        eprintln!("remote: {}: {}", from, e);
+                                    }
+                                });
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL)
+                        }
+                        Err(e) => eprintln!("remote: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            running,
+            acceptor: Some(acceptor),
+            port,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for Server {
+    /// waits for the listener to close, so the port can be bound again
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+/// the request head, without consuming it
+fn peek_head(stream: &TcpStream) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0; MAX_HEAD];
+    loop {
+        let len = stream.peek(&mut buffer)?;
+        let head = &buffer[..len];
+        if len == 0 || len == MAX_HEAD || head.windows(4).any(|w| w == b"\r\n\r\n") {
+            buffer.truncate(len);
+            return Ok(buffer);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn is_upgrade(head: &str) -> bool {
+    head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("upgrade:") && line.contains("websocket")
+    })
+}
+
+fn serve(stream: TcpStream, params: Params, running: &AtomicBool) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let head = peek_head(&stream)?;
+    let text = String::from_utf8_lossy(&head);
+    if !is_upgrade(&text) {
+        return http(stream, head.len(), &text, &params);
+    }
+
+    let mut socket = tungstenite::accept(stream).map_err(|e| Error::WebSocket(e.to_string()))?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut connection = Connection::new(params);
+    let websocket = |e: tungstenite::Error| Error::WebSocket(e.to_string());
+
+    while running.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Some(reply) = connection.handle(&text) {
+                    socket.send(Message::Text(reply)).map_err(websocket)?;
+                }
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(websocket(e)),
+        }
+        for change in connection.changes() {
+            socket.send(Message::Text(change)).map_err(websocket)?;
+        }
+    }
+    let _ = socket.close(None);
+    Ok(())
+}
+
+/// `GET /params`, for scripts that only need to look
+fn http(mut stream: TcpStream, peeked: usize, head: &str, params: &Params) -> Result<(), Error> {
+    // consume what was peeked so closing doesn't reset the connection
+    let mut consumed = vec![0; peeked];
+    let _ = stream.read_exact(&mut consumed);
+
+    let target = head.lines().next().unwrap_or("");
+    let (status, body) = match target.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", "/params", _] => ("200 OK", params_json(params)),
+        _ => (
+            "404 Not Found",
+            r#"{"op":"error","message":"only GET /params is served"}"#.to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
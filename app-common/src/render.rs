@@ -0,0 +1,410 @@
+//! Running an engine without a window: offline as fast as it goes into a
+//! file, or live on the audio device.
+//!
+//! app --render <seconds> [out.wav|out.aiff|out.flac|out.opus] [--bitrate <kbps>]
+//!     [--normalize <LUFS>]
+//! app --headless
+
+use crate::audio::{StreamConfig, Supervisor};
+use crate::diagnostics::CallbackStats;
+use crate::recorder::{Error, FileFormat, Sink, Spec};
+use dsp_common::allocation::Forbid;
+use dsp_common::denormal::DenormalGuard;
+use dsp_common::loudness::Loudness;
+use dsp_common::meter::{from_db, to_db};
+#[cfg(feature = "audio")]
+use nannou_audio::Buffer;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// how often `headless` checks on the stream
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An engine that fills interleaved buffers, on a device or offline.
+pub trait Render {
+    /// `out` is zeroed, `channels` samples per frame
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32);
+}
+
+/// audio callback for `Supervisor` driving a `Render` engine
+#[cfg(feature = "audio")]
+pub fn callback<R: Render>(engine: &mut R, buffer: &mut Buffer, stats: &CallbackStats) {
+    let _denormals = DenormalGuard::new();
+    let (channels, sample_rate) = (buffer.channels(), buffer.sample_rate());
+    render_contained(engine, &mut buffer[..], channels, sample_rate, stats);
+}
+
+/// `engine.render`, leaving `out` silent if it panics, see `contain`.
+/// Debug builds with the `alloc-check` feature panic if it allocated.
+pub fn render_contained<R: Render>(
+    engine: &mut R,
+    out: &mut [f32],
+    channels: usize,
+    sample_rate: u32,
+    stats: &CallbackStats,
+) {
+    let frames = out.len() / channels.max(1);
+    let rendered = contain(
+        stats,
+        || {
+            let forbid = Forbid::new();
+            engine.render(out, channels, sample_rate);
+            let allocations = forbid.allocations();
+            drop(forbid);
+            debug_assert_eq!(allocations, 0, "allocated while rendering");
+        },
+        || {
+            format!(
+                "rendering {} frames of {} channels at {}Hz, silencing them",
+                frames, channels, sample_rate
+            )
+        },
+    );
+    if !rendered {
+        out.iter_mut().for_each(|sample| *sample = 0.0);
+    }
+}
+
+/// Runs `f` on the audio thread without letting a panic unwind out of the
+/// callback, which would take the stream or the whole app down with it.
+///
+/// The panic is counted in `stats` for the HUD's banner and logged with
+/// `context`, on the first one and then on every doubling so a panic in
+/// every callback doesn't flood the log. False if `f` panicked.
+pub fn contain(stats: &CallbackStats, f: impl FnOnce(), context: impl FnOnce() -> String) -> bool {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => return true,
+        Err(payload) => payload,
+    };
+    let message = panic_message(&*payload);
+    let count = stats.record_panic(message);
+    if count.is_power_of_two() {
+        eprintln!(
+            "audio engine panicked {} ({} so far): {}",
+            context(),
+            count,
+            message
+        );
+    }
+    false
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown panic", String::as_str),
+    }
+}
+
+/// The loudness a file is brought to once it's measured, after EBU R128.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Normalization {
+    /// integrated LUFS
+    pub target: f32,
+    /// dBTP the true peak stays under, short of the target if need be
+    pub ceiling: f32,
+}
+
+impl Default for Normalization {
+    /// where streaming services play back, with a decibel to spare for
+    /// their encoders
+    fn default() -> Self {
+        Self {
+            target: -14.0,
+            ceiling: -1.0,
+        }
+    }
+}
+
+impl Normalization {
+    /// the gain taking what `loudness` measured to the target, 1 for silence
+    pub fn gain(&self, loudness: &Loudness) -> f32 {
+        let integrated = match loudness.integrated() {
+            Some(integrated) => integrated,
+            None => return 1.0,
+        };
+        let gain = from_db(self.target - integrated);
+        let peak = loudness.true_peak();
+        if peak * gain > from_db(self.ceiling) {
+            from_db(self.ceiling) / peak
+        } else {
+            gain
+        }
+    }
+}
+
+/// What normalizing found and did.
+#[derive(Clone, Copy, Debug)]
+pub struct Normalized {
+    /// integrated LUFS before the gain, `None` for silence
+    pub loudness: Option<f32>,
+    pub gain: f32,
+}
+
+impl fmt::Display for Normalized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.loudness {
+            Some(loudness) => write!(
+                f,
+                "measured {:.1} LUFS, {:+.1}dB applied",
+                loudness,
+                to_db(self.gain)
+            ),
+            None => write!(f, "silent, left as it was"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub frames_per_buffer: usize,
+    pub seconds: f64,
+    pub format: FileFormat,
+    /// measured and brought to a loudness before it's written when set
+    pub normalize: Option<Normalization>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Report {
+    pub frames: usize,
+    /// of the file as written
+    pub peak: f32,
+    pub elapsed: Duration,
+    pub sample_rate: u32,
+    pub normalized: Option<Normalized>,
+}
+
+impl Report {
+    /// how many times faster than realtime the render ran
+    pub fn speed(&self) -> f64 {
+        let seconds = self.frames as f64 / self.sample_rate as f64;
+        seconds / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// where a render waits to be measured before it's normalized into `path`
+fn spool_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".spool.wav");
+    path.with_file_name(name)
+}
+
+/// renders `settings.seconds` of `engine` to `path`, `progress` gets the
+/// fraction done after each buffer
+pub fn to_file<R: Render>(
+    engine: &mut R,
+    path: &Path,
+    settings: &Settings,
+    mut progress: impl FnMut(f64),
+) -> Result<Report, Error> {
+    // normalized renders go through a float wav first, the gain is only
+    // known once the whole render is measured
+    let mut loudness = settings
+        .normalize
+        .map(|_| Loudness::new(settings.channels, settings.sample_rate));
+    let spool = settings.normalize.map(|_| spool_path(path));
+    let mut sink = Sink::create(
+        spool.as_deref().unwrap_or(path),
+        &Spec {
+            channels: settings.channels as u16,
+            sample_rate: settings.sample_rate,
+            format: if spool.is_some() {
+                FileFormat::Wav
+            } else {
+                settings.format
+            },
+            buffer_seconds: 0.0,
+        },
+    )?;
+
+    let total = (settings.seconds * settings.sample_rate as f64).ceil() as usize;
+    let mut buffer = vec![0.0; settings.frames_per_buffer * settings.channels];
+    let mut report = Report {
+        frames: 0,
+        peak: 0.0,
+        elapsed: Duration::default(),
+        sample_rate: settings.sample_rate,
+        normalized: None,
+    };
+    let start = Instant::now();
+
+    let _denormals = DenormalGuard::new();
+    while report.frames < total {
+        buffer.iter_mut().for_each(|sample| *sample = 0.0);
+        engine.render(&mut buffer, settings.channels, settings.sample_rate);
+
+        // the last buffer is rendered whole and trimmed
+        let frames = settings.frames_per_buffer.min(total - report.frames);
+        let written = &buffer[..frames * settings.channels];
+        report.peak = written
+            .iter()
+            .fold(report.peak, |peak, s| peak.max(s.abs()));
+        sink.write(written)?;
+        if let Some(loudness) = &mut loudness {
+            loudness.process_interleaved(written);
+        }
+        report.frames += frames;
+        progress(report.frames as f64 / total as f64);
+    }
+
+    sink.finalize()?;
+    if let (Some(spool), Some(normalization), Some(loudness)) =
+        (spool, settings.normalize, loudness)
+    {
+        let gain = normalization.gain(&loudness);
+        let peak = apply_gain(&spool, path, settings.format, gain);
+        let _ = fs::remove_file(&spool);
+        report.peak = peak?;
+        report.normalized = Some(Normalized {
+            loudness: loudness.integrated(),
+            gain,
+        });
+    }
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// `source`, a float wav, into `dest` with `gain` on, returns the peak
+fn apply_gain(source: &Path, dest: &Path, format: FileFormat, gain: f32) -> Result<f32, Error> {
+    let mut reader = hound::WavReader::open(source)?;
+    let spec = reader.spec();
+    let mut sink = Sink::create(
+        dest,
+        &Spec {
+            channels: spec.channels,
+            sample_rate: spec.sample_rate,
+            format,
+            buffer_seconds: 0.0,
+        },
+    )?;
+    let mut peak = 0.0f32;
+    let mut buffer = Vec::with_capacity(4096);
+    let mut samples = reader.samples::<f32>();
+    loop {
+        buffer.clear();
+        for sample in samples.by_ref().take(4096) {
+            buffer.push(sample? * gain);
+        }
+        if buffer.is_empty() {
+            break;
+        }
+        peak = buffer.iter().fold(peak, |peak, s| peak.max(s.abs()));
+        sink.write(&buffer)?;
+    }
+    sink.finalize()?;
+    Ok(peak)
+}
+
+/// measures `source`, a float wav such as a take's, and writes it to `dest`
+/// as `format` at the loudness `normalization` asks for
+pub fn normalize_wav(
+    source: &Path,
+    dest: &Path,
+    format: FileFormat,
+    normalization: &Normalization,
+) -> Result<Normalized, Error> {
+    let mut reader = hound::WavReader::open(source)?;
+    let spec = reader.spec();
+    let mut loudness = Loudness::new(spec.channels as usize, spec.sample_rate);
+    let mut buffer = Vec::with_capacity(4096);
+    let mut samples = reader.samples::<f32>();
+    loop {
+        buffer.clear();
+        for sample in samples.by_ref().take(4096) {
+            buffer.push(sample?);
+        }
+        if buffer.is_empty() {
+            break;
+        }
+        loudness.process_interleaved(&buffer);
+    }
+    let gain = normalization.gain(&loudness);
+    apply_gain(source, dest, format, gain)?;
+    Ok(Normalized {
+        loudness: loudness.integrated(),
+        gain,
+    })
+}
+
+/// `--render <seconds> [path]` from the command line
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub seconds: f64,
+    pub path: PathBuf,
+    /// by the path's extension, wav for any other
+    pub format: FileFormat,
+    pub normalize: Option<Normalization>,
+}
+
+impl Request {
+    /// renders with progress on stderr and exits non-zero on failure
+    pub fn run<R: Render>(
+        &self,
+        engine: &mut R,
+        sample_rate: u32,
+        channels: usize,
+        frames_per_buffer: usize,
+    ) {
+        let settings = Settings {
+            sample_rate,
+            channels,
+            frames_per_buffer,
+            seconds: self.seconds,
+            format: self.format,
+            normalize: self.normalize,
+        };
+        let mut percent = 0;
+        let rendered = to_file(engine, &self.path, &settings, |done| {
+            let now = (done * 100.0) as usize;
+            if now / 10 > percent / 10 {
+                eprintln!("{}%", now);
+            }
+            percent = now;
+        });
+        match rendered {
+            Ok(report) => {
+                println!(
+                    "{}: {:.1}s in {:.1}s ({:.0}x realtime), peak {:.3}",
+                    self.path.display(),
+                    report.frames as f64 / sample_rate as f64,
+                    report.elapsed.as_secs_f64(),
+                    report.speed(),
+                    report.peak
+                );
+                if let Some(normalized) = report.normalized {
+                    println!("{}", normalized);
+                }
+            }
+            Err(e) => {
+                eprintln!("render to {} failed: {}", self.path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// plays `engine` on the audio device until the process is killed, for
+/// installations without a screen
+pub fn headless<R: Render + Send + 'static>(app: &str, engine: R, config: StreamConfig) {
+    let mut stream = Supervisor::idle(engine, config);
+    match stream.rebuild() {
+        Ok(()) => println!("{}: playing on {}", app, stream.device().unwrap_or("?")),
+        Err(e) => eprintln!("{}: {}, waiting for a device", app, e),
+    }
+    loop {
+        if let Some(event) = stream.poll() {
+            println!("{}: {:?}", app, event);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
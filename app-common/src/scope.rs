@@ -0,0 +1,163 @@
+//! Waveforms for the scope widget, from the audio thread to the UI.
+//!
+//! The audio thread pushes its output through a `ScopeInput`, the UI drains
+//! it into a history of the last second and shows the window starting at the
+//! latest rising crossing of the trigger level, so periodic signals stand
+//! still. Without a crossing it free-runs on the newest samples.
+
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// horizontal divisions across the view
+pub const DIVISIONS: usize = 10;
+/// seconds, the slider's range
+pub const MIN_TIME_PER_DIV: f32 = 0.000_1;
+pub const MAX_TIME_PER_DIV: f32 = 0.05;
+
+/// seconds of history, twice the widest view so a trigger can be found in it
+const HISTORY: f32 = 2.0 * MAX_TIME_PER_DIV * DIVISIONS as f32;
+/// seconds the queue holds between two UI frames
+const QUEUE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// seconds per horizontal division
+    pub time_per_div: f32,
+    /// in [-1, 1]
+    pub trigger_level: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            time_per_div: 0.002,
+            trigger_level: 0.0,
+        }
+    }
+}
+
+/// Audio thread end, mixes interleaved buffers to mono. Never blocks or allocates.
+pub struct ScopeInput {
+    producer: Producer<f32>,
+    channels: usize,
+    overruns: Arc<AtomicUsize>,
+}
+
+impl ScopeInput {
+    /// the whole buffer is dropped if the UI is behind
+    pub fn write(&mut self, interleaved: &[f32]) {
+        let frames = interleaved.len() / self.channels;
+        if self.producer.remaining() < frames {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let scale = 1.0 / self.channels as f32;
+        for frame in interleaved.chunks_exact(self.channels) {
+            let _ = self.producer.push(frame.iter().sum::<f32>() * scale);
+        }
+    }
+}
+
+/// UI end, keeps the history and the window the widget draws.
+pub struct ScopeReader {
+    consumer: Consumer<f32>,
+    history: VecDeque<f32>,
+    /// samples `history` keeps
+    capacity: usize,
+    window: Vec<f32>,
+    triggered: bool,
+    settings: Settings,
+    sample_rate: f32,
+    overruns: Arc<AtomicUsize>,
+}
+
+pub fn channel(channels: usize, sample_rate: f32) -> (ScopeInput, ScopeReader) {
+    let (producer, consumer) = RingBuffer::new((QUEUE * sample_rate) as usize).split();
+    let overruns = Arc::new(AtomicUsize::new(0));
+    let history = (HISTORY * sample_rate) as usize;
+    (
+        ScopeInput {
+            producer,
+            channels: channels.max(1),
+            overruns: overruns.clone(),
+        },
+        ScopeReader {
+            consumer,
+            history: VecDeque::with_capacity(history),
+            capacity: history,
+            window: Vec::with_capacity(history / 2),
+            triggered: false,
+            settings: Settings::default(),
+            sample_rate,
+            overruns,
+        },
+    )
+}
+
+impl ScopeReader {
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = Settings {
+            time_per_div: settings
+                .time_per_div
+                .clamp(MIN_TIME_PER_DIV, MAX_TIME_PER_DIV),
+            trigger_level: settings.trigger_level.clamp(-1.0, 1.0),
+        };
+    }
+
+    /// drains the audio thread's samples and picks the window to draw, once
+    /// per frame
+    pub fn update(&mut self) {
+        let capacity = self.capacity;
+        while let Some(sample) = self.consumer.pop() {
+            if self.history.len() == capacity {
+                self.history.pop_front();
+            }
+            self.history.push_back(sample);
+        }
+
+        let span =
+            (self.settings.time_per_div * DIVISIONS as f32 * self.sample_rate).round() as usize;
+        let span = span.clamp(2, capacity / 2);
+        let len = self.history.len();
+        self.window.clear();
+        if len < span {
+            self.triggered = false;
+            return;
+        }
+
+        let level = self.settings.trigger_level;
+        let history = &self.history;
+        let crossing = (1..=len - span)
+            .rev()
+            .find(|&i| history[i - 1] < level && history[i] >= level);
+        self.triggered = crossing.is_some();
+        let start = crossing.unwrap_or(len - span);
+        self.window
+            .extend(self.history.range(start..start + span).copied());
+    }
+
+    /// the samples in view, oldest first, empty until enough have arrived
+    pub fn samples(&self) -> &[f32] {
+        &self.window
+    }
+
+    /// whether the window starts on a crossing rather than free-running
+    pub fn triggered(&self) -> bool {
+        self.triggered
+    }
+
+    /// buffers the audio thread had to drop
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::capture::timestamp;
+use nannou::prelude::*;
+use nannou::wgpu;
+use std::{fs, path::PathBuf};
+
+pub const HOTKEY: Key = Key::P;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Clone, Debug)]
+pub struct ScreenshotSettings {
+    pub dir: PathBuf,
+    /// render at this multiple of the window size
+    pub supersample: u32,
+    /// also put the image on the clipboard, needs the `clipboard` feature
+    pub clipboard: bool,
+}
+
+impl ScreenshotSettings {
+    pub fn new(app_name: &str) -> Self {
+        Self {
+            dir: PathBuf::from("screenshots").join(app_name),
+            supersample: 1,
+            clipboard: false,
+        }
+    }
+}
+
+struct Target {
+    texture: wgpu::Texture,
+    renderer: nannou::draw::Renderer,
+}
+
+/// Saves the scene, without the UI, to a timestamped PNG.
+///
+/// The app redraws its scene into the `Draw` given by `begin` and hands it
+/// back to `end`, which renders it offscreen at the supersampled size.
+pub struct Screenshots {
+    app_name: String,
+    settings: ScreenshotSettings,
+    requested: bool,
+    target: Option<Target>,
+    capturer: wgpu::TextureCapturer,
+}
+
+impl Screenshots {
+    pub fn new(app_name: &str) -> Self {
+        Self {
+            app_name: app_name.to_string(),
+            settings: ScreenshotSettings::new(app_name),
+            requested: false,
+            target: None,
+            capturer: wgpu::TextureCapturer::default(),
+        }
+    }
+
+    pub fn settings_mut(&mut self) -> &mut ScreenshotSettings {
+        &mut self.settings
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        if key == HOTKEY {
+            self.request();
+        }
+    }
+
+    /// the next `begin` returns a draw
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// a draw scaled to the screenshot's resolution when one is due
+    pub fn begin(&mut self) -> Option<Draw> {
+        if !self.requested {
+            return None;
+        }
+        self.requested = false;
+        let factor = self.settings.supersample.max(1) as f32;
+        Some(Draw::new().scale(factor))
+    }
+
+    pub fn end(&mut self, app: &App, draw: &Draw) {
+        let window = app.main_window();
+        let device = window.swap_chain_device();
+        let (w, h) = window.inner_size_points();
+        let factor = self.settings.supersample.max(1) as f32;
+        let size = [(w * factor) as u32, (h * factor) as u32];
+
+        let stale = match &self.target {
+            Some(target) => target.texture.size() != size,
+            None => true,
+        };
+        if stale {
+            let texture = wgpu::TextureBuilder::new()
+                .size(size)
+                .usage(wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+                .format(FORMAT)
+                .build(device);
+            let renderer = nannou::draw::RendererBuilder::new()
+                .build_from_texture_descriptor(device, texture.descriptor());
+            self.target = Some(Target { texture, renderer });
+        }
+        let target = self.target.as_mut().unwrap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("screenshot"),
+        });
+        // one point per texel, the draw from `begin` carries the supersample scale
+        target
+            .renderer
+            .render_to_texture(device, &mut encoder, draw, &target.texture);
+        let snapshot = self.capturer.capture(device, &mut encoder, &target.texture);
+        window.swap_chain_queue().submit(Some(encoder.finish()));
+
+        if let Err(e) = fs::create_dir_all(&self.settings.dir) {
+            eprintln!(
+                "cannot create screenshot directory {}: {}",
+                self.settings.dir.display(),
+                e
+            );
+            return;
+        }
+        let path = self
+            .settings
+            .dir
+            .join(format!("{}-{}.png", self.app_name, timestamp()));
+        let clipboard = self.settings.clipboard;
+        let _ = snapshot.read(move |result| {
+            let image = match result {
+                Ok(image) => image.to_owned(),
+                Err(e) => {
+                    eprintln!("screenshot failed: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = image.save(&path) {
+                eprintln!("cannot write {}: {}", path.display(), e);
+            }
+            if clipboard {
+                copy_to_clipboard(image.width(), image.height(), image.into_raw());
+            }
+        });
+    }
+
+    /// block until every pending screenshot is written, for app exit
+    pub fn finish(&mut self, app: &App) {
+        let window = app.main_window();
+        let _ = self
+            .capturer
+            .await_active_snapshots(window.swap_chain_device());
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(width: u32, height: u32, rgba: Vec<u8>) {
+    let image = arboard::ImageData {
+        width: width as usize,
+        height: height as usize,
+        bytes: rgba.into(),
+    };
+    if let Err(e) = arboard::Clipboard::new().and_then(|mut c| c.set_image(image)) {
+        eprintln!("cannot copy screenshot to the clipboard: {}", e);
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_width: u32, _height: u32, _rgba: Vec<u8>) {
+    eprintln!("built without the `clipboard` feature, screenshot not copied");
+}
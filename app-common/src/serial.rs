@@ -0,0 +1,367 @@
+//! Sensors on a microcontroller's serial port driving parameters, for
+//! installations with distance sensors, light sensors or knobs on an
+//! Arduino.
+//!
+//! The board prints a line per reading. Values are named, `distance=112
+//! light=530`, `distance:112` or `distance 112`, or bare, `112,530,7`, in
+//! which case they go by their position among the bare ones from `0`.
+//! Commas, semicolons and whitespace all separate.
+//!
+//! `[serial]` names the `port` and `baud` rate and each `[[serial.inputs]]`
+//! binds an input to a parameter: the raw `min` and `max` it spans the
+//! parameter's range over, flipped by `invert`, and the seconds of
+//! `smoothing` that take the jitter out of cheap sensors. A parameter is
+//! only written when its input moves, so the sliders still work while the
+//! sensor holds still. The port is read on a thread of its own and
+//! reopened when the board is unplugged and back. Needs the `serial`
+//! feature.
+
+use crate::config::{reopen, Config};
+use crate::param::Params;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+#[cfg(feature = "serial")]
+use std::{
+    io::{self, Read},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long a read waits for the board, also how often the smoothing steps
+#[cfg(feature = "serial")]
+const TIMEOUT: Duration = Duration::from_millis(10);
+/// between attempts to reopen a port that went away
+#[cfg(feature = "serial")]
+const RETRY: Duration = Duration::from_secs(1);
+/// a line longer than this is noise, the board's or a wrong baud rate's
+#[cfg(feature = "serial")]
+const MAX_LINE: usize = 1024;
+/// smaller changes of a parameter aren't written
+#[cfg(feature = "serial")]
+const STILL: f32 = 1e-4;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    /// `/dev/ttyACM0`, `COM3`...
+    pub port: String,
+    pub baud: u32,
+    pub inputs: Vec<SensorBinding>,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            port: String::new(),
+            baud: 9600,
+            inputs: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SensorBinding {
+    /// the value's name in the line, or its position for bare values
+    pub input: String,
+    pub param: String,
+    /// raw readings at the bottom and top of the parameter's range, an
+    /// Arduino's analog pins by default
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "default_max")]
+    pub max: f32,
+    #[serde(default)]
+    pub invert: bool,
+    /// seconds to follow a change
+    #[serde(default = "default_smoothing")]
+    pub smoothing: f32,
+}
+
+fn default_max() -> f32 {
+    1023.0
+}
+
+fn default_smoothing() -> f32 {
+    0.1
+}
+
+#[cfg(feature = "serial")]
+impl SensorBinding {
+    /// `raw` in the parameter's range, from 0 to 1
+    fn normalize(&self, raw: f32) -> f32 {
+        let span = self.max - self.min;
+        let normalized = if span.abs() > f32::EPSILON {
+            ((raw - self.min) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if self.invert {
+            1.0 - normalized
+        } else {
+            normalized
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// built without the `serial` feature
+    Unavailable,
+    Port(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "built without the `serial` feature"),
+            Error::Port(e) => write!(f, "serial port: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// the values in `line` by name, bare ones named by their position
+pub fn parse(line: &str) -> Vec<(String, f32)> {
+    let mut values = Vec::new();
+    let mut bare = 0;
+    // a name waiting for the value after it
+    let mut name: Option<&str> = None;
+    let tokens = line
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        if let Some((key, value)) = token.split_once(|c| c == '=' || c == ':') {
+            name = None;
+            match (key.trim(), value.trim().parse()) {
+                (key, Ok(value)) if !key.is_empty() => values.push((key.to_string(), value)),
+                // `distance: 112`, the value comes next
+                (key, Err(_)) if value.trim().is_empty() && !key.is_empty() => name = Some(key),
+                _ => {}
+            }
+        } else if let Ok(value) = token.parse() {
+            match name.take() {
+                Some(name) => values.push((name.to_string(), value)),
+                None => {
+                    values.push((bare.to_string(), value));
+                    bare += 1;
+                }
+            }
+        } else {
+            name = Some(token);
+        }
+    }
+    values
+}
+
+/// An input's smoothed reading and the parameter it drives.
+#[cfg(feature = "serial")]
+struct Sensor {
+    binding: SensorBinding,
+    param: usize,
+    /// the newest raw reading, `None` until the first
+    target: Option<f32>,
+    smoothed: f32,
+    /// what the parameter was last set to
+    written: Option<f32>,
+}
+
+#[cfg(feature = "serial")]
+impl Sensor {
+    /// moves towards the newest reading and writes the parameter if it
+    /// moved
+    fn step(&mut self, params: &Params, seconds: f32) {
+        let target = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+        self.smoothed = match self.written {
+            Some(_) if self.binding.smoothing > 0.0 => {
+                let follow = 1.0 - (-seconds / self.binding.smoothing).exp();
+                self.smoothed + (target - self.smoothed) * follow
+            }
+            _ => target,
+        };
+        let normalized = self.binding.normalize(self.smoothed);
+        let moved = match self.written {
+            Some(written) => (normalized - written).abs() > STILL,
+            None => true,
+        };
+        if moved {
+            params.set_normalized(self.param, normalized);
+            self.written = Some(normalized);
+        }
+    }
+}
+
+/// A board's sensors setting parameters from a thread of its own, closed
+/// on drop.
+pub struct SerialInput {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SerialInput {
+    /// opens the port, see `sensors` for the inputs
+    pub fn start(app: &str, config: &SerialConfig, params: &Params) -> Result<Self, Error> {
+        #[cfg(feature = "serial")]
+        {
+            let sensors = sensors(app, config, params);
+            let port = open_port(config)?;
+            let stop = Arc::new(AtomicBool::new(false));
+            let (app, config, params) = (app.to_string(), config.clone(), params.clone());
+            let stopped = stop.clone();
+            let thread = thread::Builder::new()
+                .name(format!("{} serial", app))
+                .spawn(move || read(&app, &config, port, sensors, &params, &stopped))
+                .map_err(|e| Error::Port(e.to_string()))?;
+            Ok(Self {
+                stop,
+                thread: Some(thread),
+            })
+        }
+        #[cfg(not(feature = "serial"))]
+        {
+            let _ = (app, config, params);
+            Err(Error::Unavailable)
+        }
+    }
+}
+
+impl Drop for SerialInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// the bound inputs, those naming no parameter are left out with a warning
+/// on stderr
+#[cfg(feature = "serial")]
+fn sensors(app: &str, config: &SerialConfig, params: &Params) -> Vec<Sensor> {
+    config
+        .inputs
+        .iter()
+        .filter_map(|binding| match params.index_of(&binding.param) {
+            Some(param) => Some(Sensor {
+                binding: binding.clone(),
+                param,
+                target: None,
+                smoothed: 0.0,
+                written: None,
+            }),
+            None => {
+                eprintln!(
+                    "{}: serial input {} names no parameter {}",
+                    app, binding.input, binding.param
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "serial")]
+fn open_port(config: &SerialConfig) -> Result<Box<dyn serialport::SerialPort>, Error> {
+    serialport::new(&config.port, config.baud)
+        .timeout(TIMEOUT)
+        .open()
+        .map_err(|e| Error::Port(format!("{}: {}", config.port, e)))
+}
+
+/// the thread's loop, lines in and parameters out until `stop`
+#[cfg(feature = "serial")]
+fn read(
+    app: &str,
+    config: &SerialConfig,
+    port: Box<dyn serialport::SerialPort>,
+    mut sensors: Vec<Sensor>,
+    params: &Params,
+    stop: &AtomicBool,
+) {
+    let mut port = Some(port);
+    let mut line = Vec::with_capacity(MAX_LINE);
+    let mut buffer = [0; 256];
+    let mut last = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        let open = match &mut port {
+            Some(open) => open,
+            None => {
+                thread::sleep(RETRY);
+                port = open_port(config).ok();
+                line.clear();
+                continue;
+            }
+        };
+        match open.read(&mut buffer) {
+            Ok(read) => {
+                for &byte in &buffer[..read] {
+                    if byte == b'\n' {
+                        let text = String::from_utf8_lossy(&line);
+                        for (name, value) in parse(&text) {
+                            sensors
+                                .iter_mut()
+                                .filter(|sensor| sensor.binding.input == name)
+                                .for_each(|sensor| sensor.target = Some(value));
+                        }
+                        line.clear();
+                    } else if line.len() < MAX_LINE {
+                        line.push(byte);
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                eprintln!("{}: serial port {}: {}, reopening", app, config.port, e);
+                port = None;
+            }
+        }
+        let seconds = last.elapsed().as_secs_f32();
+        last = Instant::now();
+        for sensor in &mut sensors {
+            sensor.step(params, seconds);
+        }
+    }
+}
+
+/// `app`'s parameters driven by `config.serial` while it's set, the port
+/// reopened when a reload changes it. Why it couldn't open goes to stderr.
+pub struct Sensors {
+    app: String,
+    config: Option<SerialConfig>,
+    input: Option<SerialInput>,
+}
+
+impl Sensors {
+    pub fn from_config(app: &str, config: &Config, params: &Params) -> Self {
+        Self {
+            app: app.to_owned(),
+            config: config.serial.clone(),
+            input: open(app, config, params),
+        }
+    }
+
+    /// reopens the port if `config.serial` isn't the one being read
+    pub fn reload(&mut self, config: &Config, params: &Params) {
+        let app = &self.app;
+        reopen(&self.config, &config.serial, &mut self.input, || {
+            open(app, config, params)
+        });
+        self.config = config.serial.clone();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+fn open(app: &str, config: &Config, params: &Params) -> Option<SerialInput> {
+    let input = SerialInput::start(app, config.serial.as_ref()?, params);
+    input.map_err(|e| eprintln!("{}: {}", app, e)).ok()
+}
@@ -0,0 +1,222 @@
+//! Session bundles, a whole performance setup in one directory.
+//!
+//! `<name>.session/session.toml` holds the app's config, so the parameters,
+//! window layout, theme and devices, along with the seed its randomness
+//! starts from. Samples the config refers to are copied into `samples/`
+//! next to it. Being a plain directory it zips with whatever is at hand.
+//!
+//! Load one with `--session <path>` or `LOAD` for the newest saved one.
+//! The installed config points into the bundle, keep it around.
+
+use crate::capture::timestamp;
+use crate::cli;
+use crate::config::Config;
+use nannou::prelude::Key;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// bundles the current setup under the sessions directory
+pub const SAVE: Key = Key::F5;
+/// installs the newest bundle
+pub const LOAD: Key = Key::F9;
+
+/// bump whenever the manifest's fields change
+const VERSION: u32 = 1;
+const MANIFEST: &str = "session.toml";
+const SAMPLES: &str = "samples";
+const EXTENSION: &str = "session";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    Version {
+        found: u32,
+        supported: u32,
+    },
+    /// the bundle was saved by another app
+    WrongApp {
+        expected: String,
+        found: String,
+    },
+    /// a sample the config refers to is not where it should be
+    MissingSample(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "session io error: {}", e),
+            Error::Parse(e) => write!(f, "malformed session: {}", e),
+            Error::Version { found, supported } => write!(
+                f,
+                "session version {} cannot be read by version {}",
+                found, supported
+            ),
+            Error::WrongApp { expected, found } => {
+                write!(f, "session is for {}, not {}", found, expected)
+            }
+            Error::MissingSample(path) => write!(f, "missing sample {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(e: toml::ser::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+/// per-app session directory inside the platform data directory
+pub fn dir(app: &str) -> PathBuf {
+    directories::ProjectDirs::from("", "", app)
+        .map(|dirs| dirs.data_dir().join("sessions"))
+        .unwrap_or_else(|| PathBuf::from("sessions"))
+}
+
+/// saved bundles for `app`, oldest first
+pub fn list(app: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir(app))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(EXTENSION))
+        .collect();
+    paths.sort();
+    paths
+}
+
+pub fn latest(app: &str) -> Option<PathBuf> {
+    list(app).pop()
+}
+
+fn load_and_install(app: &str, path: &Path, config_path: &Path) -> Result<Session, Error> {
+    let session = Session::load(app, path)?;
+    session.install(config_path)?;
+    Ok(session)
+}
+
+/// `--session` installed over the app's config before it is loaded, the
+/// session is `None` without the argument or if it can't be read
+pub fn from_args(app: &str, config_path: &Path) -> Option<Session> {
+    let path = cli::args().session.as_ref()?;
+    load_and_install(app, path, config_path)
+        .map_err(|e| eprintln!("{}: cannot load {}: {}", app, path.display(), e))
+        .ok()
+}
+
+/// the newest bundle installed over the app's config, `None` if there are
+/// none
+pub fn install_latest(app: &str, config_path: &Path) -> Result<Option<Session>, Error> {
+    match latest(app) {
+        Some(path) => load_and_install(app, &path, config_path).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    // plain values before tables, toml can't serialize them the other way round
+    pub app: String,
+    pub version: u32,
+    /// where the app's randomness starts, for apps that have any
+    pub seed: Option<u64>,
+    pub config: Config,
+}
+
+/// the files a config reads, bundled along with it
+fn samples(config: &mut Config) -> impl Iterator<Item = &mut PathBuf> {
+    config
+        .sample_path
+        .iter_mut()
+        .chain(config.impulse_path.iter_mut())
+}
+
+/// copies `sample` into the bundle at `path`, its path inside the bundle
+fn bundle(sample: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let name = sample
+        .file_name()
+        .ok_or_else(|| Error::MissingSample(sample.to_path_buf()))?;
+    let bundled = Path::new(SAMPLES).join(name);
+    fs::create_dir_all(path.join(SAMPLES))?;
+    fs::copy(sample, path.join(&bundled))
+        .map_err(|_| Error::MissingSample(sample.to_path_buf()))?;
+    Ok(bundled)
+}
+
+impl Session {
+    pub fn new(app: &str, config: Config, seed: Option<u64>) -> Self {
+        Self {
+            app: app.to_string(),
+            version: VERSION,
+            seed,
+            config,
+        }
+    }
+
+    /// writes the bundle to `path`, copying samples into it
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path)?;
+        let mut manifest = self.clone();
+        for sample in samples(&mut manifest.config) {
+            *sample = bundle(sample, path)?;
+        }
+        let text = toml::to_string_pretty(&toml::Value::try_from(&manifest)?)?;
+        fs::write(path.join(MANIFEST), text)?;
+        Ok(())
+    }
+
+    /// saved under the current time, so `latest` finds it
+    pub fn save_new(&self) -> Result<PathBuf, Error> {
+        let path = dir(&self.app).join(format!("{}.{}", timestamp(), EXTENSION));
+        self.save(&path)?;
+        Ok(path)
+    }
+
+    /// reads the bundle at `path`, with sample paths pointing into it
+    pub fn load(app: &str, path: &Path) -> Result<Self, Error> {
+        let mut session: Session = toml::from_str(&fs::read_to_string(path.join(MANIFEST))?)?;
+        if session.version > VERSION {
+            return Err(Error::Version {
+                found: session.version,
+                supported: VERSION,
+            });
+        }
+        if session.app != app {
+            return Err(Error::WrongApp {
+                expected: app.to_string(),
+                found: session.app,
+            });
+        }
+        for sample in samples(&mut session.config) {
+            *sample = path.join(&*sample);
+            if !sample.exists() {
+                return Err(Error::MissingSample(sample.clone()));
+            }
+        }
+        Ok(session)
+    }
+
+    /// replaces the app's config, running apps pick it up through
+    /// `LiveConfig`
+    pub fn install(&self, config_path: &Path) -> Result<(), Error> {
+        Ok(self.config.save(config_path)?)
+    }
+}
@@ -0,0 +1,233 @@
+//! Audio settings picked before an app starts playing.
+//!
+//! The `SetupScreen` shows in place of the scene on first run, with
+//! `--setup`, or on `HOTKEY`. Up/down picks a row, left/right steps through
+//! its choices, return applies them and escape keeps what was there. The app
+//! saves what was applied in its config, so later runs start on it.
+
+use crate::audio;
+use crate::cli;
+use crate::config::Config;
+use crate::input;
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use std::path::Path;
+
+pub const HOTKEY: Key = Key::F2;
+
+const SAMPLE_RATES: [u32; 4] = [44_100, 48_000, 88_200, 96_000];
+const BUFFER_SIZES: [usize; 7] = [64, 128, 256, 512, 1024, 2048, 4096];
+
+const HOST: usize = 0;
+const OUTPUT: usize = 1;
+const INPUT: usize = 2;
+const SAMPLE_RATE: usize = 3;
+const BUFFER_SIZE: usize = 4;
+
+/// The choices the screen edits, `None` leaves each to the system or app.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioSettings {
+    pub host: Option<String>,
+    pub output_device: Option<String>,
+    pub input_device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<usize>,
+}
+
+impl AudioSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            host: config.audio_host.clone(),
+            output_device: config.audio_device.clone(),
+            input_device: config.input_device.clone(),
+            sample_rate: config.sample_rate,
+            buffer_size: config.buffer_size,
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        config.audio_host = self.host.clone();
+        config.audio_device = self.output_device.clone();
+        config.input_device = self.input_device.clone();
+        config.sample_rate = self.sample_rate;
+        config.buffer_size = self.buffer_size;
+    }
+}
+
+/// whether to start on the screen, true without a config file yet
+pub fn at_startup(config_path: &Path) -> bool {
+    cli::args().setup || !config_path.exists()
+}
+
+pub enum Outcome {
+    Apply(AudioSettings),
+    Cancel,
+}
+
+struct Row {
+    label: &'static str,
+    /// what `None` means for this row
+    unset: &'static str,
+    choices: Vec<Option<String>>,
+    current: usize,
+}
+
+impl Row {
+    /// `selected` is kept even if it isn't among `values`, a device can be
+    /// unplugged while the app isn't running
+    fn new(
+        label: &'static str,
+        unset: &'static str,
+        values: Vec<String>,
+        selected: Option<String>,
+    ) -> Self {
+        let mut choices: Vec<Option<String>> = std::iter::once(None)
+            .chain(values.into_iter().map(Some))
+            .collect();
+        if selected.is_some() && !choices.contains(&selected) {
+            choices.push(selected.clone());
+        }
+        let current = choices.iter().position(|c| *c == selected).unwrap_or(0);
+        Self {
+            label,
+            unset,
+            choices,
+            current,
+        }
+    }
+
+    fn value(&self) -> Option<String> {
+        self.choices[self.current].clone()
+    }
+
+    fn step(&mut self, forward: bool) {
+        let len = self.choices.len();
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+    }
+}
+
+/// Host, devices, sample rate and buffer size as a list of rows, drawn like
+/// the `ErrorScreen`.
+pub struct SetupScreen {
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl SetupScreen {
+    pub fn new(settings: &AudioSettings) -> Self {
+        let rates = SAMPLE_RATES.iter().map(ToString::to_string).collect();
+        let sizes = BUFFER_SIZES.iter().map(ToString::to_string).collect();
+        let mut screen = Self {
+            rows: vec![
+                Row::new(
+                    "host",
+                    "system default",
+                    audio::hosts(),
+                    settings.host.clone(),
+                ),
+                Row::new("output", "system default", Vec::new(), None),
+                Row::new("input", "system default", Vec::new(), None),
+                Row::new(
+                    "sample rate",
+                    "app default",
+                    rates,
+                    settings.sample_rate.map(|r| r.to_string()),
+                ),
+                Row::new(
+                    "buffer size",
+                    "app default",
+                    sizes,
+                    settings.buffer_size.map(|s| s.to_string()),
+                ),
+            ],
+            selected: 0,
+        };
+        screen.scan(
+            settings.output_device.clone(),
+            settings.input_device.clone(),
+        );
+        screen
+    }
+
+    /// the devices of the selected host
+    fn scan(&mut self, output: Option<String>, input: Option<String>) {
+        let host = self.rows[HOST].value();
+        let host = host.as_deref();
+        self.rows[OUTPUT] = Row::new(
+            "output",
+            "system default",
+            audio::output_devices(host),
+            output,
+        );
+        self.rows[INPUT] = Row::new("input", "system default", input::devices(host), input);
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        AudioSettings {
+            host: self.rows[HOST].value(),
+            output_device: self.rows[OUTPUT].value(),
+            input_device: self.rows[INPUT].value(),
+            sample_rate: self.rows[SAMPLE_RATE].value().and_then(|r| r.parse().ok()),
+            buffer_size: self.rows[BUFFER_SIZE].value().and_then(|s| s.parse().ok()),
+        }
+    }
+
+    /// `Some` once the screen should close
+    pub fn key_pressed(&mut self, key: Key) -> Option<Outcome> {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.rows.len() - 1),
+            Key::Left | Key::Right => {
+                self.rows[self.selected].step(key == Key::Right);
+                if self.selected == HOST {
+                    // device names mean nothing on another host
+                    self.scan(None, None);
+                }
+            }
+            Key::Return => return Some(Outcome::Apply(self.settings())),
+            Key::Escape => return Some(Outcome::Cancel),
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(&self, draw: &Draw, rect: Rect, palette: &Palette) {
+        const LINE: f32 = 24.0;
+
+        draw.background().color(theme::color(palette.background));
+        let text = theme::color(palette.line);
+        let accent = theme::color(palette.accent(0));
+        let mut y = rect.top() - 2.0 * LINE;
+        let mut line = |message: &str, color: Rgb, size: u32| {
+            draw.text(message)
+                .x_y(0.0, y)
+                .w_h(rect.w() - 4.0 * LINE, LINE)
+                .left_justify()
+                .font_size(size)
+                .color(color);
+            y -= LINE;
+        };
+
+        line("audio settings", text, 16);
+        line("", text, 14);
+        for (i, row) in self.rows.iter().enumerate() {
+            let value = row.value();
+            let value = value.as_deref().unwrap_or(row.unset);
+            if i == self.selected {
+                line(&format!("> {}: < {} >", row.label, value), accent, 14);
+            } else {
+                line(&format!("  {}: {}", row.label, value), text, 14);
+            }
+        }
+        line("", text, 14);
+        line(
+            "up/down pick a setting, left/right change it, return applies, escape cancels",
+            text,
+            12,
+        );
+    }
+}
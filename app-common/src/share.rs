@@ -0,0 +1,200 @@
+//! Publishing the rendered scene to VJ software.
+//!
+//! `FrameShare` redraws the scene offscreen every frame, like `Screenshots`
+//! does on request, and hands the pixels to its `Server`s:
+//! `syphon::SyphonServer` on macOS and `spout::SpoutSender` on Windows for
+//! software on the same machine, `ndi::NdiSender` for receivers on the
+//! network.
+
+use crate::config::Config;
+use crate::ndi::{self, NdiSender};
+use crate::spout::{self, SpoutSender};
+use crate::syphon::{self, SyphonServer};
+use nannou::prelude::*;
+use nannou::wgpu;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// One frame as tightly packed RGBA8 rows, top row first.
+pub struct SharedFrame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+/// A frame sharing endpoint other applications subscribe to by name.
+pub trait Server: Send {
+    fn name(&self) -> &str;
+    fn publish(&mut self, frame: SharedFrame);
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// neither Syphon nor Spout runs on this platform
+    Unsupported,
+    Syphon(syphon::Error),
+    Spout(spout::Error),
+    Ndi(ndi::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unsupported => write!(f, "no texture sharing on this platform"),
+            Error::Syphon(e) => write!(f, "{}", e),
+            Error::Spout(e) => write!(f, "{}", e),
+            Error::Ndi(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<syphon::Error> for Error {
+    fn from(e: syphon::Error) -> Self {
+        Error::Syphon(e)
+    }
+}
+
+impl From<spout::Error> for Error {
+    fn from(e: spout::Error) -> Self {
+        Error::Spout(e)
+    }
+}
+
+impl From<ndi::Error> for Error {
+    fn from(e: ndi::Error) -> Self {
+        Error::Ndi(e)
+    }
+}
+
+/// the platform's texture sharing server, Syphon or Spout, named `name`
+pub fn platform_server(name: &str) -> Result<Box<dyn Server>, Error> {
+    if cfg!(target_os = "macos") {
+        Ok(Box::new(SyphonServer::new(name)?))
+    } else if cfg!(target_os = "windows") {
+        Ok(Box::new(SpoutSender::new(name)?))
+    } else {
+        Err(Error::Unsupported)
+    }
+}
+
+/// A share to every server `texture_share` and `ndi` turn on, None when
+/// they are off or none could be opened.
+pub fn open(app: &str, config: &Config) -> Option<FrameShare> {
+    let mut servers = Vec::new();
+    let mut add = |server: Result<Box<dyn Server>, Error>| match server {
+        Ok(server) => servers.push(server),
+        Err(e) => eprintln!("{}: {}", app, e),
+    };
+    if config.texture_share {
+        add(platform_server(app));
+    }
+    if config.ndi {
+        add(NdiSender::new(app)
+            .map(|sender| Box::new(sender) as Box<dyn Server>)
+            .map_err(Error::from));
+    }
+    if servers.is_empty() {
+        None
+    } else {
+        Some(FrameShare::new(servers))
+    }
+}
+
+/// The config that `open` reads, to reopen the share when it changes.
+pub fn settings(config: &Config) -> (bool, bool) {
+    (config.texture_share, config.ndi)
+}
+
+/// Sends every frame of the scene, without the UI, to its servers.
+pub struct FrameShare {
+    servers: Arc<Mutex<Vec<Box<dyn Server>>>>,
+    target: Option<(wgpu::Texture, nannou::draw::Renderer)>,
+    capturer: wgpu::TextureCapturer,
+    enabled: bool,
+}
+
+impl FrameShare {
+    pub fn new(servers: Vec<Box<dyn Server>>) -> Self {
+        Self {
+            servers: Arc::new(Mutex::new(servers)),
+            target: None,
+            capturer: wgpu::TextureCapturer::default(),
+            enabled: true,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// a draw for this frame's scene while sharing is on, scaled so the
+    /// shared texture has the window's pixel size
+    pub fn begin(&self, app: &App) -> Option<Draw> {
+        if self.enabled {
+            Some(Draw::new().scale(app.main_window().scale_factor()))
+        } else {
+            None
+        }
+    }
+
+    pub fn end(&mut self, app: &App, draw: &Draw) {
+        let window = app.main_window();
+        let device = window.swap_chain_device();
+        let (w, h) = window.inner_size_pixels();
+        let size = [w, h];
+
+        let stale = match &self.target {
+            Some((texture, _)) => texture.size() != size,
+            None => true,
+        };
+        if stale {
+            let texture = wgpu::TextureBuilder::new()
+                .size(size)
+                .usage(wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+                .format(FORMAT)
+                .build(device);
+            let renderer = nannou::draw::RendererBuilder::new()
+                .build_from_texture_descriptor(device, texture.descriptor());
+            self.target = Some((texture, renderer));
+        }
+        let (texture, renderer) = self.target.as_mut().unwrap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame share"),
+        });
+        renderer.render_to_texture(device, &mut encoder, draw, texture);
+        let snapshot = self.capturer.capture(device, &mut encoder, texture);
+        window.swap_chain_queue().submit(Some(encoder.finish()));
+
+        let servers = self.servers.clone();
+        let _ = snapshot.read(move |result| {
+            let image = match result {
+                Ok(image) => image.to_owned(),
+                Err(_) => return,
+            };
+            if let Ok(mut servers) = servers.lock() {
+                for server in servers.iter_mut() {
+                    server.publish(SharedFrame {
+                        width: image.width(),
+                        height: image.height(),
+                        rgba: image.as_raw(),
+                    });
+                }
+            }
+        });
+    }
+
+    /// block until the frames in flight are published, for app exit
+    pub fn finish(&mut self, app: &App) {
+        let window = app.main_window();
+        let _ = self
+            .capturer
+            .await_active_snapshots(window.swap_chain_device());
+    }
+}
@@ -0,0 +1,250 @@
+//! The window-side state every app keeps, and what it does with it.
+//!
+//! `Shell` holds the app's config and the file it lives in, reloaded when
+//! the file is edited, the audio setup screen, themes, frame capture and
+//! screenshots. Apps configure it with their name, default theme, how they
+//! open their stream and the parameters they save, then hand it their
+//! `Supervisor` for the keys and reloads that touch the audio.
+
+use crate::audio::{StreamConfig, Supervisor};
+use crate::capture::{CaptureSettings, FrameRecorder};
+use crate::cli;
+use crate::config::{Config, LiveConfig};
+use crate::param::{ParamSnapshot, ParamSpec, Params};
+use crate::render::Render;
+use crate::screenshot::Screenshots;
+use crate::session::{self, Session};
+use crate::setup::{self, AudioSettings, Outcome, SetupScreen};
+use crate::theme::Themes;
+use dsp_common::random;
+use nannou::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// the config with `--session` installed and the flags over it, and the
+/// session's seed if it has one
+pub fn load_config(app: &str, config_path: &Path) -> (Config, Option<u64>) {
+    let session = session::from_args(app, config_path);
+    let mut config = Config::load(config_path);
+    cli::args().apply(&mut config);
+    (config, session.and_then(|s| s.seed))
+}
+
+/// `--seed`, else the session's, else a new one
+pub fn seed(session_seed: Option<u64>) -> u64 {
+    cli::args()
+        .seed
+        .or(session_seed)
+        .unwrap_or_else(random::entropy)
+}
+
+/// the saved parameters, or `--preset`'s
+pub fn load_params(app: &str, config: &Config, specs: &'static [ParamSpec]) -> Params {
+    let params = Params::new(specs);
+    config.params.apply(&params);
+    if let Some(name) = &cli::args().preset {
+        match ParamSnapshot::load_preset(app, name) {
+            Ok(preset) => preset.apply(&params),
+            Err(e) => eprintln!("{}: {}", app, e),
+        }
+    }
+    params
+}
+
+/// What the setup screen did with a key.
+pub enum SetupKey {
+    /// the screen is closed and the key is the app's
+    Ignored,
+    /// the screen opened, closed or took the key
+    Taken,
+    /// the settings were applied, saved and the stream reopened, these were
+    /// the ones before
+    Applied(AudioSettings),
+}
+
+impl SetupKey {
+    /// true unless the key is still the app's
+    pub fn taken(&self) -> bool {
+        !matches!(self, SetupKey::Ignored)
+    }
+}
+
+pub struct Shell {
+    name: &'static str,
+    pub config: Config,
+    config_path: PathBuf,
+    live_config: LiveConfig,
+    stream_config: Box<dyn Fn(&Config) -> StreamConfig>,
+    /// saved with the config, for apps that have any
+    params: Option<Params>,
+    /// audio settings, shown over everything while open
+    setup: Option<SetupScreen>,
+    pub themes: Themes,
+    pub capture: FrameRecorder,
+    pub screenshots: Screenshots,
+}
+
+impl Shell {
+    /// `stream_config` is how the app opens its stream from a config, the
+    /// setup screen opens over everything on first run or with `--setup`
+    pub fn new(
+        name: &'static str,
+        config: Config,
+        config_path: &Path,
+        theme: &str,
+        stream_config: impl Fn(&Config) -> StreamConfig + 'static,
+    ) -> Self {
+        let setup = if setup::at_startup(config_path) {
+            Some(SetupScreen::new(&AudioSettings::from_config(&config)))
+        } else {
+            None
+        };
+        Self {
+            name,
+            themes: Themes::load(config.ui.theme.as_deref().unwrap_or(theme)),
+            config,
+            config_path: config_path.to_path_buf(),
+            live_config: LiveConfig::new(config_path),
+            stream_config: Box::new(stream_config),
+            params: None,
+            setup,
+            capture: FrameRecorder::new(CaptureSettings::new(name)),
+            screenshots: Screenshots::new(name),
+        }
+    }
+
+    /// parameters saved with the config and applied when it's edited
+    pub fn with_params(mut self, params: &Params) -> Self {
+        self.params = Some(params.clone());
+        self
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    pub fn setup(&self) -> Option<&SetupScreen> {
+        self.setup.as_ref()
+    }
+
+    /// the setup screen's keys, called before anything else sees the key
+    pub fn setup_key_pressed<E: Render + Send + 'static>(
+        &mut self,
+        key: Key,
+        stream: &mut Supervisor<E>,
+    ) -> SetupKey {
+        let screen = match &mut self.setup {
+            Some(screen) => screen,
+            None if key == setup::HOTKEY => {
+                self.setup = Some(SetupScreen::new(&AudioSettings::from_config(&self.config)));
+                return SetupKey::Taken;
+            }
+            None => return SetupKey::Ignored,
+        };
+        match screen.key_pressed(key) {
+            Some(Outcome::Apply(settings)) => {
+                let previous = AudioSettings::from_config(&self.config);
+                settings.apply(&mut self.config);
+                let _ = stream.set_config((self.stream_config)(&self.config));
+                // `LiveConfig` finds nothing changed when it rereads the file
+                if let Err(e) = self.config.save(&self.config_path) {
+                    eprintln!("{}: cannot save config: {}", self.name, e);
+                }
+                self.setup = None;
+                SetupKey::Applied(previous)
+            }
+            Some(Outcome::Cancel) => {
+                self.setup = None;
+                SetupKey::Taken
+            }
+            None => SetupKey::Taken,
+        }
+    }
+
+    /// sessions, capture, screenshots and themes, called once the app's own
+    /// keys are handled. Sessions are installed as the config file,
+    /// `LiveConfig` applies them, the one installed is returned.
+    pub fn key_pressed<E: Render + Send + 'static>(
+        &mut self,
+        app: &App,
+        key: Key,
+        stream: &Supervisor<E>,
+        seed: Option<u64>,
+    ) -> Option<Session> {
+        let mut installed = None;
+        match key {
+            session::SAVE => {
+                self.capture_config(app, stream);
+                let session = Session::new(self.name, self.config.clone(), seed);
+                match session.save_new() {
+                    Ok(path) => println!("{}: saved {}", self.name, path.display()),
+                    Err(e) => eprintln!("{}: cannot save session: {}", self.name, e),
+                }
+            }
+            session::LOAD => {
+                // so `LiveConfig` compares against what's on screen
+                self.capture_config(app, stream);
+                match session::install_latest(self.name, &self.config_path) {
+                    Ok(Some(session)) => installed = Some(session),
+                    Ok(None) => eprintln!("{}: no saved sessions", self.name),
+                    Err(e) => eprintln!("{}: cannot load session: {}", self.name, e),
+                }
+            }
+            _ => {}
+        }
+        self.capture.key_pressed(app, key);
+        self.screenshots.key_pressed(key);
+        self.themes.key_pressed(key);
+        installed
+    }
+
+    /// what `exit` saves and sessions bundle
+    pub fn capture_config<E: Render + Send + 'static>(
+        &mut self,
+        app: &App,
+        stream: &Supervisor<E>,
+    ) {
+        self.config.capture_window(app);
+        self.config.audio_device = stream.config().device.clone();
+        self.config.ui.theme = Some(self.themes.current().name.clone());
+        if let Some(params) = &self.params {
+            self.config.params = ParamSnapshot::capture(params);
+        }
+    }
+
+    /// The config after an edit, with the window, theme, stream and
+    /// parameters already following it. The app applies the rest and then
+    /// stores it in `config`, which still holds the one before.
+    pub fn reload<E: Render + Send + 'static>(
+        &mut self,
+        app: &App,
+        stream: &mut Supervisor<E>,
+    ) -> Option<Config> {
+        let config = self.live_config.poll()?;
+        config.apply_window(&self.config, app);
+        if config.ui.theme != self.config.ui.theme {
+            if let Some(name) = &config.ui.theme {
+                self.themes.select(name);
+            }
+        }
+        if AudioSettings::from_config(&config) != AudioSettings::from_config(&self.config) {
+            let _ = stream.set_config((self.stream_config)(&config));
+        }
+        if config.jack != self.config.jack {
+            let _ = stream.set_jack((self.stream_config)(&config).jack);
+        }
+        if let Some(params) = &self.params {
+            if config.params != self.config.params {
+                config.params.apply(params);
+            }
+        }
+        Some(config)
+    }
+
+    /// finishes the captures and saves the config, from the app's `exit`
+    pub fn exit<E: Render + Send + 'static>(&mut self, app: &App, stream: &Supervisor<E>) {
+        self.capture.finish(app);
+        self.screenshots.finish(app);
+        self.capture_config(app, stream);
+        let _ = self.config.save(&self.config_path);
+    }
+}
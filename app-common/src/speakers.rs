@@ -0,0 +1,117 @@
+//! Surround layouts from the config, for apps that place their voices
+//! round a room rather than between two speakers.
+//!
+//! `[speakers]` names a layout, `layout = "quad"` or `"5.1"`, and can move
+//! its speakers with `angles`, one per channel in degrees clockwise from
+//! the front. The LFE's angle is ignored, nothing is panned to it. The
+//! stream opens with the layout's channels, one with other channels waits
+//! for a restart.
+//!
+//! Two layouts encode rather than pan: `"spherical-head"` for headphones,
+//! stereo out with sources placed round a modelled head, its delays and
+//! shadows rather than measured HRTFs, and `"ambix"`, first-order
+//! ambisonics in four channels for decoding to any rig later.
+//! `elevations` tilts each voice up or down for either, in degrees in voice
+//! order, level for voices without one.
+
+use dsp_common::pan::{Encoding, Layout};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutName {
+    #[default]
+    Stereo,
+    Quad,
+    #[serde(rename = "5.1")]
+    Surround,
+    #[serde(rename = "spherical-head")]
+    SphericalHead,
+    #[serde(rename = "ambix")]
+    Ambisonic,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeakerConfig {
+    pub layout: LayoutName,
+    /// over the layout's own, ignored unless there's one per channel
+    pub angles: Vec<f32>,
+    /// by voice, for the encoded layouts
+    pub elevations: Vec<f32>,
+}
+
+impl SpeakerConfig {
+    pub fn spatial(&self) -> Spatial {
+        let (mut layout, encoding) = match self.layout {
+            LayoutName::Stereo => (Layout::STEREO, None),
+            LayoutName::Quad => (Layout::QUAD, None),
+            LayoutName::Surround => (Layout::SURROUND, None),
+            LayoutName::SphericalHead => (Layout::STEREO, Some(Encoding::SphericalHead)),
+            LayoutName::Ambisonic => (Layout::STEREO, Some(Encoding::Ambisonic)),
+        };
+        if encoding.is_none() && self.angles.len() == layout.channels() {
+            for (channel, &angle) in self.angles.iter().enumerate() {
+                layout.set_angle(channel, angle);
+            }
+        }
+        Spatial { layout, encoding }
+    }
+
+    /// each of `N` voices' elevation
+    pub fn elevations<const N: usize>(&self) -> [f32; N] {
+        let mut elevations = [0.0; N];
+        for (elevation, &configured) in elevations.iter_mut().zip(self.elevations.iter()) {
+            *elevation = configured;
+        }
+        elevations
+    }
+}
+
+/// Where the voices go: panned over `layout`'s speakers, or encoded when
+/// `encoding` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spatial {
+    pub layout: Layout,
+    pub encoding: Option<Encoding>,
+}
+
+impl Spatial {
+    pub fn channels(&self) -> usize {
+        self.encoding
+            .map_or(self.layout.channels(), Encoding::channels)
+    }
+
+    /// JACK port names for the channels
+    pub fn ports(&self) -> &'static [&'static str] {
+        match (self.encoding, self.channels()) {
+            (Some(Encoding::Ambisonic), _) => &["w", "y", "z", "x"],
+            (_, 4) => &["front left", "front right", "rear left", "rear right"],
+            (_, 6) => &[
+                "left",
+                "right",
+                "centre",
+                "lfe",
+                "surround left",
+                "surround right",
+            ],
+            _ => &["left", "right"],
+        }
+    }
+}
+
+/// the configured placement, stereo without one
+pub fn spatial(speakers: Option<&SpeakerConfig>) -> Spatial {
+    speakers.map_or(
+        Spatial {
+            layout: Layout::STEREO,
+            encoding: None,
+        },
+        SpeakerConfig::spatial,
+    )
+}
+
+/// each of `N` voices' elevation, level without a config
+pub fn elevations<const N: usize>(speakers: Option<&SpeakerConfig>) -> [f32; N] {
+    speakers.map_or([0.0; N], SpeakerConfig::elevations)
+}
@@ -0,0 +1,150 @@
+//! Spectrum analysis off the audio thread.
+//!
+//! The audio thread pushes its output through an `AnalyzerInput`, a worker
+//! runs the STFT and the UI picks up the latest smoothed magnitudes.
+
+use dsp_common::spectrum::{Stft, StftConfig};
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// how long the worker sleeps when it has caught up
+const IDLE: Duration = Duration::from_millis(2);
+
+#[derive(Default)]
+struct Shared {
+    running: AtomicBool,
+    overruns: AtomicUsize,
+    /// bumped for every analysed frame
+    frames: AtomicUsize,
+    magnitudes: Mutex<Vec<f32>>,
+}
+
+/// Audio thread end, mixes interleaved buffers to mono. Never blocks or allocates.
+pub struct AnalyzerInput {
+    producer: Producer<f32>,
+    channels: usize,
+    shared: Arc<Shared>,
+}
+
+impl AnalyzerInput {
+    /// the whole buffer is dropped if the worker is behind
+    pub fn write(&mut self, interleaved: &[f32]) {
+        let frames = interleaved.len() / self.channels;
+        if self.producer.remaining() < frames {
+            self.shared.overruns.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let scale = 1.0 / self.channels as f32;
+        for frame in interleaved.chunks_exact(self.channels) {
+            let _ = self.producer.push(frame.iter().sum::<f32>() * scale);
+        }
+    }
+}
+
+/// UI end, owns the worker thread which stops when this is dropped.
+pub struct Analyzer {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+    config: StftConfig,
+    sample_rate: f32,
+}
+
+impl Analyzer {
+    pub fn start(
+        config: StftConfig,
+        channels: usize,
+        sample_rate: f32,
+    ) -> io::Result<(Analyzer, AnalyzerInput)> {
+        // a few frames of slack so a late worker doesn't drop buffers
+        let (producer, consumer) = RingBuffer::new(config.size * 4).split();
+        let shared = Arc::new(Shared {
+            magnitudes: Mutex::new(vec![0.0; config.size / 2 + 1]),
+            ..Shared::default()
+        });
+        shared.running.store(true, Ordering::Release);
+
+        let worker = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("spectrum".into())
+                .spawn(move || analyse(consumer, Stft::new(config), &shared))?
+        };
+
+        Ok((
+            Analyzer {
+                shared: shared.clone(),
+                worker: Some(worker),
+                config,
+                sample_rate,
+            },
+            AnalyzerInput {
+                producer,
+                channels: channels.max(1),
+                shared,
+            },
+        ))
+    }
+
+    pub fn config(&self) -> &StftConfig {
+        &self.config
+    }
+
+    /// centre frequency of `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / self.config.size as f32
+    }
+
+    /// frames analysed so far, compare against a previous count to see
+    /// whether `read` has anything new
+    pub fn frames(&self) -> usize {
+        self.shared.frames.load(Ordering::Relaxed)
+    }
+
+    /// copies the latest linear magnitudes into `out`
+    pub fn read(&self, out: &mut Vec<f32>) {
+        if let Ok(magnitudes) = self.shared.magnitudes.lock() {
+            out.clear();
+            out.extend_from_slice(&magnitudes);
+        }
+    }
+
+    /// buffers the audio thread had to drop
+    pub fn overruns(&self) -> usize {
+        self.shared.overruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Analyzer {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Release);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn analyse(mut consumer: Consumer<f32>, mut stft: Stft, shared: &Shared) {
+    let mut block = vec![0.0; stft.config().hop.max(1)];
+    while shared.running.load(Ordering::Acquire) {
+        let read = consumer.pop_slice(&mut block);
+        if read == 0 {
+            thread::sleep(IDLE);
+            continue;
+        }
+        let frames = stft.process(&block[..read]);
+        if frames > 0 {
+            if let Ok(mut magnitudes) = shared.magnitudes.lock() {
+                magnitudes.copy_from_slice(stft.magnitudes());
+            }
+            shared.frames.fetch_add(frames, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,514 @@
+//! Spout output on Windows, a `share::Server` publishing frames as a shared
+//! DirectX 11 texture that Resolume, TouchDesigner and other Spout receivers
+//! on the machine pick by name.
+//!
+//! Needs the `spout` feature. Spout has no runtime to install: senders list
+//! themselves in shared memory every receiver reads, next to a block naming
+//! their texture's handle, size and format.
+
+use crate::share::{Server, SharedFrame};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// not Windows, or built without the `spout` feature
+    Unavailable,
+    /// no DirectX 11 device to share textures from
+    Device,
+    /// a shared memory block couldn't be opened, by name
+    SharedMemory(String),
+    /// a running sender has the name
+    Taken(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "spout needs Windows and the `spout` feature"),
+            Error::Device => write!(f, "no DirectX 11 device for spout"),
+            Error::SharedMemory(name) => write!(f, "spout shared memory {} unavailable", name),
+            Error::Taken(name) => write!(f, "a spout sender called {} is running", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A Spout sender receivers on this machine see by name.
+pub struct SpoutSender {
+    name: String,
+    #[cfg(all(feature = "spout", target_os = "windows"))]
+    sender: dx::Sender,
+}
+
+impl SpoutSender {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        #[cfg(all(feature = "spout", target_os = "windows"))]
+        {
+            Ok(Self {
+                name: name.to_string(),
+                sender: dx::Sender::new(name)?,
+            })
+        }
+        #[cfg(not(all(feature = "spout", target_os = "windows")))]
+        {
+            let _ = name;
+            Err(Error::Unavailable)
+        }
+    }
+}
+
+impl Server for SpoutSender {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn publish(&mut self, frame: SharedFrame) {
+        #[cfg(all(feature = "spout", target_os = "windows"))]
+        self.sender.publish(frame);
+        #[cfg(not(all(feature = "spout", target_os = "windows")))]
+        let _ = frame;
+    }
+}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+mod dx {
+    use super::Error;
+    use crate::share::SharedFrame;
+    use std::{ffi::CString, mem, ptr, slice};
+    use winapi::shared::dxgi::IDXGIResource;
+    use winapi::shared::dxgiformat::DXGI_FORMAT_B8G8R8A8_UNORM;
+    use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::um::d3d11::{
+        D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Resource, ID3D11Texture2D,
+        D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        D3D11_RESOURCE_MISC_SHARED, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+    };
+    use winapi::um::d3dcommon::D3D_DRIVER_TYPE_HARDWARE;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::memoryapi::{
+        MapViewOfFile, UnmapViewOfFile, VirtualQuery, FILE_MAP_ALL_ACCESS, FILE_MAP_READ,
+    };
+    use winapi::um::synchapi::{CreateMutexA, ReleaseMutex, WaitForSingleObject};
+    use winapi::um::winbase::{
+        CreateFileMappingA, OpenFileMappingA, WAIT_ABANDONED, WAIT_OBJECT_0,
+    };
+    use winapi::um::winnt::{HANDLE, MEMORY_BASIC_INFORMATION, PAGE_READWRITE};
+    use winapi::Interface;
+
+    /// the list of running senders, `MAX_SENDERS` names of `NAME_LEN` bytes
+    const SENDER_NAMES: &str = "SpoutSenderNames";
+    /// the sender receivers pick when not told one
+    const ACTIVE_SENDER: &str = "ActiveSenderName";
+    /// Spout's default, receivers stop at the first empty name anyway
+    const MAX_SENDERS: usize = 64;
+    const NAME_LEN: usize = 256;
+    /// how long a receiver may hold a lock before the frame is dropped
+    const LOCK_MS: DWORD = 67;
+
+    /// The block named after each sender, Spout's `SharedTextureInfo`.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct TextureInfo {
+        share_handle: u32,
+        width: u32,
+        height: u32,
+        format: u32,
+        usage: u32,
+        description: [u16; 128],
+        partner_id: u32,
+    }
+
+    fn c_name(name: &str) -> Result<CString, Error> {
+        CString::new(name).map_err(|_| Error::SharedMemory(name.to_string()))
+    }
+
+    /// A named shared memory block and the `<name>_mutex` Spout guards it
+    /// with.
+    struct Shared {
+        mapping: HANDLE,
+        view: *mut u8,
+        len: usize,
+        mutex: HANDLE,
+    }
+
+    impl Shared {
+        /// the block, created `len` bytes long if it doesn't exist yet
+        fn open(name: &str, len: usize) -> Result<Self, Error> {
+            let missing = || Error::SharedMemory(name.to_string());
+            let mapping_name = c_name(name)?;
+            let mutex_name = c_name(&format!("{}_mutex", name))?;
+            unsafe {
+                let mapping = CreateFileMappingA(
+                    INVALID_HANDLE_VALUE,
+                    ptr::null_mut(),
+                    PAGE_READWRITE,
+                    0,
+                    len as DWORD,
+                    mapping_name.as_ptr(),
+                );
+                if mapping.is_null() {
+                    return Err(missing());
+                }
+                let view = MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, 0) as *mut u8;
+                if view.is_null() {
+                    CloseHandle(mapping);
+                    return Err(missing());
+                }
+                // a block other senders made first keeps the length they gave it
+                let mut region: MEMORY_BASIC_INFORMATION = mem::zeroed();
+                VirtualQuery(view as _, &mut region, mem::size_of_val(&region));
+                let mutex = CreateMutexA(ptr::null_mut(), FALSE, mutex_name.as_ptr());
+                Ok(Self {
+                    mapping,
+                    view,
+                    len: len.min(region.RegionSize),
+                    mutex,
+                })
+            }
+        }
+
+        /// `f` on the block while holding its mutex, None if a receiver
+        /// held on too long
+        fn with<R>(&self, f: impl FnOnce(&mut [u8]) -> R) -> Option<R> {
+            unsafe {
+                if !self.mutex.is_null() {
+                    let waited = WaitForSingleObject(self.mutex, LOCK_MS);
+                    if waited != WAIT_OBJECT_0 && waited != WAIT_ABANDONED {
+                        return None;
+                    }
+                }
+                let result = f(slice::from_raw_parts_mut(self.view, self.len));
+                if !self.mutex.is_null() {
+                    ReleaseMutex(self.mutex);
+                }
+                Some(result)
+            }
+        }
+    }
+
+    impl Drop for Shared {
+        fn drop(&mut self) {
+            unsafe {
+                UnmapViewOfFile(self.view as _);
+                CloseHandle(self.mapping);
+                if !self.mutex.is_null() {
+                    CloseHandle(self.mutex);
+                }
+            }
+        }
+    }
+
+    fn read_name(slot: &[u8]) -> String {
+        let end = slot.iter().position(|&b| b == 0).unwrap_or(slot.len());
+        String::from_utf8_lossy(&slot[..end]).into_owned()
+    }
+
+    fn write_name(slot: &mut [u8], name: &str) {
+        for b in slot.iter_mut() {
+            *b = 0;
+        }
+        let len = name.len().min(slot.len() - 1);
+        slot[..len].copy_from_slice(&name.as_bytes()[..len]);
+    }
+
+    fn read_names(block: &[u8]) -> Vec<String> {
+        block
+            .chunks_exact(NAME_LEN)
+            .map(read_name)
+            .take_while(|name| !name.is_empty())
+            .collect()
+    }
+
+    fn write_names(block: &mut [u8], names: &[String]) {
+        for (i, slot) in block.chunks_exact_mut(NAME_LEN).enumerate() {
+            write_name(slot, names.get(i).map_or("", String::as_str));
+        }
+    }
+
+    /// a sender that quit without unlisting itself has no info block left
+    fn running(name: &str) -> bool {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        unsafe {
+            let mapping = OpenFileMappingA(FILE_MAP_READ, FALSE, name.as_ptr());
+            if mapping.is_null() {
+                return false;
+            }
+            CloseHandle(mapping);
+            true
+        }
+    }
+
+    /// Spout's access mutex, receivers hold it while they copy the texture.
+    struct AccessMutex(HANDLE);
+
+    impl AccessMutex {
+        fn new(name: &str) -> Result<Self, Error> {
+            let mutex_name = c_name(&format!("{}_SpoutAccessMutex", name))?;
+            let mutex = unsafe { CreateMutexA(ptr::null_mut(), FALSE, mutex_name.as_ptr()) };
+            if mutex.is_null() {
+                return Err(Error::SharedMemory(name.to_string()));
+            }
+            Ok(Self(mutex))
+        }
+    }
+
+    impl Drop for AccessMutex {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// A shared texture of the last frame's size and the handle receivers
+    /// open it by.
+    struct Texture {
+        texture: *mut ID3D11Texture2D,
+        handle: HANDLE,
+        width: u32,
+        height: u32,
+    }
+
+    impl Drop for Texture {
+        fn drop(&mut self) {
+            unsafe { (*self.texture).Release() };
+        }
+    }
+
+    pub struct Sender {
+        name: String,
+        device: *mut ID3D11Device,
+        context: *mut ID3D11DeviceContext,
+        texture: Option<Texture>,
+        /// the frame swizzled to BGRA, the format every receiver takes
+        bgra: Vec<u8>,
+        names: Shared,
+        info: Shared,
+        access: AccessMutex,
+    }
+
+    // the device is free threaded and the context is only used from one
+    // thread at a time behind the share's lock
+    unsafe impl Send for Sender {}
+
+    impl Sender {
+        pub fn new(name: &str) -> Result<Self, Error> {
+            let names = Shared::open(SENDER_NAMES, MAX_SENDERS * NAME_LEN)?;
+            let info = Shared::open(name, mem::size_of::<TextureInfo>())?;
+            let access = AccessMutex::new(name)?;
+            let (device, context) = create_device()?;
+            let sender = Self {
+                name: name.to_string(),
+                device,
+                context,
+                texture: None,
+                bgra: Vec::new(),
+                names,
+                info,
+                access,
+            };
+            sender.register()?;
+            Ok(sender)
+        }
+
+        /// lists the sender, dropping any that quit without unlisting, and
+        /// makes it the active one if there's none
+        fn register(&self) -> Result<(), Error> {
+            let name = &self.name;
+            let listed = self.names.with(|block| {
+                let mut names = read_names(block);
+                names.retain(|other| other == name || running(other));
+                if names.iter().any(|other| other == name) {
+                    return false;
+                }
+                names.push(name.clone());
+                names.sort();
+                names.truncate(block.len() / NAME_LEN);
+                write_names(block, &names);
+                true
+            });
+            match listed {
+                Some(true) => {}
+                Some(false) => return Err(Error::Taken(name.clone())),
+                None => return Err(Error::SharedMemory(SENDER_NAMES.to_string())),
+            }
+            if let Ok(active) = Shared::open(ACTIVE_SENDER, NAME_LEN) {
+                active.with(|slot| {
+                    let current = read_name(slot);
+                    if current.is_empty() || !running(&current) {
+                        write_name(slot, name);
+                    }
+                });
+            }
+            Ok(())
+        }
+
+        fn unregister(&self) {
+            let name = &self.name;
+            self.names.with(|block| {
+                let mut names = read_names(block);
+                names.retain(|other| other != name);
+                write_names(block, &names);
+            });
+            if let Ok(active) = Shared::open(ACTIVE_SENDER, NAME_LEN) {
+                active.with(|slot| {
+                    if read_name(slot) == *name {
+                        write_name(slot, "");
+                    }
+                });
+            }
+        }
+
+        /// a texture the size of the frame, a new one published in the info
+        /// block when the size changes
+        fn texture(&mut self, width: u32, height: u32) -> Option<&Texture> {
+            let stale = match &self.texture {
+                Some(texture) => (texture.width, texture.height) != (width, height),
+                None => true,
+            };
+            if stale {
+                self.texture = None;
+                let texture = unsafe { create_texture(self.device, width, height)? };
+                let info = TextureInfo {
+                    share_handle: texture.handle as usize as u32,
+                    width,
+                    height,
+                    format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                    usage: 0,
+                    description: [0; 128],
+                    partner_id: 0,
+                };
+                self.info.with(|block| unsafe {
+                    ptr::write_unaligned(block.as_mut_ptr() as *mut TextureInfo, info)
+                })?;
+                self.texture = Some(texture);
+            }
+            self.texture.as_ref()
+        }
+
+        pub fn publish(&mut self, frame: SharedFrame) {
+            let stride = frame.width as usize * 4;
+            let len = stride * frame.height as usize;
+            if frame.width == 0 || frame.rgba.len() < len {
+                return;
+            }
+            self.bgra.resize(len, 0);
+            for (bgra, rgba) in self
+                .bgra
+                .chunks_exact_mut(4)
+                .zip(frame.rgba.chunks_exact(4))
+            {
+                bgra.copy_from_slice(&[rgba[2], rgba[1], rgba[0], rgba[3]]);
+            }
+            let texture = match self.texture(frame.width, frame.height) {
+                Some(texture) => texture.texture,
+                None => return,
+            };
+            unsafe {
+                let waited = WaitForSingleObject(self.access.0, LOCK_MS);
+                if waited != WAIT_OBJECT_0 && waited != WAIT_ABANDONED {
+                    return;
+                }
+                (*self.context).UpdateSubresource(
+                    texture as *mut ID3D11Resource,
+                    0,
+                    ptr::null(),
+                    self.bgra.as_ptr() as _,
+                    stride as u32,
+                    0,
+                );
+                // receivers open the texture on their own devices, it has to
+                // be on the GPU before they read it
+                (*self.context).Flush();
+                ReleaseMutex(self.access.0);
+            }
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            self.unregister();
+            self.texture = None;
+            unsafe {
+                (*self.context).Release();
+                (*self.device).Release();
+            }
+        }
+    }
+
+    fn create_device() -> Result<(*mut ID3D11Device, *mut ID3D11DeviceContext), Error> {
+        let mut device = ptr::null_mut();
+        let mut context = ptr::null_mut();
+        let result = unsafe {
+            D3D11CreateDevice(
+                ptr::null_mut(),
+                D3D_DRIVER_TYPE_HARDWARE,
+                ptr::null_mut(),
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                ptr::null(),
+                0,
+                D3D11_SDK_VERSION,
+                &mut device,
+                ptr::null_mut(),
+                &mut context,
+            )
+        };
+        if SUCCEEDED(result) && !device.is_null() && !context.is_null() {
+            Ok((device, context))
+        } else {
+            Err(Error::Device)
+        }
+    }
+
+    unsafe fn create_texture(
+        device: *mut ID3D11Device,
+        width: u32,
+        height: u32,
+    ) -> Option<Texture> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE | D3D11_BIND_RENDER_TARGET,
+            CPUAccessFlags: 0,
+            MiscFlags: D3D11_RESOURCE_MISC_SHARED,
+        };
+        let mut texture = ptr::null_mut();
+        if !SUCCEEDED((*device).CreateTexture2D(&desc, ptr::null(), &mut texture)) {
+            return None;
+        }
+        let mut texture = Texture {
+            texture,
+            handle: ptr::null_mut(),
+            width,
+            height,
+        };
+        let mut resource: *mut IDXGIResource = ptr::null_mut();
+        let queried = (*texture.texture).QueryInterface(
+            &IDXGIResource::uuidof(),
+            &mut resource as *mut *mut IDXGIResource as *mut _,
+        );
+        if !SUCCEEDED(queried) {
+            return None;
+        }
+        let mut handle = ptr::null_mut();
+        let shared = (*resource).GetSharedHandle(&mut handle);
+        (*resource).Release();
+        if !SUCCEEDED(shared) || handle.is_null() {
+            return None;
+        }
+        texture.handle = handle;
+        Some(texture)
+    }
+}
@@ -0,0 +1,170 @@
+//! What an app falls back to when part of it fails to start.
+//!
+//! A missing audio device or sample shows an `ErrorScreen` in place of the
+//! scene, the app keeps running and can retry. Only a UI that cannot be
+//! built is fatal.
+
+use crate::audio::{self, Supervisor};
+use crate::render::Render;
+use crate::theme::{self, Palette};
+use nannou::prelude::*;
+use std::{fmt, path::PathBuf};
+
+pub const RETRY: Key = Key::R;
+pub const DISMISS: Key = Key::Escape;
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    Ui(String),
+    Audio(audio::Error),
+    Sample { path: PathBuf, reason: String },
+    Input(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Ui(e) => write!(f, "failed to build the UI: {}", e),
+            Error::Audio(e) => write!(f, "{}", e),
+            Error::Sample { path, reason } => {
+                write!(f, "cannot read sample {}: {}", path.display(), reason)
+            }
+            Error::Input(e) => write!(f, "cannot open audio input: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<audio::Error> for Error {
+    fn from(e: audio::Error) -> Self {
+        Error::Audio(e)
+    }
+}
+
+/// reports `error` and exits, for failures nothing can be drawn without
+pub fn fatal(app_name: &str, error: Error) -> ! {
+    eprintln!("{}: {}", app_name, error);
+    std::process::exit(1);
+}
+
+/// Lists what went wrong at startup with retry and device-change options.
+///
+/// Up/down picks an output device, return retries on it, `RETRY` retries on
+/// the configured one and `DISMISS` carries on without what failed.
+pub struct ErrorScreen {
+    errors: Vec<Error>,
+    /// `None` is the system default
+    devices: Vec<Option<String>>,
+    selected: usize,
+}
+
+impl ErrorScreen {
+    /// `None` when there is nothing to show
+    pub fn new(errors: Vec<Error>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        let mut screen = Self {
+            errors,
+            devices: Vec::new(),
+            selected: 0,
+        };
+        screen.scan();
+        Some(screen)
+    }
+
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    fn scan(&mut self) {
+        let names = audio::output_devices(None);
+        self.devices = std::iter::once(None)
+            .chain(names.into_iter().map(Some))
+            .collect();
+        self.selected = self.selected.min(self.devices.len() - 1);
+    }
+
+    fn retry<M: Render + 'static + Send>(
+        &mut self,
+        stream: &mut Supervisor<M>,
+        device: Option<Option<String>>,
+    ) {
+        let result = match device {
+            Some(device) => stream.set_device(device),
+            None => stream.rebuild(),
+        };
+        self.errors.retain(|e| !matches!(e, Error::Audio(_)));
+        if let Err(e) = result {
+            self.errors.push(e.into());
+        }
+        self.scan();
+    }
+
+    /// true once the screen should close
+    pub fn key_pressed<M: Render + 'static + Send>(
+        &mut self,
+        key: Key,
+        stream: &mut Supervisor<M>,
+    ) -> bool {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.devices.len() - 1),
+            Key::Return => {
+                let device = self.devices[self.selected].clone();
+                self.retry(stream, Some(device));
+            }
+            RETRY => self.retry(stream, None),
+            DISMISS => return true,
+            _ => {}
+        }
+        self.errors.is_empty()
+    }
+
+    /// called at frame rate, the supervisor may have found a device on its own
+    pub fn update<M: Render + 'static + Send>(&mut self, stream: &Supervisor<M>) -> bool {
+        if stream.is_running() {
+            self.errors.retain(|e| !matches!(e, Error::Audio(_)));
+        }
+        self.errors.is_empty()
+    }
+
+    pub fn draw(&self, draw: &Draw, rect: Rect, palette: &Palette) {
+        const LINE: f32 = 24.0;
+
+        draw.background().color(theme::color(palette.background));
+        let text = theme::color(palette.line);
+        let accent = theme::color(palette.accent(0));
+        let mut y = rect.top() - 2.0 * LINE;
+        let mut line = |message: &str, color: Rgb, size: u32| {
+            draw.text(message)
+                .x_y(0.0, y)
+                .w_h(rect.w() - 4.0 * LINE, LINE)
+                .left_justify()
+                .font_size(size)
+                .color(color);
+            y -= LINE;
+        };
+
+        for error in self.errors.iter() {
+            line(&error.to_string(), text, 16);
+        }
+        line("", text, 14);
+        line("output device:", text, 14);
+        for (i, device) in self.devices.iter().enumerate() {
+            let name = device.as_deref().unwrap_or("system default");
+            if i == self.selected {
+                line(&format!("> {}", name), accent, 14);
+            } else {
+                line(&format!("  {}", name), text, 14);
+            }
+        }
+        line("", text, 14);
+        line(
+            "up/down pick a device, return retries on it, r retries, escape continues",
+            text,
+            12,
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Syphon output on macOS, a `share::Server` publishing frames as a Metal
+//! texture that Resolume, TouchDesigner, VDMX and other Syphon clients on
+//! the machine pick by name.
+//!
+//! Needs the `syphon` feature and Syphon.framework 5 or later, in
+//! `/Library/Frameworks` or the app bundle's `Frameworks`. The framework is
+//! loaded when the server is created rather than linked.
+
+use crate::share::{Server, SharedFrame};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// not macOS, or built without the `syphon` feature
+    Unavailable,
+    /// the framework is missing or has no Metal server
+    Framework(String),
+    /// no Metal device, or the framework refused the server
+    Server,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(f, "syphon needs macOS and the `syphon` feature"),
+            Error::Framework(e) => write!(f, "syphon framework: {}", e),
+            Error::Server => write!(f, "syphon server could not be created"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A Syphon server clients on this machine see as `<app> - <name>`.
+pub struct SyphonServer {
+    name: String,
+    #[cfg(all(feature = "syphon", target_os = "macos"))]
+    publisher: metal::Publisher,
+}
+
+impl SyphonServer {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        #[cfg(all(feature = "syphon", target_os = "macos"))]
+        {
+            Ok(Self {
+                name: name.to_string(),
+                publisher: metal::Publisher::new(name)?,
+            })
+        }
+        #[cfg(not(all(feature = "syphon", target_os = "macos")))]
+        {
+            let _ = name;
+            Err(Error::Unavailable)
+        }
+    }
+}
+
+impl Server for SyphonServer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn publish(&mut self, frame: SharedFrame) {
+        #[cfg(all(feature = "syphon", target_os = "macos"))]
+        self.publisher.publish(frame);
+        #[cfg(not(all(feature = "syphon", target_os = "macos")))]
+        let _ = frame;
+    }
+}
+
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+mod metal {
+    use super::Error;
+    use crate::share::SharedFrame;
+    use objc::rc::autoreleasepool;
+    use objc::runtime::{Class, Object, NO};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::{ffi::CString, os::raw::c_void, path::PathBuf, ptr};
+
+    /// `MTLPixelFormatRGBA8Unorm`, the layout frames arrive in
+    const RGBA8_UNORM: usize = 70;
+    /// `MTLTextureUsageShaderRead`, Syphon draws from the texture
+    const SHADER_READ: usize = 1;
+
+    #[repr(C)]
+    struct NSPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    struct NSSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    struct NSRect {
+        origin: NSPoint,
+        size: NSSize,
+    }
+
+    #[repr(C)]
+    struct MTLRegion {
+        origin: [usize; 3],
+        size: [usize; 3],
+    }
+
+    #[link(name = "Metal", kind = "framework")]
+    extern "C" {
+        fn MTLCreateSystemDefaultDevice() -> *mut Object;
+    }
+
+    fn candidates() -> Vec<PathBuf> {
+        let binary = "Syphon.framework/Syphon";
+        let mut paths = Vec::new();
+        if let Some(bundle) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.parent()?.join("Frameworks")))
+        {
+            paths.push(bundle.join(binary));
+        }
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(PathBuf::from(home).join("Library/Frameworks").join(binary));
+        }
+        paths.push(PathBuf::from("/Library/Frameworks").join(binary));
+        paths
+    }
+
+    fn load() -> Result<libloading::Library, Error> {
+        let mut last_error = String::from("no framework to load");
+        for path in candidates() {
+            // registers the framework's classes and nothing else
+            match unsafe { libloading::Library::new(&path) } {
+                Ok(library) => return Ok(library),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(Error::Framework(last_error))
+    }
+
+    /// The server with a texture of the last frame's size to upload into.
+    pub struct Publisher {
+        device: *mut Object,
+        queue: *mut Object,
+        server: *mut Object,
+        texture: Option<(*mut Object, u32, u32)>,
+        // keeps the server's class loaded
+        _framework: libloading::Library,
+    }
+
+    // Metal devices and queues are thread safe, and the server is only used
+    // from one thread at a time behind the share's lock
+    unsafe impl Send for Publisher {}
+
+    impl Publisher {
+        pub fn new(name: &str) -> Result<Self, Error> {
+            let framework = load()?;
+            let class = Class::get("SyphonMetalServer").ok_or_else(|| {
+                Error::Framework("no SyphonMetalServer, Syphon 5 or later is needed".into())
+            })?;
+            let name = CString::new(name).map_err(|_| Error::Server)?;
+            autoreleasepool(|| unsafe {
+                let device = MTLCreateSystemDefaultDevice();
+                if device.is_null() {
+                    return Err(Error::Server);
+                }
+                let queue: *mut Object = msg_send![device, newCommandQueue];
+                let name: *mut Object =
+                    msg_send![class!(NSString), stringWithUTF8String: name.as_ptr()];
+                let server: *mut Object = msg_send![class, alloc];
+                let server: *mut Object = msg_send![server, initWithName: name
+                                                            device: device
+                                                            options: ptr::null_mut::<Object>()];
+                if server.is_null() {
+                    let _: () = msg_send![queue, release];
+                    let _: () = msg_send![device, release];
+                    return Err(Error::Server);
+                }
+                Ok(Self {
+                    device,
+                    queue,
+                    server,
+                    texture: None,
+                    _framework: framework,
+                })
+            })
+        }
+
+        /// a texture the size of the frame, the last one if it still fits
+        unsafe fn texture(&mut self, width: u32, height: u32) -> *mut Object {
+            match self.texture {
+                Some((texture, w, h)) if (w, h) == (width, height) => texture,
+                _ => {
+                    if let Some((old, _, _)) = self.texture.take() {
+                        let _: () = msg_send![old, release];
+                    }
+                    let descriptor: *mut Object = msg_send![class!(MTLTextureDescriptor),
+                        texture2DDescriptorWithPixelFormat: RGBA8_UNORM
+                        width: width as usize
+                        height: height as usize
+                        mipmapped: NO];
+                    let _: () = msg_send![descriptor, setUsage: SHADER_READ];
+                    let texture: *mut Object =
+                        msg_send![self.device, newTextureWithDescriptor: descriptor];
+                    self.texture = Some((texture, width, height));
+                    texture
+                }
+            }
+        }
+
+        pub fn publish(&mut self, frame: SharedFrame) {
+            let stride = frame.width as usize * 4;
+            if frame.width == 0 || frame.rgba.len() < stride * frame.height as usize {
+                return;
+            }
+            autoreleasepool(|| unsafe {
+                let texture = self.texture(frame.width, frame.height);
+                if texture.is_null() {
+                    return;
+                }
+                let region = MTLRegion {
+                    origin: [0, 0, 0],
+                    size: [frame.width as usize, frame.height as usize, 1],
+                };
+                let _: () = msg_send![texture, replaceRegion: region
+                                               mipmapLevel: 0usize
+                                               withBytes: frame.rgba.as_ptr() as *const c_void
+                                               bytesPerRow: stride];
+                let bounds = NSRect {
+                    origin: NSPoint { x: 0.0, y: 0.0 },
+                    size: NSSize {
+                        width: frame.width as f64,
+                        height: frame.height as f64,
+                    },
+                };
+                let commands: *mut Object = msg_send![self.queue, commandBuffer];
+                // rows are top first, as Metal has them
+                let _: () = msg_send![self.server, publishFrameTexture: texture
+                                                   onCommandBuffer: commands
+                                                   imageRegion: bounds
+                                                   flipped: NO];
+                let _: () = msg_send![commands, commit];
+                // Syphon has copied the texture before the next frame overwrites it
+                let _: () = msg_send![commands, waitUntilCompleted];
+            });
+        }
+    }
+
+    impl Drop for Publisher {
+        fn drop(&mut self) {
+            unsafe {
+                let _: () = msg_send![self.server, stop];
+                let _: () = msg_send![self.server, release];
+                if let Some((texture, _, _)) = self.texture.take() {
+                    let _: () = msg_send![texture, release];
+                }
+                let _: () = msg_send![self.queue, release];
+                let _: () = msg_send![self.device, release];
+            }
+        }
+    }
+}
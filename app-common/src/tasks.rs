@@ -0,0 +1,223 @@
+//! A few threads for work too slow for either the audio callback or a
+//! frame: loading samples, resampling, analysis, exports.
+//!
+//! A job is handed a `Progress` to report how far along it is and its
+//! result comes back through the `Pending` that `spawn` returns. The window
+//! polls `Tasks` once a frame and draws a bar for each job still running.
+
+use crate::theme::Palette;
+use crate::widget::Level;
+use nannou::ui::prelude::*;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// enough for most apps, file work is mostly waiting on the disk
+pub const THREADS: usize = 2;
+/// progress smaller than this isn't worth a message, a bar 200 points
+/// wide moves by two
+const STEP: f32 = 0.01;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+enum Message {
+    Progress(u64, f32),
+    Done(u64),
+}
+
+/// Where a job tells the window how far along it is.
+pub struct Progress {
+    id: u64,
+    messages: Sender<Message>,
+    /// the last fraction sent
+    sent: Cell<f32>,
+}
+
+impl Progress {
+    /// `fraction` of the job done, from 0 to 1, cheap enough to call every
+    /// buffer of an export
+    pub fn set(&self, fraction: f64) {
+        let fraction = fraction.clamp(0.0, 1.0) as f32;
+        if (fraction - self.sent.get()).abs() < STEP && fraction < 1.0 {
+            return;
+        }
+        self.sent.set(fraction);
+        let _ = self.messages.send(Message::Progress(self.id, fraction));
+    }
+}
+
+/// The result of a job, once it's done.
+pub struct Pending<T> {
+    result: Receiver<T>,
+}
+
+impl<T> Pending<T> {
+    /// the result if the job has finished since, `None` while it runs or if
+    /// it panicked
+    pub fn take(&self) -> Option<T> {
+        self.result.try_recv().ok()
+    }
+
+    /// blocks until the job is done, for app exit
+    pub fn wait(self) -> Option<T> {
+        self.result.recv().ok()
+    }
+}
+
+/// A job still running, as drawn.
+#[derive(Clone, Debug)]
+pub struct Running {
+    id: u64,
+    pub name: String,
+    /// from 0 to 1
+    pub progress: f32,
+}
+
+/// A pool of threads running jobs in the order they're spawned.
+pub struct Tasks {
+    app_name: String,
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    messages: Sender<Message>,
+    received: Receiver<Message>,
+    running: Vec<Running>,
+    next_id: u64,
+    bars: widget::id::List,
+    labels: widget::id::List,
+}
+
+impl Tasks {
+    pub fn new(app_name: &str, threads: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let queue = queue.clone();
+                thread::Builder::new()
+                    .name(format!("{}-task-{}", app_name, i))
+                    .spawn(move || loop {
+                        // the lock is only held while waiting, never while a
+                        // job runs
+                        let job = match queue.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => return,
+                        };
+                        job();
+                    })
+                    .expect("cannot spawn task thread")
+            })
+            .collect();
+        let (messages, received) = mpsc::channel();
+        Self {
+            app_name: app_name.to_string(),
+            jobs: Some(jobs),
+            workers,
+            messages,
+            received,
+            running: Vec::new(),
+            next_id: 0,
+            bars: widget::id::List::new(),
+            labels: widget::id::List::new(),
+        }
+    }
+
+    /// Queues `job` behind those already spawned, shown as `name` until it
+    /// finishes. A job that panics is logged and its `Pending` never
+    /// returns a result.
+    pub fn spawn<T, F>(&mut self, name: &str, job: F) -> Pending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Progress) -> T + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push(Running {
+            id,
+            name: name.to_string(),
+            progress: 0.0,
+        });
+
+        let (result, receiver) = mpsc::sync_channel(1);
+        let progress = Progress {
+            id,
+            messages: self.messages.clone(),
+            sent: Cell::new(0.0),
+        };
+        let (app_name, name) = (self.app_name.clone(), name.to_string());
+        let job = move || {
+            match panic::catch_unwind(AssertUnwindSafe(|| job(&progress))) {
+                Ok(value) => {
+                    let _ = result.send(value);
+                }
+                Err(_) => eprintln!("{}: {} panicked", app_name, name),
+            }
+            let _ = progress.messages.send(Message::Done(progress.id));
+        };
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(Box::new(job));
+        }
+        Pending { result: receiver }
+    }
+
+    /// takes in what the jobs have reported, once a frame
+    pub fn poll(&mut self) {
+        for message in self.received.try_iter() {
+            match message {
+                Message::Progress(id, fraction) => {
+                    if let Some(task) = self.running.iter_mut().find(|task| task.id == id) {
+                        task.progress = fraction;
+                    }
+                }
+                Message::Done(id) => self.running.retain(|task| task.id != id),
+            }
+        }
+    }
+
+    /// jobs queued or running, oldest first
+    pub fn running(&self) -> &[Running] {
+        &self.running
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// a progress bar for each job still running, below the last widget
+    pub fn panel(&mut self, palette: &Palette, ui: &mut UiCell) {
+        let count = self.running.len();
+        if self.bars.len() < count {
+            self.bars.resize(count, &mut ui.widget_id_generator());
+            self.labels.resize(count, &mut ui.widget_id_generator());
+        }
+        let style = palette.control_style();
+        for (i, task) in self.running.iter().enumerate() {
+            let bar = Level::new(task.progress, 0.0, 1.0)
+                .with_style(style)
+                .w_h(200.0, 20.0);
+            let bar = if i == 0 {
+                bar.down(20.0)
+            } else {
+                bar.down_from(self.bars[i - 1], 10.0)
+            };
+            bar.set(self.bars[i], ui);
+            widget::Text::new(&task.name)
+                .font_size(12)
+                .color(style.label)
+                .mid_left_with_margin_on(self.bars[i], 4.0)
+                .graphics_for(self.bars[i])
+                .set(self.labels[i], ui);
+        }
+    }
+}
+
+impl Drop for Tasks {
+    /// lets queued jobs finish, an export cut short is a broken file
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::widget::{control, meter, scope};
+use nannou::prelude::*;
+use nannou::ui;
+use nannou::ui::prelude::{Colorable, Labelable};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// cycles through the loaded palettes
+pub const HOTKEY: Key = Key::T;
+
+pub type Rgb = [f32; 3];
+
+pub fn color(c: Rgb) -> nannou::color::Rgb {
+    rgb(c[0], c[1], c[2])
+}
+
+pub fn ui_color(c: Rgb) -> ui::Color {
+    meter::rgb(c[0], c[1], c[2])
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiColors {
+    pub fill: Rgb,
+    pub label: Rgb,
+}
+
+impl Default for UiColors {
+    fn default() -> Self {
+        Self {
+            fill: [0.0, 0.5, 0.0],
+            label: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeterColors {
+    pub background: Rgb,
+    pub fill: Rgb,
+    pub peak: Rgb,
+    pub over: Rgb,
+}
+
+impl Default for MeterColors {
+    fn default() -> Self {
+        Self {
+            background: [0.1, 0.1, 0.1],
+            fill: [0.0, 0.5, 0.0],
+            peak: [0.0, 1.0, 0.0],
+            over: [1.0, 0.2, 0.2],
+        }
+    }
+}
+
+/// Named colors shared by the drawing and the UI, a missing key in a
+/// palette file takes the default palette's color.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub name: String,
+    pub background: Rgb,
+    pub line: Rgb,
+    /// per layer or voice colors, cycled when there are more layers
+    pub accents: Vec<Rgb>,
+    pub ui: UiColors,
+    pub meter: MeterColors,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::phosphor()
+    }
+}
+
+impl Palette {
+    /// green on black, lissa's original look
+    pub fn phosphor() -> Self {
+        Self {
+            name: String::from("phosphor"),
+            background: [0.04, 0.04, 0.04],
+            line: [0.0, 1.0, 0.0],
+            accents: vec![[0.0, 1.0, 0.0], [0.0, 0.5, 0.0]],
+            ui: UiColors::default(),
+            meter: MeterColors::default(),
+        }
+    }
+
+    /// bisque and pastels, yfes's original look
+    pub fn pastel() -> Self {
+        Self {
+            name: String::from("pastel"),
+            background: [1.0, 0.89, 0.77],
+            line: [0.13, 0.7, 0.67],
+            accents: vec![
+                [0.94, 0.5, 0.5],
+                [1.0, 0.63, 0.48],
+                [0.13, 0.7, 0.67],
+                [0.0, 0.81, 0.82],
+            ],
+            ui: UiColors {
+                fill: [0.13, 0.7, 0.67],
+                label: [0.0, 0.0, 0.0],
+            },
+            meter: MeterColors {
+                background: [0.76, 0.69, 0.6],
+                fill: [0.13, 0.7, 0.67],
+                peak: [0.0, 0.81, 0.82],
+                over: [0.94, 0.5, 0.5],
+            },
+        }
+    }
+
+    /// dark blue, kima's original look
+    pub fn midnight() -> Self {
+        Self {
+            name: String::from("midnight"),
+            background: [0.0, 0.0, 0.55],
+            line: [0.53, 0.81, 0.98],
+            accents: vec![[0.53, 0.81, 0.98], [1.0, 0.84, 0.0], [0.93, 0.51, 0.93]],
+            ui: UiColors {
+                fill: [0.53, 0.81, 0.98],
+                label: [0.0, 0.0, 0.2],
+            },
+            meter: MeterColors {
+                background: [0.0, 0.0, 0.3],
+                fill: [0.53, 0.81, 0.98],
+                peak: [1.0, 1.0, 1.0],
+                over: [1.0, 0.2, 0.2],
+            },
+        }
+    }
+
+    pub fn builtin() -> Vec<Palette> {
+        vec![Self::phosphor(), Self::pastel(), Self::midnight()]
+    }
+
+    pub fn accent(&self, index: usize) -> Rgb {
+        match self.accents.len() {
+            0 => self.line,
+            len => self.accents[index % len],
+        }
+    }
+
+    pub fn meter_style(&self) -> meter::Style {
+        meter::Style {
+            background: ui_color(self.meter.background),
+            fill: ui_color(self.meter.fill),
+            peak: ui_color(self.meter.peak),
+            over: ui_color(self.meter.over),
+        }
+    }
+
+    /// knobs, pads, toggles, readouts and levels
+    pub fn control_style(&self) -> control::Style {
+        control::Style {
+            background: ui_color(self.meter.background),
+            fill: ui_color(self.ui.fill),
+            accent: ui_color(self.accent(0)),
+            label: ui_color(self.line),
+        }
+    }
+
+    /// the meter's background, the figure's line and the UI's colors
+    pub fn scope_style(&self) -> scope::Style {
+        scope::Style {
+            background: ui_color(self.meter.background),
+            grid: ui_color(self.meter.fill).with_alpha(0.3),
+            trace: ui_color(self.line),
+            trigger: ui_color(self.meter.over),
+            fill: ui_color(self.ui.fill),
+            label: ui_color(self.ui.label),
+        }
+    }
+}
+
+/// Gives a conrod widget the palette's fill and label colors.
+pub trait Themed<'a>: Colorable + Labelable<'a> + Sized {
+    fn themed(self, palette: &Palette) -> Self {
+        self.color(ui_color(palette.ui.fill))
+            .label_color(ui_color(palette.ui.label))
+    }
+}
+
+impl<'a, W: Colorable + Labelable<'a>> Themed<'a> for W {}
+
+/// `themes/` in the config directory shared by every app
+pub fn dir() -> PathBuf {
+    directories::ProjectDirs::from("", "", "nannou-apps")
+        .map(|dirs| dirs.config_dir().join("themes"))
+        .unwrap_or_else(|| PathBuf::from("themes"))
+}
+
+/// every `.toml` palette in `dir()`, malformed files are skipped with a warning
+pub fn load_dir() -> Vec<Palette> {
+    let entries = match fs::read_dir(dir()) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut palettes: Vec<Palette> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("toml"))
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path).ok()?;
+            match toml::from_str::<Palette>(&text) {
+                Ok(mut palette) => {
+                    if palette.name == Palette::default().name {
+                        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                            palette.name = stem.to_string();
+                        }
+                    }
+                    Some(palette)
+                }
+                Err(e) => {
+                    eprintln!("ignoring malformed theme {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+    palettes.sort_by(|a, b| a.name.cmp(&b.name));
+    palettes
+}
+
+/// The built-in palettes followed by the user's, one of them current.
+pub struct Themes {
+    palettes: Vec<Palette>,
+    current: usize,
+}
+
+impl Themes {
+    /// starts on `name`, or the first palette if there is none by that name
+    pub fn load(name: &str) -> Self {
+        let mut palettes = Palette::builtin();
+        for palette in load_dir() {
+            match palettes.iter_mut().find(|p| p.name == palette.name) {
+                Some(existing) => *existing = palette,
+                None => palettes.push(palette),
+            }
+        }
+        let mut themes = Self {
+            palettes,
+            current: 0,
+        };
+        themes.select(name);
+        themes
+    }
+
+    pub fn current(&self) -> &Palette {
+        &self.palettes[self.current]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.palettes.iter().map(|p| p.name.as_str())
+    }
+
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.palettes.iter().position(|p| p.name == name) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.palettes.len();
+    }
+
+    /// cycles on `HOTKEY`, true if the palette changed
+    pub fn key_pressed(&mut self, key: Key) -> bool {
+        if key == HOTKEY && self.palettes.len() > 1 {
+            self.cycle();
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use super::{FrameRate, Reading, Timecode};
+
+/// bits to a frame, the last 16 are the sync word
+const BITS: u32 = 80;
+/// the sync word as it reads least significant bit first, backwards it
+/// would be another
+const SYNC: u128 = 0xBFFC;
+/// how far past zero the signal has to swing to count as a flip
+const HYSTERESIS: f32 = 0.01;
+/// how quickly the bit length follows the source's speed
+const ADAPT: f32 = 0.1;
+
+/// Linear timecode off an audio input, in biphase mark: every bit starts
+/// with a flip and a 1 flips again halfway through.
+#[derive(Clone, Debug)]
+pub struct LtcDecoder {
+    sample_rate: u32,
+    high: bool,
+    /// samples since the last flip
+    since: f32,
+    /// samples a bit lasts
+    bit: f32,
+    /// the first half of a 1 has gone by
+    half: bool,
+    /// the latest frame's worth of bits, the newest at the top
+    bits: u128,
+}
+
+impl LtcDecoder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            high: false,
+            since: 0.0,
+            // 30fps, the others are close enough to be found from it
+            bit: sample_rate as f32 / (30 * BITS) as f32,
+            half: false,
+            bits: 0,
+        }
+    }
+
+    /// starts over when the rate changes
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            *self = Self::new(sample_rate);
+        }
+    }
+
+    /// a channel of input, the last reading completed in it
+    pub fn push(&mut self, samples: &[f32]) -> Option<Reading> {
+        let mut reading = None;
+        for (i, &x) in samples.iter().enumerate() {
+            self.since += 1.0;
+            let high = x > if self.high { -HYSTERESIS } else { HYSTERESIS };
+            if high == self.high {
+                continue;
+            }
+            self.high = high;
+            let interval = std::mem::replace(&mut self.since, 0.0);
+            if let Some(bit) = self.flip(interval) {
+                self.bits = self.bits >> 1 | u128::from(bit) << (BITS - 1);
+                if self.bits >> (BITS - 16) == SYNC {
+                    let after = (samples.len() - i - 1) as f64 / f64::from(self.sample_rate);
+                    reading = Some(self.decode(after));
+                }
+            }
+        }
+        reading
+    }
+
+    /// the bit a flip `interval` samples after the last one ends, if it
+    /// ends one
+    fn flip(&mut self, interval: f32) -> Option<bool> {
+        if interval < self.bit * 0.75 {
+            self.bit += (interval * 2.0 - self.bit) * ADAPT;
+            self.half = !self.half;
+            if self.half {
+                None
+            } else {
+                Some(true)
+            }
+        } else if interval < self.bit * 1.5 {
+            self.bit += (interval - self.bit) * ADAPT;
+            // a 0 always starts a bit, which puts the halves back in step
+            self.half = false;
+            Some(false)
+        } else {
+            // silence or a dropout
+            self.half = false;
+            self.bits = 0;
+            None
+        }
+    }
+
+    /// the frame in `bits`, read to its end and `after` seconds more
+    fn decode(&self, after: f64) -> Reading {
+        let field = |start: u32, len: u32| (self.bits >> start & ((1 << len) - 1)) as u8;
+        let timecode = Timecode {
+            hours: field(48, 4) + 10 * field(56, 2),
+            minutes: field(32, 4) + 10 * field(40, 3),
+            seconds: field(16, 4) + 10 * field(24, 3),
+            frames: field(0, 4) + 10 * field(8, 2),
+        };
+        // only drop frame is flagged, the others are told apart by speed
+        let fps = self.sample_rate as f32 / (self.bit * BITS as f32);
+        let rate = if field(10, 1) == 1 {
+            FrameRate::Fps30Drop
+        } else if fps < 24.5 {
+            FrameRate::Fps24
+        } else if fps < 27.5 {
+            FrameRate::Fps25
+        } else {
+            FrameRate::Fps30
+        };
+        Reading {
+            timecode,
+            rate,
+            seconds: timecode.to_seconds(rate) + 1.0 / rate.fps() + after,
+            running: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Biphase mark at a steady speed, carrying on from where it left off.
+    struct Encoder {
+        level: f32,
+        /// samples a bit lasts
+        bit: f64,
+        /// where the signal is up to, between samples
+        time: f64,
+        out: Vec<f32>,
+    }
+
+    impl Encoder {
+        fn new(rate: FrameRate) -> Self {
+            Self {
+                level: -0.5,
+                bit: f64::from(SAMPLE_RATE) / (rate.fps() * f64::from(BITS)),
+                time: 0.0,
+                out: Vec::new(),
+            }
+        }
+
+        fn hold(&mut self, level: f32, samples: f64) {
+            self.time += samples;
+            while (self.out.len() as f64) < self.time.round() {
+                self.out.push(level);
+            }
+        }
+
+        fn bit(&mut self, one: bool) {
+            self.level = -self.level;
+            self.hold(self.level, self.bit / 2.0);
+            if one {
+                self.level = -self.level;
+            }
+            self.hold(self.level, self.bit / 2.0);
+        }
+
+        /// the frame's bits and the sync word, least significant first
+        fn frame(&mut self, timecode: Timecode, drop: bool) {
+            let bcd = |value: u8, start: u32| {
+                u128::from(value % 10) << start | u128::from(value / 10) << (start + 8)
+            };
+            let word = bcd(timecode.frames, 0)
+                | u128::from(drop) << 10
+                | bcd(timecode.seconds, 16)
+                | bcd(timecode.minutes, 32)
+                | bcd(timecode.hours, 48)
+                | SYNC << (BITS - 16);
+            for i in 0..BITS {
+                self.bit(word >> i & 1 == 1);
+            }
+        }
+
+        fn frames(&mut self, from: Timecode, count: u8, drop: bool) {
+            for i in 0..count {
+                self.frame(
+                    Timecode {
+                        frames: from.frames + i,
+                        ..from
+                    },
+                    drop,
+                );
+            }
+        }
+
+        fn silence(&mut self, samples: f64) {
+            self.hold(0.0, samples);
+        }
+
+        /// the next bit's flip, which ends the last one
+        fn finish(mut self) -> Vec<f32> {
+            self.level = -self.level;
+            self.hold(self.level, self.bit / 2.0);
+            self.out
+        }
+    }
+
+    fn timecode(hours: u8, minutes: u8, seconds: u8, frames: u8) -> Timecode {
+        Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+        }
+    }
+
+    #[test]
+    fn round_trips_at_every_rate() {
+        let rates = [
+            FrameRate::Fps24,
+            FrameRate::Fps25,
+            FrameRate::Fps30Drop,
+            FrameRate::Fps30,
+        ];
+        for &rate in &rates {
+            let drop = rate == FrameRate::Fps30Drop;
+            let start = timecode(13, 47, 29, 10);
+            let mut encoder = Encoder::new(rate);
+            // a second to find the speed, the last frame is the one read
+            encoder.frames(start, 12, drop);
+            let signal = encoder.finish();
+
+            let mut decoder = LtcDecoder::new(SAMPLE_RATE);
+            let reading = decoder.push(&signal).unwrap();
+            let last = timecode(13, 47, 29, 21);
+            assert_eq!(reading.timecode, last, "{:?}", rate);
+            assert_eq!(reading.rate, rate);
+            assert!(reading.running);
+            // read as the next frame starts, half a bit before the signal ends
+            let bit = 1.0 / (rate.fps() * f64::from(BITS));
+            let expected = last.to_seconds(rate) + 1.0 / rate.fps() + bit / 2.0;
+            assert!((reading.seconds - expected).abs() < 1e-3, "{:?}", rate);
+        }
+    }
+
+    #[test]
+    fn reads_across_pushes() {
+        let mut encoder = Encoder::new(FrameRate::Fps25);
+        encoder.frames(timecode(0, 0, 1, 0), 6, false);
+        let signal = encoder.finish();
+
+        let mut decoder = LtcDecoder::new(SAMPLE_RATE);
+        let reading = signal
+            .chunks(64)
+            .filter_map(|block| decoder.push(block))
+            .last()
+            .unwrap();
+        assert_eq!(reading.timecode, timecode(0, 0, 1, 5));
+    }
+
+    #[test]
+    fn a_dropout_forgets_the_bits_before_it() {
+        let mut encoder = Encoder::new(FrameRate::Fps25);
+        encoder.frames(timecode(10, 0, 0, 0), 4, false);
+        // half a frame, then the cable's pulled
+        for i in 0..BITS / 2 {
+            encoder.bit(i % 3 == 0);
+        }
+        encoder.silence(f64::from(SAMPLE_RATE) / 10.0);
+        let resumed = encoder.out.len();
+        encoder.frame(timecode(10, 0, 5, 0), false);
+        let signal = encoder.finish();
+
+        let mut decoder = LtcDecoder::new(SAMPLE_RATE);
+        let reading = decoder.push(&signal[..resumed]).unwrap();
+        assert_eq!(reading.timecode, timecode(10, 0, 0, 3));
+        assert_ne!(decoder.bits, 0);
+        // the flip the signal comes back with ends the silence, not a bit
+        assert_eq!(decoder.push(&signal[resumed..resumed + 1]), None);
+        assert_eq!(decoder.bits, 0);
+        let reading = decoder.push(&signal[resumed + 1..]).unwrap();
+        assert_eq!(reading.timecode, timecode(10, 0, 5, 0));
+    }
+}
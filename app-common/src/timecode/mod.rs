@@ -0,0 +1,431 @@
+//! Chasing show control timecode with the transport, so an app can run as
+//! one timed element of a larger installation.
+//!
+//! `[timecode]` reads MIDI Time Code from a port, `source = "mtc"`, or
+//! linear timecode from an audio input, `source = "ltc"` with the input's
+//! `channel`. The transport plays while the code runs and parks where it
+//! stops, beat 0 falling on `start`, e.g. `"01:00:00:00"`, at the
+//! transport's tempo. It's moved back whenever it drifts more than a
+//! frame, between times it runs on its own clock.
+
+use crate::input::{self, Input, InputConfig, InputReader};
+use crate::midi;
+use crate::transport::Transport;
+use ringbuf::{Consumer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+mod ltc;
+mod mtc;
+
+pub use ltc::LtcDecoder;
+pub use mtc::MtcDecoder;
+
+/// how long the source can go quiet before it counts as stopped
+const DROPOUT: Duration = Duration::from_millis(250);
+/// seconds the transport can drift from the code before it's moved back,
+/// a frame at 25fps
+const TOLERANCE: f64 = 0.04;
+/// MTC readings between polls, well over a second's worth
+const READINGS: usize = 256;
+/// LTC samples read at a time
+const SCRATCH: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameRate {
+    Fps24,
+    Fps25,
+    /// 29.97fps, counting on as if 30 with two frame numbers skipped most
+    /// minutes
+    Fps30Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    /// from MTC's two bit rate code
+    fn from_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps30Drop,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    pub fn fps(self) -> f64 {
+        match self {
+            FrameRate::Fps24 => 24.0,
+            FrameRate::Fps25 => 25.0,
+            FrameRate::Fps30Drop => 30_000.0 / 1001.0,
+            FrameRate::Fps30 => 30.0,
+        }
+    }
+}
+
+/// `hh:mm:ss:ff`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+}
+
+impl Timecode {
+    /// since midnight, with frames at `rate`
+    pub fn to_seconds(self, rate: FrameRate) -> f64 {
+        let minutes = u32::from(self.hours) * 60 + u32::from(self.minutes);
+        let whole = minutes * 60 + u32::from(self.seconds);
+        match rate {
+            FrameRate::Fps30Drop => {
+                // frames 0 and 1 are skipped every minute but the tenth
+                let frame = (whole * 30 + u32::from(self.frames))
+                    .saturating_sub(2 * (minutes - minutes / 10));
+                f64::from(frame) / rate.fps()
+            }
+            _ => f64::from(whole) + f64::from(self.frames) / rate.fps(),
+        }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = Error;
+
+    /// `hh:mm:ss:ff`, drop frame's `;` before the frames is taken too
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let bad = || Error::Start(s.to_string());
+        let fields: Vec<u8> = s
+            .split([':', ';'])
+            .map(|field| field.trim().parse().map_err(|_| bad()))
+            .collect::<Result<_, _>>()?;
+        match *fields.as_slice() {
+            [hours, minutes, seconds, frames] if hours < 24 && minutes < 60 && seconds < 60 => {
+                Ok(Self {
+                    hours,
+                    minutes,
+                    seconds,
+                    frames,
+                })
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// Where the source is, as of when it arrived.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    pub timecode: Timecode,
+    pub rate: FrameRate,
+    /// since midnight, on from `timecode` by the time it took to send
+    pub seconds: f64,
+    /// false when the source has located and is parked there
+    pub running: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Mtc,
+    Ltc,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimecodeConfig {
+    pub source: Source,
+    /// MIDI port for MTC, input device for LTC, the first port or the
+    /// default device when absent
+    pub device: Option<String>,
+    /// the input channel carrying LTC, 0-based
+    pub channel: usize,
+    /// the timecode beat 0 falls on, midnight when absent
+    pub start: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// not `hh:mm:ss:ff`
+    Start(String),
+    NoPorts,
+    Midi(midi::Error),
+    Input(input::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Start(start) => write!(f, "timecode start {} is not hh:mm:ss:ff", start),
+            Error::NoPorts => write!(f, "no midi input ports for timecode"),
+            Error::Midi(e) => write!(f, "{}", e),
+            Error::Input(e) => write!(f, "timecode input: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<midi::Error> for Error {
+    fn from(e: midi::Error) -> Self {
+        Error::Midi(e)
+    }
+}
+
+impl From<midir::InitError> for Error {
+    fn from(e: midir::InitError) -> Self {
+        Error::Midi(e.into())
+    }
+}
+
+impl From<input::Error> for Error {
+    fn from(e: input::Error) -> Self {
+        Error::Input(e)
+    }
+}
+
+/// What the readings come in through, closed on drop.
+enum Receiver {
+    /// decoded on the MIDI thread as the messages arrive
+    Mtc {
+        _connection: midir::MidiInputConnection<()>,
+        readings: Consumer<(Reading, Instant)>,
+    },
+    /// decoded when polled
+    Ltc {
+        _input: Input,
+        reader: InputReader,
+        decoder: LtcDecoder,
+        scratch: Vec<f32>,
+    },
+}
+
+/// A timecode source and the transport following it.
+pub struct Chase {
+    receiver: Receiver,
+    start: Timecode,
+    /// the newest reading and when it came in, until acted on for good
+    last: Option<(Reading, Instant)>,
+    /// the transport plays because the source does
+    chasing: bool,
+}
+
+impl Chase {
+    pub fn open(client_name: &str, config: &TimecodeConfig) -> Result<Self, Error> {
+        let start = match &config.start {
+            Some(start) => start.parse()?,
+            None => Timecode::default(),
+        };
+        let receiver = match config.source {
+            Source::Mtc => open_mtc(client_name, config.device.as_deref())?,
+            Source::Ltc => {
+                let (input, reader) = Input::start(&InputConfig {
+                    device: config.device.clone(),
+                    channels: vec![config.channel],
+                    ..InputConfig::default()
+                })?;
+                Receiver::Ltc {
+                    _input: input,
+                    decoder: LtcDecoder::new(reader.sample_rate()),
+                    reader,
+                    scratch: vec![0.0; SCRATCH],
+                }
+            }
+        };
+        Ok(Self {
+            receiver,
+            start,
+            last: None,
+            chasing: false,
+        })
+    }
+
+    /// the last timecode read, for showing
+    pub fn timecode(&self) -> Option<Timecode> {
+        self.last.map(|(reading, _)| reading.timecode)
+    }
+
+    /// reads what's come in and moves `transport` after it, called at
+    /// frame rate
+    pub fn poll(&mut self, transport: &Transport) {
+        self.read();
+        let (reading, at) = match self.last {
+            Some(last) => last,
+            None => return,
+        };
+        let running = reading.running && at.elapsed() < DROPOUT;
+        let seconds = if running {
+            reading.seconds + at.elapsed().as_secs_f64()
+        } else {
+            reading.seconds
+        };
+        let beats_per_second = transport.bpm() / 60.0;
+        let beat = (seconds - self.start.to_seconds(reading.rate)) * beats_per_second;
+        if running {
+            let drift = (transport.position() - beat).abs() / beats_per_second;
+            if !transport.is_playing() || drift > TOLERANCE {
+                transport.locate(beat);
+            }
+            transport.play();
+            self.chasing = true;
+        } else if self.chasing || !reading.running {
+            // parked where the code stopped or located to, the transport's
+            // the user's again until the next reading
+            transport.stop();
+            transport.locate(beat);
+            self.chasing = false;
+            self.last = None;
+        }
+    }
+
+    fn read(&mut self) {
+        match &mut self.receiver {
+            Receiver::Mtc { readings, .. } => {
+                while let Some(reading) = readings.pop() {
+                    self.last = Some(reading);
+                }
+            }
+            Receiver::Ltc {
+                reader,
+                decoder,
+                scratch,
+                ..
+            } => {
+                decoder.set_sample_rate(reader.sample_rate());
+                loop {
+                    let frames = reader.read(scratch);
+                    if frames == 0 {
+                        break;
+                    }
+                    if let Some(reading) = decoder.push(&scratch[..frames]) {
+                        self.last = Some((reading, Instant::now()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// connects to `device`, the first port without one
+fn open_mtc(client_name: &str, device: Option<&str>) -> Result<Receiver, Error> {
+    let input = midir::MidiInput::new(&format!("{} timecode", client_name))?;
+    let port = input
+        .ports()
+        .into_iter()
+        .find(|port| match device {
+            Some(name) => input.port_name(port).ok().as_deref() == Some(name),
+            None => true,
+        })
+        .ok_or_else(|| match device {
+            Some(name) => Error::Midi(midi::Error::NoSuchPort(name.into())),
+            None => Error::NoPorts,
+        })?;
+    let (mut producer, readings) = RingBuffer::new(READINGS).split();
+    let mut decoder = MtcDecoder::default();
+    let connection = input
+        .connect(
+            &port,
+            &format!("{} timecode", client_name),
+            move |_, bytes, _| {
+                if let Some(reading) = decoder.push(bytes) {
+                    let _ = producer.push((reading, Instant::now()));
+                }
+            },
+            (),
+        )
+        .map_err(|e| midi::Error::Connect(e.to_string()))?;
+    Ok(Receiver::Mtc {
+        _connection: connection,
+        readings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timecode(s: &str) -> Timecode {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn non_drop_rates_count_whole_seconds() {
+        let tc = timecode("01:00:00:12");
+        assert_eq!(tc.to_seconds(FrameRate::Fps24), 3600.5);
+        assert_eq!(tc.to_seconds(FrameRate::Fps25), 3600.48);
+        assert_eq!(tc.to_seconds(FrameRate::Fps30), 3600.4);
+    }
+
+    #[test]
+    fn drop_frame_skips_two_frames_most_minutes() {
+        let rate = FrameRate::Fps30Drop;
+        let frame = |tc: &str| timecode(tc).to_seconds(rate) * rate.fps();
+        // 00:00:59;29 is followed by 00:01:00;02
+        assert!((frame("00:01:00;02") - frame("00:00:59;29") - 1.0).abs() < 1e-6);
+        // but not on the tenth minute
+        assert!((frame("00:10:00;00") - frame("00:09:59;29") - 1.0).abs() < 1e-6);
+        assert!((frame("00:10:00;00") - 17_982.0).abs() < 1e-6);
+        // an hour of it is 3.6 seconds short of an hour of frames
+        assert!((frame("01:00:00;00") - 107_892.0).abs() < 1e-6);
+        assert!((timecode("01:00:00;00").to_seconds(rate) - 3600.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn parses_and_prints_hh_mm_ss_ff() {
+        let tc = timecode("01:02:03:04");
+        assert_eq!(
+            tc,
+            Timecode {
+                hours: 1,
+                minutes: 2,
+                seconds: 3,
+                frames: 4
+            }
+        );
+        assert_eq!(tc.to_string(), "01:02:03:04");
+        assert_eq!(timecode("01:02:03;04"), tc);
+        assert_eq!(timecode(" 1: 2: 3: 4"), tc);
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_timecode() {
+        for s in &[
+            "",
+            "01:02:03",
+            "01:02:03:04:05",
+            "24:00:00:00",
+            "00:60:00:00",
+            "aa:00:00:00",
+        ] {
+            assert!(
+                matches!(s.parse::<Timecode>(), Err(Error::Start(_))),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn mtc_rate_codes() {
+        assert_eq!(FrameRate::from_code(0), FrameRate::Fps24);
+        assert_eq!(FrameRate::from_code(1), FrameRate::Fps25);
+        assert_eq!(FrameRate::from_code(2), FrameRate::Fps30Drop);
+        assert_eq!(FrameRate::from_code(3), FrameRate::Fps30);
+        // the hours byte's top bit is ignored
+        assert_eq!(FrameRate::from_code(0b111), FrameRate::Fps30);
+    }
+}
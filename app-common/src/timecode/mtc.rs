@@ -0,0 +1,173 @@
+use super::{FrameRate, Reading, Timecode};
+
+/// quarter frame messages to a timecode, sent a quarter of a frame apart
+const PIECES: usize = 8;
+
+/// MIDI Time Code: quarter frames while the source runs, a full frame
+/// message when it locates.
+#[derive(Clone, Debug, Default)]
+pub struct MtcDecoder {
+    /// each piece's nibble
+    pieces: [u8; PIECES],
+    /// a bit for each piece in order since piece 0
+    seen: u8,
+}
+
+impl MtcDecoder {
+    /// one message's bytes, the reading when they complete one
+    pub fn push(&mut self, bytes: &[u8]) -> Option<Reading> {
+        match *bytes {
+            [0xF1, data] => self.quarter(data),
+            [0xF0, 0x7F, _, 0x01, 0x01, hours, minutes, seconds, frames, 0xF7] => {
+                self.seen = 0;
+                let rate = FrameRate::from_code(hours >> 5);
+                let timecode = Timecode {
+                    hours: hours & 0x1F,
+                    minutes,
+                    seconds,
+                    frames,
+                };
+                Some(Reading {
+                    timecode,
+                    rate,
+                    seconds: timecode.to_seconds(rate),
+                    running: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn quarter(&mut self, data: u8) -> Option<Reading> {
+        let piece = usize::from(data >> 4 & 0x07);
+        // out of order, running backwards or a message lost, starts over
+        if piece == 0 || self.seen != (1 << piece) - 1 {
+            self.seen = 0;
+            if piece != 0 {
+                return None;
+            }
+        }
+        self.pieces[piece] = data & 0x0F;
+        self.seen |= 1 << piece;
+        if piece != PIECES - 1 {
+            return None;
+        }
+        let p = &self.pieces;
+        let rate = FrameRate::from_code(p[7] >> 1);
+        let timecode = Timecode {
+            hours: p[6] | (p[7] & 0x01) << 4,
+            minutes: p[4] | p[5] << 4,
+            seconds: p[2] | p[3] << 4,
+            frames: p[0] | p[1] << 4,
+        };
+        // piece 0 went out on the frame it carries, seven quarters ago
+        Some(Reading {
+            timecode,
+            rate,
+            seconds: timecode.to_seconds(rate) + 1.75 / rate.fps(),
+            running: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMECODE: Timecode = Timecode {
+        hours: 17,
+        minutes: 42,
+        seconds: 9,
+        frames: 23,
+    };
+
+    /// the eight quarter frames sending `timecode` with rate `code`
+    fn quarters(timecode: Timecode, code: u8) -> Vec<[u8; 2]> {
+        let nibbles = [
+            timecode.frames & 0x0F,
+            timecode.frames >> 4,
+            timecode.seconds & 0x0F,
+            timecode.seconds >> 4,
+            timecode.minutes & 0x0F,
+            timecode.minutes >> 4,
+            timecode.hours & 0x0F,
+            timecode.hours >> 4 | code << 1,
+        ];
+        (0..PIECES)
+            .map(|piece| [0xF1, (piece as u8) << 4 | nibbles[piece]])
+            .collect()
+    }
+
+    fn push_all(decoder: &mut MtcDecoder, messages: &[[u8; 2]]) -> Vec<Reading> {
+        messages
+            .iter()
+            .filter_map(|message| decoder.push(message))
+            .collect()
+    }
+
+    #[test]
+    fn quarter_frames_complete_a_running_reading() {
+        let rates = [
+            FrameRate::Fps24,
+            FrameRate::Fps25,
+            FrameRate::Fps30Drop,
+            FrameRate::Fps30,
+        ];
+        for (code, &rate) in rates.iter().enumerate() {
+            let mut decoder = MtcDecoder::default();
+            let readings = push_all(&mut decoder, &quarters(TIMECODE, code as u8));
+            assert_eq!(readings.len(), 1);
+            let reading = readings[0];
+            assert_eq!(reading.timecode, TIMECODE);
+            assert_eq!(reading.rate, rate);
+            assert!(reading.running);
+            let expected = TIMECODE.to_seconds(rate) + 1.75 / rate.fps();
+            assert!((reading.seconds - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn a_lost_quarter_frame_waits_for_the_next_piece_0() {
+        let mut decoder = MtcDecoder::default();
+        let mut messages = quarters(TIMECODE, 1);
+        messages.remove(5);
+        assert!(push_all(&mut decoder, &messages).is_empty());
+
+        // joined halfway through, the first whole run is read
+        let mut messages = quarters(TIMECODE, 1)[3..].to_vec();
+        messages.extend(quarters(TIMECODE, 1));
+        let readings = push_all(&mut decoder, &messages);
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].timecode, TIMECODE);
+    }
+
+    #[test]
+    fn running_backwards_reads_nothing() {
+        let mut decoder = MtcDecoder::default();
+        let mut messages = quarters(TIMECODE, 1);
+        messages.reverse();
+        assert!(push_all(&mut decoder, &messages).is_empty());
+    }
+
+    #[test]
+    fn a_full_frame_locates_and_parks() {
+        let mut decoder = MtcDecoder::default();
+        // halfway through a run, which the locate abandons
+        push_all(&mut decoder, &quarters(TIMECODE, 1)[..4]);
+        let message = [0xF0, 0x7F, 0x7F, 0x01, 0x01, 2 << 5 | 17, 42, 9, 23, 0xF7];
+        let reading = decoder.push(&message).unwrap();
+        assert_eq!(reading.timecode, TIMECODE);
+        assert_eq!(reading.rate, FrameRate::Fps30Drop);
+        assert!(!reading.running);
+        assert_eq!(reading.seconds, TIMECODE.to_seconds(FrameRate::Fps30Drop));
+        assert!(push_all(&mut decoder, &quarters(TIMECODE, 1)[4..]).is_empty());
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let mut decoder = MtcDecoder::default();
+        assert_eq!(decoder.push(&[0x90, 60, 100]), None);
+        assert_eq!(decoder.push(&[0xF0, 0x7F, 0x7F, 0x01, 0x01, 0xF7]), None);
+        assert_eq!(decoder.push(&[]), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! TouchOSC layouts generated from an app's parameters.
+//!
+//! One labelled horizontal fader per parameter, stacked like the app's own
+//! sliders, sending plain values to the addresses `param::Bindings` gives
+//! them. The file is the original `.touchosc` format, which both TouchOSC
+//! Mk1 and the current TouchOSC open. Pair it with `param::OscFeedback` so
+//! the faders follow changes made anywhere else.
+
+use crate::param::{Bindings, Params};
+use nannou::prelude::Key;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// writes the layout for the running app
+pub const EXPORT: Key = Key::O;
+
+/// iPad portrait, the layout is scaled to other screens by the editor
+const WIDTH: u32 = 768;
+const MARGIN: u32 = 20;
+const LABEL_HEIGHT: u32 = 30;
+const FADER_HEIGHT: u32 = 60;
+const ROW_HEIGHT: u32 = LABEL_HEIGHT + FADER_HEIGHT + MARGIN;
+
+/// `layouts/<app>.touchosc` next to the screenshots and captures
+pub fn path(app: &str) -> PathBuf {
+    PathBuf::from("layouts").join(format!("{}.touchosc", app))
+}
+
+/// writes the layout to `path(app)`
+pub fn export(app: &str, params: &Params, bindings: &Bindings) -> io::Result<PathBuf> {
+    let path = path(app);
+    write(&path, app, params, bindings)?;
+    Ok(path)
+}
+
+pub fn write(path: &Path, app: &str, params: &Params, bindings: &Bindings) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let xml = layout(app, params, bindings);
+    fs::write(path, stored_zip("index.xml", xml.as_bytes()))
+}
+
+/// the layout's `index.xml`
+pub fn layout(app: &str, params: &Params, bindings: &Bindings) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(r#"<layout version="15" mode="1" orientation="vertical">"#);
+    xml.push_str(&format!(
+        r#"<tabpage name="{}" scalef="0.0" scalet="1.0">"#,
+        base64(app)
+    ));
+    for (i, spec) in params.specs().iter().enumerate() {
+        let y = MARGIN + i as u32 * ROW_HEIGHT;
+        let width = WIDTH - 2 * MARGIN;
+        let label = if spec.unit.is_empty() {
+            spec.name.to_string()
+        } else {
+            format!("{} ({})", spec.name, spec.unit)
+        };
+        xml.push_str(&format!(
+            r#"<control name="{}" x="{}" y="{}" w="{}" h="{}" color="gray" outline="false" background="false" text="{}" size="14" type="labelh"></control>"#,
+            base64(&format!("label_{}", i)),
+            MARGIN,
+            y,
+            width,
+            LABEL_HEIGHT,
+            base64(&label),
+        ));
+        xml.push_str(&format!(
+            r#"<control name="{}" x="{}" y="{}" w="{}" h="{}" color="green" scalef="{}" scalet="{}" osc_cs="{}" type="faderh" response="absolute" inverted="false" centered="false"></control>"#,
+            base64(&format!("fader_{}", i)),
+            MARGIN,
+            y + LABEL_HEIGHT,
+            width,
+            FADER_HEIGHT,
+            spec.min,
+            spec.max,
+            base64(&bindings.osc_address(spec.name)),
+        ));
+    }
+    xml.push_str("</tabpage></layout>");
+    xml
+}
+
+/// the layout format stores names, labels and addresses this way
+fn base64(text: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// a zip archive holding one uncompressed file, all a layout needs
+fn stored_zip(name: &str, data: &[u8]) -> Vec<u8> {
+    let crc = crc32(data).to_le_bytes();
+    let size = (data.len() as u32).to_le_bytes();
+    let name_len = (name.len() as u16).to_le_bytes();
+    // version needed, flags, method (stored), time, date
+    let common = [20, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    let mut zip = Vec::with_capacity(data.len() + 2 * name.len() + 100);
+    zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+    zip.extend_from_slice(&common);
+    zip.extend_from_slice(&crc);
+    zip.extend_from_slice(&size);
+    zip.extend_from_slice(&size);
+    zip.extend_from_slice(&name_len);
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip.extend_from_slice(name.as_bytes());
+    zip.extend_from_slice(data);
+
+    let directory = zip.len() as u32;
+    zip.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+    // version made by
+    zip.extend_from_slice(&20u16.to_le_bytes());
+    zip.extend_from_slice(&common);
+    zip.extend_from_slice(&crc);
+    zip.extend_from_slice(&size);
+    zip.extend_from_slice(&size);
+    zip.extend_from_slice(&name_len);
+    // extra, comment, disk, internal and external attributes, offset
+    zip.extend_from_slice(&[0; 16]);
+    zip.extend_from_slice(name.as_bytes());
+    let directory_len = zip.len() as u32 - directory;
+
+    zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    // this disk, the directory's disk, then one entry here and overall
+    zip.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+    zip.extend_from_slice(&directory_len.to_le_bytes());
+    zip.extend_from_slice(&directory.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip
+}
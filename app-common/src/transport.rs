@@ -0,0 +1,410 @@
+//! Play/stop, tempo and position, shared by the UI and the engine.
+//!
+//! The UI holds the `Transport`, the engine its `TransportClock` and
+//! advances it once per buffer. `advance` reports the beats falling inside
+//! the buffer with the frame each one lands on, so an engine can split its
+//! render there and act on the exact sample. With Link enabled the clock
+//! follows the session's beat, resynced at the start of every buffer.
+
+use crate::link::LinkClock;
+use crate::theme::{Palette, Themed};
+use nannou::prelude::Key;
+use nannou::ui::prelude::*;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+/// plays or stops
+pub const HOTKEY: Key = Key::Space;
+
+pub const MIN_BPM: f64 = 20.0;
+pub const MAX_BPM: f64 = 300.0;
+
+/// in frames, how far rounding in the position can leave a beat off its
+/// frame, a beat that close to a buffer's end belongs to the next one
+const ROUNDING: f64 = 1e-6;
+
+/// A beat inside the buffer `TransportClock::advance` was called for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tick {
+    /// into the buffer
+    pub frame: usize,
+    /// since the start, the first is 0, counting pulses for `Ticks::every`
+    pub beat: i64,
+    /// the beat starts a bar
+    pub bar: bool,
+}
+
+struct Shared {
+    playing: AtomicBool,
+    /// f64 bits, as are the others
+    bpm: AtomicU64,
+    /// in beats, written by the clock after every buffer
+    position: AtomicU64,
+    /// where the clock should jump to, NaN once it has
+    locate: AtomicU64,
+}
+
+fn load(value: &AtomicU64) -> f64 {
+    f64::from_bits(value.load(Ordering::Relaxed))
+}
+
+fn store(value: &AtomicU64, x: f64) {
+    value.store(x.to_bits(), Ordering::Relaxed);
+}
+
+/// UI side, cheap to clone.
+#[derive(Clone)]
+pub struct Transport {
+    shared: Arc<Shared>,
+    beats_per_bar: u32,
+}
+
+impl Transport {
+    /// playing from the first beat
+    pub fn new(bpm: f64, beats_per_bar: u32) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                playing: AtomicBool::new(true),
+                bpm: AtomicU64::new(bpm.clamp(MIN_BPM, MAX_BPM).to_bits()),
+                position: AtomicU64::new(0f64.to_bits()),
+                locate: AtomicU64::new(f64::NAN.to_bits()),
+            }),
+            beats_per_bar: beats_per_bar.max(1),
+        }
+    }
+
+    /// for the engine, follows `link` while it is enabled
+    pub fn clock(&self, link: Option<LinkClock>) -> TransportClock {
+        TransportClock {
+            shared: self.shared.clone(),
+            beats_per_bar: self.beats_per_bar as i64,
+            link,
+            position: load(&self.shared.position),
+        }
+    }
+
+    pub fn play(&self) {
+        self.shared.playing.store(true, Ordering::Relaxed);
+    }
+
+    /// keeps the position, `locate` to rewind
+    pub fn stop(&self) {
+        self.shared.playing.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.shared.playing.load(Ordering::Relaxed)
+    }
+
+    pub fn bpm(&self) -> f64 {
+        load(&self.shared.bpm)
+    }
+
+    pub fn set_bpm(&self, bpm: f64) {
+        store(&self.shared.bpm, bpm.clamp(MIN_BPM, MAX_BPM));
+    }
+
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar
+    }
+
+    /// in beats, as of the last buffer rendered
+    pub fn position(&self) -> f64 {
+        load(&self.shared.position)
+    }
+
+    /// `position` while playing, for a `BeatGrid` on the UI thread
+    pub fn beat(&self) -> Option<f64> {
+        if self.is_playing() {
+            Some(self.position())
+        } else {
+            None
+        }
+    }
+
+    /// from the next buffer on, ignored while following Link
+    pub fn locate(&self, beat: f64) {
+        store(&self.shared.locate, beat);
+    }
+
+    pub fn key_pressed(&self, key: Key) -> bool {
+        if key != HOTKEY {
+            return false;
+        }
+        if self.is_playing() {
+            self.stop();
+        } else {
+            self.play();
+        }
+        true
+    }
+
+    /// play toggle and tempo slider, laid out below the previous widget
+    pub fn panel(&self, toggle: widget::Id, tempo: widget::Id, palette: &Palette, ui: &mut UiCell) {
+        let playing = self.is_playing();
+        let label = if playing {
+            format!(
+                "playing: bar {}",
+                (self.position() / self.beats_per_bar as f64).floor() + 1.0
+            )
+        } else {
+            String::from("stopped")
+        };
+        for value in widget::Toggle::new(playing)
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(&label)
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(toggle, ui)
+        {
+            if value {
+                self.play();
+            } else {
+                self.stop();
+            }
+        }
+
+        let label = format!("{:.0} bpm", self.bpm());
+        for bpm in widget::Slider::new(self.bpm() as f32, MIN_BPM as f32, MAX_BPM as f32)
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(&label)
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(tempo, ui)
+        {
+            self.set_bpm(bpm.round() as f64);
+        }
+    }
+}
+
+/// Audio thread side, allocation free.
+pub struct TransportClock {
+    shared: Arc<Shared>,
+    beats_per_bar: i64,
+    link: Option<LinkClock>,
+    /// in beats, where the next buffer starts
+    position: f64,
+}
+
+impl TransportClock {
+    /// moves on by a buffer of `frames`, call it once per buffer before
+    /// rendering it
+    pub fn advance(&mut self, frames: usize, sample_rate: u32) -> Ticks {
+        let per_frame = load(&self.shared.bpm) / 60.0 / sample_rate.max(1) as f64;
+        let buffer_micros = (frames as u64 * 1_000_000 / sample_rate.max(1) as u64) as i64;
+        let session = self.link.as_mut().and_then(|link| link.beat(buffer_micros));
+        let playing = self.shared.playing.load(Ordering::Relaxed);
+
+        let locate = f64::from_bits(
+            self.shared
+                .locate
+                .swap(f64::NAN.to_bits(), Ordering::Relaxed),
+        );
+        if !locate.is_nan() && session.is_none() {
+            self.position = locate;
+        }
+
+        // Link's beat is where this buffer starts, counting on from where
+        // the last one ended so no beat is reported twice or skipped, unless
+        // the session jumped, e.g. on joining it
+        let (from, start) = match session {
+            Some(beat) if (beat - self.position).abs() > 1.0 => (beat, beat),
+            Some(beat) => (self.position, beat),
+            None => (self.position, self.position),
+        };
+        let ticks = if playing {
+            let end = start + frames as f64 * per_frame;
+            self.position = end.max(from);
+            Ticks {
+                next: (from - ROUNDING * per_frame).ceil() as i64,
+                from,
+                end: self.position,
+                start,
+                per_frame,
+                frames,
+                beats_per_bar: self.beats_per_bar,
+            }
+        } else {
+            Ticks::none()
+        };
+        store(&self.shared.position, self.position);
+        ticks
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.shared.playing.load(Ordering::Relaxed)
+    }
+}
+
+/// The beats inside one buffer, in order.
+pub struct Ticks {
+    next: i64,
+    /// where counting starts, in beats
+    from: f64,
+    /// exclusive, in beats
+    end: f64,
+    start: f64,
+    per_frame: f64,
+    frames: usize,
+    beats_per_bar: i64,
+}
+
+impl Ticks {
+    fn none() -> Self {
+        Self {
+            next: 0,
+            from: 0.0,
+            end: 0.0,
+            start: 0.0,
+            per_frame: 1.0,
+            frames: 0,
+            beats_per_bar: 1,
+        }
+    }
+
+    /// the same buffer counted in pulses `step` beats apart, `per_bar` of
+    /// them to a bar, e.g. a bar of 4 split in 3 is `every(4.0 / 3.0, 3)`
+    pub fn every(&self, step: f64, per_bar: i64) -> Self {
+        Self {
+            next: ((self.from - ROUNDING * self.per_frame) / step).ceil() as i64,
+            from: self.from / step,
+            end: self.end / step,
+            start: self.start / step,
+            per_frame: self.per_frame / step,
+            frames: self.frames,
+            beats_per_bar: per_bar.max(1),
+        }
+    }
+}
+
+impl Iterator for Ticks {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        let beat = self.next;
+        if beat as f64 >= self.end - ROUNDING * self.per_frame {
+            return None;
+        }
+        self.next += 1;
+        // the first frame at or after the beat, give or take rounding
+        let frame = ((beat as f64 - self.start) / self.per_frame - ROUNDING)
+            .ceil()
+            .max(0.0) as usize;
+        Some(Tick {
+            frame: frame.min(self.frames.saturating_sub(1)),
+            beat,
+            bar: beat.rem_euclid(self.beats_per_bar) == 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// the ticks of `buffers` buffers of `frames`, counted in frames from
+    /// the first
+    fn run(clock: &mut TransportClock, frames: usize, buffers: usize) -> Vec<(usize, Tick)> {
+        (0..buffers)
+            .flat_map(|buffer| {
+                clock
+                    .advance(frames, SAMPLE_RATE)
+                    .map(move |tick| (buffer * frames + tick.frame, tick))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn beats_land_on_their_frame_whatever_the_buffer() {
+        for &frames in &[64, 500, 512, 4096] {
+            let transport = Transport::new(120.0, 4);
+            let mut clock = transport.clock(None);
+            let ticks = run(&mut clock, frames, 5 * SAMPLE_RATE as usize / frames);
+            let beats: Vec<_> = ticks
+                .iter()
+                .map(|&(frame, tick)| (frame, tick.beat))
+                .collect();
+            let expected: Vec<_> = (0..10).map(|beat| (beat as usize * 24_000, beat)).collect();
+            assert_eq!(beats, expected, "buffers of {}", frames);
+            let bars: Vec<_> = ticks
+                .iter()
+                .filter(|(_, tick)| tick.bar)
+                .map(|(_, tick)| tick.beat)
+                .collect();
+            assert_eq!(bars, vec![0, 4, 8]);
+        }
+    }
+
+    #[test]
+    fn stopping_holds_the_position() {
+        let transport = Transport::new(120.0, 4);
+        let mut clock = transport.clock(None);
+        run(&mut clock, 1000, 30);
+        assert!((transport.position() - 1.25).abs() < 1e-9);
+        transport.stop();
+        assert_eq!(transport.beat(), None);
+        assert!(run(&mut clock, 1000, 100).is_empty());
+        assert!((transport.position() - 1.25).abs() < 1e-9);
+        // carries on three quarters of a beat before the next
+        assert!(transport.key_pressed(HOTKEY));
+        let ticks = run(&mut clock, 1000, 20);
+        assert_eq!(ticks[0].0, 18_000);
+        assert_eq!(ticks[0].1.beat, 2);
+    }
+
+    #[test]
+    fn locate_jumps_on_the_next_buffer() {
+        let transport = Transport::new(60.0, 4);
+        let mut clock = transport.clock(None);
+        run(&mut clock, 512, 10);
+        transport.locate(7.5);
+        let ticks = run(&mut clock, 48_000, 1);
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks[0].0, 24_000);
+        assert_eq!(
+            ticks[0].1,
+            Tick {
+                frame: 24_000,
+                beat: 8,
+                bar: true
+            }
+        );
+        // and only once
+        assert!((transport.position() - 8.5).abs() < 1e-9);
+        run(&mut clock, 48_000, 1);
+        assert!((transport.position() - 9.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_counts_other_pulses() {
+        let transport = Transport::new(120.0, 4);
+        let mut clock = transport.clock(None);
+        // a bar of 4 in triplet halves, three of them to two beats
+        let ticks = clock.advance(SAMPLE_RATE as usize * 2, SAMPLE_RATE);
+        let pulses: Vec<_> = ticks.every(4.0 / 6.0, 6).collect();
+        assert_eq!(pulses.len(), 6);
+        for (i, tick) in pulses.iter().enumerate() {
+            assert_eq!(tick.beat, i as i64);
+            assert_eq!(tick.frame, i * 16_000);
+            assert_eq!(tick.bar, i == 0);
+        }
+    }
+
+    #[test]
+    fn tempo_is_kept_in_range() {
+        let transport = Transport::new(1000.0, 0);
+        assert_eq!(transport.bpm(), MAX_BPM);
+        assert_eq!(transport.beats_per_bar(), 1);
+        transport.set_bpm(1.0);
+        assert_eq!(transport.bpm(), MIN_BPM);
+    }
+}
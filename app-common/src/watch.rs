@@ -0,0 +1,118 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+/// editors write in several steps, wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Notify(notify::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Notify(e) => write!(f, "file watcher: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(e: notify::Error) -> Self {
+        Error::Notify(e)
+    }
+}
+
+/// Reports edits to a set of files, polled from `update`.
+///
+/// The parent directories are watched rather than the files themselves so
+/// saves that replace the file (most editors) and files that don't exist
+/// yet are picked up too.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    files: BTreeSet<PathBuf>,
+    dirs: BTreeSet<PathBuf>,
+    pending: BTreeSet<PathBuf>,
+    last_event: Instant,
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        let name = path.file_name().map(PathBuf::from).unwrap_or_default();
+        path.parent()
+            .and_then(|dir| fs::canonicalize(dir).ok())
+            .map(|dir| dir.join(&name))
+            .unwrap_or_else(|| path.to_path_buf())
+    })
+}
+
+impl FileWatcher {
+    pub fn new() -> Result<Self, Error> {
+        let (tx, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        Ok(Self {
+            watcher,
+            events,
+            files: BTreeSet::new(),
+            dirs: BTreeSet::new(),
+            pending: BTreeSet::new(),
+            last_event: Instant::now(),
+        })
+    }
+
+    /// creates the parent directory if needed
+    pub fn watch(&mut self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let path = absolute(path);
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if !self.dirs.contains(&dir) {
+            self.watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            self.dirs.insert(dir);
+        }
+        self.files.insert(path);
+        Ok(())
+    }
+
+    /// watched files that changed and have been quiet since, each reported once
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) if !event.kind.is_access() => event,
+                _ => continue,
+            };
+            for path in event.paths {
+                let path = absolute(&path);
+                if self.files.contains(&path) {
+                    self.pending.insert(path);
+                    self.last_event = Instant::now();
+                }
+            }
+        }
+
+        if self.pending.is_empty() || self.last_event.elapsed() < DEBOUNCE {
+            return Vec::new();
+        }
+        std::mem::take(&mut self.pending).into_iter().collect()
+    }
+}
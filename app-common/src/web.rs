@@ -0,0 +1,191 @@
+//! Browser front end: canvas drawing, animation loop and WebAudio output.
+//!
+//! nannou 0.15 has no web backend, so the wasm builds draw with the 2D
+//! canvas API directly and leave out everything that needs the OS
+//! (devices, files, MIDI, OSC, recording).
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    AudioContext, AudioProcessingEvent, CanvasRenderingContext2d, HtmlCanvasElement,
+    ScriptProcessorNode,
+};
+
+fn window() -> web_sys::Window {
+    web_sys::window().expect("no global window")
+}
+
+type FrameCallback = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+fn css(rgb: [f32; 3]) -> String {
+    let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+    format!("rgb({},{},{})", r, g, b)
+}
+
+/// Canvas filling the page, coordinates are centred with y up like nannou.
+pub struct Canvas {
+    element: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+
+impl Canvas {
+    /// uses the canvas with this id, or appends a new one to the body
+    pub fn fullscreen(id: &str) -> Result<Self, JsValue> {
+        let document = window().document().expect("no document");
+        let element = match document.get_element_by_id(id) {
+            Some(element) => element,
+            None => {
+                let element = document.create_element("canvas")?;
+                element.set_id(id);
+                document.body().expect("no body").append_child(&element)?;
+                element
+            }
+        };
+        let element: HtmlCanvasElement = element.dyn_into()?;
+        let context = element
+            .get_context("2d")?
+            .expect("no 2d context")
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        let canvas = Self { element, context };
+        canvas.resize();
+        Ok(canvas)
+    }
+
+    /// match the window size, call once per frame
+    pub fn resize(&self) {
+        let window = window();
+        let width = window
+            .inner_width()
+            .ok()
+            .and_then(|w| w.as_f64())
+            .unwrap_or(1024.0);
+        let height = window
+            .inner_height()
+            .ok()
+            .and_then(|h| h.as_f64())
+            .unwrap_or(768.0);
+        if self.element.width() != width as u32 || self.element.height() != height as u32 {
+            self.element.set_width(width as u32);
+            self.element.set_height(height as u32);
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.element.width() as f32
+    }
+
+    pub fn height(&self) -> f32 {
+        self.element.height() as f32
+    }
+
+    pub fn element(&self) -> &HtmlCanvasElement {
+        &self.element
+    }
+
+    pub fn background(&self, rgb: [f32; 3]) {
+        self.context.set_fill_style_str(&css(rgb));
+        self.context
+            .fill_rect(0.0, 0.0, self.width() as f64, self.height() as f64);
+    }
+
+    pub fn polyline(&self, points: &[[f32; 2]], weight: f32, rgb: [f32; 3]) {
+        let (cx, cy) = (self.width() as f64 / 2.0, self.height() as f64 / 2.0);
+        self.context.begin_path();
+        for (i, [x, y]) in points.iter().enumerate() {
+            let (x, y) = (cx + *x as f64, cy - *y as f64);
+            if i == 0 {
+                self.context.move_to(x, y);
+            } else {
+                self.context.line_to(x, y);
+            }
+        }
+        self.context.set_line_width(weight as f64);
+        self.context.set_stroke_style_str(&css(rgb));
+        self.context.stroke();
+    }
+}
+
+/// calls `frame` with the time in seconds on every animation frame, forever
+pub fn animate(mut frame: impl FnMut(f64) + 'static) {
+    let callback: FrameCallback = Rc::new(RefCell::new(None));
+    let next = callback.clone();
+    *callback.borrow_mut() = Some(Closure::wrap(Box::new(move |millis: f64| {
+        frame(millis / 1000.0);
+        if let Some(next) = next.borrow().as_ref() {
+            let _ = window().request_animation_frame(next.as_ref().unchecked_ref());
+        }
+    }) as Box<dyn FnMut(f64)>));
+    let first = callback.borrow();
+    let _ = window().request_animation_frame(first.as_ref().unwrap().as_ref().unchecked_ref());
+}
+
+/// WebAudio output pulling interleaved blocks from a render callback.
+///
+/// Browsers keep the context suspended until a user gesture, call `resume`
+/// from a click handler.
+pub struct WebAudio {
+    context: AudioContext,
+    _node: ScriptProcessorNode,
+    _callback: Closure<dyn FnMut(AudioProcessingEvent)>,
+}
+
+impl WebAudio {
+    pub fn start(
+        channels: usize,
+        frames_per_buffer: u32,
+        mut render: impl FnMut(&mut [f32], f32) + 'static,
+    ) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let node = context.create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+            frames_per_buffer,
+            0,
+            channels as u32,
+        )?;
+
+        let sample_rate = context.sample_rate();
+        let mut interleaved = vec![0.0; frames_per_buffer as usize * channels];
+        let mut planar = vec![0.0; frames_per_buffer as usize];
+        let callback = Closure::wrap(Box::new(move |event: AudioProcessingEvent| {
+            let output = match event.output_buffer() {
+                Ok(output) => output,
+                Err(_) => return,
+            };
+            interleaved.iter_mut().for_each(|sample| *sample = 0.0);
+            render(&mut interleaved, sample_rate);
+            for channel in 0..channels {
+                for (frame, sample) in planar.iter_mut().enumerate() {
+                    *sample = interleaved[frame * channels + channel];
+                }
+                let _ = output.copy_to_channel(&planar, channel as i32);
+            }
+        }) as Box<dyn FnMut(AudioProcessingEvent)>);
+
+        node.set_onaudioprocess(Some(callback.as_ref().unchecked_ref()));
+        node.connect_with_audio_node(&context.destination())?;
+
+        Ok(Self {
+            context,
+            _node: node,
+            _callback: callback,
+        })
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.context.sample_rate()
+    }
+
+    pub fn resume(&self) {
+        let _ = self.context.resume();
+    }
+}
+
+/// run `action` on every click on `canvas`
+pub fn on_click(canvas: &Canvas, action: impl FnMut() + 'static) {
+    let callback = Closure::wrap(Box::new(action) as Box<dyn FnMut()>);
+    canvas
+        .element()
+        .set_onclick(Some(callback.as_ref().unchecked_ref()));
+    callback.forget();
+}
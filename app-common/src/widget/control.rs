@@ -0,0 +1,42 @@
+//! What the kit's controls share: one style so a knob next to a pad next
+//! to a toggle looks like one panel, and the mapping between values and
+//! their position along a range.
+
+use super::meter::rgb;
+use nannou::ui::prelude::*;
+
+pub const LABEL_SIZE: FontSize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub background: Color,
+    /// the part of a control showing its value
+    pub fill: Color,
+    /// handles, markers and anything that moves
+    pub accent: Color,
+    pub label: Color,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: rgb(0.1, 0.1, 0.1),
+            fill: rgb(0.0, 0.5, 0.0),
+            accent: rgb(0.0, 1.0, 0.0),
+            label: rgb(0.8, 0.8, 0.8),
+        }
+    }
+}
+
+/// `value` as a fraction of `min..max`, clamped to [0, 1]
+pub fn normalize(value: f32, min: f32, max: f32) -> f32 {
+    if max == min {
+        return 0.0;
+    }
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// back from `normalize`
+pub fn denormalize(fraction: f32, min: f32, max: f32) -> f32 {
+    min + (max - min) * fraction.clamp(0.0, 1.0)
+}
@@ -0,0 +1,66 @@
+use crate::theme::{Palette, Themed};
+use nannou::ui::prelude::*;
+
+const NONE: &str = "none";
+
+pub enum Selection {
+    Device(String),
+    None,
+}
+
+/// Drop down of device names with a leading "none" entry.
+pub struct DevicePicker<'a> {
+    devices: &'a [String],
+    selected: Option<&'a str>,
+    label: &'a str,
+    palette: Option<&'a Palette>,
+}
+
+impl<'a> DevicePicker<'a> {
+    pub fn new(devices: &'a [String], selected: Option<&'a str>) -> Self {
+        Self {
+            devices,
+            selected,
+            label: "",
+            palette: None,
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn palette(mut self, palette: &'a Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// lays the picker out below the previously set widget
+    pub fn set(self, id: widget::Id, ui: &mut UiCell) -> Option<Selection> {
+        let items: Vec<&str> = std::iter::once(NONE)
+            .chain(self.devices.iter().map(String::as_str))
+            .collect();
+        let selected = match self.selected {
+            Some(name) => self.devices.iter().position(|d| d == name).map(|i| i + 1),
+            None => Some(0),
+        };
+
+        let default = Palette::default();
+        let palette = self.palette.unwrap_or(&default);
+
+        widget::DropDownList::new(&items, selected)
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .max_visible_items(8)
+            .label(self.label)
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(id, ui)
+            .map(|index| match index {
+                0 => Selection::None,
+                i => Selection::Device(self.devices[i - 1].clone()),
+            })
+    }
+}
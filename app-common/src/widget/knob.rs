@@ -0,0 +1,208 @@
+use super::control::{denormalize, normalize, Style, LABEL_SIZE};
+use nannou::ui::input::keyboard::ModifierKey;
+use nannou::ui::prelude::*;
+
+/// radians either side of straight up the arc covers
+const SWEEP: f64 = 0.75 * std::f64::consts::PI;
+/// points along the whole arc
+const ARC_POINTS: usize = 48;
+/// pixels of drag for the whole range, finer with shift
+const TRAVEL: f32 = 200.0;
+const FINE: f32 = 10.0;
+/// of the range per scroll line
+const SCROLL_STEP: f32 = 0.01;
+
+widget_ids! {
+    pub struct KnobIds {
+        background,
+        track,
+        arc,
+        pointer,
+        value,
+        label,
+    }
+}
+
+pub struct KnobState {
+    ids: KnobIds,
+}
+
+/// Rotary control over `min..max`, with the value in the middle and the
+/// label underneath. Dragging up or scrolling turns it up.
+///
+/// Reports the new value while it is turned.
+pub struct Knob<'a> {
+    common: widget::CommonBuilder,
+    value: f32,
+    min: f32,
+    max: f32,
+    label: &'a str,
+    precision: usize,
+    style: Style,
+}
+
+impl<'a> Knob<'a> {
+    pub fn new(value: f32, min: f32, max: f32) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            value,
+            min,
+            max,
+            label: "",
+            precision: 2,
+            style: Style::default(),
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// decimals shown
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> widget::Common for Knob<'a> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+/// the point at `fraction` along the arc
+fn on_arc(center: Point, radius: f64, fraction: f32) -> Point {
+    let angle = SWEEP - 2.0 * SWEEP * fraction as f64 + std::f64::consts::FRAC_PI_2;
+    [
+        center[0] + radius * angle.cos(),
+        center[1] + radius * angle.sin(),
+    ]
+}
+
+fn arc(center: Point, radius: f64, to: f32) -> impl Iterator<Item = Point> {
+    let points = (ARC_POINTS as f32 * to).ceil() as usize;
+    (0..=points).map(move |i| on_arc(center, radius, to * i as f32 / points.max(1) as f32))
+}
+
+impl<'a> Widget for Knob<'a> {
+    type State = KnobState;
+    type Style = Style;
+    type Event = Option<f32>;
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        KnobState {
+            ids: KnobIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let style = self.style;
+
+        let mut fraction = normalize(self.value, self.min, self.max);
+        let start = fraction;
+        let input = ui.widget_input(id);
+        for drag in input.drags().left() {
+            let travel = if drag.modifiers.contains(ModifierKey::SHIFT) {
+                TRAVEL * FINE
+            } else {
+                TRAVEL
+            };
+            fraction += drag.delta_xy[1] as f32 / travel;
+        }
+        for scroll in input.scrolls() {
+            fraction += scroll.y as f32 * SCROLL_STEP;
+        }
+        let fraction = fraction.clamp(0.0, 1.0);
+        let value = denormalize(fraction, self.min, self.max);
+
+        // the label takes a line under the dial
+        let label_height = if self.label.is_empty() {
+            0.0
+        } else {
+            LABEL_SIZE as f64 * 1.5
+        };
+        let dial = Rect::from_corners(
+            [rect.left(), rect.bottom() + label_height],
+            [rect.right(), rect.top()],
+        );
+        let radius = dial.w().min(dial.h()) * 0.5 - 2.0;
+        let center = dial.xy();
+
+        widget::Circle::fill(radius)
+            .xy(center)
+            .graphics_for(id)
+            .parent(id)
+            .color(style.background)
+            .set(state.ids.background, ui);
+
+        widget::PointPath::abs(arc(center, radius - 3.0, 1.0))
+            .thickness(3.0)
+            .color(style.background.highlighted())
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.track, ui);
+
+        widget::PointPath::abs(arc(center, radius - 3.0, fraction))
+            .thickness(3.0)
+            .color(style.fill)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.arc, ui);
+
+        widget::Line::abs(
+            on_arc(center, radius * 0.3, fraction),
+            on_arc(center, radius - 6.0, fraction),
+        )
+        .thickness(2.0)
+        .color(style.accent)
+        .graphics_for(id)
+        .parent(id)
+        .set(state.ids.pointer, ui);
+
+        widget::Text::new(&format!("{:.*}", self.precision, value))
+            .font_size(LABEL_SIZE)
+            .color(style.label)
+            .x_y(center[0], center[1] - radius * 0.5)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.value, ui);
+
+        if !self.label.is_empty() {
+            widget::Text::new(self.label)
+                .font_size(LABEL_SIZE)
+                .color(style.label)
+                .mid_bottom_of(id)
+                .graphics_for(id)
+                .parent(id)
+                .set(state.ids.label, ui);
+        }
+
+        if fraction != start {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
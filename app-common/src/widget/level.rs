@@ -0,0 +1,100 @@
+use super::control::{normalize, Style};
+use nannou::ui::prelude::*;
+
+widget_ids! {
+    pub struct LevelIds {
+        background,
+        fill,
+    }
+}
+
+pub struct LevelState {
+    ids: LevelIds,
+}
+
+/// A bar filled to a value in `min..max`, upwards when taller than wide.
+///
+/// For control signals like envelopes, CV or a voice count; audio levels
+/// go on a `VerticalMeter`, which knows about decibels and peaks.
+pub struct Level {
+    common: widget::CommonBuilder,
+    value: f32,
+    min: f32,
+    max: f32,
+    style: Style,
+}
+
+impl Level {
+    pub fn new(value: f32, min: f32, max: f32) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            value,
+            min,
+            max,
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl widget::Common for Level {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl Widget for Level {
+    type State = LevelState;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        LevelState {
+            ids: LevelIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let (w, h) = rect.w_h();
+        let fraction = normalize(self.value, self.min, self.max) as f64;
+
+        widget::Rectangle::fill([w, h])
+            .middle_of(id)
+            .graphics_for(id)
+            .color(self.style.background)
+            .set(state.ids.background, ui);
+
+        let fill = widget::Rectangle::fill(if h > w {
+            [w, (h * fraction).max(1.0)]
+        } else {
+            [(w * fraction).max(1.0), h]
+        });
+        let fill = if h > w {
+            fill.mid_bottom_of(id)
+        } else {
+            fill.mid_left_of(id)
+        };
+        fill.graphics_for(id)
+            .color(self.style.fill)
+            .set(state.ids.fill, ui);
+    }
+}
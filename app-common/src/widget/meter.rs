@@ -0,0 +1,223 @@
+use dsp_common::meter::{to_db, Reading};
+use nannou::ui::prelude::*;
+
+const FLOOR_DB: f32 = -60.0;
+
+/// maps a linear level onto [0, 1] of the meter height
+fn scale(amplitude: f32) -> f64 {
+    ((to_db(amplitude) - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0) as f64
+}
+
+pub fn rgb(r: f32, g: f32, b: f32) -> Color {
+    Color::Rgba(r, g, b, 1.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub background: Color,
+    pub fill: Color,
+    pub peak: Color,
+    pub over: Color,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: rgb(0.1, 0.1, 0.1),
+            fill: rgb(0.0, 0.5, 0.0),
+            peak: rgb(0.0, 1.0, 0.0),
+            over: rgb(1.0, 0.2, 0.2),
+        }
+    }
+}
+
+widget_ids! {
+    pub struct MeterIds {
+        background,
+        rms,
+        peak,
+        true_peak,
+    }
+}
+
+pub struct MeterState {
+    ids: MeterIds,
+}
+
+/// RMS bar with sample peak and true peak markers, true peak
+/// turns to the `over` colour once it crosses 0 dBFS.
+pub struct VerticalMeter {
+    common: widget::CommonBuilder,
+    reading: Reading,
+    style: Style,
+}
+
+impl VerticalMeter {
+    pub fn new(reading: Reading) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            reading,
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl widget::Common for VerticalMeter {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl Widget for VerticalMeter {
+    type State = MeterState;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        MeterState {
+            ids: MeterIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let (w, h) = rect.w_h();
+        let Reading {
+            rms,
+            peak,
+            true_peak,
+        } = self.reading;
+
+        widget::Rectangle::fill([w, h])
+            .middle_of(id)
+            .graphics_for(id)
+            .color(self.style.background)
+            .set(state.ids.background, ui);
+
+        widget::Rectangle::fill([w, (h * scale(rms)).max(1.0)])
+            .mid_bottom_of(id)
+            .graphics_for(id)
+            .color(self.style.fill)
+            .set(state.ids.rms, ui);
+
+        widget::Rectangle::fill([w, 2.0])
+            .mid_bottom_with_margin_on(id, h * scale(peak))
+            .graphics_for(id)
+            .color(self.style.peak)
+            .set(state.ids.peak, ui);
+
+        widget::Rectangle::fill([w * 0.5, 2.0])
+            .mid_bottom_with_margin_on(id, h * scale(true_peak))
+            .graphics_for(id)
+            .color(if true_peak >= 1.0 {
+                self.style.over
+            } else {
+                self.style.peak
+            })
+            .set(state.ids.true_peak, ui);
+    }
+}
+
+widget_ids! {
+    pub struct StereoMeterIds {
+        left,
+        right,
+    }
+}
+
+pub struct StereoMeterState {
+    ids: StereoMeterIds,
+}
+
+/// Two `VerticalMeter`s side by side, sized to fill the given width.
+pub struct StereoMeter {
+    common: widget::CommonBuilder,
+    readings: [Reading; 2],
+    style: Style,
+}
+
+impl StereoMeter {
+    pub fn new(readings: [Reading; 2]) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            readings,
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl widget::Common for StereoMeter {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl Widget for StereoMeter {
+    type State = StereoMeterState;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        StereoMeterState {
+            ids: StereoMeterIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let (w, h) = rect.w_h();
+        let bar = (w - 4.0) * 0.5;
+
+        VerticalMeter::new(self.readings[0])
+            .with_style(self.style)
+            .w_h(bar, h)
+            .bottom_left_of(id)
+            .parent(id)
+            .set(state.ids.left, ui);
+
+        VerticalMeter::new(self.readings[1])
+            .with_style(self.style)
+            .w_h(bar, h)
+            .bottom_right_of(id)
+            .parent(id)
+            .set(state.ids.right, ui);
+    }
+}
@@ -0,0 +1,20 @@
+pub mod control;
+mod device_picker;
+pub mod knob;
+pub mod level;
+pub mod meter;
+mod preset_browser;
+pub mod readout;
+pub mod scope;
+pub mod toggle;
+pub mod xy_pad;
+
+pub use device_picker::{DevicePicker, Selection};
+pub use knob::Knob;
+pub use level::Level;
+pub use meter::{StereoMeter, VerticalMeter};
+pub use preset_browser::{PresetBrowser, PresetBrowserIds, PresetEvent};
+pub use readout::Readout;
+pub use scope::Scope;
+pub use toggle::Toggle;
+pub use xy_pad::XyPad;
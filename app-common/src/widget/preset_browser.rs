@@ -0,0 +1,110 @@
+use crate::preset;
+use crate::theme::{Palette, Themed};
+use nannou::ui::prelude::*;
+
+widget_ids! {
+    pub struct PresetBrowserIds {
+        name,
+        save,
+        load,
+        list,
+    }
+}
+
+pub enum PresetEvent {
+    Save(String),
+    Load(String),
+}
+
+/// Name field, save/load buttons and a drop down of the app's saved presets.
+///
+/// The browser only reports what was asked for, the app does the actual
+/// (de)serialization so it can apply a loaded preset in one go.
+pub struct PresetBrowser {
+    app: &'static str,
+    names: Vec<String>,
+    selected: Option<usize>,
+    name: String,
+}
+
+impl PresetBrowser {
+    pub fn new(app: &'static str) -> Self {
+        Self {
+            app,
+            names: preset::list(app),
+            selected: None,
+            name: String::from("untitled"),
+        }
+    }
+
+    /// rescan the preset directory, e.g. after saving
+    pub fn refresh(&mut self) {
+        self.names = preset::list(self.app);
+        self.selected = self.names.iter().position(|n| *n == self.name);
+    }
+
+    /// lays the browser out below the previously set widget
+    pub fn set(
+        &mut self,
+        ids: &PresetBrowserIds,
+        palette: &Palette,
+        ui: &mut UiCell,
+    ) -> Option<PresetEvent> {
+        let button = |label: &'static str| {
+            widget::Button::new()
+                .w_h(95.0, 30.0)
+                .label(label)
+                .label_font_size(15)
+                .themed(palette)
+                .border(0.0)
+        };
+
+        let mut event = None;
+
+        for edit in widget::TextBox::new(&self.name)
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .font_size(15)
+            .border(0.0)
+            .set(ids.name, ui)
+        {
+            if let widget::text_box::Event::Update(text) = edit {
+                self.name = text;
+            }
+        }
+
+        if button("save").down(10.0).set(ids.save, ui).was_clicked() && !self.name.is_empty() {
+            event = Some(PresetEvent::Save(self.name.clone()));
+        }
+
+        if button("load").right(10.0).set(ids.load, ui).was_clicked() {
+            if let Some(name) = self.selected.and_then(|i| self.names.get(i)) {
+                event = Some(PresetEvent::Load(name.clone()));
+            }
+        }
+
+        if let Some(index) = widget::DropDownList::new(&self.names, self.selected)
+            .w_h(200.0, 30.0)
+            .down_from(ids.save, 10.0)
+            .max_visible_items(8)
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(ids.list, ui)
+        {
+            self.selected = Some(index);
+            self.name = self.names[index].clone();
+        }
+
+        if let Some(PresetEvent::Save(_)) = event {
+            // the app writes the file after this returns, list it pre-emptively
+            if !self.names.contains(&self.name) {
+                self.names.push(self.name.clone());
+                self.names.sort();
+            }
+            self.selected = self.names.iter().position(|n| *n == self.name);
+        }
+
+        event
+    }
+}
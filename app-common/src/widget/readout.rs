@@ -0,0 +1,118 @@
+use super::control::{Style, LABEL_SIZE};
+use nannou::ui::prelude::*;
+
+widget_ids! {
+    pub struct ReadoutIds {
+        background,
+        label,
+        value,
+    }
+}
+
+pub struct ReadoutState {
+    ids: ReadoutIds,
+}
+
+/// A labelled number that can't be edited, e.g. the frequency a knob
+/// ends up at or the grains playing.
+pub struct Readout<'a> {
+    common: widget::CommonBuilder,
+    label: &'a str,
+    value: f32,
+    unit: &'a str,
+    precision: usize,
+    style: Style,
+}
+
+impl<'a> Readout<'a> {
+    pub fn new(label: &'a str, value: f32) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            label,
+            value,
+            unit: "",
+            precision: 2,
+            style: Style::default(),
+        }
+    }
+
+    /// after the value, e.g. "Hz"
+    pub fn unit(mut self, unit: &'a str) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// decimals shown
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> widget::Common for Readout<'a> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl<'a> Widget for Readout<'a> {
+    type State = ReadoutState;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        ReadoutState {
+            ids: ReadoutIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let style = self.style;
+
+        widget::Rectangle::fill(rect.dim())
+            .middle_of(id)
+            .graphics_for(id)
+            .color(style.background)
+            .set(state.ids.background, ui);
+
+        widget::Text::new(self.label)
+            .font_size(LABEL_SIZE)
+            .color(style.label)
+            .mid_left_with_margin_on(id, 4.0)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.label, ui);
+
+        let value = match self.unit {
+            "" => format!("{:.*}", self.precision, self.value),
+            unit => format!("{:.*} {}", self.precision, self.value, unit),
+        };
+        widget::Text::new(&value)
+            .font_size(LABEL_SIZE)
+            .color(style.accent)
+            .mid_right_with_margin_on(id, 4.0)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.value, ui);
+    }
+}
@@ -0,0 +1,211 @@
+use super::meter::rgb;
+use crate::scope::{Settings, DIVISIONS, MAX_TIME_PER_DIV, MIN_TIME_PER_DIV};
+use nannou::ui::prelude::*;
+
+/// vertical divisions, half of them either side of zero
+const LEVELS: usize = 8;
+const SLIDER_HEIGHT: f64 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub background: Color,
+    pub grid: Color,
+    pub trace: Color,
+    pub trigger: Color,
+    /// slider fill and labels
+    pub fill: Color,
+    pub label: Color,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: rgb(0.1, 0.1, 0.1),
+            grid: rgb(0.25, 0.25, 0.25),
+            trace: rgb(0.0, 1.0, 0.0),
+            trigger: rgb(1.0, 0.2, 0.2),
+            fill: rgb(0.0, 0.5, 0.0),
+            label: rgb(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+widget_ids! {
+    pub struct ScopeIds {
+        background,
+        trigger,
+        trace,
+        time_per_div,
+        trigger_level,
+    }
+}
+
+pub struct ScopeState {
+    ids: ScopeIds,
+    grid: widget::id::List,
+}
+
+/// Waveform over a grid with the trigger level marked, and sliders for the
+/// time per division and the trigger level along the bottom.
+///
+/// Reports the settings when a slider moves, for `ScopeReader::set_settings`.
+pub struct Scope<'a> {
+    common: widget::CommonBuilder,
+    samples: &'a [f32],
+    settings: Settings,
+    style: Style,
+}
+
+impl<'a> Scope<'a> {
+    /// `samples` spans the whole width, see `ScopeReader::samples`
+    pub fn new(samples: &'a [f32], settings: Settings) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            samples,
+            settings,
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> widget::Common for Scope<'a> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl<'a> Widget for Scope<'a> {
+    type State = ScopeState;
+    type Style = Style;
+    type Event = Option<Settings>;
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        ScopeState {
+            ids: ScopeIds::new(id_gen),
+            grid: widget::id::List::new(),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let style = self.style;
+        let (w, h) = rect.w_h();
+        // the trace above the sliders
+        let view = Rect::from_corners(
+            [rect.left(), rect.bottom() + SLIDER_HEIGHT],
+            [rect.right(), rect.top()],
+        );
+        let to_y = |sample: f32| view.y() + sample.clamp(-1.0, 1.0) as f64 * view.h() * 0.5;
+
+        widget::Rectangle::fill([w, h])
+            .middle_of(id)
+            .graphics_for(id)
+            .color(style.background)
+            .set(state.ids.background, ui);
+
+        let lines = DIVISIONS - 1 + LEVELS - 1;
+        if state.grid.len() != lines {
+            state.update(|state| state.grid.resize(lines, &mut ui.widget_id_generator()));
+        }
+        let verticals = (1..DIVISIONS).map(|i| {
+            let x = view.left() + view.w() * i as f64 / DIVISIONS as f64;
+            ([x, view.bottom()], [x, view.top()])
+        });
+        let horizontals = (1..LEVELS).map(|i| {
+            let y = view.bottom() + view.h() * i as f64 / LEVELS as f64;
+            ([view.left(), y], [view.right(), y])
+        });
+        for ((start, end), &line) in verticals.chain(horizontals).zip(state.grid.iter()) {
+            widget::Line::abs(start, end)
+                .color(style.grid)
+                .graphics_for(id)
+                .parent(id)
+                .set(line, ui);
+        }
+
+        let level = to_y(self.settings.trigger_level);
+        widget::Line::abs([view.left(), level], [view.right(), level])
+            .color(style.trigger)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.trigger, ui);
+
+        if self.samples.len() > 1 {
+            // no more than two points per pixel
+            let step = (self.samples.len() / (w as usize * 2).max(1)).max(1);
+            let scale = view.w() / (self.samples.len() - 1) as f64;
+            let points = self
+                .samples
+                .iter()
+                .enumerate()
+                .step_by(step)
+                .map(|(i, &sample)| [view.left() + i as f64 * scale, to_y(sample)]);
+            widget::PointPath::abs(points)
+                .color(style.trace)
+                .graphics_for(id)
+                .parent(id)
+                .set(state.ids.trace, ui);
+        }
+
+        let time_label = format!("{:.1} ms/div", self.settings.time_per_div * 1000.0);
+        let level_label = format!("trigger {:+.2}", self.settings.trigger_level);
+        let slider = |value: f32, min: f32, max: f32| {
+            widget::Slider::new(value, min, max)
+                .w_h(w * 0.5, SLIDER_HEIGHT)
+                .label_font_size(12)
+                .color(style.fill)
+                .label_color(style.label)
+                .border(0.0)
+                .parent(id)
+        };
+
+        let mut settings = None;
+        // logarithmic, the range spans almost three decades
+        for log in slider(
+            self.settings.time_per_div.ln(),
+            MIN_TIME_PER_DIV.ln(),
+            MAX_TIME_PER_DIV.ln(),
+        )
+        .label(&time_label)
+        .bottom_left_of(id)
+        .set(state.ids.time_per_div, ui)
+        {
+            settings = Some(Settings {
+                time_per_div: log.exp(),
+                ..self.settings
+            });
+        }
+
+        for level in slider(self.settings.trigger_level, -1.0, 1.0)
+            .label(&level_label)
+            .bottom_right_of(id)
+            .set(state.ids.trigger_level, ui)
+        {
+            settings = Some(Settings {
+                trigger_level: level,
+                ..self.settings
+            });
+        }
+
+        settings
+    }
+}
@@ -0,0 +1,121 @@
+use super::control::{Style, LABEL_SIZE};
+use nannou::ui::prelude::*;
+
+widget_ids! {
+    pub struct ToggleIds {
+        background,
+        indicator,
+        label,
+    }
+}
+
+pub struct ToggleState {
+    ids: ToggleIds,
+}
+
+/// A lit square and its label, flipped by a click.
+///
+/// Reports the new state when clicked.
+pub struct Toggle<'a> {
+    common: widget::CommonBuilder,
+    on: bool,
+    label: &'a str,
+    style: Style,
+}
+
+impl<'a> Toggle<'a> {
+    pub fn new(on: bool) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            on,
+            label: "",
+            style: Style::default(),
+        }
+    }
+
+    /// drawn right of the square
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> widget::Common for Toggle<'a> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl<'a> Widget for Toggle<'a> {
+    type State = ToggleState;
+    type Style = Style;
+    type Event = Option<bool>;
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        ToggleState {
+            ids: ToggleIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let style = self.style;
+
+        let clicks = ui.widget_input(id).clicks().left().count();
+        let on = self.on ^ (clicks % 2 == 1);
+
+        let side = rect.h();
+        widget::Rectangle::fill([side, side])
+            .mid_left_of(id)
+            .graphics_for(id)
+            .parent(id)
+            .color(style.background)
+            .set(state.ids.background, ui);
+
+        widget::Rectangle::fill([side * 0.6, side * 0.6])
+            .middle_of(state.ids.background)
+            .graphics_for(id)
+            .parent(id)
+            .color(if on {
+                style.accent
+            } else {
+                style.background.highlighted()
+            })
+            .set(state.ids.indicator, ui);
+
+        if !self.label.is_empty() {
+            widget::Text::new(self.label)
+                .font_size(LABEL_SIZE)
+                .color(style.label)
+                .right_from(state.ids.background, side * 0.5)
+                .graphics_for(id)
+                .parent(id)
+                .set(state.ids.label, ui);
+        }
+
+        if clicks > 0 && on != self.on {
+            Some(on)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use super::control::{denormalize, normalize, Style, LABEL_SIZE};
+use nannou::ui::prelude::*;
+
+const HANDLE_RADIUS: f64 = 6.0;
+
+widget_ids! {
+    pub struct XyPadIds {
+        background,
+        horizontal,
+        vertical,
+        handle,
+        label,
+    }
+}
+
+pub struct XyPadState {
+    ids: XyPadIds,
+}
+
+/// Two values at once on a square, x across and y up, for pairs that move
+/// together like lissa's two frequencies.
+///
+/// Reports both values while the pad is pressed.
+pub struct XyPad<'a> {
+    common: widget::CommonBuilder,
+    x: (f32, f32, f32),
+    y: (f32, f32, f32),
+    label: &'a str,
+    style: Style,
+}
+
+impl<'a> XyPad<'a> {
+    /// `value, min, max` for each axis
+    pub fn new(x: (f32, f32, f32), y: (f32, f32, f32)) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            x,
+            y,
+            label: "",
+            style: Style::default(),
+        }
+    }
+
+    /// drawn in the top left corner
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> widget::Common for XyPad<'a> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl<'a> Widget for XyPad<'a> {
+    type State = XyPadState;
+    type Style = Style;
+    type Event = Option<(f32, f32)>;
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        XyPadState {
+            ids: XyPadIds::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let style = self.style;
+        let ((x, x_min, x_max), (y, y_min, y_max)) = (self.x, self.y);
+
+        let pressed = ui
+            .widget_input(id)
+            .mouse()
+            .filter(|mouse| mouse.buttons.left().is_down())
+            .map(|mouse| {
+                let [mx, my] = mouse.rel_xy();
+                ((mx / rect.w() + 0.5) as f32, (my / rect.h() + 0.5) as f32)
+            });
+        let (fx, fy) = pressed.unwrap_or((normalize(x, x_min, x_max), normalize(y, y_min, y_max)));
+        let (fx, fy) = (fx.clamp(0.0, 1.0), fy.clamp(0.0, 1.0));
+        let handle = [
+            rect.left() + rect.w() * fx as f64,
+            rect.bottom() + rect.h() * fy as f64,
+        ];
+
+        widget::Rectangle::fill(rect.dim())
+            .middle_of(id)
+            .graphics_for(id)
+            .color(style.background)
+            .set(state.ids.background, ui);
+
+        widget::Line::abs([rect.left(), handle[1]], [rect.right(), handle[1]])
+            .color(style.fill)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.horizontal, ui);
+
+        widget::Line::abs([handle[0], rect.bottom()], [handle[0], rect.top()])
+            .color(style.fill)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.vertical, ui);
+
+        widget::Circle::fill(HANDLE_RADIUS)
+            .xy(handle)
+            .color(style.accent)
+            .graphics_for(id)
+            .parent(id)
+            .set(state.ids.handle, ui);
+
+        if !self.label.is_empty() {
+            widget::Text::new(self.label)
+                .font_size(LABEL_SIZE)
+                .color(style.label)
+                .top_left_with_margin_on(id, 4.0)
+                .graphics_for(id)
+                .parent(id)
+                .set(state.ids.label, ui);
+        }
+
+        pressed.map(|_| (denormalize(fx, x_min, x_max), denormalize(fy, y_min, y_max)))
+    }
+}
@@ -0,0 +1,366 @@
+use crate::dsp::{self, Engine, State};
+use crate::system::Kind;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::collections::VecDeque;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// points of the path drawn, a couple of seconds of it
+const TRAIL: usize = 3000;
+/// turns a second the view goes round the attractor
+const SPIN: f32 = 0.02;
+/// how far the view looks down on it, in radians
+const TILT: f32 = 0.35;
+/// how much nearer parts grow, 0 is flat
+const PERSPECTIVE: f32 = 0.2;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        systems[],
+    }
+}
+
+fn engine(config: &Config, params: &Params) -> (Engine, UiEnd<(), State>) {
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    let mut engine = Engine::new(audio_bus, params.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("attractor", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the system playing without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("attractor", &config::path("attractor"));
+    let params = shell::load_params("attractor", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the system playing on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("attractor", &config::path("attractor"));
+    let params = shell::load_params("attractor", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params);
+    render::headless("attractor", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<(), State>,
+    /// the sound as of the last buffer played
+    state: Option<State>,
+    /// where the system has been, oldest first
+    trail: VecDeque<[f32; 3]>,
+    /// seconds since the window opened, the view turns with it
+    time: f32,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("attractor");
+    let (config, _) = shell::load_config("attractor", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("attractor", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("attractor", &config, &params);
+    let serial = serial::Sensors::from_config("attractor", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("attractor", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "attractor", &config, stream.stats());
+    let shell = Shell::new("attractor", config, &config_path, "phosphor", stream_config)
+        .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        state: None,
+        trail: VecDeque::with_capacity(TRAIL),
+        time: 0.0,
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.time += update.since_last.as_secs_f32();
+    // every buffer's points, not just the latest, so the path is whole
+    for state in model.bus.events() {
+        if model.state.map_or(false, |last| last.kind != state.kind) {
+            model.trail.clear();
+        }
+        for &point in state.points.iter() {
+            if model.trail.len() == TRAIL {
+                model.trail.pop_front();
+            }
+            model.trail.push_back(point);
+        }
+        model.state = Some(state);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    if model.ids.systems.len() != Kind::ALL.len() {
+        model
+            .ids
+            .systems
+            .resize(Kind::ALL.len(), &mut ui.widget_id_generator());
+    }
+    let current = dsp::kind(&model.params);
+    let mut picked = None;
+    for (i, kind) in Kind::ALL.iter().enumerate() {
+        for _click in widget::Button::new()
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(kind.name())
+            .label_font_size(15)
+            .themed(palette)
+            .border(if *kind == current { 2.0 } else { 0.0 })
+            .set(model.ids.systems[i], ui)
+        {
+            picked = Some(i);
+        }
+    }
+    if let Some(i) = picked {
+        model.params.set(dsp::SYSTEM, i as f32);
+        // the pushed system's coefficients start from its classic ones
+        for &param in [dsp::SIGMA, dsp::RHO, dsp::BETA].iter() {
+            model.params.set(param, dsp::PARAMS[param].default);
+        }
+    }
+}
+
+/// `point` on screen, turned `yaw` radians about the vertical, with z up,
+/// and how near it is from -1 to 1
+fn project(point: [f32; 3], yaw: f32, center: Point2, scale: f32) -> (Point2, f32) {
+    // the runaway spikes of Rössler's z stay on screen
+    let [x, y, z] = [point[0], point[1], point[2].clamp(-1.5, 1.5)];
+    let across = x * yaw.cos() - y * yaw.sin();
+    let depth = x * yaw.sin() + y * yaw.cos();
+    let up = z * TILT.cos() + depth * TILT.sin();
+    let near = -depth * TILT.cos() + z * TILT.sin();
+    let grow = 1.0 + near * PERSPECTIVE;
+    (
+        center + vec2(across, up) * scale * grow,
+        near.clamp(-1.0, 1.0),
+    )
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let center = area.xy();
+    let scale = area.w().min(area.h()) * 0.35;
+    let yaw = model.time * SPIN * TAU;
+    let [r, g, b] = palette.line;
+    let [ar, ag, ab] = palette.accent(0);
+
+    // what each variable plays along its axis
+    for (axis, label) in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        .iter()
+        .zip(["pan", "pitch", "cutoff"].iter())
+    {
+        let (start, _) = project([-axis[0], -axis[1], -axis[2]], yaw, center, scale);
+        let (end, _) = project(*axis, yaw, center, scale);
+        draw.line()
+            .start(start)
+            .end(end)
+            .weight(1.0)
+            .color(rgba(r, g, b, 0.2));
+        let direction = end - start;
+        let length = direction.x.hypot(direction.y).max(1.0);
+        draw.text(label)
+            .xy(end + direction * (16.0 / length))
+            .font_size(12)
+            .color(rgba(r, g, b, 0.5));
+    }
+
+    // the path fading into the past, brighter nearer
+    let len = model.trail.len();
+    let path = model.trail.iter().enumerate().map(|(i, &point)| {
+        let (at, near) = project(point, yaw, center, scale);
+        let age = i as f32 / len as f32;
+        (at, rgba(ar, ag, ab, age * (0.6 + 0.4 * near)))
+    });
+    draw.polyline().weight(1.5).points_colored(path);
+
+    let state = match &model.state {
+        Some(state) => state,
+        None => return,
+    };
+    if let Some(&point) = model.trail.back() {
+        let (at, near) = project(point, yaw, center, scale);
+        draw.ellipse()
+            .xy(at)
+            .radius(4.0 + 2.0 * near)
+            .color(rgba(r, g, b, 1.0));
+    }
+    draw.text(&format!(
+        "{}  {:.0}Hz  cutoff {:.0}Hz  pan {:.2}",
+        state.kind.name(),
+        state.freq,
+        state.cutoff,
+        state.pan
+    ))
+    .xy(pt2(area.x(), area.bottom() + 12.0))
+    .w(area.w())
+    .font_size(14)
+    .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
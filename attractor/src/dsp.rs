@@ -0,0 +1,200 @@
+use crate::system::{Coefficients, Kind, System};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::filter::Svf;
+use dsp_common::limiter::Limiter;
+use dsp_common::pan;
+use dsp_common::tuning::midi_to_freq;
+use dsp_common::Wavetable;
+use std::f32::consts::PI;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// points of the path published each buffer, evenly through it
+pub const POINTS: usize = 16;
+/// harmonics in the saw, few enough to stay under Nyquist at the top note
+const HARMONICS: usize = 16;
+const TABLE_SIZE: usize = 4096;
+/// frames between filter cutoff updates
+const BLOCK: usize = 32;
+const GAIN: f32 = 0.3;
+
+pub const SYSTEM: usize = 0;
+pub const SIGMA: usize = 1;
+pub const RHO: usize = 2;
+pub const BETA: usize = 3;
+pub const SPEED: usize = 4;
+pub const PITCH: usize = 5;
+pub const RANGE: usize = 6;
+pub const VOLUME: usize = 7;
+
+/// which system and its coefficients, how fast it runs, where it sounds
+/// and how widely, then how loud it plays
+pub static PARAMS: [ParamSpec; 8] = [
+    ParamSpec::new("system", 0.0, (Kind::ALL.len() - 1) as f32, 0.0),
+    ParamSpec::new("sigma", 0.1, 30.0, 10.0),
+    ParamSpec::new("rho", 0.1, 60.0, 28.0),
+    ParamSpec::new("beta", 0.1, 8.0, 2.67),
+    // of the system's time a second
+    ParamSpec::new("speed", 0.05, 5.0, 0.5).curve(Curve::Exponential),
+    // the middle of y
+    ParamSpec::new("pitch", 36.0, 72.0, 48.0),
+    // semitones either side of it
+    ParamSpec::new("range", 0.0, 36.0, 12.0),
+    ParamSpec::new("volume", 0.0, 1.0, 0.6),
+];
+
+/// the system the slider picks
+pub fn kind(params: &Params) -> Kind {
+    Kind::at(params.get(SYSTEM).round() as usize)
+}
+
+pub fn coefficients(params: &Params) -> Coefficients {
+    Coefficients {
+        sigma: params.get(SIGMA) as f64,
+        rho: params.get(RHO) as f64,
+        beta: params.get(BETA) as f64,
+    }
+}
+
+/// Where the system has been and what it sounds like, published after
+/// every buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct State {
+    pub kind: Kind,
+    /// normalized, see `System::normalized`
+    pub points: [[f32; 3]; POINTS],
+    pub freq: f32,
+    pub cutoff: f32,
+    /// 0 is hard left
+    pub pan: f32,
+}
+
+/// What each variable plays: x pans, y sets the pitch and z the cutoff.
+#[derive(Clone, Copy, Debug, Default)]
+struct Voice {
+    freq: f32,
+    cutoff: f32,
+    pan: f32,
+}
+
+impl Voice {
+    fn new([x, y, z]: [f32; 3], params: &Params) -> Self {
+        let note = params.get(PITCH) + y.clamp(-1.5, 1.5) * params.get(RANGE);
+        Self {
+            freq: midi_to_freq(note),
+            // from a dull 200Hz to a bright 6.4kHz
+            cutoff: 200.0 * 2f32.powf(2.5 * (z.clamp(-1.0, 1.0) + 1.0)),
+            pan: 0.5 + 0.5 * x.clamp(-1.0, 1.0),
+        }
+    }
+}
+
+/// A saw through a lowpass, played by the system as it integrates at
+/// sample rate.
+pub struct Engine {
+    bus: AudioEnd<(), State>,
+    params: Params,
+    system: System,
+    saw: Wavetable,
+    phase: f32,
+    filter: Svf,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<(), State>, params: Params) -> Self {
+        let saw = (0..TABLE_SIZE)
+            .map(|i| {
+                let t = i as f32 / TABLE_SIZE as f32;
+                (1..=HARMONICS)
+                    .map(|h| (2.0 * PI * h as f32 * t).sin() / h as f32)
+                    .sum::<f32>()
+                    * 0.55
+            })
+            .collect();
+        let mut filter = Svf::new(1000.0, 2.0, SAMPLE_RATE as f32);
+        filter.set_smoothing(0.01);
+        Self {
+            system: System::new(kind(&params)),
+            bus,
+            params,
+            saw: Wavetable::new(saw),
+            phase: 0.0,
+            filter,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.limiter.set_sample_rate(sample_rate as f32);
+        self.filter = Svf::new(self.filter.cutoff(), 2.0, sample_rate as f32);
+        self.filter.set_smoothing(0.01);
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.set_sample_rate(sample_rate);
+        }
+        let kind = kind(&self.params);
+        if kind != self.system.kind {
+            self.system = System::new(kind);
+        }
+        let coefficients = coefficients(&self.params);
+        let dt = (self.params.get(SPEED) / sample_rate as f32) as f64;
+        let gain = self.params.get(VOLUME) * GAIN;
+        let every = (out.len() / channels / POINTS).max(1);
+
+        let mut points = [[0.0; 3]; POINTS];
+        let mut voice = Voice::default();
+        let mut frame_index = 0;
+        for block in out.chunks_mut(BLOCK * channels) {
+            voice = Voice::new(self.system.normalized(), &self.params);
+            self.filter.set_cutoff(voice.cutoff);
+            let increment = voice.freq / sample_rate as f32;
+            for frame in block.chunks_exact_mut(channels) {
+                self.system.step(dt, &coefficients);
+                // the path a few times a buffer, evenly whatever its size
+                if frame_index % every == 0 && frame_index / every < POINTS {
+                    points[frame_index / every] = self.system.normalized();
+                }
+                frame_index += 1;
+
+                let sample = self.filter.process(self.saw.at(self.phase)).low * gain;
+                self.phase = (self.phase + increment).fract();
+                match frame {
+                    [mono] => *mono = sample,
+                    [l, r, ..] => {
+                        let (left, right) = pan::equal_power(sample, voice.pan);
+                        *l = left;
+                        *r = right;
+                    }
+                    [] => {}
+                }
+            }
+        }
+
+        self.bus.publish(State {
+            kind,
+            points,
+            freq: voice.freq,
+            cutoff: voice.cutoff,
+            pan: voice.pan,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
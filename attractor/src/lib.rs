@@ -0,0 +1,5 @@
+mod app;
+mod dsp;
+mod system;
+
+pub use app::{headless, render, run};
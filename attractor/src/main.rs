@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("attractor");
+    match &args.render {
+        Some(request) => attractor::render(request),
+        None if args.headless => attractor::headless(),
+        None => attractor::run(),
+    }
+}
@@ -0,0 +1,105 @@
+//! The Lorenz and Rössler systems and a Runge-Kutta step through them.
+//!
+//! Both take three coefficients from the same sliders: sigma, rho and beta
+//! for Lorenz, and for Rössler a, c and b scaled so the sliders' defaults
+//! land on its classic chaotic 0.2, 5.7 and 0.2.
+
+/// where a system starts, and starts again if it runs away
+pub const START: [f64; 3] = [1.0, 1.0, 1.0];
+/// further out than either system reaches while bounded
+const ESCAPED: f64 = 1e3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Lorenz,
+    Rossler,
+}
+
+impl Kind {
+    pub const ALL: [Self; 2] = [Self::Lorenz, Self::Rossler];
+
+    /// the kind at `index`, clamped
+    pub fn at(index: usize) -> Self {
+        Self::ALL[index.min(Self::ALL.len() - 1)]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Lorenz => "lorenz",
+            Kind::Rossler => "rössler",
+        }
+    }
+
+    /// About where each variable stays while the system is chaotic, as the
+    /// middle and half the width, to map them onto [-1, 1].
+    fn extent(self) -> [(f64, f64); 3] {
+        match self {
+            Kind::Lorenz => [(0.0, 20.0), (0.0, 27.0), (25.0, 25.0)],
+            Kind::Rossler => [(0.0, 11.0), (-1.5, 11.0), (4.0, 6.0)],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coefficients {
+    pub sigma: f64,
+    pub rho: f64,
+    pub beta: f64,
+}
+
+/// A point moving through a system.
+#[derive(Clone, Copy, Debug)]
+pub struct System {
+    pub kind: Kind,
+    pub point: [f64; 3],
+}
+
+impl System {
+    pub fn new(kind: Kind) -> Self {
+        Self { kind, point: START }
+    }
+
+    fn derivative(&self, [x, y, z]: [f64; 3], k: &Coefficients) -> [f64; 3] {
+        match self.kind {
+            Kind::Lorenz => [k.sigma * (y - x), x * (k.rho - z) - y, x * y - k.beta * z],
+            Kind::Rossler => {
+                let a = k.sigma * 0.02;
+                let b = k.beta * 0.075;
+                let c = k.rho * 5.7 / 28.0;
+                // sped up to go round about as often as Lorenz does
+                let t = 5.0;
+                [t * (-y - z), t * (x + a * y), t * (b + z * (x - c))]
+            }
+        }
+    }
+
+    /// moves on by `dt` with fourth order Runge-Kutta, back to the start if
+    /// the coefficients sent it off to infinity
+    pub fn step(&mut self, dt: f64, k: &Coefficients) {
+        let p = self.point;
+        let along = |d: [f64; 3], h: f64| [p[0] + d[0] * h, p[1] + d[1] * h, p[2] + d[2] * h];
+        let k1 = self.derivative(p, k);
+        let k2 = self.derivative(along(k1, dt * 0.5), k);
+        let k3 = self.derivative(along(k2, dt * 0.5), k);
+        let k4 = self.derivative(along(k3, dt), k);
+        for i in 0..3 {
+            self.point[i] += dt / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+        }
+        if self
+            .point
+            .iter()
+            .any(|v| !v.is_finite() || v.abs() > ESCAPED)
+        {
+            self.point = START;
+        }
+    }
+
+    /// the point with each variable about in [-1, 1], more outside it
+    pub fn normalized(&self) -> [f32; 3] {
+        let mut normalized = [0.0; 3];
+        for (i, (middle, half)) in self.kind.extent().iter().enumerate() {
+            normalized[i] = ((self.point[i] - middle) / half) as f32;
+        }
+        normalized
+    }
+}
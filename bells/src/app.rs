@@ -0,0 +1,521 @@
+use crate::dsp::{self, Command, Engine, Strike};
+use crate::modes::{self, Material, MODES};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::midi::{MidiInput, MidiMessage, MidiReceiver};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use dsp_common::tuning::{midi_to_freq, note_name};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::time::Duration;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+const BELLS: usize = 5;
+/// semitones above the root of each bell, a major pentatonic
+const SCALE: [i32; BELLS] = [0, 2, 4, 7, 9];
+/// strike the bells left to right, where the position slider says
+const KEYS: [Key; BELLS] = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5];
+/// how hard clicks and keys strike
+const VELOCITY: f32 = 0.8;
+/// impacts drawn at once, the oldest go first
+const MAX_IMPACTS: usize = 64;
+/// of a bell's radius a second, for the fundamental's ring, higher modes
+/// spread faster
+const RING_SPEED: f32 = 0.6;
+/// how often `headless` reads the MIDI input, short enough not to be heard
+const MIDI_POLL: Duration = Duration::from_millis(2);
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        materials[],
+    }
+}
+
+fn engine(config: &Config, params: &Params) -> (Engine, UiEnd<Command, ()>) {
+    let (ui_bus, audio_bus) = bus::bus(64, 1);
+    let mut engine = Engine::new(audio_bus, params.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("bells", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// every bell struck at once without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("bells", &config::path("bells"));
+    let params = shell::load_params("bells", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params);
+    for bell in 0..BELLS {
+        engine.strike(&Strike {
+            freq: midi_to_freq(note(&params, bell) as f32),
+            position: params.get(dsp::POSITION),
+            velocity: VELOCITY,
+            pan: pan(bell),
+        });
+    }
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// MIDI notes striking the bells on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("bells", &config::path("bells"));
+    let params = shell::load_params("bells", &config, &dsp::PARAMS);
+    let (engine, mut bus) = engine(&config, &params);
+    let mut midi = open_midi(&config);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    match stream.rebuild() {
+        Ok(()) => println!("bells: playing on {}", stream.device().unwrap_or("?")),
+        Err(e) => eprintln!("bells: {}, waiting for a device", e),
+    }
+    loop {
+        if let Some(event) = stream.poll() {
+            println!("bells: {:?}", event);
+        }
+        if let Some((input, receiver)) = &mut midi {
+            input.poll();
+            for event in receiver.drain() {
+                if let Some((_, strike)) = midi_strike(&params, event.message) {
+                    let _ = bus.send(Command::Strike(strike));
+                }
+            }
+        }
+        std::thread::sleep(MIDI_POLL);
+    }
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<Command, ()>,
+    /// notes strike the bells, from `midi_device` or the first port found
+    midi: Option<(MidiInput, MidiReceiver)>,
+    /// strikes still ringing on screen, oldest first
+    impacts: Vec<Impact>,
+    /// seconds since the window opened, impacts are timed against it
+    time: f32,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+/// A strike as drawn, kept until its longest mode has died away.
+struct Impact {
+    bell: usize,
+    /// from the bell's middle, in radii
+    offset: Vector2,
+    position: f32,
+    velocity: f32,
+    /// as they were when struck, so changing them doesn't change the rings
+    material: Material,
+    decay: f32,
+    /// `Model::time` when struck
+    time: f32,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("bells");
+    let (config, _) = shell::load_config("bells", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("bells", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("bells", &config, &params);
+    let serial = serial::Sensors::from_config("bells", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("bells", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "bells", &config, stream.stats());
+    let shell =
+        Shell::new("bells", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        midi: open_midi(&shell.config),
+        impacts: Vec::new(),
+        time: 0.0,
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        Event::WindowEvent {
+            simple: Some(MousePressed(MouseButton::Left)),
+            ..
+        } => {
+            if model.shell.setup().is_none() && model.errors.is_none() {
+                clicked(app, model);
+            }
+            return;
+        }
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if let Some(bell) = KEYS.iter().position(|&k| k == key) {
+        let position = model.params.get(dsp::POSITION);
+        let strike = Strike {
+            freq: midi_to_freq(note(&model.params, bell) as f32),
+            position,
+            velocity: VELOCITY,
+            pan: pan(bell),
+        };
+        strike_bell(model, bell, vec2(0.0, position), strike);
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+/// strikes the bell under the mouse where it is, unless the controls have
+/// the mouse
+fn clicked(app: &App, model: &mut Model) {
+    let capturing = model.ui.global_input().current.widget_capturing_mouse;
+    if capturing.map_or(false, |id| id != model.ui.window) {
+        return;
+    }
+    let mouse = app.mouse.position();
+    // how far out from each bell's middle, in radii
+    let hit = bells(scene_area(app))
+        .iter()
+        .map(|&(centre, radius)| (mouse - centre) / radius)
+        .enumerate()
+        .find(|(_, offset)| offset.x.hypot(offset.y) <= 1.0);
+    if let Some((bell, offset)) = hit {
+        let strike = Strike {
+            freq: midi_to_freq(note(&model.params, bell) as f32),
+            position: offset.x.hypot(offset.y),
+            velocity: VELOCITY,
+            pan: pan(bell),
+        };
+        strike_bell(model, bell, offset, strike);
+    }
+}
+
+/// sends `strike` and rings `bell` on screen, `offset` from its middle in
+/// radii
+fn strike_bell(model: &mut Model, bell: usize, offset: Vector2, strike: Strike) {
+    if model.bus.send(Command::Strike(strike)).is_err() {
+        return;
+    }
+    if model.impacts.len() == MAX_IMPACTS {
+        model.impacts.remove(0);
+    }
+    model.impacts.push(Impact {
+        bell,
+        offset,
+        position: strike.position,
+        velocity: strike.velocity,
+        material: dsp::material(&model.params),
+        decay: model.params.get(dsp::DECAY),
+        time: model.time,
+    });
+}
+
+/// the note of each bell, up the scale from the root
+fn note(params: &Params, bell: usize) -> i32 {
+    params.get(dsp::ROOT).round() as i32 + SCALE[bell]
+}
+
+/// left to right as they're drawn
+fn pan(bell: usize) -> f32 {
+    0.2 + 0.6 * bell as f32 / (BELLS - 1) as f32
+}
+
+/// A note on as a strike at the position slider's, with the bell nearest
+/// it in pitch class to show it on.
+fn midi_strike(params: &Params, message: MidiMessage) -> Option<(usize, Strike)> {
+    let (note, velocity) = match message {
+        MidiMessage::NoteOn { note, velocity, .. } if velocity > 0 => (note, velocity),
+        _ => return None,
+    };
+    let class = (note as i32 - params.get(dsp::ROOT).round() as i32).rem_euclid(12);
+    let distance = |bell: &usize| {
+        let d = (class - SCALE[*bell]).abs();
+        d.min(12 - d)
+    };
+    let bell = (0..BELLS).min_by_key(distance)?;
+    let strike = Strike {
+        freq: midi_to_freq(note as f32),
+        position: params.get(dsp::POSITION),
+        velocity: velocity as f32 / 127.0,
+        pan: pan(bell),
+    };
+    Some((bell, strike))
+}
+
+fn open_midi(config: &Config) -> Option<(MidiInput, MidiReceiver)> {
+    let (mut input, receiver) = MidiInput::new("bells", 256)
+        .map_err(|e| eprintln!("bells: {}", e))
+        .ok()?;
+    if let Some(port) = config
+        .midi_device
+        .clone()
+        .or_else(|| input.ports().first().cloned())
+    {
+        if let Err(e) = input.select(&port) {
+            eprintln!("bells: {}", e);
+        }
+    }
+    Some((input, receiver))
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.time += update.since_last.as_secs_f32();
+    let time = model.time;
+    model
+        .impacts
+        .retain(|impact| time - impact.time < impact.material.longest(impact.decay));
+    let mut struck = Vec::new();
+    if let Some((input, receiver)) = &mut model.midi {
+        input.poll();
+        for event in receiver.drain() {
+            struck.extend(midi_strike(&model.params, event.message));
+        }
+    }
+    for (bell, strike) in struck {
+        let offset = vec2(0.0, strike.position);
+        strike_bell(model, bell, offset, strike);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    // presets for the material slider, the current one outlined
+    if model.ids.materials.len() != Material::ALL.len() {
+        model
+            .ids
+            .materials
+            .resize(Material::ALL.len(), &mut ui.widget_id_generator());
+    }
+    let current = dsp::material(&model.params);
+    let mut picked = None;
+    for (i, material) in Material::ALL.iter().enumerate() {
+        for _click in widget::Button::new()
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(material.name)
+            .label_font_size(15)
+            .themed(palette)
+            .border(if *material == current { 2.0 } else { 0.0 })
+            .set(model.ids.materials[i], ui)
+        {
+            picked = Some(i);
+        }
+    }
+    if let Some(i) = picked {
+        model.params.set(dsp::MATERIAL, i as f32);
+    }
+}
+
+/// right of the controls
+fn scene_area(app: &App) -> Rect {
+    app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN)
+}
+
+/// the middle and radius of each bell, in a row across `area`, lower ones
+/// bigger
+fn bells(area: Rect) -> [(Point2, f32); BELLS] {
+    let slot = area.w() / BELLS as f32;
+    let largest = (slot * 0.45).min(area.h() * 0.3);
+    let mut bells = [(pt2(0.0, 0.0), 0.0); BELLS];
+    for (i, bell) in bells.iter_mut().enumerate() {
+        let x = area.left() + slot * (i as f32 + 0.5);
+        *bell = (pt2(x, area.y()), largest * (1.0 - 0.1 * i as f32));
+    }
+    bells
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = scene_area(app);
+    let bells = bells(area);
+    let [r, g, b] = palette.line;
+    for (i, &(centre, radius)) in bells.iter().enumerate() {
+        draw.ellipse()
+            .xy(centre)
+            .radius(radius)
+            .no_fill()
+            .stroke_weight(2.0)
+            .stroke(rgba(r, g, b, 0.6));
+        let name = note_name(note(&model.params, i));
+        draw.text(&format!("{}\n{}", i + 1, name))
+            .xy(centre - vec2(0.0, radius + 24.0))
+            .font_size(12)
+            .color(rgba(r, g, b, 0.6));
+    }
+
+    // a ring a mode spreading from where it was struck, higher modes
+    // faster, each fading as the mode dies away
+    for impact in &model.impacts {
+        let (centre, radius) = bells[impact.bell];
+        let at = centre + impact.offset * radius;
+        let age = model.time - impact.time;
+        for mode in 0..MODES {
+            let level = impact.velocity
+                * modes::excitation(mode, impact.position)
+                * modes::envelope(age, impact.material.decays[mode] * impact.decay);
+            if level < 1e-3 {
+                continue;
+            }
+            let [ar, ag, ab] = palette.accent(mode);
+            let spread = radius * RING_SPEED * age * impact.material.ratios[mode].sqrt();
+            draw.ellipse()
+                .xy(at)
+                .radius(spread + 2.0)
+                .no_fill()
+                .stroke_weight(1.0 + level * 3.0)
+                .stroke(rgba(ar, ag, ab, level.min(1.0)));
+        }
+        // the strike point, bright as it lands
+        let flash = modes::envelope(age, 0.3) * impact.velocity;
+        draw.ellipse()
+            .xy(at)
+            .radius(3.0 + flash * 6.0)
+            .color(rgba(r, g, b, flash));
+    }
+
+    draw.text(dsp::material(&model.params).name)
+        .xy(pt2(area.x(), area.top() - 20.0))
+        .font_size(16)
+        .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,199 @@
+use crate::modes::{self, Material, MODES};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::pan;
+use std::f32::consts::TAU;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// strikes ringing at once, the quietest makes way for a new one
+const VOICES: usize = 12;
+const GAIN: f32 = 0.3;
+/// energy under which a mode, or a whole voice, has died away
+const SILENT: f32 = 1e-8;
+
+pub const MATERIAL: usize = 0;
+pub const DECAY: usize = 1;
+pub const POSITION: usize = 2;
+pub const ROOT: usize = 3;
+pub const VOLUME: usize = 4;
+
+/// what's struck and how long it rings, where MIDI strikes it, the note of
+/// the lowest bell, then how loud it plays
+pub static PARAMS: [ParamSpec; 5] = [
+    ParamSpec::new("material", 0.0, (Material::ALL.len() - 1) as f32, 0.0),
+    ParamSpec::new("decay", 0.25, 4.0, 1.0).curve(Curve::Exponential),
+    ParamSpec::new("position", 0.0, 1.0, 0.3),
+    ParamSpec::new("root", 36.0, 84.0, 60.0),
+    ParamSpec::new("volume", 0.0, 1.0, 0.8),
+];
+
+/// the material the slider picks
+pub fn material(params: &Params) -> Material {
+    Material::at(params.get(MATERIAL).round() as usize)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Strike {
+    pub freq: f32,
+    /// from the middle of the object at 0 to its rim at 1
+    pub position: f32,
+    /// from 0 to 1
+    pub velocity: f32,
+    /// 0 is hard left
+    pub pan: f32,
+}
+
+pub enum Command {
+    Strike(Strike),
+}
+
+/// A mode as a decaying phasor, struck by pushing it along its real axis so
+/// the output, its imaginary part, never jumps.
+#[derive(Clone, Copy, Debug, Default)]
+struct Mode {
+    re: f32,
+    im: f32,
+    /// the rotation a frame, shrunk by the decay
+    cos: f32,
+    sin: f32,
+}
+
+impl Mode {
+    fn tune(&mut self, freq: f32, decay: f32, sample_rate: f32) {
+        let radius = modes::envelope(1.0 / sample_rate, decay);
+        let angle = TAU * freq / sample_rate;
+        self.cos = radius * angle.cos();
+        self.sin = radius * angle.sin();
+    }
+
+    #[inline(always)]
+    fn step(&mut self) -> f32 {
+        let re = self.re * self.cos - self.im * self.sin;
+        self.im = self.re * self.sin + self.im * self.cos;
+        self.re = re;
+        self.im
+    }
+
+    fn energy(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Voice {
+    modes: [Mode; MODES],
+    pan: f32,
+}
+
+impl Voice {
+    fn energy(&self) -> f32 {
+        self.modes.iter().map(Mode::energy).sum()
+    }
+
+    fn strike(&mut self, strike: &Strike, material: &Material, decay: f32, sample_rate: f32) {
+        self.pan = strike.pan;
+        for (i, mode) in self.modes.iter_mut().enumerate() {
+            let freq = strike.freq * material.ratios[i];
+            *mode = Mode::default();
+            // modes past Nyquist would alias back down
+            if freq < sample_rate * 0.45 {
+                mode.tune(freq, material.decays[i] * decay, sample_rate);
+                mode.re = strike.velocity * modes::excitation(i, strike.position);
+            }
+        }
+    }
+}
+
+/// Voices of modal resonators, each struck afresh.
+pub struct Engine {
+    bus: AudioEnd<Command, ()>,
+    params: Params,
+    voices: [Voice; VOICES],
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, ()>, params: Params) -> Self {
+        Self {
+            bus,
+            params,
+            voices: [Voice::default(); VOICES],
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// rings the quietest voice
+    pub fn strike(&mut self, strike: &Strike) {
+        let material = material(&self.params);
+        let decay = self.params.get(DECAY);
+        quietest(&mut self.voices).strike(strike, &material, decay, self.sample_rate as f32);
+    }
+}
+
+fn quietest(voices: &mut [Voice; VOICES]) -> &mut Voice {
+    let mut quietest = 0;
+    for (i, voice) in voices.iter().enumerate() {
+        if voice.energy() < voices[quietest].energy() {
+            quietest = i;
+        }
+    }
+    &mut voices[quietest]
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        let material = material(&self.params);
+        let decay = self.params.get(DECAY);
+        for command in self.bus.commands() {
+            match command {
+                Command::Strike(strike) => {
+                    quietest(&mut self.voices).strike(&strike, &material, decay, sample_rate as f32)
+                }
+            }
+        }
+
+        let gain = self.params.get(VOLUME) * GAIN;
+        for voice in self.voices.iter_mut() {
+            if voice.energy() < SILENT {
+                continue;
+            }
+            for frame in out.chunks_exact_mut(channels) {
+                let sample = voice.modes.iter_mut().map(Mode::step).sum::<f32>() * gain;
+                match frame {
+                    [mono] => *mono += sample,
+                    [l, r, ..] => {
+                        let (left, right) = pan::equal_power(sample, voice.pan);
+                        *l += left;
+                        *r += right;
+                    }
+                    [] => {}
+                }
+            }
+            // before they reach denormals, which are slow
+            for mode in voice.modes.iter_mut() {
+                if mode.energy() < SILENT {
+                    *mode = Mode::default();
+                }
+            }
+        }
+
+        self.limiter.process_interleaved(out, channels);
+    }
+}
@@ -0,0 +1,5 @@
+mod app;
+mod dsp;
+mod modes;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("bells");
+    match &args.render {
+        Some(request) => bells::render(request),
+        None if args.headless => bells::headless(),
+        None => bells::run(),
+    }
+}
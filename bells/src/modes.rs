@@ -0,0 +1,69 @@
+//! Modal models of struck objects.
+//!
+//! A material is a handful of modes, each a sine at some ratio of the
+//! fundamental dying away at its own rate. Where the object is struck sets
+//! how hard each mode is driven: near a mode's node it hardly sounds.
+
+use std::f32::consts::PI;
+
+pub const MODES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub name: &'static str,
+    /// of the fundamental, per mode
+    pub ratios: [f32; MODES],
+    /// seconds to fall by 60dB, per mode
+    pub decays: [f32; MODES],
+}
+
+impl Material {
+    /// a wine glass, sparse and long
+    pub const GLASS: Self = Self {
+        name: "glass",
+        ratios: [1.0, 2.32, 4.25, 6.63, 9.38, 12.6, 16.1, 20.0],
+        decays: [4.0, 3.2, 2.4, 1.8, 1.2, 0.9, 0.6, 0.4],
+    };
+
+    /// a marimba bar, its upper modes damped quickly
+    pub const WOOD: Self = Self {
+        name: "wood",
+        ratios: [1.0, 3.99, 10.65, 20.8, 34.3, 51.2, 71.4, 95.0],
+        decays: [0.8, 0.3, 0.12, 0.06, 0.04, 0.03, 0.02, 0.02],
+    };
+
+    /// a church bell, hum and tierce under the strike note
+    pub const METAL: Self = Self {
+        name: "metal",
+        ratios: [0.5, 1.0, 1.2, 1.5, 2.0, 2.5, 2.67, 3.01],
+        decays: [8.0, 6.0, 5.0, 4.0, 3.0, 2.0, 1.6, 1.2],
+    };
+
+    pub const ALL: [Self; 3] = [Self::GLASS, Self::WOOD, Self::METAL];
+
+    /// the material at `index`, clamped
+    pub fn at(index: usize) -> Self {
+        Self::ALL[index.min(Self::ALL.len() - 1)]
+    }
+
+    /// the longest any mode rings, scaled by `decay`
+    pub fn longest(&self, decay: f32) -> f32 {
+        self.decays.iter().fold(0.0f32, |a, &b| a.max(b)) * decay
+    }
+}
+
+/// How hard a strike at `position` drives `mode`, 0 in the middle of the
+/// object where the even modes have their nodes, towards 1 at the rim where
+/// the upper modes come through.
+pub fn excitation(mode: usize, position: f32) -> f32 {
+    let x = 0.5 - 0.45 * position.clamp(0.0, 1.0);
+    // a little of every mode, strikes are never exactly on a node
+    (PI * (mode + 1) as f32 * x).sin().abs().max(0.05) / (mode + 1) as f32
+}
+
+/// The level `seconds` after a strike of a mode that takes `decay` seconds
+/// to fall by 60dB.
+pub fn envelope(seconds: f32, decay: f32) -> f32 {
+    // ln(1000)
+    (-6.91 * seconds / decay.max(1e-3)).exp()
+}
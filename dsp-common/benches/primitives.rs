@@ -0,0 +1,159 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dsp_common::env::{Envelope, Shape};
+use dsp_common::filter::{Biquad, Response, Svf};
+use dsp_common::simd::{self, scalar};
+use dsp_common::spectrum::{Stft, StftConfig};
+use dsp_common::{filut, filut_clamped, limiter::Limiter, pan, Wavetable};
+
+const BLOCK: usize = 512;
+
+fn wavetable(c: &mut Criterion) {
+    let table = Wavetable::sine(64);
+    c.bench_function("filut 512", |b| {
+        b.iter(|| {
+            (0..BLOCK).fold(0.0, |acc, i| {
+                acc + filut(table.as_slice(), black_box(i as f32 * 0.13) % 64.0)
+            })
+        })
+    });
+    c.bench_function("filut_clamped 512", |b| {
+        b.iter(|| {
+            (0..BLOCK).fold(0.0, |acc, i| {
+                acc + filut_clamped(table.as_slice(), black_box(i as f32 * 0.13) % 64.0)
+            })
+        })
+    });
+    c.bench_function("wavetable at 512", |b| {
+        b.iter(|| (0..BLOCK).fold(0.0, |acc, i| acc + table.at(black_box(i as f32 * 0.001))))
+    });
+}
+
+fn envelope(c: &mut Criterion) {
+    c.bench_function("trapezoid 512", |b| {
+        let mut env = Envelope::new(Shape::trapezoid(0.125, 0.125));
+        b.iter(|| {
+            env.trigger(BLOCK as f32);
+            (0..BLOCK).fold(0.0, |acc, _| acc + env.step())
+        })
+    });
+    c.bench_function("curved adsr 512", |b| {
+        let mut env = Envelope::new(Shape::adsr(0.002, 0.004, 0.5, 0.004).curve(4.0));
+        b.iter(|| {
+            env.gate_on(1.0 / 44_100.0);
+            let held = (0..BLOCK / 2).fold(0.0, |acc, _| acc + env.step());
+            env.gate_off();
+            (0..BLOCK / 2).fold(held, |acc, _| acc + env.step())
+        })
+    });
+}
+
+fn panning(c: &mut Criterion) {
+    c.bench_function("pan linear 512", |b| {
+        b.iter(|| {
+            (0..BLOCK).fold((0.0, 0.0), |(l, r), i| {
+                let (x, y) = pan::linear(black_box(0.5), i as f32 / BLOCK as f32);
+                (l + x, r + y)
+            })
+        })
+    });
+    c.bench_function("pan equal_power 512", |b| {
+        b.iter(|| {
+            (0..BLOCK).fold((0.0, 0.0), |(l, r), i| {
+                let (x, y) = pan::equal_power(black_box(0.5), i as f32 / BLOCK as f32);
+                (l + x, r + y)
+            })
+        })
+    });
+}
+
+fn limiting(c: &mut Criterion) {
+    c.bench_function("limiter stereo 512", |b| {
+        let mut limiter = Limiter::new(48_000.0);
+        let mut buffer: Vec<f32> = (0..BLOCK * 2)
+            .map(|i| (i as f32 * 0.01).sin() * 2.0)
+            .collect();
+        b.iter(|| limiter.process_interleaved(black_box(&mut buffer), 2))
+    });
+}
+
+fn filtering(c: &mut Criterion) {
+    let input: Vec<f32> = (0..BLOCK).map(|i| (i as f32 * 0.37).sin()).collect();
+    c.bench_function("biquad 512", |b| {
+        let mut filter = Biquad::lowpass(1000.0, 48_000.0);
+        let mut block = input.clone();
+        b.iter(|| filter.process_block(black_box(&mut block)))
+    });
+    c.bench_function("biquad sweeping 512", |b| {
+        let mut filter = Biquad::lowpass(1000.0, 48_000.0);
+        filter.set_smoothing(0.05);
+        let mut block = input.clone();
+        let mut up = false;
+        b.iter(|| {
+            up = !up;
+            filter.set_cutoff(if up { 8000.0 } else { 200.0 });
+            filter.process_block(black_box(&mut block))
+        })
+    });
+    c.bench_function("svf 512", |b| {
+        let mut filter = Svf::new(1000.0, 2.0, 48_000.0);
+        let mut block = input.clone();
+        b.iter(|| filter.process_block(black_box(&mut block), Response::Bandpass))
+    });
+}
+
+/// the granular inner loop, table read, envelope and pan per grain
+fn grains(c: &mut Criterion) {
+    const GRAIN_BLOCK: usize = 64;
+    let table: Vec<f32> = (0..48_000).map(|i| (i as f32 * 0.01).sin()).collect();
+    let env: Vec<f32> = (0..GRAIN_BLOCK)
+        .map(|i| i as f32 / GRAIN_BLOCK as f32)
+        .collect();
+
+    for &count in [8, 64, 256].iter() {
+        let mix = |filut_block: fn(&[f32], &mut [f32]),
+                   multiply: fn(&mut [f32], &[f32]),
+                   pan_accumulate: fn(&[f32], f32, f32, &mut [f32], &mut [f32])| {
+            let mut samples = [0.0; GRAIN_BLOCK];
+            let (mut left, mut right) = ([0.0; GRAIN_BLOCK], [0.0; GRAIN_BLOCK]);
+            for grain in 0..count {
+                let increment = 1.0 + grain as f32 * 0.01;
+                for (i, phase) in samples.iter_mut().enumerate() {
+                    *phase = (grain * 97) as f32 + i as f32 * increment;
+                }
+                filut_block(&table, &mut samples);
+                multiply(&mut samples, &env);
+                pan_accumulate(&samples, 0.3, 0.125, &mut left, &mut right);
+            }
+            (left[0], right[0])
+        };
+        c.bench_function(&format!("grains scalar {}", count), |b| {
+            b.iter(|| {
+                mix(
+                    scalar::filut_clamped_block,
+                    scalar::multiply,
+                    scalar::pan_accumulate,
+                )
+            })
+        });
+        c.bench_function(&format!("grains simd {}", count), |b| {
+            b.iter(|| {
+                mix(
+                    simd::filut_clamped_block,
+                    simd::multiply,
+                    simd::pan_accumulate,
+                )
+            })
+        });
+    }
+}
+
+fn spectrum(c: &mut Criterion) {
+    c.bench_function("stft 2048 hop 512", |b| {
+        let mut stft = Stft::new(StftConfig::default());
+        let input: Vec<f32> = (0..512).map(|i| (i as f32 * 0.05).sin()).collect();
+        b.iter(|| stft.process(black_box(&input)))
+    });
+}
+
+criterion_group!(benches, wavetable, envelope, panning, limiting, filtering, grains, spectrum);
+criterion_main!(benches);
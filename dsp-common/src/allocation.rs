@@ -0,0 +1,79 @@
+//! Catching allocations on the audio thread.
+//!
+//! `Checked` wraps the system allocator and counts what a thread allocates
+//! while it holds a `Forbid`. A binary opts in by making it the global
+//! allocator, app-common does with its `alloc-check` feature; otherwise
+//! `Forbid` never sees anything. Frees are let through, commands sent to
+//! the audio thread are boxed by the UI and freed once they've run.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    /// `Forbid`s alive on this thread
+    static FORBIDDEN: Cell<u32> = const { Cell::new(0) };
+    /// allocations on this thread while one was
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn note() {
+    // a thread being torn down has no locals left, and nothing to check
+    let _ = FORBIDDEN.try_with(|forbidden| {
+        if forbidden.get() > 0 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        }
+    });
+}
+
+/// The system allocator, counting allocations under a `Forbid`.
+pub struct Checked;
+
+// SAFETY: every call is passed straight on to the system allocator, only
+// thread locals without destructors are touched besides
+unsafe impl GlobalAlloc for Checked {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        note();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        note();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        note();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Counts allocations on the current thread until dropped. Create one
+/// around a render callback and ask it afterwards.
+pub struct Forbid {
+    before: usize,
+}
+
+impl Forbid {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        FORBIDDEN.with(|forbidden| forbidden.set(forbidden.get() + 1));
+        Self {
+            before: ALLOCATIONS.with(Cell::get),
+        }
+    }
+
+    /// allocations since `new`, always 0 unless `Checked` is installed
+    pub fn allocations(&self) -> usize {
+        ALLOCATIONS.with(Cell::get) - self.before
+    }
+}
+
+impl Drop for Forbid {
+    fn drop(&mut self) {
+        FORBIDDEN.with(|forbidden| forbidden.set(forbidden.get() - 1));
+    }
+}
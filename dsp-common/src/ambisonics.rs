@@ -0,0 +1,151 @@
+//! First-order ambisonics in AmbiX: channels in ACN order, W, Y, Z then X,
+//! with SN3D weights so W carries a source at its own level. A decoder
+//! further down the line places it on whatever rig the room has.
+
+use crate::pan::{Layout, MAX_SPEAKERS};
+
+pub const CHANNELS: usize = 4;
+
+/// the channels' gains for a source at `azimuth` degrees clockwise from the
+/// front and `elevation` degrees up
+pub fn encode(azimuth: f32, elevation: f32) -> [f32; CHANNELS] {
+    // AmbiX turns anticlockwise, Y points left
+    let (azimuth, elevation) = (-azimuth.to_radians(), elevation.to_radians());
+    let (sin, cos) = azimuth.sin_cos();
+    [
+        1.0,
+        sin * elevation.cos(),
+        elevation.sin(),
+        cos * elevation.cos(),
+    ]
+}
+
+/// A `Layout`'s speakers encoded where they stand, all tilted up or down
+/// together.
+#[derive(Clone, Copy, Debug)]
+pub struct Encoder {
+    layout: Layout,
+    elevation: f32,
+    /// by channel, speakerless ones are silent
+    gains: [[f32; CHANNELS]; MAX_SPEAKERS],
+}
+
+impl Encoder {
+    /// `elevation` in degrees, up is positive
+    pub fn new(layout: Layout, elevation: f32) -> Self {
+        let mut encoder = Self {
+            layout,
+            elevation,
+            gains: [[0.0; CHANNELS]; MAX_SPEAKERS],
+        };
+        encoder.place();
+        encoder
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    pub fn set_elevation(&mut self, elevation: f32) {
+        self.elevation = elevation;
+        self.place();
+    }
+
+    fn place(&mut self) {
+        let elevation = self.elevation.clamp(-90.0, 90.0);
+        for (channel, gains) in self.gains.iter_mut().enumerate() {
+            *gains = self
+                .layout
+                .angle(channel)
+                .map_or([0.0; CHANNELS], |azimuth| encode(azimuth, elevation));
+        }
+    }
+
+    /// `input` interleaved in the layout's channels, mixed into the first
+    /// four of interleaved `out`
+    pub fn process(&self, input: &[f32], out: &mut [f32], channels: usize) {
+        let speakers = self.layout.channels();
+        for (frame, out) in input
+            .chunks_exact(speakers)
+            .zip(out.chunks_exact_mut(channels))
+        {
+            for (&x, gains) in frame.iter().zip(self.gains.iter()) {
+                for (out, gain) in out.iter_mut().zip(gains.iter()) {
+                    *out += x * gain;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_gains(gains: [f32; CHANNELS], expected: [f32; CHANNELS]) {
+        for (gain, expected) in gains.iter().zip(&expected) {
+            assert!((gain - expected).abs() < 1e-6, "{:?}", gains);
+        }
+    }
+
+    #[test]
+    fn cardinal_directions_land_on_their_channels() {
+        // W, Y (left), Z (up), X (front)
+        assert_gains(encode(0.0, 0.0), [1.0, 0.0, 0.0, 1.0]);
+        assert_gains(encode(-90.0, 0.0), [1.0, 1.0, 0.0, 0.0]);
+        assert_gains(encode(90.0, 0.0), [1.0, -1.0, 0.0, 0.0]);
+        assert_gains(encode(180.0, 0.0), [1.0, 0.0, 0.0, -1.0]);
+        assert_gains(encode(0.0, 90.0), [1.0, 0.0, 1.0, 0.0]);
+        assert_gains(encode(0.0, -90.0), [1.0, 0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn sn3d_keeps_every_direction_at_the_level_of_w() {
+        for azimuth in (-180..180).step_by(15) {
+            for elevation in (-90..=90).step_by(15) {
+                let [w, y, z, x] = encode(azimuth as f32, elevation as f32);
+                assert!((y * y + z * z + x * x - w * w).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn decoding_finds_the_speaker_playing() {
+        let layout = Layout::SURROUND;
+        let encoder = Encoder::new(layout, 0.0);
+        for speaker in (0..layout.channels()).filter(|&c| layout.angle(c).is_some()) {
+            let mut input = vec![0.0; layout.channels()];
+            input[speaker] = 1.0;
+            let mut out = [0.0; CHANNELS];
+            encoder.process(&input, &mut out, CHANNELS);
+            // a plain projection decoder pointed all the way round
+            let loudest = (-180..180)
+                .map(|azimuth| {
+                    let gains = encode(azimuth as f32, 0.0);
+                    let level: f32 = gains.iter().zip(&out).map(|(g, b)| g * b).sum();
+                    (level, azimuth as f32)
+                })
+                .fold((f32::MIN, 0.0), |a, b| if b.0 > a.0 { b } else { a });
+            assert_eq!(loudest.1, layout.angle(speaker).unwrap());
+        }
+    }
+
+    #[test]
+    fn the_lfe_stays_out_and_elevation_tilts_everything() {
+        let layout = Layout::SURROUND;
+        let mut input = vec![0.0; layout.channels()];
+        input[3] = 1.0;
+        let mut out = [0.0; CHANNELS];
+        Encoder::new(layout, 0.0).process(&input, &mut out, CHANNELS);
+        assert_eq!(out, [0.0; CHANNELS]);
+
+        input[3] = 0.0;
+        input[2] = 1.0;
+        Encoder::new(layout, 90.0).process(&input, &mut out, CHANNELS);
+        assert_gains(out, [1.0, 0.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,97 @@
+//! What visuals follow in a signal besides its level: how its energy spreads
+//! over the spectrum and where the beats fall.
+//!
+//! Both work on `Stft` magnitudes, so they can run wherever the spectrum is
+//! read, the audio thread never has to.
+
+use crate::meter::to_db;
+
+/// the bottom of the lowest band, below it is rumble
+pub const LOWEST: f32 = 40.0;
+/// the top of the highest band
+pub const HIGHEST: f32 = 16_000.0;
+/// dB a band reads 0 at in `level`
+const FLOOR: f32 = -60.0;
+
+/// bands the lowest beats are listened for in, kicks and bass
+const BEAT_BANDS: usize = 2;
+/// seconds the energy is averaged over to tell a beat from what's around it
+const AVERAGE: f32 = 1.0;
+/// times the average energy a beat has to reach
+const THRESHOLD: f32 = 1.5;
+/// below this nothing is a beat, however quiet the average
+const QUIET: f32 = 1e-4;
+/// seconds after a beat before another, 240bpm at the most
+const REFRACTORY: f32 = 0.25;
+
+/// where band `band` of `bands` starts, log spaced from `LOWEST` to `HIGHEST`
+pub fn band_edge(band: usize, bands: usize) -> f32 {
+    LOWEST * (HIGHEST / LOWEST).powf(band as f32 / bands.max(1) as f32)
+}
+
+/// Fills `out` with the magnitude of each of `out.len()` log spaced bands,
+/// the root of the energy in the bins it covers, so a full scale sine
+/// reads about 1 in its band. `bin_width` is in Hz.
+pub fn bands(magnitudes: &[f32], bin_width: f32, out: &mut [f32]) {
+    let count = out.len();
+    for (band, out) in out.iter_mut().enumerate() {
+        let low = (band_edge(band, count) / bin_width).ceil() as usize;
+        // a band narrower than a bin still reads the bin it falls in
+        let high = ((band_edge(band + 1, count) / bin_width).ceil() as usize).max(low + 1);
+        let bins = magnitudes
+            .get(low..high.min(magnitudes.len()))
+            .unwrap_or(&[]);
+        *out = bins.iter().map(|m| m * m).sum::<f32>().sqrt();
+    }
+}
+
+/// a band's magnitude as 0 at `FLOOR` dB up to 1 at full scale, how loud
+/// it looks rather than how much energy it holds
+pub fn level(magnitude: f32) -> f32 {
+    ((to_db(magnitude) - FLOOR) / -FLOOR).clamp(0.0, 1.0)
+}
+
+/// Finds beats as jumps in the low bands' energy over its recent average.
+#[derive(Clone, Debug)]
+pub struct Beats {
+    average: f32,
+    /// seconds since the last beat
+    since: f32,
+    count: u64,
+}
+
+impl Default for Beats {
+    fn default() -> Self {
+        Self {
+            average: 0.0,
+            since: f32::INFINITY,
+            count: 0,
+        }
+    }
+}
+
+impl Beats {
+    /// `bands` as from `bands`, `dt` seconds after the last push, true on a
+    /// beat
+    pub fn push(&mut self, bands: &[f32], dt: f32) -> bool {
+        let energy: f32 = bands.iter().take(BEAT_BANDS).map(|m| m * m).sum();
+        self.since += dt;
+        let beat = energy > QUIET && energy > self.average * THRESHOLD && self.since > REFRACTORY;
+        self.average += (energy - self.average) * (1.0 - (-dt / AVERAGE).exp());
+        if beat {
+            self.since = 0.0;
+            self.count += 1;
+        }
+        beat
+    }
+
+    /// seconds since the last beat, infinite before the first
+    pub fn since(&self) -> f32 {
+        self.since
+    }
+
+    /// beats found so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
@@ -0,0 +1,65 @@
+//! Sample-accurate replay of recorded parameter changes.
+
+use crate::param::Params;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    /// frames since the start of the recording
+    pub frame: u64,
+    pub index: usize,
+    pub value: f32,
+}
+
+/// Audio thread cursor over events in time order. Never allocates.
+#[derive(Clone, Debug, Default)]
+pub struct Player {
+    events: Vec<Event>,
+    next: usize,
+}
+
+impl Player {
+    /// events for indices outside `params` are skipped when applied
+    pub fn new(mut events: Vec<Event>) -> Self {
+        // stable, so changes stamped on the same frame keep their order
+        events.sort_by_key(|e| e.frame);
+        Self { events, next: 0 }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// the last event's frame, 0 when empty
+    pub fn length(&self) -> u64 {
+        self.events.last().map_or(0, |e| e.frame)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+
+    /// the next `apply` starts with the first event at or after `frame`
+    pub fn seek(&mut self, frame: u64) {
+        self.next = self.events.partition_point(|e| e.frame < frame);
+    }
+
+    /// when the next event is due, for splitting a block in front of it
+    pub fn next_frame(&self) -> Option<u64> {
+        self.events.get(self.next).map(|e| e.frame)
+    }
+
+    /// applies every event due at or before `frame`, true if any was
+    pub fn apply(&mut self, frame: u64, params: &Params) -> bool {
+        let start = self.next;
+        while let Some(event) = self.events.get(self.next) {
+            if event.frame > frame {
+                break;
+            }
+            if event.index < params.len() {
+                params.set(event.index, event.value);
+            }
+            self.next += 1;
+        }
+        self.next > start
+    }
+}
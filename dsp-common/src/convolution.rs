@@ -0,0 +1,384 @@
+//! Convolution reverb over recorded impulse responses.
+//!
+//! Uniformly partitioned overlap-save: the response is cut into `BLOCK`
+//! sized partitions, each transformed once, and every block of input is
+//! transformed and multiplied with all of them against a delay line of past
+//! input spectra. The wet signal comes out a block late, which the
+//! pre-delay takes up.
+
+use realfft::num_complex::Complex;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::sync::Arc;
+
+/// samples per partition, and the wet signal's latency
+pub const BLOCK: usize = 512;
+/// seconds of response kept, past it is cut
+pub const MAX_LENGTH: f32 = 10.0;
+/// seconds of pre-delay at most
+pub const MAX_PRE_DELAY: f32 = 0.5;
+
+/// A recorded space, as read from a file.
+#[derive(Clone, Debug)]
+pub struct Impulse {
+    /// one response per channel, all the same length and never empty
+    channels: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+}
+
+impl Impulse {
+    /// `samples` interleaved in `channels`, cut at `MAX_LENGTH` and scaled
+    /// so noise comes out of the loudest channel at the level it went in,
+    /// `None` without a whole frame
+    pub fn new(samples: &[f32], channels: usize, sample_rate: u32) -> Option<Self> {
+        let channels = channels.max(1);
+        let frames = (samples.len() / channels).min((MAX_LENGTH * sample_rate as f32) as usize);
+        if frames == 0 {
+            return None;
+        }
+        let mut split: Vec<Vec<f32>> = (0..channels)
+            .map(|c| {
+                samples
+                    .iter()
+                    .skip(c)
+                    .step_by(channels)
+                    .take(frames)
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        let energy = split
+            .iter()
+            .map(|channel| channel.iter().map(|s| s * s).sum::<f32>())
+            .fold(0.0, f32::max);
+        if energy > 0.0 {
+            let scale = energy.sqrt().recip();
+            split
+                .iter_mut()
+                .flatten()
+                .for_each(|sample| *sample *= scale);
+        }
+        Some(Self {
+            channels: split,
+            sample_rate,
+        })
+    }
+
+    /// the response for output `channel` at `sample_rate`, linearly resampled
+    fn resampled(&self, channel: usize, sample_rate: u32) -> Vec<f32> {
+        let source = &self.channels[channel % self.channels.len()];
+        if sample_rate == self.sample_rate {
+            return source.clone();
+        }
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let len = (source.len() as f64 / step) as usize;
+        (0..len)
+            .map(|i| {
+                let position = i as f64 * step;
+                let at = position as usize;
+                let next = source.get(at + 1).copied().unwrap_or(0.0);
+                let frac = (position - at as f64) as f32;
+                // keeps the level when the response is stretched or squeezed
+                (source[at] + (next - source[at]) * frac) * step.sqrt() as f32
+            })
+            .collect()
+    }
+}
+
+/// One channel's convolution, a block at a time.
+struct Convolver {
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    /// each partition's spectrum
+    partitions: Vec<Vec<Complex<f32>>>,
+    /// the spectra of the latest blocks of input, `newest` first round
+    history: Vec<Vec<Complex<f32>>>,
+    newest: usize,
+    /// the previous block of input then the current one
+    frame: Vec<f32>,
+    /// `frame` for the transform to work in
+    transform: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    output: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Convolver {
+    fn new(response: &[f32], planner: &mut RealFftPlanner<f32>) -> Self {
+        let forward = planner.plan_fft_forward(BLOCK * 2);
+        let inverse = planner.plan_fft_inverse(BLOCK * 2);
+        let mut scratch =
+            vec![Complex::default(); forward.get_scratch_len().max(inverse.get_scratch_len())];
+        let partitions: Vec<Vec<Complex<f32>>> = response
+            .chunks(BLOCK)
+            .map(|partition| {
+                let mut frame = forward.make_input_vec();
+                frame[..partition.len()].copy_from_slice(partition);
+                let mut spectrum = forward.make_output_vec();
+                let _ = forward.process_with_scratch(&mut frame, &mut spectrum, &mut scratch);
+                spectrum
+            })
+            .collect();
+        Self {
+            history: vec![forward.make_output_vec(); partitions.len().max(1)],
+            newest: 0,
+            partitions,
+            frame: forward.make_input_vec(),
+            transform: forward.make_input_vec(),
+            spectrum: forward.make_output_vec(),
+            output: inverse.make_output_vec(),
+            scratch,
+            forward,
+            inverse,
+        }
+    }
+
+    /// `block` of input in, the same length of output back into it
+    fn process(&mut self, block: &mut [f32]) {
+        self.frame.copy_within(BLOCK.., 0);
+        self.frame[BLOCK..].copy_from_slice(block);
+        self.newest = (self.newest + 1) % self.history.len();
+        self.transform.copy_from_slice(&self.frame);
+        let _ = self.forward.process_with_scratch(
+            &mut self.transform,
+            &mut self.history[self.newest],
+            &mut self.scratch,
+        );
+
+        self.spectrum
+            .iter_mut()
+            .for_each(|bin| *bin = Complex::default());
+        let len = self.history.len();
+        for (age, partition) in self.partitions.iter().enumerate() {
+            let input = &self.history[(self.newest + len - age) % len];
+            for ((out, x), h) in self.spectrum.iter_mut().zip(input).zip(partition) {
+                *out += x * h;
+            }
+        }
+        let _ = self.inverse.process_with_scratch(
+            &mut self.spectrum,
+            &mut self.output,
+            &mut self.scratch,
+        );
+        // the first half wrapped round, the second is the block's output
+        let scale = 1.0 / (BLOCK * 2) as f32;
+        for (out, y) in block.iter_mut().zip(&self.output[BLOCK..]) {
+            *out = y * scale;
+        }
+    }
+}
+
+/// Convolution over interleaved audio, channel `c` through the response's
+/// channel `c` wrapped round its count, so a mono response fills them all.
+/// Nothing allocates while processing, only when the response or the
+/// sample rate changes.
+pub struct Reverb {
+    impulse: Impulse,
+    sample_rate: u32,
+    convolvers: Vec<Convolver>,
+    /// by channel, the input gathered for the next block, then the wet
+    /// output of the last one as it's read out
+    input: Vec<[f32; BLOCK]>,
+    wet: Vec<[f32; BLOCK]>,
+    position: usize,
+    /// by channel, a ring of the input for the pre-delay
+    delay: Vec<Vec<f32>>,
+    delay_write: usize,
+    /// samples, past the block's own latency
+    delay_samples: usize,
+    pre_delay: f32,
+    mix: f32,
+}
+
+impl Reverb {
+    pub fn new(impulse: Impulse, channels: usize, sample_rate: u32) -> Self {
+        let mut reverb = Self {
+            impulse,
+            sample_rate,
+            convolvers: Vec::new(),
+            input: vec![[0.0; BLOCK]; channels],
+            wet: vec![[0.0; BLOCK]; channels],
+            position: 0,
+            delay: vec![vec![0.0; (MAX_PRE_DELAY * sample_rate as f32) as usize + 1]; channels],
+            delay_write: 0,
+            delay_samples: 0,
+            pre_delay: 0.0,
+            mix: 0.0,
+        };
+        reverb.plan();
+        reverb
+    }
+
+    fn plan(&mut self) {
+        let mut planner = RealFftPlanner::new();
+        self.convolvers = (0..self.input.len())
+            .map(|c| Convolver::new(&self.impulse.resampled(c, self.sample_rate), &mut planner))
+            .collect();
+        self.set_pre_delay(self.pre_delay);
+    }
+
+    /// allocates, the response is resampled to the new rate
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate == self.sample_rate {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        let len = (MAX_PRE_DELAY * sample_rate as f32) as usize + 1;
+        self.delay
+            .iter_mut()
+            .for_each(|delay| *delay = vec![0.0; len]);
+        self.delay_write = 0;
+        self.plan();
+    }
+
+    /// seconds before the reverb starts, a block's worth at least
+    pub fn set_pre_delay(&mut self, seconds: f32) {
+        self.pre_delay = seconds.clamp(0.0, MAX_PRE_DELAY);
+        let samples = (self.pre_delay * self.sample_rate as f32) as usize;
+        let len = self.delay.first().map_or(1, Vec::len);
+        self.delay_samples = samples.saturating_sub(BLOCK).min(len - 1);
+    }
+
+    /// 0 is dry, 1 only the reverb
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// mixes the reverb into `buffer` in place, channels past the ones it
+    /// was made with stay dry
+    pub fn process_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        let count = self.input.len().min(channels);
+        if count == 0 {
+            return;
+        }
+        let (dry, wet) = (1.0 - self.mix, self.mix);
+        for frame in buffer.chunks_exact_mut(channels) {
+            let read =
+                (self.delay_write + self.delay[0].len() - self.delay_samples) % self.delay[0].len();
+            for (c, sample) in frame[..count].iter_mut().enumerate() {
+                self.delay[c][self.delay_write] = *sample;
+                self.input[c][self.position] = self.delay[c][read];
+                *sample = *sample * dry + self.wet[c][self.position] * wet;
+            }
+            self.delay_write = (self.delay_write + 1) % self.delay[0].len();
+            self.position += 1;
+            if self.position == BLOCK {
+                self.position = 0;
+                for c in 0..count {
+                    self.wet[c] = self.input[c];
+                    self.convolvers[c].process(&mut self.wet[c]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn noise(rng: &mut Rng, len: usize) -> Vec<f32> {
+        (0..len).map(|_| rng.bipolar()).collect()
+    }
+
+    /// `input` through `response` sample by sample
+    fn direct(input: &[f32], response: &[f32]) -> Vec<f32> {
+        (0..input.len())
+            .map(|n| {
+                response
+                    .iter()
+                    .take(n + 1)
+                    .enumerate()
+                    .map(|(k, h)| h * input[n - k])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// all wet, so a block late and otherwise the convolution
+    fn wet(impulse: Impulse, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut reverb = Reverb::new(impulse, channels, SAMPLE_RATE);
+        reverb.set_mix(1.0);
+        let mut buffer = input.to_vec();
+        // in uneven pieces, as callbacks come
+        for piece in buffer.chunks_mut(channels * 300) {
+            reverb.process_interleaved(piece, channels);
+        }
+        buffer
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < 1e-4, "sample {}: {} against {}", i, a, e);
+        }
+    }
+
+    #[test]
+    fn overlap_save_matches_direct_convolution() {
+        let mut rng = Rng::new(7);
+        // over three partitions, the last one short
+        let impulse = Impulse::new(&noise(&mut rng, BLOCK * 2 + 100), 1, SAMPLE_RATE).unwrap();
+        let input = noise(&mut rng, BLOCK * 8);
+        let expected = direct(&input, &impulse.channels[0]);
+
+        let output = wet(impulse, &input, 1);
+        assert!(output[..BLOCK].iter().all(|&sample| sample == 0.0));
+        assert_close(&output[BLOCK..], &expected[..input.len() - BLOCK]);
+    }
+
+    #[test]
+    fn each_channel_has_its_own_response() {
+        let mut rng = Rng::new(11);
+        let stereo = noise(&mut rng, 2 * 700);
+        let impulse = Impulse::new(&stereo, 2, SAMPLE_RATE).unwrap();
+        let input = noise(&mut rng, 2 * BLOCK * 4);
+        let output = wet(impulse.clone(), &input, 2);
+
+        for c in 0..2 {
+            let channel: Vec<f32> = input.iter().skip(c).step_by(2).copied().collect();
+            let expected = direct(&channel, &impulse.channels[c]);
+            let actual: Vec<f32> = output.iter().skip(c).step_by(2).copied().collect();
+            assert_close(&actual[BLOCK..], &expected[..channel.len() - BLOCK]);
+        }
+    }
+
+    #[test]
+    fn a_mono_response_fills_every_channel() {
+        let mut rng = Rng::new(13);
+        let impulse = Impulse::new(&noise(&mut rng, 300), 1, SAMPLE_RATE).unwrap();
+        let channel = noise(&mut rng, BLOCK * 3);
+        let input: Vec<f32> = channel.iter().flat_map(|&s| vec![s; 3]).collect();
+        let output = wet(impulse, &input, 3);
+        for frame in output.chunks(3) {
+            assert_eq!(frame[0], frame[1]);
+            assert_eq!(frame[0], frame[2]);
+        }
+    }
+
+    #[test]
+    fn the_loudest_channel_is_scaled_to_unit_energy() {
+        let impulse = Impulse::new(&[0.5, 0.25, 0.5, 0.0], 2, SAMPLE_RATE).unwrap();
+        let energy = |c: usize| impulse.channels[c].iter().map(|s| s * s).sum::<f32>();
+        assert!((energy(0) - 1.0).abs() < 1e-6);
+        assert!((energy(1) - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn an_impulse_without_a_frame_is_rejected() {
+        assert!(Impulse::new(&[], 1, SAMPLE_RATE).is_none());
+        assert!(Impulse::new(&[], 0, SAMPLE_RATE).is_none());
+        // half a stereo frame
+        assert!(Impulse::new(&[1.0], 2, SAMPLE_RATE).is_none());
+        assert!(Impulse::new(&[1.0], 0, SAMPLE_RATE).is_some());
+    }
+
+    #[test]
+    fn resampling_stretches_the_response() {
+        let impulse = Impulse::new(&[1.0; 100], 1, SAMPLE_RATE / 2).unwrap();
+        assert_eq!(impulse.resampled(0, SAMPLE_RATE).len(), 200);
+        assert_eq!(impulse.resampled(1, SAMPLE_RATE / 2), impulse.channels[0]);
+    }
+}
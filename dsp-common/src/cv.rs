@@ -0,0 +1,145 @@
+//! Control voltages for DC-coupled outputs.
+//!
+//! DC-coupled interfaces (Expert Sleepers ES-8 and ES-9, some MOTUs...) put
+//! out a fixed voltage at full scale, usually 10V, so a sample is just a
+//! scaled voltage. Pitches follow volt-per-octave around a reference
+//! frequency at 0V.
+
+use crate::param::Smoothed;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CvScale {
+    /// volts at a sample of 1.0
+    pub full_scale: f32,
+    /// the pitch at 0V
+    pub zero_volt_hz: f32,
+}
+
+impl Default for CvScale {
+    /// ±10V, 0V at middle C
+    fn default() -> Self {
+        Self {
+            full_scale: 10.0,
+            zero_volt_hz: 261.63,
+        }
+    }
+}
+
+impl CvScale {
+    /// clipped to the interface's range
+    pub fn sample(&self, volts: f32) -> f32 {
+        (volts / self.full_scale).clamp(-1.0, 1.0)
+    }
+
+    /// volt-per-octave, frequencies at or below 0 clip to the lowest voltage
+    pub fn hz_to_volts(&self, hz: f32) -> f32 {
+        if hz <= 0.0 {
+            return -self.full_scale;
+        }
+        (hz / self.zero_volt_hz).log2()
+    }
+}
+
+/// One control signal, declared by the app in channel order.
+#[derive(Clone, Copy, Debug)]
+pub struct CvSignal {
+    pub name: &'static str,
+    /// seconds to glide to a new voltage, 0 steps, like pitch should
+    pub slew: f32,
+}
+
+impl CvSignal {
+    pub const fn new(name: &'static str, slew: f32) -> Self {
+        Self { name, slew }
+    }
+}
+
+/// Lock-free voltages set from the UI thread for `CvRenderer` to play.
+#[derive(Clone)]
+pub struct CvTargets {
+    signals: &'static [CvSignal],
+    volts: Arc<[AtomicU32]>,
+}
+
+impl CvTargets {
+    pub fn new(signals: &'static [CvSignal]) -> Self {
+        Self {
+            signals,
+            volts: signals
+                .iter()
+                .map(|_| AtomicU32::new(0f32.to_bits()))
+                .collect::<Vec<_>>()
+                .into(),
+        }
+    }
+
+    pub fn signals(&self) -> &'static [CvSignal] {
+        self.signals
+    }
+
+    pub fn len(&self) -> usize {
+        self.signals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty()
+    }
+
+    pub fn set(&self, index: usize, volts: f32) {
+        self.volts[index].store(volts.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self, index: usize) -> f32 {
+        f32::from_bits(self.volts[index].load(Ordering::Relaxed))
+    }
+}
+
+/// Audio thread end, slews every signal towards its target.
+pub struct CvRenderer {
+    targets: CvTargets,
+    scale: CvScale,
+    slews: Vec<Smoothed>,
+    sample_rate: u32,
+}
+
+impl CvRenderer {
+    pub fn new(targets: CvTargets, scale: CvScale) -> Self {
+        Self {
+            slews: targets.signals.iter().map(|_| Smoothed::new(0.0)).collect(),
+            targets,
+            scale,
+            sample_rate: 0,
+        }
+    }
+
+    /// writes the signals to consecutive channels of interleaved `out`
+    /// from `first_channel` on, those past the last channel are dropped
+    pub fn render(
+        &mut self,
+        out: &mut [f32],
+        channels: usize,
+        first_channel: usize,
+        sample_rate: u32,
+    ) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            for (slew, signal) in self.slews.iter_mut().zip(self.targets.signals) {
+                slew.set_time(signal.slew, sample_rate as f32);
+            }
+        }
+        for (i, slew) in self.slews.iter_mut().enumerate() {
+            slew.set_target(self.scale.sample(self.targets.get(i)));
+            let channel = first_channel + i;
+            if channel >= channels {
+                continue;
+            }
+            for frame in out.chunks_exact_mut(channels) {
+                frame[channel] = slew.step();
+            }
+        }
+    }
+}
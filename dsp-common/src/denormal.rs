@@ -0,0 +1,129 @@
+//! Keeping decaying tails out of the denormal range.
+//!
+//! Filter and meter states decay towards zero and, on the way, through
+//! subnormal floats, which most CPUs handle in microcode at a hundred times
+//! the cost. `DenormalGuard` sets flush-to-zero for the thread it runs on,
+//! where the CPU can't, `flush` does the same in software on the recursive
+//! states.
+
+/// true where `DenormalGuard` sets the CPU's flush-to-zero mode
+pub const HARDWARE: bool = mode::FLAGS != 0;
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse"
+))]
+mod mode {
+    pub type Mode = u32;
+
+    /// MXCSR flush-to-zero and denormals-are-zero
+    pub const FLAGS: Mode = 0x8000 | 0x0040;
+
+    pub fn read() -> Mode {
+        let mut mxcsr: u32 = 0;
+        // SAFETY: stores the SSE control register to a local
+        unsafe {
+            std::arch::asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack, preserves_flags));
+        }
+        mxcsr
+    }
+
+    pub fn write(mxcsr: Mode) {
+        // SAFETY: writes back a value read from the register, plus flags
+        unsafe {
+            std::arch::asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, preserves_flags));
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod mode {
+    pub type Mode = u64;
+
+    /// FPCR flush-to-zero
+    pub const FLAGS: Mode = 1 << 24;
+
+    pub fn read() -> Mode {
+        let fpcr: u64;
+        // SAFETY: reads the floating point control register
+        unsafe {
+            std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags));
+        }
+        fpcr
+    }
+
+    pub fn write(fpcr: Mode) {
+        // SAFETY: writes back a value read from the register, plus flags
+        unsafe {
+            std::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack, preserves_flags));
+        }
+    }
+}
+
+/// no control register we know of, `flush` does the work
+#[cfg(not(any(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    ),
+    target_arch = "aarch64"
+)))]
+mod mode {
+    pub type Mode = u32;
+
+    pub const FLAGS: Mode = 0;
+
+    pub fn read() -> Mode {
+        0
+    }
+
+    pub fn write(_: Mode) {}
+}
+
+/// Flush-to-zero on the current thread until dropped, then the previous
+/// mode again. Create one at the top of every audio callback, it's a couple
+/// of register moves.
+pub struct DenormalGuard {
+    /// `None` when the flags were already set
+    previous: Option<mode::Mode>,
+}
+
+impl DenormalGuard {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let previous = mode::read();
+        if previous & mode::FLAGS == mode::FLAGS {
+            return Self { previous: None };
+        }
+        mode::write(previous | mode::FLAGS);
+        Self {
+            previous: Some(previous),
+        }
+    }
+}
+
+impl Drop for DenormalGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous {
+            mode::write(previous);
+        }
+    }
+}
+
+/// `x`, or 0 when subnormal and the CPU can't flush it; for recursive state
+#[inline(always)]
+pub fn flush(x: f32) -> f32 {
+    if !HARDWARE && x.abs() < f32::MIN_POSITIVE {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// `flush` on every sample
+pub fn flush_block(block: &mut [f32]) {
+    if HARDWARE {
+        return;
+    }
+    block.iter_mut().for_each(|sample| *sample = flush(*sample));
+}
@@ -0,0 +1,347 @@
+//! Envelopes, from the grains' one-shot trapezoids to gated DAHDSRs.
+//!
+//! Stage times are in whatever `rate` advances per `step`: seconds when
+//! gated with `1 / sample_rate`, or fractions of a length with `trigger`.
+
+/// below this a curve is a straight line
+const STRAIGHT: f32 = 1e-3;
+
+/// `t` in [0, 1] bent by `curve`, straight at 0, quick to start and slow to
+/// settle above it like an analog envelope, the other way round below
+#[inline(always)]
+pub fn bend(t: f32, curve: f32) -> f32 {
+    if curve.abs() < STRAIGHT {
+        t
+    } else {
+        (1.0 - (-curve * t).exp()) / (1.0 - (-curve).exp())
+    }
+}
+
+/// a falling segment's level with `remaining` of it left
+#[inline(always)]
+fn fall(remaining: f32, curve: f32) -> f32 {
+    if curve.abs() < STRAIGHT {
+        remaining
+    } else {
+        1.0 - bend(1.0 - remaining, curve)
+    }
+}
+
+/// Stage lengths and levels, shared by every kind of envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shape {
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    /// level held while the gate is open, in [0, 1]
+    pub sustain: f32,
+    pub release: f32,
+    /// applied to every segment, see `bend`
+    pub curve: f32,
+    /// releases once the sustain level is reached instead of waiting for
+    /// `gate_off`
+    pub one_shot: bool,
+}
+
+impl Shape {
+    /// rises then falls, without a gate
+    pub const fn ar(attack: f32, release: f32) -> Self {
+        Self::dahdsr(0.0, attack, 0.0, 0.0, 1.0, release).one_shot()
+    }
+
+    pub const fn adsr(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        Self::dahdsr(0.0, attack, 0.0, decay, sustain, release)
+    }
+
+    pub const fn dahdsr(
+        delay: f32,
+        attack: f32,
+        hold: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> Self {
+        Self {
+            delay,
+            attack,
+            hold,
+            decay,
+            sustain,
+            release,
+            curve: 0.0,
+            one_shot: false,
+        }
+    }
+
+    /// A one-shot over a length of 1, for `trigger`: `attack` and `release`
+    /// are fractions of it and the rest is flat.
+    pub fn trapezoid(attack: f32, release: f32) -> Self {
+        let attack = attack.clamp(0.0, 1.0);
+        let release = release.clamp(0.0, 1.0 - attack);
+        Self {
+            hold: 1.0 - attack - release,
+            ..Self::ar(attack, release)
+        }
+    }
+
+    pub const fn curve(mut self, curve: f32) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub const fn one_shot(mut self) -> Self {
+        self.one_shot = true;
+        self
+    }
+
+    fn attack_end(&self) -> f32 {
+        self.delay + self.attack
+    }
+
+    fn hold_end(&self) -> f32 {
+        self.attack_end() + self.hold
+    }
+
+    fn decay_end(&self) -> f32 {
+        self.hold_end() + self.decay
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Idle,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// What a new gate does to an envelope that is still sounding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retrigger {
+    /// from silence through the delay, clicks unless something masks it
+    Restart,
+    /// straight to the attack, from the current level
+    Legato,
+}
+
+/// Steps through a `Shape` one sample at a time.
+///
+/// Positions count from the gate so the edges come out the same whatever
+/// the stages before them, which the golden renders rely on.
+#[derive(Clone, Copy, Debug)]
+pub struct Envelope {
+    shape: Shape,
+    retrigger: Retrigger,
+    stage: Stage,
+    /// since the gate, in stage units
+    position: f32,
+    rate: f32,
+    attack_from: f32,
+    release_from: f32,
+    release_start: f32,
+}
+
+impl Envelope {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            retrigger: Retrigger::Restart,
+            stage: Stage::Idle,
+            position: 0.0,
+            rate: 0.0,
+            attack_from: 0.0,
+            release_from: 0.0,
+            release_start: 0.0,
+        }
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    /// for the next gate, changing it on the way moves the stage edges
+    pub fn set_shape(&mut self, shape: Shape) {
+        self.shape = shape;
+    }
+
+    pub fn set_retrigger(&mut self, retrigger: Retrigger) {
+        self.retrigger = retrigger;
+    }
+
+    /// opens the gate, advancing `rate` stage units per `step`
+    pub fn gate_on(&mut self, rate: f32) {
+        let level = self.value();
+        self.rate = rate;
+        match self.retrigger {
+            Retrigger::Legato if self.is_active() => {
+                self.attack_from = level;
+                self.position = self.shape.delay;
+            }
+            _ => {
+                self.attack_from = 0.0;
+                self.position = 0.0;
+            }
+        }
+        // anything but a release, so the stage follows the position
+        self.stage = Stage::Delay;
+        self.stage = self.stage_at(self.position);
+    }
+
+    /// releases from wherever the envelope is
+    pub fn gate_off(&mut self) {
+        if !self.is_active() || self.stage == Stage::Release {
+            return;
+        }
+        self.release_from = self.value();
+        self.release_start = self.position;
+        self.stage = Stage::Release;
+    }
+
+    /// a one-shot lasting `length` calls to `step()`
+    pub fn trigger(&mut self, length: f32) {
+        self.gate_on(1.0 / length);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Idle
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// the gain `step` returns next
+    #[inline(always)]
+    pub fn value(&self) -> f32 {
+        let shape = &self.shape;
+        let position = self.position;
+        match self.stage {
+            Stage::Idle => 0.0,
+            Stage::Delay => self.attack_from,
+            Stage::Attack => {
+                let t = (position - shape.delay) / shape.attack;
+                self.attack_from + (1.0 - self.attack_from) * bend(t, shape.curve)
+            }
+            Stage::Hold => 1.0,
+            Stage::Decay => {
+                let remaining = (shape.decay_end() - position) / shape.decay;
+                shape.sustain + (1.0 - shape.sustain) * fall(remaining, shape.curve)
+            }
+            Stage::Sustain => shape.sustain,
+            Stage::Release => {
+                let end = self.release_start + shape.release;
+                self.release_from * fall((end - position) / shape.release, shape.curve)
+            }
+        }
+    }
+
+    /// called at sample rate
+    #[inline(always)]
+    pub fn step(&mut self) -> f32 {
+        let value = self.value();
+        if self.stage != Stage::Idle && self.stage != Stage::Sustain {
+            self.position += self.rate;
+            self.stage = self.stage_at(self.position);
+        }
+        value
+    }
+
+    fn stage_at(&mut self, position: f32) -> Stage {
+        let shape = self.shape;
+        if self.stage != Stage::Release {
+            if position < shape.delay {
+                return Stage::Delay;
+            } else if position < shape.attack_end() {
+                return Stage::Attack;
+            } else if position < shape.hold_end() {
+                return Stage::Hold;
+            } else if position < shape.decay_end() {
+                return Stage::Decay;
+            } else if !shape.one_shot {
+                return Stage::Sustain;
+            }
+            self.release_from = shape.sustain;
+            self.release_start = shape.decay_end();
+        }
+        if position < self.release_start + shape.release {
+            Stage::Release
+        } else {
+            Stage::Idle
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// one stage unit per step keeps the positions exact
+    fn steps(env: &mut Envelope, n: usize) -> Vec<f32> {
+        (0..n).map(|_| env.step()).collect()
+    }
+
+    #[test]
+    fn adsr_goes_through_its_stages() {
+        let mut env = Envelope::new(Shape::adsr(10.0, 10.0, 0.5, 10.0));
+        env.gate_on(1.0);
+        let attack = steps(&mut env, 10);
+        assert_eq!(attack[0], 0.0);
+        assert!(attack.windows(2).all(|pair| pair[1] > pair[0]));
+        assert_eq!(env.value(), 1.0);
+
+        steps(&mut env, 10);
+        assert_eq!(env.stage(), Stage::Sustain);
+        assert!(steps(&mut env, 100).iter().all(|value| *value == 0.5));
+
+        env.gate_off();
+        let release = steps(&mut env, 10);
+        assert_eq!(release[0], 0.5);
+        assert!(release.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(!env.is_active());
+        assert_eq!(env.value(), 0.0);
+    }
+
+    #[test]
+    fn legato_attacks_from_the_current_level() {
+        let mut env = Envelope::new(Shape::adsr(10.0, 10.0, 0.5, 10.0));
+        env.gate_on(1.0);
+        steps(&mut env, 5);
+        env.set_retrigger(Retrigger::Legato);
+        env.gate_on(1.0);
+        assert_eq!(env.value(), 0.5);
+        assert_eq!(env.stage(), Stage::Attack);
+
+        env.set_retrigger(Retrigger::Restart);
+        env.gate_on(1.0);
+        assert_eq!(env.value(), 0.0);
+    }
+
+    #[test]
+    fn a_trigger_lasts_its_length() {
+        let mut env = Envelope::new(Shape::trapezoid(0.25, 0.25));
+        env.trigger(100.0);
+        let mut length = 0;
+        let mut peak = 0.0f32;
+        while env.is_active() {
+            peak = peak.max(env.step());
+            length += 1;
+            assert!(length <= 101);
+        }
+        assert!(length >= 100);
+        assert_eq!(peak, 1.0);
+    }
+
+    #[test]
+    fn bends_keep_their_ends() {
+        for &curve in &[-4.0, 0.0, 4.0] {
+            assert!(bend(0.0, curve).abs() < 1e-6);
+            assert!((bend(1.0, curve) - 1.0).abs() < 1e-6);
+        }
+        assert!(bend(0.5, 4.0) > 0.5);
+        assert!(bend(0.5, -4.0) < 0.5);
+    }
+}
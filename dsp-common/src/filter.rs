@@ -0,0 +1,383 @@
+//! Biquads and a state variable filter with smoothed cutoff and Q.
+//!
+//! Cutoff and Q glide through `Smoothed`, coefficients follow them every
+//! `UPDATE_INTERVAL` samples while they move so sweeps stay cheap and
+//! zipper free.
+
+use crate::denormal::flush;
+use crate::param::Smoothed;
+use std::f32::consts::PI;
+
+/// samples between coefficient updates while cutoff or Q glides
+const UPDATE_INTERVAL: usize = 16;
+pub const DEFAULT_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Lowpass,
+    Highpass,
+    /// constant 0 dB peak gain
+    Bandpass,
+    Notch,
+}
+
+/// Normalized biquad coefficients, `a0` divided out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coeffs {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Coeffs {
+    /// passes everything through
+    pub const IDENTITY: Coeffs = Coeffs {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// RBJ cookbook, `cutoff` is kept below Nyquist
+    pub fn new(response: Response, cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q.max(0.01));
+
+        let (b0, b1, b2) = match response {
+            Response::Lowpass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            Response::Highpass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0),
+            Response::Bandpass => (alpha, 0.0, -alpha),
+            Response::Notch => (1.0, -2.0 * cos, 1.0),
+        };
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+}
+
+/// Cutoff and Q on their way to new targets.
+#[derive(Clone, Copy, Debug)]
+struct Tuning {
+    cutoff: Smoothed,
+    q: Smoothed,
+    countdown: usize,
+}
+
+impl Tuning {
+    fn new(cutoff: f32, q: f32) -> Self {
+        Self {
+            cutoff: Smoothed::new(cutoff),
+            q: Smoothed::new(q),
+            countdown: 0,
+        }
+    }
+
+    fn set_time(&mut self, seconds: f32, sample_rate: f32) {
+        // stepped once per update, not per sample
+        let rate = sample_rate / UPDATE_INTERVAL as f32;
+        self.cutoff.set_time(seconds, rate);
+        self.q.set_time(seconds, rate);
+    }
+
+    fn settled(&self) -> bool {
+        let close = |s: &Smoothed| (s.value() - s.target()).abs() <= s.target().abs() * 1e-4;
+        close(&self.cutoff) && close(&self.q)
+    }
+
+    /// `Some((cutoff, q))` when the coefficients are due an update
+    #[inline(always)]
+    fn tick(&mut self) -> Option<(f32, f32)> {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return None;
+        }
+        if self.settled() {
+            return None;
+        }
+        self.countdown = UPDATE_INTERVAL - 1;
+        Some((self.cutoff.step(), self.q.step()))
+    }
+}
+
+/// Mono biquad, transposed direct form II.
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    response: Response,
+    sample_rate: f32,
+    tuning: Tuning,
+    coeffs: Coeffs,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn new(response: Response, cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        Self {
+            response,
+            sample_rate,
+            tuning: Tuning::new(cutoff, q),
+            coeffs: Coeffs::new(response, cutoff, q, sample_rate),
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn lowpass(cutoff: f32, sample_rate: f32) -> Self {
+        Self::new(Response::Lowpass, cutoff, DEFAULT_Q, sample_rate)
+    }
+
+    pub fn highpass(cutoff: f32, sample_rate: f32) -> Self {
+        Self::new(Response::Highpass, cutoff, DEFAULT_Q, sample_rate)
+    }
+
+    /// seconds for cutoff and Q to cover ~63% of a change, 0 jumps
+    pub fn set_smoothing(&mut self, seconds: f32) {
+        self.tuning.set_time(seconds, self.sample_rate);
+    }
+
+    pub fn set_cutoff(&mut self, hz: f32) {
+        self.tuning.cutoff.set_target(hz);
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.tuning.q.set_target(q);
+    }
+
+    pub fn set_response(&mut self, response: Response) {
+        self.response = response;
+        self.retune(self.tuning.cutoff.value(), self.tuning.q.value());
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.tuning.cutoff.value()
+    }
+
+    pub fn coeffs(&self) -> Coeffs {
+        self.coeffs
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    fn retune(&mut self, cutoff: f32, q: f32) {
+        self.coeffs = Coeffs::new(self.response, cutoff, q, self.sample_rate);
+    }
+
+    /// called at sample rate
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        if let Some((cutoff, q)) = self.tuning.tick() {
+            self.retune(cutoff, q);
+        }
+        let c = &self.coeffs;
+        let y = c.b0 * x + self.z1;
+        self.z1 = flush(c.b1 * x - c.a1 * y + self.z2);
+        self.z2 = flush(c.b2 * x - c.a2 * y);
+        y
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// filters one channel of an interleaved buffer
+    pub fn process_channel(&mut self, buffer: &mut [f32], channel: usize, channels: usize) {
+        for frame in buffer.chunks_exact_mut(channels) {
+            frame[channel] = self.process(frame[channel]);
+        }
+    }
+}
+
+/// Every output of the state variable filter for one sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SvfOutputs {
+    pub low: f32,
+    /// peaks at Q, unlike the biquad bandpass
+    pub band: f32,
+    pub high: f32,
+    pub notch: f32,
+}
+
+impl SvfOutputs {
+    pub fn get(&self, response: Response) -> f32 {
+        match response {
+            Response::Lowpass => self.low,
+            Response::Highpass => self.high,
+            Response::Bandpass => self.band,
+            Response::Notch => self.notch,
+        }
+    }
+}
+
+/// Trapezoidal state variable filter, stable under fast modulation.
+#[derive(Clone, Copy, Debug)]
+pub struct Svf {
+    sample_rate: f32,
+    tuning: Tuning,
+    g: f32,
+    k: f32,
+    ic1: f32,
+    ic2: f32,
+}
+
+impl Svf {
+    pub fn new(cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let mut svf = Self {
+            sample_rate,
+            tuning: Tuning::new(cutoff, q),
+            g: 0.0,
+            k: 0.0,
+            ic1: 0.0,
+            ic2: 0.0,
+        };
+        svf.retune(cutoff, q);
+        svf
+    }
+
+    /// seconds for cutoff and Q to cover ~63% of a change, 0 jumps
+    pub fn set_smoothing(&mut self, seconds: f32) {
+        self.tuning.set_time(seconds, self.sample_rate);
+    }
+
+    pub fn set_cutoff(&mut self, hz: f32) {
+        self.tuning.cutoff.set_target(hz);
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.tuning.q.set_target(q);
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.tuning.cutoff.value()
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1 = 0.0;
+        self.ic2 = 0.0;
+    }
+
+    fn retune(&mut self, cutoff: f32, q: f32) {
+        let cutoff = cutoff.clamp(1.0, self.sample_rate * 0.49);
+        self.g = (PI * cutoff / self.sample_rate).tan();
+        self.k = 1.0 / q.max(0.01);
+    }
+
+    /// called at sample rate
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> SvfOutputs {
+        if let Some((cutoff, q)) = self.tuning.tick() {
+            self.retune(cutoff, q);
+        }
+        let (g, k) = (self.g, self.k);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = x - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = flush(2.0 * v1 - self.ic1);
+        self.ic2 = flush(2.0 * v2 - self.ic2);
+
+        let high = x - k * v1 - v2;
+        SvfOutputs {
+            low: v2,
+            band: v1,
+            high,
+            notch: v2 + high,
+        }
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32], response: Response) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample).get(response);
+        }
+    }
+
+    /// filters one channel of an interleaved buffer
+    pub fn process_channel(
+        &mut self,
+        buffer: &mut [f32],
+        channel: usize,
+        channels: usize,
+        response: Response,
+    ) {
+        for frame in buffer.chunks_exact_mut(channels) {
+            frame[channel] = self.process(frame[channel]).get(response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter::to_db;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const CUTOFF: f32 = 1000.0;
+
+    /// gain in dB at `freq`, measured on a second of sine once it's settled
+    fn gain(mut filter: impl FnMut(f32) -> f32, freq: f32) -> f32 {
+        let frames = SAMPLE_RATE as usize;
+        let (mut input, mut output) = (0.0, 0.0);
+        for i in 0..frames {
+            let x = (2.0 * PI * freq * i as f32 / SAMPLE_RATE).sin();
+            let y = filter(x);
+            if i >= frames / 2 {
+                input += x * x;
+                output += y * y;
+            }
+        }
+        to_db((output / input).sqrt())
+    }
+
+    #[test]
+    fn biquads_are_3_db_down_at_cutoff() {
+        let mut lowpass = Biquad::lowpass(CUTOFF, SAMPLE_RATE);
+        assert!((gain(|x| lowpass.process(x), CUTOFF) + 3.01).abs() < 0.1);
+        let mut lowpass = Biquad::lowpass(CUTOFF, SAMPLE_RATE);
+        assert!(gain(|x| lowpass.process(x), CUTOFF / 10.0).abs() < 0.1);
+        let mut lowpass = Biquad::lowpass(CUTOFF, SAMPLE_RATE);
+        assert!(gain(|x| lowpass.process(x), CUTOFF * 10.0) < -35.0);
+
+        let mut highpass = Biquad::highpass(CUTOFF, SAMPLE_RATE);
+        assert!((gain(|x| highpass.process(x), CUTOFF) + 3.01).abs() < 0.1);
+        let mut highpass = Biquad::highpass(CUTOFF, SAMPLE_RATE);
+        assert!(gain(|x| highpass.process(x), CUTOFF * 10.0).abs() < 0.1);
+        let mut highpass = Biquad::highpass(CUTOFF, SAMPLE_RATE);
+        assert!(gain(|x| highpass.process(x), CUTOFF / 10.0) < -35.0);
+    }
+
+    #[test]
+    fn svf_matches_the_biquads_at_cutoff() {
+        for &response in &[Response::Lowpass, Response::Highpass] {
+            let mut svf = Svf::new(CUTOFF, DEFAULT_Q, SAMPLE_RATE);
+            let svf = gain(|x| svf.process(x).get(response), CUTOFF);
+            let mut biquad = Biquad::new(response, CUTOFF, DEFAULT_Q, SAMPLE_RATE);
+            let biquad = gain(|x| biquad.process(x), CUTOFF);
+            assert!((svf - biquad).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn notches_cut_the_cutoff() {
+        let mut svf = Svf::new(CUTOFF, DEFAULT_Q, SAMPLE_RATE);
+        assert!(gain(|x| svf.process(x).notch, CUTOFF) < -40.0);
+        let mut biquad = Biquad::new(Response::Notch, CUTOFF, DEFAULT_Q, SAMPLE_RATE);
+        assert!(gain(|x| biquad.process(x), CUTOFF) < -40.0);
+    }
+}
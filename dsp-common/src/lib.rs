@@ -0,0 +1,25 @@
+pub mod allocation;
+pub mod ambisonics;
+pub mod analysis;
+pub mod automation;
+pub mod convolution;
+pub mod cv;
+pub mod denormal;
+pub mod env;
+pub mod filter;
+pub mod limiter;
+pub mod loudness;
+pub mod meter;
+pub mod noise;
+pub mod pan;
+pub mod param;
+pub mod pitch;
+pub mod pool;
+pub mod random;
+pub mod simd;
+pub mod spectrum;
+pub mod spherical_head;
+pub mod table;
+pub mod tuning;
+
+pub use table::{filut, filut_clamped, lerp, Wavetable};
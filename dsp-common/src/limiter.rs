@@ -0,0 +1,168 @@
+use crate::meter::{from_db, to_db};
+
+const THRESHOLD_DB: f32 = -1.0;
+const KNEE_DB: f32 = 6.0;
+const CEILING_DB: f32 = -0.3;
+const ATTACK: f32 = 0.001;
+const RELEASE: f32 = 0.1;
+/// the clipper is linear up to this fraction of the ceiling
+const CLIP_KNEE: f32 = 0.8;
+
+/// Smoothly saturates above `CLIP_KNEE * ceiling`, never exceeding `ceiling`.
+#[inline(always)]
+pub fn soft_clip(sample: f32, ceiling: f32) -> f32 {
+    let knee = CLIP_KNEE * ceiling;
+    let abs = sample.abs();
+    if abs <= knee {
+        return sample;
+    }
+    let room = ceiling - knee;
+    (knee + room * ((abs - knee) / room).tanh()).copysign(sample)
+}
+
+/// gain reduction in dB for a level in dB, infinite ratio with a quadratic knee
+#[inline(always)]
+fn reduction_db(level: f32, threshold: f32, knee: f32) -> f32 {
+    let over = level - threshold;
+    if 2.0 * over < -knee {
+        0.0
+    } else if 2.0 * over.abs() <= knee {
+        -(over + knee / 2.0).powi(2) / (2.0 * knee)
+    } else {
+        -over
+    }
+}
+
+/// Soft-knee peak limiter followed by a soft clipper, channels linked.
+///
+/// Without lookahead the attack lets the first few samples of a transient
+/// through, the clipper keeps those under the ceiling.
+#[derive(Clone, Debug)]
+pub struct Limiter {
+    threshold: f32,
+    knee: f32,
+    ceiling: f32,
+    attack: f32,
+    release: f32,
+    gain: f32,
+    bypass: bool,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            threshold: THRESHOLD_DB,
+            knee: KNEE_DB,
+            ceiling: from_db(CEILING_DB),
+            attack: (-1.0 / (ATTACK * sample_rate)).exp(),
+            release: (-1.0 / (RELEASE * sample_rate)).exp(),
+            gain: 1.0,
+            bypass: false,
+        }
+    }
+
+    /// for a stream that came back at another rate, keeps the current gain
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.attack = (-1.0 / (ATTACK * sample_rate)).exp();
+        self.release = (-1.0 / (RELEASE * sample_rate)).exp();
+    }
+
+    pub fn set_threshold(&mut self, db: f32) {
+        self.threshold = db;
+    }
+
+    /// absolute maximum output level
+    pub fn set_ceiling(&mut self, db: f32) {
+        self.ceiling = from_db(db);
+    }
+
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.bypass = bypass;
+        if bypass {
+            self.gain = 1.0;
+        }
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.bypass
+    }
+
+    /// current gain reduction, 0 or negative
+    pub fn reduction(&self) -> f32 {
+        to_db(self.gain)
+    }
+
+    /// limits one frame in place
+    #[inline]
+    pub fn process_frame(&mut self, frame: &mut [f32]) {
+        let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let target = from_db(reduction_db(to_db(peak), self.threshold, self.knee));
+        let coeff = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain = target + coeff * (self.gain - target);
+
+        for sample in frame.iter_mut() {
+            *sample = soft_clip(*sample * self.gain, self.ceiling);
+        }
+    }
+
+    pub fn process_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        if self.bypass {
+            return;
+        }
+        for frame in buffer.chunks_exact_mut(channels) {
+            self.process_frame(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine(db: f32, frames: usize) -> Vec<f32> {
+        let amplitude = from_db(db);
+        (0..frames)
+            .flat_map(|i| {
+                let sample = amplitude * (i as f32 * 0.05).sin();
+                vec![sample, -sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_hot_signal_stays_under_the_ceiling() {
+        for &ceiling in &[CEILING_DB, -6.0] {
+            let mut limiter = Limiter::new(SAMPLE_RATE);
+            limiter.set_ceiling(ceiling);
+            let mut buffer = sine(12.0, 48000);
+            limiter.process_interleaved(&mut buffer, 2);
+            assert!(buffer.iter().all(|sample| sample.abs() <= from_db(ceiling)));
+            assert!(limiter.reduction() < -10.0);
+        }
+    }
+
+    #[test]
+    fn a_quiet_signal_passes_unchanged() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let mut buffer = sine(-12.0, 48000);
+        let input = buffer.clone();
+        limiter.process_interleaved(&mut buffer, 2);
+        assert_eq!(buffer, input);
+        assert_eq!(limiter.reduction(), 0.0);
+    }
+
+    #[test]
+    fn the_clipper_is_linear_below_its_knee() {
+        assert_eq!(soft_clip(0.5, 1.0), 0.5);
+        assert_eq!(soft_clip(-0.5, 1.0), -0.5);
+        assert!(soft_clip(100.0, 1.0) <= 1.0);
+        assert!(soft_clip(-100.0, 1.0) >= -1.0);
+        assert!(soft_clip(0.9, 1.0) < 0.9 && soft_clip(0.9, 1.0) > 0.8);
+    }
+}
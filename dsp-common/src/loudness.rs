@@ -0,0 +1,249 @@
+//! Integrated loudness and true peak after ITU-R BS.1770 / EBU R128, for
+//! measuring whole renders rather than metering live.
+//!
+//! Each channel is K-weighted and its mean square summed over 100ms
+//! steps. Loudness is read over 400ms blocks overlapping by 75%, gated
+//! absolutely at -70 LUFS and then 10 LU below the loudness of what's left.
+//!
+//! Channels are weighted by where their speaker is, as in the standard:
+//! surrounds 60 to 120 degrees off centre count 1.41 times, the LFE not at
+//! all and the rest once. `Loudness::new` reads six channels as 5.1 in the
+//! SMPTE order and any other count as speakers that all count once.
+
+use crate::meter::Meter;
+use crate::pan::Layout;
+use std::f64::consts::PI;
+
+/// seconds between gating blocks
+const STEP: f64 = 0.1;
+/// steps per gating block
+const BLOCK_STEPS: usize = 4;
+/// LUFS below which blocks are left out as silence
+const ABSOLUTE_GATE: f64 = -70.0;
+/// LU below the absolutely gated loudness blocks are left out at
+const RELATIVE_GATE: f64 = -10.0;
+
+/// LUFS of a mean square summed over channels
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(powers: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = powers.fold((0.0, 0), |(sum, count), power| (sum + power, count + 1));
+    sum / count.max(1) as f64
+}
+
+/// A biquad in double precision, the weighting's low shelf sits far below
+/// where single precision stays accurate at high rates.
+#[derive(Clone, Copy, Debug)]
+struct Stage {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Stage {
+    /// the high shelf modelling the head, +4dB above about 1.7kHz
+    fn shelf(sample_rate: f64) -> Self {
+        let (f0, gain, q) = (
+            1_681.974_450_955_533,
+            3.999_843_853_973_347,
+            0.707_175_236_955_419_6,
+        );
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        Self {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    /// the revised low frequency B curve, a highpass at about 38Hz
+    fn highpass(sample_rate: f64) -> Self {
+        let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Self {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// how much a speaker at `angle` counts, `None` for an LFE
+fn weight(angle: Option<f32>) -> f64 {
+    match angle {
+        Some(angle) if (60.0..=120.0).contains(&angle.abs()) => 1.41,
+        Some(_) => 1.0,
+        None => 0.0,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Channel {
+    weight: f64,
+    shelf: Stage,
+    highpass: Stage,
+    /// for the true peak
+    meter: Meter,
+}
+
+/// Integrated loudness and the highest true peak of everything it's fed.
+#[derive(Clone, Debug)]
+pub struct Loudness {
+    channels: Vec<Channel>,
+    /// frames per step
+    step: usize,
+    /// the weighted squares so far this step, summed over channels
+    sum: f64,
+    frames: usize,
+    /// each whole step's mean square
+    steps: Vec<f64>,
+    true_peak: f32,
+}
+
+impl Loudness {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        if channels == Layout::SURROUND.channels() {
+            return Self::with_layout(&Layout::SURROUND, sample_rate);
+        }
+        Self::with_weights(&vec![1.0; channels.max(1)], sample_rate)
+    }
+
+    /// a channel for each of `layout`'s, weighted by its speaker's angle
+    pub fn with_layout(layout: &Layout, sample_rate: u32) -> Self {
+        let weights: Vec<f64> = (0..layout.channels())
+            .map(|channel| weight(layout.angle(channel)))
+            .collect();
+        Self::with_weights(&weights, sample_rate)
+    }
+
+    fn with_weights(weights: &[f64], sample_rate: u32) -> Self {
+        let rate = f64::from(sample_rate);
+        let channel = |&weight| Channel {
+            weight,
+            shelf: Stage::shelf(rate),
+            highpass: Stage::highpass(rate),
+            meter: Meter::new(sample_rate as f32),
+        };
+        Self {
+            channels: weights.iter().map(channel).collect(),
+            step: ((STEP * rate).round() as usize).max(1),
+            sum: 0.0,
+            frames: 0,
+            steps: Vec::new(),
+            true_peak: 0.0,
+        }
+    }
+
+    /// `samples` interleaved in as many channels as it was made with
+    pub fn process_interleaved(&mut self, samples: &[f32]) {
+        let count = self.channels.len();
+        for frame in samples.chunks_exact(count) {
+            for (channel, &sample) in self.channels.iter_mut().zip(frame) {
+                let weighted = channel
+                    .highpass
+                    .process(channel.shelf.process(f64::from(sample)));
+                self.sum += channel.weight * weighted * weighted;
+                channel.meter.process(sample);
+                // the meter's peak falls back, the highest is kept here
+                self.true_peak = self.true_peak.max(channel.meter.reading().true_peak);
+            }
+            self.frames += 1;
+            if self.frames == self.step {
+                self.steps.push(self.sum / self.step as f64);
+                self.sum = 0.0;
+                self.frames = 0;
+            }
+        }
+    }
+
+    /// LUFS over everything so far, `None` for less than a block or
+    /// nothing above the absolute gate
+    pub fn integrated(&self) -> Option<f32> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(BLOCK_STEPS)
+            .map(|block| block.iter().sum::<f64>() / BLOCK_STEPS as f64)
+            .filter(|&power| lufs(power) > ABSOLUTE_GATE)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+        let gate = lufs(mean(blocks.iter().copied())) + RELATIVE_GATE;
+        let power = mean(blocks.iter().copied().filter(|&power| lufs(power) > gate));
+        Some(lufs(power) as f32)
+    }
+
+    /// the highest 4x oversampled peak of any channel, linear
+    pub fn true_peak(&self) -> f32 {
+        self.true_peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter::from_db;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// `seconds` of a 997Hz sine peaking at `db` on `channels` of `count`
+    fn sine(db: f32, seconds: usize, channels: &[usize], count: usize) -> Vec<f32> {
+        let amplitude = from_db(db);
+        let frames = seconds * SAMPLE_RATE as usize;
+        let mut samples = vec![0.0; frames * count];
+        for (i, frame) in samples.chunks_exact_mut(count).enumerate() {
+            let t = i as f64 / f64::from(SAMPLE_RATE);
+            let sample = amplitude * (2.0 * PI * 997.0 * t).sin() as f32;
+            for &channel in channels {
+                frame[channel] = sample;
+            }
+        }
+        samples
+    }
+
+    fn integrated(loudness: &mut Loudness, samples: &[f32]) -> Option<f32> {
+        loudness.process_interleaved(samples);
+        loudness.integrated()
+    }
+
+    #[test]
+    fn a_stereo_sine_at_minus_23_dbfs_is_minus_23_lufs() {
+        let mut loudness = Loudness::new(2, SAMPLE_RATE);
+        let lufs = integrated(&mut loudness, &sine(-23.0, 5, &[0, 1], 2)).unwrap();
+        assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+    }
+
+    #[test]
+    fn silence_is_gated_out() {
+        let mut loudness = Loudness::new(2, SAMPLE_RATE);
+        assert_eq!(integrated(&mut loudness, &[0.0; 96000]), None);
+    }
+
+    #[test]
+    fn surrounds_count_more_and_the_lfe_not_at_all() {
+        let front = integrated(&mut Loudness::new(6, SAMPLE_RATE), &sine(-23.0, 5, &[0], 6));
+        let surround = integrated(&mut Loudness::new(6, SAMPLE_RATE), &sine(-23.0, 5, &[4], 6));
+        let difference = surround.unwrap() - front.unwrap();
+        assert!((difference - 10.0 * 1.41f32.log10()).abs() < 0.01);
+
+        let lfe = integrated(&mut Loudness::new(6, SAMPLE_RATE), &sine(-23.0, 5, &[3], 6));
+        assert_eq!(lfe, None);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::denormal::flush;
+use std::f32::consts::PI;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+const RMS_TIME: f32 = 0.3;
+/// dB per second the peak falls back at
+const PEAK_FALL: f32 = 20.0 / 1.7;
+const OVERSAMPLING: usize = 4;
+const TAPS: usize = 12;
+
+pub fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-6).log10()
+}
+
+pub fn from_db(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// linear levels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Reading {
+    pub rms: f32,
+    pub peak: f32,
+    pub true_peak: f32,
+}
+
+/// Single channel RMS, sample peak and 4x oversampled true peak
+/// with exponential RMS integration and a falling peak.
+#[derive(Clone, Debug)]
+pub struct Meter {
+    rms_coeff: f32,
+    peak_release: f32,
+    mean_square: f32,
+    peak: f32,
+    true_peak: f32,
+    history: [f32; TAPS],
+    cursor: usize,
+    kernel: [[f32; TAPS]; OVERSAMPLING],
+}
+
+impl Meter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            rms_coeff: (-1.0 / (RMS_TIME * sample_rate)).exp(),
+            peak_release: 10.0f32.powf(-PEAK_FALL / 20.0 / sample_rate),
+            mean_square: 0.0,
+            peak: 0.0,
+            true_peak: 0.0,
+            history: [0.0; TAPS],
+            cursor: 0,
+            kernel: interpolation_kernel(),
+        }
+    }
+
+    /// keeps the current reading
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.rms_coeff = (-1.0 / (RMS_TIME * sample_rate)).exp();
+        self.peak_release = 10.0f32.powf(-PEAK_FALL / 20.0 / sample_rate);
+    }
+
+    /// called at sample rate
+    #[inline]
+    pub fn process(&mut self, sample: f32) {
+        self.mean_square =
+            flush(sample * sample + self.rms_coeff * (self.mean_square - sample * sample));
+
+        let abs = sample.abs();
+        self.peak = if abs > self.peak {
+            abs
+        } else {
+            self.peak * self.peak_release
+        };
+
+        self.history[self.cursor] = sample;
+        self.cursor = (self.cursor + 1) % TAPS;
+
+        let mut inter_peak = 0.0f32;
+        for phase in self.kernel.iter() {
+            let mut acc = 0.0;
+            for (k, tap) in phase.iter().enumerate() {
+                acc += tap * self.history[(self.cursor + TAPS - 1 - k) % TAPS];
+            }
+            inter_peak = inter_peak.max(acc.abs());
+        }
+        self.true_peak = if inter_peak > self.true_peak {
+            inter_peak
+        } else {
+            self.true_peak * self.peak_release
+        };
+    }
+
+    pub fn process_block(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.process(sample);
+        }
+    }
+
+    pub fn reading(&self) -> Reading {
+        Reading {
+            rms: self.mean_square.sqrt(),
+            peak: self.peak,
+            true_peak: self.true_peak,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.mean_square = 0.0;
+        self.peak = 0.0;
+        self.true_peak = 0.0;
+        self.history = [0.0; TAPS];
+    }
+}
+
+/// Hann windowed sinc, one row per fractional position between samples
+fn interpolation_kernel() -> [[f32; TAPS]; OVERSAMPLING] {
+    let half = TAPS as f32 / 2.0;
+    let mut kernel = [[0.0; TAPS]; OVERSAMPLING];
+    for (phase, row) in kernel.iter_mut().enumerate() {
+        let fraction = phase as f32 / OVERSAMPLING as f32;
+        for (k, tap) in row.iter_mut().enumerate() {
+            let x = k as f32 - half + fraction;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let window = 0.5 * (1.0 + (PI * x / half).cos());
+            *tap = sinc * window;
+        }
+    }
+    kernel
+}
+
+#[derive(Clone, Debug)]
+pub struct StereoMeter {
+    pub left: Meter,
+    pub right: Meter,
+}
+
+impl StereoMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            left: Meter::new(sample_rate),
+            right: Meter::new(sample_rate),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.left.set_sample_rate(sample_rate);
+        self.right.set_sample_rate(sample_rate);
+    }
+
+    /// meters the first two channels of an interleaved buffer
+    pub fn process_interleaved(&mut self, samples: &[f32], channels: usize) {
+        for frame in samples.chunks(channels) {
+            self.left.process(frame[0]);
+            self.right.process(*frame.get(1).unwrap_or(&frame[0]));
+        }
+    }
+
+    pub fn readings(&self) -> [Reading; 2] {
+        [self.left.reading(), self.right.reading()]
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    rms: AtomicU32,
+    peak: AtomicU32,
+    true_peak: AtomicU32,
+}
+
+/// Audio thread side of a wait-free, latest-value-wins reading slot.
+pub struct MeterWriter {
+    slots: Arc<Vec<Slot>>,
+}
+
+/// UI side of the reading slot.
+#[derive(Clone)]
+pub struct MeterReader {
+    slots: Arc<Vec<Slot>>,
+}
+
+pub fn channel(channels: usize) -> (MeterWriter, MeterReader) {
+    let slots = Arc::new((0..channels).map(|_| Slot::default()).collect::<Vec<_>>());
+    (
+        MeterWriter {
+            slots: slots.clone(),
+        },
+        MeterReader { slots },
+    )
+}
+
+impl MeterWriter {
+    pub fn write(&self, channel: usize, reading: Reading) {
+        if let Some(slot) = self.slots.get(channel) {
+            slot.rms.store(reading.rms.to_bits(), Ordering::Relaxed);
+            slot.peak.store(reading.peak.to_bits(), Ordering::Relaxed);
+            slot.true_peak
+                .store(reading.true_peak.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn write_all(&self, readings: &[Reading]) {
+        for (channel, reading) in readings.iter().enumerate() {
+            self.write(channel, *reading);
+        }
+    }
+}
+
+impl MeterReader {
+    pub fn read(&self, channel: usize) -> Reading {
+        self.slots
+            .get(channel)
+            .map(|slot| Reading {
+                rms: f32::from_bits(slot.rms.load(Ordering::Relaxed)),
+                peak: f32::from_bits(slot.peak.load(Ordering::Relaxed)),
+                true_peak: f32::from_bits(slot.true_peak.load(Ordering::Relaxed)),
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn sine(freq: f32, phase: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (TAU * freq * i as f32 / SAMPLE_RATE + phase).sin())
+            .collect()
+    }
+
+    #[test]
+    fn true_peak_finds_the_peak_between_samples() {
+        // every sample lands 45 degrees off a crest, at -3dB
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.process_block(&sine(SAMPLE_RATE / 4.0, TAU / 8.0, 4800));
+        let reading = meter.reading();
+        assert!(
+            (to_db(reading.peak) + 3.01).abs() < 0.01,
+            "{}",
+            reading.peak
+        );
+        assert!(
+            to_db(reading.true_peak).abs() < 0.2,
+            "{}",
+            reading.true_peak
+        );
+    }
+
+    #[test]
+    fn rms_and_peak_of_a_sine() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.process_block(&sine(1000.0, 0.0, SAMPLE_RATE as usize * 3));
+        let reading = meter.reading();
+        assert!(
+            (reading.rms - 0.5f32.sqrt()).abs() < 0.01,
+            "{}",
+            reading.rms
+        );
+        assert!((reading.peak - 1.0).abs() < 1e-3);
+        assert!(reading.true_peak >= reading.peak * 0.99);
+    }
+
+    #[test]
+    fn peaks_fall_back_at_their_rate() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.process_block(&sine(1000.0, 0.0, 480));
+        meter.process_block(&vec![0.0; (1.7 * SAMPLE_RATE) as usize]);
+        let reading = meter.reading();
+        assert!(
+            (to_db(reading.peak) + 20.0).abs() < 0.1,
+            "{}",
+            to_db(reading.peak)
+        );
+    }
+}
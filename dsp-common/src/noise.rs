@@ -0,0 +1,333 @@
+//! Noise to listen to and noise to modulate with.
+//!
+//! `White`, `Pink` and `Brown` fill buffers at sample rate. `perlin`,
+//! `simplex` and `Drift` are smooth in their input, for parameters that
+//! should wander rather than jump. All of it is seeded, the same seed
+//! replays the same noise.
+
+use crate::random::Rng;
+
+/// Flat spectrum, in [-1, 1).
+#[derive(Clone, Debug)]
+pub struct White {
+    rng: Rng,
+}
+
+impl White {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+        }
+    }
+
+    #[inline]
+    pub fn sample(&mut self) -> f32 {
+        self.rng.bipolar()
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|sample| *sample = self.sample());
+    }
+}
+
+/// -3dB per octave, Paul Kellet's filter bank over white noise. Roughly in
+/// [-1, 1], accurate to half a dB above 10Hz at 44.1kHz.
+#[derive(Clone, Debug)]
+pub struct Pink {
+    white: White,
+    poles: [f32; 7],
+}
+
+impl Pink {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            white: White::new(seed),
+            poles: [0.0; 7],
+        }
+    }
+
+    #[inline]
+    pub fn sample(&mut self) -> f32 {
+        let white = self.white.sample();
+        let b = &mut self.poles;
+        b[0] = 0.99886 * b[0] + white * 0.055_517_9;
+        b[1] = 0.99332 * b[1] + white * 0.075_075_9;
+        b[2] = 0.96900 * b[2] + white * 0.153_852;
+        b[3] = 0.86650 * b[3] + white * 0.310_485_6;
+        b[4] = 0.55000 * b[4] + white * 0.532_952_2;
+        b[5] = -0.7616 * b[5] - white * 0.016_898;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115_926;
+        pink * 0.11
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|sample| *sample = self.sample());
+    }
+}
+
+/// -6dB per octave, leakily integrated white noise so it never wanders off.
+/// Roughly in [-1, 1].
+#[derive(Clone, Debug)]
+pub struct Brown {
+    white: White,
+    level: f32,
+}
+
+impl Brown {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            white: White::new(seed),
+            level: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn sample(&mut self) -> f32 {
+        self.level = (self.level + 0.02 * self.white.sample()) / 1.02;
+        self.level * 3.5
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|sample| *sample = self.sample());
+    }
+}
+
+/// integer coordinates to bits, the same for the same seed
+#[inline(always)]
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x27d4_eb2d)
+        ^ (y as u32).wrapping_mul(0x1656_67b1)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+/// in [-1, 1]
+#[inline(always)]
+fn gradient(x: i32, y: i32, seed: u32) -> f32 {
+    hash(x, y, seed) as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+/// 0 and flat at both ends
+#[inline(always)]
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// One-dimensional gradient noise in [-1, 1], 0 on every integer and
+/// about one bump per unit of `x`.
+pub fn perlin(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let i = cell as i32;
+    let a = gradient(i, 0, seed) * t;
+    let b = gradient(i.wrapping_add(1), 0, seed) * (t - 1.0);
+    // the largest a pair of gradients can reach is half
+    2.0 * (a + (b - a) * fade(t))
+}
+
+/// `perlin` over `octaves` octaves, each twice as fast and half as loud as
+/// the last, still in [-1, 1].
+pub fn fractal(x: f32, octaves: usize, seed: u32) -> f32 {
+    let (mut sum, mut amp, mut total, mut freq) = (0.0, 1.0, 0.0, 1.0);
+    for octave in 0..octaves.max(1) {
+        sum += amp * perlin(x * freq, seed.wrapping_add(octave as u32));
+        total += amp;
+        amp *= 0.5;
+        freq *= 2.0;
+    }
+    sum / total
+}
+
+/// Two-dimensional simplex noise, roughly in [-1, 1], for fields like
+/// flocking or terrain that `perlin` can't cover.
+pub fn simplex(x: f32, y: f32, seed: u32) -> f32 {
+    // skews the plane so the simplex grid lines up with integers
+    const F2: f32 = 0.366_025_4;
+    const G2: f32 = 0.211_324_87;
+
+    let s = (x + y) * F2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let corners = [
+        (0, 0, x0, y0),
+        (i1, j1, x0 - i1 as f32 + G2, y0 - j1 as f32 + G2),
+        (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+    ];
+    let (i, j) = (i as i32, j as i32);
+    let sum: f32 = corners
+        .iter()
+        .map(|&(di, dj, dx, dy)| {
+            let falloff = 0.5 - dx * dx - dy * dy;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            let angle = gradient(i + di, j + dj, seed) * std::f32::consts::PI;
+            let dot = angle.cos() * dx + angle.sin() * dy;
+            falloff.powi(4) * dot
+        })
+        .sum();
+    // scales the peaks to about 1
+    (99.0 * sum).clamp(-1.0, 1.0)
+}
+
+/// A value that wanders smoothly around 0, for slow parameter drift.
+#[derive(Clone, Debug)]
+pub struct Drift {
+    seed: u32,
+    /// bumps per second
+    rate: f32,
+    octaves: usize,
+    position: f32,
+}
+
+impl Drift {
+    pub fn new(seed: u32, rate: f32) -> Self {
+        Self {
+            seed,
+            rate,
+            octaves: 2,
+            position: 0.0,
+        }
+    }
+
+    /// rougher with more, see `fractal`
+    pub fn octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    /// in [-1, 1]
+    pub fn value(&self) -> f32 {
+        fractal(self.position, self.octaves, self.seed)
+    }
+
+    /// moves on by `seconds`, e.g. once per frame
+    pub fn step(&mut self, seconds: f32) -> f32 {
+        self.position += self.rate * seconds;
+        self.value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const BLOCK: usize = 1024;
+
+    /// power around `freq`, averaged over Hann windowed blocks of `signal`
+    fn band_power(signal: &[f32], freq: f32) -> f32 {
+        let mut power = 0.0;
+        let blocks = signal.chunks_exact(BLOCK);
+        let count = blocks.len() as f32;
+        for block in blocks {
+            for &f in &[0.9 * freq, freq, 1.1 * freq] {
+                let (mut re, mut im) = (0.0, 0.0);
+                for (i, &x) in block.iter().enumerate() {
+                    let window = 0.5 - 0.5 * (TAU * i as f32 / BLOCK as f32).cos();
+                    let phase = TAU * f * i as f32 / SAMPLE_RATE;
+                    re += x * window * phase.cos();
+                    im += x * window * phase.sin();
+                }
+                power += re * re + im * im;
+            }
+        }
+        power / count
+    }
+
+    /// dB from `low` to three octaves above it
+    fn slope(fill: impl FnOnce(&mut [f32]), low: f32) -> f32 {
+        let mut signal = vec![0.0; 200 * BLOCK];
+        fill(&mut signal);
+        10.0 * (band_power(&signal, 8.0 * low) / band_power(&signal, low)).log10()
+    }
+
+    #[test]
+    fn colours_fall_off_at_their_slopes() {
+        let white = slope(|out| White::new(1).fill(out), 750.0);
+        let pink = slope(|out| Pink::new(1).fill(out), 750.0);
+        let brown = slope(|out| Brown::new(1).fill(out), 750.0);
+        assert!(white.abs() < 1.0, "{}", white);
+        assert!((pink + 9.0).abs() < 1.0, "{}", pink);
+        assert!((brown + 18.0).abs() < 1.5, "{}", brown);
+    }
+
+    #[test]
+    fn noise_replays_from_its_seed_and_stays_in_range() {
+        let mut a = vec![0.0; 48_000];
+        let mut b = a.clone();
+        Pink::new(5).fill(&mut a);
+        Pink::new(5).fill(&mut b);
+        assert_eq!(a, b);
+        Pink::new(6).fill(&mut b);
+        assert_ne!(a, b);
+        assert!(a.iter().all(|x| x.abs() < 1.5));
+        Brown::new(5).fill(&mut a);
+        assert!(a.iter().all(|x| x.abs() < 1.5));
+        White::new(5).fill(&mut a);
+        assert!(a.iter().all(|x| (-1.0..1.0).contains(x)));
+    }
+
+    #[test]
+    fn perlin_is_smooth_and_zero_on_integers() {
+        for seed in 0..4 {
+            let mut last = perlin(-8.0, seed);
+            for i in -8000..8000 {
+                let x = i as f32 / 1000.0;
+                let value = perlin(x, seed);
+                assert!((-1.0..=1.0).contains(&value));
+                assert!((value - last).abs() < 0.01, "jumps at {}", x);
+                if i % 1000 == 0 {
+                    assert!(value.abs() < 1e-6);
+                }
+                let layered = fractal(x, 4, seed);
+                assert!((-1.0..=1.0).contains(&layered));
+                last = value;
+            }
+        }
+        assert_ne!(perlin(0.5, 1), perlin(0.5, 2));
+    }
+
+    #[test]
+    fn simplex_is_smooth_in_both_directions() {
+        let mut largest = 0.0f32;
+        for i in 0..200 {
+            for j in 0..200 {
+                let (x, y) = (i as f32 / 20.0, j as f32 / 20.0);
+                let value = simplex(x, y, 3);
+                assert!((-1.0..=1.0).contains(&value));
+                assert!((simplex(x + 0.001, y, 3) - value).abs() < 0.02);
+                assert!((simplex(x, y + 0.001, 3) - value).abs() < 0.02);
+                assert_eq!(value, simplex(x, y, 3));
+                largest = largest.max(value.abs());
+            }
+        }
+        // scaled up to about the whole range
+        assert!(largest > 0.6, "{}", largest);
+    }
+
+    #[test]
+    fn drift_moves_at_its_rate() {
+        let mut drift = Drift::new(9, 0.5).octaves(3);
+        assert_eq!(drift.value(), 0.0);
+        for _ in 0..60 {
+            drift.step(1.0 / 60.0);
+        }
+        assert!((drift.value() - fractal(0.5, 3, 9)).abs() < 1e-4);
+        drift.set_rate(0.0);
+        let held = drift.value();
+        assert_eq!(drift.step(1.0), held);
+    }
+}
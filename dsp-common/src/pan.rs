@@ -0,0 +1,245 @@
+use std::f32::consts::FRAC_PI_2;
+
+/// pan in [0, 1], 0 is hard left
+#[inline(always)]
+pub fn linear(sample: f32, pan: f32) -> (f32, f32) {
+    (sample * (1.0 - pan), sample * pan)
+}
+
+/// constant power pan law, pan in [0, 1], 0 is hard left
+#[inline(always)]
+pub fn equal_power(sample: f32, pan: f32) -> (f32, f32) {
+    let theta = pan * FRAC_PI_2;
+    (sample * theta.cos(), sample * theta.sin())
+}
+
+/// the most channels a `Layout` has
+pub const MAX_SPEAKERS: usize = 8;
+
+/// Speakers around the listener by channel, each at an angle in degrees
+/// clockwise from the front. Channels without one, such as an LFE, are
+/// left out of panning.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    angles: [Option<f32>; MAX_SPEAKERS],
+    channels: usize,
+}
+
+impl Layout {
+    pub const STEREO: Layout = Layout::fixed(&[Some(-30.0), Some(30.0)]);
+    /// front left, front right, rear left, rear right
+    pub const QUAD: Layout = Layout::fixed(&[Some(-45.0), Some(45.0), Some(-135.0), Some(135.0)]);
+    /// 5.1 in the SMPTE order: left, right, centre, LFE, surround left and
+    /// right, at the ITU angles
+    pub const SURROUND: Layout = Layout::fixed(&[
+        Some(-30.0),
+        Some(30.0),
+        Some(0.0),
+        None,
+        Some(-110.0),
+        Some(110.0),
+    ]);
+    /// eight speakers every 45 degrees from the front, clockwise
+    pub const RING: Layout = Layout::fixed(&[
+        Some(0.0),
+        Some(45.0),
+        Some(90.0),
+        Some(135.0),
+        Some(180.0),
+        Some(-135.0),
+        Some(-90.0),
+        Some(-45.0),
+    ]);
+
+    const fn fixed(angles: &[Option<f32>]) -> Self {
+        let mut layout = Layout {
+            angles: [None; MAX_SPEAKERS],
+            channels: angles.len(),
+        };
+        let mut i = 0;
+        while i < angles.len() {
+            layout.angles[i] = angles[i];
+            i += 1;
+        }
+        layout
+    }
+
+    /// channels past `MAX_SPEAKERS` are dropped
+    pub fn new(angles: &[Option<f32>]) -> Self {
+        Self::fixed(&angles[..angles.len().min(MAX_SPEAKERS)])
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn angle(&self, channel: usize) -> Option<f32> {
+        self.angles.get(channel).copied().flatten()
+    }
+
+    /// moves a speaker, channels without one stay out of panning
+    pub fn set_angle(&mut self, channel: usize, angle: f32) {
+        if let Some(Some(current)) = self.angles[..self.channels].get_mut(channel) {
+            *current = angle;
+        }
+    }
+}
+
+/// Ways to the output other than panning straight onto speakers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// round a modelled head for headphones, see `spherical_head`
+    SphericalHead,
+    /// first-order B-format, see `ambisonics`
+    Ambisonic,
+}
+
+impl Encoding {
+    /// output channels it takes
+    pub fn channels(self) -> usize {
+        match self {
+            Encoding::SphericalHead => 2,
+            Encoding::Ambisonic => crate::ambisonics::CHANNELS,
+        }
+    }
+}
+
+/// Pairwise panning over a `Layout`: a source between two neighbouring
+/// speakers plays from those two only. Speakers all the way round wrap
+/// behind the listener, a front arc stops at its ends.
+#[derive(Clone, Copy, Debug)]
+pub struct Panner {
+    layout: Layout,
+    /// channels with a speaker, by angle
+    order: [usize; MAX_SPEAKERS],
+    speakers: usize,
+    /// no gap between neighbours reaches half the circle
+    surrounds: bool,
+}
+
+impl Panner {
+    pub fn new(layout: Layout) -> Self {
+        let mut order = [0; MAX_SPEAKERS];
+        let mut speakers = 0;
+        for channel in (0..layout.channels).filter(|&c| layout.angle(c).is_some()) {
+            order[speakers] = channel;
+            speakers += 1;
+        }
+        let angle = |channel: usize| wrap(layout.angle(channel).unwrap_or(0.0));
+        order[..speakers].sort_by(|&a, &b| angle(a).total_cmp(&angle(b)));
+        let surrounds = speakers > 2
+            && (0..speakers).all(|i| {
+                let next = order[(i + 1) % speakers];
+                (angle(next) - angle(order[i])).rem_euclid(360.0) < 180.0
+            });
+        Self {
+            layout,
+            order,
+            speakers,
+            surrounds,
+        }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    fn angle(&self, i: usize) -> f32 {
+        wrap(self.layout.angle(self.order[i]).unwrap_or(0.0))
+    }
+
+    /// the angle at `position` in [0, 1] across the layout: left to right
+    /// over a front arc, once round from behind for speakers all round
+    pub fn spread(&self, position: f32) -> f32 {
+        if self.surrounds || self.speakers < 2 {
+            return -180.0 + 360.0 * position;
+        }
+        let (first, last) = (self.angle(0), self.angle(self.speakers - 1));
+        first + (last - first) * position
+    }
+
+    /// the channels either side of `azimuth` and where it is from the first
+    /// to the second in [0, 1], for any pan law
+    pub fn pair(&self, azimuth: f32) -> (usize, usize, f32) {
+        let n = self.speakers;
+        if n < 2 {
+            return (self.order[0], self.order[0], 0.0);
+        }
+        let azimuth = wrap(azimuth);
+        let (first, last) = (self.angle(0), self.angle(n - 1));
+        if azimuth < first || azimuth >= last {
+            if self.surrounds {
+                let span = (first - last).rem_euclid(360.0);
+                let t = (azimuth - last).rem_euclid(360.0) / span;
+                return (self.order[n - 1], self.order[0], t.min(1.0));
+            }
+            return if azimuth < first {
+                (self.order[0], self.order[1], 0.0)
+            } else {
+                (self.order[n - 2], self.order[n - 1], 1.0)
+            };
+        }
+        let i = (0..n - 1)
+            .find(|&i| azimuth < self.angle(i + 1))
+            .unwrap_or(n - 2);
+        let (from, to) = (self.angle(i), self.angle(i + 1));
+        let t = (azimuth - from) / (to - from).max(f32::EPSILON);
+        (self.order[i], self.order[i + 1], t)
+    }
+
+    /// constant power gains by channel for a source at `azimuth`
+    pub fn gains(&self, azimuth: f32) -> [f32; MAX_SPEAKERS] {
+        let mut gains = [0.0; MAX_SPEAKERS];
+        if self.speakers == 0 {
+            return gains;
+        }
+        let (a, b, t) = self.pair(azimuth);
+        let (to_a, to_b) = equal_power(1.0, t);
+        gains[a] += to_a;
+        gains[b] += to_b;
+        gains
+    }
+}
+
+/// degrees into [-180, 180)
+fn wrap(degrees: f32) -> f32 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_power_keeps_the_power() {
+        for i in 0..=10 {
+            let (left, right) = equal_power(1.0, i as f32 / 10.0);
+            assert!((left * left + right * right - 1.0).abs() < 1e-6);
+        }
+        let (left, right) = equal_power(1.0, 0.0);
+        assert!((left - 1.0).abs() < 1e-6 && right.abs() < 1e-6);
+    }
+
+    #[test]
+    fn stereo_pairs_follow_the_angle() {
+        let panner = Panner::new(Layout::STEREO);
+        let centre = panner.gains(0.0);
+        assert!((centre[0] - centre[1]).abs() < 1e-6);
+        let left = panner.gains(-30.0);
+        assert!((left[0] - 1.0).abs() < 1e-6 && left[1].abs() < 1e-6);
+        // a front arc stops at its ends
+        let past = panner.gains(-90.0);
+        assert!((past[0] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn surround_skips_the_lfe() {
+        let panner = Panner::new(Layout::SURROUND);
+        for azimuth in (-180..180).step_by(15) {
+            let gains = panner.gains(azimuth as f32);
+            assert_eq!(gains[3], 0.0);
+            let power: f32 = gains.iter().map(|gain| gain * gain).sum();
+            assert!((power - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// equal ratios per equal slider travel, both ends must share a sign
+    Exponential,
+    /// normalized value raised to the given power
+    Power(f32),
+}
+
+/// Everything the UI, presets and controllers need to know about one parameter.
+#[derive(Clone, Copy, Debug)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub curve: Curve,
+    pub unit: &'static str,
+    /// seconds to glide to a new value on the audio thread, 0 jumps
+    pub smoothing: f32,
+}
+
+impl ParamSpec {
+    pub const fn new(name: &'static str, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            name,
+            min,
+            max,
+            default,
+            curve: Curve::Linear,
+            unit: "",
+            smoothing: 0.0,
+        }
+    }
+
+    pub const fn curve(mut self, curve: Curve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub const fn unit(mut self, unit: &'static str) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub const fn smoothing(mut self, seconds: f32) -> Self {
+        self.smoothing = seconds;
+        self
+    }
+
+    pub fn clamp(&self, value: f32) -> f32 {
+        let (lo, hi) = if self.min < self.max {
+            (self.min, self.max)
+        } else {
+            (self.max, self.min)
+        };
+        value.max(lo).min(hi)
+    }
+
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = self.clamp(value);
+        let linear = (value - self.min) / (self.max - self.min);
+        match self.curve {
+            Curve::Linear => linear,
+            Curve::Exponential => (value / self.min).ln() / (self.max / self.min).ln(),
+            Curve::Power(k) => linear.powf(1.0 / k),
+        }
+    }
+
+    pub fn denormalize(&self, normalized: f32) -> f32 {
+        let n = normalized.clamp(0.0, 1.0);
+        match self.curve {
+            Curve::Linear => self.min + n * (self.max - self.min),
+            Curve::Exponential => self.min * (self.max / self.min).powf(n),
+            Curve::Power(k) => self.min + n.powf(k) * (self.max - self.min),
+        }
+    }
+
+    pub fn format(&self, value: f32) -> String {
+        if self.unit.is_empty() {
+            format!("{}: {:.3}", self.name, value)
+        } else {
+            format!("{}: {:.3} {}", self.name, value, self.unit)
+        }
+    }
+}
+
+/// Lock-free parameter values shared between the UI and audio threads.
+#[derive(Clone)]
+pub struct Params {
+    specs: &'static [ParamSpec],
+    values: Arc<[AtomicU32]>,
+}
+
+impl Params {
+    pub fn new(specs: &'static [ParamSpec]) -> Self {
+        Self {
+            specs,
+            values: specs
+                .iter()
+                .map(|spec| AtomicU32::new(spec.default.to_bits()))
+                .collect::<Vec<_>>()
+                .into(),
+        }
+    }
+
+    pub fn specs(&self) -> &'static [ParamSpec] {
+        self.specs
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.specs.iter().position(|spec| spec.name == name)
+    }
+
+    pub fn get(&self, index: usize) -> f32 {
+        f32::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, index: usize, value: f32) {
+        let value = self.specs[index].clamp(value);
+        self.values[index].store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get_normalized(&self, index: usize) -> f32 {
+        self.specs[index].normalize(self.get(index))
+    }
+
+    pub fn set_normalized(&self, index: usize, normalized: f32) {
+        self.set(index, self.specs[index].denormalize(normalized));
+    }
+
+    pub fn set_by_name(&self, name: &str, value: f32) -> bool {
+        self.index_of(name).map(|i| self.set(i, value)).is_some()
+    }
+
+    pub fn reset(&self) {
+        for (i, spec) in self.specs.iter().enumerate() {
+            self.set(i, spec.default);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<(&'static str, f32)> {
+        self.specs
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| (spec.name, self.get(i)))
+            .collect()
+    }
+}
+
+/// One-pole glide toward a target, called at sample rate.
+#[derive(Clone, Copy, Debug)]
+pub struct Smoothed {
+    value: f32,
+    target: f32,
+    coeff: f32,
+}
+
+impl Smoothed {
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            target: value,
+            coeff: 0.0,
+        }
+    }
+
+    /// `seconds` to cover ~63% of the distance to a new target
+    pub fn set_time(&mut self, seconds: f32, sample_rate: f32) {
+        self.coeff = if seconds > 0.0 {
+            (-1.0 / (seconds * sample_rate)).exp()
+        } else {
+            0.0
+        };
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    pub fn jump(&mut self, value: f32) {
+        self.value = value;
+        self.target = value;
+    }
+
+    #[inline(always)]
+    pub fn step(&mut self) -> f32 {
+        self.value = self.target + self.coeff * (self.value - self.target);
+        self.value
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+}
+
+/// Audio thread view of `Params`, one smoother per parameter.
+pub struct SmoothedParams {
+    params: Params,
+    smoothers: Vec<Smoothed>,
+}
+
+impl SmoothedParams {
+    pub fn new(params: Params, sample_rate: f32) -> Self {
+        let smoothers = params
+            .specs()
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let mut smoother = Smoothed::new(params.get(i));
+                smoother.set_time(spec.smoothing, sample_rate);
+                smoother
+            })
+            .collect();
+        Self { params, smoothers }
+    }
+
+    /// called at buffer rate, picks up the latest targets
+    pub fn update(&mut self) {
+        for (i, smoother) in self.smoothers.iter_mut().enumerate() {
+            smoother.set_target(self.params.get(i));
+        }
+    }
+
+    /// called at sample rate
+    #[inline(always)]
+    pub fn step(&mut self, index: usize) -> f32 {
+        self.smoothers[index].step()
+    }
+
+    pub fn value(&self, index: usize) -> f32 {
+        self.smoothers[index].value()
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+}
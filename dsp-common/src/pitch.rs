@@ -0,0 +1,275 @@
+//! Monophonic pitch detection with YIN (de Cheveigné & Kawahara, 2002).
+//!
+//! The difference between the signal and itself delayed by each lag in the
+//! search range, normalized by its running mean, dips at the period. The
+//! first dip under the threshold is refined with a parabola through its
+//! neighbours, so the estimate isn't stuck on whole samples.
+
+use crate::tuning::{freq_to_midi, midi_to_freq};
+use std::collections::HashMap;
+
+/// Detected fundamental.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pitch {
+    pub freq: f32,
+    /// 1 for a pure periodic signal, towards 0 for noise
+    pub clarity: f32,
+}
+
+pub struct Yin {
+    min_lag: usize,
+    max_lag: usize,
+    /// the dip has to go below this, 0.1 to 0.15 are the usual choices
+    threshold: f32,
+    diff: Vec<f32>,
+}
+
+impl Yin {
+    /// searches `min_freq..max_freq` at `sample_rate`
+    pub fn new(min_freq: f32, max_freq: f32, sample_rate: f32) -> Self {
+        let max_lag = (sample_rate / min_freq.max(1.0)).ceil() as usize;
+        let min_lag = ((sample_rate / max_freq.max(1.0)).floor() as usize).clamp(2, max_lag);
+        Self {
+            min_lag,
+            max_lag,
+            threshold: 0.15,
+            diff: vec![0.0; max_lag + 2],
+        }
+    }
+
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// samples `detect` looks at, older ones are ignored
+    pub fn len(&self) -> usize {
+        2 * (self.max_lag + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the pitch of the newest `len` samples of `signal`, `None` when it is
+    /// too short or nothing periodic enough is in range
+    pub fn detect(&mut self, signal: &[f32], sample_rate: f32) -> Option<Pitch> {
+        let len = self.len();
+        if signal.len() < len {
+            return None;
+        }
+        let signal = &signal[signal.len() - len..];
+        let window = len / 2;
+        let max_lag = self.max_lag + 1;
+
+        // squared difference at each lag, then divided by its running mean
+        self.diff[0] = 1.0;
+        let mut sum = 0.0;
+        for lag in 1..=max_lag {
+            let d: f32 = signal[..window]
+                .iter()
+                .zip(&signal[lag..lag + window])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            sum += d;
+            self.diff[lag] = if sum > 0.0 { d * lag as f32 / sum } else { 1.0 };
+        }
+
+        let mut lag = self.min_lag;
+        while lag < max_lag {
+            if self.diff[lag] < self.threshold {
+                // down to the bottom of the dip
+                while lag + 1 < max_lag && self.diff[lag + 1] < self.diff[lag] {
+                    lag += 1;
+                }
+                break;
+            }
+            lag += 1;
+        }
+        if lag >= max_lag {
+            return None;
+        }
+
+        let (before, at, after) = (self.diff[lag - 1], self.diff[lag], self.diff[lag + 1]);
+        let curvature = before + after - 2.0 * at;
+        let shift = if curvature.abs() > f32::EPSILON {
+            ((before - after) / (2.0 * curvature)).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some(Pitch {
+            freq: sample_rate / (lag as f32 + shift),
+            clarity: (1.0 - at).clamp(0.0, 1.0),
+        })
+    }
+}
+
+/// clearer than this, a detection counts towards `dominant`
+const CLEAR: f32 = 0.8;
+/// windows `dominant` looks at most, spread over the signal so a long one
+/// takes no longer than a short one
+const MAX_WINDOWS: usize = 400;
+
+/// The pitch `signal` dwells on the most, for a whole recording rather than
+/// the newest samples: the clear detections are counted by the semitone
+/// they're nearest and those in the most counted one are averaged. `None`
+/// when nothing in `min_freq..max_freq` is clear enough.
+pub fn dominant(signal: &[f32], min_freq: f32, max_freq: f32, sample_rate: f32) -> Option<Pitch> {
+    let mut yin = Yin::new(min_freq, max_freq, sample_rate);
+    let len = yin.len();
+    if signal.len() < len {
+        return None;
+    }
+    let hop = ((signal.len() - len) / MAX_WINDOWS).max(len / 2).max(1);
+
+    // (nearest semitone, exact note, clarity) for each clear window
+    let mut found = Vec::new();
+    for start in (0..=signal.len() - len).step_by(hop) {
+        if let Some(pitch) = yin.detect(&signal[start..start + len], sample_rate) {
+            if pitch.clarity >= CLEAR {
+                let note = freq_to_midi(pitch.freq);
+                found.push((note.round() as i32, note, pitch.clarity));
+            }
+        }
+    }
+
+    let mut counts = HashMap::new();
+    for &(semitone, _, _) in &found {
+        *counts.entry(semitone).or_insert(0) += 1;
+    }
+    let (&semitone, _) = counts
+        .iter()
+        // the lower of two as common, so the answer doesn't hang on the order
+        .max_by_key(|&(&semitone, &count)| (count, -semitone))?;
+    let (mut note, mut clarity, mut count) = (0.0, 0.0, 0.0);
+    for &(_, n, c) in found.iter().filter(|found| found.0 == semitone) {
+        note += n * c;
+        clarity += c;
+        count += 1.0;
+    }
+    Some(Pitch {
+        freq: midi_to_freq(note / clarity),
+        clarity: clarity / count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// `len` samples of harmonics `1..=harmonics` of `freq` at `1 / n`
+    fn tone(freq: f32, harmonics: usize, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                (1..=harmonics)
+                    .map(|n| (TAU * freq * n as f32 * t).sin() / n as f32)
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn cents(freq: f32, expected: f32) -> f32 {
+        1200.0 * (freq / expected).log2()
+    }
+
+    #[test]
+    fn sines_are_found_within_two_cents() {
+        let mut yin = Yin::new(50.0, 2000.0, SAMPLE_RATE);
+        for &freq in &[55.0, 82.41, 220.0, 440.0, 1046.5, 1900.0] {
+            let pitch = yin.detect(&tone(freq, 1, yin.len()), SAMPLE_RATE).unwrap();
+            assert!(
+                cents(pitch.freq, freq).abs() < 2.0,
+                "{} for {}",
+                pitch.freq,
+                freq
+            );
+            assert!(pitch.clarity > 0.95);
+        }
+    }
+
+    #[test]
+    fn harmonics_dont_pull_it_up_an_octave() {
+        let mut yin = Yin::new(50.0, 2000.0, SAMPLE_RATE);
+        for &freq in &[110.0, 196.0, 523.25] {
+            let pitch = yin.detect(&tone(freq, 12, yin.len()), SAMPLE_RATE).unwrap();
+            assert!(
+                cents(pitch.freq, freq).abs() < 1.0,
+                "{} for {}",
+                pitch.freq,
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn only_the_newest_samples_count() {
+        let mut yin = Yin::new(50.0, 2000.0, SAMPLE_RATE);
+        let mut signal = tone(880.0, 1, 3 * yin.len());
+        signal.extend(tone(330.0, 1, yin.len()));
+        let pitch = yin.detect(&signal, SAMPLE_RATE).unwrap();
+        assert!(cents(pitch.freq, 330.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn nothing_without_a_period_in_range() {
+        let mut yin = Yin::new(50.0, 2000.0, SAMPLE_RATE);
+        assert_eq!(yin.detect(&vec![0.0; yin.len()], SAMPLE_RATE), None);
+        // too short to hold two of the longest periods
+        assert_eq!(
+            yin.detect(&tone(440.0, 1, yin.len() - 1), SAMPLE_RATE),
+            None
+        );
+        // below the range, where no lag is a whole period
+        assert_eq!(yin.detect(&tone(30.0, 1, yin.len()), SAMPLE_RATE), None);
+        let mut rng = crate::random::Rng::new(7);
+        let noise: Vec<f32> = (0..yin.len()).map(|_| rng.bipolar()).collect();
+        assert_eq!(yin.detect(&noise, SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn dominant_is_the_note_held_longest() {
+        let second = SAMPLE_RATE as usize;
+        let mut signal = tone(330.0, 6, second / 8);
+        signal.extend(tone(220.0, 6, second));
+        signal.extend(vec![0.0; second / 4]);
+        signal.extend(tone(440.0, 6, second / 4));
+        let pitch = dominant(&signal, 50.0, 2000.0, SAMPLE_RATE).unwrap();
+        assert!(cents(pitch.freq, 220.0).abs() < 1.0, "{}", pitch.freq);
+        assert!(pitch.clarity >= CLEAR);
+    }
+
+    #[test]
+    fn dominant_averages_within_the_semitone() {
+        // a slow vibrato a fifth of a semitone either side of A3
+        let mut phase = 0.0;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                phase += TAU * 220.0 * 2f32.powf(0.2 * (TAU * 3.0 * t).sin() / 12.0) / SAMPLE_RATE;
+                phase.sin()
+            })
+            .collect();
+        let pitch = dominant(&signal, 50.0, 2000.0, SAMPLE_RATE).unwrap();
+        assert!(cents(pitch.freq, 220.0).abs() < 5.0, "{}", pitch.freq);
+    }
+
+    #[test]
+    fn dominant_needs_something_clear() {
+        let second = SAMPLE_RATE as usize;
+        assert_eq!(
+            dominant(&vec![0.0; second], 50.0, 2000.0, SAMPLE_RATE),
+            None
+        );
+        assert_eq!(
+            dominant(&tone(220.0, 1, 100), 50.0, 2000.0, SAMPLE_RATE),
+            None
+        );
+        let mut rng = crate::random::Rng::new(11);
+        let noise: Vec<f32> = (0..second).map(|_| rng.bipolar()).collect();
+        assert_eq!(dominant(&noise, 50.0, 2000.0, SAMPLE_RATE), None);
+    }
+}
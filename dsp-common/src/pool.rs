@@ -0,0 +1,109 @@
+//! A fixed number of slots held inline, for state the audio thread starts
+//! and stops: grains, voices, events.
+//!
+//! Inserting takes a free slot and removing gives it back, neither
+//! allocates, so the pool can be used from a render callback and, holding
+//! `Copy` values, sent across the bus whole. When every slot is taken the
+//! value is handed back, like a full queue on the bus.
+
+#[derive(Clone, Copy, Debug)]
+pub struct Pool<T, const N: usize> {
+    slots: [Option<T>; N],
+    /// indices of the empty slots, a stack so a freed slot is reused first
+    free: [usize; N],
+    free_len: usize,
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    pub fn new() -> Self {
+        let mut free = [0; N];
+        for (i, index) in free.iter_mut().enumerate() {
+            *index = N - 1 - i;
+        }
+        Self {
+            slots: std::array::from_fn(|_| None),
+            free,
+            free_len: N,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        N - self.free_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free_len == N
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free_len == 0
+    }
+
+    /// `value` in a free slot, handed back when there's none
+    pub fn insert(&mut self, value: T) -> Result<&mut T, T> {
+        if self.free_len == 0 {
+            return Err(value);
+        }
+        self.free_len -= 1;
+        let index = self.free[self.free_len];
+        Ok(self.slots[index].get_or_insert(value))
+    }
+
+    /// empties the slot at `index`, an index from `indices`
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let value = self.slots.get_mut(index)?.take()?;
+        self.free[self.free_len] = index;
+        self.free_len += 1;
+        Some(value)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.slots.get(index)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.slots.get_mut(index)?.as_mut()
+    }
+
+    /// empties every slot `keep` is false for
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|value| !keep(value)) {
+                *slot = None;
+                self.free[self.free_len] = index;
+                self.free_len += 1;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+
+    /// the taken slots' indices, for `get` and `remove`
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_some())
+            .map(|(index, _)| index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(Option::as_ref)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(Option::as_mut)
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,224 @@
+//! Seeded randomness for everything that should replay the same way.
+//!
+//! `Rng` is small and never allocates or locks, so the audio thread can own
+//! one. Seed it from `--seed` or a session for reproducible runs, from
+//! `entropy` otherwise. `fork` hands independent streams to voices or
+//! threads without them sharing a generator.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// SplitMix64, one word of state and good enough for anything audible.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// a different sequence every time
+    pub fn from_entropy() -> Self {
+        Self::new(entropy())
+    }
+
+    /// an independent generator seeded from this one
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    #[inline]
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// in [0, 1)
+    #[inline]
+    pub fn unit(&mut self) -> f32 {
+        // the 24 bits an f32 mantissa holds
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// in [-1, 1)
+    #[inline]
+    pub fn bipolar(&mut self) -> f32 {
+        self.unit() * 2.0 - 1.0
+    }
+
+    /// in [min, max)
+    #[inline]
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.unit()
+    }
+
+    /// in [0, n), 0 when `n` is
+    #[inline]
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// true with `probability`
+    #[inline]
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.unit() < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        match items.len() {
+            0 => None,
+            len => Some(&items[self.below(len)]),
+        }
+    }
+}
+
+/// a seed that differs on every call, from the OS where it has one
+pub fn entropy() -> u64 {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(CALLS.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// Weights summed up front, for picking among the same options many times.
+#[derive(Clone, Debug, Default)]
+pub struct Weighted {
+    cumulative: Vec<f32>,
+}
+
+impl Weighted {
+    /// negative weights count as 0
+    pub fn new(weights: &[f32]) -> Self {
+        let mut total = 0.0;
+        let cumulative = weights
+            .iter()
+            .map(|weight| {
+                total += weight.max(0.0);
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cumulative.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cumulative.is_empty()
+    }
+
+    /// an index picked in proportion to its weight, `None` if they are all 0
+    pub fn choose(&self, rng: &mut Rng) -> Option<usize> {
+        let total = *self.cumulative.last()?;
+        if total <= 0.0 {
+            return None;
+        }
+        let target = rng.unit() * total;
+        let index = self.cumulative.partition_point(|&sum| sum <= target);
+        Some(index.min(self.cumulative.len() - 1))
+    }
+}
+
+/// `Weighted::choose` once, without keeping the sums
+pub fn weighted(rng: &mut Rng, weights: &[f32]) -> Option<usize> {
+    let total: f32 = weights.iter().map(|weight| weight.max(0.0)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut target = rng.unit() * total;
+    let last = weights.iter().rposition(|&weight| weight > 0.0)?;
+    for (i, weight) in weights[..last].iter().enumerate() {
+        target -= weight.max(0.0);
+        if target < 0.0 {
+            return Some(i);
+        }
+    }
+    Some(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_replays_its_sequence() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..1000 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        let mut other = Rng::new(43);
+        assert_ne!(a.next_u64(), other.next_u64());
+        // a fork goes its own way and moves its parent on
+        let mut fork = a.fork();
+        assert_ne!(fork.next_u64(), a.clone().next_u64());
+        assert_ne!(a.next_u64(), b.next_u64());
+        assert_ne!(entropy(), entropy());
+    }
+
+    #[test]
+    fn draws_stay_in_their_ranges() {
+        let mut rng = Rng::new(1);
+        let mut sum = 0.0;
+        for _ in 0..100_000 {
+            let unit = rng.unit();
+            assert!((0.0..1.0).contains(&unit));
+            sum += unit;
+            assert!((-1.0..1.0).contains(&rng.bipolar()));
+            assert!((3.0..5.0).contains(&rng.range(3.0, 5.0)));
+            assert!(rng.below(7) < 7);
+        }
+        assert!((sum / 100_000.0 - 0.5).abs() < 0.01);
+        assert_eq!(rng.below(0), 0);
+        assert_eq!(rng.pick::<u8>(&[]), None);
+        assert!(!rng.chance(0.0));
+        assert!(rng.chance(1.0));
+    }
+
+    #[test]
+    fn below_covers_every_value_evenly() {
+        let mut rng = Rng::new(2);
+        let mut counts = [0; 10];
+        for _ in 0..100_000 {
+            counts[rng.below(10)] += 1;
+        }
+        for &count in &counts {
+            assert!((count as f32 / 10_000.0 - 1.0).abs() < 0.05, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn weights_set_the_odds() {
+        let weights = [1.0, 0.0, 3.0, -2.0];
+        let table = Weighted::new(&weights);
+        let (mut rng, mut once) = (Rng::new(3), Rng::new(4));
+        let (mut counts, mut once_counts) = ([0; 4], [0; 4]);
+        for _ in 0..40_000 {
+            counts[table.choose(&mut rng).unwrap()] += 1;
+            once_counts[weighted(&mut once, &weights).unwrap()] += 1;
+        }
+        for counts in &[counts, once_counts] {
+            assert_eq!(counts[1], 0);
+            assert_eq!(counts[3], 0);
+            assert!(
+                (counts[2] as f32 / counts[0] as f32 - 3.0).abs() < 0.15,
+                "{:?}",
+                counts
+            );
+        }
+        assert_eq!(Weighted::new(&[0.0, -1.0]).choose(&mut rng), None);
+        assert_eq!(Weighted::new(&[]).choose(&mut rng), None);
+        assert_eq!(weighted(&mut rng, &[0.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Block kernels for the hot inner loops, eight lanes at a time.
+//!
+//! With the `simd` feature these run on `wide` vectors, without it they fall
+//! back to the loops in `scalar`. Both give bit identical results, every lane
+//! does the same operations in the same order as the per-sample code.
+
+#[cfg(feature = "simd")]
+use wide::f32x8;
+
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+/// Per-sample reference versions, always available for comparison.
+pub mod scalar {
+    use crate::table::{filut_clamped, lerp};
+
+    /// `block[i] *= gains[i]`
+    pub fn multiply(block: &mut [f32], gains: &[f32]) {
+        for (sample, gain) in block.iter_mut().zip(gains) {
+            *sample *= gain;
+        }
+    }
+
+    /// linear pan of `mono` scaled by `gain`, added to `left` and `right`
+    pub fn pan_accumulate(mono: &[f32], pan: f32, gain: f32, left: &mut [f32], right: &mut [f32]) {
+        for ((sample, l), r) in mono.iter().zip(left.iter_mut()).zip(right.iter_mut()) {
+            *l += sample * (1.0 - pan) * gain;
+            *r += sample * pan * gain;
+        }
+    }
+
+    /// replaces each index in `block` with `filut_clamped(table, index)`
+    pub fn filut_clamped_block(table: &[f32], block: &mut [f32]) {
+        if table.is_empty() {
+            block.iter_mut().for_each(|sample| *sample = 0.0);
+            return;
+        }
+        for index in block.iter_mut() {
+            *index = filut_clamped(table, *index);
+        }
+    }
+
+    /// `block[i] = lerp(from[i], to[i], block[i])`
+    pub fn lerp_block(from: &[f32], to: &[f32], block: &mut [f32]) {
+        for ((w, x0), x1) in block.iter_mut().zip(from).zip(to) {
+            *w = lerp(*x0, *x1, *w);
+        }
+    }
+}
+
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn load(slice: &[f32]) -> f32x8 {
+    let mut lanes = [0.0; LANES];
+    lanes.copy_from_slice(slice);
+    f32x8::from(lanes)
+}
+
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn store(slice: &mut [f32], v: f32x8) {
+    slice.copy_from_slice(&v.to_array());
+}
+
+/// `block[i] *= gains[i]`
+pub fn multiply(block: &mut [f32], gains: &[f32]) {
+    #[cfg(feature = "simd")]
+    {
+        let len = block.len().min(gains.len());
+        let split = len - len % LANES;
+        let (head, tail) = block[..len].split_at_mut(split);
+        for (chunk, gains) in head.chunks_exact_mut(LANES).zip(gains.chunks_exact(LANES)) {
+            store(chunk, load(chunk) * load(gains));
+        }
+        scalar::multiply(tail, &gains[split..len]);
+    }
+    #[cfg(not(feature = "simd"))]
+    scalar::multiply(block, gains);
+}
+
+/// linear pan of `mono` scaled by `gain`, added to `left` and `right`
+pub fn pan_accumulate(mono: &[f32], pan: f32, gain: f32, left: &mut [f32], right: &mut [f32]) {
+    #[cfg(feature = "simd")]
+    {
+        let len = mono.len().min(left.len()).min(right.len());
+        let split = len - len % LANES;
+        let (to_left, to_right) = (f32x8::splat(1.0 - pan), f32x8::splat(pan));
+        let gain_v = f32x8::splat(gain);
+        for i in (0..split).step_by(LANES) {
+            let range = i..i + LANES;
+            let sample = load(&mono[range.clone()]);
+            let l = load(&left[range.clone()]) + sample * to_left * gain_v;
+            let r = load(&right[range.clone()]) + sample * to_right * gain_v;
+            store(&mut left[range.clone()], l);
+            store(&mut right[range], r);
+        }
+        scalar::pan_accumulate(
+            &mono[split..len],
+            pan,
+            gain,
+            &mut left[split..len],
+            &mut right[split..len],
+        );
+    }
+    #[cfg(not(feature = "simd"))]
+    scalar::pan_accumulate(mono, pan, gain, left, right);
+}
+
+/// replaces each index in `block` with `filut_clamped(table, index)`,
+/// the reads are scalar, the index math and interpolation are not
+pub fn filut_clamped_block(table: &[f32], block: &mut [f32]) {
+    #[cfg(feature = "simd")]
+    {
+        if table.is_empty() {
+            block.iter_mut().for_each(|sample| *sample = 0.0);
+            return;
+        }
+        let last = table.len() - 1;
+        let (zero, one) = (f32x8::splat(0.0), f32x8::splat(1.0));
+        let last_v = f32x8::splat(last as f32);
+        let split = block.len() - block.len() % LANES;
+        let (head, tail) = block.split_at_mut(split);
+        for chunk in head.chunks_exact_mut(LANES) {
+            let index = load(chunk);
+            // truncation is floor once negative indices are clamped away
+            let whole = index.max(zero).min(last_v).trunc_int();
+            let weight = (index - whole.round_float()).max(zero).min(one);
+
+            let (mut x0, mut x1) = ([0.0; LANES], [0.0; LANES]);
+            for (lane, &i0) in whole.to_array().iter().enumerate() {
+                let i0 = i0 as usize;
+                x0[lane] = table[i0];
+                x1[lane] = table[(i0 + 1).min(last)];
+            }
+            let (x0, x1) = (f32x8::from(x0), f32x8::from(x1));
+            store(chunk, (one - weight) * x0 + weight * x1);
+        }
+        scalar::filut_clamped_block(table, tail);
+    }
+    #[cfg(not(feature = "simd"))]
+    scalar::filut_clamped_block(table, block);
+}
+
+/// `block[i] = lerp(from[i], to[i], block[i])`
+pub fn lerp_block(from: &[f32], to: &[f32], block: &mut [f32]) {
+    #[cfg(feature = "simd")]
+    {
+        let len = block.len().min(from.len()).min(to.len());
+        let split = len - len % LANES;
+        let one = f32x8::splat(1.0);
+        for i in (0..split).step_by(LANES) {
+            let range = i..i + LANES;
+            let w = load(&block[range.clone()]);
+            let (x0, x1) = (load(&from[range.clone()]), load(&to[range.clone()]));
+            store(&mut block[range], (one - w) * x0 + w * x1);
+        }
+        scalar::lerp_block(&from[split..len], &to[split..len], &mut block[split..len]);
+    }
+    #[cfg(not(feature = "simd"))]
+    scalar::lerp_block(from, to, block);
+}
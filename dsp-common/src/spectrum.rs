@@ -0,0 +1,144 @@
+//! Short-time Fourier transform with windowing, overlap and smoothing.
+
+use realfft::{num_complex::Complex, RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let n = (size.max(2) - 1) as f32;
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * PI * i as f32 / n;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StftConfig {
+    /// samples per transform, a power of 2 is fastest
+    pub size: usize,
+    /// samples between transforms, `size / 4` is 75% overlap
+    pub hop: usize,
+    pub window: Window,
+    /// 0 shows every frame as is, towards 1 averages over more frames
+    pub smoothing: f32,
+}
+
+impl Default for StftConfig {
+    fn default() -> Self {
+        Self {
+            size: 2048,
+            hop: 512,
+            window: Window::Hann,
+            smoothing: 0.8,
+        }
+    }
+}
+
+/// Mono STFT, keeps the smoothed magnitudes of the latest frame.
+pub struct Stft {
+    config: StftConfig,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    pending: Vec<f32>,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    /// a full scale sine reads 1 whatever the window
+    scale: f32,
+}
+
+impl Stft {
+    pub fn new(config: StftConfig) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(config.size);
+        let window = config.window.coefficients(config.size);
+        let scale = 2.0 / window.iter().sum::<f32>();
+        Self {
+            window,
+            pending: Vec::with_capacity(config.size * 2),
+            frame: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            magnitudes: vec![0.0; config.size / 2 + 1],
+            scale,
+            fft,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &StftConfig {
+        &self.config
+    }
+
+    pub fn bins(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// centre frequency of `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize, sample_rate: f32) -> f32 {
+        bin as f32 * sample_rate / self.config.size as f32
+    }
+
+    /// linear magnitudes, 1 is full scale
+    pub fn magnitudes(&self) -> &[f32] {
+        &self.magnitudes
+    }
+
+    /// feeds samples in, returns how many new frames were analysed
+    pub fn process(&mut self, samples: &[f32]) -> usize {
+        self.pending.extend_from_slice(samples);
+        let mut frames = 0;
+        while self.pending.len() >= self.config.size {
+            self.analyse();
+            self.pending.drain(..self.config.hop.max(1));
+            frames += 1;
+        }
+        frames
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.magnitudes.iter_mut().for_each(|m| *m = 0.0);
+    }
+
+    fn analyse(&mut self) {
+        for ((out, sample), w) in self
+            .frame
+            .iter_mut()
+            .zip(&self.pending[..self.config.size])
+            .zip(&self.window)
+        {
+            *out = sample * w;
+        }
+        if self
+            .fft
+            .process_with_scratch(&mut self.frame, &mut self.spectrum, &mut self.scratch)
+            .is_err()
+        {
+            return;
+        }
+
+        let smoothing = self.config.smoothing.clamp(0.0, 0.999);
+        for (smoothed, bin) in self.magnitudes.iter_mut().zip(&self.spectrum) {
+            let magnitude = bin.norm() * self.scale;
+            *smoothed = magnitude + smoothing * (*smoothed - magnitude);
+        }
+    }
+}
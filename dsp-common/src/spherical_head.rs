@@ -0,0 +1,219 @@
+//! Headphone rendering after Brown and Duda's structural model of the head,
+//! a sphere with a pinna rather than measured HRTFs.
+//!
+//! A sphere delays and shadows the far ear by the source's angle to it, the
+//! near ear gets a lift in the highs. The pinna's notch climbs with
+//! elevation and sources behind lose some air. Anything panned over a
+//! `Layout` plays on headphones by feeding `SphericalHead` a virtual speaker per
+//! channel, each heard from its own direction.
+
+use crate::denormal::flush;
+use crate::filter::{Biquad, Response};
+use crate::pan::{Layout, MAX_SPEAKERS};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// metres, an average adult's
+const HEAD_RADIUS: f32 = 0.0875;
+/// metres a second
+const SPEED_OF_SOUND: f32 = 343.0;
+/// samples of input kept for the ear delays, the longest is under half
+/// this at 192kHz
+const HISTORY: usize = 256;
+/// the far ear's shadow at its deepest, as a gain on the highs
+const MIN_SHADOW: f32 = 0.1;
+/// degrees from the ear where the shadow is deepest
+const DEEPEST_SHADOW: f32 = 150.0;
+/// Hz, the pinna notch at level, it moves 40Hz a degree
+const NOTCH: f32 = 8_000.0;
+const NOTCH_Q: f32 = 2.0;
+/// how much of the notch band is taken out
+const NOTCH_DEPTH: f32 = 0.7;
+/// Hz, where the highs roll off for a source straight behind
+const REAR_CUTOFF: f32 = 6_000.0;
+
+/// The head's shelf for one ear, one pole and one zero by the bilinear
+/// transform.
+#[derive(Clone, Copy, Debug, Default)]
+struct Shadow {
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Shadow {
+    /// `angle` in radians from the ear to the source
+    fn new(angle: f32, sample_rate: f32) -> Self {
+        let alpha = (1.0 + MIN_SHADOW / 2.0)
+            + (1.0 - MIN_SHADOW / 2.0) * (angle.to_degrees() / DEEPEST_SHADOW * PI).cos();
+        let beta = 2.0 * SPEED_OF_SOUND / HEAD_RADIUS;
+        let k = 2.0 * sample_rate;
+        Self {
+            b0: (alpha * k + beta) / (k + beta),
+            b1: (beta - alpha * k) / (k + beta),
+            a1: (beta - k) / (k + beta),
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    #[inline(always)]
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = x;
+        self.y1 = flush(y);
+        y
+    }
+}
+
+/// One direction, mono in and both ears out.
+#[derive(Clone, Copy, Debug)]
+struct Source {
+    history: [f32; HISTORY],
+    write: usize,
+    /// samples each ear hears the source late by, left then right
+    delays: [f32; 2],
+    shadows: [Shadow; 2],
+    /// one pole lowpass coefficient, 0 passes everything
+    rear: f32,
+    rear_state: f32,
+}
+
+impl Source {
+    fn new(azimuth: f32, elevation: f32, sample_rate: f32) -> Self {
+        let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+        // how far right the source is, the ears point straight out
+        let right = elevation.cos() * azimuth.sin();
+        let ear = |side: f32| {
+            let angle = (side * right).clamp(-1.0, 1.0).acos();
+            let delay = if angle < FRAC_PI_2 {
+                1.0 - angle.cos()
+            } else {
+                1.0 + angle - FRAC_PI_2
+            };
+            (
+                delay * HEAD_RADIUS / SPEED_OF_SOUND * sample_rate,
+                Shadow::new(angle, sample_rate),
+            )
+        };
+        let (left, right) = (ear(-1.0), ear(1.0));
+        // nothing behind at the sides, all of it straight back
+        let behind = (-azimuth.cos() * elevation.cos()).max(0.0);
+        let cutoff = sample_rate / 2.0 + (REAR_CUTOFF - sample_rate / 2.0) * behind;
+        Self {
+            history: [0.0; HISTORY],
+            write: 0,
+            delays: [left.0, right.0],
+            shadows: [left.1, right.1],
+            rear: (-2.0 * PI * cutoff / sample_rate).exp() * behind,
+            rear_state: 0.0,
+        }
+    }
+
+    /// the input `delay` samples ago, between samples linearly
+    #[inline(always)]
+    fn read(&self, delay: f32) -> f32 {
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let at = |back: usize| self.history[(self.write + HISTORY - back) % HISTORY];
+        at(whole) + (at(whole + 1) - at(whole)) * frac
+    }
+
+    #[inline(always)]
+    fn process(&mut self, x: f32) -> (f32, f32) {
+        self.rear_state = flush(x + (self.rear_state - x) * self.rear);
+        self.history[self.write] = self.rear_state;
+        let left = self.shadows[0].process(self.read(self.delays[0]));
+        let right = self.shadows[1].process(self.read(self.delays[1]));
+        self.write = (self.write + 1) % HISTORY;
+        (left, right)
+    }
+}
+
+/// A `Layout`'s speakers, each a source round the listener's head, all
+/// tilted up or down together.
+#[derive(Clone, Debug)]
+pub struct SphericalHead {
+    layout: Layout,
+    elevation: f32,
+    sample_rate: f32,
+    sources: [Option<Source>; MAX_SPEAKERS],
+    /// left and right
+    notches: [Biquad; 2],
+}
+
+impl SphericalHead {
+    /// `elevation` in degrees, up is positive
+    pub fn new(layout: Layout, elevation: f32, sample_rate: f32) -> Self {
+        let notch = Biquad::new(Response::Bandpass, NOTCH, NOTCH_Q, sample_rate);
+        let mut head = Self {
+            layout,
+            elevation,
+            sample_rate,
+            sources: [None; MAX_SPEAKERS],
+            notches: [notch; 2],
+        };
+        head.place();
+        head
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    /// moves every source, what they hold is dropped
+    pub fn set_elevation(&mut self, elevation: f32) {
+        self.elevation = elevation;
+        self.place();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.place();
+    }
+
+    fn place(&mut self) {
+        let (elevation, sample_rate) = (self.elevation.clamp(-90.0, 90.0), self.sample_rate);
+        for (channel, source) in self.sources.iter_mut().enumerate() {
+            *source = self
+                .layout
+                .angle(channel)
+                .map(|azimuth| Source::new(azimuth, elevation, sample_rate));
+        }
+        let notch = NOTCH + 40.0 * elevation.clamp(-45.0, 90.0);
+        for filter in self.notches.iter_mut() {
+            *filter = Biquad::new(Response::Bandpass, notch, NOTCH_Q, self.sample_rate);
+        }
+    }
+
+    /// `input` interleaved in the layout's channels, mixed into the first
+    /// two of interleaved `out`
+    pub fn process(&mut self, input: &[f32], out: &mut [f32], channels: usize) {
+        let speakers = self.layout.channels();
+        for (frame, out) in input
+            .chunks_exact(speakers)
+            .zip(out.chunks_exact_mut(channels))
+        {
+            let (mut left, mut right) = (0.0, 0.0);
+            for (source, &x) in self.sources.iter_mut().zip(frame) {
+                if let Some(source) = source {
+                    let (l, r) = source.process(x);
+                    left += l;
+                    right += r;
+                }
+            }
+            let [left_notch, right_notch] = &mut self.notches;
+            let left = left - NOTCH_DEPTH * left_notch.process(left);
+            let right = right - NOTCH_DEPTH * right_notch.process(right);
+            out[0] += left;
+            if let Some(out) = out.get_mut(1) {
+                *out += right;
+            }
+        }
+    }
+}
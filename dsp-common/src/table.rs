@@ -0,0 +1,109 @@
+use std::f32::consts::PI;
+
+#[inline(always)]
+pub fn lerp(x0: f32, x1: f32, w: f32) -> f32 {
+    (1.0 - w) * x0 + w * x1
+}
+
+/// table must be power of 2, the index wraps around
+#[inline(always)]
+pub fn filut(table: &[f32], index: f32) -> f32 {
+    debug_assert!(table.len().is_power_of_two());
+    let wrap_mask = table.len() - 1;
+    let index0 = index as usize;
+    let weight = index - index0 as f32;
+    let index0 = index0 & wrap_mask;
+    let index1 = (index0 + 1) & wrap_mask;
+    lerp(table[index0], table[index1], weight)
+}
+
+/// any table length, the index is clamped to the last entry
+#[inline(always)]
+pub fn filut_clamped(table: &[f32], index: f32) -> f32 {
+    let last = table.len() - 1;
+    let index0 = (index.max(0.0) as usize).min(last);
+    let index1 = (index0 + 1).min(last);
+    let weight = (index - index0 as f32).clamp(0.0, 1.0);
+    lerp(table[index0], table[index1], weight)
+}
+
+/// Single cycle table read with linear interpolation.
+#[derive(Clone, Debug)]
+pub struct Wavetable {
+    table: Vec<f32>,
+}
+
+impl Wavetable {
+    pub fn new(table: Vec<f32>) -> Self {
+        assert!(
+            table.len().is_power_of_two(),
+            "wavetable length must be a power of 2"
+        );
+        Self { table }
+    }
+
+    pub fn sine(size: usize) -> Self {
+        Self::new(
+            (0..size)
+                .map(|i| (2.0 * PI * i as f32 / size as f32).sin())
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.table
+    }
+
+    /// index in table units, wraps in both directions
+    #[inline(always)]
+    pub fn lookup(&self, index: f32) -> f32 {
+        filut(&self.table, index.rem_euclid(self.table.len() as f32))
+    }
+
+    /// phase in cycles, i.e. 1.0 is one full period
+    #[inline(always)]
+    pub fn at(&self, phase: f32) -> f32 {
+        self.lookup(phase * self.table.len() as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filut_interpolates_and_wraps() {
+        let table = [0.0, 1.0, 2.0, 3.0];
+        assert_eq!(filut(&table, 1.5), 1.5);
+        // past the end it reads from the start again
+        assert_eq!(filut(&table, 3.5), 1.5);
+        assert_eq!(filut(&table, 5.25), 1.25);
+    }
+
+    #[test]
+    fn filut_clamped_holds_the_ends() {
+        let table = [0.0, 1.0, 2.0];
+        assert_eq!(filut_clamped(&table, -1.0), 0.0);
+        assert_eq!(filut_clamped(&table, 0.5), 0.5);
+        assert_eq!(filut_clamped(&table, 10.0), 2.0);
+    }
+
+    #[test]
+    fn sine_table_at_its_quarters() {
+        let sine = Wavetable::sine(1024);
+        assert!(sine.at(0.0).abs() < 1e-6);
+        assert!((sine.at(0.25) - 1.0).abs() < 1e-6);
+        assert!((sine.at(0.75) + 1.0).abs() < 1e-6);
+        // phases wrap both ways
+        assert!((sine.at(-0.75) - 1.0).abs() < 1e-6);
+        assert!((sine.at(1.25) - 1.0).abs() < 1e-6);
+    }
+}
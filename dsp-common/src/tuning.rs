@@ -0,0 +1,388 @@
+pub const A4_MIDI: f32 = 69.0;
+pub const A4_FREQ: f32 = 440.0;
+
+/// 12-TET, A4 = 440Hz
+#[inline(always)]
+pub fn midi_to_freq(note: f32) -> f32 {
+    A4_FREQ * 2f32.powf((note - A4_MIDI) / 12.0)
+}
+
+#[inline(always)]
+pub fn freq_to_midi(freq: f32) -> f32 {
+    A4_MIDI + 12.0 * (freq / A4_FREQ).log2()
+}
+
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// `A4`, `C#-1`, sharps rather than flats
+pub fn note_name(note: i32) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[note.rem_euclid(12) as usize],
+        note.div_euclid(12) - 1
+    )
+}
+
+/// Steps in semitones from the root, within one period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scale {
+    pub name: &'static str,
+    pub steps: &'static [f32],
+    pub period: f32,
+}
+
+impl Scale {
+    pub const CHROMATIC: Scale = Scale::new(
+        "chromatic",
+        &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0],
+    );
+    pub const IONIAN: Scale = Scale::new("ionian", &[0.0, 2.0, 4.0, 5.0, 7.0, 9.0, 11.0]);
+    pub const DORIAN: Scale = Scale::new("dorian", &[0.0, 2.0, 3.0, 5.0, 7.0, 9.0, 10.0]);
+    pub const PHRYGIAN: Scale = Scale::new("phrygian", &[0.0, 1.0, 3.0, 5.0, 7.0, 8.0, 10.0]);
+    pub const LYDIAN: Scale = Scale::new("lydian", &[0.0, 2.0, 4.0, 6.0, 7.0, 9.0, 11.0]);
+    pub const MIXOLYDIAN: Scale = Scale::new("mixolydian", &[0.0, 2.0, 4.0, 5.0, 7.0, 9.0, 10.0]);
+    pub const AEOLIAN: Scale = Scale::new("aeolian", &[0.0, 2.0, 3.0, 5.0, 7.0, 8.0, 10.0]);
+    pub const LOCRIAN: Scale = Scale::new("locrian", &[0.0, 1.0, 3.0, 5.0, 6.0, 8.0, 10.0]);
+    pub const MAJOR: Scale = Scale::IONIAN;
+    pub const MINOR: Scale = Scale::AEOLIAN;
+    pub const HARMONIC_MINOR: Scale =
+        Scale::new("harmonic minor", &[0.0, 2.0, 3.0, 5.0, 7.0, 8.0, 11.0]);
+    pub const MELODIC_MINOR: Scale =
+        Scale::new("melodic minor", &[0.0, 2.0, 3.0, 5.0, 7.0, 9.0, 11.0]);
+    pub const MAJOR_PENTATONIC: Scale = Scale::new("major pentatonic", &[0.0, 2.0, 4.0, 7.0, 9.0]);
+    pub const MINOR_PENTATONIC: Scale = Scale::new("minor pentatonic", &[0.0, 3.0, 5.0, 7.0, 10.0]);
+    pub const WHOLE_TONE: Scale = Scale::new("whole tone", &[0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+
+    /// the seven diatonic modes, in order
+    pub const MODES: [Scale; 7] = [
+        Scale::IONIAN,
+        Scale::DORIAN,
+        Scale::PHRYGIAN,
+        Scale::LYDIAN,
+        Scale::MIXOLYDIAN,
+        Scale::AEOLIAN,
+        Scale::LOCRIAN,
+    ];
+
+    pub const fn new(name: &'static str, steps: &'static [f32]) -> Self {
+        Self {
+            name,
+            steps,
+            period: 12.0,
+        }
+    }
+
+    /// for non-octave scales
+    pub const fn period(mut self, semitones: f32) -> Self {
+        self.period = semitones;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// midi note of a degree above `root`, wrapping into further periods,
+    /// negative degrees go below the root
+    pub fn note(&self, root: f32, degree: i32) -> f32 {
+        let len = self.steps.len() as i32;
+        let period = degree.div_euclid(len);
+        let step = self.steps[degree.rem_euclid(len) as usize];
+        root + period as f32 * self.period + step
+    }
+
+    pub fn freq(&self, root: f32, degree: i32) -> f32 {
+        midi_to_freq(self.note(root, degree))
+    }
+
+    pub fn notes(&self, root: f32, count: usize) -> Vec<f32> {
+        (0..count as i32).map(|d| self.note(root, d)).collect()
+    }
+
+    pub fn freqs(&self, root: f32, count: usize) -> Vec<f32> {
+        (0..count as i32).map(|d| self.freq(root, d)).collect()
+    }
+
+    /// nearest midi note in the scale
+    pub fn quantize(&self, root: f32, note: f32) -> f32 {
+        let relative = note - root;
+        let period = (relative / self.period).floor();
+        let within = relative - period * self.period;
+        let step = self
+            .steps
+            .iter()
+            .copied()
+            .chain(std::iter::once(self.period))
+            .min_by(|a, b| (a - within).abs().partial_cmp(&(b - within).abs()).unwrap())
+            .unwrap_or(0.0);
+        root + period * self.period + step
+    }
+}
+
+/// Equal division of the octave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edo {
+    pub divisions: u32,
+}
+
+impl Edo {
+    pub const fn new(divisions: u32) -> Self {
+        Self { divisions }
+    }
+
+    /// frequency ratio of a step above the reference
+    pub fn ratio(&self, step: i32) -> f32 {
+        2f32.powf(step as f32 / self.divisions as f32)
+    }
+
+    pub fn freq(&self, reference: f32, step: i32) -> f32 {
+        reference * self.ratio(step)
+    }
+
+    /// steps as semitones, for use as a `Scale`
+    pub fn semitones(&self) -> Vec<f32> {
+        let size = 12.0 / self.divisions as f32;
+        (0..self.divisions).map(|i| i as f32 * size).collect()
+    }
+}
+
+/// A twelve note keyboard tuning, each pitch class in cents above the
+/// temperament's tonic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Temperament {
+    pub name: &'static str,
+    pub cents: [f32; 12],
+}
+
+impl Temperament {
+    pub const EQUAL: Temperament = Temperament::new(
+        "equal",
+        [
+            0.0, 100.0, 200.0, 300.0, 400.0, 500.0, 600.0, 700.0, 800.0, 900.0, 1000.0, 1100.0,
+        ],
+    );
+    /// pure fifths, the wolf between G# and Eb
+    pub const PYTHAGOREAN: Temperament = Temperament::new(
+        "pythagorean",
+        [
+            0.0, 113.69, 203.91, 294.13, 407.82, 498.05, 611.73, 701.96, 815.64, 905.87, 996.09,
+            1109.78,
+        ],
+    );
+    /// 5-limit ratios, `just::MAJOR` filled in with the minor and chromatic
+    /// steps
+    pub const JUST: Temperament = Temperament::new(
+        "just",
+        [
+            0.0, 111.73, 203.91, 315.64, 386.31, 498.05, 590.22, 701.96, 813.69, 884.36, 1017.6,
+            1088.27,
+        ],
+    );
+    /// pure major thirds
+    pub const MEANTONE: Temperament = Temperament::new(
+        "quarter-comma meantone",
+        [
+            0.0, 76.05, 193.16, 310.26, 386.31, 503.42, 579.47, 696.58, 772.63, 889.74, 1006.84,
+            1082.89,
+        ],
+    );
+    /// well temperament, every key playable and each its own colour
+    pub const WERCKMEISTER: Temperament = Temperament::new(
+        "werckmeister III",
+        [
+            0.0, 90.22, 192.18, 294.13, 390.23, 498.05, 588.27, 696.09, 792.18, 888.27, 996.09,
+            1092.18,
+        ],
+    );
+
+    pub const ALL: [Temperament; 5] = [
+        Temperament::EQUAL,
+        Temperament::PYTHAGOREAN,
+        Temperament::JUST,
+        Temperament::MEANTONE,
+        Temperament::WERCKMEISTER,
+    ];
+
+    pub const fn new(name: &'static str, cents: [f32; 12]) -> Self {
+        Self { name, cents }
+    }
+
+    /// cents away from 12-TET of `note` with the tonic on pitch class
+    /// `root`, A kept where it is so the reference pitch still holds
+    pub fn offset(&self, note: i32, root: i32) -> f32 {
+        let deviation = |pitch_class: i32| {
+            let step = (pitch_class - root).rem_euclid(12);
+            self.cents[step as usize] - 100.0 * step as f32
+        };
+        deviation(note.rem_euclid(12)) - deviation(A4_MIDI as i32 % 12)
+    }
+
+    /// frequency of the MIDI `note` with A4 at `reference`
+    pub fn freq(&self, note: i32, root: i32, reference: f32) -> f32 {
+        let cents = 100.0 * (note as f32 - A4_MIDI) + self.offset(note, root);
+        reference * 2f32.powf(cents / 1200.0)
+    }
+}
+
+/// Just intonation ratio, `num / den`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ratio(pub u32, pub u32);
+
+impl Ratio {
+    pub fn value(self) -> f32 {
+        self.0 as f32 / self.1 as f32
+    }
+
+    pub fn semitones(self) -> f32 {
+        12.0 * self.value().log2()
+    }
+
+    pub fn freq(self, reference: f32) -> f32 {
+        reference * self.value()
+    }
+
+    /// the `num / den` closest to `value` as an interval, neither above
+    /// `limit`, in lowest terms
+    pub fn nearest(value: f32, limit: u32) -> Self {
+        let target = value.log2();
+        let mut nearest = Ratio(1, 1);
+        let mut distance = f32::INFINITY;
+        // smaller denominators first, so reducible ratios never win a tie
+        for den in 1..=limit {
+            for num in 1..=limit {
+                let ratio = Ratio(num, den);
+                let d = (ratio.value().log2() - target).abs();
+                if d < distance {
+                    nearest = ratio;
+                    distance = d;
+                }
+            }
+        }
+        nearest
+    }
+}
+
+pub mod just {
+    use super::Ratio;
+
+    pub const MAJOR: [Ratio; 7] = [
+        Ratio(1, 1),
+        Ratio(9, 8),
+        Ratio(5, 4),
+        Ratio(4, 3),
+        Ratio(3, 2),
+        Ratio(5, 3),
+        Ratio(15, 8),
+    ];
+
+    pub const MINOR: [Ratio; 7] = [
+        Ratio(1, 1),
+        Ratio(9, 8),
+        Ratio(6, 5),
+        Ratio(4, 3),
+        Ratio(3, 2),
+        Ratio(8, 5),
+        Ratio(9, 5),
+    ];
+
+    pub const PENTATONIC: [Ratio; 5] = [
+        Ratio(1, 1),
+        Ratio(9, 8),
+        Ratio(5, 4),
+        Ratio(3, 2),
+        Ratio(5, 3),
+    ];
+
+    /// partials 1..=n of the harmonic series
+    pub fn harmonics(n: u32) -> Vec<Ratio> {
+        (1..=n).map(|i| Ratio(i, 1)).collect()
+    }
+
+    /// every `i / j` for `i, j` in `1..=limit`, row major, duplicates kept
+    pub fn grid(limit: u32) -> Vec<Ratio> {
+        (1..=limit)
+            .flat_map(|i| (1..=limit).map(move |j| Ratio(i, j)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midi_and_freq_round_trip() {
+        assert_eq!(midi_to_freq(A4_MIDI), A4_FREQ);
+        assert!((midi_to_freq(81.0) - 880.0).abs() < 1e-3);
+        for note in 0..128 {
+            assert!((freq_to_midi(midi_to_freq(note as f32)) - note as f32).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn scale_notes_quantize_to_themselves() {
+        for scale in Scale::MODES.iter().chain(&[Scale::WHOLE_TONE]) {
+            for degree in -14..14 {
+                let note = scale.note(60.0, degree);
+                assert_eq!(scale.quantize(60.0, note), note);
+            }
+        }
+    }
+
+    #[test]
+    fn quantize_picks_the_nearest_step() {
+        let major = Scale::MAJOR;
+        // C# is as far from C as from D, the first step wins
+        assert_eq!(major.quantize(60.0, 61.0), 60.0);
+        assert_eq!(major.quantize(60.0, 61.4), 62.0);
+        assert_eq!(major.quantize(60.0, 71.6), 72.0);
+        assert_eq!(major.quantize(60.0, 58.6), 59.0);
+        assert_eq!(major.note(60.0, -1), 59.0);
+        assert_eq!(major.note(60.0, 7), 72.0);
+    }
+
+    #[test]
+    fn note_names() {
+        assert_eq!(note_name(69), "A4");
+        assert_eq!(note_name(1), "C#-1");
+    }
+
+    #[test]
+    fn nearest_ratio_finds_simple_intervals_in_lowest_terms() {
+        for &(num, den) in &[
+            (1, 1),
+            (2, 1),
+            (3, 2),
+            (4, 3),
+            (5, 4),
+            (7, 4),
+            (8, 5),
+            (3, 4),
+        ] {
+            let ratio = Ratio(num, den);
+            assert_eq!(Ratio::nearest(ratio.value(), 8), ratio);
+            // a few cents out either way
+            assert_eq!(Ratio::nearest(ratio.value() * 1.003, 8), ratio);
+            assert_eq!(Ratio::nearest(ratio.value() / 1.003, 8), ratio);
+        }
+    }
+
+    #[test]
+    fn nearest_ratio_stays_within_its_limit() {
+        // 9/8 is too big a step for 8, 10/9 fits 10
+        assert_eq!(Ratio::nearest(1.1, 8), Ratio(8, 7));
+        assert_eq!(Ratio::nearest(1.1, 10), Ratio(10, 9));
+        assert_eq!(Ratio::nearest(20.0, 8), Ratio(8, 1));
+        assert_eq!(Ratio::nearest(0.01, 8), Ratio(1, 8));
+        for i in 1..200 {
+            let Ratio(num, den) = Ratio::nearest(i as f32 / 50.0, 6);
+            assert!(num <= 6 && den <= 6);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use dsp_common::env::{Envelope, Retrigger, Shape};
+use dsp_common::filter::{Biquad, Response, Svf};
+use dsp_common::limiter::Limiter;
+use dsp_common::random::Rng;
+use dsp_common::{pan, Wavetable};
+use lissa::figure::{Lissajous, Tone};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+pub const BLOCK: usize = 512;
+
+const SEED: u64 = 0;
+
+/// A deterministic render compared against `res/<name>.wav`.
+pub struct Case {
+    pub name: &'static str,
+    pub channels: usize,
+    /// interleaved output
+    pub render: fn() -> Vec<f32>,
+}
+
+pub static CASES: &[Case] = &[
+    Case {
+        name: "granular",
+        channels: 2,
+        render: granular,
+    },
+    Case {
+        name: "lissa",
+        channels: 2,
+        render: lissa,
+    },
+    Case {
+        name: "trapezoid",
+        channels: 1,
+        render: trapezoid,
+    },
+    Case {
+        name: "envelopes",
+        channels: 1,
+        render: envelopes,
+    },
+    Case {
+        name: "pan",
+        channels: 2,
+        render: equal_power,
+    },
+    Case {
+        name: "limiter",
+        channels: 2,
+        render: limiter,
+    },
+    Case {
+        name: "filter",
+        channels: 2,
+        render: filter,
+    },
+];
+
+/// one second of a detuned saw, the same table as the granular example
+fn saw_table() -> &'static [f32] {
+    granular::leak_table(
+        (0..SAMPLE_RATE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                ((t * 110.0).fract() + (t * 110.7).fract()) - 1.0
+            })
+            .collect(),
+    )
+}
+
+fn granular() -> Vec<f32> {
+    const CHANNELS: usize = 2;
+    const BLOCKS: usize = 128;
+
+    let mut engine = granular::Engine::with_seed(saw_table(), SAMPLE_RATE as f32, SEED);
+    engine.set_params(granular::Params {
+        trigger_interval: Some(8),
+        grain_interval: 2,
+        voice_length: (0.5, 1.0),
+        ..granular::Params::default()
+    });
+
+    let mut out = vec![0.0; BLOCKS * BLOCK * CHANNELS];
+    for block in out.chunks_exact_mut(BLOCK * CHANNELS) {
+        engine.process(block, CHANNELS);
+        while engine.poll_event().is_some() {}
+    }
+    out
+}
+
+fn lissa() -> Vec<f32> {
+    const BLOCKS: usize = 64;
+    /// blocks between jumps, like a fast free-running app
+    const JUMP: usize = 8;
+
+    let mut rng = Rng::new(SEED);
+    let mut figure = Lissajous::new(1024.0, 768.0);
+    let mut tone = Tone::new();
+
+    let mut out = vec![0.0; BLOCKS * BLOCK * 2];
+    for (i, block) in out.chunks_exact_mut(BLOCK * 2).enumerate() {
+        if i % JUMP == 0 {
+            figure.randomize(&mut rng);
+        }
+        figure.compute();
+        tone.process(block, figure.freqs(), None, SAMPLE_RATE as f32);
+    }
+    out
+}
+
+/// a sine through retriggered envelopes of growing length and slope
+fn trapezoid() -> Vec<f32> {
+    let sine = Wavetable::sine(1024);
+    let mut out = Vec::new();
+    for (length, slope) in [(256, 2.0), (1024, 4.0), (4096, 8.0), (8192, 32.0)].iter() {
+        let edge = 1.0 / *slope;
+        let mut env = Envelope::new(Shape::trapezoid(edge, edge));
+        env.trigger(*length as f32);
+        for i in 0..*length {
+            let phase = (i as f32 * 440.0 / SAMPLE_RATE as f32).fract();
+            out.push(sine.at(phase) * env.step());
+        }
+    }
+    out
+}
+
+/// a sine through gated envelopes, straight and curved, released early and
+/// retriggered legato mid-release
+fn envelopes() -> Vec<f32> {
+    const GATE: usize = 8192;
+    const TAIL: usize = 4096;
+    let rate = 1.0 / SAMPLE_RATE as f32;
+    let sine = Wavetable::sine(1024);
+    let shapes = [
+        Shape::ar(0.02, 0.05),
+        Shape::adsr(0.01, 0.05, 0.5, 0.08),
+        Shape::adsr(0.01, 0.05, 0.5, 0.08).curve(5.0),
+        Shape::dahdsr(0.02, 0.03, 0.02, 0.04, 0.3, 0.06).curve(-3.0),
+    ];
+
+    let mut out = Vec::new();
+    let mut frame = 0;
+    let mut push = |gain: f32| {
+        let phase = (frame as f32 * 440.0 / SAMPLE_RATE as f32).fract();
+        out.push(sine.at(phase) * gain);
+        frame += 1;
+    };
+    for shape in shapes.iter() {
+        let mut env = Envelope::new(*shape);
+        env.gate_on(rate);
+        (0..GATE).for_each(|_| push(env.step()));
+        env.gate_off();
+        (0..TAIL).for_each(|_| push(env.step()));
+    }
+
+    let mut env = Envelope::new(Shape::adsr(0.01, 0.02, 0.6, 0.1).curve(2.0));
+    env.set_retrigger(Retrigger::Legato);
+    env.gate_on(rate);
+    (0..GATE).for_each(|_| push(env.step()));
+    env.gate_off();
+    (0..TAIL / 4).for_each(|_| push(env.step()));
+    env.gate_on(rate);
+    (0..GATE).for_each(|_| push(env.step()));
+    env.gate_off();
+    (0..TAIL).for_each(|_| push(env.step()));
+    out
+}
+
+/// a sine swept from hard left to hard right
+fn equal_power() -> Vec<f32> {
+    const FRAMES: usize = 16_384;
+    let sine = Wavetable::sine(1024);
+    let mut out = Vec::with_capacity(FRAMES * 2);
+    for i in 0..FRAMES {
+        let phase = (i as f32 * 220.0 / SAMPLE_RATE as f32).fract();
+        let (left, right) = pan::equal_power(sine.at(phase), i as f32 / (FRAMES - 1) as f32);
+        out.push(left);
+        out.push(right);
+    }
+    out
+}
+
+/// a sine and its octave swelling from -20 to +12 dBFS, then back down
+fn limiter() -> Vec<f32> {
+    const FRAMES: usize = 32_768;
+    let sine = Wavetable::sine(1024);
+    let mut limiter = Limiter::new(SAMPLE_RATE as f32);
+    let mut out = Vec::with_capacity(FRAMES * 2);
+    for i in 0..FRAMES {
+        let swell = 1.0 - (2.0 * i as f32 / FRAMES as f32 - 1.0).abs();
+        let gain = dsp_common::meter::from_db(-20.0 + 32.0 * swell);
+        let phase = (i as f32 * 110.0 / SAMPLE_RATE as f32).fract();
+        out.push(sine.at(phase) * gain);
+        out.push(sine.at((phase * 2.0).fract()) * gain);
+    }
+    limiter.process_interleaved(&mut out, 2);
+    out
+}
+
+/// a saw through a lowpass biquad and a resonant SVF bandpass, both swept
+/// from 100Hz to 8kHz and back with smoothing between the steps
+fn filter() -> Vec<f32> {
+    const FRAMES: usize = 32_768;
+    const STEP: usize = 1024;
+    const Q: f32 = 4.0;
+    let rate = SAMPLE_RATE as f32;
+    let mut biquad = Biquad::lowpass(100.0, rate);
+    let mut svf = Svf::new(100.0, Q, rate);
+    biquad.set_smoothing(0.01);
+    svf.set_smoothing(0.01);
+
+    let mut out = Vec::with_capacity(FRAMES * 2);
+    for i in 0..FRAMES {
+        if i % STEP == 0 {
+            let sweep = 1.0 - (2.0 * i as f32 / FRAMES as f32 - 1.0).abs();
+            let cutoff = 100.0 * 80.0f32.powf(sweep);
+            biquad.set_cutoff(cutoff);
+            svf.set_cutoff(cutoff);
+        }
+        let saw = 2.0 * (i as f32 * 110.0 / rate).fract() - 1.0;
+        out.push(biquad.process(saw));
+        out.push(svf.process(saw).get(Response::Bandpass) / Q);
+    }
+    out
+}
@@ -0,0 +1,141 @@
+//! Renders every DSP case with fixed seeds and compares it to its golden WAV.
+//!
+//! cargo golden [--bless] [case...]
+//!
+//! `--bless` rewrites the goldens, only do it when a change to the sound is
+//! intended and listened to.
+
+mod cases;
+
+use cases::{Case, CASES, SAMPLE_RATE};
+use std::path::PathBuf;
+use std::process;
+
+/// float renders are bit exact on one machine, leave room for other targets
+const TOLERANCE: f32 = 1e-4;
+
+enum Outcome {
+    Pass,
+    Blessed,
+    Missing,
+    Mismatch(String),
+}
+
+fn golden_path(case: &Case) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("res")
+        .join(format!("{}.wav", case.name))
+}
+
+fn spec(case: &Case) -> hound::WavSpec {
+    hound::WavSpec {
+        channels: case.channels as u16,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    }
+}
+
+fn write(case: &Case, samples: &[f32]) -> Result<(), hound::Error> {
+    let mut writer = hound::WavWriter::create(golden_path(case), spec(case))?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()
+}
+
+fn compare(case: &Case, rendered: &[f32]) -> Outcome {
+    let mut reader = match hound::WavReader::open(golden_path(case)) {
+        Ok(reader) => reader,
+        Err(_) => return Outcome::Missing,
+    };
+    if reader.spec() != spec(case) {
+        return Outcome::Mismatch(format!("format changed, {:?}", reader.spec()));
+    }
+    let golden: Vec<f32> = match reader.samples::<f32>().collect() {
+        Ok(golden) => golden,
+        Err(e) => return Outcome::Mismatch(format!("unreadable golden, {}", e)),
+    };
+    if golden.len() != rendered.len() {
+        return Outcome::Mismatch(format!(
+            "{} samples, expected {}",
+            rendered.len(),
+            golden.len()
+        ));
+    }
+
+    let (worst, error) = golden
+        .iter()
+        .zip(rendered)
+        .map(|(a, b)| (a - b).abs())
+        .enumerate()
+        .fold((0, 0.0f32), |worst, (i, error)| {
+            if error > worst.1 {
+                (i, error)
+            } else {
+                worst
+            }
+        });
+    if error > TOLERANCE {
+        Outcome::Mismatch(format!(
+            "off by {:.6} at frame {} channel {}",
+            error,
+            worst / case.channels,
+            worst % case.channels
+        ))
+    } else {
+        Outcome::Pass
+    }
+}
+
+fn main() {
+    let mut bless = false;
+    let mut only = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            _ => only.push(arg),
+        }
+    }
+
+    if let Some(unknown) = only
+        .iter()
+        .find(|name| CASES.iter().all(|c| &c.name != name))
+    {
+        let names: Vec<_> = CASES.iter().map(|c| c.name).collect();
+        eprintln!("no case named {}, there are: {}", unknown, names.join(", "));
+        process::exit(2);
+    }
+
+    let mut failures = 0;
+    for case in CASES
+        .iter()
+        .filter(|c| only.is_empty() || only.iter().any(|name| name == c.name))
+    {
+        let rendered = (case.render)();
+        let outcome = if bless {
+            match write(case, &rendered) {
+                Ok(()) => Outcome::Blessed,
+                Err(e) => Outcome::Mismatch(format!("cannot write golden, {}", e)),
+            }
+        } else {
+            compare(case, &rendered)
+        };
+        match outcome {
+            Outcome::Pass => println!("{:<12} ok", case.name),
+            Outcome::Blessed => println!("{:<12} blessed", case.name),
+            Outcome::Missing => {
+                failures += 1;
+                println!("{:<12} FAILED: no golden, run with --bless", case.name);
+            }
+            Outcome::Mismatch(reason) => {
+                failures += 1;
+                println!("{:<12} FAILED: {}", case.name, reason);
+            }
+        }
+    }
+
+    if failures > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use graindelay::delay::{DelayLine, Grain, Grains, Scratch, BLOCK};
+
+/// a second of the line filled with something to read
+fn line() -> DelayLine {
+    let mut line = DelayLine::new(48_000);
+    for i in 0..48_000 {
+        line.write((i as f32 * 0.013).sin());
+    }
+    line
+}
+
+/// `count` grains spread through the line at different rates, long enough
+/// to outlast the benchmark
+fn grains(count: usize) -> Grains {
+    let mut grains = Grains::default();
+    for i in 0..count {
+        grains.start(Grain {
+            position: (i * 997) as f64,
+            rate: 0.5 + i as f64 * 0.05,
+            age: 0,
+            length: usize::MAX,
+            level: 0.5,
+            pan: i as f32 / count as f32,
+        });
+    }
+    grains
+}
+
+fn block(c: &mut Criterion) {
+    let line = line();
+    for &count in [8, 48].iter() {
+        c.bench_function(&format!("grains advance {}", count), |b| {
+            let mut grains = grains(count);
+            b.iter(|| {
+                let (mut left, mut right) = ([0.0; BLOCK], [0.0; BLOCK]);
+                for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                    let (a, b) = grains.advance(&line);
+                    *l = a;
+                    *r = b;
+                }
+                black_box((left, right))
+            })
+        });
+        c.bench_function(&format!("grains process {}", count), |b| {
+            let mut grains = grains(count);
+            let mut scratch = Scratch::default();
+            b.iter(|| {
+                let (mut left, mut right) = ([0.0; BLOCK], [0.0; BLOCK]);
+                grains.process(&line, &mut left, &mut right, &mut scratch);
+                black_box((left, right))
+            })
+        });
+    }
+}
+
+criterion_group!(benches, block);
+criterion_main!(benches);
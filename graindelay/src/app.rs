@@ -0,0 +1,432 @@
+use crate::delay::Grain;
+use crate::dsp::{self, Command, Engine, Source, State, PEAKS};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::input::{self, Input, InputConfig};
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::setup::AudioSettings;
+use app_common::shell::{self, SetupKey, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::io::Cursor;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a bar of guitar, shared with yfes, delayed by `--render` in place of an
+/// input
+static LOOP: Asset = Asset::new("gtr.wav", include_bytes!("../../yfes/res/gtr.wav"));
+
+/// holds what's in the delay line, or lets the input back in
+const FREEZE: Key = Key::Space;
+
+/// how far an octave of pitch moves a grain off the ring, as a share of
+/// its radius
+const OCTAVE_OFFSET: f32 = 0.25;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        freeze,
+    }
+}
+
+/// the first channel of the configured input device, at the rate the
+/// output is asked for
+fn open_input(config: &Config) -> Result<(Input, Source), input::Error> {
+    let (input, reader) = Input::start(&InputConfig {
+        host: config.audio_host.clone(),
+        device: config.input_device.clone(),
+        channels: vec![0],
+        sample_rate: Some(config.sample_rate.unwrap_or(dsp::SAMPLE_RATE as u32)),
+        frames_per_buffer: config.buffer_size,
+        ..InputConfig::default()
+    })?;
+    Ok((input, Source::Input(reader)))
+}
+
+/// the built in loop mixed to mono, and its rate
+fn load_loop() -> Result<(Vec<f32>, u32), String> {
+    let bytes = LOOP.bytes().map_err(|e| e.to_string())?;
+    let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = reader
+        .into_samples::<i16>()
+        .map(|s| s.map(|s| s as f32 / 32_768.0))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    source: Source,
+    seed: u64,
+) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), source, seed);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("graindelay", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the built in loop through the delay, without a window or audio device
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("graindelay", &config::path("graindelay"));
+    let seed = shell::seed(session_seed);
+    let (samples, sample_rate) = load_loop().unwrap_or_else(|e| {
+        eprintln!("graindelay: cannot read {}: {}", LOOP.source().display(), e);
+        std::process::exit(1);
+    });
+    let source = Source::Loop {
+        samples,
+        position: 0,
+    };
+    let params = shell::load_params("graindelay", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, source, seed);
+    request.run(
+        &mut engine,
+        sample_rate,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the input through the delay on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("graindelay", &config::path("graindelay"));
+    let seed = shell::seed(session_seed);
+    let (input, source) = match open_input(&config) {
+        Ok(input) => input,
+        Err(e) => startup::fatal("graindelay", startup::Error::Input(e.to_string())),
+    };
+    println!("graindelay: listening on {}", input.device());
+    let params = shell::load_params("graindelay", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, source, seed);
+    render::headless("graindelay", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    bus: UiEnd<Command, State>,
+    /// the delay line and grains as of the last buffer played
+    state: State,
+    /// dropping it stops listening
+    input: Option<Input>,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("graindelay");
+    let (config, session_seed) = shell::load_config("graindelay", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("graindelay", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("graindelay", &config, &params);
+    let serial = serial::Sensors::from_config("graindelay", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("graindelay", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    let (input, source) = match open_input(&config) {
+        Ok((input, source)) => (Some(input), source),
+        Err(e) => {
+            errors.push(startup::Error::Input(e.to_string()));
+            (None, Source::Silence)
+        }
+    };
+    let (engine, bus) = engine(&config, &params, source, seed);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "graindelay", &config, stream.stats());
+    let shell = Shell::new(
+        "graindelay",
+        config,
+        &config_path,
+        "phosphor",
+        stream_config,
+    )
+    .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        bus,
+        state: State::default(),
+        input,
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// listens on the input the config names now, keeping the old one if the
+/// new one won't open
+fn reopen_input(model: &mut Model, config: &Config) {
+    match open_input(config) {
+        Ok((input, source)) => {
+            model.input = Some(input);
+            model.stream.send(move |engine| engine.set_source(source));
+        }
+        Err(e) => eprintln!("graindelay: {}", e),
+    }
+}
+
+fn toggle_freeze(model: &mut Model) {
+    let _ = model.bus.send(Command::Freeze(!model.state.frozen));
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if setup_key_pressed(model, key) {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == FREEZE {
+        toggle_freeze(model);
+    }
+    model.hud.key_pressed(key);
+    model
+        .shell
+        .key_pressed(app, key, &model.stream, Some(model.seed));
+}
+
+/// true while the setup screen takes the keys, the input follows the
+/// output's rate so it's reopened on any change
+fn setup_key_pressed(model: &mut Model, key: Key) -> bool {
+    let outcome = model.shell.setup_key_pressed(key, &mut model.stream);
+    if let SetupKey::Applied(previous) = &outcome {
+        if *previous != AudioSettings::from_config(&model.shell.config) || model.input.is_none() {
+            let config = model.shell.config.clone();
+            reopen_input(model, &config);
+        }
+    }
+    outcome.taken()
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if AudioSettings::from_config(&config) != AudioSettings::from_config(&model.shell.config) {
+            reopen_input(model, &config);
+        }
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let label = if model.state.frozen {
+        "frozen"
+    } else {
+        "freeze"
+    };
+    let mut toggled = false;
+    for _value in widget::Toggle::new(model.state.frozen)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.freeze, ui)
+    {
+        toggled = true;
+    }
+    if toggled {
+        toggle_freeze(model);
+    }
+}
+
+/// `turns` of the way round clockwise from the top, `radius` out
+fn around(center: Point2, radius: f32, turns: f32) -> Point2 {
+    let angle = (0.25 - turns) * TAU;
+    center + vec2(angle.cos(), angle.sin()) * radius
+}
+
+/// where `grain` is reading, as a turn round the delay line
+fn turns(grain: &Grain, len: usize) -> f32 {
+    (grain.position.rem_euclid(len as f64) / len as f64) as f32
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let center = area.xy();
+    let radius = area.w().min(area.h()) * 0.3;
+    let state = &model.state;
+    let [r, g, b] = palette.line;
+
+    // the delay line round the ring, fading from the write head back so
+    // the oldest audio is faintest, unless it's held
+    for (bin, peak) in state.peaks.iter().enumerate() {
+        let at = bin as f32 / PEAKS as f32;
+        let age = (state.head - at).rem_euclid(1.0);
+        let alpha = if state.frozen { 0.8 } else { 1.0 - age * 0.8 };
+        let reach = peak.min(1.0) * radius * 0.3;
+        draw.line()
+            .start(around(center, radius - reach, at))
+            .end(around(center, radius + reach, at))
+            .weight(2.0)
+            .color(rgba(r, g, b, alpha));
+    }
+    let head = if state.frozen { 0.3 } else { 1.0 };
+    draw.line()
+        .start(around(center, radius * 0.6, state.head))
+        .end(around(center, radius * 1.4, state.head))
+        .weight(2.0)
+        .color(rgba(r, g, b, head));
+
+    // each grain where it reads, off the ring by its pitch and as big as
+    // its window is open
+    for (i, grain) in state.grains.active().enumerate() {
+        let [r, g, b] = palette.accent(i);
+        let offset = (grain.rate as f32).log2() * OCTAVE_OFFSET * radius;
+        let window = grain.window();
+        draw.ellipse()
+            .xy(around(center, radius + offset, turns(grain, state.len)))
+            .radius(2.0 + window * 8.0)
+            .color(rgba(r, g, b, 0.3 + window * 0.7));
+    }
+
+    if state.frozen {
+        draw.text("frozen")
+            .xy(center)
+            .font_size(18)
+            .color(theme::color(palette.line));
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,217 @@
+//! The input's last few seconds and the grains reading them back.
+//!
+//! The input is written round a buffer and each grain reads a short
+//! windowed stretch of it from some way behind the write head, at its own
+//! speed so it plays back shifted in pitch.
+//!
+//! Grains render a block at a time: each reads its stretch of the line into
+//! scratch, windows it and pans it into the mix, so the loops are short
+//! and simple enough to vectorize. `advance` does the same a frame at a
+//! time, the reference the benches measure `process` against.
+
+use dsp_common::pool::Pool;
+use dsp_common::{pan, simd};
+use std::f32::consts::{PI, TAU};
+
+pub const MAX_GRAINS: usize = 48;
+/// frames grains are rendered at a time
+pub const BLOCK: usize = 256;
+
+/// Written round and round, the oldest frame overwritten first.
+pub struct DelayLine {
+    samples: Vec<f32>,
+    head: usize,
+}
+
+impl DelayLine {
+    pub fn new(len: usize) -> Self {
+        Self {
+            samples: vec![0.0; len.max(2)],
+            head: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// where the next frame is written
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    pub fn write(&mut self, sample: f32) {
+        self.samples[self.head] = sample;
+        self.head = (self.head + 1) % self.samples.len();
+    }
+
+    /// at `position` frames into the buffer, between frames and wrapping
+    pub fn at(&self, position: f64) -> f32 {
+        let len = self.samples.len();
+        let position = position.rem_euclid(len as f64);
+        let index = position as usize % len;
+        let w = (position - index as f64) as f32;
+        let a = self.samples[index];
+        let b = self.samples[(index + 1) % len];
+        a + (b - a) * w
+    }
+
+    /// `out.len()` frames from `position` on, `rate` apart, as `at` would
+    /// read them but wrapping once rather than every frame; returns the
+    /// position after the last
+    pub fn read(&self, position: f64, rate: f64, out: &mut [f32]) -> f64 {
+        let len = self.samples.len();
+        let mut position = position.rem_euclid(len as f64);
+        // rounding can land a tiny negative position on the end
+        if position >= len as f64 {
+            position -= len as f64;
+        }
+        for sample in out.iter_mut() {
+            let index = position as usize;
+            let next = if index + 1 == len { 0 } else { index + 1 };
+            let w = (position - index as f64) as f32;
+            let a = self.samples[index];
+            *sample = a + (self.samples[next] - a) * w;
+            position += rate;
+            if position >= len as f64 {
+                position -= len as f64;
+            }
+        }
+        position
+    }
+}
+
+/// Working space for rendering grains a block at a time.
+pub struct Scratch {
+    samples: [f32; BLOCK],
+    window: [f32; BLOCK],
+}
+
+impl Default for Scratch {
+    fn default() -> Self {
+        Self {
+            samples: [0.0; BLOCK],
+            window: [0.0; BLOCK],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Grain {
+    /// frames into the delay line, wrapping
+    pub position: f64,
+    /// frames read per frame played, 2 is an octave up
+    pub rate: f64,
+    /// frames played so far
+    pub age: usize,
+    /// 0 once the grain has finished
+    pub length: usize,
+    pub level: f32,
+    /// 0 is hard left
+    pub pan: f32,
+}
+
+impl Grain {
+    pub fn is_active(&self) -> bool {
+        self.age < self.length
+    }
+
+    /// the Hann window where the grain is now
+    pub fn window(&self) -> f32 {
+        let x = self.age as f32 / self.length.max(1) as f32;
+        (PI * x).sin().powi(2)
+    }
+
+    /// the next frame from `line`, panned
+    pub fn advance(&mut self, line: &DelayLine) -> (f32, f32) {
+        let sample = line.at(self.position) * self.window() * self.level;
+        self.position += self.rate;
+        self.age += 1;
+        pan::equal_power(sample, self.pan)
+    }
+
+    /// The same as `advance` over `left.len()` frames, up to `BLOCK`, added
+    /// to `left` and `right`. Stops early when the grain finishes.
+    pub fn process(
+        &mut self,
+        line: &DelayLine,
+        left: &mut [f32],
+        right: &mut [f32],
+        scratch: &mut Scratch,
+    ) {
+        let frames = left.len().min(self.length.saturating_sub(self.age));
+        if frames == 0 {
+            return;
+        }
+        let samples = &mut scratch.samples[..frames];
+        self.position = line.read(self.position, self.rate, samples);
+
+        // sin² as a raised cosine, turned a frame at a time from where the
+        // block starts so rounding can't build up over a long grain
+        let step = TAU / self.length as f32;
+        let (step_sin, step_cos) = step.sin_cos();
+        let (mut sin, mut cos) = (step * self.age as f32).sin_cos();
+        let window = &mut scratch.window[..frames];
+        for gain in window.iter_mut() {
+            *gain = (0.5 - 0.5 * cos) * self.level;
+            let turned = cos * step_cos - sin * step_sin;
+            sin = sin * step_cos + cos * step_sin;
+            cos = turned;
+        }
+        simd::multiply(samples, window);
+
+        let (left_gain, right_gain) = pan::equal_power(1.0, self.pan);
+        for ((sample, l), r) in samples.iter().zip(left.iter_mut()).zip(right.iter_mut()) {
+            *l += sample * left_gain;
+            *r += sample * right_gain;
+        }
+        self.age += frames;
+    }
+}
+
+/// Every grain sounding, fixed in size so it can cross the audio bus.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Grains {
+    grains: Pool<Grain, MAX_GRAINS>,
+}
+
+impl Grains {
+    /// takes the place of a finished grain, dropped when all are sounding
+    pub fn start(&mut self, grain: Grain) -> Option<&mut Grain> {
+        self.grains.retain(Grain::is_active);
+        self.grains.insert(grain).ok()
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Grain> {
+        self.grains.iter().filter(|g| g.is_active())
+    }
+
+    /// the next frame of every grain mixed
+    pub fn advance(&mut self, line: &DelayLine) -> (f32, f32) {
+        let (mut left, mut right) = (0.0, 0.0);
+        for grain in self.grains.iter_mut().filter(|g| g.is_active()) {
+            let (l, r) = grain.advance(line);
+            left += l;
+            right += r;
+        }
+        (left, right)
+    }
+
+    /// every grain over the next `left.len()` frames, up to `BLOCK`, added
+    /// to `left` and `right`
+    pub fn process(
+        &mut self,
+        line: &DelayLine,
+        left: &mut [f32],
+        right: &mut [f32],
+        scratch: &mut Scratch,
+    ) {
+        for grain in self.grains.iter_mut().filter(|g| g.is_active()) {
+            grain.process(line, left, right, scratch);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::delay::{DelayLine, Grain, Grains, Scratch, BLOCK};
+use app_common::bus::AudioEnd;
+use app_common::input::InputReader;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::random::Rng;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at and expects the input at the same one
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// seconds the delay line holds, room for the longest time and a grain
+/// reaching past it
+const LINE_SECONDS: f32 = 4.0;
+/// frames the input may run ahead before the oldest are dropped, as the
+/// input and output clocks drift apart
+const SLACK: usize = 2048;
+/// bins the delay line's waveform is drawn with
+pub const PEAKS: usize = 360;
+
+pub const TIME: usize = 0;
+pub const SIZE: usize = 1;
+pub const DENSITY: usize = 2;
+pub const SCATTER: usize = 3;
+pub const PITCH: usize = 4;
+pub const FEEDBACK: usize = 5;
+pub const MIX: usize = 6;
+
+/// where grains read from and what they're like, then how much comes round
+/// again and how much is heard
+pub static PARAMS: [ParamSpec; 7] = [
+    ParamSpec::new("time", 0.05, 2.0, 0.4)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    ParamSpec::new("size", 0.02, 0.5, 0.12)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    // grains a second
+    ParamSpec::new("density", 2.0, 60.0, 16.0).curve(Curve::Exponential),
+    // a share of the time grains start anywhere up to before it
+    ParamSpec::new("scatter", 0.0, 1.0, 0.2),
+    ParamSpec::new("pitch", -12.0, 12.0, 0.0).unit("st"),
+    ParamSpec::new("feedback", 0.0, 0.95, 0.4),
+    ParamSpec::new("mix", 0.0, 1.0, 0.5),
+];
+
+pub enum Command {
+    /// stops writing the input, the grains go on reading what's held
+    Freeze(bool),
+}
+
+/// What the window draws, published after every buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct State {
+    /// the loudest sample in each bin of the delay line, round from the
+    /// start of the line
+    pub peaks: [f32; PEAKS],
+    /// the write head, from 0 to 1 round the line
+    pub head: f32,
+    pub grains: Grains,
+    /// frames in the delay line, to place the grains
+    pub len: usize,
+    pub frozen: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            peaks: [0.0; PEAKS],
+            head: 0.0,
+            grains: Grains::default(),
+            len: 1,
+            frozen: false,
+        }
+    }
+}
+
+/// What runs through the delay.
+pub enum Source {
+    Input(InputReader),
+    /// round and round, for rendering without an input
+    Loop {
+        samples: Vec<f32>,
+        position: usize,
+    },
+    Silence,
+}
+
+impl Source {
+    /// the next `out.len()` mono frames, silent where there are none yet
+    fn fill(&mut self, out: &mut [f32]) {
+        let read = match self {
+            Source::Input(reader) if reader.available() > out.len() + SLACK => reader.latest(out),
+            Source::Input(reader) => reader.read(out),
+            Source::Loop { samples, position } if !samples.is_empty() => {
+                for sample in out.iter_mut() {
+                    *sample = samples[*position];
+                    *position = (*position + 1) % samples.len();
+                }
+                out.len()
+            }
+            Source::Loop { .. } | Source::Silence => 0,
+        };
+        for sample in out[read..].iter_mut() {
+            *sample = 0.0;
+        }
+    }
+}
+
+/// Writes the input round a delay line and plays grains back from it,
+/// feeding them back in as it goes.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    source: Source,
+    line: DelayLine,
+    grains: Grains,
+    scratch: Scratch,
+    peaks: [f32; PEAKS],
+    /// the one being written
+    bin: usize,
+    /// frames until the next grain starts
+    until_grain: f32,
+    frozen: bool,
+    rng: Rng,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, State>, params: Params, source: Source, seed: u64) -> Self {
+        Self {
+            bus,
+            params,
+            source,
+            line: DelayLine::new((LINE_SECONDS * SAMPLE_RATE as f32) as usize),
+            grains: Grains::default(),
+            scratch: Scratch::default(),
+            peaks: [0.0; PEAKS],
+            bin: 0,
+            until_grain: 0.0,
+            frozen: false,
+            rng: Rng::new(seed),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// the caller keeps nothing of the old source, it's dropped here
+    pub fn set_source(&mut self, source: Source) {
+        self.source = source;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.limiter.set_sample_rate(sample_rate as f32);
+        // only when the device changes, the held audio is at the old rate
+        // anyway
+        self.line = DelayLine::new((LINE_SECONDS * sample_rate as f32) as usize);
+        self.grains = Grains::default();
+        self.peaks = [0.0; PEAKS];
+        self.bin = 0;
+    }
+
+    /// a grain from `time` back, or further with scatter, starting `at`
+    /// frames into the block about to be written
+    fn grain(&mut self, at: usize) -> Grain {
+        let sample_rate = self.sample_rate as f32;
+        let length = (self.params.get(SIZE) * sample_rate) as usize;
+        let rate = 2f64.powf(self.params.get(PITCH) as f64 / 12.0);
+        let scatter = self.rng.unit() * self.params.get(SCATTER);
+        let mut delay = (self.params.get(TIME) * (1.0 + scatter) * sample_rate) as f64;
+        if !self.frozen {
+            // a grain going faster than the input mustn't catch up with it,
+            // and reads a block ahead of the writes
+            delay = delay.max((rate - 1.0) * length as f64 + BLOCK as f64 + 2.0);
+        }
+        // overlapping grains add up, keep the sum about as loud as one
+        let overlap = (self.params.get(DENSITY) * self.params.get(SIZE)).max(1.0);
+        Grain {
+            position: (self.line.head() + at) as f64 - delay,
+            rate,
+            age: 0,
+            length,
+            level: overlap.sqrt().recip(),
+            pan: self.rng.range(0.2, 0.8),
+        }
+    }
+
+    fn write(&mut self, sample: f32) {
+        let bin = self.line.head() * PEAKS / self.line.len();
+        // a bin starts over as the head comes round to it
+        if bin != self.bin {
+            self.bin = bin;
+            self.peaks[bin] = 0.0;
+        }
+        self.peaks[bin] = self.peaks[bin].max(sample.abs());
+        self.line.write(sample);
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.set_sample_rate(sample_rate);
+        }
+        for command in self.bus.commands() {
+            match command {
+                Command::Freeze(frozen) => self.frozen = frozen,
+            }
+        }
+
+        let feedback = self.params.get(FEEDBACK);
+        let mix = self.params.get(MIX);
+        let every = sample_rate as f32 / self.params.get(DENSITY);
+        let mut input = [0.0; BLOCK];
+        for block in out.chunks_mut(BLOCK * channels) {
+            let frames = block.len() / channels;
+            let input = &mut input[..frames];
+            self.source.fill(input);
+
+            // the grains for the whole block first, the writes that follow
+            // are at least a block ahead of where any of them reads
+            let mut left = [0.0; BLOCK];
+            let mut right = [0.0; BLOCK];
+            let (left, right) = (&mut left[..frames], &mut right[..frames]);
+            self.grains
+                .process(&self.line, left, right, &mut self.scratch);
+            while self.until_grain < frames as f32 {
+                let at = self.until_grain.max(0.0) as usize;
+                let grain = self.grain(at);
+                if let Some(grain) = self.grains.start(grain) {
+                    grain.process(
+                        &self.line,
+                        &mut left[at..],
+                        &mut right[at..],
+                        &mut self.scratch,
+                    );
+                }
+                self.until_grain += every * self.rng.range(0.5, 1.5);
+            }
+            self.until_grain -= frames as f32;
+
+            for (((frame, dry), left), right) in block
+                .chunks_exact_mut(channels)
+                .zip(input.iter())
+                .zip(left.iter())
+                .zip(right.iter())
+            {
+                if !self.frozen {
+                    // saturating, so a long feedback can't run away
+                    self.write((dry + (left + right) * 0.5 * feedback).tanh());
+                }
+                match frame {
+                    [mono] => *mono = dry * (1.0 - mix) + (left + right) * 0.5 * mix,
+                    [l, r, ..] => {
+                        *l = dry * (1.0 - mix) + left * mix;
+                        *r = dry * (1.0 - mix) + right * mix;
+                    }
+                    [] => {}
+                }
+            }
+        }
+
+        self.bus.publish(State {
+            peaks: self.peaks,
+            head: self.line.head() as f32 / self.line.len() as f32,
+            grains: self.grains,
+            len: self.line.len(),
+            frozen: self.frozen,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
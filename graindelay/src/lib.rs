@@ -0,0 +1,6 @@
+mod app;
+/// public for the benches
+pub mod delay;
+mod dsp;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("graindelay");
+    match &args.render {
+        Some(request) => graindelay::render(request),
+        None if args.headless => graindelay::headless(),
+        None => graindelay::run(),
+    }
+}
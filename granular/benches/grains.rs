@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dsp_common::env::Shape;
+use dsp_common::pan::{Layout, Panner, MAX_SPEAKERS};
+use dsp_common::random::Rng;
+use granular::{leak_table, Grains, Scratch, BLOCK};
+
+/// ten seconds of something to read, grains last a few of them
+fn table() -> &'static [f32] {
+    leak_table((0..480_000).map(|i| (i as f32 * 0.013).sin()).collect())
+}
+
+/// every grain busy, grains that ended since are started again
+fn fill(grains: &mut Grains, rng: &mut Rng) {
+    while grains.activate(0.75, Shape::trapezoid(0.125, 0.125), rng) {}
+}
+
+fn block(c: &mut Criterion) {
+    let table = table();
+    c.bench_function("grains advance", |b| {
+        let (mut grains, mut rng) = (Grains::new(table), Rng::new(7));
+        b.iter(|| {
+            fill(&mut grains, &mut rng);
+            let (mut left, mut right) = ([0.0; BLOCK], [0.0; BLOCK]);
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                let (a, b) = grains.advance();
+                *l = a;
+                *r = b;
+            }
+            black_box((left, right))
+        })
+    });
+    c.bench_function("grains process", |b| {
+        let (mut grains, mut rng) = (Grains::new(table), Rng::new(7));
+        let panner = Panner::new(Layout::STEREO);
+        let mut scratch = Scratch::new();
+        b.iter(|| {
+            fill(&mut grains, &mut rng);
+            let mut lanes = [[0.0; BLOCK]; MAX_SPEAKERS];
+            grains.process(&mut lanes, BLOCK, &panner, &mut scratch);
+            black_box(lanes)
+        })
+    });
+}
+
+criterion_group!(benches, block);
+criterion_main!(benches);
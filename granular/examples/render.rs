@@ -0,0 +1,85 @@
+//! Renders the engine offline, no audio device needed.
+//!
+//! cargo run -p granular --example render -- [input.wav] [output.wav] [seconds]
+
+use granular::{Engine, Event};
+
+const SAMPLE_RATE: u32 = 44_100;
+const CHANNELS: usize = 2;
+const BLOCK: usize = 2048;
+
+fn load(path: &str) -> Vec<f32> {
+    let mut reader = hound::WavReader::open(path).unwrap();
+    match reader.spec().sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().map(Result::unwrap).collect(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u32 << (reader.spec().bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|x| x.unwrap() as f32 * scale)
+                .collect()
+        }
+    }
+}
+
+/// one second of a detuned saw, used when no input is given
+fn fallback() -> Vec<f32> {
+    (0..SAMPLE_RATE)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            ((t * 110.0).fract() + (t * 110.7).fract()) - 1.0
+        })
+        .collect()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let samples = args
+        .first()
+        .filter(|path| !path.is_empty())
+        .map(|path| load(path))
+        .unwrap_or_else(fallback);
+    let output = args.get(1).map(String::as_str).unwrap_or("granular.wav");
+    let seconds: f32 = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(30.0);
+
+    let mut engine = Engine::with_seed(granular::leak_table(samples), SAMPLE_RATE as f32, 0);
+    let mut writer = hound::WavWriter::create(
+        output,
+        hound::WavSpec {
+            channels: CHANNELS as u16,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        },
+    )
+    .unwrap();
+
+    let mut block = vec![0.0; BLOCK * CHANNELS];
+    let (mut voices, mut grains, mut peak) = (0, 0, 0.0f32);
+    let blocks = (seconds * SAMPLE_RATE as f32 / BLOCK as f32).ceil() as usize;
+    for _ in 0..blocks {
+        block.iter_mut().for_each(|s| *s = 0.0);
+        engine.process(&mut block, CHANNELS);
+        while let Some(event) = engine.poll_event() {
+            match event {
+                Event::VoiceStarted { .. } => voices += 1,
+                Event::GrainStarted { .. } => grains += 1,
+                Event::VoiceEnded { .. } => {}
+            }
+        }
+        for &sample in block.iter() {
+            peak = peak.max(sample.abs());
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+
+    println!(
+        "{}: {} voices, {} grains, peak {:.3}, {} events dropped",
+        output,
+        voices,
+        grains,
+        peak,
+        engine.dropped_events()
+    );
+}
@@ -0,0 +1,519 @@
+use crate::grain::BLOCK;
+use crate::voice::{Scratch, Voice};
+use crate::NUM_VOICES;
+use dsp_common::ambisonics::Encoder;
+use dsp_common::denormal::DenormalGuard;
+use dsp_common::env::Shape;
+use dsp_common::pan::{Encoding, Layout, Panner, MAX_SPEAKERS};
+use dsp_common::random::Rng;
+use dsp_common::spherical_head::SphericalHead;
+use dsp_common::tuning;
+use std::collections::VecDeque;
+
+pub type Voices = [Voice; NUM_VOICES];
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// buffers between voice triggers, `None` to only trigger manually
+    pub trigger_interval: Option<usize>,
+    /// buffers between grains within a voice
+    pub grain_interval: usize,
+    /// grain envelope attack and release, larger is steeper
+    pub grain_slope: f32,
+    /// grain envelope curve, see `env::bend`
+    pub grain_curve: f32,
+    /// voice envelope over its length, see `Shape::trapezoid`
+    pub voice_shape: Shape,
+    /// voice length range in seconds
+    pub voice_length: (f32, f32),
+    /// midi note of each voice
+    pub notes: [f32; NUM_VOICES],
+    /// semitones, one is picked at random for each trigger
+    pub transpose: &'static [f32],
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            trigger_interval: Some(64),
+            grain_interval: 4,
+            grain_slope: 8.0,
+            grain_curve: 0.0,
+            voice_shape: Shape::trapezoid(0.25, 0.25),
+            voice_length: (4.0, 24.0),
+            notes: [60.0, 67.0, 74.0, 79.0],
+            transpose: &[-12.0, -12.0, 0.0, 0.0, 0.0, 7.0],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    VoiceStarted { voice: usize, pitch: f32 },
+    VoiceEnded { voice: usize },
+    GrainStarted { voice: usize },
+}
+
+/// Granular synth over a single sample table.
+///
+/// `process` neither allocates nor locks, it is safe to call from an audio callback.
+pub struct Engine {
+    voices: Voices,
+    params: Params,
+    sample_rate: f32,
+    rng: Rng,
+    events: VecDeque<Event>,
+    dropped_events: usize,
+    buffers_since_last_trigger: usize,
+    scratch: Scratch,
+    /// one for each thread past the first, empty when rendering on one
+    lanes: Vec<Lane>,
+    /// where grains go, stereo unless set
+    panner: Panner,
+    /// over `panner` when set
+    encoded: Option<Box<Encoded>>,
+}
+
+/// Where a thread mixes its share of the voices, summed into the output
+/// once they're all done.
+struct Lane {
+    out: Vec<f32>,
+    scratch: Scratch,
+}
+
+/// How one voice leaves its ring.
+// every voice's is the same kind, boxed together in `Encoded`
+#[allow(clippy::large_enum_variant)]
+enum Stage {
+    SphericalHead(SphericalHead),
+    Ambisonic(Encoder),
+}
+
+impl Stage {
+    fn new(encoding: Encoding, elevation: f32, sample_rate: f32) -> Self {
+        match encoding {
+            Encoding::SphericalHead => {
+                Stage::SphericalHead(SphericalHead::new(Layout::RING, elevation, sample_rate))
+            }
+            Encoding::Ambisonic => Stage::Ambisonic(Encoder::new(Layout::RING, elevation)),
+        }
+    }
+
+    fn set_elevation(&mut self, elevation: f32) {
+        match self {
+            Stage::SphericalHead(head) if head.elevation() != elevation => {
+                head.set_elevation(elevation)
+            }
+            Stage::Ambisonic(encoder) => encoder.set_elevation(elevation),
+            Stage::SphericalHead(_) => {}
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        if let Stage::SphericalHead(head) = self {
+            head.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn process(&mut self, ring: &[f32], out: &mut [f32], channels: usize) {
+        match self {
+            Stage::SphericalHead(head) => head.process(ring, out, channels),
+            Stage::Ambisonic(encoder) => encoder.process(ring, out, channels),
+        }
+    }
+}
+
+/// Each voice on a ring of virtual speakers, encoded from there at its own
+/// elevation.
+struct Encoded {
+    encoding: Encoding,
+    panner: Panner,
+    voices: [Stage; NUM_VOICES],
+    /// one block of a voice on the ring, interleaved
+    ring: [f32; BLOCK * MAX_SPEAKERS],
+}
+
+impl Encoded {
+    fn new(encoding: Encoding, elevations: [f32; NUM_VOICES], sample_rate: f32) -> Self {
+        Self {
+            encoding,
+            panner: Panner::new(Layout::RING),
+            voices: std::array::from_fn(|voice| {
+                Stage::new(encoding, elevations[voice], sample_rate)
+            }),
+            ring: [0.0; BLOCK * MAX_SPEAKERS],
+        }
+    }
+
+    fn process(
+        &mut self,
+        voices: &mut Voices,
+        out: &mut [f32],
+        channels: usize,
+        scratch: &mut Scratch,
+    ) {
+        let speakers = self.panner.layout().channels();
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let ring = &mut self.ring[..chunk.len() / channels * speakers];
+            for (voice, stage) in voices.iter_mut().zip(self.voices.iter_mut()) {
+                if !voice.active {
+                    continue;
+                }
+                ring.iter_mut().for_each(|sample| *sample = 0.0);
+                voice.process(ring, speakers, &self.panner, scratch);
+                stage.process(ring, chunk, channels);
+            }
+        }
+    }
+}
+
+impl Engine {
+    pub fn new(table: &'static [f32], sample_rate: f32) -> Self {
+        Self::with_rng(table, sample_rate, Rng::from_entropy())
+    }
+
+    /// reproducible output, for offline renders and tests
+    pub fn with_seed(table: &'static [f32], sample_rate: f32, seed: u64) -> Self {
+        Self::with_rng(table, sample_rate, Rng::new(seed))
+    }
+
+    fn with_rng(table: &'static [f32], sample_rate: f32, rng: Rng) -> Self {
+        Self {
+            voices: [Voice::new(table); NUM_VOICES],
+            params: Params::default(),
+            sample_rate,
+            rng,
+            events: VecDeque::with_capacity(EVENT_CAPACITY),
+            dropped_events: 0,
+            buffers_since_last_trigger: 0,
+            scratch: Scratch::new(),
+            lanes: Vec::new(),
+            panner: Panner::new(Layout::STEREO),
+            encoded: None,
+        }
+    }
+
+    /// the speakers grains are spread over, each at a random angle round
+    /// them, `process` then mixes into as many channels
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.panner = Panner::new(layout);
+    }
+
+    pub fn layout(&self) -> &Layout {
+        self.panner.layout()
+    }
+
+    /// Voices through a ring of virtual speakers round the listener, each
+    /// at its elevation in degrees, encoded into the first channels instead
+    /// of panned over the layout. Renders on one thread whatever
+    /// `set_threads` asks. `None` goes back to the layout's speakers.
+    pub fn set_encoding(&mut self, encoding: Option<Encoding>, elevations: [f32; NUM_VOICES]) {
+        match (&mut self.encoded, encoding) {
+            // no allocation when only the elevations move
+            (Some(encoded), Some(encoding)) if encoded.encoding == encoding => {
+                for (stage, &elevation) in encoded.voices.iter_mut().zip(elevations.iter()) {
+                    stage.set_elevation(elevation);
+                }
+            }
+            _ => {
+                let sample_rate = self.sample_rate;
+                self.encoded = encoding
+                    .map(|encoding| Box::new(Encoded::new(encoding, elevations, sample_rate)));
+            }
+        }
+    }
+
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoded.as_ref().map(|encoded| encoded.encoding)
+    }
+
+    /// Renders the voices split across `threads`, each into its own buffer.
+    /// Starts threads every block, so it's for offline renders and big
+    /// buffers, not the audio callback. 1 renders on the caller's thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        let lanes = threads.clamp(1, NUM_VOICES) - 1;
+        self.lanes.resize_with(lanes, || Lane {
+            out: Vec::new(),
+            scratch: Scratch::new(),
+        });
+    }
+
+    pub fn threads(&self) -> usize {
+        self.lanes.len() + 1
+    }
+
+    /// takes effect on grains and voices started from now on
+    pub fn load_table(&mut self, table: &'static [f32]) {
+        for voice in self.voices.iter_mut() {
+            voice.set_table(table);
+        }
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: Params) {
+        self.params = params;
+    }
+
+    pub fn voices(&self) -> &Voices {
+        &self.voices
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// takes effect on grains and voices started from now on
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        if let Some(encoded) = &mut self.encoded {
+            for stage in encoded.voices.iter_mut() {
+                stage.set_sample_rate(sample_rate);
+            }
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// events lost because nobody polled them in time
+    pub fn dropped_events(&self) -> usize {
+        self.dropped_events
+    }
+
+    fn emit(&mut self, event: Event) {
+        if self.events.len() < EVENT_CAPACITY {
+            self.events.push_back(event);
+        } else {
+            self.dropped_events += 1;
+        }
+    }
+
+    /// start a random idle voice, false if they are all busy
+    pub fn trigger(&mut self) -> bool {
+        let index = match self.idle_voice() {
+            Some(index) => index,
+            None => return false,
+        };
+        let transpose = match self.params.transpose.len() {
+            0 => 0.0,
+            len => self.params.transpose[self.rng.below(len)],
+        };
+        let (min, max) = self.params.voice_length;
+        let seconds = if max > min {
+            self.rng.range(min, max)
+        } else {
+            min
+        };
+        self.start(index, self.params.notes[index] + transpose, seconds);
+        true
+    }
+
+    /// start a random idle voice on midi `note` for `seconds`, whatever the
+    /// params say, false if they are all busy
+    pub fn play(&mut self, note: f32, seconds: f32) -> bool {
+        match self.idle_voice() {
+            Some(index) => {
+                self.start(index, note, seconds);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn idle_voice(&mut self) -> Option<usize> {
+        let idle = self.voices.iter().filter(|voice| !voice.active).count();
+        if idle == 0 {
+            return None;
+        }
+        let nth = self.rng.below(idle);
+        self.voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| !voice.active)
+            .nth(nth)
+            .map(|(i, _)| i)
+    }
+
+    fn start(&mut self, index: usize, note: f32, seconds: f32) {
+        let pitch = tuning::midi_to_freq(note);
+        let length = (seconds * self.sample_rate) as usize;
+        self.voices[index].activate(length, pitch, self.params.voice_shape);
+        self.emit(Event::VoiceStarted {
+            voice: index,
+            pitch,
+        });
+    }
+
+    /// called once per block, before rendering
+    fn update(&mut self) {
+        if let Some(interval) = self.params.trigger_interval {
+            if self.buffers_since_last_trigger >= interval {
+                self.trigger();
+                self.buffers_since_last_trigger = 0;
+            }
+            self.buffers_since_last_trigger += 1;
+        }
+
+        for voice in 0..NUM_VOICES {
+            if !self.voices[voice].active {
+                continue;
+            }
+            let Params {
+                grain_interval,
+                grain_slope,
+                grain_curve,
+                ..
+            } = self.params;
+            // below a slope of 2 the edges meet in a triangle
+            let edge = (1.0 / grain_slope).min(0.5);
+            if self.voices[voice].update_grains(
+                grain_interval,
+                Shape::trapezoid(edge, edge).curve(grain_curve),
+                self.sample_rate,
+                &mut self.rng,
+            ) {
+                self.emit(Event::GrainStarted { voice });
+            }
+        }
+    }
+
+    /// mixes one block into interleaved `out`, which should be zeroed by the caller
+    pub fn process(&mut self, out: &mut [f32], channels: usize) {
+        self.update();
+        let mut active = [false; NUM_VOICES];
+        for (voice, active) in self.voices.iter().zip(active.iter_mut()) {
+            *active = voice.active;
+        }
+        if let Some(encoded) = &mut self.encoded {
+            encoded.process(&mut self.voices, out, channels, &mut self.scratch);
+        } else if self.lanes.is_empty() {
+            for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+                voice.process(out, channels, &self.panner, &mut self.scratch);
+            }
+        } else {
+            self.process_parallel(out, channels);
+        }
+        for (voice, was_active) in active.iter().enumerate() {
+            if *was_active && !self.voices[voice].active {
+                self.emit(Event::VoiceEnded { voice });
+            }
+        }
+    }
+
+    /// the first share of the voices on this thread, the others each on
+    /// their own, then every lane summed into `out`
+    fn process_parallel(&mut self, out: &mut [f32], channels: usize) {
+        let share = (NUM_VOICES + self.lanes.len()) / (self.lanes.len() + 1);
+        let mut shares = self.voices.chunks_mut(share);
+        let first = shares.next().unwrap_or_default();
+        let scratch = &mut self.scratch;
+        let panner = &self.panner;
+        // lanes left without a share stay empty and aren't summed
+        for lane in self.lanes.iter_mut() {
+            lane.out.clear();
+        }
+        let lanes = &mut self.lanes;
+        let len = out.len();
+        std::thread::scope(|scope| {
+            for (voices, lane) in shares.zip(lanes.iter_mut()) {
+                scope.spawn(move || {
+                    // the caller's flush to zero doesn't carry over
+                    let _denormals = DenormalGuard::new();
+                    lane.out.resize(len, 0.0);
+                    for voice in voices.iter_mut().filter(|voice| voice.active) {
+                        voice.process(&mut lane.out, channels, panner, &mut lane.scratch);
+                    }
+                });
+            }
+            for voice in first.iter_mut().filter(|voice| voice.active) {
+                voice.process(out, channels, panner, scratch);
+            }
+        });
+        for lane in self.lanes.iter().filter(|lane| lane.out.len() == len) {
+            for (sample, lane) in out.iter_mut().zip(lane.out.iter()) {
+                *sample += lane;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leak_table;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const FRAMES: usize = 512;
+
+    fn table() -> &'static [f32] {
+        leak_table(
+            (0..4096)
+                .map(|i| (i as f32 / 4096.0 * std::f32::consts::TAU).sin())
+                .collect(),
+        )
+    }
+
+    fn render(engine: &mut Engine, buffers: usize) -> Vec<f32> {
+        let mut out = vec![0.0; FRAMES * 2 * buffers];
+        for buffer in out.chunks_mut(FRAMES * 2) {
+            engine.process(buffer, 2);
+        }
+        out
+    }
+
+    #[test]
+    fn the_same_seed_renders_the_same() {
+        let table = table();
+        let mut a = Engine::with_seed(table, SAMPLE_RATE, 7);
+        let mut b = Engine::with_seed(table, SAMPLE_RATE, 7);
+        let out = render(&mut a, 256);
+        assert_eq!(out, render(&mut b, 256));
+        assert!(out.iter().any(|sample| *sample != 0.0));
+        assert!(out.iter().all(|sample| sample.is_finite()));
+    }
+
+    #[test]
+    fn a_played_voice_sounds_then_ends() {
+        let mut engine = Engine::with_seed(table(), SAMPLE_RATE, 7);
+        engine.set_params(Params {
+            trigger_interval: None,
+            grain_interval: 1,
+            ..Params::default()
+        });
+        assert!(engine.play(60.0, 0.5));
+        assert!(matches!(
+            engine.poll_event(),
+            Some(Event::VoiceStarted { .. })
+        ));
+
+        let mut sounded = false;
+        let mut ended = false;
+        for _ in 0..100 {
+            sounded |= render(&mut engine, 1).iter().any(|sample| *sample != 0.0);
+            while let Some(event) = engine.poll_event() {
+                ended |= matches!(event, Event::VoiceEnded { .. });
+            }
+        }
+        assert!(sounded && ended);
+        assert!(engine.voices().iter().all(|voice| !voice.active));
+        assert!(render(&mut engine, 1).iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn threads_render_the_same_voices() {
+        let table = table();
+        let mut one = Engine::with_seed(table, SAMPLE_RATE, 7);
+        let mut four = Engine::with_seed(table, SAMPLE_RATE, 7);
+        four.set_threads(4);
+        let (one, four) = (render(&mut one, 64), render(&mut four, 64));
+        for (one, four) in one.iter().zip(four.iter()) {
+            assert!((one - four).abs() < 1e-5);
+        }
+    }
+}
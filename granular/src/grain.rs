@@ -0,0 +1,273 @@
+use crate::NUM_GRAINS;
+use dsp_common::env::{Envelope, Shape};
+use dsp_common::pan::{self, Panner, MAX_SPEAKERS};
+use dsp_common::random::Rng;
+use dsp_common::{filut_clamped, simd};
+
+/// Looping read over a slice, the phase is in samples.
+#[derive(Clone, Copy, Debug)]
+pub struct TableReader {
+    table: &'static [f32],
+    phase: f32,
+    increment: f32,
+}
+
+impl TableReader {
+    pub fn new(table: &'static [f32], increment: f32) -> Self {
+        Self {
+            table,
+            phase: 0.0,
+            increment,
+        }
+    }
+
+    pub fn step(&mut self) -> f32 {
+        if self.table.is_empty() {
+            return 0.0;
+        }
+        let sample = filut_clamped(self.table, self.phase);
+        self.phase = (self.phase + self.increment).rem_euclid(self.table.len() as f32);
+        sample
+    }
+
+    /// the next `out.len()` steps at once
+    fn fill(&mut self, out: &mut [f32]) {
+        if self.table.is_empty() {
+            out.iter_mut().for_each(|sample| *sample = 0.0);
+            return;
+        }
+        let len = self.table.len() as f32;
+        for phase in out.iter_mut() {
+            *phase = self.phase;
+            self.phase = (self.phase + self.increment).rem_euclid(len);
+        }
+        simd::filut_clamped_block(self.table, out);
+    }
+}
+
+/// frames rendered per grain at a time
+pub const BLOCK: usize = 64;
+
+/// a block for each output channel
+pub type Lanes = [[f32; BLOCK]; MAX_SPEAKERS];
+
+/// Working space for rendering grains a block at a time.
+pub struct Scratch {
+    samples: [f32; BLOCK],
+    env: [f32; BLOCK],
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self {
+            samples: [0.0; BLOCK],
+            env: [0.0; BLOCK],
+        }
+    }
+}
+
+impl Default for Scratch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn random_slice(table: &'static [f32], rng: &mut Rng) -> &'static [f32] {
+    let table_len = table.len() as f32;
+    let start = (rng.range(0.0, 0.4) * table_len) as usize;
+    let length = (rng.range(0.8, 1.0) * table_len) as usize;
+    let end = (start + length).min(table.len());
+    &table[start..end]
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Grain {
+    pub active: bool,
+    pub volume: f32,
+    pub pan: f32,
+    pub reader: TableReader,
+    pub slice: &'static [f32],
+    env: Envelope,
+}
+
+impl Grain {
+    fn idle(table: &'static [f32]) -> Self {
+        Self {
+            active: false,
+            volume: 0.0,
+            pan: 0.5,
+            reader: TableReader::new(table, 0.0),
+            slice: table,
+            env: Envelope::new(Shape::trapezoid(0.125, 0.125)),
+        }
+    }
+
+    fn generate(table: &'static [f32], increment: f32, shape: Shape, rng: &mut Rng) -> Self {
+        let slice = random_slice(table, rng);
+        Self {
+            active: true,
+            env: {
+                let mut env = Envelope::new(shape);
+                env.gate_on(increment / slice.len().max(1) as f32);
+                env
+            },
+            slice,
+            reader: TableReader::new(slice, increment),
+            volume: rng.unit().powf(0.3),
+            pan: rng.unit(),
+        }
+    }
+
+    /// one sample, for drawing
+    pub fn advance(&mut self) -> (f32, f32) {
+        if !self.active {
+            return (0.0, 0.0);
+        }
+
+        let vol = self.env.step() * self.volume;
+        self.active = self.env.is_active();
+        let sample = self.reader.step();
+        pan::linear(sample * vol, self.pan)
+    }
+
+    /// the same as `advance` over `frames` of up to `BLOCK`, mixed into the
+    /// two `lanes` either side of it scaled by `gain`
+    fn process(
+        &mut self,
+        lanes: &mut Lanes,
+        frames: usize,
+        panner: &Panner,
+        gain: f32,
+        scratch: &mut Scratch,
+    ) {
+        if !self.active {
+            return;
+        }
+
+        let available = frames;
+        let mut frames = 0;
+        for vol in scratch.env[..available].iter_mut() {
+            *vol = self.env.step() * self.volume;
+            frames += 1;
+            if !self.env.is_active() {
+                self.active = false;
+                break;
+            }
+        }
+
+        let samples = &mut scratch.samples[..frames];
+        self.reader.fill(samples);
+        simd::multiply(samples, &scratch.env[..frames]);
+        let (a, b, pan) = panner.pair(panner.spread(self.pan));
+        if a == b {
+            for (out, sample) in lanes[a].iter_mut().zip(samples.iter()) {
+                *out += sample * gain;
+            }
+            return;
+        }
+        // the lower channel's lane first, whichever side of the pair it's on
+        let (low, high) = lanes.split_at_mut(a.max(b));
+        let (first, second) = (&mut low[a.min(b)], &mut high[0]);
+        let (left, right) = if a < b {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        simd::pan_accumulate(
+            samples,
+            pan,
+            gain,
+            &mut left[..frames],
+            &mut right[..frames],
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Grains {
+    pub grains: [Grain; NUM_GRAINS],
+    table: &'static [f32],
+}
+
+impl Grains {
+    pub fn new(table: &'static [f32]) -> Self {
+        Self {
+            grains: [Grain::idle(table); NUM_GRAINS],
+            table,
+        }
+    }
+
+    pub(crate) fn set_table(&mut self, table: &'static [f32]) {
+        self.table = table;
+    }
+
+    /// false when every grain is busy
+    pub fn activate(&mut self, increment: f32, shape: Shape, rng: &mut Rng) -> bool {
+        for grain in self.grains.iter_mut() {
+            if !grain.active {
+                *grain = Grain::generate(self.table, increment, shape, rng);
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn active(&self) -> usize {
+        self.grains.iter().filter(|grain| grain.active).count()
+    }
+
+    /// one stereo frame of every grain, the per-frame loop `process`
+    /// replaced, kept as its reference
+    pub fn advance(&mut self) -> (f32, f32) {
+        const INV_NUM_GRAINS: f32 = 1.0 / NUM_GRAINS as f32;
+        self.grains
+            .iter_mut()
+            .fold((0.0, 0.0), |(left, right), grain| {
+                let (l, r) = grain.advance();
+                (left + l * INV_NUM_GRAINS, right + r * INV_NUM_GRAINS)
+            })
+    }
+
+    /// mixes every grain into `lanes` where `panner` places it, up to
+    /// `BLOCK` frames
+    pub fn process(
+        &mut self,
+        lanes: &mut Lanes,
+        frames: usize,
+        panner: &Panner,
+        scratch: &mut Scratch,
+    ) {
+        const INV_NUM_GRAINS: f32 = 1.0 / NUM_GRAINS as f32;
+        for grain in self.grains.iter_mut() {
+            grain.process(lanes, frames, panner, INV_NUM_GRAINS, scratch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leak_table;
+    use dsp_common::pan::Layout;
+
+    #[test]
+    fn blocks_match_the_per_frame_loop() {
+        let table = leak_table((0..4096).map(|i| (i as f32 * 0.013).sin()).collect());
+        let mut rng = Rng::new(7);
+        let mut grains = Grains::new(table);
+        while grains.activate(0.75, Shape::trapezoid(0.125, 0.125), &mut rng) {}
+        let mut reference = grains;
+
+        let panner = Panner::new(Layout::STEREO);
+        let mut scratch = Scratch::new();
+        for _ in 0..16 {
+            let mut lanes = [[0.0; BLOCK]; MAX_SPEAKERS];
+            grains.process(&mut lanes, BLOCK, &panner, &mut scratch);
+            for (block_left, block_right) in lanes[0].iter().zip(lanes[1].iter()) {
+                let (left, right) = reference.advance();
+                assert!((block_left - left).abs() < 1e-5);
+                assert!((block_right - right).abs() < 1e-5);
+            }
+        }
+    }
+}
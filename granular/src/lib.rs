@@ -0,0 +1,17 @@
+//! Granular synthesis over a sample table, independent of any audio backend.
+
+mod engine;
+mod grain;
+mod voice;
+
+pub use engine::{Engine, Event, Params, Voices};
+pub use grain::{Grain, Grains, Lanes, Scratch, TableReader, BLOCK};
+pub use voice::Voice;
+
+pub const NUM_GRAINS: usize = 8;
+pub const NUM_VOICES: usize = 4;
+
+/// keeps samples alive for the rest of the program, tables are shared by every grain
+pub fn leak_table(samples: Vec<f32>) -> &'static [f32] {
+    Box::leak(samples.into_boxed_slice())
+}
@@ -0,0 +1,125 @@
+use crate::grain::{self, Grains, Lanes, BLOCK};
+use dsp_common::env::{Envelope, Shape};
+use dsp_common::pan::{Panner, MAX_SPEAKERS};
+use dsp_common::random::Rng;
+use dsp_common::{simd, tuning};
+
+/// Working space for rendering a voice, shared by all of them.
+pub(crate) struct Scratch {
+    lanes: Lanes,
+    env: [f32; BLOCK],
+    grain: grain::Scratch,
+}
+
+impl Scratch {
+    pub(crate) fn new() -> Self {
+        Self {
+            lanes: [[0.0; BLOCK]; MAX_SPEAKERS],
+            env: [0.0; BLOCK],
+            grain: grain::Scratch::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Voice {
+    pub grains: Grains,
+    pub active: bool,
+    pub pitch: f32,
+
+    length: usize,
+    env: Envelope,
+    buffers_since_last_trigger: usize,
+}
+
+impl Voice {
+    pub fn new(table: &'static [f32]) -> Self {
+        Self {
+            grains: Grains::new(table),
+            active: false,
+            pitch: 440.0,
+            length: 0,
+            env: Envelope::new(Shape::trapezoid(0.25, 0.25)),
+            buffers_since_last_trigger: 0,
+        }
+    }
+
+    /// the envelope's gain, 0 while idle
+    pub fn level(&self) -> f32 {
+        if self.active {
+            self.env.value()
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn set_table(&mut self, table: &'static [f32]) {
+        self.grains.set_table(table);
+    }
+
+    /// called at buffer rate
+    pub(crate) fn update_grains(
+        &mut self,
+        interval: usize,
+        shape: Shape,
+        sample_rate: f32,
+        rng: &mut Rng,
+    ) -> bool {
+        let mut triggered = false;
+        if self.buffers_since_last_trigger >= interval {
+            let increment = self.pitch * tuning::midi_to_freq(60.0) / sample_rate;
+            triggered = self.grains.activate(increment, shape, rng);
+            self.buffers_since_last_trigger = 0;
+        }
+        self.buffers_since_last_trigger += 1;
+        triggered
+    }
+
+    /// `shape` as fractions of `length`, see `Shape::trapezoid`
+    pub(crate) fn activate(&mut self, length: usize, pitch: f32, shape: Shape) {
+        self.length = length;
+        self.env.set_shape(shape);
+        self.env.trigger(length as f32);
+        self.pitch = pitch;
+        self.active = true;
+    }
+
+    /// mixes into interleaved `out`, its first channels laid out as
+    /// `panner`'s speakers
+    pub(crate) fn process(
+        &mut self,
+        out: &mut [f32],
+        channels: usize,
+        panner: &Panner,
+        scratch: &mut Scratch,
+    ) {
+        // grains land on every speaker, only those the stream has are heard
+        let speakers = panner.layout().channels();
+        let heard = speakers.min(channels);
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let frames = chunk.len() / channels;
+            let env = &mut scratch.env[..frames];
+
+            for lane in scratch.lanes[..speakers].iter_mut() {
+                lane[..frames].iter_mut().for_each(|sample| *sample = 0.0);
+            }
+            self.grains
+                .process(&mut scratch.lanes, frames, panner, &mut scratch.grain);
+
+            for gain in env.iter_mut() {
+                *gain = self.env.step();
+            }
+            self.active = self.env.is_active();
+            let lanes = &mut scratch.lanes[..heard];
+            for lane in lanes.iter_mut() {
+                simd::multiply(&mut lane[..frames], env);
+            }
+
+            for (i, frame) in chunk.chunks_exact_mut(channels).enumerate() {
+                for (out, lane) in frame.iter_mut().zip(lanes.iter()) {
+                    *out += lane[i];
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::dsp::{self, Command, Engine, State};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use app_common::widget::Level;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a new figure without waiting for this one to die away
+const RESTART: Key = Key::N;
+
+/// pen positions drawn per second of pendulum time
+const POINTS_PER_SECOND: f32 = 400.0;
+/// beyond this the trace is drawn coarser instead of longer
+const MAX_POINTS: usize = 24_000;
+/// of the window's shorter side
+const SCALE: f32 = 0.45;
+
+widget_ids! {
+    struct Ids {
+        restart,
+        levels[],
+    }
+}
+
+fn engine(config: &Config, params: &Params, seed: u64) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), seed);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("harmonograph", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the pendulums without a window or audio device, figures follow each
+/// other as they die away
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("harmonograph", &config::path("harmonograph"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("harmonograph", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, seed);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the pendulums on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("harmonograph", &config::path("harmonograph"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("harmonograph", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, seed);
+    render::headless("harmonograph", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    bus: UiEnd<Command, State>,
+    /// the figure as of the last buffer played
+    state: State,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("harmonograph");
+    let (config, session_seed) = shell::load_config("harmonograph", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("harmonograph", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("harmonograph", &config, &params);
+    let serial = serial::Sensors::from_config("harmonograph", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("harmonograph", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params, seed);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "harmonograph", &config, stream.stats());
+    let shell = Shell::new(
+        "harmonograph",
+        config,
+        &config_path,
+        "phosphor",
+        stream_config,
+    )
+    .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        bus,
+        state: State::default(),
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if model
+            .shell
+            .setup_key_pressed(key, &mut model.stream)
+            .taken()
+        {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        if key == RESTART {
+            let _ = model.bus.send(Command::Restart);
+        }
+        model.hud.key_pressed(key);
+        model
+            .shell
+            .key_pressed(app, key, &model.stream, Some(model.seed));
+    }
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("new figure")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.restart, ui)
+    {
+        let _ = model.bus.send(Command::Restart);
+    }
+
+    // how much swing each pendulum has left
+    let pendulums = model.state.figure.pendulums();
+    if model.ids.levels.len() != pendulums.len() {
+        model
+            .ids
+            .levels
+            .resize(pendulums.len(), &mut ui.widget_id_generator());
+    }
+    for (i, pendulum) in pendulums.iter().enumerate() {
+        let level = Level::new(pendulum.amplitude(model.state.time), 0.0, pendulum.amp)
+            .with_style(palette.control_style())
+            .w_h(20.0, 100.0);
+        let level = if i == 0 {
+            level.down_from(model.ids.restart, 20.0)
+        } else {
+            level.right(10.0)
+        };
+        level.set(model.ids.levels[i], ui);
+    }
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let State { figure, time } = model.state;
+    let rect = app.window_rect();
+    let scale = rect.w().min(rect.h()) * SCALE;
+    let points = ((time * POINTS_PER_SECOND) as usize).clamp(2, MAX_POINTS);
+    let step = time / (points - 1) as f32;
+    let trace = (0..points).map(|i| {
+        let (x, y) = figure.point(i as f32 * step);
+        pt2(x * scale, y * scale)
+    });
+    draw.polyline()
+        .weight(1.0)
+        .points(trace)
+        .color(theme::color(palette.line));
+
+    let (x, y) = figure.point(time);
+    draw.ellipse()
+        .x_y(x * scale, y * scale)
+        .radius(4.0)
+        .color(theme::color(palette.accent(0)));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,150 @@
+use crate::pendulum::{Figure, MAX_PENDULUMS};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::random::Rng;
+use dsp_common::Wavetable;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// swings per second of the first pendulum, the others are ratios of it
+const SWING: f32 = 2.0;
+/// a figure is done once its pendulums are down to this of their swing
+const STILL: f32 = 0.01;
+const TABLE_SIZE: usize = 1024;
+
+pub const PENDULUMS: usize = 0;
+pub const SPEED: usize = 1;
+pub const DAMPING: usize = 2;
+pub const DETUNE: usize = 3;
+pub const PITCH: usize = 4;
+pub const VOLUME: usize = 5;
+
+/// the next figure's shape, then how it plays
+pub static PARAMS: [ParamSpec; 6] = [
+    ParamSpec::new("pendulums", 2.0, 4.0, 4.0),
+    ParamSpec::new("speed", 0.25, 8.0, 1.0).curve(Curve::Exponential),
+    ParamSpec::new("damping", 0.005, 0.5, 0.03).curve(Curve::Exponential),
+    ParamSpec::new("detune", 0.0, 0.02, 0.004),
+    ParamSpec::new("pitch", 55.0, 440.0, 110.0)
+        .curve(Curve::Exponential)
+        .unit("Hz"),
+    ParamSpec::new("volume", 0.0, 1.0, 0.5),
+];
+
+pub enum Command {
+    /// a new figure now rather than once this one is still
+    Restart,
+}
+
+/// What the window draws, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    pub figure: Figure,
+    /// pendulum time, seconds at a speed of 1
+    pub time: f32,
+}
+
+/// One decaying sine per pendulum, `pitch` over `SWING` times faster, so
+/// the chord dies away with the drawing. Pen pendulums lean left, paper
+/// pendulums right.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    rng: Rng,
+    state: State,
+    phases: [f32; MAX_PENDULUMS],
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, State>, params: Params, seed: u64) -> Self {
+        let mut engine = Self {
+            bus,
+            params,
+            rng: Rng::new(seed),
+            state: State::default(),
+            phases: [0.0; MAX_PENDULUMS],
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        };
+        engine.restart();
+        engine
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    fn restart(&mut self) {
+        self.state = State {
+            figure: Figure::random(
+                &mut self.rng,
+                self.params.get(PENDULUMS).round() as usize,
+                SWING,
+                self.params.get(DETUNE),
+                self.params.get(DAMPING),
+            ),
+            time: 0.0,
+        };
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        let mut restart = false;
+        for command in self.bus.commands() {
+            match command {
+                Command::Restart => restart = true,
+            }
+        }
+        if restart || self.state.figure.is_still(self.state.time, STILL) {
+            self.restart();
+        }
+
+        let sample_time = 1.0 / sample_rate as f32;
+        let step = self.params.get(SPEED) * sample_time;
+        let pitch = self.params.get(PITCH) / SWING * sample_time;
+        let pendulums = self.state.figure.pendulums();
+        // each axis reaches 1 at most, and so does each channel
+        let gain = self.params.get(VOLUME) * 0.5;
+        let time = &mut self.state.time;
+        for frame in out.chunks_exact_mut(channels) {
+            let (mut left, mut right) = (0.0, 0.0);
+            for (i, (pendulum, phase)) in pendulums.iter().zip(&mut self.phases).enumerate() {
+                let sample = self.sine.at(*phase) * pendulum.amplitude(*time);
+                *phase = (*phase + pendulum.freq * pitch).fract();
+                if i % 2 == 0 {
+                    left += sample * 0.75;
+                    right += sample * 0.25;
+                } else {
+                    left += sample * 0.25;
+                    right += sample * 0.75;
+                }
+            }
+            match frame {
+                [mono] => *mono = (left + right) * 0.5 * gain,
+                [l, r, ..] => {
+                    *l = left * gain;
+                    *r = right * gain;
+                }
+                [] => {}
+            }
+            *time += step;
+        }
+        self.bus.publish(self.state);
+        self.limiter.process_interleaved(out, channels);
+    }
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("harmonograph");
+    match &args.render {
+        Some(request) => harmonograph::render(request),
+        None if args.headless => harmonograph::headless(),
+        None => harmonograph::run(),
+    }
+}
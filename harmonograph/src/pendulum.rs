@@ -0,0 +1,98 @@
+//! Damped pendulums and the figure they draw together.
+//!
+//! A harmonograph hangs a pen from one set of pendulums and the paper from
+//! another, each swinging a sine that dies away. Odd pendulums move the pen
+//! across, even ones up and down, so two draw a damped Lissajous figure and
+//! four the classic tangles. Everything is a function of time, the drawing
+//! and the chord read the same pendulums at the same `t`.
+
+use dsp_common::random::Rng;
+use std::f32::consts::TAU;
+
+pub const MIN_PENDULUMS: usize = 2;
+pub const MAX_PENDULUMS: usize = 4;
+
+/// the ratios pendulums swing at, against the first
+const RATIOS: [f32; 6] = [1.0, 1.5, 2.0, 3.0, 4.0 / 3.0, 1.25];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pendulum {
+    /// swings per second
+    pub freq: f32,
+    /// in turns
+    pub phase: f32,
+    pub amp: f32,
+    /// per second, the amplitude is `amp * e^(-damping * t)`
+    pub damping: f32,
+}
+
+impl Pendulum {
+    pub fn amplitude(&self, t: f32) -> f32 {
+        self.amp * (-self.damping * t).exp()
+    }
+
+    pub fn position(&self, t: f32) -> f32 {
+        (TAU * (self.freq * t + self.phase)).sin() * self.amplitude(t)
+    }
+}
+
+/// Up to `MAX_PENDULUMS`, kept inline so figures can cross the audio bus.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Figure {
+    pendulums: [Pendulum; MAX_PENDULUMS],
+    len: usize,
+}
+
+impl Figure {
+    /// `count` pendulums near small whole ratios of `freq`, `detune` apart
+    /// from them, and dying away at around `damping`
+    pub fn random(rng: &mut Rng, count: usize, freq: f32, detune: f32, damping: f32) -> Self {
+        let len = count.clamp(MIN_PENDULUMS, MAX_PENDULUMS);
+        let mut figure = Self {
+            pendulums: [Pendulum::default(); MAX_PENDULUMS],
+            len,
+        };
+        // the axes share their reach between their pendulums
+        let per_axis = [len - len / 2, len / 2];
+        for (i, pendulum) in figure.pendulums[..len].iter_mut().enumerate() {
+            let ratio = match i {
+                0 => 1.0,
+                _ => rng.pick(&RATIOS).copied().unwrap_or(1.0),
+            };
+            *pendulum = Pendulum {
+                freq: freq * ratio * (1.0 + rng.bipolar() * detune),
+                phase: rng.unit(),
+                amp: rng.range(0.5, 1.0) / per_axis[i % 2] as f32,
+                damping: damping * rng.range(0.5, 1.5),
+            };
+        }
+        figure
+    }
+
+    pub fn pendulums(&self) -> &[Pendulum] {
+        &self.pendulums[..self.len]
+    }
+
+    /// where the pen is on the paper, within -1 to 1 on both axes
+    pub fn point(&self, t: f32) -> (f32, f32) {
+        self.pendulums()
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(x, y), (i, pendulum)| {
+                let p = pendulum.position(t);
+                if i % 2 == 0 {
+                    (x + p, y)
+                } else {
+                    (x, y + p)
+                }
+            })
+    }
+
+    /// once every pendulum is below `threshold` of its swing there's
+    /// nothing left to see or hear
+    pub fn is_still(&self, t: f32, threshold: f32) -> bool {
+        self.pendulums()
+            .iter()
+            .all(|pendulum| pendulum.amplitude(t) < threshold * pendulum.amp.max(f32::EPSILON))
+    }
+}
@@ -0,0 +1,255 @@
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::cli;
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::link::{Link, LinkClock};
+use app_common::render::{self, Render, Request};
+use app_common::shell::{self, Shell};
+use app_common::speakers::{self, Spatial};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme;
+use dsp_common::limiter::Limiter;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::path::Path;
+
+/// asked of the stream unless the config says otherwise
+const SAMPLE_RATE: usize = 44_100;
+const BUFFER_SIZE: usize = 512;
+
+/// sequencers read the shared transport from `clock`
+struct Engine {
+    clock: LinkClock,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        self.limiter.process_interleaved(out, channels);
+    }
+}
+
+widget_ids! {
+    struct Ids {
+        link,
+    }
+}
+
+/// the config with `--session` installed and the flags over it
+fn load_config(config_path: &Path) -> Config {
+    if cli::args().preset.is_some() {
+        eprintln!("kima: has no presets, ignoring --preset");
+    }
+    shell::load_config("kima", config_path).0
+}
+
+fn engine(config: &Config, clock: LinkClock) -> Engine {
+    let mut engine = Engine {
+        clock,
+        limiter: Limiter::new(SAMPLE_RATE as f32),
+        sample_rate: SAMPLE_RATE as u32,
+    };
+    engine.limiter.set_bypass(config.bypass_limiter);
+    engine
+}
+
+/// as many channels as `spatial` takes
+fn stream_config(config: &Config, spatial: &Spatial) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(SAMPLE_RATE as u32),
+        frames_per_buffer: Some(BUFFER_SIZE),
+        channels: Some(spatial.channels()),
+        jack: config.jack_client("kima", spatial.ports()),
+        ..StreamConfig::default()
+    })
+}
+
+/// the engine without a window or audio device
+pub fn render(request: &Request) {
+    let config = load_config(&config::path("kima"));
+    let link = Link::new(120.0, 4.0);
+    let mut engine = engine(&config, link.clock());
+    let channels = speakers::spatial(config.speakers.as_ref()).channels();
+    request.run(&mut engine, SAMPLE_RATE as u32, channels, BUFFER_SIZE);
+}
+
+/// the engine on the audio device without a window
+pub fn headless() {
+    let config = load_config(&config::path("kima"));
+    let link = Link::new(120.0, 4.0);
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    render::headless(
+        "kima",
+        engine(&config, link.clock()),
+        stream_config(&config, &spatial),
+    );
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    link: Link,
+    stream: Supervisor<Engine>,
+    /// the channels the stream opened with, others wait for a restart
+    spatial: Spatial,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    /// callback timing, and a banner when the engine panics
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    app.set_loop_mode(LoopMode::rate_fps(SAMPLE_RATE as f64 / BUFFER_SIZE as f64));
+
+    let config_path = config::path("kima");
+    let config = load_config(&config_path);
+    config.build_window(app, view);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("kima", startup::Error::Ui(format!("{:?}", e))));
+    let link = Link::new(120.0, 4.0);
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    let mut stream = Supervisor::idle(
+        engine(&config, link.clock()),
+        stream_config(&config, &spatial),
+    );
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "kima", &config, stream.stats());
+    let shell = Shell::new("kima", config, &config_path, "midnight", move |config| {
+        stream_config(config, &spatial)
+    });
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        link,
+        stream,
+        spatial,
+        errors,
+        hud,
+        kiosk,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if model
+            .shell
+            .setup_key_pressed(key, &mut model.stream)
+            .taken()
+        {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        model.hud.key_pressed(key);
+        model.shell.key_pressed(app, key, &model.stream, None);
+    }
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.limiter.set_bypass(bypass));
+        }
+        if speakers::spatial(config.speakers.as_ref()).ports() != model.spatial.ports() {
+            eprintln!("kima: the new speaker layout takes effect on restart");
+        }
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    model
+        .link
+        .panel(model.ids.link, model.shell.themes.current(), ui);
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(model: &Model, draw: &Draw) {
+    draw.background()
+        .color(theme::color(model.shell.themes.current().background));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
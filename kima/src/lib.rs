@@ -0,0 +1,7 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod app;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::{headless, render, run};
@@ -0,0 +1,19 @@
+//! Browser build, the visuals only for now.
+//!
+//! wasm-pack build kima --target web
+
+use app_common::web::{self, Canvas};
+use wasm_bindgen::prelude::*;
+
+/// nannou's DARKBLUE
+const BACKGROUND: [f32; 3] = [0.0, 0.0, 0.545];
+
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let canvas = Canvas::fullscreen("kima")?;
+    web::animate(move |_seconds| {
+        canvas.resize();
+        canvas.background(BACKGROUND);
+    });
+    Ok(())
+}
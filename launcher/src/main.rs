@@ -0,0 +1,151 @@
+use app_common::cli;
+use app_common::render::Request;
+use clap::Arg;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::process::Command;
+
+/// name, window, `--render` and `--headless` entry points
+type Entry = (&'static str, fn(), fn(&Request), fn());
+
+const APPS: [Entry; 19] = [
+    ("lissa", lissa::run, lissa::render, lissa::headless),
+    ("yfes", yfes::run, yfes::render, yfes::headless),
+    ("kima", kima::run, kima::render, kima::headless),
+    (
+        "harmonograph",
+        harmonograph::run,
+        harmonograph::render,
+        harmonograph::headless,
+    ),
+    ("tuner", tuner::run, tuner::render, tuner::headless),
+    ("painter", painter::run, painter::render, painter::headless),
+    (
+        "shuffler",
+        shuffler::run,
+        shuffler::render,
+        shuffler::headless,
+    ),
+    (
+        "metronome",
+        metronome::run,
+        metronome::render,
+        metronome::headless,
+    ),
+    (
+        "graindelay",
+        graindelay::run,
+        graindelay::render,
+        graindelay::headless,
+    ),
+    ("shepard", shepard::run, shepard::render, shepard::headless),
+    ("bells", bells::run, bells::render, bells::headless),
+    ("ocean", ocean::run, ocean::render, ocean::headless),
+    (
+        "attractor",
+        attractor::run,
+        attractor::render,
+        attractor::headless,
+    ),
+    ("score", score::run, score::render, score::headless),
+    (
+        "playground",
+        playground::run,
+        playground::render,
+        playground::headless,
+    ),
+    ("tracker", tracker::run, tracker::render, tracker::headless),
+    ("turing", turing::run, turing::render, turing::headless),
+    ("mixer", mixer::run, mixer::render, mixer::headless),
+    ("lsystem", lsystem::run, lsystem::render, lsystem::headless),
+];
+
+/// buttons stacked before starting another column
+const PER_COLUMN: usize = 5;
+
+fn main() {
+    let matches = cli::command("launcher")
+        .arg(
+            Arg::with_name("app")
+                .long("app")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("run an app directly"),
+        )
+        .get_matches();
+    let requested = matches.value_of("app");
+    let args = cli::init_from(requested.unwrap_or("launcher"), &matches);
+
+    match requested {
+        Some(name) => match APPS.iter().find(|(app, ..)| *app == name) {
+            Some((_, run, render, headless)) => match &args.render {
+                Some(request) => render(request),
+                None if args.headless => headless(),
+                None => run(),
+            },
+            None => {
+                let names: Vec<&str> = APPS.iter().map(|(app, ..)| *app).collect();
+                eprintln!("unknown app {}, expected one of {}", name, names.join("|"));
+                std::process::exit(1);
+            }
+        },
+        None => nannou::app(model).update(update).simple_window(view).run(),
+    }
+}
+
+struct Model {
+    ui: Ui,
+    ids: widget::id::List,
+}
+
+fn model(app: &App) -> Model {
+    let mut ui = app.new_ui().build().unwrap();
+    let mut ids = widget::id::List::new();
+    ids.resize(APPS.len(), &mut ui.widget_id_generator());
+    Model { ui, ids }
+}
+
+/// the event loop can only be started once per process, so the
+/// chosen app gets a fresh process of this same executable, with the
+/// launcher's flags
+fn launch(app: &App, name: &str) {
+    let flags = std::env::args().skip(1);
+    let spawned = std::env::current_exe()
+        .and_then(|exe| Command::new(exe).args(flags).args(&["--app", name]).spawn());
+    match spawned {
+        Ok(_) => app.quit(),
+        Err(e) => eprintln!("failed to launch {}: {}", name, e),
+    }
+}
+
+fn update(app: &App, model: &mut Model, _update: Update) {
+    let ui = &mut model.ui.set_widgets();
+
+    for (i, (name, ..)) in APPS.iter().enumerate() {
+        let button = widget::Button::new()
+            .w_h(200.0, 60.0)
+            .label(name)
+            .label_font_size(20)
+            .rgb(0.0, 0.5, 0.0)
+            .label_rgb(0.0, 0.0, 0.0)
+            .border(0.0);
+        // down each column, then on to the next
+        let button = if i == 0 {
+            button.top_left_with_margin(40.0)
+        } else if i % PER_COLUMN == 0 {
+            button.right_from(model.ids[i - PER_COLUMN], 20.0)
+        } else {
+            button.down(20.0)
+        };
+        if button.set(model.ids[i], ui).was_clicked() {
+            launch(app, name);
+        }
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    draw.background().rgb(0.04, 0.04, 0.04);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,184 @@
+//! The lissa dual-sine synth as a CLAP/VST3 plugin.
+//!
+//! Right plays `freq`, left plays `freq * ratio`, the same pair the app
+//! draws as a Lissajous figure. Incoming notes override `freq` until reset.
+
+use dsp_common::denormal::DenormalGuard;
+use dsp_common::tuning;
+use dsp_common::Wavetable;
+use nih_plug::prelude::*;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+const TABLE_SIZE: usize = 2048;
+
+#[derive(Params)]
+struct LissaParams {
+    #[id = "freq"]
+    freq: FloatParam,
+    #[id = "ratio"]
+    ratio: FloatParam,
+    #[id = "gain"]
+    gain: FloatParam,
+}
+
+impl Default for LissaParams {
+    fn default() -> Self {
+        Self {
+            freq: FloatParam::new(
+                "Frequency",
+                tuning::midi_to_freq(48.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2_000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            ratio: FloatParam::new(
+                "Ratio",
+                1.5,
+                FloatRange::Skewed {
+                    min: 1.0 / 6.0,
+                    max: 6.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(3)),
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-20.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-60.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-60.0, 0.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+struct Lissa {
+    params: Arc<LissaParams>,
+    table: Wavetable,
+    sample_rate: f32,
+    phases: [f32; 2],
+    note_freq: Option<f32>,
+}
+
+impl Default for Lissa {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(LissaParams::default()),
+            table: Wavetable::sine(TABLE_SIZE),
+            sample_rate: 48_000.0,
+            phases: [0.0; 2],
+            note_freq: None,
+        }
+    }
+}
+
+impl Plugin for Lissa {
+    const NAME: &'static str = "Lissa";
+    const VENDOR: &'static str = "Nico Chatzi";
+    const URL: &'static str = "https://github.com/nicochatzi/nannou-apps";
+    const EMAIL: &'static str = "nico.chatzigianis@focusrite.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(2),
+        ..AudioIOLayout::const_default()
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+        true
+    }
+
+    fn reset(&mut self) {
+        self.phases = [0.0; 2];
+        self.note_freq = None;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let _denormals = DenormalGuard::new();
+        let mut next_event = context.next_event();
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
+                }
+                if let NoteEvent::NoteOn { note, .. } = event {
+                    self.note_freq = Some(tuning::midi_to_freq(note as f32));
+                }
+                next_event = context.next_event();
+            }
+
+            let freq = self.params.freq.smoothed.next();
+            let freq = self.note_freq.unwrap_or(freq);
+            let ratio = self.params.ratio.smoothed.next();
+            let gain = self.params.gain.smoothed.next();
+            let freqs = [freq * ratio, freq];
+
+            for (channel, sample) in channel_samples.into_iter().enumerate() {
+                let voice = channel % 2;
+                *sample = self.table.at(self.phases[voice]) * gain;
+            }
+            for (phase, freq) in self.phases.iter_mut().zip(freqs.iter()) {
+                *phase = (*phase + freq / self.sample_rate).fract();
+            }
+        }
+
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for Lissa {
+    const CLAP_ID: &'static str = "com.nicochatzi.lissa";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Two sines at a harmonic ratio");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for Lissa {
+    const VST3_CLASS_ID: [u8; 16] = *b"NannouLissaSynth";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(Lissa);
+nih_export_vst3!(Lissa);
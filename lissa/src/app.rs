@@ -0,0 +1,1417 @@
+use crate::bounce::Bounce;
+use crate::figure::{Lissajous, Settings, FREQS, SAMPLE_RATE, TABLE_SIZE};
+use crate::orbit::Orbit;
+use crate::oscillators::{self, Oscillators};
+use crate::preset;
+use crate::progression::{Playhead, Progression};
+use crate::worker::Worker;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::automation::{self, Automated, Automation, Clock, Recorder};
+use app_common::bus::{self, AudioEnd, UiEnd};
+use app_common::camera::{self, Camera};
+use app_common::cli;
+use app_common::config::{self, Config};
+use app_common::cv::{self, CvOutput, CvSignal, CvTargets, WithCv};
+use app_common::diagnostics::Hud;
+use app_common::dmx::DmxOutput;
+use app_common::gamepad::{self, GamepadEditor, GamepadMap, Gamepads};
+use app_common::kiosk::{self, Kiosk};
+use app_common::learn::MidiLearn;
+use app_common::link::{BeatGrid, Link};
+use app_common::midi::{self, MidiInput, MidiMessage, MidiOutput, MidiReceiver};
+use app_common::mirror::{self, Mirror};
+use app_common::osc::Osc;
+use app_common::oscquery;
+use app_common::output::OutputWindow;
+use app_common::param::{
+    self, Bindings, Curve, OscFeedback, ParamPreset, ParamSnapshot, ParamSpec, Params,
+};
+use app_common::performance::{self, Replay, Take};
+use app_common::remote::RemoteServer;
+use app_common::render::{self, Render, Request};
+use app_common::scope::{self, ScopeInput, ScopeReader};
+use app_common::serial;
+use app_common::session::Session;
+use app_common::share::{self, FrameShare};
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Palette, Themed};
+use app_common::timecode::Chase;
+use app_common::touchosc;
+use app_common::transport::{Transport, TransportClock};
+use app_common::widget::{PresetBrowser, PresetBrowserIds, PresetEvent, Scope, StereoMeter};
+use dsp_common::env::{Envelope, Retrigger, Shape};
+use dsp_common::limiter::Limiter;
+use dsp_common::meter::{self, MeterReader, MeterWriter};
+use dsp_common::random::{self, Rng};
+use dsp_common::tuning::midi_to_freq;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::fs;
+use std::path::Path;
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+/// frames between figure updates offline, the app's frame rate at 60Hz
+const RENDER_BLOCK: usize = SAMPLE_RATE as usize / 60;
+
+/// The synth following the figure without a window, free-running.
+struct Headless {
+    synth: Synth,
+    bus: UiEnd<Command, ()>,
+    lissa: Lissajous,
+    /// picks when the figure jumps, `None` while a take's jumps replay
+    rng: Option<Rng>,
+    /// picks where it jumps to, as in the app
+    figure_rng: Rng,
+    /// steps the figure through chords instead, as in the app
+    progression: Option<Playhead>,
+    transport: Transport,
+    beats: BeatGrid,
+    /// where the figure's settings go after every buffer, for a host to
+    /// draw it by
+    figure: Option<AudioEnd<(), Settings>>,
+}
+
+impl Headless {
+    fn jump(&mut self) {
+        self.lissa.randomize(&mut self.figure_rng);
+        let _ = self.bus.send(Command::Jump);
+    }
+}
+
+impl Render for Headless {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        // the same jumps as `update`
+        let beat = self.transport.beat().unwrap_or_default();
+        let crossed = self.beats.crossed(beat);
+        match &mut self.progression {
+            Some(progression) => {
+                if let Some(freqs) = progression.step(beat) {
+                    self.lissa.chord = Some(freqs);
+                    let _ = self.bus.send(Command::Jump);
+                }
+            }
+            None => {
+                if crossed && self.rng.as_mut().map_or(false, |rng| rng.chance(0.5)) {
+                    self.jump();
+                }
+            }
+        }
+
+        let (x_freq, y_freq) = self.lissa.freqs();
+        let z_freq = self.lissa.z_freq();
+        let _ = self.bus.send(Command::Freqs(x_freq, y_freq, z_freq));
+        self.synth.render(out, channels, sample_rate);
+        if let Some(figure) = &mut self.figure {
+            figure.publish(self.lissa.settings());
+        }
+    }
+}
+
+/// the saved parameters, or `--preset`'s, on `transport`
+fn headless_engine(config: &Config, seed: u64, transport: Transport) -> Headless {
+    let params = Params::new(&PARAMS);
+    config.params.apply(&params);
+    apply_preset(&params);
+    let (mut synth, bus, _meter, _scope) = synth(&params, transport.clock(None));
+    synth.limiter.set_bypass(config.bypass_limiter);
+
+    let mut lissa = Lissajous::new(0.0, 0.0);
+    lissa.delta = params.get(DELTA);
+    lissa.resolution = params.get(RESOLUTION);
+
+    Headless {
+        synth,
+        bus,
+        lissa,
+        rng: Some(Rng::new(seed)),
+        figure_rng: Rng::new(seed),
+        progression: load_progression(config).map(Playhead::new),
+        transport,
+        beats: BeatGrid::new(1.0),
+        figure: None,
+    }
+}
+
+/// the chords `sample_path` names, the figure jumps at random without them
+fn load_progression(config: &Config) -> Option<Progression> {
+    let path = config.sample_path.as_ref()?;
+    let progression = fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| Progression::decode(&bytes, BEATS_PER_BAR));
+    progression
+        .map_err(|e| eprintln!("lissa: cannot read {}: {}", path.display(), e))
+        .ok()
+}
+
+/// the synth without a window or audio device
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("lissa", &config::path("lissa"));
+    let seed = shell::seed(session_seed);
+    let mut headless = headless_engine(&config, seed, Transport::new(BPM, BEATS_PER_BAR));
+    request.run(&mut headless, SAMPLE_RATE as u32, 2, RENDER_BLOCK);
+}
+
+/// a take rendered again from its events into its folder, see `performance`
+pub fn replay(path: &Path) {
+    let replay = Replay::load("lissa", path).unwrap_or_else(|e| {
+        eprintln!("lissa: cannot replay {}: {}", path.display(), e);
+        std::process::exit(1);
+    });
+    let seed = replay.session.seed.unwrap_or_default();
+    let mut headless = headless_engine(
+        &replay.session.config,
+        seed,
+        Transport::new(BPM, BEATS_PER_BAR),
+    );
+    headless.rng = None;
+    let params = headless.synth.params.clone();
+    replay.run(
+        headless,
+        &params,
+        CHANNELS,
+        RENDER_BLOCK,
+        |headless, name| {
+            if name == JUMP {
+                headless.jump();
+            }
+        },
+    );
+}
+
+/// the synth on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("lissa", &config::path("lissa"));
+    let seed = shell::seed(session_seed);
+    let stream = config.stream_config(StreamConfig {
+        jack: config.jack_client("lissa", &JACK_PORTS),
+        ..StreamConfig::default()
+    });
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    render::headless("lissa", headless_engine(&config, seed, transport), stream);
+}
+
+/// The synth following its figure as `headless` plays it, from the app's
+/// saved config and on `transport`, for hosts playing it beside other
+/// engines. The figure's settings come back after every buffer.
+pub fn embedded(transport: &Transport) -> (impl Render + Send, UiEnd<(), Settings>) {
+    let config = Config::load(&config::default_path("lissa"));
+    let mut headless = headless_engine(&config, random::entropy(), transport.clone());
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    headless.figure = Some(audio_bus);
+    (headless, ui_bus)
+}
+
+/// `--preset` over the saved parameters
+fn apply_preset(params: &Params) {
+    if let Some(name) = &cli::args().preset {
+        match ParamSnapshot::load_preset("lissa", name) {
+            Ok(preset) => preset.apply(params),
+            Err(e) => eprintln!("lissa: {}", e),
+        }
+    }
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    link: Link,
+    /// the figure may jump on every beat while playing
+    transport: Transport,
+    /// moves the transport after show control, when `timecode` is set
+    timecode: Option<Chase>,
+    beats: BeatGrid,
+    /// what the figure is, the worker draws it
+    lissa: Lissajous,
+    worker: Worker,
+    /// what the figure is seen through in 3D
+    orbit: Orbit,
+    /// where `rng` and `figure_rng` started, bundled with sessions
+    seed: u64,
+    /// picks when the figure jumps
+    rng: Rng,
+    /// picks where it jumps to, replayed by mirror followers
+    figure_rng: Rng,
+    /// jumps since `seed`
+    steps: u64,
+    /// the chords the figure steps through on the transport's beats
+    /// instead of jumping, from `sample_path`
+    progression: Option<Playhead>,
+    meter: MeterReader,
+    /// the output, triggered
+    scope: ScopeReader,
+    stream: Supervisor<WithCv<Automated<Synth>>>,
+    /// the figure's pitches and jumps for modular synths, see `CV_SIGNALS`
+    cv: CvTargets,
+    clock: Clock,
+    /// recording while `Some`
+    recorder: Option<Recorder>,
+    /// the last recording, replayed by `automation::PLAY`
+    automation: Option<Automation>,
+    /// audio and events while `performance::RECORD` is on
+    take: Option<Take>,
+    /// the output alone while the record button is on
+    bounce: Option<Bounce>,
+    /// `bounce` is under a capture timed by its audio, to be encoded with it
+    exporting: bool,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// flash 0 on every figure jump, level 0 follows the output peak
+    dmx: Option<DmxOutput>,
+    /// parameter CCs and keys, from `midi_device` or the first port found
+    midi: Option<(MidiInput, MidiReceiver)>,
+    /// notes held down and their velocities, the newest last and playing
+    keys: Vec<(u8, u8)>,
+    /// the sines' level as last sent, kept in presets
+    level: f32,
+    presets: PresetBrowser,
+    preset_ids: PresetBrowserIds,
+    /// the figure's frequencies as notes on every jump
+    midi_out: Option<MidiOutput>,
+    learn: MidiLearn,
+    /// OSC addresses of the parameters, `/lissa/<name>`
+    bindings: Bindings,
+    /// parameter control from `osc`, with feedback when it has `send_to`
+    osc: Option<(Osc, OscFeedback)>,
+    /// `None` without the `gamepad` feature or a controller backend
+    gamepads: Option<Gamepads>,
+    gamepad_map: GamepadMap,
+    gamepad_editor: GamepadEditor,
+    /// the room's brightness and motion over the figure, when `camera` is set
+    camera: Option<Camera>,
+    bus: UiEnd<Command, ()>,
+    /// the scene to Syphon, Spout or NDI, see `share::open`
+    share: Option<FrameShare>,
+    /// the parameters over WebSocket when `remote` is set
+    remote: Option<RemoteServer>,
+    /// other instances following this one, or the one this follows
+    mirror: Option<Mirror>,
+    /// projection window, opened at startup when configured
+    output: Option<OutputWindow>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+const BPM: f64 = 120.0;
+const BEATS_PER_BAR: u32 = 4;
+
+const CHANNELS: usize = 2;
+/// a take's trigger for each figure jump
+const JUMP: &str = "jump";
+/// the y sine is on the left, x on the right
+const JACK_PORTS: [&str; CHANNELS] = ["left_y", "right_x"];
+
+const CV_X: usize = 0;
+const CV_Y: usize = 1;
+const CV_JUMP: usize = 2;
+
+/// volt-per-octave pitches of the two sines, and a trigger on every jump
+static CV_SIGNALS: [CvSignal; 3] = [
+    CvSignal::new("x", 0.0),
+    CvSignal::new("y", 0.0),
+    CvSignal::new("jump", 0.0),
+];
+
+const DELTA: usize = 0;
+const RESOLUTION: usize = 1;
+const ATTACK: usize = 2;
+const DECAY: usize = 3;
+const SUSTAIN: usize = 4;
+
+/// the figure, then the accent every jump puts on the tone
+static PARAMS: [ParamSpec; 5] = [
+    ParamSpec::new("δ", 0.0, TABLE_SIZE as f32, PI),
+    ParamSpec::new("γ", 0.05, 0.001, 0.01).curve(Curve::Exponential),
+    ParamSpec::new("attack", 0.001, 1.0, 0.005)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    ParamSpec::new("decay", 0.01, 4.0, 0.3)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    ParamSpec::new("sustain", 0.0, 1.0, 1.0),
+];
+
+enum Command {
+    /// x, y and z while the figure is in 3D
+    Freqs(f32, f32, Option<f32>),
+    /// the sines' level, from a key's velocity
+    Level(f32),
+    /// a preset's frequencies and level in the same buffer, so it never
+    /// sounds half recalled
+    Recall {
+        freqs: (f32, f32),
+        z_freq: Option<f32>,
+        level: f32,
+    },
+    /// re-attacks the tone from its current level
+    Jump,
+}
+
+fn amp_shape(params: &Params) -> Shape {
+    Shape::adsr(
+        params.get(ATTACK),
+        params.get(DECAY),
+        params.get(SUSTAIN),
+        0.0,
+    )
+}
+
+struct Synth {
+    oscillators: Box<dyn Oscillators>,
+    /// advanced with every buffer so jumps land on the beat
+    transport: TransportClock,
+    bus: AudioEnd<Command, ()>,
+    /// gated for good, retriggered on jumps
+    amp: Envelope,
+    params: Params,
+    limiter: Limiter,
+    meter: meter::StereoMeter,
+    meter_out: MeterWriter,
+    scope_out: ScopeInput,
+    /// what the limiter and meter were set up for
+    sample_rate: u32,
+}
+
+widget_ids! {
+    struct Ids {
+        tick,
+        x_freq,
+        y_freq,
+        freq_idx,
+        ratio_idx,
+        meter,
+        scope,
+        link,
+        transport,
+        tempo,
+        snap,
+        depth,
+        record,
+        export,
+    }
+}
+
+/// the oscillators and their control ends, shared by the app and offline renders
+fn synth(
+    params: &Params,
+    transport: TransportClock,
+) -> (Synth, UiEnd<Command, ()>, MeterReader, ScopeReader) {
+    let (meter_out, meter_in) = meter::channel(2);
+    let (scope_out, scope_in) = scope::channel(2, SAMPLE_RATE);
+    let (ui_bus, audio_bus) = bus::bus(64, 1);
+
+    let synth = Synth {
+        oscillators: oscillators::new(),
+        transport,
+        bus: audio_bus,
+        amp: {
+            let mut amp = Envelope::new(amp_shape(params));
+            amp.set_retrigger(Retrigger::Legato);
+            amp.gate_on(1.0 / SAMPLE_RATE);
+            amp
+        },
+        params: params.clone(),
+        limiter: Limiter::new(SAMPLE_RATE),
+        meter: meter::StereoMeter::new(SAMPLE_RATE),
+        meter_out,
+        scope_out,
+        sample_rate: SAMPLE_RATE as u32,
+    };
+
+    (synth, ui_bus, meter_in, scope_in)
+}
+
+fn open_dmx(config: &Config) -> Option<DmxOutput> {
+    let dmx = DmxOutput::open("lissa", config.dmx.clone()?);
+    dmx.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_remote(config: &Config, params: &Params) -> Option<RemoteServer> {
+    let remote = RemoteServer::start(config.remote.as_ref()?, params.clone());
+    remote.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_mirror(config: &Config) -> Option<Mirror> {
+    let mirror = Mirror::open("lissa", config.mirror.clone()?);
+    mirror.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_cv(config: &Config, targets: &CvTargets) -> Option<CvOutput> {
+    Some(CvOutput::new(
+        targets.clone(),
+        config.cv.as_ref()?,
+        CHANNELS,
+    ))
+}
+
+/// at the device's rate, the synth follows it
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        channels: stream_channels(config),
+        jack: config.jack_client("lissa", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+fn stream_channels(config: &Config) -> Option<usize> {
+    let cv = config.cv.as_ref()?;
+    Some(cv.channels(CHANNELS, CV_SIGNALS.len()))
+}
+
+fn open_midi(config: &Config) -> Option<(MidiInput, MidiReceiver)> {
+    let (mut input, receiver) = MidiInput::new("lissa", 256)
+        .map_err(|e| eprintln!("lissa: {}", e))
+        .ok()?;
+    if let Some(port) = config
+        .midi_device
+        .clone()
+        .or_else(|| input.ports().first().cloned())
+    {
+        if let Err(e) = input.select(&port) {
+            eprintln!("lissa: {}", e);
+        }
+    }
+    Some((input, receiver))
+}
+
+fn open_midi_out(config: &Config) -> Option<MidiOutput> {
+    let output = MidiOutput::open("lissa", config.midi_out.clone()?);
+    output.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_osc(config: &Config, params: &Params, bindings: &Bindings) -> Option<(Osc, OscFeedback)> {
+    let mut osc = Osc::new(config.osc.as_ref()?)
+        .map_err(|e| eprintln!("lissa: {}", e))
+        .ok()?;
+    bindings.register_osc(params, &mut osc);
+    Some((osc, OscFeedback::new(params)))
+}
+
+fn open_gamepads() -> Option<Gamepads> {
+    match Gamepads::new() {
+        Ok(gamepads) => Some(gamepads),
+        Err(gamepad::Error::Unavailable) => None,
+        Err(e) => {
+            eprintln!("lissa: {}", e);
+            None
+        }
+    }
+}
+
+fn open_output(app: &App, main: WindowId, config: &Config) -> Option<OutputWindow> {
+    let output = OutputWindow::open(app, main, config.output.as_ref()?, output_view);
+    output.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_timecode(config: &Config) -> Option<Chase> {
+    let chase = Chase::open("lissa", config.timecode.as_ref()?);
+    chase.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn open_camera(config: &Config) -> Option<Camera> {
+    let camera = Camera::open("lissa", config.camera.as_ref()?);
+    camera.map_err(|e| eprintln!("lissa: {}", e)).ok()
+}
+
+fn model(app: &App) -> Model {
+    app.set_loop_mode(LoopMode::RefreshSync);
+
+    let config_path = config::path("lissa");
+    let (config, session_seed) = shell::load_config("lissa", &config_path);
+    let seed = shell::seed(session_seed);
+    let main = config.build_window(app, view);
+
+    let params = Params::new(&PARAMS);
+    config.params.apply(&params);
+    apply_preset(&params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("lissa", startup::Error::Ui(format!("{:?}", e))));
+    let ids = Ids::new(ui.widget_id_generator());
+    let preset_ids = PresetBrowserIds::new(ui.widget_id_generator());
+    let lissa = Lissajous::new(ui.win_w.clone() as f32, ui.win_h.clone() as f32);
+
+    let link = Link::new(BPM, BEATS_PER_BAR as f64);
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let (mut synth, ui_bus, meter, scope) = synth(&params, transport.clock(Some(link.clock())));
+    synth.limiter.set_bypass(config.bypass_limiter);
+    let oscquery = oscquery::Service::from_config("lissa", &config, &params);
+    let serial = serial::Sensors::from_config("lissa", &config, &params);
+
+    let synth = Automated::new(synth, params.clone());
+    let clock = synth.clock();
+    let cv = CvTargets::new(&CV_SIGNALS);
+    let synth = WithCv::new(synth, CHANNELS, open_cv(&config, &cv));
+    let mut stream = Supervisor::idle(synth, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "lissa", &config, stream.stats());
+    let remote = open_remote(&config, &params);
+    let bindings = Bindings::new("lissa");
+    let osc = open_osc(&config, &params, &bindings);
+    // audio consumers smooth edited parameters through `SmoothedParams`
+    let shell =
+        Shell::new("lissa", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ui,
+        link,
+        transport,
+        beats: BeatGrid::new(1.0),
+        ids,
+        param_ids: widget::id::List::new(),
+        params,
+        lissa,
+        worker: Worker::new(),
+        orbit: Orbit::new(),
+        seed,
+        rng: Rng::new(seed),
+        figure_rng: Rng::new(seed),
+        steps: 0,
+        progression: load_progression(&shell.config).map(Playhead::new),
+        meter,
+        scope,
+        stream,
+        cv,
+        clock,
+        recorder: None,
+        automation: None,
+        take: None,
+        bounce: None,
+        exporting: false,
+        errors,
+        hud,
+        kiosk,
+        dmx: open_dmx(&shell.config),
+        midi: open_midi(&shell.config),
+        keys: Vec::new(),
+        level: 1.0,
+        presets: PresetBrowser::new("lissa"),
+        preset_ids,
+        midi_out: open_midi_out(&shell.config),
+        learn: MidiLearn::load(&config_path),
+        bindings,
+        osc,
+        gamepads: open_gamepads(),
+        gamepad_map: GamepadMap::load(&GamepadMap::path(&config_path)),
+        gamepad_editor: GamepadEditor::default(),
+        camera: open_camera(&shell.config),
+        bus: ui_bus,
+        share: share::open("lissa", &shell.config),
+        remote,
+        mirror: open_mirror(&shell.config),
+        output: open_output(app, main, &shell.config),
+        timecode: open_timecode(&shell.config),
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if model
+            .shell
+            .setup_key_pressed(key, &mut model.stream)
+            .taken()
+        {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        let device = midi_device(model).to_string();
+        if model.learn.key_pressed(key, &device, &model.params) {
+            return;
+        }
+        if model
+            .gamepad_editor
+            .key_pressed(key, &model.params, &mut model.gamepad_map)
+        {
+            save_gamepad_map(model);
+            return;
+        }
+        automation_key_pressed(model, key);
+        take_key_pressed(app, model, key);
+        if key == touchosc::EXPORT {
+            match touchosc::export("lissa", &model.params, &model.bindings) {
+                Ok(path) => println!("lissa: saved {}", path.display()),
+                Err(e) => eprintln!("lissa: cannot save layout: {}", e),
+            }
+        }
+        model.hud.key_pressed(key);
+        let loaded = model
+            .shell
+            .key_pressed(app, key, &model.stream, Some(model.seed));
+        if let Some(session) = loaded {
+            reseed(model, session.seed.unwrap_or(model.seed));
+            model.rng = Rng::new(model.seed);
+        }
+        model.transport.key_pressed(key);
+    }
+}
+
+/// record stops any playback, recordings are saved as they finish
+fn automation_key_pressed(model: &mut Model, key: Key) {
+    match key {
+        automation::RECORD => match model.recorder.take() {
+            Some(recorder) => {
+                let recording = recorder.stop(&model.clock);
+                if let Err(e) = recording.save_new("lissa") {
+                    eprintln!("lissa: cannot save automation: {}", e);
+                }
+                model.automation = Some(recording);
+            }
+            None => {
+                model.stream.send(|synth| synth.engine_mut().stop());
+                model.recorder = Some(Recorder::start(&model.params, &model.clock));
+            }
+        },
+        automation::PLAY if model.recorder.is_none() => {
+            let recording = match model.automation.take() {
+                Some(recording) => Some(recording),
+                None => Automation::latest("lissa"),
+            };
+            if let Some(recording) = recording {
+                let sample_rate = model.clock.sample_rate();
+                let player = recording.player(&model.params, sample_rate);
+                model
+                    .stream
+                    .send(move |synth| synth.engine_mut().play(player));
+                model.automation = Some(recording);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// takes start from the figure as it is, so its jumps so far come first
+fn take_key_pressed(app: &App, model: &mut Model, key: Key) {
+    if key != performance::RECORD {
+        return;
+    }
+    match model.take.take() {
+        Some(take) => {
+            model.stream.send(|synth| synth.engine_mut().record(None));
+            match take.stop(&model.clock) {
+                Ok(path) => println!("lissa: saved {}", path.display()),
+                Err(e) => eprintln!("lissa: cannot save take: {}", e),
+            }
+        }
+        None => {
+            // the take's tap takes over the output
+            if let Some(bounce) = model.bounce.take() {
+                finish_bounce(bounce);
+                if model.exporting {
+                    model.shell.capture.stop(app);
+                    model.exporting = false;
+                }
+            }
+            model.shell.capture_config(app, &model.stream);
+            let session = Session::new("lissa", model.shell.config.clone(), Some(model.seed));
+            match Take::start(&session, &model.params, &model.clock, CHANNELS) {
+                Ok((mut take, tap)) => {
+                    for _ in 0..model.steps {
+                        take.trigger(JUMP, &model.clock);
+                    }
+                    model
+                        .stream
+                        .send(move |synth| synth.engine_mut().record(Some(tap)));
+                    println!("lissa: recording {}", take.path().display());
+                    model.take = Some(take);
+                }
+                Err(e) => eprintln!("lissa: cannot start take: {}", e),
+            }
+        }
+    }
+}
+
+/// starts the output into a file, or stops it and writes the file out
+fn toggle_bounce(
+    bounce: &mut Option<Bounce>,
+    stream: &Supervisor<WithCv<Automated<Synth>>>,
+    clock: &Clock,
+    config: &Config,
+) {
+    match bounce.take() {
+        Some(stopped) => {
+            stream.send(|synth| synth.engine_mut().record(None));
+            finish_bounce(stopped);
+        }
+        None => match Bounce::start(config.recording, clock, CHANNELS) {
+            Ok((started, tap)) => {
+                stream.send(move |synth| synth.engine_mut().record(Some(tap)));
+                println!("lissa: recording {}", started.path().display());
+                *bounce = Some(started);
+            }
+            Err(e) => eprintln!("lissa: cannot record: {}", e),
+        },
+    }
+}
+
+fn finish_bounce(bounce: Bounce) {
+    match bounce.stop() {
+        Ok(path) => println!("lissa: saved {}", path.display()),
+        Err(e) => eprintln!("lissa: cannot save recording: {}", e),
+    }
+}
+
+/// the port bindings are learnt for
+fn midi_device(model: &Model) -> &str {
+    model
+        .midi
+        .as_ref()
+        .and_then(|(input, _)| input.selected())
+        .unwrap_or("none")
+}
+
+fn save_gamepad_map(model: &Model) {
+    let path = GamepadMap::path(model.shell.config_path());
+    if let Err(e) = model.gamepad_map.save(&path) {
+        eprintln!("lissa: cannot save {}: {}", path.display(), e);
+    }
+}
+
+/// the figure as it was at `seed`, before any jumps
+fn reseed(model: &mut Model, seed: u64) {
+    model.seed = seed;
+    model.figure_rng = Rng::new(seed);
+    model.steps = 0;
+    model.lissa.freq_idx = 0.0;
+    model.lissa.ratio_idx = 0.0;
+}
+
+fn jump(model: &mut Model) {
+    model.lissa.randomize(&mut model.figure_rng);
+    model.steps += 1;
+    if let Some(take) = &mut model.take {
+        take.trigger(JUMP, &model.clock);
+    }
+}
+
+/// holds or lets go of a key, a new one re-attacks the tone at its
+/// velocity and letting go of it goes back to the one before's, or to full
+/// level once none are held
+fn press(
+    keys: &mut Vec<(u8, u8)>,
+    level: &mut f32,
+    bus: &mut UiEnd<Command, ()>,
+    message: &MidiMessage,
+) {
+    let velocity = match *message {
+        MidiMessage::NoteOn { note, velocity, .. } => {
+            keys.retain(|&(held, _)| held != note);
+            keys.push((note, velocity));
+            let _ = bus.send(Command::Jump);
+            velocity
+        }
+        MidiMessage::NoteOff { note, .. } => {
+            let newest = keys.last().map(|&(held, _)| held);
+            keys.retain(|&(held, _)| held != note);
+            match keys.last() {
+                Some(&(_, velocity)) if newest == Some(note) => velocity,
+                None if newest == Some(note) => 127,
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+    *level = velocity as f32 / 127.0;
+    let _ = bus.send(Command::Level(*level));
+}
+
+/// frame widths a second of motion that turn the figure all the way
+const CAMERA_MOTION: f32 = 0.5;
+
+/// the root from how bright the room is, a dark room low, and the phase
+/// between the sines turned the way people move, further the faster, so
+/// the figure settles back on δ when they stand still
+fn follow_camera(lissa: &mut Lissajous, reading: camera::Reading) {
+    lissa.freq_idx = reading.brightness.clamp(0.0, 1.0) * (FREQS.len() - 1) as f32;
+    let turn = reading.direction() / TAU * (reading.speed() / CAMERA_MOTION).min(1.0);
+    lissa.delta = (lissa.delta + turn * TABLE_SIZE as f32).rem_euclid(TABLE_SIZE as f32);
+}
+
+/// catches up with the leader's jumps and parameters, true if it jumped
+fn follow(model: &mut Model, state: mirror::State) -> bool {
+    if state.seed != model.seed || state.steps < model.steps {
+        reseed(model, state.seed);
+    }
+    let jumped = model.steps < state.steps;
+    while model.steps < state.steps {
+        jump(model);
+    }
+    if state.params != ParamSnapshot::capture(&model.params) {
+        state.params.apply(&model.params);
+    }
+    jumped
+}
+
+fn exit(app: &App, mut model: Model) {
+    // before the capture, an export is encoded with the finished file
+    if let Some(bounce) = model.bounce.take() {
+        finish_bounce(bounce);
+    }
+    if let Some(share) = &mut model.share {
+        share.finish(app);
+    }
+    if let Some(take) = model.take.take() {
+        if let Err(e) = take.stop(&model.clock) {
+            eprintln!("lissa: cannot save take: {}", e);
+        }
+    }
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    let stats = model.bus.stats();
+    model.hud.queue(
+        "commands",
+        stats.commands_queued,
+        stats.commands_capacity,
+        stats.commands_dropped,
+    );
+    if let Some((input, receiver)) = &mut model.midi {
+        input.poll();
+        let device = input.selected().unwrap_or("none");
+        for event in receiver.drain() {
+            model.learn.midi(device, &event.message, &model.params);
+            let message = &event.message;
+            press(&mut model.keys, &mut model.level, &mut model.bus, message);
+        }
+    }
+    // x on the newest key held, the random walk still picks the ratio
+    model.lissa.key = model
+        .keys
+        .last()
+        .map(|&(note, _)| midi_to_freq(note as f32));
+    if let Some((osc, feedback)) = &mut model.osc {
+        for message in osc.poll() {
+            feedback.apply(&model.bindings, &model.params, &message);
+        }
+    }
+    if let Some(recorder) = &mut model.recorder {
+        recorder.poll(&model.params, &model.clock);
+    }
+    if let Some(take) = &mut model.take {
+        take.poll(&model.params, &model.clock);
+    }
+    if let Some(gamepads) = &mut model.gamepads {
+        gamepads.poll(&model.gamepad_map, &model.params);
+        if model
+            .gamepad_editor
+            .update(gamepads, &model.params, &mut model.gamepad_map)
+        {
+            save_gamepad_map(model);
+        }
+    }
+    if let Some(camera) = &mut model.camera {
+        if let Err(e) = camera.poll() {
+            eprintln!("lissa: {}", e);
+            model.camera = None;
+        }
+    }
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(draw) = model.share.as_ref().and_then(|share| share.begin(app)) {
+        scene(model, &draw);
+        if let Some(share) = &mut model.share {
+            share.end(app, &draw);
+        }
+    }
+    if let Some(timecode) = &mut model.timecode {
+        timecode.poll(&model.transport);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |synth| synth.engine_mut().engine_mut().limiter.set_bypass(bypass));
+        }
+        if config.sample_path != model.shell.config.sample_path {
+            model.progression = load_progression(&config).map(Playhead::new);
+            // on the tables until the new progression's first chord
+            model.lissa.chord = None;
+        }
+        if config.cv != model.shell.config.cv {
+            // the stream first, the signals go to the new one
+            let _ = model.stream.set_channels(stream_channels(&config));
+            let output = open_cv(&config, &model.cv);
+            model.stream.send(move |synth| synth.set_output(output));
+        }
+        if config.dmx != model.shell.config.dmx {
+            model.dmx = open_dmx(&config);
+        }
+        config::reopen(
+            &model.shell.config.midi_out,
+            &config.midi_out,
+            &mut model.midi_out,
+            || open_midi_out(&config),
+        );
+        config::reopen(
+            &share::settings(&model.shell.config),
+            &share::settings(&config),
+            &mut model.share,
+            || share::open("lissa", &config),
+        );
+        let (params, bindings) = (&model.params, &model.bindings);
+        config::reopen(&model.shell.config.osc, &config.osc, &mut model.osc, || {
+            open_osc(&config, params, bindings)
+        });
+        config::reopen(
+            &model.shell.config.mirror,
+            &config.mirror,
+            &mut model.mirror,
+            || open_mirror(&config),
+        );
+        config::reopen(
+            &model.shell.config.remote,
+            &config.remote,
+            &mut model.remote,
+            || open_remote(&config, params),
+        );
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        config::reopen(
+            &model.shell.config.timecode,
+            &config.timecode,
+            &mut model.timecode,
+            || open_timecode(&config),
+        );
+        config::reopen(
+            &model.shell.config.camera,
+            &config.camera,
+            &mut model.camera,
+            || open_camera(&config),
+        );
+        model.shell.config = config;
+    }
+
+    // a drag off the widgets turns the figure, taken before they're set
+    let on_widget = model.ui.global_input().current.widget_capturing_mouse;
+    let on_scene = on_widget.filter(|&id| id != model.ui.window).is_none();
+    let dragging = if on_scene && app.mouse.buttons.left().is_down() {
+        Some(app.mouse.position())
+    } else {
+        None
+    };
+
+    let ui = &mut model.ui.set_widgets();
+
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+    model.learn.watch(&model.param_ids, ui);
+    model.lissa.delta = model.params.get(DELTA);
+    model.lissa.resolution = model.params.get(RESOLUTION);
+    if let Some(camera) = &model.camera {
+        follow_camera(&mut model.lissa, camera.reading());
+    }
+    // after the sliders, so dragging them moves the controller's faders too
+    if let Some((osc, feedback)) = &mut model.osc {
+        if let Err(e) = feedback.send_changes(&model.bindings, &model.params, osc) {
+            eprintln!("lissa: {}", e);
+        }
+    }
+    model.link.panel(model.ids.link, palette, ui);
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+    snap_toggle(&mut model.lissa, model.ids.snap, palette, ui);
+    depth_toggle(&mut model.lissa, model.ids.depth, palette, ui);
+    match model.presets.set(&model.preset_ids, palette, ui) {
+        Some(PresetEvent::Save(name)) => {
+            let saved = preset::capture(&model.params, &model.lissa, model.level);
+            if let Err(e) = saved.save("lissa", &name) {
+                eprintln!("lissa: {}", e);
+            }
+        }
+        Some(PresetEvent::Load(name)) => match ParamPreset::load("lissa", &name) {
+            Ok(loaded) => {
+                model.level = preset::apply(&loaded, &model.params, &mut model.lissa);
+                let _ = model.bus.send(Command::Recall {
+                    freqs: model.lissa.freqs(),
+                    z_freq: model.lissa.z_freq(),
+                    level: model.level,
+                });
+            }
+            Err(e) => eprintln!("lissa: {}", e),
+        },
+        None => {}
+    }
+    let label = match &model.bounce {
+        Some(bounce) => {
+            let seconds = bounce.seconds(&model.clock) as u64;
+            format!("stop recording {}:{:02}", seconds / 60, seconds % 60)
+        }
+        None => String::from("record"),
+    };
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(&label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.record, ui)
+    {
+        if model.take.is_some() || model.exporting {
+            eprintln!("lissa: the output is being recorded already");
+        } else {
+            toggle_bounce(
+                &mut model.bounce,
+                &model.stream,
+                &model.clock,
+                &model.shell.config,
+            );
+        }
+    }
+    let label = match &model.bounce {
+        Some(bounce) if model.exporting => {
+            let seconds = bounce.seconds(&model.clock) as u64;
+            format!("stop export {}:{:02}", seconds / 60, seconds % 60)
+        }
+        _ => String::from("export video"),
+    };
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(&label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.export, ui)
+    {
+        let recording = model.take.is_some() || model.bounce.is_some();
+        if model.exporting {
+            // the recording's finished first, the video is encoded with it
+            toggle_bounce(
+                &mut model.bounce,
+                &model.stream,
+                &model.clock,
+                &model.shell.config,
+            );
+            model.shell.capture.stop(app);
+            model.exporting = false;
+        } else if recording || model.shell.capture.is_recording() {
+            eprintln!("lissa: stop recording before exporting");
+        } else {
+            toggle_bounce(
+                &mut model.bounce,
+                &model.stream,
+                &model.clock,
+                &model.shell.config,
+            );
+            // frames from the audio's clock, so they line up with the file
+            if let Some(bounce) = &model.bounce {
+                model
+                    .shell
+                    .capture
+                    .start_synced(&model.clock, bounce.path());
+                model.exporting = true;
+            }
+        }
+    }
+
+    StereoMeter::new([model.meter.read(0), model.meter.read(1)])
+        .with_style(palette.meter_style())
+        .w_h(30.0, 200.0)
+        .top_right_with_margin(20.0)
+        .set(model.ids.meter, ui);
+
+    model.scope.update();
+    if let Some(settings) = Scope::new(model.scope.samples(), *model.scope.settings())
+        .with_style(palette.scope_style())
+        .w_h(300.0, 160.0)
+        .bottom_right_with_margin(20.0)
+        .set(model.ids.scope, ui)
+    {
+        model.scope.set_settings(settings);
+    }
+
+    let following = matches!(&model.mirror, Some(mirror) if !mirror.is_leader());
+    let randomize = if following {
+        let state = model.mirror.as_mut().and_then(Mirror::poll);
+        state.map_or(false, |state| follow(model, state))
+    } else {
+        // on the transport's beats, phase-locked to Link when enabled, or
+        // on the progression's chords when there is one
+        let randomize = match (model.transport.beat(), &mut model.progression) {
+            (Some(beat), Some(progression)) => match progression.step(beat) {
+                Some(freqs) => {
+                    model.lissa.chord = Some(freqs);
+                    true
+                }
+                None => false,
+            },
+            (Some(beat), None) => model.beats.crossed(beat) && model.rng.chance(0.5),
+            (None, progression) => {
+                model.beats.reset();
+                if let Some(progression) = progression {
+                    progression.reset();
+                }
+                false
+            }
+        };
+        // chords come from the beat, a take's replay finds them again
+        if randomize && model.progression.is_none() {
+            jump(model);
+        }
+        randomize
+    };
+
+    if let Some(mirror) = model.mirror.as_mut().filter(|mirror| mirror.is_leader()) {
+        let state = mirror::State {
+            position: update.since_start.as_secs_f64(),
+            seed: model.seed,
+            steps: model.steps,
+            params: ParamSnapshot::capture(&model.params),
+        };
+        if let Err(e) = mirror.publish(&state) {
+            eprintln!("lissa: {}", e);
+        }
+    }
+
+    if let Some(dmx) = &mut model.dmx {
+        if randomize {
+            dmx.flash(0);
+        }
+        dmx.set_level(0, model.meter.read(0).peak.max(model.meter.read(1).peak));
+        if let Err(e) = dmx.update() {
+            eprintln!("lissa: {}", e);
+            model.dmx = None;
+        }
+    }
+
+    let win = app.window_rect();
+    model.lissa.resize(win.w(), win.h());
+    if model.lissa.depth {
+        let seconds = update.since_last.as_secs_f32();
+        model.orbit.update(seconds, dragging, win.w());
+    }
+    model.worker.compute(&model.lissa);
+    model.worker.receive();
+    let (x_freq, y_freq) = model.lissa.freqs();
+    let z_freq = model.lissa.z_freq();
+    let scale = model.shell.config.cv.clone().unwrap_or_default().scale();
+    model.cv.set(CV_X, scale.hz_to_volts(x_freq));
+    model.cv.set(CV_Y, scale.hz_to_volts(y_freq));
+    // held for a frame
+    model
+        .cv
+        .set(CV_JUMP, if randomize { cv::GATE } else { 0.0 });
+    if let Some(output) = &mut model.midi_out {
+        output.poll();
+        // the figure's two frequencies, held until the next jump
+        if randomize {
+            let notes: Vec<u8> = [x_freq, y_freq]
+                .iter()
+                .filter_map(|&f| midi::note(f))
+                .collect();
+            output.play(&notes);
+        }
+    }
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    let _ = model.bus.send(Command::Freqs(x_freq, y_freq, z_freq));
+    if randomize {
+        let _ = model.bus.send(Command::Jump);
+    }
+}
+
+/// the simple ratio the figure is nearest, and how far off it is until
+/// snapped onto it
+fn snap_toggle(lissa: &mut Lissajous, id: widget::Id, palette: &Palette, ui: &mut UiCell) {
+    let nearest = lissa.nearest_ratio();
+    let label = if lissa.snap {
+        format!("snapped to {}:{}", nearest.1, nearest.0)
+    } else {
+        let cents = 1200.0 * (lissa.ratio() / nearest.value()).log2();
+        format!("snap to {}:{} ({:+.0}c)", nearest.1, nearest.0, cents)
+    };
+    for value in widget::Toggle::new(lissa.snap)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(&label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(id, ui)
+    {
+        lissa.snap = value;
+    }
+}
+
+/// the figure flat or turning in 3D with its third sine
+fn depth_toggle(lissa: &mut Lissajous, id: widget::Id, palette: &Palette, ui: &mut UiCell) {
+    let label = if lissa.depth { "3d" } else { "2d" };
+    for value in widget::Toggle::new(lissa.depth)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(id, ui)
+    {
+        lissa.depth = value;
+    }
+}
+
+impl Render for Synth {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        for command in self.bus.commands() {
+            match command {
+                Command::Freqs(x_freq, y_freq, z_freq) => {
+                    self.oscillators.set_freqs(x_freq, y_freq, z_freq)
+                }
+                Command::Level(level) => self.oscillators.set_level(level),
+                Command::Recall {
+                    freqs: (x_freq, y_freq),
+                    z_freq,
+                    level,
+                } => {
+                    self.oscillators.set_freqs(x_freq, y_freq, z_freq);
+                    self.oscillators.set_level(level);
+                }
+                Command::Jump => self.amp.gate_on(1.0 / sample_rate as f32),
+            }
+        }
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+            self.meter.set_sample_rate(sample_rate as f32);
+        }
+        self.amp.set_shape(amp_shape(&self.params));
+        // only the position matters, jumps are picked on the UI thread
+        self.transport.advance(out.len() / channels, sample_rate);
+
+        self.oscillators.render(out, channels, sample_rate);
+        for frame in out.chunks_exact_mut(channels) {
+            let gain = self.amp.step();
+            frame.iter_mut().for_each(|channel| *channel *= gain);
+        }
+
+        self.limiter.process_interleaved(out, channels);
+        self.meter.process_interleaved(out, channels);
+        self.meter_out.write_all(&self.meter.readings());
+        self.scope_out.write(out);
+    }
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let points = model.worker.points().iter().map(|&[x, y, z]| pt3(x, y, z));
+    if model.lissa.depth {
+        draw.polyline()
+            .weight(1.0)
+            .points(points.map(|point| model.orbit.project(point)))
+            .color(theme::color(palette.line));
+    } else {
+        draw.polyline()
+            .weight(1.0)
+            .points(points.map(|point| pt2(point.x, point.y)))
+            .color(theme::color(palette.line));
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model.learn.draw(
+        &overlay,
+        app.window_rect(),
+        model.shell.themes.current(),
+        &model.params,
+        midi_device(model),
+    );
+    if model.gamepad_editor.is_open() {
+        let connected = match &model.gamepads {
+            Some(gamepads) => gamepads.connected(),
+            None => &[],
+        };
+        model.gamepad_editor.draw(
+            &overlay,
+            app.window_rect(),
+            model.shell.themes.current(),
+            &model.params,
+            &model.gamepad_map,
+            connected,
+        );
+    }
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
+
+/// the scene alone, in the main window's coordinates
+fn output_view(app: &App, model: &Model, frame: Frame) {
+    if let Some(output) = &model.output {
+        let draw = output.draw(app);
+        scene(model, &draw);
+        draw.to_frame(app, &frame).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! The output recorded into a file from the record button, to bounce the
+//! piece as it plays.
+//!
+//! The audio thread pushes every buffer into the recorder's ring and its
+//! writer thread empties it into the file, a disk that falls behind costs
+//! buffers, counted, rather than dropouts. Written as the config's
+//! `recording` format, WAV unless it says otherwise, to
+//! `lissa-<timestamp>.wav` in the working directory.
+
+use app_common::automation::Clock;
+use app_common::capture::timestamp;
+use app_common::recorder::{self, FileFormat, Recorder, RecorderInput, Spec};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// how far the audio thread can get ahead of the writer
+const BUFFER_SECONDS: f32 = 4.0;
+
+#[derive(Debug)]
+pub enum Error {
+    /// the sample rate isn't known before the synth has rendered
+    NotRunning,
+    Recorder(recorder::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotRunning => write!(f, "no audio is playing to record"),
+            Error::Recorder(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<recorder::Error> for Error {
+    fn from(e: recorder::Error) -> Self {
+        Error::Recorder(e)
+    }
+}
+
+pub struct Bounce {
+    recorder: Recorder,
+    path: PathBuf,
+    /// the clock's position when it started
+    start: u64,
+}
+
+impl Bounce {
+    /// a new file, the input goes to the engine's `Automated::record`
+    pub fn start(
+        format: Option<FileFormat>,
+        clock: &Clock,
+        channels: usize,
+    ) -> Result<(Self, RecorderInput), Error> {
+        let sample_rate = clock.sample_rate();
+        if sample_rate == 0 {
+            return Err(Error::NotRunning);
+        }
+        let format = format.unwrap_or(FileFormat::Wav);
+        let path = PathBuf::from(format!("lissa-{}.{}", timestamp(), format.extension()));
+        let (recorder, input) = Recorder::start(
+            &path,
+            Spec {
+                channels: channels as u16,
+                sample_rate,
+                format,
+                buffer_seconds: BUFFER_SECONDS,
+            },
+        )?;
+        let bounce = Self {
+            recorder,
+            path,
+            start: clock.position(),
+        };
+        Ok((bounce, input))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// how long it's been recording, by the audio rendered
+    pub fn seconds(&self, clock: &Clock) -> f64 {
+        let frames = clock.position().saturating_sub(self.start);
+        frames as f64 / clock.sample_rate().max(1) as f64
+    }
+
+    /// writes out what's left once the engine has let go of the input, a
+    /// warning on stderr if the disk fell behind
+    pub fn stop(self) -> Result<PathBuf, Error> {
+        let dropped = self.recorder.dropped_frames();
+        if dropped > 0 {
+            eprintln!(
+                "lissa: {} dropped {} frames in {} buffers, the disk fell behind",
+                self.path.display(),
+                dropped,
+                self.recorder.overruns()
+            );
+        }
+        self.recorder.stop()?;
+        Ok(self.path)
+    }
+}
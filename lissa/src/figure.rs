@@ -0,0 +1,273 @@
+//! The figure and its frequencies, shared by the native and web front ends.
+
+use dsp_common::random::Rng;
+use dsp_common::tuning::{just, Ratio, Scale};
+use dsp_common::{filut_clamped, Wavetable};
+use lazy_static::lazy_static;
+use std::f32::consts::PI;
+
+pub const TABLE_SIZE: usize = 64;
+pub const SAMPLE_RATE: f32 = 48_000.0;
+
+pub const NUM_POINTS: usize = TABLE_SIZE * 4;
+const SCALING: f32 = 0.25;
+/// the largest numerator or denominator `Lissajous::nearest_ratio` uses
+const SNAP_LIMIT: u32 = 8;
+
+lazy_static! {
+    pub static ref SIN_TABLE: Wavetable = Wavetable::sine(TABLE_SIZE);
+    /// C melodic minor, from C3
+    pub static ref FREQS: Vec<f32> = Scale::MELODIC_MINOR.freqs(48.0, 7);
+    pub static ref RATIOS: Vec<f32> = just::grid(6).into_iter().map(Ratio::value).collect();
+}
+
+/// `raw_idx` into a table of `len`, mostly on an entry and gliding to the
+/// next one only at the very end
+fn skewed(raw_idx: f32, len: usize) -> f32 {
+    const SKEW: f32 = 10.0;
+    ((raw_idx as usize) as f32 + (raw_idx % 1.0).powf(SKEW)) % len as f32
+}
+
+fn sin(freq: f32, t: f32, phase: f32) -> f32 {
+    const SAMPLE_TIME: f32 = 1.0 / SAMPLE_RATE;
+    SIN_TABLE.lookup(TABLE_SIZE as f32 * freq * t * SAMPLE_TIME + phase)
+}
+
+/// Everything `Lissajous::compute` draws from, to hand it to another
+/// thread without the points or where the figure has got to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    x_amp: f32,
+    y_amp: f32,
+    delta: f32,
+    freq_idx: f32,
+    ratio_idx: f32,
+    resolution: f32,
+    snap: bool,
+    chord: Option<(f32, f32)>,
+    key: Option<f32>,
+    depth: bool,
+    z_ratio_idx: f32,
+    z_phase: f32,
+}
+
+pub struct Lissajous {
+    x_amp: f32,
+    y_amp: f32,
+    /// x, y and z, z left at 0 unless `depth` is on
+    pub points: Vec<[f32; 3]>,
+    pub delta: f32,
+    phase: f32,
+    pub freq_idx: f32,
+    pub ratio_idx: f32,
+    pub resolution: f32,
+    /// on `nearest_ratio` rather than between ratios, so the figure closes
+    pub snap: bool,
+    /// x and y from a chord progression, over the tables while set
+    pub chord: Option<(f32, f32)>,
+    /// x from a key held down, y still the ratio over it, over the chord
+    /// and the tables while set
+    pub key: Option<f32>,
+    /// a third sine on z, the figure drawn turning in 3D
+    pub depth: bool,
+    /// z's frequency over x's, an index into `RATIOS` as `ratio_idx` is
+    pub z_ratio_idx: f32,
+    /// z's offset from x, in table steps as `delta` is
+    pub z_phase: f32,
+    /// what `points` were computed from, `None` before the first time
+    computed: Option<Settings>,
+}
+
+impl Lissajous {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            x_amp: width * SCALING,
+            y_amp: height * SCALING,
+            points: vec![[0.0; 3]; NUM_POINTS],
+            delta: PI,
+            phase: 0.0,
+            freq_idx: 0.0,
+            ratio_idx: 0.0,
+            resolution: 0.01,
+            snap: false,
+            chord: None,
+            key: None,
+            depth: false,
+            z_ratio_idx: 0.0,
+            z_phase: TABLE_SIZE as f32 / 4.0,
+            computed: None,
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.x_amp = width * SCALING;
+        self.y_amp = height * SCALING;
+    }
+
+    pub fn settings(&self) -> Settings {
+        Settings {
+            x_amp: self.x_amp,
+            y_amp: self.y_amp,
+            delta: self.delta,
+            freq_idx: self.freq_idx,
+            ratio_idx: self.ratio_idx,
+            resolution: self.resolution,
+            snap: self.snap,
+            chord: self.chord,
+            key: self.key,
+            depth: self.depth,
+            z_ratio_idx: self.z_ratio_idx,
+            z_phase: self.z_phase,
+        }
+    }
+
+    /// takes on another figure's settings, carrying on from its own phase
+    pub fn apply(&mut self, settings: &Settings) {
+        self.x_amp = settings.x_amp;
+        self.y_amp = settings.y_amp;
+        self.delta = settings.delta;
+        self.freq_idx = settings.freq_idx;
+        self.ratio_idx = settings.ratio_idx;
+        self.resolution = settings.resolution;
+        self.snap = settings.snap;
+        self.chord = settings.chord;
+        self.key = settings.key;
+        self.depth = settings.depth;
+        self.z_ratio_idx = settings.z_ratio_idx;
+        self.z_phase = settings.z_phase;
+    }
+
+    /// true when something `compute` draws from has changed since it last ran
+    pub fn is_dirty(&self) -> bool {
+        self.computed != Some(self.settings())
+    }
+
+    /// `compute` if the points are out of date, true if it ran
+    pub fn update(&mut self) -> bool {
+        let dirty = self.is_dirty();
+        if dirty {
+            self.compute();
+        }
+        dirty
+    }
+
+    pub fn compute(&mut self) {
+        self.computed = Some(self.settings());
+        let (x_freq, y_freq) = self.freqs();
+        let z_freq = self.z_freq();
+        // as deep as it's wide
+        let z_amp = self.x_amp;
+        for i in 0..NUM_POINTS {
+            self.phase += i as f32 * self.resolution;
+            self.points[i][0] = self.x_amp * sin(x_freq, self.phase, self.delta);
+            self.points[i][1] = self.y_amp * sin(y_freq, self.phase, 0.0);
+            self.points[i][2] = match z_freq {
+                Some(z_freq) => z_amp * sin(z_freq, self.phase, self.delta + self.z_phase),
+                None => 0.0,
+            };
+        }
+    }
+
+    /// y's frequency over x's, unsnapped
+    pub fn ratio(&self) -> f32 {
+        filut_clamped(&RATIOS, skewed(self.ratio_idx, RATIOS.len()))
+    }
+
+    /// the simple ratio `ratio` is closest to, what `snap` holds it on
+    pub fn nearest_ratio(&self) -> Ratio {
+        Ratio::nearest(self.ratio(), SNAP_LIMIT)
+    }
+
+    /// `ratio_idx`'s ratio, or `z_ratio_idx`'s, on the nearest simple one
+    /// while snapped
+    fn snapped(&self, ratio_idx: f32) -> f32 {
+        let ratio = filut_clamped(&RATIOS, skewed(ratio_idx, RATIOS.len()));
+        if self.snap {
+            Ratio::nearest(ratio, SNAP_LIMIT).value()
+        } else {
+            ratio
+        }
+    }
+
+    pub fn freqs(&self) -> (f32, f32) {
+        let ratio = self.snapped(self.ratio_idx);
+        if let Some(freq) = self.key {
+            return (freq, freq * ratio);
+        }
+        if let Some(chord) = self.chord {
+            return chord;
+        }
+        let mut freq = filut_clamped(&FREQS, skewed(self.freq_idx, FREQS.len()));
+        if ratio >= 3.0 {
+            freq /= 2.0;
+        }
+        (freq, freq * ratio)
+    }
+
+    /// the third sine's frequency, over x's, while `depth` is on
+    pub fn z_freq(&self) -> Option<f32> {
+        if !self.depth {
+            return None;
+        }
+        let (x_freq, _) = self.freqs();
+        Some(x_freq * self.snapped(self.z_ratio_idx))
+    }
+
+    /// maybe jump to a new ratio and root
+    pub fn randomize(&mut self, rng: &mut Rng) {
+        if rng.chance(0.5) {
+            self.ratio_idx = rng.range(0.0, (RATIOS.len() - 1) as f32);
+        }
+        if self.depth && rng.chance(0.5) {
+            self.z_ratio_idx = rng.range(0.0, (RATIOS.len() - 1) as f32);
+        }
+        if rng.chance(0.5) {
+            let new_freq = rng.range(0.0, (FREQS.len() - 1) as f32);
+            if (new_freq % 1.0) as u8 != (self.freq_idx % 1.0) as u8 {
+                self.freq_idx = new_freq;
+            }
+        }
+    }
+}
+
+/// The sines voicing the figure, x on the right channel, y on the left and
+/// z, when there is one, in the middle.
+#[derive(Clone, Debug, Default)]
+pub struct Tone {
+    phases: [f32; 3],
+}
+
+impl Tone {
+    pub const AMP: f32 = 0.1;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// z's share of each channel, it's on both
+    pub const Z_MIX: f32 = 0.5;
+
+    /// fills interleaved stereo `out` with `freqs` from `Lissajous::freqs`
+    /// and `z_freq` from `Lissajous::z_freq`
+    pub fn process(
+        &mut self,
+        out: &mut [f32],
+        (x_freq, y_freq): (f32, f32),
+        z_freq: Option<f32>,
+        sample_rate: f32,
+    ) {
+        for frame in out.chunks_exact_mut(2) {
+            frame[0] = SIN_TABLE.at(self.phases[1]) * Self::AMP;
+            frame[1] = SIN_TABLE.at(self.phases[0]) * Self::AMP;
+            self.phases[0] = (self.phases[0] + x_freq / sample_rate).fract();
+            self.phases[1] = (self.phases[1] + y_freq / sample_rate).fract();
+            // left alone without, so the flat figure sounds as it always has
+            if let Some(z_freq) = z_freq {
+                let z = SIN_TABLE.at(self.phases[2]) * Self::AMP * Self::Z_MIX;
+                frame[0] += z;
+                frame[1] += z;
+                self.phases[2] = (self.phases[2] + z_freq / sample_rate).fract();
+            }
+        }
+    }
+}
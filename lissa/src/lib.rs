@@ -0,0 +1,21 @@
+pub mod figure;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod app;
+#[cfg(not(target_arch = "wasm32"))]
+mod bounce;
+#[cfg(not(target_arch = "wasm32"))]
+mod orbit;
+#[cfg(not(target_arch = "wasm32"))]
+mod oscillators;
+#[cfg(not(target_arch = "wasm32"))]
+mod preset;
+#[cfg(not(target_arch = "wasm32"))]
+mod progression;
+#[cfg(target_arch = "wasm32")]
+mod web;
+#[cfg(not(target_arch = "wasm32"))]
+mod worker;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::{embedded, headless, render, replay, run};
@@ -0,0 +1,71 @@
+//! The camera the figure is seen through in 3D, turning round it slowly on
+//! its own or dragged round with the mouse.
+
+use nannou::prelude::*;
+
+/// radians a second it turns on its own
+const SPEED: f32 = 0.2;
+/// radians a pixel dragged
+const DRAG: f32 = 0.01;
+/// looking down on the figure a little, so its depth shows from the start
+const PITCH: f32 = 0.4;
+/// kept short of looking straight down or up
+const MAX_PITCH: f32 = 1.4;
+/// away from the figure's centre, in window widths
+const DISTANCE: f32 = 1.5;
+
+pub struct Orbit {
+    /// round y, then tilted round x
+    yaw: f32,
+    pitch: f32,
+    /// in pixels
+    distance: f32,
+    /// where the mouse was at the last update, while dragging
+    grabbed: Option<Point2>,
+}
+
+impl Orbit {
+    pub fn new() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: PITCH,
+            distance: 0.0,
+            grabbed: None,
+        }
+    }
+
+    /// turns on by `seconds`, or after the mouse while it's at `dragging`
+    pub fn update(&mut self, seconds: f32, dragging: Option<Point2>, width: f32) {
+        match (dragging, self.grabbed) {
+            (Some(at), Some(from)) => {
+                let moved = at - from;
+                self.yaw += moved.x * DRAG;
+                self.pitch = (self.pitch - moved.y * DRAG).clamp(-MAX_PITCH, MAX_PITCH);
+            }
+            (Some(_), None) => {}
+            (None, _) => self.yaw += seconds * SPEED,
+        }
+        self.yaw %= TAU;
+        self.grabbed = dragging;
+        self.distance = width * DISTANCE;
+    }
+
+    /// `point` in perspective, nearer is bigger
+    pub fn project(&self, point: Point3) -> Point2 {
+        let (sin, cos) = self.yaw.sin_cos();
+        let point = pt3(
+            point.x * cos + point.z * sin,
+            point.y,
+            point.z * cos - point.x * sin,
+        );
+        let (sin, cos) = self.pitch.sin_cos();
+        let point = pt3(
+            point.x,
+            point.y * cos - point.z * sin,
+            point.y * sin + point.z * cos,
+        );
+        // the figure is never deeper than half a window, so never behind
+        let scale = self.distance / (self.distance - point.z).max(1.0);
+        pt2(point.x, point.y) * scale
+    }
+}
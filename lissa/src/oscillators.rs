@@ -0,0 +1,222 @@
+//! The sines voicing the figure, x on the right channel, y on the left and
+//! z mixed into both in 3D, as a rume graph or, without the `audio`
+//! feature, the web front end's `Tone`.
+
+use crate::figure::Tone;
+#[cfg(feature = "audio")]
+use rume::{Processor, Renderable};
+
+/// What the synth needs from its oscillators, the envelope, limiter and
+/// meters are applied on top.
+pub trait Oscillators: Send {
+    /// from the next `render` on, z silent while `None`
+    fn set_freqs(&mut self, x_freq: f32, y_freq: f32, z_freq: Option<f32>);
+
+    /// the sines' level from 0 to 1, a key's velocity, full by default
+    fn set_level(&mut self, level: f32);
+
+    /// fills the first two channels of every frame of interleaved `out`
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32);
+}
+
+/// the oscillators this build has
+pub fn new() -> Box<dyn Oscillators> {
+    #[cfg(feature = "audio")]
+    {
+        Box::new(Graph::new())
+    }
+    #[cfg(not(feature = "audio"))]
+    {
+        Box::new(Sines::default())
+    }
+}
+
+/// An input's latest value, sent once a buffer so a burst of changes can't
+/// fill its queue.
+#[cfg(feature = "audio")]
+struct Input {
+    producer: rume::InputStreamProducer,
+    pending: Option<f32>,
+}
+
+#[cfg(feature = "audio")]
+impl Input {
+    fn new(producer: rume::InputStreamProducer) -> Self {
+        Self {
+            producer,
+            pending: None,
+        }
+    }
+
+    fn set(&mut self, value: f32) {
+        self.pending = Some(value);
+    }
+
+    /// a full queue keeps the value for the next buffer
+    fn send(&mut self) {
+        if let Some(value) = self.pending {
+            if self.producer.enqueue(value).is_ok() {
+                self.pending = None;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+struct Graph {
+    graph: rume::SignalChain,
+    freq_a: Input,
+    freq_b: Input,
+    freq_c: Input,
+    amp: Input,
+    /// z's level, 0 while it's off
+    amp_c: Input,
+    outputs: Vec<rume::OutputStreamConsumer>,
+    out_z: rume::OutputStreamConsumer,
+    level: f32,
+    depth: bool,
+}
+
+#[cfg(feature = "audio")]
+impl Graph {
+    fn new() -> Self {
+        let (freq_a_prod, freq_a_con) = rume::input!(FREQ_A_ENDPOINT);
+        let (freq_b_prod, freq_b_con) = rume::input!(FREQ_B_ENDPOINT);
+        let (freq_c_prod, freq_c_con) = rume::input!(FREQ_C_ENDPOINT);
+        let (amp_prod, amp_con) = rume::input!(AMP_ENDPOINT);
+        let (amp_c_prod, amp_c_con) = rume::input!(AMP_C_ENDPOINT);
+        let (out_r_prod, out_r_con) = rume::output!(OUT_R_ENDPOINT);
+        let (out_l_prod, out_l_con) = rume::output!(OUT_L_ENDPOINT);
+        let (out_z_prod, out_z_con) = rume::output!(OUT_Z_ENDPOINT);
+
+        let graph = rume::graph! {
+            endpoints: {
+                freq_a: rume::InputEndpoint::new(freq_a_con),
+                freq_b: rume::InputEndpoint::new(freq_b_con),
+                freq_c: rume::InputEndpoint::new(freq_c_con),
+                amp: rume::InputEndpoint::new(amp_con),
+                amp_c: rume::InputEndpoint::new(amp_c_con),
+                out_r: rume::OutputEndpoint::new(out_r_prod),
+                out_l: rume::OutputEndpoint::new(out_l_prod),
+                out_z: rume::OutputEndpoint::new(out_z_prod),
+            },
+            processors: {
+                sine_a: rume::Sine::default(),
+                sine_b: rume::Sine::default(),
+                sine_c: rume::Sine::default(),
+            },
+            connections: {
+                freq_a.output   -> sine_a.input.0,
+                freq_b.output   -> sine_b.input.0,
+                freq_c.output   -> sine_c.input.0,
+                amp.output      -> sine_a.input.1,
+                amp.output      -> sine_b.input.1,
+                amp_c.output    -> sine_c.input.1,
+                sine_a.output   -> out_r.input,
+                sine_b.output   -> out_l.input,
+                sine_c.output   -> out_z.input,
+            }
+        };
+
+        let mut graph = Self {
+            graph,
+            freq_a: Input::new(freq_a_prod),
+            freq_b: Input::new(freq_b_prod),
+            freq_c: Input::new(freq_c_prod),
+            amp: Input::new(amp_prod),
+            amp_c: Input::new(amp_c_prod),
+            outputs: vec![out_l_con, out_r_con],
+            out_z: out_z_con,
+            level: 1.0,
+            depth: false,
+        };
+        graph.set_level(1.0);
+        graph
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Oscillators for Graph {
+    fn set_freqs(&mut self, x_freq: f32, y_freq: f32, z_freq: Option<f32>) {
+        self.freq_a.set(x_freq);
+        self.freq_b.set(y_freq);
+        if let Some(z_freq) = z_freq {
+            self.freq_c.set(z_freq);
+        }
+        if self.depth != z_freq.is_some() {
+            self.depth = z_freq.is_some();
+            self.set_level(self.level);
+        }
+    }
+
+    fn set_level(&mut self, level: f32) {
+        self.level = level;
+        self.amp.set(Tone::AMP * level);
+        let z_level = if self.depth { level } else { 0.0 };
+        self.amp_c.set(Tone::AMP * z_level);
+    }
+
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        for input in [
+            &mut self.freq_a,
+            &mut self.freq_b,
+            &mut self.freq_c,
+            &mut self.amp,
+            &mut self.amp_c,
+        ] {
+            input.send();
+        }
+        self.graph.prepare(sample_rate.into());
+        self.graph.render(out.len() / channels);
+        for frame in out.chunks_exact_mut(channels) {
+            let z = self.out_z.dequeue().unwrap() * Tone::Z_MIX;
+            for (channel, output) in frame.iter_mut().zip(self.outputs.iter_mut()) {
+                *channel = output.dequeue().unwrap() + z;
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+struct Sines {
+    tone: Tone,
+    freqs: (f32, f32),
+    z_freq: Option<f32>,
+    level: f32,
+}
+
+#[cfg(not(feature = "audio"))]
+impl Default for Sines {
+    fn default() -> Self {
+        Self {
+            tone: Tone::default(),
+            freqs: (0.0, 0.0),
+            z_freq: None,
+            level: 1.0,
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl Oscillators for Sines {
+    fn set_freqs(&mut self, x_freq: f32, y_freq: f32, z_freq: Option<f32>) {
+        self.freqs = (x_freq, y_freq);
+        self.z_freq = z_freq;
+    }
+
+    fn set_level(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        for frame in out.chunks_exact_mut(channels) {
+            let stereo = channels.min(2);
+            let mut pair = [0.0; 2];
+            self.tone
+                .process(&mut pair, self.freqs, self.z_freq, sample_rate as f32);
+            for (channel, sample) in frame[..stereo].iter_mut().zip(pair) {
+                *channel = sample * self.level;
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Presets of the parameters and where the figure is, so a recalled figure
+//! comes back as it was saved rather than wherever the next jump takes it.
+//!
+//! They're every app's presets with the figure in their `state`, `--preset`
+//! reads the parameters of one and a plain preset recalled here leaves the
+//! figure where it is.
+
+use crate::figure::Lissajous;
+use app_common::param::{ParamPreset, ParamSnapshot, Params};
+
+const FREQ_IDX: &str = "freq_idx";
+const RATIO_IDX: &str = "ratio_idx";
+const SNAP: &str = "snap";
+const DEPTH: &str = "depth";
+const Z_RATIO_IDX: &str = "z_ratio_idx";
+/// the sines' level, from a key's velocity
+const LEVEL: &str = "level";
+/// the level of a preset saved without one
+const FULL_LEVEL: f32 = 1.0;
+
+pub fn capture(params: &Params, lissa: &Lissajous, level: f32) -> ParamPreset {
+    let state = [
+        (FREQ_IDX, lissa.freq_idx),
+        (RATIO_IDX, lissa.ratio_idx),
+        (SNAP, if lissa.snap { 1.0 } else { 0.0 }),
+        (DEPTH, if lissa.depth { 1.0 } else { 0.0 }),
+        (Z_RATIO_IDX, lissa.z_ratio_idx),
+        (LEVEL, level),
+    ];
+    ParamPreset {
+        params: ParamSnapshot::capture(params),
+        macros: Vec::new(),
+        state: state
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value))
+            .collect(),
+    }
+}
+
+/// the parameters and the figure, and the level for the caller to send
+/// with the figure's frequencies
+pub fn apply(preset: &ParamPreset, params: &Params, lissa: &mut Lissajous) -> f32 {
+    preset.params.apply(params);
+    let state = |name: &str| preset.state.get(name).copied();
+    if let Some(freq_idx) = state(FREQ_IDX) {
+        lissa.freq_idx = freq_idx;
+    }
+    if let Some(ratio_idx) = state(RATIO_IDX) {
+        lissa.ratio_idx = ratio_idx;
+    }
+    if let Some(snap) = state(SNAP) {
+        lissa.snap = snap > 0.5;
+    }
+    if let Some(depth) = state(DEPTH) {
+        lissa.depth = depth > 0.5;
+    }
+    if let Some(z_ratio_idx) = state(Z_RATIO_IDX) {
+        lissa.z_ratio_idx = z_ratio_idx;
+    }
+    state(LEVEL).map_or(FULL_LEVEL, |level| level.clamp(0.0, 1.0))
+}
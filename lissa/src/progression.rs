@@ -0,0 +1,156 @@
+//! Chord progressions read from standard MIDI files, for the figure to step
+//! through in time with the transport.
+//!
+//! Every track but the drums is laid out in beats, the file's own tempo is
+//! ignored so the transport sets the pace. A chord starts wherever the held
+//! notes change, and is voiced for the figure by its outer notes, the bass
+//! on x and the top on y.
+
+use dsp_common::tuning::midi_to_freq;
+use midly::{MidiMessage, Smf, Timing, TrackEventKind};
+
+/// the General MIDI drum channel, left out as drums make no chords
+const DRUMS: u8 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chord {
+    /// from the start of the progression
+    pub beat: f64,
+    /// x then y, as `Lissajous::freqs`
+    pub freqs: (f32, f32),
+}
+
+/// Chords in the order they start, looped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progression {
+    pub chords: Vec<Chord>,
+    /// in beats, to the end of the bar the last note ends in
+    pub length: f64,
+}
+
+impl Progression {
+    /// reads a standard MIDI file, one without notes is an error
+    pub fn decode(bytes: &[u8], beats_per_bar: u32) -> Result<Self, String> {
+        let smf = Smf::parse(bytes).map_err(|e| e.to_string())?;
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ppq) => f64::from(u16::from(ppq).max(1)),
+            Timing::Timecode(..) => return Err("timecode files have no beats".to_string()),
+        };
+        let mut events = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            for event in track {
+                tick += u64::from(u32::from(event.delta));
+                let (channel, message) = match event.kind {
+                    TrackEventKind::Midi { channel, message } => (u8::from(channel), message),
+                    _ => continue,
+                };
+                // a note on at velocity 0 is a note off
+                let (key, on) = match message {
+                    MidiMessage::NoteOn { key, vel } => (u8::from(key), u8::from(vel) > 0),
+                    MidiMessage::NoteOff { key, .. } => (u8::from(key), false),
+                    _ => continue,
+                };
+                if channel != DRUMS {
+                    events.push((tick, key, on));
+                }
+            }
+        }
+        // offs first, so a note struck again on the same tick stays held
+        events.sort_by_key(|&(tick, _, on)| (tick, on));
+
+        // by key, how many tracks and channels hold it
+        let mut held = [0u32; 128];
+        let mut chords: Vec<Chord> = Vec::new();
+        let mut events = events.into_iter().peekable();
+        let mut end = 0;
+        while let Some((tick, key, on)) = events.next() {
+            let count = &mut held[usize::from(key)];
+            *count = if on {
+                *count + 1
+            } else {
+                count.saturating_sub(1)
+            };
+            if matches!(events.peek(), Some(&(next, _, _)) if next == tick) {
+                continue;
+            }
+            end = tick;
+            // rests hold the chord before them
+            if let Some(freqs) = voicing(&held) {
+                if chords.last().map(|chord| chord.freqs) != Some(freqs) {
+                    chords.push(Chord {
+                        beat: tick as f64 / ticks_per_beat,
+                        freqs,
+                    });
+                }
+            }
+        }
+        if chords.is_empty() {
+            return Err("no notes".to_string());
+        }
+        let bar = f64::from(beats_per_bar.max(1));
+        Ok(Self {
+            chords,
+            length: (end as f64 / ticks_per_beat / bar).ceil().max(1.0) * bar,
+        })
+    }
+
+    /// the chord sounding at `beat` of the transport, looping, and its index
+    pub fn at(&self, beat: f64) -> (usize, &Chord) {
+        let beat = beat.rem_euclid(self.length);
+        // before the first chord the last one carries on from the loop before
+        let index = self
+            .chords
+            .partition_point(|chord| chord.beat <= beat)
+            .checked_sub(1)
+            .unwrap_or(self.chords.len() - 1);
+        (index, &self.chords[index])
+    }
+}
+
+/// the bass and the highest note that isn't one of its octaves, folded down
+/// to within the octave above it, the bass's octave when every note is one
+fn voicing(held: &[u32; 128]) -> Option<(f32, f32)> {
+    let mut keys = (0..128u8).filter(|&key| held[usize::from(key)] > 0);
+    let bass = keys.next()?;
+    let interval = keys
+        .map(|key| (key - bass) % 12)
+        .rfind(|&interval| interval != 0)
+        .unwrap_or(12);
+    Some((
+        midi_to_freq(f32::from(bass)),
+        midi_to_freq(f32::from(bass + interval)),
+    ))
+}
+
+/// Where the figure is in a progression.
+pub struct Playhead {
+    progression: Progression,
+    /// the chord last stepped onto
+    chord: Option<usize>,
+}
+
+impl Playhead {
+    pub fn new(progression: Progression) -> Self {
+        Self {
+            progression,
+            chord: None,
+        }
+    }
+
+    /// the frequencies of the chord at `beat`, when it's another than the
+    /// last step's
+    pub fn step(&mut self, beat: f64) -> Option<(f32, f32)> {
+        let (index, chord) = self.progression.at(beat);
+        if self.chord == Some(index) {
+            return None;
+        }
+        self.chord = Some(index);
+        Some(chord.freqs)
+    }
+
+    /// the next step strikes its chord even if it's the last one again
+    pub fn reset(&mut self) {
+        self.chord = None;
+    }
+}
@@ -0,0 +1,64 @@
+//! Browser build: the figure on a canvas and the two sines through WebAudio.
+//!
+//! wasm-pack build lissa --target web
+
+use crate::figure::{Lissajous, Tone};
+use app_common::web::{self, Canvas, WebAudio};
+use dsp_common::random::Rng;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+const NUM_CHANNELS: usize = 2;
+const BUFFER_SIZE: u32 = 1024;
+
+#[wasm_bindgen]
+extern "C" {
+    /// the browser's, there is no OS entropy to seed from
+    #[wasm_bindgen(js_namespace = Math)]
+    fn random() -> f64;
+}
+
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let canvas = Rc::new(Canvas::fullscreen("lissa")?);
+    let freqs = Rc::new(Cell::new((0.0, 0.0)));
+
+    let audio_freqs = freqs.clone();
+    let mut tone = Tone::new();
+    let audio = Rc::new(WebAudio::start(
+        NUM_CHANNELS,
+        BUFFER_SIZE,
+        move |buffer, sample_rate| tone.process(buffer, audio_freqs.get(), None, sample_rate),
+    )?);
+
+    let resume = audio.clone();
+    web::on_click(&canvas, move || resume.resume());
+
+    let lissa = RefCell::new(Lissajous::new(canvas.width(), canvas.height()));
+    let mut tick = 0u32;
+    let mut rng = Rng::new((random() * u64::MAX as f64) as u64);
+    web::animate(move |seconds| {
+        let _keep_alive = &audio;
+        let mut lissa = lissa.borrow_mut();
+
+        canvas.resize();
+        lissa.resize(canvas.width(), canvas.height());
+
+        tick += ((seconds * 10.0) % 2.0) as u32;
+        if tick as f32 > rng.range(1.0, 300.0) {
+            lissa.randomize(&mut rng);
+            tick = 0;
+        }
+
+        lissa.update();
+        freqs.set(lissa.freqs());
+
+        canvas.background([0.04, 0.04, 0.04]);
+        // flat on the web, z stays 0
+        let points: Vec<[f32; 2]> = lissa.points.iter().map(|&[x, y, _]| [x, y]).collect();
+        canvas.polyline(&points, 1.0, [0.0, 1.0, 0.0]);
+    });
+
+    Ok(())
+}
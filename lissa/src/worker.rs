@@ -0,0 +1,110 @@
+//! `Lissajous::compute` on a thread of its own.
+//!
+//! The UI hands over the figure's settings once a frame and draws whichever
+//! point set was finished last. There are two sets: the worker fills its
+//! own while the UI draws the other, then the two are swapped under a lock
+//! held only for the swap, so heavy curves never hold up a frame.
+
+use crate::figure::{Lissajous, Settings, NUM_POINTS};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// The finished set, waiting for the UI to swap it out.
+struct Shared {
+    points: Mutex<Vec<[f32; 3]>>,
+    /// set when `points` holds a set the UI hasn't taken
+    fresh: AtomicBool,
+}
+
+pub struct Worker {
+    /// dropped to stop the thread
+    settings: Option<SyncSender<Settings>>,
+    shared: Arc<Shared>,
+    /// the set being drawn
+    points: Vec<[f32; 3]>,
+    /// the settings last handed over, a figure that hasn't changed since
+    /// isn't computed again
+    sent: Option<Settings>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared {
+            points: Mutex::new(vec![[0.0; 3]; NUM_POINTS]),
+            fresh: AtomicBool::new(false),
+        });
+        // one frame's settings in hand, later ones wait for the worker
+        let (sender, receiver) = mpsc::sync_channel::<Settings>(1);
+        let worker_shared = shared.clone();
+        let thread = thread::spawn(move || {
+            let mut lissa = Lissajous::new(0.0, 0.0);
+            for settings in receiver {
+                lissa.apply(&settings);
+                if !lissa.update() {
+                    continue;
+                }
+                if let Ok(mut points) = worker_shared.points.lock() {
+                    std::mem::swap(&mut *points, &mut lissa.points);
+                    worker_shared.fresh.store(true, Ordering::Release);
+                }
+            }
+        });
+        Self {
+            settings: Some(sender),
+            shared,
+            points: vec![[0.0; 3]; NUM_POINTS],
+            sent: None,
+            thread: Some(thread),
+        }
+    }
+
+    /// Asks for the next set, called once a frame, unless nothing it's
+    /// drawn from has changed. While the worker is still busy with the last
+    /// one this frame's is skipped, the figure slows down rather than the
+    /// frame.
+    pub fn compute(&mut self, lissa: &Lissajous) {
+        let settings = lissa.settings();
+        if self.sent == Some(settings) {
+            return;
+        }
+        if let Some(sender) = &self.settings {
+            match sender.try_send(settings) {
+                Ok(()) => self.sent = Some(settings),
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Disconnected(_)) => {
+                    eprintln!("lissa: the figure's worker has stopped");
+                    self.settings = None;
+                }
+            }
+        }
+    }
+
+    /// takes the newest finished set, if there's one the UI hasn't had
+    pub fn receive(&mut self) {
+        if self.shared.fresh.load(Ordering::Acquire) {
+            // the worker only holds the lock to swap, try again next frame
+            // rather than wait
+            if let Ok(mut points) = self.shared.points.try_lock() {
+                std::mem::swap(&mut *points, &mut self.points);
+                self.shared.fresh.store(false, Ordering::Release);
+            }
+        }
+    }
+
+    /// the set to draw, as of the last `receive`
+    pub fn points(&self) -> &[[f32; 3]] {
+        &self.points
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.settings = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use crate::dsp::{self, Engine, State, BEATS_PER_BAR, BPM};
+use crate::lsystem::{Growth, Rules};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme;
+use app_common::transport::Transport;
+use dsp_common::tuning;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a bush, unless the config names another rules file
+static RULES: Asset = Asset::new("bush.lsys", include_bytes!("../res/bush.lsys"));
+
+/// reads the rules file again, for editing it alongside
+const RELOAD: Key = Key::L;
+
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        transport,
+        tempo,
+    }
+}
+
+/// `path`, or the built in bush
+fn load_rules(path: Option<&Path>) -> Result<Rules, startup::Error> {
+    let (path, bytes) = match path {
+        Some(path) => (path.to_path_buf(), fs::read(path).map(Cow::Owned)),
+        None => (RULES.source(), RULES.bytes()),
+    };
+    let failed = |reason: String| startup::Error::Sample {
+        path: path.clone(),
+        reason,
+    };
+    let bytes = bytes.map_err(|e| failed(e.to_string()))?;
+    Rules::parse(&String::from_utf8_lossy(&bytes)).map_err(failed)
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    transport: &Transport,
+    growth: Arc<Growth>,
+) -> (Engine, UiEnd<(), State>) {
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), transport.clock(None), growth);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("lsystem", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the headless engine on the configured rules, silent if they can't be
+/// read
+fn headless_engine(config: &Config) -> Engine {
+    let params = load_params(config);
+    let growth = match load_rules(config.sample_path.as_deref()) {
+        Ok(rules) => rules.grow(dsp::depth(&params)),
+        Err(e) => {
+            eprintln!("lsystem: {}", e);
+            Growth::default()
+        }
+    };
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let (engine, _bus) = engine(config, &params, &transport, Arc::new(growth));
+    engine
+}
+
+/// the melody without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("lsystem", &config::path("lsystem"));
+    let mut engine = headless_engine(&config);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the melody on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("lsystem", &config::path("lsystem"));
+    render::headless("lsystem", headless_engine(&config), stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    transport: Transport,
+    bus: UiEnd<(), State>,
+    /// the melody as of the last buffer played
+    state: State,
+    rules: Rules,
+    /// the rules grown to the depth asked for, as the engine plays it
+    growth: Arc<Growth>,
+    /// the growth before the last one, kept so the audio thread never
+    /// frees it
+    _previous: Option<Arc<Growth>>,
+    /// the depth `growth` was asked to grow to, it may have stopped short
+    depth: usize,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("lsystem");
+    let (config, _) = shell::load_config("lsystem", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("lsystem", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("lsystem", &config, &params);
+    let serial = serial::Sensors::from_config("lsystem", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("lsystem", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    // the bush when the configured rules can't be read
+    let rules = load_rules(config.sample_path.as_deref())
+        .or_else(|e| {
+            errors.push(e);
+            load_rules(None)
+        })
+        .unwrap_or_else(|e| startup::fatal("lsystem", e));
+    let depth = dsp::depth(&params);
+    let growth = Arc::new(rules.grow(depth));
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let (engine, bus) = engine(&config, &params, &transport, growth.clone());
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "lsystem", &config, stream.stats());
+    let shell =
+        Shell::new("lsystem", config, &config_path, "pastel", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        transport,
+        bus,
+        state: State::default(),
+        rules,
+        growth,
+        _previous: None,
+        depth,
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// grows the rules again and hands the engine the result
+fn regrow(model: &mut Model) {
+    model.depth = dsp::depth(&model.params);
+    let growth = Arc::new(model.rules.grow(model.depth));
+    model._previous = Some(std::mem::replace(&mut model.growth, growth.clone()));
+    model.stream.send(move |engine| engine.set_growth(growth));
+}
+
+/// reads the rules at `path`, or the built in ones, and grows them,
+/// keeping the ones playing if they can't be read
+fn load(model: &mut Model, path: Option<&Path>) {
+    match load_rules(path) {
+        Ok(rules) => {
+            model.rules = rules;
+            model.shell.config.sample_path = path.map(Path::to_path_buf);
+            regrow(model);
+        }
+        Err(e) => eprintln!("lsystem: {}", e),
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(DroppedFile(path)),
+            ..
+        } => {
+            load(model, Some(&path));
+            return;
+        }
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == RELOAD {
+        let path = model.shell.config.sample_path.clone();
+        load(model, path.as_deref());
+        return;
+    }
+    model.transport.key_pressed(key);
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    if dsp::depth(&model.params) != model.depth {
+        regrow(model);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        let sample_path = config.sample_path.clone();
+        let reload = sample_path != model.shell.config.sample_path;
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+        if reload {
+            load(model, sample_path.as_deref());
+        }
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let growth = &model.growth;
+    let [left, bottom, right, top] = growth.bounds;
+    let scale = (area.w() / (right - left).max(1.0)).min(area.h() / (top - bottom).max(1.0));
+    // the tree's base centred on the bottom of the area
+    let origin = pt2(
+        area.x() - (left + right) * 0.5 * scale,
+        area.bottom() - bottom * scale,
+    );
+    let at = |[x, y]: [f32; 2]| origin + vec2(x, y) * scale;
+
+    // grown as far as the melody's got, the rest still to come faintly,
+    // each branch deeper in the next colour
+    // the state can be a buffer behind a regrow
+    let playing = model.state.note.and_then(|i| growth.notes.get(i));
+    let [r, g, b] = palette.line;
+    for segment in &growth.segments {
+        let sounding = playing.map_or(false, |note| {
+            (note.step..note.step + note.length).contains(&segment.step)
+        });
+        let color = if sounding {
+            let [r, g, b] = palette.accent(segment.depth);
+            rgba(r, g, b, 0.5 + 0.5 * model.state.level)
+        } else if segment.step <= model.state.step {
+            let [r, g, b] = palette.accent(segment.depth);
+            rgba(r, g, b, 0.6)
+        } else {
+            rgba(r, g, b, 0.1)
+        };
+        draw.line()
+            .start(at(segment.from))
+            .end(at(segment.to))
+            .weight(if sounding { 3.0 } else { 1.5 })
+            .color(color);
+    }
+
+    let note = match playing {
+        Some(note) => tuning::note_name(dsp::note(&model.params, note.degree).round() as i32),
+        None => "--".to_string(),
+    };
+    draw.text(&format!(
+        "{}\ngeneration {}\nstep {} of {}",
+        note, growth.generations, model.state.step, growth.steps
+    ))
+    .xy(area.top_right() + vec2(-80.0, -30.0))
+    .w(160.0)
+    .font_size(14)
+    .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
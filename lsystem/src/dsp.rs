@@ -0,0 +1,264 @@
+use crate::lsystem::Growth;
+use app_common::bus::AudioEnd;
+use app_common::param::{ParamSpec, Params};
+use app_common::render::Render;
+use app_common::transport::TransportClock;
+use dsp_common::env::{Envelope, Shape};
+use dsp_common::limiter::Limiter;
+use dsp_common::pan;
+use dsp_common::tuning::{midi_to_freq, Scale};
+use dsp_common::Wavetable;
+use std::sync::Arc;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+pub const BEATS_PER_BAR: u32 = 4;
+pub const BPM: f64 = 96.0;
+/// generations the rules can be rewritten
+pub const MAX_DEPTH: usize = 8;
+
+pub const SCALES: [Scale; 5] = [
+    Scale::MAJOR_PENTATONIC,
+    Scale::MINOR_PENTATONIC,
+    Scale::DORIAN,
+    Scale::LYDIAN,
+    Scale::WHOLE_TONE,
+];
+
+/// notes ringing at once, a release is cut short past that
+const VOICES: usize = 4;
+const SHAPE: Shape = Shape::adsr(0.005, 0.25, 0.4, 0.4);
+const TABLE_SIZE: usize = 4096;
+/// each branch deeper plays this much quieter
+const FALLOFF: f32 = 0.8;
+const GAIN: f32 = 0.25;
+
+pub const DEPTH: usize = 0;
+pub const DIVISION: usize = 1;
+pub const ROOT: usize = 2;
+pub const SCALE: usize = 3;
+pub const RANGE: usize = 4;
+pub const GATE: usize = 5;
+pub const VOLUME: usize = 6;
+
+/// how many times the rules are rewritten and how many steps a beat, then
+/// the notes the turns are read as, how long each one holds of its steps
+/// and how loud they play
+pub static PARAMS: [ParamSpec; 7] = [
+    ParamSpec::new("depth", 0.0, MAX_DEPTH as f32, 3.0),
+    ParamSpec::new("division", 1.0, 4.0, 2.0),
+    ParamSpec::new("root", 36.0, 72.0, 55.0),
+    ParamSpec::new("scale", 0.0, (SCALES.len() - 1) as f32, 0.0),
+    ParamSpec::new("range", 1.0, 4.0, 2.0).unit("oct"),
+    ParamSpec::new("gate", 0.05, 1.0, 0.8),
+    ParamSpec::new("volume", 0.0, 1.0, 0.7),
+];
+
+/// the generations asked for
+pub fn depth(params: &Params) -> usize {
+    (params.get(DEPTH).round() as usize).min(MAX_DEPTH)
+}
+
+pub fn scale(params: &Params) -> &'static Scale {
+    &SCALES[(params.get(SCALE).round() as usize).min(SCALES.len() - 1)]
+}
+
+/// the midi note `degree` plays, folded back into `range` octaves over
+/// the root so a long climb wraps round
+pub fn note(params: &Params, degree: i32) -> f32 {
+    let scale = scale(params);
+    let span = scale.len() as i32 * params.get(RANGE).round().max(1.0) as i32;
+    scale
+        .note(params.get(ROOT).round(), degree.rem_euclid(span))
+        .clamp(0.0, 127.0)
+}
+
+/// Where the melody is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// into the growth, the tree is drawn up to here
+    pub step: usize,
+    /// the note sounding, its index in the growth
+    pub note: Option<usize>,
+    /// the newest voice's envelope
+    pub level: f32,
+}
+
+/// Walks the tree's melody in time with the transport, a step a
+/// division, round again from the top when it's done.
+pub struct Engine {
+    bus: AudioEnd<(), State>,
+    params: Params,
+    transport: TransportClock,
+    growth: Arc<Growth>,
+    step: usize,
+    /// frames between the last two steps, what the notes are held for
+    interval: usize,
+    since_step: usize,
+    voices: [Voice; VOICES],
+    /// the voice taking the next note
+    next: usize,
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(
+        bus: AudioEnd<(), State>,
+        params: Params,
+        transport: TransportClock,
+        growth: Arc<Growth>,
+    ) -> Self {
+        Self {
+            bus,
+            params,
+            transport,
+            growth,
+            step: 0,
+            interval: SAMPLE_RATE / 4,
+            since_step: 0,
+            voices: [Voice::new(); VOICES],
+            next: 0,
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// plays on from the transport's step into it, the growth going out has
+    /// to be dropped off the audio thread
+    pub fn set_growth(&mut self, growth: Arc<Growth>) {
+        self.growth = growth;
+    }
+
+    /// moves to pulse `beat` of the transport and starts the note there
+    fn step(&mut self, beat: i64) {
+        if self.since_step > 0 {
+            self.interval = self.since_step;
+        }
+        self.since_step = 0;
+        self.step = beat.rem_euclid(self.growth.steps.max(1) as i64) as usize;
+        let played = match self.growth.note_at(self.step) {
+            Some(played) => *played,
+            None => return,
+        };
+        let hold = (played.length * self.interval) as f32 * self.params.get(GATE);
+        let freq = midi_to_freq(note(&self.params, played.degree));
+        let velocity = FALLOFF.powi(played.depth as i32);
+        self.voices[self.next].play(freq, hold as usize, velocity, played.pan, self.sample_rate);
+        self.next = (self.next + 1) % VOICES;
+    }
+
+    /// the voices over `out`, counting the frames since the last step
+    fn process(&mut self, out: &mut [f32], channels: usize, gain: f32) {
+        self.since_step += out.len() / channels;
+        for voice in &mut self.voices {
+            voice.process(&self.sine, out, channels, gain);
+        }
+    }
+
+    fn state(&self) -> State {
+        let newest = &self.voices[(self.next + VOICES - 1) % VOICES];
+        State {
+            step: self.step,
+            note: self.growth.note_over(self.step),
+            level: newest.envelope.value(),
+        }
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        let frames = out.len() / channels;
+        let ticks = self.transport.advance(frames, sample_rate);
+        let division = self.params.get(DIVISION).round().max(1.0) as i64;
+        let gain = self.params.get(VOLUME) * GAIN;
+        let mut done = 0;
+        // split at each step so its note starts on its frame
+        let steps = ticks.every(1.0 / division as f64, BEATS_PER_BAR as i64 * division);
+        for tick in steps {
+            self.process(
+                &mut out[done * channels..tick.frame * channels],
+                channels,
+                gain,
+            );
+            done = tick.frame;
+            self.step(tick.beat);
+        }
+        self.process(&mut out[done * channels..], channels, gain);
+
+        self.bus.publish(self.state());
+        self.limiter.process_interleaved(out, channels);
+    }
+}
+
+/// A sine and a little of its octave, panned, held for a number of frames.
+#[derive(Clone, Copy)]
+struct Voice {
+    envelope: Envelope,
+    phase: f32,
+    increment: f32,
+    /// frames until the gate closes
+    hold: usize,
+    velocity: f32,
+    pan: f32,
+}
+
+impl Voice {
+    fn new() -> Self {
+        Self {
+            envelope: Envelope::new(SHAPE),
+            phase: 0.0,
+            increment: 0.0,
+            hold: 0,
+            velocity: 0.0,
+            pan: 0.5,
+        }
+    }
+
+    fn play(&mut self, freq: f32, hold: usize, velocity: f32, pan: f32, sample_rate: u32) {
+        let rate = 1.0 / sample_rate as f32;
+        self.increment = freq * rate;
+        self.hold = hold.max(1);
+        self.velocity = velocity;
+        self.pan = pan;
+        self.envelope.gate_on(rate);
+    }
+
+    /// adds the voice to the first two channels of `out`
+    fn process(&mut self, sine: &Wavetable, out: &mut [f32], channels: usize, gain: f32) {
+        if !self.envelope.is_active() {
+            return;
+        }
+        let gain = gain * self.velocity;
+        for frame in out.chunks_exact_mut(channels) {
+            if self.hold > 0 {
+                self.hold -= 1;
+                if self.hold == 0 {
+                    self.envelope.gate_off();
+                }
+            }
+            let level = self.envelope.step();
+            let tone = sine.at(self.phase) + 0.3 * sine.at((2.0 * self.phase).fract());
+            let (left, right) = pan::equal_power(tone * level * gain, self.pan);
+            frame[0] += left;
+            if let Some(sample) = frame.get_mut(1) {
+                *sample += right;
+            }
+            self.phase = (self.phase + self.increment).fract();
+        }
+    }
+}
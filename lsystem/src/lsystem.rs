@@ -0,0 +1,358 @@
+//! L-systems, rewritten a number of generations and walked by a turtle.
+//!
+//! A rules file has an `axiom`, an `angle` in degrees and a line for each
+//! symbol rewritten, `F = F[+F]F[-F]F`, `#` starts a comment. The turtle
+//! draws a step forward on `F` or `G`, moves one without drawing on `f`,
+//! turns on `+` and `-` and saves and restores where it is on `[` and `]`.
+//! Other symbols only take part in the rewriting.
+//!
+//! The walk is also the melody: each unbroken run of steps forward is a
+//! note as many steps long, a turn moves it a scale degree up or down and
+//! a branch remembers the degree it left from, so the melody climbs and
+//! falls back as the branches do.
+
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+
+/// symbols kept at most, generations past this are left out so a deep
+/// tree doesn't stall the window
+pub const MAX_SYMBOLS: usize = 50_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub axiom: String,
+    /// in degrees
+    pub angle: f32,
+    rewrites: HashMap<char, String>,
+}
+
+impl Rules {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut axiom = None;
+        let mut angle = 90.0;
+        let mut rewrites = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = || format!("line {}: {}", i + 1, line);
+            if let Some((symbol, replacement)) = line.split_once('=') {
+                let mut symbol = symbol.trim().chars();
+                match (symbol.next(), symbol.next()) {
+                    (Some(symbol), None) => {
+                        rewrites.insert(symbol, replacement.split_whitespace().collect());
+                    }
+                    _ => return Err(format!("{}, rules rewrite a single symbol", bad())),
+                }
+            } else if let Some(rest) = line.strip_prefix("axiom") {
+                axiom = Some(rest.split_whitespace().collect::<String>());
+            } else if let Some(rest) = line.strip_prefix("angle") {
+                angle = rest.trim().parse().map_err(|_| bad())?;
+            } else {
+                return Err(bad());
+            }
+        }
+        match axiom {
+            Some(axiom) if !axiom.is_empty() => Ok(Self {
+                axiom,
+                angle,
+                rewrites,
+            }),
+            _ => Err("no axiom".to_string()),
+        }
+    }
+
+    /// the axiom rewritten `generations` times, or as many as fit in
+    /// `MAX_SYMBOLS`, and how many that was
+    pub fn expand(&self, generations: usize) -> (String, usize) {
+        let mut symbols = self.axiom.clone();
+        for generation in 0..generations {
+            let mut next = String::with_capacity(symbols.len() * 2);
+            for symbol in symbols.chars() {
+                match self.rewrites.get(&symbol) {
+                    Some(replacement) => next.push_str(replacement),
+                    None => next.push(symbol),
+                }
+                if next.len() > MAX_SYMBOLS {
+                    return (symbols, generation);
+                }
+            }
+            symbols = next;
+        }
+        (symbols, generations)
+    }
+
+    /// expanded and walked
+    pub fn grow(&self, generations: usize) -> Growth {
+        let (symbols, generations) = self.expand(generations);
+        let mut growth = Growth::walk(&symbols, self.angle.to_radians());
+        growth.generations = generations;
+        growth
+    }
+}
+
+/// A line the turtle drew.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    /// the step it was drawn on, when it plays
+    pub step: usize,
+    /// branches deep
+    pub depth: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    /// the first step it plays on
+    pub step: usize,
+    /// in steps
+    pub length: usize,
+    /// scale degrees above the root, or below
+    pub degree: i32,
+    /// branches deep, deeper notes play quieter
+    pub depth: usize,
+    /// where it starts across the tree, from 0 on the left to 1
+    pub pan: f32,
+}
+
+/// The tree and its melody, the notes in the order they play.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Growth {
+    pub generations: usize,
+    pub segments: Vec<Segment>,
+    pub notes: Vec<Note>,
+    /// the melody's length, notes and rests
+    pub steps: usize,
+    /// left, bottom, right and top of what was drawn
+    pub bounds: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Turtle {
+    position: [f32; 2],
+    heading: f32,
+    degree: i32,
+}
+
+impl Turtle {
+    /// a step ahead
+    fn forward(&mut self) -> [f32; 2] {
+        let [x, y] = self.position;
+        self.position = [x + self.heading.cos(), y + self.heading.sin()];
+        self.position
+    }
+}
+
+impl Growth {
+    /// `symbols` walked from the origin facing up, turning `angle` radians
+    fn walk(symbols: &str, angle: f32) -> Self {
+        let mut turtle = Turtle {
+            position: [0.0; 2],
+            heading: FRAC_PI_2,
+            degree: 0,
+        };
+        let mut stack = Vec::new();
+        let mut growth = Growth::default();
+        // the note being drawn, ended by anything but another step
+        let mut note: Option<Note> = None;
+        let mut bounds = [0.0f32; 4];
+        for symbol in symbols.chars() {
+            if !matches!(symbol, 'F' | 'G') {
+                if let Some(note) = note.take() {
+                    growth.notes.push(note);
+                }
+            }
+            match symbol {
+                'F' | 'G' => {
+                    let from = turtle.position;
+                    let to = turtle.forward();
+                    let step = growth.steps;
+                    let depth = stack.len();
+                    growth.segments.push(Segment {
+                        from,
+                        to,
+                        step,
+                        depth,
+                    });
+                    match &mut note {
+                        Some(note) => note.length += 1,
+                        None => {
+                            note = Some(Note {
+                                step,
+                                length: 1,
+                                degree: turtle.degree,
+                                depth,
+                                pan: 0.5,
+                            })
+                        }
+                    }
+                    bounds = [
+                        bounds[0].min(to[0]),
+                        bounds[1].min(to[1]),
+                        bounds[2].max(to[0]),
+                        bounds[3].max(to[1]),
+                    ];
+                    growth.steps += 1;
+                }
+                'f' => {
+                    turtle.forward();
+                    growth.steps += 1;
+                }
+                '+' => {
+                    turtle.heading += angle;
+                    turtle.degree += 1;
+                }
+                '-' => {
+                    turtle.heading -= angle;
+                    turtle.degree -= 1;
+                }
+                '[' => stack.push(turtle),
+                ']' => {
+                    if let Some(saved) = stack.pop() {
+                        turtle = saved;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(last) = note {
+            growth.notes.push(last);
+        }
+
+        // the notes' starting points across the tree, now that it's known
+        let width = (bounds[2] - bounds[0]).max(f32::EPSILON);
+        let mut starts = growth
+            .segments
+            .iter()
+            .map(|segment| (segment.step, segment.from[0]));
+        for note in &mut growth.notes {
+            if let Some((_, x)) = starts.find(|&(step, _)| step == note.step) {
+                note.pan = (x - bounds[0]) / width;
+            }
+        }
+        growth.bounds = bounds;
+        growth
+    }
+
+    /// the note starting on `step`
+    pub fn note_at(&self, step: usize) -> Option<&Note> {
+        self.notes
+            .binary_search_by_key(&step, |note| note.step)
+            .ok()
+            .map(|i| &self.notes[i])
+    }
+
+    /// the index of the note sounding over `step`
+    pub fn note_over(&self, step: usize) -> Option<usize> {
+        let i = self.notes.partition_point(|note| note.step <= step);
+        let i = i.checked_sub(1)?;
+        let note = &self.notes[i];
+        if step < note.step + note.length {
+            Some(i)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5
+    }
+
+    #[test]
+    fn rewriting_replaces_every_symbol_at_once() {
+        // Lindenmayer's algae
+        let algae = Rules::parse("axiom A\nA = AB\nB = A").unwrap();
+        let generations: Vec<_> = (0..5).map(|n| algae.expand(n).0).collect();
+        assert_eq!(generations, ["A", "AB", "ABA", "ABAAB", "ABAABABA"]);
+
+        // symbols without a rule are carried over
+        let koch = Rules::parse("axiom F+F\nangle 60\nF = F-F++F-F").unwrap();
+        let (symbols, generations) = koch.expand(2);
+        assert_eq!(generations, 2);
+        assert_eq!(symbols.matches('F').count(), 32);
+        assert_eq!(symbols.matches('+').count(), 1 + 2 * 2 + 2 * 8);
+    }
+
+    #[test]
+    fn expansion_stops_short_of_the_limit() {
+        let doubling = Rules::parse("axiom F\nF = FF").unwrap();
+        let (symbols, generations) = doubling.expand(30);
+        assert_eq!(symbols.len(), 1 << generations);
+        assert!(symbols.len() <= MAX_SYMBOLS && symbols.len() * 2 > MAX_SYMBOLS);
+    }
+
+    #[test]
+    fn parsing_skips_comments_and_spaces() {
+        let bush = Rules::parse(
+            "# a bush\naxiom F  # the trunk\n\nangle 22.5\nF = FF-[-F+F+F] + [+F-F-F]\n",
+        )
+        .unwrap();
+        assert_eq!(bush.axiom, "F");
+        assert_eq!(bush.angle, 22.5);
+        assert_eq!(bush.expand(1).0, "FF-[-F+F+F]+[+F-F-F]");
+
+        assert_eq!(Rules::parse("axiom X").unwrap().angle, 90.0);
+        assert!(Rules::parse("F = FF").is_err());
+        assert!(Rules::parse("axiom\nF = FF").is_err());
+        assert!(Rules::parse("axiom F\nFF = F").is_err());
+        assert_eq!(
+            Rules::parse("axiom F\nangle steep"),
+            Err("line 2: angle steep".to_string())
+        );
+        assert!(Rules::parse("axiom F\ngrow F").is_err());
+    }
+
+    #[test]
+    fn the_turtle_branches_and_comes_back() {
+        let growth = Rules::parse("axiom F[+F]F").unwrap().grow(0);
+        let ends: Vec<_> = growth
+            .segments
+            .iter()
+            .map(|segment| (segment.from, segment.to, segment.step, segment.depth))
+            .collect();
+        let expected = [
+            ([0.0, 0.0], [0.0, 1.0], 0, 0),
+            ([0.0, 1.0], [-1.0, 1.0], 1, 1),
+            ([0.0, 1.0], [0.0, 2.0], 2, 0),
+        ];
+        assert_eq!(ends.len(), expected.len());
+        for (segment, expected) in ends.iter().zip(&expected) {
+            assert!(near(segment.0, expected.0) && near(segment.1, expected.1));
+            assert_eq!((segment.2, segment.3), (expected.2, expected.3));
+        }
+        assert!(near([growth.bounds[0], growth.bounds[1]], [-1.0, 0.0]));
+        assert!(near([growth.bounds[2], growth.bounds[3]], [0.0, 2.0]));
+
+        // the branch a degree up and quieter, back down after it
+        let notes: Vec<_> = growth
+            .notes
+            .iter()
+            .map(|note| (note.step, note.length, note.degree, note.depth))
+            .collect();
+        assert_eq!(notes, [(0, 1, 0, 0), (1, 1, 1, 1), (2, 1, 0, 0)]);
+    }
+
+    #[test]
+    fn runs_of_steps_are_notes_and_moves_are_rests() {
+        let growth = Rules::parse("axiom FFfF-G").unwrap().grow(0);
+        assert_eq!(growth.steps, 5);
+        let notes: Vec<_> = growth
+            .notes
+            .iter()
+            .map(|note| (note.step, note.length, note.degree))
+            .collect();
+        assert_eq!(notes, [(0, 2, 0), (3, 1, 0), (4, 1, -1)]);
+        assert_eq!(growth.note_over(1), Some(0));
+        assert_eq!(growth.note_over(2), None);
+        assert_eq!(growth.note_over(4), Some(2));
+        assert_eq!(growth.note_at(3).map(|note| note.length), Some(1));
+        assert_eq!(growth.note_at(1), None);
+    }
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("lsystem");
+    match &args.render {
+        Some(request) => lsystem::render(request),
+        None if args.headless => lsystem::headless(),
+        None => lsystem::run(),
+    }
+}
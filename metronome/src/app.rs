@@ -0,0 +1,345 @@
+use crate::dsp::{self, Engine, BEATS_PER_BAR, BPM, RINGS};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::link::{Link, LinkClock};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme;
+use app_common::timecode::Chase;
+use app_common::transport::Transport;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// seconds for a hit's dot to fade back after it lights
+const FLASH: f32 = 0.15;
+/// points along each ring's trail
+const TRAIL_POINTS: usize = 64;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        link,
+        transport,
+        tempo,
+    }
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    transport: &Transport,
+    link: Option<LinkClock>,
+) -> Engine {
+    let mut engine = Engine::new(params.clone(), transport.clock(link));
+    engine.set_limiter_bypass(config.bypass_limiter);
+    engine
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("metronome", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the clicks from the first bar without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("metronome", &config::path("metronome"));
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let params = shell::load_params("metronome", &config, &dsp::PARAMS);
+    let mut engine = engine(&config, &params, &transport, None);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the clicks on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("metronome", &config::path("metronome"));
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let params = shell::load_params("metronome", &config, &dsp::PARAMS);
+    render::headless(
+        "metronome",
+        engine(&config, &params, &transport, None),
+        stream_config(&config),
+    );
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    link: Link,
+    transport: Transport,
+    /// moves the transport after show control, when `timecode` is set
+    timecode: Option<Chase>,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn open_timecode(config: &Config) -> Option<Chase> {
+    let chase = Chase::open("metronome", config.timecode.as_ref()?);
+    chase.map_err(|e| eprintln!("metronome: {}", e)).ok()
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("metronome");
+    let (config, _) = shell::load_config("metronome", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("metronome", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("metronome", &config, &params);
+    let serial = serial::Sensors::from_config("metronome", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("metronome", startup::Error::Ui(format!("{:?}", e))));
+    let link = Link::new(BPM, BEATS_PER_BAR as f64);
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let engine = engine(&config, &params, &transport, Some(link.clock()));
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "metronome", &config, stream.stats());
+    let shell = Shell::new("metronome", config, &config_path, "phosphor", stream_config)
+        .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        link,
+        transport,
+        stream,
+        errors,
+        hud,
+        kiosk,
+        timecode: open_timecode(&shell.config),
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    model.transport.key_pressed(key);
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(timecode) = &mut model.timecode {
+        timecode.poll(&model.transport);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        config::reopen(
+            &model.shell.config.timecode,
+            &config.timecode,
+            &mut model.timecode,
+            || open_timecode(&config),
+        );
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+    model.link.panel(model.ids.link, palette, ui);
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+}
+
+/// `turns` of the way round clockwise from the top, `radius` out
+fn around(center: Point2, radius: f32, turns: f32) -> Point2 {
+    let angle = (0.25 - turns) * TAU;
+    center + vec2(angle.cos(), angle.sin()) * radius
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let center = area.xy();
+    let gap = area.w().min(area.h()) * 0.5 / (RINGS as f32 + 0.5);
+    let bar = BEATS_PER_BAR as f64;
+    let phase = (model.transport.position().rem_euclid(bar) / bar) as f32;
+    let bar_seconds = (bar * 60.0 / model.transport.bpm()) as f32;
+    let playing = model.transport.is_playing();
+
+    let mut ratio = Vec::new();
+    for ring in 0..RINGS {
+        let radius = gap * (ring + 1) as f32;
+        let [r, g, b] = palette.accent(ring);
+        let divisions = dsp::divisions(&model.params, ring);
+        draw.ellipse()
+            .xy(center)
+            .radius(radius)
+            .no_fill()
+            .stroke_weight(1.0)
+            .stroke(rgba(r, g, b, if divisions > 0 { 0.3 } else { 0.08 }));
+        if divisions == 0 {
+            continue;
+        }
+        ratio.push(divisions.to_string());
+
+        // the trail back to the last hit, longer on rings with fewer
+        let position = phase * divisions as f32;
+        let hit = position.floor();
+        let since = hit / divisions as f32;
+        let trail = (0..=TRAIL_POINTS).map(|i| {
+            let turns = since + (phase - since) * i as f32 / TRAIL_POINTS as f32;
+            around(center, radius, turns)
+        });
+        draw.polyline()
+            .weight(4.0)
+            .points(trail)
+            .color(rgba(r, g, b, 0.5));
+
+        // a dot per hit, the first of the bar larger, the last one played lit
+        let flash = if playing {
+            (-(position - hit) / divisions as f32 * bar_seconds / FLASH).exp()
+        } else {
+            0.0
+        };
+        for division in 0..divisions {
+            let lit = if division == hit as usize { flash } else { 0.0 };
+            let size = if division == 0 { 7.0 } else { 4.0 };
+            draw.ellipse()
+                .xy(around(center, radius, division as f32 / divisions as f32))
+                .radius(size * (1.0 + lit))
+                .color(rgba(r, g, b, 0.5 + 0.5 * lit));
+        }
+
+        // this ring's stretch of the sweep arm
+        draw.line()
+            .start(around(center, radius - gap * 0.5, phase))
+            .end(around(center, radius + gap * 0.5, phase))
+            .weight(2.0)
+            .color(rgb(r, g, b));
+    }
+
+    let [r, g, b] = palette.line;
+    let beat = (phase * BEATS_PER_BAR as f32).floor() as usize + 1;
+    draw.text(&format!("{}\nbeat {}", ratio.join(" : "), beat))
+        .xy(center)
+        .font_size(14)
+        .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,147 @@
+use app_common::param::{ParamSpec, Params};
+use app_common::render::Render;
+use app_common::transport::TransportClock;
+use dsp_common::limiter::Limiter;
+use dsp_common::Wavetable;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+pub const RINGS: usize = 4;
+pub const BEATS_PER_BAR: u32 = 4;
+pub const BPM: f64 = 90.0;
+pub const MAX_DIVISIONS: usize = 16;
+
+/// each ring's click from the innermost out, the first of a bar sounds an
+/// octave up
+const PITCHES: [f32; RINGS] = [1568.0, 1175.0, 784.0, 587.0];
+/// seconds for a click to fall by about two thirds
+const DECAY: f32 = 0.02;
+/// under this a click has died away
+const SILENT: f32 = 1e-4;
+const GAIN: f32 = 0.3;
+const TABLE_SIZE: usize = 4096;
+
+/// the first ring's divisions, the others' follow it
+pub const DIVISIONS: usize = 0;
+pub const ACCENT: usize = DIVISIONS + RINGS;
+pub const VOLUME: usize = ACCENT + 1;
+
+/// how many clicks each ring splits the bar into, none mutes it, then how
+/// much the first of each bar stands out and how loud they play
+pub static PARAMS: [ParamSpec; RINGS + 2] = [
+    ParamSpec::new("ring 1", 0.0, MAX_DIVISIONS as f32, 4.0),
+    ParamSpec::new("ring 2", 0.0, MAX_DIVISIONS as f32, 3.0),
+    ParamSpec::new("ring 3", 0.0, MAX_DIVISIONS as f32, 5.0),
+    ParamSpec::new("ring 4", 0.0, MAX_DIVISIONS as f32, 0.0),
+    ParamSpec::new("accent", 0.0, 1.0, 0.6),
+    ParamSpec::new("volume", 0.0, 1.0, 0.8),
+];
+
+/// how many clicks `ring` plays a bar, 0 while muted
+pub fn divisions(params: &Params, ring: usize) -> usize {
+    (params.get(DIVISIONS + ring).round() as usize).min(MAX_DIVISIONS)
+}
+
+/// A decaying sine, restarted by each hit.
+#[derive(Clone, Copy, Default)]
+struct Click {
+    phase: f32,
+    increment: f32,
+    level: f32,
+    decay: f32,
+}
+
+impl Click {
+    fn trigger(&mut self, freq: f32, level: f32, sample_rate: u32) {
+        self.phase = 0.0;
+        self.increment = freq / sample_rate as f32;
+        self.level = level;
+        self.decay = (-1.0 / (DECAY * sample_rate as f32)).exp();
+    }
+
+    /// adds the click to every channel of `out`
+    fn process(&mut self, out: &mut [f32], channels: usize, sine: &Wavetable) {
+        if self.level < SILENT {
+            return;
+        }
+        for frame in out.chunks_exact_mut(channels) {
+            let sample = sine.at(self.phase) * self.level;
+            for out in frame.iter_mut() {
+                *out += sample;
+            }
+            self.phase = (self.phase + self.increment).fract();
+            self.level *= self.decay;
+        }
+    }
+}
+
+/// A click per ring, each hit where the transport crosses one of the ring's
+/// divisions of the bar.
+pub struct Engine {
+    params: Params,
+    transport: TransportClock,
+    clicks: [Click; RINGS],
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(params: Params, transport: TransportClock) -> Self {
+        Self {
+            params,
+            transport,
+            clicks: [Click::default(); RINGS],
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+
+        let frames = out.len() / channels;
+        let ticks = self.transport.advance(frames, sample_rate);
+        let gain = self.params.get(VOLUME) * GAIN;
+        let offbeat = 1.0 - self.params.get(ACCENT);
+        for (ring, click) in self.clicks.iter_mut().enumerate() {
+            let divisions = divisions(&self.params, ring);
+            let mut done = 0;
+            if divisions > 0 {
+                // split at each hit so it starts on its frame
+                let step = BEATS_PER_BAR as f64 / divisions as f64;
+                for tick in ticks.every(step, divisions as i64) {
+                    click.process(
+                        &mut out[done * channels..tick.frame * channels],
+                        channels,
+                        &self.sine,
+                    );
+                    done = tick.frame;
+                    let (freq, level) = if tick.bar {
+                        (PITCHES[ring] * 2.0, 1.0)
+                    } else {
+                        (PITCHES[ring], offbeat)
+                    };
+                    click.trigger(freq, level * gain, sample_rate);
+                }
+            }
+            click.process(&mut out[done * channels..], channels, &self.sine);
+        }
+
+        self.limiter.process_interleaved(out, channels);
+    }
+}
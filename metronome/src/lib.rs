@@ -0,0 +1,4 @@
+mod app;
+mod dsp;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("metronome");
+    match &args.render {
+        Some(request) => metronome::render(request),
+        None if args.headless => metronome::headless(),
+        None => metronome::run(),
+    }
+}
@@ -0,0 +1,432 @@
+use crate::dsp::{self, Engine, Strip, GAIN, MASTER, MUTE, PAN, STRIPS};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::UiEnd;
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::Params;
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Palette, Themed};
+use app_common::transport::Transport;
+use app_common::widget::{Knob, StereoMeter};
+use dsp_common::meter::{self, MeterReader, Reading};
+use lissa::figure::{Lissajous, Settings};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use yfes::{Voices, NUM_VOICES};
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// room on the left for the strips
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+const KNOB_SIZE: f32 = 60.0;
+/// quieter than this and an engine is drawn as good as gone
+const FLOOR_DB: f32 = -60.0;
+
+widget_ids! {
+    struct Ids {
+        master,
+        master_meter,
+        transport,
+        tempo,
+    }
+}
+
+widget_ids! {
+    struct StripIds {
+        mute,
+        gain,
+        pan,
+        meter,
+    }
+}
+
+/// What the engines hand back to draw by.
+struct Readers {
+    figure: UiEnd<(), Settings>,
+    voices: UiEnd<(), Voices>,
+    meter: MeterReader,
+}
+
+/// lissa and yfes as they last ran, both on `transport`
+fn engine(config: &Config, params: &Params, transport: &Transport) -> (Engine, Readers) {
+    let (lissa, figure) = lissa::embedded(transport);
+    let (yfes, voices) = yfes::embedded(transport);
+    let (meter_out, meter) = meter::channel((STRIPS.len() + 1) * 2);
+    let strips = vec![Strip::new(Box::new(lissa)), Strip::new(Box::new(yfes))];
+    let mut engine = Engine::new(strips, meter_out, params.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    let readers = Readers {
+        figure,
+        voices,
+        meter,
+    };
+    (engine, readers)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("mixer", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// both engines without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("mixer", &config::path("mixer"));
+    let transport = Transport::new(120.0, 4);
+    let params = shell::load_params("mixer", &config, &dsp::PARAMS);
+    let (mut engine, _readers) = engine(&config, &params, &transport);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// both engines on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("mixer", &config::path("mixer"));
+    let transport = Transport::new(120.0, 4);
+    let params = shell::load_params("mixer", &config, &dsp::PARAMS);
+    let (engine, _readers) = engine(&config, &params, &transport);
+    render::headless("mixer", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    strip_ids: Vec<StripIds>,
+    /// the faders, the engine reads them directly
+    params: Params,
+    /// both engines follow it
+    transport: Transport,
+    readers: Readers,
+    /// lissa's figure as of the last buffer, laid out for the window
+    figure: Lissajous,
+    /// yfes's voices as of the last buffer
+    voices: Option<Voices>,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the faders for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("mixer");
+    let (config, _) = shell::load_config("mixer", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("mixer", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("mixer", &config, &params);
+    let serial = serial::Sensors::from_config("mixer", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("mixer", startup::Error::Ui(format!("{:?}", e))));
+    let transport = Transport::new(120.0, 4);
+    let (engine, readers) = engine(&config, &params, &transport);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "mixer", &config, stream.stats());
+    let shell =
+        Shell::new("mixer", config, &config_path, "midnight", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        strip_ids: STRIPS
+            .iter()
+            .map(|_| StripIds::new(ui.widget_id_generator()))
+            .collect(),
+        ui,
+        params,
+        transport,
+        readers,
+        figure: Lissajous::new(0.0, 0.0),
+        voices: None,
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if model.transport.key_pressed(key) {
+        return;
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(settings) = model.readers.figure.latest() {
+        model.figure.apply(&settings);
+    }
+    let area = scene_rect(app);
+    model.figure.resize(area.w(), area.h());
+    model.figure.update();
+    if let Some(voices) = model.readers.voices.latest() {
+        model.voices = Some(voices);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let palette = model.shell.themes.current();
+    let ui = &mut model.ui.set_widgets();
+    for (i, ids) in model.strip_ids.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| model.strip_ids[i].gain);
+        strip(
+            &model.params,
+            i,
+            ids,
+            previous,
+            &model.readers.meter,
+            palette,
+            ui,
+        );
+    }
+
+    let [l, r] = dsp::meter_channels(STRIPS.len());
+    StereoMeter::new([model.readers.meter.read(l), model.readers.meter.read(r)])
+        .with_style(palette.meter_style())
+        .w_h(30.0, 200.0)
+        .top_right_with_margin(MARGIN)
+        .set(model.ids.master_meter, ui);
+    let master = Knob::new(model.params.get(MASTER), -60.0, 6.0)
+        .label("master dB")
+        .precision(1)
+        .with_style(palette.control_style())
+        .w_h(KNOB_SIZE, KNOB_SIZE);
+    let master = match model.strip_ids.last() {
+        Some(last) => master.down_from(last.gain, 30.0).align_left_of(last.gain),
+        None => master.top_left_with_margin(MARGIN),
+    };
+    if let Some(value) = master.set(model.ids.master, ui) {
+        model.params.set(MASTER, value);
+    }
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+}
+
+/// strip `strip`'s mute over its fader, balance and meter, below the
+/// fader of the strip before when there is one
+fn strip(
+    params: &Params,
+    strip: usize,
+    ids: &StripIds,
+    previous: Option<widget::Id>,
+    meter: &MeterReader,
+    palette: &Palette,
+    ui: &mut UiCell,
+) {
+    let muted = params.get(dsp::param(strip, MUTE)) > 0.5;
+    // lit while playing, like a channel's on switch
+    let mute = widget::Toggle::new(!muted)
+        .w_h(200.0, 30.0)
+        .label(STRIPS[strip])
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0);
+    let mute = match previous {
+        Some(previous) => mute.down_from(previous, 30.0).align_left_of(previous),
+        None => mute.top_left_with_margin(MARGIN),
+    };
+    for on in mute.set(ids.mute, ui) {
+        params.set(dsp::param(strip, MUTE), if on { 0.0 } else { 1.0 });
+    }
+
+    if let Some(value) = Knob::new(params.get(dsp::param(strip, GAIN)), -60.0, 6.0)
+        .label("dB")
+        .precision(1)
+        .with_style(palette.control_style())
+        .w_h(KNOB_SIZE, KNOB_SIZE)
+        .down(10.0)
+        .set(ids.gain, ui)
+    {
+        params.set(dsp::param(strip, GAIN), value);
+    }
+    if let Some(value) = Knob::new(params.get(dsp::param(strip, PAN)), -1.0, 1.0)
+        .label("pan")
+        .with_style(palette.control_style())
+        .w_h(KNOB_SIZE, KNOB_SIZE)
+        .right(10.0)
+        .set(ids.pan, ui)
+    {
+        params.set(dsp::param(strip, PAN), value);
+    }
+    let [l, r] = dsp::meter_channels(strip);
+    StereoMeter::new([meter.read(l), meter.read(r)])
+        .with_style(palette.meter_style())
+        .w_h(20.0, KNOB_SIZE)
+        .right(10.0)
+        .set(ids.meter, ui);
+}
+
+/// the window right of the strips, what the engines are drawn in
+fn scene_rect(app: &App) -> Rect {
+    app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN)
+}
+
+/// 0 at `FLOOR_DB` and below to 1 at full scale, the louder side's peak
+fn presence([left, right]: [Reading; 2]) -> f32 {
+    let db = meter::to_db(left.peak.max(right.peak));
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = scene_rect(app);
+    let meter = &model.readers.meter;
+    let strip_presence = |strip: usize| {
+        let [l, r] = dsp::meter_channels(strip);
+        presence([meter.read(l), meter.read(r)])
+    };
+    // each engine moves over as it's panned and fades as it's turned down
+    let offset = |strip: usize| area.w() * 0.25 * model.params.get(dsp::param(strip, PAN));
+
+    // lissa's figure in the middle
+    let [r, g, b] = palette.line;
+    let alpha = 0.1 + 0.9 * strip_presence(0);
+    let centre = area.xy() + vec2(offset(0), 0.0);
+    let points = model
+        .figure
+        .points
+        .iter()
+        .map(|&[x, y, _]| centre + vec2(x, y));
+    draw.polyline()
+        .weight(1.0)
+        .points(points)
+        .color(rgba(r, g, b, alpha));
+
+    // yfes's grains round it, a row to each voice as yfes lays them out
+    let voices = match &model.voices {
+        Some(voices) => voices,
+        None => return,
+    };
+    let presence = strip_presence(1);
+    for (i, voice) in voices.iter().enumerate().filter(|(_, voice)| voice.active) {
+        let [ar, ag, ab] = palette.accent(i);
+        let y = area.y() + area.h() * 0.6 * ((i as f32 + 0.5) / NUM_VOICES as f32 - 0.5);
+        for grain in voice.grains.grains.iter().filter(|grain| grain.active) {
+            let x = area.x() + offset(1) + area.w() * 0.4 * (grain.pan * 2.0 - 1.0);
+            draw.ellipse()
+                .x_y(x, y)
+                .radius(2.0 + grain.volume * 12.0)
+                .color(rgba(ar, ag, ab, presence * (0.2 + 0.6 * grain.volume)));
+        }
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,181 @@
+use app_common::param::{ParamSpec, Params, Smoothed};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::meter::{from_db, MeterWriter, StereoMeter};
+use dsp_common::pan;
+use std::f32::consts::SQRT_2;
+
+/// asked of the stream unless the config says otherwise, the engines follow
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+/// yfes's, it counts the time between grains in buffers
+pub const BUFFER_SIZE: usize = 2048;
+
+/// the engines, a strip each in this order
+pub const STRIPS: [&str; 2] = ["lissa", "yfes"];
+
+pub const GAIN: usize = 0;
+pub const PAN: usize = 1;
+pub const MUTE: usize = 2;
+const PER_STRIP: usize = 3;
+pub const MASTER: usize = STRIPS.len() * PER_STRIP;
+
+/// each strip's fader, balance and mute, then the master fader
+pub static PARAMS: [ParamSpec; MASTER + 1] = [
+    ParamSpec::new("lissa gain", -60.0, 6.0, 0.0).unit("dB"),
+    ParamSpec::new("lissa pan", -1.0, 1.0, 0.0),
+    ParamSpec::new("lissa mute", 0.0, 1.0, 0.0),
+    ParamSpec::new("yfes gain", -60.0, 6.0, 0.0).unit("dB"),
+    ParamSpec::new("yfes pan", -1.0, 1.0, 0.0),
+    ParamSpec::new("yfes mute", 0.0, 1.0, 0.0),
+    ParamSpec::new("master", -60.0, 6.0, 0.0).unit("dB"),
+];
+
+/// strip `strip`'s `GAIN`, `PAN` or `MUTE`
+pub fn param(strip: usize, param: usize) -> usize {
+    strip * PER_STRIP + param
+}
+
+/// the meter channels strip `strip` is read from, the master's come after
+/// the strips'
+pub fn meter_channels(strip: usize) -> [usize; 2] {
+    [strip * 2, strip * 2 + 1]
+}
+
+/// seconds a fader takes to settle, quick enough to feel direct
+const GLIDE: f32 = 0.02;
+
+/// An engine and its fader.
+pub struct Strip {
+    engine: Box<dyn Render + Send>,
+    /// what the engine rendered, before the fader
+    scratch: Vec<f32>,
+    /// left then right
+    gains: [Smoothed; 2],
+    meter: StereoMeter,
+}
+
+impl Strip {
+    pub fn new(engine: Box<dyn Render + Send>) -> Self {
+        let mut gains = [Smoothed::new(1.0); 2];
+        gains
+            .iter_mut()
+            .for_each(|gain| gain.set_time(GLIDE, SAMPLE_RATE as f32));
+        Self {
+            engine,
+            scratch: vec![0.0; BUFFER_SIZE * NUM_CHANNELS],
+            gains,
+            meter: StereoMeter::new(SAMPLE_RATE as f32),
+        }
+    }
+}
+
+/// The strips' stereo summed through a master fader and limiter.
+pub struct Engine {
+    strips: Vec<Strip>,
+    params: Params,
+    master: Smoothed,
+    limiter: Limiter,
+    meter: StereoMeter,
+    /// each strip's two channels, then the master's
+    meter_out: MeterWriter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(strips: Vec<Strip>, meter_out: MeterWriter, params: Params) -> Self {
+        let mut master = Smoothed::new(from_db(params.get(MASTER)));
+        master.set_time(GLIDE, SAMPLE_RATE as f32);
+        Self {
+            strips,
+            params,
+            master,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            meter: StereoMeter::new(SAMPLE_RATE as f32),
+            meter_out,
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        let rate = sample_rate as f32;
+        for strip in &mut self.strips {
+            strip
+                .gains
+                .iter_mut()
+                .for_each(|gain| gain.set_time(GLIDE, rate));
+            strip.meter.set_sample_rate(rate);
+        }
+        self.master.set_time(GLIDE, rate);
+        self.limiter.set_sample_rate(rate);
+        self.meter.set_sample_rate(rate);
+    }
+}
+
+/// left and right gain for strip `strip`, panning a stereo source turns the
+/// far side down and leaves the near one be
+fn strip_gains(params: &Params, strip: usize) -> [f32; 2] {
+    if params.get(param(strip, MUTE)) > 0.5 {
+        return [0.0; 2];
+    }
+    let gain = from_db(params.get(param(strip, GAIN)));
+    let (left, right) = pan::equal_power(SQRT_2, (params.get(param(strip, PAN)) + 1.0) * 0.5);
+    [gain * left.min(1.0), gain * right.min(1.0)]
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.set_sample_rate(sample_rate);
+        }
+        // the engines fill the first two channels, the rest stay silent
+        let stereo = channels.min(2);
+        for (i, strip) in self.strips.iter_mut().enumerate() {
+            if strip.scratch.len() < out.len() {
+                // only when the stream's buffers grow past what was asked
+                strip.scratch.resize(out.len(), 0.0);
+            }
+            let scratch = &mut strip.scratch[..out.len()];
+            scratch.fill(0.0);
+            strip.engine.render(scratch, channels, sample_rate);
+
+            let targets = strip_gains(&self.params, i);
+            for (gain, target) in strip.gains.iter_mut().zip(targets) {
+                gain.set_target(target);
+            }
+            for (frame, summed) in scratch
+                .chunks_exact_mut(channels)
+                .zip(out.chunks_exact_mut(channels))
+            {
+                let sides = frame[..stereo].iter_mut().zip(&mut summed[..stereo]);
+                for ((sample, sum), gain) in sides.zip(&mut strip.gains) {
+                    *sample *= gain.step();
+                    *sum += *sample;
+                }
+            }
+            strip.meter.process_interleaved(scratch, channels);
+            let [left, right] = strip.meter.readings();
+            let [l, r] = meter_channels(i);
+            self.meter_out.write(l, left);
+            self.meter_out.write(r, right);
+        }
+
+        self.master.set_target(from_db(self.params.get(MASTER)));
+        for frame in out.chunks_exact_mut(channels) {
+            let gain = self.master.step();
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        self.limiter.process_interleaved(out, channels);
+        self.meter.process_interleaved(out, channels);
+        let [left, right] = self.meter.readings();
+        let [l, r] = meter_channels(self.strips.len());
+        self.meter_out.write(l, left);
+        self.meter_out.write(r, right);
+    }
+}
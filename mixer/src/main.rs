@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("mixer");
+    match &args.render {
+        Some(request) => mixer::render(request),
+        None if args.headless => mixer::headless(),
+        None => mixer::run(),
+    }
+}
@@ -0,0 +1,408 @@
+use crate::dsp::{self, Engine, State};
+use crate::weather;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use dsp_common::noise;
+use dsp_common::random::Rng;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// evolving on or off
+const EVOLVE: Key = Key::Space;
+/// where evolve goes when switched on from nothing
+const EVOLVE_ON: f32 = 0.5;
+
+/// rows of sea from the horizon to the shore
+const ROWS: usize = 16;
+/// points across each row
+const ROW_POINTS: usize = 96;
+/// streaks of wind and rain drawn at most, each somewhere of its own
+const SPECKS: usize = 400;
+/// seconds the foam of a wave that's come in takes to fade
+const FOAM: f32 = 3.0;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        evolve,
+    }
+}
+
+fn engine(config: &Config, params: &Params, seed: u64) -> (Engine, UiEnd<(), State>) {
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), seed);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("ocean", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the weather without a window or audio device
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("ocean", &config::path("ocean"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("ocean", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, seed);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the weather on the audio device without a window, for leaving running
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("ocean", &config::path("ocean"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("ocean", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, seed);
+    render::headless("ocean", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    bus: UiEnd<(), State>,
+    /// the weather as of the last buffer played
+    state: State,
+    /// seconds since the window opened, the sea moves with it
+    time: f32,
+    /// `time` when the last wave came in
+    broke: f32,
+    /// where each streak of wind or rain starts and how fast it goes, in
+    /// [0, 1]
+    specks: Vec<[f32; 3]>,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("ocean");
+    let (config, session_seed) = shell::load_config("ocean", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("ocean", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("ocean", &config, &params);
+    let serial = serial::Sensors::from_config("ocean", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("ocean", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params, seed);
+    let mut rng = Rng::new(seed);
+    let specks = (0..SPECKS)
+        .map(|_| [rng.unit(), rng.unit(), rng.unit()])
+        .collect();
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "ocean", &config, stream.stats());
+    let shell =
+        Shell::new("ocean", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        bus,
+        state: State::default(),
+        time: 0.0,
+        broke: f32::MIN,
+        specks,
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == EVOLVE {
+        toggle_evolve(model);
+    }
+    model.hud.key_pressed(key);
+    model
+        .shell
+        .key_pressed(app, key, &model.stream, Some(model.seed));
+}
+
+/// evolve back to nothing, or to where it's usually wanted
+fn toggle_evolve(model: &mut Model) {
+    let evolve = if model.params.get(dsp::EVOLVE) > 0.0 {
+        0.0
+    } else {
+        EVOLVE_ON
+    };
+    model.params.set(dsp::EVOLVE, evolve);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.time += update.since_last.as_secs_f32();
+    if let Some(state) = model.bus.latest() {
+        if state.count != model.state.count {
+            model.broke = model.time;
+        }
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let mut toggled = false;
+    for _value in widget::Toggle::new(model.params.get(dsp::EVOLVE) > 0.0)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("evolve")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.evolve, ui)
+    {
+        toggled = true;
+    }
+    if toggled {
+        toggle_evolve(model);
+    }
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let state = &model.state;
+    let time = model.time;
+    let [r, g, b] = palette.line;
+    let horizon = area.bottom() + area.h() * 0.55;
+    let seed = model.seed as u32;
+
+    // the sky darkens under rain
+    let sky = Rect::from_corners(pt2(area.left(), horizon), area.top_right());
+    draw.rect()
+        .xy(sky.xy())
+        .wh(sky.wh())
+        .color(rgba(r, g, b, 0.02 + 0.08 * state.rain));
+
+    // rows of sea, far ones flat and faint, near ones tall, all rising with
+    // the swell of the wave coming in
+    let swell = weather::swell(state.phase);
+    let [ar, ag, ab] = palette.accent(0);
+    for row in 0..ROWS {
+        let depth = (row as f32 + 1.0) / ROWS as f32;
+        let base = horizon - (horizon - area.bottom()) * depth.powf(1.5);
+        let height = area.h() * 0.02 * depth * (0.3 + state.waves * (0.5 + swell));
+        let roughness = 1.0 + 3.0 * state.wind;
+        let points = (0..=ROW_POINTS).map(|i| {
+            let x = i as f32 / ROW_POINTS as f32;
+            let y = noise::simplex(
+                x * roughness * 4.0 / depth + time * 0.1 * depth,
+                row as f32 * 1.7 + time * 0.2,
+                seed,
+            );
+            pt2(area.left() + area.w() * x, base + y * height)
+        });
+        draw.polyline()
+            .weight(1.0 + depth)
+            .points(points)
+            .color(rgba(ar, ag, ab, 0.15 + 0.6 * depth));
+    }
+
+    // foam where the last wave broke, spreading and fading
+    let since = time - model.broke;
+    if since < FOAM {
+        let fade = 1.0 - since / FOAM;
+        let x = area.left() + area.w() * state.pan;
+        let shore = area.bottom() + area.h() * 0.05;
+        let width = area.w() * (0.2 + 0.3 * since / FOAM) * (0.5 + state.waves);
+        let foam = model.specks.iter().take(SPECKS / 2).map(|[u, v, _]| {
+            pt2(
+                x + (u - 0.5) * width,
+                shore + v * area.h() * 0.08 * (1.0 - since / FOAM),
+            )
+        });
+        for point in foam {
+            draw.ellipse()
+                .xy(point)
+                .radius(1.5)
+                .color(rgba(r, g, b, fade * state.waves));
+        }
+    }
+
+    // wind streaks across the sky, more and faster the stronger it blows
+    let gust = 0.5 + 0.5 * state.gust;
+    let streaks = (state.wind * SPECKS as f32 * 0.1) as usize;
+    for [u, v, w] in model.specks.iter().take(streaks) {
+        let speed = 0.05 + 0.3 * state.wind * (0.5 + *w) * (0.5 + gust);
+        let x = sky.left() + sky.w() * (u + time * speed).fract();
+        let y = sky.bottom() + sky.h() * v;
+        let length = 10.0 + 60.0 * state.wind * w;
+        draw.line()
+            .start(pt2(x, y))
+            .end(pt2((x + length).min(sky.right()), y))
+            .weight(1.0)
+            .color(rgba(r, g, b, 0.1 + 0.2 * w));
+    }
+
+    // rain falling the whole height, blown sideways by the wind
+    let drops = (state.rain * SPECKS as f32) as usize;
+    let slant = state.wind * (0.3 + 0.4 * gust);
+    for [u, v, w] in model.specks.iter().rev().take(drops) {
+        let fall = (v + time * (0.8 + 0.4 * w)).fract();
+        let y = area.top() - area.h() * fall;
+        let x = area.left() + area.w() * (u + slant * fall).fract();
+        let length = 8.0 + 12.0 * w;
+        draw.line()
+            .start(pt2(x, y))
+            .end(pt2(x - slant * length, y + length))
+            .weight(1.0)
+            .color(rgba(r, g, b, 0.2 + 0.3 * state.rain));
+    }
+
+    let evolving = if model.params.get(dsp::EVOLVE) > 0.0 {
+        ", evolving"
+    } else {
+        ""
+    };
+    draw.text(&format!(
+        "wind {:.2}  waves {:.2}  rain {:.2}{}",
+        state.wind, state.waves, state.rain, evolving
+    ))
+    .xy(pt2(area.x(), area.top() - 12.0))
+    .w(area.w())
+    .font_size(14)
+    .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,171 @@
+use crate::weather::{Rain, Waves, Wind};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::noise::Drift;
+use dsp_common::random::Rng;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// frames between intensity updates, so gusts and swells move smoothly
+/// whatever the buffer size
+const BLOCK: usize = 256;
+const GAIN: f32 = 0.5;
+/// bumps a second of the drift evolving the intensities, a few minutes to
+/// go from calm to storm
+const EVOLVE_RATE: f32 = 0.01;
+
+pub const WIND: usize = 0;
+pub const WAVES: usize = 1;
+pub const RAIN: usize = 2;
+pub const SWELL: usize = 3;
+pub const EVOLVE: usize = 4;
+pub const VOLUME: usize = 5;
+
+/// how much of each weather, how long waves take, how far it all wanders
+/// on its own, then how loud it plays
+pub static PARAMS: [ParamSpec; 6] = [
+    ParamSpec::new("wind", 0.0, 1.0, 0.3),
+    ParamSpec::new("waves", 0.0, 1.0, 0.6),
+    ParamSpec::new("rain", 0.0, 1.0, 0.1),
+    ParamSpec::new("swell", 4.0, 16.0, 8.0)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    // none at 0, the intensities are left where they're set
+    ParamSpec::new("evolve", 0.0, 1.0, 0.0),
+    ParamSpec::new("volume", 0.0, 1.0, 0.7),
+];
+
+/// The weather as heard, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// intensities after evolving
+    pub wind: f32,
+    pub waves: f32,
+    pub rain: f32,
+    /// in [-1, 1]
+    pub gust: f32,
+    /// through the current wave, from 0 to 1
+    pub phase: f32,
+    /// where the current wave breaks, 0 is hard left
+    pub pan: f32,
+    /// waves come in so far
+    pub count: u64,
+}
+
+/// The three layers, remade when the sample rate changes.
+struct Weather {
+    wind: Wind,
+    waves: Waves,
+    rain: Rain,
+}
+
+impl Weather {
+    fn new(rng: &mut Rng, sample_rate: f32) -> Self {
+        Self {
+            wind: Wind::new(rng, sample_rate),
+            waves: Waves::new(rng, sample_rate),
+            rain: Rain::new(rng, sample_rate),
+        }
+    }
+}
+
+/// Wind, waves and rain from filtered noise, their intensities wandering
+/// from where they're set as far as evolve lets them.
+pub struct Engine {
+    bus: AudioEnd<(), State>,
+    params: Params,
+    weather: Weather,
+    /// one for each intensity and one for the swell
+    drifts: [Drift; 4],
+    rng: Rng,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<(), State>, params: Params, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut drift = || Drift::new(rng.next_u32(), EVOLVE_RATE);
+        let drifts = [drift(), drift(), drift(), drift()];
+        Self {
+            bus,
+            params,
+            weather: Weather::new(&mut rng, SAMPLE_RATE as f32),
+            drifts,
+            rng,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// `index`'s param moved by its drift, as far as evolve allows
+    fn evolved(&self, index: usize, drift: f32) -> f32 {
+        let evolve = self.params.get(EVOLVE);
+        (self.params.get(index) + drift * evolve).clamp(0.0, 1.0)
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+            self.weather = Weather::new(&mut self.rng, sample_rate as f32);
+        }
+        let sample_rate = sample_rate as f32;
+
+        let gain = self.params.get(VOLUME) * GAIN;
+        let mut state = State::default();
+        for block in out.chunks_mut(BLOCK * channels) {
+            let frames = block.len() / channels;
+            let seconds = frames as f32 / sample_rate;
+            let mut drifts = [0.0; 4];
+            for (drift, value) in self.drifts.iter_mut().zip(drifts.iter_mut()) {
+                *value = drift.step(seconds);
+            }
+            state.wind = self.evolved(WIND, drifts[0]);
+            state.waves = self.evolved(WAVES, drifts[1]);
+            state.rain = self.evolved(RAIN, drifts[2]);
+            // a swell a third longer or shorter at most
+            let evolve = self.params.get(EVOLVE);
+            let period = self.params.get(SWELL) * (1.0 + drifts[3] * evolve * 0.33);
+
+            let weather = &mut self.weather;
+            weather.wind.set(state.wind, frames, sample_rate);
+            weather.waves.set(state.waves, period, frames, sample_rate);
+            weather.rain.set(state.rain, frames, sample_rate);
+            for frame in block.chunks_exact_mut(channels) {
+                let wind = weather.wind.next();
+                let waves = weather.waves.next();
+                let rain = weather.rain.next(sample_rate);
+                let left = (wind.0 + waves.0 + rain.0) * gain;
+                let right = (wind.1 + waves.1 + rain.1) * gain;
+                match frame {
+                    [mono] => *mono = (left + right) * 0.5,
+                    [l, r, ..] => {
+                        *l = left;
+                        *r = right;
+                    }
+                    [] => {}
+                }
+            }
+        }
+
+        state.gust = self.weather.wind.gust();
+        state.phase = self.weather.waves.phase();
+        state.pan = self.weather.waves.pan();
+        state.count = self.weather.waves.count();
+        self.bus.publish(state);
+        self.limiter.process_interleaved(out, channels);
+    }
+}
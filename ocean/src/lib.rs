@@ -0,0 +1,5 @@
+mod app;
+mod dsp;
+mod weather;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("ocean");
+    match &args.render {
+        Some(request) => ocean::render(request),
+        None if args.headless => ocean::headless(),
+        None => ocean::run(),
+    }
+}
@@ -0,0 +1,276 @@
+//! Wind, waves and rain, each noise shaped by a single intensity.
+//!
+//! An intensity from 0 to 1 is a macro over everything a layer does: how
+//! loud, how bright, how gusty, how many drops. Layers make a stereo frame
+//! at a time and are told their intensity once a block.
+
+use dsp_common::denormal::flush;
+use dsp_common::filter::Svf;
+use dsp_common::noise::{Brown, Drift, Pink, White};
+use dsp_common::random::Rng;
+use std::f32::consts::TAU;
+
+/// seconds filters glide to a new cutoff over, long enough that once a
+/// block is smooth
+const GLIDE: f32 = 0.05;
+
+/// A level followed linearly across a block so it never steps.
+#[derive(Clone, Copy, Debug, Default)]
+struct Ramp {
+    value: f32,
+    step: f32,
+}
+
+impl Ramp {
+    fn set(&mut self, target: f32, frames: usize) {
+        self.step = (target - self.value) / frames.max(1) as f32;
+    }
+
+    #[inline(always)]
+    fn next(&mut self) -> f32 {
+        self.value += self.step;
+        self.value
+    }
+}
+
+/// Pink noise through resonant bandpasses swept by gusts.
+pub struct Wind {
+    noise: [Pink; 2],
+    filters: [Svf; 2],
+    /// one for each side, so gusts move across
+    gusts: [Drift; 2],
+    levels: [Ramp; 2],
+}
+
+impl Wind {
+    pub fn new(rng: &mut Rng, sample_rate: f32) -> Self {
+        let filter = || {
+            let mut filter = Svf::new(400.0, 2.0, sample_rate);
+            filter.set_smoothing(GLIDE);
+            filter
+        };
+        Self {
+            noise: [Pink::new(rng.next_u64()), Pink::new(rng.next_u64())],
+            filters: [filter(), filter()],
+            gusts: [
+                Drift::new(rng.next_u32(), 0.3).octaves(3),
+                Drift::new(rng.next_u32(), 0.3).octaves(3),
+            ],
+            levels: [Ramp::default(); 2],
+        }
+    }
+
+    /// how strong the gusts are now, in [-1, 1]
+    pub fn gust(&self) -> f32 {
+        (self.gusts[0].value() + self.gusts[1].value()) * 0.5
+    }
+
+    pub fn set(&mut self, intensity: f32, frames: usize, sample_rate: f32) {
+        let seconds = frames as f32 / sample_rate;
+        for side in 0..2 {
+            // stronger wind gusts harder and howls higher
+            let gust = self.gusts[side].step(seconds * (0.5 + intensity));
+            let cutoff = 200.0 * 2f32.powf(3.0 * intensity + gust * (0.5 + intensity));
+            self.filters[side].set_cutoff(cutoff);
+            // a narrow band of pink noise is quiet, brought up to the waves
+            let level = intensity * (0.7 + 0.3 * gust) * 2.5;
+            self.levels[side].set(level.max(0.0), frames);
+        }
+    }
+
+    #[inline]
+    pub fn next(&mut self) -> (f32, f32) {
+        let mut side = |i: usize| {
+            let noise = self.noise[i].sample();
+            self.filters[i].process(noise).band * self.levels[i].next()
+        };
+        (side(0), side(1))
+    }
+}
+
+/// Where a wave is between arriving and washing out, from 0 to 1: a slow
+/// rise, the crash at 0.6, then the wash. Never quite silent, the sea is
+/// always moving.
+pub fn swell(phase: f32) -> f32 {
+    let shape = if phase < 0.6 {
+        (phase / 0.6).powi(2)
+    } else {
+        (-(phase - 0.6) * 6.0).exp()
+    };
+    0.15 + 0.85 * shape
+}
+
+/// Brown and pink noise under a lowpass that opens as each wave breaks.
+pub struct Waves {
+    rumble: Brown,
+    noise: [Pink; 2],
+    filters: [Svf; 2],
+    /// through the current wave
+    phase: f32,
+    /// seconds the current wave lasts
+    period: f32,
+    /// where the current wave breaks, 0 is hard left
+    pan: f32,
+    /// waves started, so the window can tell a new one has come in
+    count: u64,
+    levels: [Ramp; 2],
+    rng: Rng,
+}
+
+impl Waves {
+    pub fn new(rng: &mut Rng, sample_rate: f32) -> Self {
+        let filter = || {
+            let mut filter = Svf::new(300.0, 0.7, sample_rate);
+            filter.set_smoothing(GLIDE);
+            filter
+        };
+        Self {
+            rumble: Brown::new(rng.next_u64()),
+            noise: [Pink::new(rng.next_u64()), Pink::new(rng.next_u64())],
+            filters: [filter(), filter()],
+            phase: 0.0,
+            period: 8.0,
+            pan: 0.5,
+            count: 0,
+            levels: [Ramp::default(); 2],
+            rng: rng.fork(),
+        }
+    }
+
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// `period` is the average seconds a wave takes, each differs a little
+    pub fn set(&mut self, intensity: f32, period: f32, frames: usize, sample_rate: f32) {
+        self.phase += frames as f32 / sample_rate / self.period;
+        if self.phase >= 1.0 {
+            self.phase = 0.0;
+            self.period = period * self.rng.range(0.8, 1.25);
+            self.pan = self.rng.range(0.25, 0.75);
+            self.count += 1;
+        }
+        let swell = swell(self.phase);
+        // bigger waves break brighter
+        let cutoff = 150.0 + 3000.0 * swell * intensity * intensity;
+        for filter in self.filters.iter_mut() {
+            filter.set_cutoff(cutoff);
+        }
+        let level = intensity * swell * 3.0;
+        self.levels[0].set(level * (1.0 - self.pan), frames);
+        self.levels[1].set(level * self.pan, frames);
+    }
+
+    #[inline]
+    pub fn next(&mut self) -> (f32, f32) {
+        let rumble = self.rumble.sample() * 0.5;
+        let mut side = |i: usize| {
+            let noise = self.noise[i].sample() + rumble;
+            self.filters[i].process(noise).low * self.levels[i].next()
+        };
+        (side(0), side(1))
+    }
+}
+
+/// drops ringing at once, a new one takes the place of the oldest
+const DROPS: usize = 24;
+
+/// A drop as a short decaying sine, started at a zero crossing so it
+/// plinks rather than clicks.
+#[derive(Clone, Copy, Debug, Default)]
+struct Droplet {
+    re: f32,
+    im: f32,
+    cos: f32,
+    sin: f32,
+    pan: f32,
+}
+
+impl Droplet {
+    #[inline(always)]
+    fn next(&mut self) -> f32 {
+        let re = self.re * self.cos - self.im * self.sin;
+        self.im = flush(self.re * self.sin + self.im * self.cos);
+        self.re = flush(re);
+        self.im
+    }
+}
+
+/// Highpassed hiss with drops landing in it.
+pub struct Rain {
+    hiss: [White; 2],
+    filters: [Svf; 2],
+    drops: [Droplet; DROPS],
+    /// the oldest drop, the next to be replaced
+    next_drop: usize,
+    /// chance of a drop each frame
+    chance: f32,
+    intensity: f32,
+    level: Ramp,
+    rng: Rng,
+}
+
+impl Rain {
+    pub fn new(rng: &mut Rng, sample_rate: f32) -> Self {
+        Self {
+            hiss: [White::new(rng.next_u64()), White::new(rng.next_u64())],
+            filters: [
+                Svf::new(3000.0, 0.7, sample_rate),
+                Svf::new(3000.0, 0.7, sample_rate),
+            ],
+            drops: [Droplet::default(); DROPS],
+            next_drop: 0,
+            chance: 0.0,
+            intensity: 0.0,
+            level: Ramp::default(),
+            rng: rng.fork(),
+        }
+    }
+
+    pub fn set(&mut self, intensity: f32, frames: usize, sample_rate: f32) {
+        // from a few drops a second to a downpour
+        self.chance = 600.0 * intensity * intensity / sample_rate;
+        self.intensity = intensity;
+        self.level.set(intensity.powf(1.5), frames);
+    }
+
+    fn land(&mut self, sample_rate: f32) {
+        let freq = self.rng.range(1500.0, 6000.0);
+        // seconds to die away
+        let decay = self.rng.range(0.005, 0.03);
+        let radius = (-6.91 / (decay * sample_rate)).exp();
+        let angle = TAU * freq / sample_rate;
+        self.drops[self.next_drop] = Droplet {
+            re: self.rng.range(0.05, 0.6) * (0.3 + 0.7 * self.intensity),
+            im: 0.0,
+            cos: radius * angle.cos(),
+            sin: radius * angle.sin(),
+            pan: self.rng.unit(),
+        };
+        self.next_drop = (self.next_drop + 1) % DROPS;
+    }
+
+    #[inline]
+    pub fn next(&mut self, sample_rate: f32) -> (f32, f32) {
+        if self.chance > 0.0 && self.rng.chance(self.chance) {
+            self.land(sample_rate);
+        }
+        let level = self.level.next();
+        let mut left = self.filters[0].process(self.hiss[0].sample()).high * level * 0.3;
+        let mut right = self.filters[1].process(self.hiss[1].sample()).high * level * 0.3;
+        for drop in self.drops.iter_mut() {
+            let sample = drop.next();
+            left += sample * (1.0 - drop.pan);
+            right += sample * drop.pan;
+        }
+        (left, right)
+    }
+}
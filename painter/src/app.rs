@@ -0,0 +1,435 @@
+use crate::canvas::{Canvas, COLUMNS, ROWS, ROWS_PER_OCTAVE};
+use crate::dsp::{self, Command, Engine, State};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::capture::timestamp;
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, ParamSnapshot, Params};
+use app_common::recorder::FileFormat;
+use app_common::render::{self, Request, Settings};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::tasks::{self, Tasks};
+use app_common::theme::{self, Palette, Themed};
+use dsp_common::random::Rng;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::path::PathBuf;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+const PLAY: Key = Key::Space;
+const CLEAR: Key = Key::C;
+/// one pass of the canvas into a WAV file next to the app
+const EXPORT: Key = Key::E;
+
+/// lines on the canvas the seed paints at startup
+const STROKES: usize = 12;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        play,
+        clear,
+        export,
+    }
+}
+
+/// the canvas the seed paints
+fn load_canvas(seed: u64) -> Canvas {
+    let canvas = Canvas::default();
+    canvas.scatter(&mut Rng::new(seed), STROKES);
+    canvas
+}
+
+fn engine(config: &Config, params: &Params, canvas: &Canvas) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), canvas.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("painter", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the seed's canvas without a window or audio device, looping
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("painter", &config::path("painter"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("painter", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, &load_canvas(seed));
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the seed's canvas on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("painter", &config::path("painter"));
+    let seed = shell::seed(session_seed);
+    let params = shell::load_params("painter", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, &load_canvas(seed));
+    render::headless("painter", engine, stream_config(&config));
+}
+
+/// renders one pass of the canvas as it is now as a task, on a copy so
+/// painting can carry on
+fn export(model: &mut Model) {
+    let params = Params::new(&dsp::PARAMS);
+    ParamSnapshot::capture(&model.params).apply(&params);
+    let (mut engine, _bus) = engine(&model.shell.config, &params, &model.canvas.copy());
+    let format = model.shell.config.recording.unwrap_or(FileFormat::Wav);
+    let settings = Settings {
+        sample_rate: dsp::SAMPLE_RATE as u32,
+        channels: dsp::NUM_CHANNELS,
+        frames_per_buffer: dsp::BUFFER_SIZE,
+        seconds: params.get(dsp::LENGTH) as f64,
+        format,
+        normalize: model.shell.config.normalize,
+    };
+    let path = PathBuf::from(format!("painter-{}.{}", timestamp(), format.extension()));
+    model.tasks.spawn("exporting", move |progress| {
+        match render::to_file(&mut engine, &path, &settings, |f| progress.set(f)) {
+            Ok(report) => {
+                println!(
+                    "painter: exported {}, peak {:.3}",
+                    path.display(),
+                    report.peak
+                );
+                if let Some(normalized) = report.normalized {
+                    println!("painter: {}", normalized);
+                }
+            }
+            Err(e) => eprintln!("painter: cannot export {}: {}", path.display(), e),
+        }
+    });
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    canvas: Canvas,
+    /// where the last dab went while a button is held, from 0 to 1
+    brush: Option<(f32, f32)>,
+    bus: UiEnd<Command, State>,
+    /// playback as of the last buffer played
+    state: State,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// exports, finished before the app exits
+    tasks: Tasks,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("painter");
+    let (config, session_seed) = shell::load_config("painter", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("painter", &config, &dsp::PARAMS);
+    let canvas = load_canvas(seed);
+    let oscquery = oscquery::Service::from_config("painter", &config, &params);
+    let serial = serial::Sensors::from_config("painter", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("painter", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params, &canvas);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "painter", &config, stream.stats());
+    let shell =
+        Shell::new("painter", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        canvas,
+        brush: None,
+        bus,
+        state: State {
+            position: 0.0,
+            playing: true,
+        },
+        stream,
+        errors,
+        hud,
+        kiosk,
+        tasks: Tasks::new("painter", tasks::THREADS),
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if model
+            .shell
+            .setup_key_pressed(key, &mut model.stream)
+            .taken()
+        {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        match key {
+            PLAY => {
+                let _ = model.bus.send(Command::Play(!model.state.playing));
+            }
+            CLEAR => model.canvas.clear(),
+            EXPORT => export(model),
+            _ => {}
+        }
+        model.hud.key_pressed(key);
+        model
+            .shell
+            .key_pressed(app, key, &model.stream, Some(model.seed));
+    }
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// where the canvas is drawn in the window
+fn canvas_rect(window: Rect) -> Rect {
+    window.pad_left(CONTROLS_WIDTH).pad(MARGIN)
+}
+
+/// the left button paints, the right one rubs out and the middle one moves
+/// the cursor, unless the controls have the mouse
+fn paint(app: &App, model: &mut Model) {
+    let buttons = &app.mouse.buttons;
+    let sign = if buttons.left().is_down() {
+        1.0
+    } else if buttons.right().is_down() {
+        -1.0
+    } else if buttons.middle().is_down() {
+        0.0
+    } else {
+        model.brush = None;
+        return;
+    };
+    let capturing = model.ui.global_input().current.widget_capturing_mouse;
+    if capturing.map_or(false, |id| id != model.ui.window) {
+        return;
+    }
+    let rect = canvas_rect(app.window_rect());
+    let at = (
+        (app.mouse.x - rect.left()) / rect.w(),
+        (app.mouse.y - rect.bottom()) / rect.h(),
+    );
+    if !(0.0..=1.0).contains(&at.0) || !(0.0..=1.0).contains(&at.1) {
+        model.brush = None;
+        return;
+    }
+    if sign == 0.0 {
+        let _ = model.bus.send(Command::Seek(at.0));
+        return;
+    }
+    let radius = model.params.get(dsp::BRUSH);
+    let intensity = model.params.get(dsp::INTENSITY) * sign;
+    match model.brush {
+        Some(from) => model.canvas.stroke(from, at, radius, intensity),
+        None => model.canvas.dab(at.0, at.1, radius, intensity),
+    }
+    model.brush = Some(at);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.tasks.poll();
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    if model.shell.setup().is_none() && model.errors.is_none() {
+        paint(app, model);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let mut exporting = false;
+    {
+        let ui = &mut model.ui.set_widgets();
+        let palette = model.shell.themes.current();
+        param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+        let label = if model.state.playing { "pause" } else { "play" };
+        for _click in button(label, palette).set(model.ids.play, ui) {
+            let _ = model.bus.send(Command::Play(!model.state.playing));
+        }
+        for _click in button("clear", palette).set(model.ids.clear, ui) {
+            model.canvas.clear();
+        }
+        for _click in button("export wav", palette).set(model.ids.export, ui) {
+            exporting = true;
+        }
+        model.tasks.panel(palette, ui);
+    }
+    if exporting {
+        export(model);
+    }
+}
+
+fn button<'a>(label: &'a str, palette: &Palette) -> widget::Button<'a, widget::button::Flat> {
+    widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let rect = canvas_rect(app.window_rect());
+    let (w, h) = (rect.w() / COLUMNS as f32, rect.h() / ROWS as f32);
+    let line = palette.line;
+    draw.rect()
+        .xy(rect.xy())
+        .wh(rect.wh())
+        .no_fill()
+        .stroke_weight(1.0)
+        .stroke(rgba(line[0], line[1], line[2], 0.5));
+    // an octave line at every A
+    for octave in 1..ROWS / ROWS_PER_OCTAVE {
+        let y = rect.bottom() + (octave * ROWS_PER_OCTAVE) as f32 * h;
+        draw.line()
+            .start(pt2(rect.left(), y))
+            .end(pt2(rect.right(), y))
+            .weight(1.0)
+            .color(rgba(line[0], line[1], line[2], 0.15));
+    }
+
+    let [r, g, b] = palette.accent(0);
+    for row in 0..ROWS {
+        for column in 0..COLUMNS {
+            let value = model.canvas.get(column, row);
+            if value > 0.0 {
+                draw.rect()
+                    .x_y(
+                        rect.left() + (column as f32 + 0.5) * w,
+                        rect.bottom() + (row as f32 + 0.5) * h,
+                    )
+                    .w_h(w, h)
+                    .color(rgba(r, g, b, value));
+            }
+        }
+    }
+
+    let x = rect.left() + model.state.position * rect.w();
+    draw.line()
+        .start(pt2(x, rect.bottom()))
+        .end(pt2(x, rect.top()))
+        .weight(2.0)
+        .color(theme::color(palette.line));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
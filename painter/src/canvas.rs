@@ -0,0 +1,120 @@
+//! The time–frequency canvas the window paints and the engine plays.
+//!
+//! Columns are time across one pass, rows are semitones up from `LOW`. The
+//! cells are atomics shared the way `Params` are, so a brush stroke is heard
+//! in the next buffer without a message per cell.
+
+use dsp_common::random::Rng;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+pub const COLUMNS: usize = 256;
+/// seven octaves of semitones
+pub const ROWS: usize = 84;
+pub const ROWS_PER_OCTAVE: usize = 12;
+/// A1, the bottom row
+pub const LOW: f32 = 55.0;
+
+/// the band `row` plays
+pub fn freq(row: usize) -> f32 {
+    LOW * 2f32.powf(row as f32 / ROWS_PER_OCTAVE as f32)
+}
+
+/// Cell loudness from 0 to 1, cheap to clone and share with the engine.
+#[derive(Clone)]
+pub struct Canvas {
+    cells: Arc<[AtomicU32]>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        let cells: Vec<AtomicU32> = (0..COLUMNS * ROWS)
+            .map(|_| AtomicU32::new(0.0f32.to_bits()))
+            .collect();
+        Self {
+            cells: cells.into(),
+        }
+    }
+}
+
+impl Canvas {
+    pub fn get(&self, column: usize, row: usize) -> f32 {
+        f32::from_bits(self.cells[row * COLUMNS + column].load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, column: usize, row: usize, value: f32) {
+        self.cells[row * COLUMNS + column]
+            .store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// a separate canvas with the same cells, `clone` shares them
+    pub fn copy(&self) -> Self {
+        let cells: Vec<AtomicU32> = self
+            .cells
+            .iter()
+            .map(|cell| AtomicU32::new(cell.load(Ordering::Relaxed)))
+            .collect();
+        Self {
+            cells: cells.into(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for cell in self.cells.iter() {
+            cell.store(0.0f32.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// adds `intensity` around `(x, y)`, both from 0 to 1 across the canvas
+    /// with y up, fading out `radius` cells away, a negative `intensity`
+    /// rubs out
+    pub fn dab(&self, x: f32, y: f32, radius: f32, intensity: f32) {
+        let (cx, cy) = (x * COLUMNS as f32, y * ROWS as f32);
+        let radius = radius.max(0.5);
+        let cells = |centre: f32, len: usize| {
+            let low = (centre - radius).floor().max(0.0) as usize;
+            let high = ((centre + radius).ceil().max(0.0) as usize).min(len);
+            low..high
+        };
+        for row in cells(cy, ROWS) {
+            for column in cells(cx, COLUMNS) {
+                let dx = column as f32 + 0.5 - cx;
+                let dy = row as f32 + 0.5 - cy;
+                let weight = 1.0 - (dx * dx + dy * dy).sqrt() / radius;
+                if weight > 0.0 {
+                    self.set(column, row, self.get(column, row) + intensity * weight);
+                }
+            }
+        }
+    }
+
+    /// dabs every half cell from `from` to `to`, so a fast drag leaves a
+    /// line rather than dots
+    pub fn stroke(&self, from: (f32, f32), to: (f32, f32), radius: f32, intensity: f32) {
+        let dx = (to.0 - from.0) * COLUMNS as f32;
+        let dy = (to.1 - from.1) * ROWS as f32;
+        let steps = ((dx * dx + dy * dy).sqrt() * 2.0).ceil().max(1.0) as usize;
+        // the start was dabbed by the previous stroke
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let x = from.0 + (to.0 - from.0) * t;
+            let y = from.1 + (to.1 - from.1) * t;
+            self.dab(x, y, radius, intensity);
+        }
+    }
+
+    /// `count` gliding lines, something to hear before anything is painted
+    pub fn scatter(&self, rng: &mut Rng, count: usize) {
+        for _ in 0..count {
+            let from = (rng.unit(), rng.range(0.1, 0.8));
+            let length = rng.range(0.05, 0.3);
+            let to = (from.0 + length, from.1 + rng.bipolar() * length * 0.5);
+            let radius = rng.range(1.0, 2.5);
+            let intensity = rng.range(0.2, 0.5);
+            self.dab(from.0, from.1, radius, intensity);
+            self.stroke(from, to, radius, intensity);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::canvas::{self, Canvas, COLUMNS, ROWS};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::Wavetable;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// per band at full volume, about seven loud bands together reach full scale
+const GAIN: f32 = 0.15;
+const TABLE_SIZE: usize = 4096;
+/// spreads the starting phases so the bands don't all peak together
+const GOLDEN: f32 = 0.618_034;
+
+pub const LENGTH: usize = 0;
+pub const BRUSH: usize = 1;
+pub const INTENSITY: usize = 2;
+pub const VOLUME: usize = 3;
+
+/// how long a pass takes, the brush, then how loud it plays
+pub static PARAMS: [ParamSpec; 4] = [
+    ParamSpec::new("length", 2.0, 30.0, 8.0)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    // in cells, and added per frame while the button is down
+    ParamSpec::new("brush", 0.5, 12.0, 2.0).curve(Curve::Exponential),
+    ParamSpec::new("intensity", 0.01, 1.0, 0.2).curve(Curve::Exponential),
+    ParamSpec::new("volume", 0.0, 1.0, 0.5),
+];
+
+pub enum Command {
+    Play(bool),
+    /// to a fraction of the way through the canvas
+    Seek(f32),
+}
+
+/// Where playback is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// from 0 to 1 across the canvas
+    pub position: f32,
+    pub playing: bool,
+}
+
+/// A sine per row, each as loud as its cell under the cursor.
+///
+/// Loudness follows the canvas between columns and ramps across each buffer,
+/// painting under the cursor is heard without clicks.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    canvas: Canvas,
+    /// in columns
+    position: f32,
+    playing: bool,
+    phases: Vec<f32>,
+    amps: Vec<f32>,
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, State>, params: Params, canvas: Canvas) -> Self {
+        Self {
+            bus,
+            params,
+            canvas,
+            position: 0.0,
+            playing: true,
+            phases: (0..ROWS).map(|row| (row as f32 * GOLDEN).fract()).collect(),
+            amps: vec![0.0; ROWS],
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// `row`'s cell at `position` columns, blended between its neighbours
+    fn level(&self, row: usize, position: f32) -> f32 {
+        let column = position.floor() as usize % COLUMNS;
+        let next = (column + 1) % COLUMNS;
+        let w = position.fract();
+        self.canvas.get(column, row) * (1.0 - w) + self.canvas.get(next, row) * w
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        for command in self.bus.commands() {
+            match command {
+                Command::Play(playing) => self.playing = playing,
+                Command::Seek(at) => self.position = at.clamp(0.0, 1.0) * COLUMNS as f32,
+            }
+        }
+
+        let frames = out.len() / channels;
+        let sample_time = 1.0 / sample_rate as f32;
+        if self.playing {
+            let speed = COLUMNS as f32 / self.params.get(LENGTH);
+            self.position = (self.position + speed * sample_time * frames as f32) % COLUMNS as f32;
+        }
+
+        // mono into the first channel, copied to the rest after
+        let gain = self.params.get(VOLUME) * GAIN;
+        for row in 0..ROWS {
+            let target = if self.playing {
+                self.level(row, self.position)
+            } else {
+                0.0
+            };
+            let mut amp = self.amps[row];
+            if amp == 0.0 && target == 0.0 {
+                continue;
+            }
+            let step = (target - amp) / frames as f32;
+            let increment = canvas::freq(row) * sample_time;
+            let phase = &mut self.phases[row];
+            for frame in out.chunks_exact_mut(channels) {
+                frame[0] += self.sine.at(*phase) * amp * gain;
+                *phase = (*phase + increment).fract();
+                amp += step;
+            }
+            self.amps[row] = target;
+        }
+        for frame in out.chunks_exact_mut(channels) {
+            let sample = frame[0];
+            for out in frame[1..].iter_mut() {
+                *out = sample;
+            }
+        }
+
+        self.bus.publish(State {
+            position: self.position / COLUMNS as f32,
+            playing: self.playing,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
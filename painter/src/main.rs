@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("painter");
+    match &args.render {
+        Some(request) => painter::render(request),
+        None if args.headless => painter::headless(),
+        None => painter::run(),
+    }
+}
@@ -0,0 +1,14 @@
+// A bar for each band, flashing on the beat.
+//
+// Shaders give a fragment `main` taking `uv`, 0 to 1 across the window up
+// from the bottom left. `u` and `band(i)` are declared for them, see the
+// playground's prelude.
+
+@fragment
+fn main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    let level = band(i32(uv.x * 8.0));
+    let lit = step(uv.y, level);
+    let flash = exp(-u.since_beat * 6.0);
+    let hue = vec3<f32>(uv.x, 0.4 + 0.4 * uv.y, 1.0 - uv.x);
+    return vec4<f32>(hue * lit + vec3<f32>(0.15 * flash), 1.0);
+}
@@ -0,0 +1,15 @@
+// Rings spreading from the centre, pushed out by the bass and brightened
+// by the highs.
+
+@fragment
+fn main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    let aspect = u.resolution.x / max(u.resolution.y, 1.0);
+    let p = (uv - vec2<f32>(0.5)) * vec2<f32>(aspect, 1.0);
+    let r = length(p);
+    let bass = band(0) + band(1);
+    let rings = 0.5 + 0.5 * sin(r * 40.0 - u.time * 3.0 - bass * 6.0);
+    let glow = exp(-r * (4.0 - 3.0 * u.rms)) * (0.4 + band(6));
+    let flash = exp(-u.since_beat * 4.0);
+    let color = vec3<f32>(0.2 + flash, 0.4, 0.9) * rings * glow;
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,643 @@
+use crate::canvas::{self, Canvas, Uniforms, BANDS};
+use crate::dsp::{self, Engine, Source, State};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::input::{self, Input, InputConfig};
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::setup::AudioSettings;
+use app_common::shell::{self, SetupKey, Shell};
+use app_common::spectrum::{Analyzer, AnalyzerInput};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use app_common::watch::FileWatcher;
+use dsp_common::analysis::{self, Beats};
+use dsp_common::spectrum::StftConfig;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a bar of guitar, shared with yfes, listened to by `--render` in place
+/// of an input
+static LOOP: Asset = Asset::new("gtr.wav", include_bytes!("../../yfes/res/gtr.wav"));
+
+/// written to the shader folder when it has none, to start from
+const EXAMPLES: [(&str, &str); 2] = [
+    ("bands.wgsl", include_str!("../res/bands.wgsl")),
+    ("rings.wgsl", include_str!("../res/rings.wgsl")),
+];
+
+const PREVIOUS: Key = Key::Left;
+const NEXT: Key = Key::Right;
+
+/// seconds a band takes to fall back, they jump straight up
+const FALL: f32 = 0.15;
+/// what `since_beat` stops counting at, before the first beat too
+const LONG_AGO: f32 = 1000.0;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        next,
+    }
+}
+
+/// the rate the stream is asked for, the input and analysis follow it
+fn sample_rate(config: &Config) -> u32 {
+    config.sample_rate.unwrap_or(dsp::SAMPLE_RATE as u32)
+}
+
+/// the first channel of the configured input device, at the rate the
+/// output is asked for
+fn open_input(config: &Config) -> Result<(Input, Source), input::Error> {
+    let (input, reader) = Input::start(&InputConfig {
+        host: config.audio_host.clone(),
+        device: config.input_device.clone(),
+        channels: vec![0],
+        sample_rate: Some(sample_rate(config)),
+        frames_per_buffer: config.buffer_size,
+        ..InputConfig::default()
+    })?;
+    Ok((input, Source::Input(reader)))
+}
+
+/// the spectrum the bands are read from, barely smoothed so beats stand out
+fn start_analyzer(config: &Config) -> Option<(Analyzer, AnalyzerInput)> {
+    let stft = StftConfig {
+        size: 2048,
+        hop: 512,
+        smoothing: 0.2,
+        ..StftConfig::default()
+    };
+    match Analyzer::start(stft, 1, sample_rate(config) as f32) {
+        Ok(analyzer) => Some(analyzer),
+        Err(e) => {
+            eprintln!("playground: cannot start the analysis: {}", e);
+            None
+        }
+    }
+}
+
+/// the built in loop mixed to mono, and its rate
+fn load_loop() -> Result<(Vec<f32>, u32), String> {
+    let bytes = LOOP.bytes().map_err(|e| e.to_string())?;
+    let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = reader
+        .into_samples::<i16>()
+        .map(|s| s.map(|s| s as f32 / 32_768.0))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    source: Source,
+    analyzer: Option<AnalyzerInput>,
+) -> (Engine, UiEnd<(), State>) {
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), source, analyzer);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("playground", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the built in loop as it's monitored, without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("playground", &config::path("playground"));
+    let (samples, sample_rate) = load_loop().unwrap_or_else(|e| {
+        eprintln!("playground: cannot read {}: {}", LOOP.source().display(), e);
+        std::process::exit(1);
+    });
+    let source = Source::Loop {
+        samples,
+        position: 0,
+    };
+    let params = shell::load_params("playground", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, source, None);
+    request.run(
+        &mut engine,
+        sample_rate,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the input monitored on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("playground", &config::path("playground"));
+    let (input, source) = match open_input(&config) {
+        Ok(input) => input,
+        Err(e) => startup::fatal("playground", startup::Error::Input(e.to_string())),
+    };
+    println!("playground: listening on {}", input.device());
+    let params = shell::load_params("playground", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, source, None);
+    render::headless("playground", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+/// The shader folder and which of its files is drawn.
+struct Shaders {
+    dir: PathBuf,
+    /// the `.wgsl` files in it, by name
+    files: Vec<PathBuf>,
+    current: usize,
+    /// reports saves to the current file
+    watcher: Option<FileWatcher>,
+    /// why the current file didn't compile, the last one that did is drawn
+    error: Option<String>,
+}
+
+impl Shaders {
+    /// beside the config, with the examples written to it if it has no
+    /// shaders yet
+    fn new(config_path: &Path) -> Self {
+        let dir = config_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join("shaders");
+        let mut shaders = Self {
+            dir,
+            files: Vec::new(),
+            current: 0,
+            watcher: None,
+            error: None,
+        };
+        shaders.scan();
+        if shaders.files.is_empty() {
+            let written = fs::create_dir_all(&shaders.dir).and_then(|_| {
+                EXAMPLES
+                    .iter()
+                    .try_for_each(|(name, source)| fs::write(shaders.dir.join(name), source))
+            });
+            if let Err(e) = written {
+                eprintln!("playground: cannot write {}: {}", shaders.dir.display(), e);
+            }
+            shaders.scan();
+        }
+        println!("playground: shaders in {}", shaders.dir.display());
+        shaders
+    }
+
+    fn scan(&mut self) {
+        let current = self.path().map(Path::to_path_buf);
+        self.files = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().map_or(false, |ext| ext == "wgsl"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.files.sort();
+        self.current = current
+            .and_then(|current| self.files.iter().position(|path| *path == current))
+            .unwrap_or(0);
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.files.get(self.current).map(PathBuf::as_path)
+    }
+
+    /// the one `step` files on, picking up any added or removed since
+    fn step(&mut self, step: isize) {
+        self.scan();
+        if !self.files.is_empty() {
+            let len = self.files.len() as isize;
+            self.current = (self.current as isize + step).rem_euclid(len) as usize;
+        }
+    }
+
+    /// compiles the current file into `canvas`, and watches it for saves
+    fn load(&mut self, window: &Window, canvas: &mut Canvas) {
+        let path = match self.path() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                self.error = Some(format!("no .wgsl files in {}", self.dir.display()));
+                return;
+            }
+        };
+        // the old file's saves aren't wanted any more
+        self.watcher = FileWatcher::new()
+            .and_then(|mut watcher| watcher.watch(&path).map(|_| watcher))
+            .map_err(|e| eprintln!("playground: cannot watch {}: {}", path.display(), e))
+            .ok();
+        self.reload(window, canvas);
+    }
+
+    fn reload(&mut self, window: &Window, canvas: &mut Canvas) {
+        let path = match self.path() {
+            Some(path) => path,
+            None => return,
+        };
+        let compiled = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| canvas::compile(&source));
+        match compiled {
+            Ok(spirv) => {
+                canvas.set_shader(window, &spirv);
+                self.error = None;
+            }
+            Err(e) => {
+                eprintln!("playground: {}:\n{}", path.display(), e);
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// true if the current file was saved since last asked
+    fn saved(&mut self) -> bool {
+        self.watcher
+            .as_mut()
+            .map_or(false, |watcher| !watcher.poll().is_empty())
+    }
+
+    fn name(&self) -> String {
+        self.path()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<(), State>,
+    /// the level as of the last buffer played
+    state: State,
+    /// dropping it stops listening
+    input: Option<Input>,
+    stream: Supervisor<Engine>,
+    /// `None` if its thread wouldn't start, the bands stay at 0
+    analyzer: Option<Analyzer>,
+    /// frames it had analysed when last read
+    analysed: usize,
+    magnitudes: Vec<f32>,
+    /// from the latest spectrum
+    bands: [f32; BANDS],
+    /// seconds since then, the beats are found a spectrum at a time
+    unanalysed: f32,
+    /// each band's `analysis::level`, falling back slowly
+    levels: [f32; BANDS],
+    beats: Beats,
+    canvas: Canvas,
+    shaders: Shaders,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("playground");
+    let (config, _) = shell::load_config("playground", &config_path);
+    let window = config.build_window(app, view);
+    let params = shell::load_params("playground", &config, &dsp::PARAMS);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("playground", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    let (input, source) = match open_input(&config) {
+        Ok((input, source)) => (Some(input), source),
+        Err(e) => {
+            errors.push(startup::Error::Input(e.to_string()));
+            (None, Source::Silence)
+        }
+    };
+    let (analyzer, analyzer_input) = start_analyzer(&config).unzip();
+    let (engine, bus) = engine(&config, &params, source, analyzer_input);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "playground", &config, stream.stats());
+    let oscquery = oscquery::Service::from_config("playground", &config, &params);
+    let serial = serial::Sensors::from_config("playground", &config, &params);
+
+    let window = app.window(window).unwrap();
+    let mut canvas = Canvas::new(&window);
+    let mut shaders = Shaders::new(&config_path);
+    shaders.load(&window, &mut canvas);
+    let shell = Shell::new(
+        "playground",
+        config,
+        &config_path,
+        "phosphor",
+        stream_config,
+    )
+    .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        state: State::default(),
+        input,
+        stream,
+        analyzer,
+        analysed: 0,
+        magnitudes: Vec::new(),
+        bands: [0.0; BANDS],
+        unanalysed: 0.0,
+        levels: [0.0; BANDS],
+        beats: Beats::default(),
+        canvas,
+        shaders,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// listens on the input the config names now, keeping the old one if the
+/// new one won't open, and analyses at its rate
+fn reopen_input(model: &mut Model, config: &Config) {
+    match open_input(config) {
+        Ok((input, source)) => {
+            model.input = Some(input);
+            let (analyzer, analyzer_input) = start_analyzer(config).unzip();
+            model.stream.send(move |engine| {
+                engine.set_source(source);
+                engine.set_analyzer(analyzer_input);
+            });
+            model.analyzer = analyzer;
+            model.analysed = 0;
+            model.bands = [0.0; BANDS];
+        }
+        Err(e) => eprintln!("playground: {}", e),
+    }
+}
+
+fn step_shader(app: &App, model: &mut Model, step: isize) {
+    model.shaders.step(step);
+    model.shaders.load(&app.main_window(), &mut model.canvas);
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if setup_key_pressed(model, key) {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    match key {
+        PREVIOUS => step_shader(app, model, -1),
+        NEXT => step_shader(app, model, 1),
+        _ => {}
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+/// true while the setup screen takes the keys, the input follows the
+/// output's rate so it's reopened on any change
+fn setup_key_pressed(model: &mut Model, key: Key) -> bool {
+    let outcome = model.shell.setup_key_pressed(key, &mut model.stream);
+    if let SetupKey::Applied(previous) = &outcome {
+        if *previous != AudioSettings::from_config(&model.shell.config) || model.input.is_none() {
+            let config = model.shell.config.clone();
+            reopen_input(model, &config);
+        }
+    }
+    outcome.taken()
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// the bands and beats from any new spectrum, the levels falling back
+/// towards the bands over `dt` seconds
+fn analyse(model: &mut Model, dt: f32) {
+    model.unanalysed += dt;
+    if let Some(analyzer) = &model.analyzer {
+        let frames = analyzer.frames();
+        if frames != model.analysed {
+            model.analysed = frames;
+            analyzer.read(&mut model.magnitudes);
+            analysis::bands(
+                &model.magnitudes,
+                analyzer.bin_frequency(1),
+                &mut model.bands,
+            );
+            model.beats.push(&model.bands, model.unanalysed);
+            model.unanalysed = 0.0;
+        }
+    }
+    let fall = 1.0 - (-dt / FALL).exp();
+    for (level, band) in model.levels.iter_mut().zip(model.bands.iter()) {
+        let target = analysis::level(*band);
+        if target > *level {
+            *level = target;
+        } else {
+            *level += (target - *level) * fall;
+        }
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    analyse(model, update.since_last.as_secs_f32());
+    if model.shaders.saved() {
+        model.shaders.reload(&app.main_window(), &mut model.canvas);
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        draw.background()
+            .color(theme::color(model.shell.themes.current().background));
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if AudioSettings::from_config(&config) != AudioSettings::from_config(&model.shell.config) {
+            reopen_input(model, &config);
+        }
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let mut next = false;
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("next shader")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.next, ui)
+    {
+        next = true;
+    }
+    if next {
+        step_shader(app, model, 1);
+    }
+}
+
+/// what the shader is given this frame
+fn uniforms(app: &App, model: &Model) -> Uniforms {
+    let (w, h) = app.main_window().inner_size_pixels();
+    Uniforms {
+        resolution: [w as f32, h as f32],
+        time: app.time,
+        rms: model.state.rms,
+        since_beat: model.beats.since().min(LONG_AGO),
+        beats: model.beats.count() as f32,
+        bands: model.levels,
+        ..Uniforms::default()
+    }
+}
+
+/// the shader's name and why it won't compile, over it in the window and on
+/// its own in screenshots, which are drawn without it
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let [r, g, b] = palette.line;
+
+    draw.text(&model.shaders.name())
+        .x_y(area.right() - 100.0, area.bottom() + 10.0)
+        .w_h(200.0, 20.0)
+        .right_justify()
+        .font_size(12)
+        .color(rgba(r, g, b, 0.8));
+
+    if let Some(error) = &model.shaders.error {
+        let [br, bg, bb] = palette.background;
+        draw.rect()
+            .xy(area.xy())
+            .wh(area.wh())
+            .color(rgba(br, bg, bb, 0.85));
+        draw.text(error)
+            .xy(area.xy())
+            .wh(area.wh())
+            .left_justify()
+            .align_text_top()
+            .font_size(13)
+            .color(theme::color(palette.line));
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if model.canvas.is_ready() {
+        model
+            .canvas
+            .draw(&app.main_window(), &frame, &uniforms(app, model));
+    } else {
+        draw.background()
+            .color(theme::color(model.shell.themes.current().background));
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
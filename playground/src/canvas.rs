@@ -0,0 +1,143 @@
+//! The user's fragment shader drawn over the whole window.
+//!
+//! Shaders are WGSL, translated to SPIR-V with naga as this wgpu only takes
+//! SPIR-V. The prelude declaring the uniforms is appended to each one and
+//! the vertex shader is built in, its GLSL beside its SPIR-V.
+
+use nannou::prelude::*;
+use nannou::wgpu::{self, BufferInitDescriptor, DeviceExt};
+use std::mem;
+
+/// bands the shaders get, as two `vec4`s
+pub const BANDS: usize = 8;
+
+/// declares `u` and `band` after every shader
+const PRELUDE: &str = include_str!("shaders/prelude.wgsl");
+
+/// What every shader is given, laid out as the prelude's `Uniforms`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Uniforms {
+    pub resolution: [f32; 2],
+    pub time: f32,
+    pub rms: f32,
+    pub since_beat: f32,
+    pub beats: f32,
+    /// the array of `vec4`s starts 16 bytes in
+    pub _padding: [f32; 2],
+    /// from `analysis::level`, lowest first
+    pub bands: [f32; BANDS],
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    // SAFETY: only called with `Uniforms`, plain f32s without padding
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) }
+}
+
+/// Translates `source` with the prelude to SPIR-V, or says why it can't,
+/// with the lines as in the file.
+pub fn compile(source: &str) -> Result<Vec<u8>, String> {
+    let full = format!("{}\n{}", source, PRELUDE);
+    let module = naga::front::wgsl::parse_str(&full).map_err(|e| e.emit_to_string(&full))?;
+    let main = module
+        .entry_points
+        .iter()
+        .any(|entry| entry.name == "main" && entry.stage == naga::ShaderStage::Fragment);
+    if !main {
+        return Err("no `@fragment fn main` to draw with".to_string());
+    }
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|e| e.emit_to_string(&full))?;
+    // the oldest SPIR-V, and no flipping, the vertex shader has it right
+    let options = naga::back::spv::Options {
+        lang_version: (1, 0),
+        flags: naga::back::spv::WriterFlags::empty(),
+        ..naga::back::spv::Options::default()
+    };
+    let words =
+        naga::back::spv::write_vec(&module, &info, &options, None).map_err(|e| e.to_string())?;
+    Ok(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+/// A shader drawn over the window it was made with.
+pub struct Canvas {
+    vertex: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    bind_group: wgpu::BindGroup,
+    uniforms: wgpu::Buffer,
+    /// `None` until a shader has compiled
+    pipeline: Option<wgpu::RenderPipeline>,
+    msaa_samples: u32,
+}
+
+impl Canvas {
+    pub fn new(window: &Window) -> Self {
+        let device = window.swap_chain_device();
+        let vertex =
+            wgpu::shader_from_spirv_bytes(device, include_bytes!("shaders/fullscreen.vert.spv"));
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("playground uniforms"),
+            contents: as_bytes(&[Uniforms::default()]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .uniform_buffer(wgpu::ShaderStage::FRAGMENT, false)
+            .build(device);
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .buffer::<Uniforms>(&uniforms, 0..1)
+            .build(device, &bind_group_layout);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("playground"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            vertex,
+            layout,
+            bind_group,
+            uniforms,
+            pipeline: None,
+            msaa_samples: window.msaa_samples(),
+        }
+    }
+
+    /// draws with `spirv` from `compile` from now on
+    pub fn set_shader(&mut self, window: &Window, spirv: &[u8]) {
+        let device = window.swap_chain_device();
+        let fragment = wgpu::shader_from_spirv_bytes(device, spirv);
+        let pipeline = wgpu::RenderPipelineBuilder::from_layout(&self.layout, &self.vertex)
+            .fragment_shader(&fragment)
+            .color_format(Frame::TEXTURE_FORMAT)
+            .sample_count(self.msaa_samples)
+            .build(device);
+        self.pipeline = Some(pipeline);
+    }
+
+    /// whether a shader has compiled yet
+    pub fn is_ready(&self) -> bool {
+        self.pipeline.is_some()
+    }
+
+    /// fills the frame with the shader, nothing before one has compiled
+    pub fn draw(&self, window: &Window, frame: &Frame, uniforms: &Uniforms) {
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        window
+            .swap_chain_queue()
+            .write_buffer(&self.uniforms, 0, as_bytes(&[*uniforms]));
+
+        let mut encoder = frame.command_encoder();
+        let mut pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(frame.texture_view(), |color| color)
+            .begin(&mut encoder);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(pipeline);
+        pass.draw(0..3, 0..1);
+    }
+}
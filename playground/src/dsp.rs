@@ -0,0 +1,152 @@
+use app_common::bus::AudioEnd;
+use app_common::input::InputReader;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use app_common::spectrum::AnalyzerInput;
+use dsp_common::limiter::Limiter;
+use dsp_common::meter::Meter;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at and expects the input at the same one
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// frames the input may run ahead before the oldest are dropped, as the
+/// input and output clocks drift apart
+const SLACK: usize = 2048;
+/// frames handled at a time
+const BLOCK: usize = 256;
+
+pub const GAIN: usize = 0;
+pub const MONITOR: usize = 1;
+
+/// how hard the shaders are driven, then how loud the input is heard
+pub static PARAMS: [ParamSpec; 2] = [
+    // on the signal analysed, quiet inputs can still fill the bands
+    ParamSpec::new("gain", 0.25, 16.0, 1.0).curve(Curve::Exponential),
+    // silent by default, a microphone next to the speakers would feed back
+    ParamSpec::new("monitor", 0.0, 1.0, 0.0),
+];
+
+/// How loud it is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// linear, after the gain
+    pub rms: f32,
+}
+
+/// What the shaders listen to.
+pub enum Source {
+    Input(InputReader),
+    /// round and round, for rendering without an input
+    Loop {
+        samples: Vec<f32>,
+        position: usize,
+    },
+    Silence,
+}
+
+impl Source {
+    /// the next `out.len()` mono frames, silent where there are none yet
+    fn fill(&mut self, out: &mut [f32]) {
+        let read = match self {
+            Source::Input(reader) if reader.available() > out.len() + SLACK => reader.latest(out),
+            Source::Input(reader) => reader.read(out),
+            Source::Loop { samples, position } if !samples.is_empty() => {
+                for sample in out.iter_mut() {
+                    *sample = samples[*position];
+                    *position = (*position + 1) % samples.len();
+                }
+                out.len()
+            }
+            Source::Loop { .. } | Source::Silence => 0,
+        };
+        for sample in out[read..].iter_mut() {
+            *sample = 0.0;
+        }
+    }
+}
+
+/// Passes the source on to be analysed, and to the output as loud as it's
+/// monitored.
+pub struct Engine {
+    bus: AudioEnd<(), State>,
+    params: Params,
+    source: Source,
+    /// `None` without a window to draw what it finds
+    analyzer: Option<AnalyzerInput>,
+    meter: Meter,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(
+        bus: AudioEnd<(), State>,
+        params: Params,
+        source: Source,
+        analyzer: Option<AnalyzerInput>,
+    ) -> Self {
+        Self {
+            bus,
+            params,
+            source,
+            analyzer,
+            meter: Meter::new(SAMPLE_RATE as f32),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// the caller keeps nothing of the old source, it's dropped here
+    pub fn set_source(&mut self, source: Source) {
+        self.source = source;
+    }
+
+    /// for an analyzer at a new rate, the old one is dropped here too
+    pub fn set_analyzer(&mut self, analyzer: Option<AnalyzerInput>) {
+        self.analyzer = analyzer;
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.meter.set_sample_rate(sample_rate as f32);
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+
+        let gain = self.params.get(GAIN);
+        // undoes the gain, it's only for the analysis
+        let monitor = self.params.get(MONITOR) / gain;
+        let mut input = [0.0; BLOCK];
+        for block in out.chunks_mut(BLOCK * channels) {
+            let frames = block.len() / channels;
+            let input = &mut input[..frames];
+            self.source.fill(input);
+            for sample in input.iter_mut() {
+                *sample *= gain;
+            }
+            self.meter.process_block(input);
+            if let Some(analyzer) = &mut self.analyzer {
+                analyzer.write(input);
+            }
+            for (frame, sample) in block.chunks_exact_mut(channels).zip(input.iter()) {
+                for out in frame.iter_mut() {
+                    *out = sample * monitor;
+                }
+            }
+        }
+
+        self.bus.publish(State {
+            rms: self.meter.reading().rms,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
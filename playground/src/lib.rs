@@ -0,0 +1,5 @@
+mod app;
+mod canvas;
+mod dsp;
+
+pub use app::{headless, render, run};
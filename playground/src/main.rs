@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("playground");
+    match &args.render {
+        Some(request) => playground::render(request),
+        None if args.headless => playground::headless(),
+        None => playground::run(),
+    }
+}
@@ -0,0 +1,11 @@
+#version 450
+
+// one triangle over the whole window, its corners picked by vertex index
+layout(location = 0) out vec2 uv;
+
+void main() {
+    vec2 corner = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    // 0 to 1 across the window, up from the bottom left
+    uv = corner;
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,25 @@
+// Appended to every shader, after it so its line numbers match the file.
+
+struct Uniforms {
+    // in pixels
+    resolution: vec2<f32>,
+    // seconds since the playground started
+    time: f32,
+    // of what's heard, linear, full scale is 1
+    rms: f32,
+    // seconds since the last beat, large before the first
+    since_beat: f32,
+    // beats so far
+    beats: f32,
+    // eight bands from 40Hz to 16kHz, each 0 at -60dB to 1 at full scale
+    bands: array<vec4<f32>, 2>,
+}
+
+@group(0) @binding(0)
+var<uniform> u: Uniforms;
+
+// band `i` from 0, the lowest, to 7
+fn band(i: i32) -> f32 {
+    let clamped = clamp(i, 0, 7);
+    return u.bands[clamped / 4][clamped % 4];
+}
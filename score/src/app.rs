@@ -0,0 +1,566 @@
+use crate::dsp::{self, Command, Engine, State};
+use crate::song::{Song, MAX_PARTS};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::tasks::{self, Pending, Tasks};
+use app_common::theme::{self, Themed};
+use dsp_common::random::Rng;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a few bars in three parts, unless the config names another file
+static SONG: Asset = Asset::new("demo.mid", include_bytes!("../res/demo.mid"));
+
+/// back to the start of the song
+const RESTART: Key = Key::Space;
+
+/// seconds of the song across the score, as written, so it scrolls faster
+/// as the tempo goes up
+const SHOWN: f64 = 8.0;
+/// how far across the score the playhead sits
+const PLAYHEAD: f64 = 0.25;
+/// thrown off each note as it starts
+const PARTICLES: usize = 8;
+/// particles alive at once, the oldest go first
+const MAX_PARTICLES: usize = 2000;
+/// seconds a particle lasts
+const LIFE: f32 = 1.2;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        restart,
+    }
+}
+
+/// `path`, or the built in song
+fn load_song(path: Option<&Path>) -> Result<Song, startup::Error> {
+    let (path, bytes) = match path {
+        Some(path) => (path.to_path_buf(), fs::read(path).map(Cow::Owned)),
+        None => (SONG.source(), SONG.bytes()),
+    };
+    let failed = |reason: String| startup::Error::Sample {
+        path: path.clone(),
+        reason,
+    };
+    Song::decode(&bytes.map_err(|e| failed(e.to_string()))?).map_err(failed)
+}
+
+fn engine(config: &Config, params: &Params, song: Arc<Song>) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), song);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("score", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the headless engine on the configured song, silent if it can't be read
+fn headless_engine(config: &Config) -> Engine {
+    let song = load_song(config.sample_path.as_deref()).unwrap_or_else(|e| {
+        eprintln!("score: {}", e);
+        Song::default()
+    });
+    let (engine, _bus) = engine(config, &load_params(config), Arc::new(song));
+    engine
+}
+
+/// the song without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("score", &config::path("score"));
+    let mut engine = headless_engine(&config);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the song on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("score", &config::path("score"));
+    render::headless("score", headless_engine(&config), stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+/// A spark thrown off a note as it starts.
+struct Particle {
+    position: Point2,
+    velocity: Vec2,
+    part: usize,
+    /// seconds left
+    life: f32,
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    mute_ids: widget::id::List,
+    solo_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<Command, State>,
+    /// the song as of the last buffer played
+    state: State,
+    /// the song the engine plays
+    song: Arc<Song>,
+    /// the song before the last one dropped in, kept so the audio thread
+    /// never frees it
+    _previous: Option<Arc<Song>>,
+    /// a bit for each part, as sent to the engine
+    muted: u32,
+    soloed: u32,
+    particles: Vec<Particle>,
+    rng: Rng,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// songs being read, off the window's thread so it keeps drawing
+    tasks: Tasks,
+    /// the last song asked for, until it's swapped in
+    loading: Option<Pending<Result<Loaded, startup::Error>>>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("score");
+    let (config, _) = shell::load_config("score", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("score", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("score", &config, &params);
+    let serial = serial::Sensors::from_config("score", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("score", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    let song = Arc::new(
+        load_song(config.sample_path.as_deref()).unwrap_or_else(|e| {
+            errors.push(e);
+            Song::default()
+        }),
+    );
+    let (engine, bus) = engine(&config, &params, song.clone());
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "score", &config, stream.stats());
+    let shell =
+        Shell::new("score", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        mute_ids: widget::id::List::new(),
+        solo_ids: widget::id::List::new(),
+        params,
+        bus,
+        state: State::default(),
+        muted: dsp::initial_mutes(&song),
+        soloed: 0,
+        song,
+        _previous: None,
+        particles: Vec::new(),
+        rng: Rng::from_entropy(),
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        tasks: Tasks::new("score", tasks::THREADS),
+        loading: None,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// A song read as a task.
+struct Loaded {
+    song: Song,
+    path: Option<PathBuf>,
+}
+
+/// reads the song at `path`, or the built in one, as a task, dropping any
+/// asked for before that hasn't come in yet
+fn load(model: &mut Model, path: Option<&Path>) {
+    let path = path.map(Path::to_path_buf);
+    let pending = model.tasks.spawn("reading song", move |_| {
+        let song = load_song(path.as_deref())?;
+        Ok(Loaded { song, path })
+    });
+    model.loading = Some(pending);
+}
+
+/// swaps in the song once it's read and remembers it in the config
+fn swap_song(model: &mut Model) {
+    let loaded = match model.loading.as_ref().and_then(Pending::take) {
+        Some(Ok(loaded)) => loaded,
+        Some(Err(e)) => {
+            eprintln!("score: {}", e);
+            model.loading = None;
+            return;
+        }
+        None => return,
+    };
+    model.loading = None;
+    let song = Arc::new(loaded.song);
+    // the engine resets its parts the same way
+    model.muted = dsp::initial_mutes(&song);
+    model.soloed = 0;
+    model.particles.clear();
+    model._previous = Some(std::mem::replace(&mut model.song, song.clone()));
+    model.stream.send(move |engine| engine.set_song(song));
+    model.shell.config.sample_path = loaded.path;
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(DroppedFile(path)),
+            ..
+        } => {
+            load(model, Some(&path));
+            return;
+        }
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == RESTART {
+        let _ = model.bus.send(Command::Restart);
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// where the score is drawn
+fn score_rect(app: &App) -> Rect {
+    app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN)
+}
+
+/// `key` up the score, the song's range filling it with a key spare at
+/// either end
+fn key_y(song: &Song, area: Rect, key: f32) -> f32 {
+    let (low, high) = song.range;
+    let keys = f32::from(high - low) + 2.0;
+    area.bottom() + area.h() * (key - f32::from(low) + 1.0) / keys
+}
+
+/// `time` in the song across the score, with the playhead at `position`
+fn time_x(area: Rect, position: f64, time: f64) -> f32 {
+    let shown = (time - position) / SHOWN + PLAYHEAD;
+    area.left() + area.w() * shown as f32
+}
+
+/// sparks off every note heard starting since the last frame, and the old
+/// ones moved on
+fn throw_particles(app: &App, model: &mut Model, previous: f64, dt: f32) {
+    for particle in model.particles.iter_mut() {
+        particle.position += particle.velocity * dt;
+        particle.velocity *= 1.0 - dt * 1.5;
+        particle.life -= dt;
+    }
+    model.particles.retain(|particle| particle.life > 0.0);
+
+    let position = model.state.position;
+    // back at the start, nothing to throw for the jump
+    if position < previous {
+        return;
+    }
+    let area = score_rect(app);
+    let x = time_x(area, position, position);
+    let first = model
+        .song
+        .notes
+        .partition_point(|note| note.start <= previous);
+    for note in model.song.notes[first..]
+        .iter()
+        .take_while(|note| note.start <= position)
+    {
+        if !dsp::audible(note.part, model.muted, model.soloed) {
+            continue;
+        }
+        let y = key_y(&model.song, area, f32::from(note.key));
+        for _ in 0..PARTICLES {
+            let angle = model.rng.range(0.0, TAU);
+            let speed = model.rng.range(20.0, 120.0) * (0.5 + note.velocity);
+            model.particles.push(Particle {
+                position: pt2(x, y),
+                velocity: vec2(angle.cos(), angle.sin()) * speed,
+                part: note.part,
+                life: LIFE * model.rng.range(0.5, 1.0),
+            });
+        }
+    }
+    let excess = model.particles.len().saturating_sub(MAX_PARTICLES);
+    model.particles.drain(..excess);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.tasks.poll();
+    swap_song(model);
+    let previous = model.state.position;
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    throw_particles(app, model, previous, update.since_last.as_secs_f32());
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        let sample_path = config.sample_path.clone();
+        let reload = sample_path != model.shell.config.sample_path;
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+        if reload {
+            load(model, sample_path.as_deref());
+        }
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("restart")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.restart, ui)
+    {
+        let _ = model.bus.send(Command::Restart);
+    }
+
+    // a mute and a solo for each part, side by side
+    let parts = model.song.parts.len().min(MAX_PARTS);
+    if model.mute_ids.len() < parts {
+        model.mute_ids.resize(parts, &mut ui.widget_id_generator());
+        model.solo_ids.resize(parts, &mut ui.widget_id_generator());
+    }
+    let (mut muted, mut soloed) = (model.muted, model.soloed);
+    for (i, part) in model.song.parts.iter().take(parts).enumerate() {
+        let bit = 1 << i;
+        let mute = widget::Toggle::new(muted & bit != 0)
+            .w_h(145.0, 24.0)
+            .label(&part.name)
+            .label_font_size(12)
+            .themed(palette)
+            .border(0.0);
+        let mute = if i == 0 {
+            mute.down(20.0)
+        } else {
+            mute.down_from(model.mute_ids[i - 1], 6.0)
+        };
+        for _value in mute.set(model.mute_ids[i], ui) {
+            muted ^= bit;
+        }
+        for _value in widget::Toggle::new(soloed & bit != 0)
+            .w_h(50.0, 24.0)
+            .right_from(model.mute_ids[i], 5.0)
+            .label("solo")
+            .label_font_size(12)
+            .themed(palette)
+            .border(0.0)
+            .set(model.solo_ids[i], ui)
+        {
+            soloed ^= bit;
+        }
+    }
+    if (muted, soloed) != (model.muted, model.soloed) {
+        model.muted = muted;
+        model.soloed = soloed;
+        let _ = model.bus.send(Command::Parts { muted, soloed });
+    }
+    model.tasks.panel(palette, ui);
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = score_rect(app);
+    let song = &model.song;
+    let position = model.state.position;
+    let [r, g, b] = palette.line;
+
+    // a line at every C
+    let (low, high) = song.range;
+    for key in (low..=high).filter(|key| key % 12 == 0) {
+        let y = key_y(song, area, f32::from(key));
+        draw.line()
+            .start(pt2(area.left(), y))
+            .end(pt2(area.right(), y))
+            .weight(1.0)
+            .color(rgba(r, g, b, 0.1));
+    }
+
+    // every note in view, lit while it sounds, faint if its part is out
+    let height = (area.h() / (f32::from(high - low) + 2.0)).clamp(2.0, 16.0);
+    let from = position - SHOWN * PLAYHEAD;
+    let until = position + SHOWN * (1.0 - PLAYHEAD);
+    for note in song.notes.iter().take_while(|note| note.start < until) {
+        if note.end < from {
+            continue;
+        }
+        let left = time_x(area, position, note.start).max(area.left());
+        let right = time_x(area, position, note.end).min(area.right());
+        let y = key_y(song, area, f32::from(note.key));
+        let [nr, ng, nb] = palette.accent(note.part);
+        let sounding = note.start <= position && position < note.end;
+        let alpha = if !dsp::audible(note.part, model.muted, model.soloed) {
+            0.1
+        } else if sounding {
+            1.0
+        } else {
+            0.3 + 0.4 * note.velocity
+        };
+        let grow = if sounding { 1.5 } else { 1.0 };
+        draw.rect()
+            .x_y((left + right) * 0.5, y)
+            .w_h((right - left).max(1.0), height * grow)
+            .color(rgba(nr, ng, nb, alpha));
+    }
+
+    for particle in &model.particles {
+        let [pr, pg, pb] = palette.accent(particle.part);
+        let fade = (particle.life / LIFE).clamp(0.0, 1.0);
+        draw.ellipse()
+            .xy(particle.position)
+            .radius(1.0 + 2.0 * fade)
+            .color(rgba(pr, pg, pb, fade));
+    }
+
+    let x = time_x(area, position, position);
+    draw.line()
+        .start(pt2(x, area.bottom()))
+        .end(pt2(x, area.top()))
+        .weight(2.0)
+        .color(rgba(r, g, b, 0.8));
+
+    let time = |seconds: f64| format!("{}:{:02}", seconds as u64 / 60, seconds as u64 % 60);
+    draw.text(&format!(
+        "{} / {}",
+        time(position.min(song.length)),
+        time(song.length)
+    ))
+    .x_y(area.right() - 60.0, area.top() - 10.0)
+    .font_size(12)
+    .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
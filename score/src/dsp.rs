@@ -0,0 +1,298 @@
+use crate::oscillators::{self, Oscillators};
+use crate::song::Song;
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::env::{Envelope, Retrigger, Shape};
+use dsp_common::limiter::Limiter;
+use dsp_common::pan;
+use dsp_common::tuning::midi_to_freq;
+use std::sync::Arc;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// notes sounding at once, the oldest is taken over past that
+pub const VOICES: usize = 8;
+/// frames rendered between looking for notes to start and stop, notes land
+/// within about a millisecond of where they're written
+pub const BLOCK: usize = 64;
+/// per voice at full volume and velocity, chords of four or so stay clear
+/// of the limiter
+const GAIN: f32 = 0.2;
+/// seconds of silence after the last note before the song starts again
+pub const GAP: f64 = 1.0;
+/// how far the parts are spread from the centre, 1 is hard left and right
+const SPREAD: f32 = 0.6;
+
+pub const TEMPO: usize = 0;
+pub const ATTACK: usize = 1;
+pub const RELEASE: usize = 2;
+pub const VOLUME: usize = 3;
+
+/// how fast the song plays, the voices' envelope, then how loud it plays
+pub static PARAMS: [ParamSpec; 4] = [
+    // times the tempo written in the file
+    ParamSpec::new("tempo", 0.25, 4.0, 1.0).curve(Curve::Exponential),
+    // in seconds
+    ParamSpec::new("attack", 0.001, 0.5, 0.01).curve(Curve::Exponential),
+    ParamSpec::new("release", 0.01, 2.0, 0.3).curve(Curve::Exponential),
+    ParamSpec::new("volume", 0.0, 1.0, 0.7),
+];
+
+pub enum Command {
+    /// back to the start of the song
+    Restart,
+    /// which parts are heard, a bit for each: any soloed parts, else those
+    /// not muted
+    Parts { muted: u32, soloed: u32 },
+}
+
+/// whether `part` is heard with `muted` and `soloed` parts
+pub fn audible(part: usize, muted: u32, soloed: u32) -> bool {
+    let bit = 1 << part;
+    if soloed != 0 {
+        soloed & bit != 0
+    } else {
+        muted & bit == 0
+    }
+}
+
+/// Where the song is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// in seconds of the song as written, whatever the tempo
+    pub position: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Voice {
+    /// the note held, until it ends and the voice releases
+    note: Option<usize>,
+    end: f64,
+    part: usize,
+    velocity: f32,
+    /// from 0 to 1, left to right
+    pan: f32,
+    /// when it started, in notes started before it
+    age: u64,
+    env: Envelope,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        let mut env = Envelope::new(Shape::adsr(0.01, 0.3, 0.6, 0.3));
+        // a voice taken over rises from where it is rather than clicking
+        env.set_retrigger(Retrigger::Legato);
+        Self {
+            note: None,
+            end: 0.0,
+            part: 0,
+            velocity: 0.0,
+            pan: 0.5,
+            age: 0,
+            env,
+        }
+    }
+}
+
+/// Plays a song's notes on a handful of enveloped sines.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    song: Arc<Song>,
+    /// in seconds of the song as written
+    position: f64,
+    /// the next note to start
+    cursor: usize,
+    voices: [Voice; VOICES],
+    started: u64,
+    oscillators: Box<dyn Oscillators>,
+    blocks: [[f32; BLOCK]; VOICES],
+    muted: u32,
+    soloed: u32,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, State>, params: Params, song: Arc<Song>) -> Self {
+        let muted = initial_mutes(&song);
+        Self {
+            bus,
+            params,
+            song,
+            position: 0.0,
+            cursor: 0,
+            voices: [Voice::default(); VOICES],
+            started: 0,
+            oscillators: oscillators::new(),
+            blocks: [[0.0; BLOCK]; VOICES],
+            muted,
+            soloed: 0,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// starts over on another song, the caller keeps a reference to the old
+    /// one so it isn't freed on the audio thread
+    pub fn set_song(&mut self, song: Arc<Song>) {
+        self.muted = initial_mutes(&song);
+        self.soloed = 0;
+        self.song = song;
+        self.restart();
+    }
+
+    fn restart(&mut self) {
+        self.position = 0.0;
+        self.cursor = 0;
+        self.release(|_| true);
+    }
+
+    /// lets go of the notes `which` picks
+    fn release(&mut self, which: impl Fn(&Voice) -> bool) {
+        for voice in self.voices.iter_mut() {
+            if voice.note.is_some() && which(voice) {
+                voice.note = None;
+                voice.env.gate_off();
+            }
+        }
+    }
+
+    /// a silent voice, else the one that started longest ago
+    fn free_voice(&self) -> usize {
+        let mut oldest = 0;
+        for (i, voice) in self.voices.iter().enumerate() {
+            if !voice.env.is_active() {
+                return i;
+            }
+            if voice.age < self.voices[oldest].age {
+                oldest = i;
+            }
+        }
+        oldest
+    }
+
+    fn start(&mut self, index: usize) {
+        let note = self.song.notes[index];
+        let parts = self.song.parts.len();
+        // first part on the left, last on the right
+        let across = if parts > 1 {
+            note.part as f32 / (parts - 1) as f32 - 0.5
+        } else {
+            0.0
+        };
+        let shape = Shape::adsr(self.params.get(ATTACK), 0.3, 0.6, self.params.get(RELEASE));
+        let v = self.free_voice();
+        self.oscillators
+            .set_freq(v, midi_to_freq(f32::from(note.key)));
+        let voice = &mut self.voices[v];
+        voice.note = Some(index);
+        voice.end = note.end;
+        voice.part = note.part;
+        voice.velocity = note.velocity;
+        voice.pan = 0.5 + SPREAD * across;
+        voice.age = self.started;
+        voice.env.set_shape(shape);
+        voice.env.gate_on(1.0 / self.sample_rate as f32);
+        self.started += 1;
+    }
+
+    /// moves the song on by `frames`, starting and stopping notes on the way
+    fn advance(&mut self, frames: usize) {
+        let seconds = frames as f64 / self.sample_rate as f64;
+        let end = self.position + seconds * self.params.get(TEMPO) as f64;
+        self.release(|voice| voice.end <= end);
+        while let Some(note) = self.song.notes.get(self.cursor) {
+            if note.start >= end {
+                break;
+            }
+            if audible(note.part, self.muted, self.soloed) && note.end > self.position {
+                self.start(self.cursor);
+            }
+            self.cursor += 1;
+        }
+        self.position = end;
+        if self.position >= self.song.length + GAP {
+            self.position -= self.song.length + GAP;
+            self.cursor = 0;
+        }
+    }
+}
+
+/// drums are left out until they're unmuted
+pub fn initial_mutes(song: &Song) -> u32 {
+    song.parts
+        .iter()
+        .enumerate()
+        .filter(|(_, part)| part.drums)
+        .fold(0, |muted, (i, _)| muted | 1 << i)
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        let (mut restart, mut parts) = (false, None);
+        for command in self.bus.commands() {
+            match command {
+                Command::Restart => restart = true,
+                Command::Parts { muted, soloed } => parts = Some((muted, soloed)),
+            }
+        }
+        if let Some((muted, soloed)) = parts {
+            self.muted = muted;
+            self.soloed = soloed;
+            self.release(|voice| !audible(voice.part, muted, soloed));
+        }
+        if restart {
+            self.restart();
+        }
+
+        let gain = self.params.get(VOLUME) * GAIN;
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let frames = chunk.len() / channels;
+            self.advance(frames);
+            self.oscillators
+                .render(&mut self.blocks, frames, self.sample_rate);
+            for (i, frame) in chunk.chunks_exact_mut(channels).enumerate() {
+                let mut pair = (0.0, 0.0);
+                for (voice, block) in self.voices.iter_mut().zip(self.blocks.iter()) {
+                    if !voice.env.is_active() {
+                        continue;
+                    }
+                    let sample = block[i] * voice.env.step() * voice.velocity * gain;
+                    let (left, right) = pan::equal_power(sample, voice.pan);
+                    pair.0 += left;
+                    pair.1 += right;
+                }
+                match frame {
+                    [mono] => *mono = pair.0 + pair.1,
+                    [l, r, rest @ ..] => {
+                        *l = pair.0;
+                        *r = pair.1;
+                        for out in rest.iter_mut() {
+                            *out = 0.0;
+                        }
+                    }
+                    [] => {}
+                }
+            }
+        }
+
+        self.bus.publish(State {
+            position: self.position,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
@@ -0,0 +1,6 @@
+mod app;
+mod dsp;
+mod oscillators;
+mod song;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("score");
+    match &args.render {
+        Some(request) => score::render(request),
+        None if args.headless => score::headless(),
+        None => score::run(),
+    }
+}
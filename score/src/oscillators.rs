@@ -0,0 +1,198 @@
+//! The sines voicing the song, one per voice, as a rume graph or, without
+//! the `audio` feature, wavetable sines.
+
+use crate::dsp::{BLOCK, VOICES};
+#[cfg(not(feature = "audio"))]
+use dsp_common::Wavetable;
+#[cfg(feature = "audio")]
+use rume::{Processor, Renderable};
+
+/// Each voice's raw sine, the envelopes and mix are applied on top.
+pub trait Oscillators: Send {
+    /// from the next `render` on
+    fn set_freq(&mut self, voice: usize, freq: f32);
+
+    /// the first `frames` samples of each voice's block, `frames` at most
+    /// `BLOCK`
+    fn render(&mut self, out: &mut [[f32; BLOCK]; VOICES], frames: usize, sample_rate: u32);
+}
+
+/// the oscillators this build has
+pub fn new() -> Box<dyn Oscillators> {
+    #[cfg(feature = "audio")]
+    {
+        Box::new(Graph::new())
+    }
+    #[cfg(not(feature = "audio"))]
+    {
+        Box::new(Sines::new())
+    }
+}
+
+#[cfg(feature = "audio")]
+struct Graph {
+    graph: rume::SignalChain,
+    freqs: Vec<rume::InputStreamProducer>,
+    /// the last frequency sent to each voice, only changes are queued
+    sent: [f32; VOICES],
+    outputs: Vec<rume::OutputStreamConsumer>,
+}
+
+#[cfg(feature = "audio")]
+impl Graph {
+    fn new() -> Self {
+        let (freq_0_prod, freq_0_con) = rume::input!(FREQ_0_ENDPOINT);
+        let (freq_1_prod, freq_1_con) = rume::input!(FREQ_1_ENDPOINT);
+        let (freq_2_prod, freq_2_con) = rume::input!(FREQ_2_ENDPOINT);
+        let (freq_3_prod, freq_3_con) = rume::input!(FREQ_3_ENDPOINT);
+        let (freq_4_prod, freq_4_con) = rume::input!(FREQ_4_ENDPOINT);
+        let (freq_5_prod, freq_5_con) = rume::input!(FREQ_5_ENDPOINT);
+        let (freq_6_prod, freq_6_con) = rume::input!(FREQ_6_ENDPOINT);
+        let (freq_7_prod, freq_7_con) = rume::input!(FREQ_7_ENDPOINT);
+        let (out_0_prod, out_0_con) = rume::output!(OUT_0_ENDPOINT);
+        let (out_1_prod, out_1_con) = rume::output!(OUT_1_ENDPOINT);
+        let (out_2_prod, out_2_con) = rume::output!(OUT_2_ENDPOINT);
+        let (out_3_prod, out_3_con) = rume::output!(OUT_3_ENDPOINT);
+        let (out_4_prod, out_4_con) = rume::output!(OUT_4_ENDPOINT);
+        let (out_5_prod, out_5_con) = rume::output!(OUT_5_ENDPOINT);
+        let (out_6_prod, out_6_con) = rume::output!(OUT_6_ENDPOINT);
+        let (out_7_prod, out_7_con) = rume::output!(OUT_7_ENDPOINT);
+
+        let graph = rume::graph! {
+            endpoints: {
+                freq_0: rume::InputEndpoint::new(freq_0_con),
+                freq_1: rume::InputEndpoint::new(freq_1_con),
+                freq_2: rume::InputEndpoint::new(freq_2_con),
+                freq_3: rume::InputEndpoint::new(freq_3_con),
+                freq_4: rume::InputEndpoint::new(freq_4_con),
+                freq_5: rume::InputEndpoint::new(freq_5_con),
+                freq_6: rume::InputEndpoint::new(freq_6_con),
+                freq_7: rume::InputEndpoint::new(freq_7_con),
+                out_0: rume::OutputEndpoint::new(out_0_prod),
+                out_1: rume::OutputEndpoint::new(out_1_prod),
+                out_2: rume::OutputEndpoint::new(out_2_prod),
+                out_3: rume::OutputEndpoint::new(out_3_prod),
+                out_4: rume::OutputEndpoint::new(out_4_prod),
+                out_5: rume::OutputEndpoint::new(out_5_prod),
+                out_6: rume::OutputEndpoint::new(out_6_prod),
+                out_7: rume::OutputEndpoint::new(out_7_prod),
+            },
+            processors: {
+                sine_0: rume::Sine::default(),
+                sine_1: rume::Sine::default(),
+                sine_2: rume::Sine::default(),
+                sine_3: rume::Sine::default(),
+                sine_4: rume::Sine::default(),
+                sine_5: rume::Sine::default(),
+                sine_6: rume::Sine::default(),
+                sine_7: rume::Sine::default(),
+                // full scale, the envelopes set each voice's level
+                amp: rume::Value::new(1.0),
+            },
+            connections: {
+                freq_0.output   -> sine_0.input.0,
+                freq_1.output   -> sine_1.input.0,
+                freq_2.output   -> sine_2.input.0,
+                freq_3.output   -> sine_3.input.0,
+                freq_4.output   -> sine_4.input.0,
+                freq_5.output   -> sine_5.input.0,
+                freq_6.output   -> sine_6.input.0,
+                freq_7.output   -> sine_7.input.0,
+                amp.output      -> sine_0.input.1,
+                amp.output      -> sine_1.input.1,
+                amp.output      -> sine_2.input.1,
+                amp.output      -> sine_3.input.1,
+                amp.output      -> sine_4.input.1,
+                amp.output      -> sine_5.input.1,
+                amp.output      -> sine_6.input.1,
+                amp.output      -> sine_7.input.1,
+                sine_0.output   -> out_0.input,
+                sine_1.output   -> out_1.input,
+                sine_2.output   -> out_2.input,
+                sine_3.output   -> out_3.input,
+                sine_4.output   -> out_4.input,
+                sine_5.output   -> out_5.input,
+                sine_6.output   -> out_6.input,
+                sine_7.output   -> out_7.input,
+            }
+        };
+
+        Self {
+            graph,
+            freqs: vec![
+                freq_0_prod,
+                freq_1_prod,
+                freq_2_prod,
+                freq_3_prod,
+                freq_4_prod,
+                freq_5_prod,
+                freq_6_prod,
+                freq_7_prod,
+            ],
+            sent: [0.0; VOICES],
+            outputs: vec![
+                out_0_con, out_1_con, out_2_con, out_3_con, out_4_con, out_5_con, out_6_con,
+                out_7_con,
+            ],
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Oscillators for Graph {
+    fn set_freq(&mut self, voice: usize, freq: f32) {
+        if self.sent[voice] != freq {
+            self.sent[voice] = freq;
+            self.freqs[voice].enqueue(freq).unwrap();
+        }
+    }
+
+    fn render(&mut self, out: &mut [[f32; BLOCK]; VOICES], frames: usize, sample_rate: u32) {
+        self.graph.prepare(sample_rate.into());
+        self.graph.render(frames);
+        for (block, output) in out.iter_mut().zip(self.outputs.iter_mut()) {
+            for sample in block[..frames].iter_mut() {
+                *sample = output.dequeue().unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+const TABLE_SIZE: usize = 4096;
+
+#[cfg(not(feature = "audio"))]
+struct Sines {
+    sine: Wavetable,
+    freqs: [f32; VOICES],
+    phases: [f32; VOICES],
+}
+
+#[cfg(not(feature = "audio"))]
+impl Sines {
+    fn new() -> Self {
+        Self {
+            sine: Wavetable::sine(TABLE_SIZE),
+            freqs: [0.0; VOICES],
+            phases: [0.0; VOICES],
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl Oscillators for Sines {
+    fn set_freq(&mut self, voice: usize, freq: f32) {
+        self.freqs[voice] = freq;
+    }
+
+    fn render(&mut self, out: &mut [[f32; BLOCK]; VOICES], frames: usize, sample_rate: u32) {
+        for (voice, block) in out.iter_mut().enumerate() {
+            let increment = self.freqs[voice] / sample_rate as f32;
+            let phase = &mut self.phases[voice];
+            for sample in block[..frames].iter_mut() {
+                *sample = self.sine.at(*phase);
+                *phase = (*phase + increment).fract();
+            }
+        }
+    }
+}
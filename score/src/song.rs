@@ -0,0 +1,217 @@
+//! Standard MIDI files read into notes timed in seconds.
+//!
+//! Every track is laid out on one timeline through the tempo changes, then
+//! split into parts, one for each track and channel that plays notes, so a
+//! single track file still gets a part per instrument.
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use std::collections::HashMap;
+
+/// parts beyond this are played by the last one
+pub const MAX_PARTS: usize = 32;
+/// the General MIDI drum channel, its parts start muted as sines make poor
+/// drums
+const DRUMS: u8 = 9;
+/// until the first tempo change, 120bpm
+const DEFAULT_TEMPO: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    /// in seconds from the start of the song
+    pub start: f64,
+    pub end: f64,
+    pub key: u8,
+    /// in [0, 1]
+    pub velocity: f32,
+    pub part: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    pub name: String,
+    pub drums: bool,
+    pub notes: usize,
+}
+
+/// A song's notes in the order they start.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Song {
+    pub notes: Vec<Note>,
+    pub parts: Vec<Part>,
+    /// in seconds, until the last note ends
+    pub length: f64,
+    /// the lowest and highest keys played
+    pub range: (u8, u8),
+}
+
+impl Song {
+    /// reads a standard MIDI file, a song without notes is an error
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let smf = Smf::parse(bytes).map_err(|e| e.to_string())?;
+        let mut events = Vec::new();
+        for (track, events_in) in smf.tracks.iter().enumerate() {
+            let mut tick = 0u64;
+            for event in events_in {
+                tick += u64::from(u32::from(event.delta));
+                events.push((tick, track, event.kind));
+            }
+        }
+        // stable, so events at the same tick keep the order they're written
+        events.sort_by_key(|(tick, _, _)| *tick);
+
+        let mut clock = Clock::new(smf.header.timing);
+        let mut names = HashMap::new();
+        let mut parts: Vec<(usize, u8)> = Vec::new();
+        let mut held: HashMap<(usize, u8, u8), (f64, f32)> = HashMap::new();
+        let mut notes = Vec::new();
+        for (tick, track, kind) in events {
+            let time = clock.seconds(tick);
+            let (channel, message) = match kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    clock.set_tempo(tick, u32::from(tempo));
+                    continue;
+                }
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                    names.insert(track, String::from_utf8_lossy(name).trim().to_string());
+                    continue;
+                }
+                TrackEventKind::Midi { channel, message } => (u8::from(channel), message),
+                _ => continue,
+            };
+            let (key, velocity) = match message {
+                MidiMessage::NoteOn { key, vel } => (u8::from(key), u8::from(vel)),
+                MidiMessage::NoteOff { key, .. } => (u8::from(key), 0),
+                _ => continue,
+            };
+            // a note on at velocity 0 is a note off
+            let ended = if velocity == 0 {
+                held.remove(&(track, channel, key))
+            } else {
+                held.insert((track, channel, key), (time, f32::from(velocity) / 127.0))
+            };
+            if let Some((start, velocity)) = ended {
+                let part = part_index(&mut parts, track, channel);
+                notes.push(Note {
+                    start,
+                    end: time,
+                    key,
+                    velocity,
+                    part,
+                });
+            }
+        }
+        // notes never let go of end with the last event
+        let last = clock.seconds(clock.last_tick);
+        for ((track, channel, key), (start, velocity)) in held {
+            let part = part_index(&mut parts, track, channel);
+            notes.push(Note {
+                start,
+                end: last.max(start),
+                key,
+                velocity,
+                part,
+            });
+        }
+        if notes.is_empty() {
+            return Err("no notes".to_string());
+        }
+
+        // parts in the order of their tracks, not of their first notes
+        let mut order: Vec<usize> = (0..parts.len()).collect();
+        order.sort_by_key(|&i| parts[i]);
+        let mut moved = vec![0; parts.len()];
+        for (new, &old) in order.iter().enumerate() {
+            moved[old] = new;
+        }
+        let parts: Vec<(usize, u8)> = order.iter().map(|&i| parts[i]).collect();
+        for note in &mut notes {
+            note.part = moved[note.part];
+        }
+        notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.key.cmp(&b.key)));
+        let name = |track: &usize| {
+            names
+                .get(track)
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .unwrap_or_else(|| format!("track {}", track + 1))
+        };
+        let mut parts: Vec<Part> = parts
+            .iter()
+            .map(|(track, channel)| Part {
+                // a track split over channels tells its parts apart by them
+                name: if parts.iter().filter(|(t, _)| t == track).count() > 1 {
+                    format!("{} ch {}", name(track), channel + 1)
+                } else {
+                    name(track)
+                },
+                drums: *channel == DRUMS,
+                notes: 0,
+            })
+            .collect();
+        for note in &notes {
+            parts[note.part].notes += 1;
+        }
+        Ok(Self {
+            length: notes.iter().map(|note| note.end).fold(0.0, f64::max),
+            range: (
+                notes.iter().map(|note| note.key).min().unwrap_or(0),
+                notes.iter().map(|note| note.key).max().unwrap_or(127),
+            ),
+            notes,
+            parts,
+        })
+    }
+}
+
+/// the part playing `channel` of `track`, a new one the first time
+fn part_index(parts: &mut Vec<(usize, u8)>, track: usize, channel: u8) -> usize {
+    match parts.iter().position(|&part| part == (track, channel)) {
+        Some(index) => index,
+        None if parts.len() < MAX_PARTS => {
+            parts.push((track, channel));
+            parts.len() - 1
+        }
+        None => MAX_PARTS - 1,
+    }
+}
+
+/// Ticks to seconds through the tempo changes so far.
+struct Clock {
+    timing: Timing,
+    /// where the current tempo took over, in ticks and seconds
+    since: (u64, f64),
+    /// seconds a beat
+    tempo: f64,
+    last_tick: u64,
+}
+
+impl Clock {
+    fn new(timing: Timing) -> Self {
+        Self {
+            timing,
+            since: (0, 0.0),
+            tempo: DEFAULT_TEMPO,
+            last_tick: 0,
+        }
+    }
+
+    /// `tick` in seconds, from the tempo changes before it
+    fn seconds(&mut self, tick: u64) -> f64 {
+        self.last_tick = self.last_tick.max(tick);
+        let ticks = tick.saturating_sub(self.since.0) as f64;
+        let per_tick = match self.timing {
+            Timing::Metrical(ppq) => self.tempo / f64::from(u16::from(ppq).max(1)),
+            // timecode ignores tempo, a tick is a fraction of a frame
+            Timing::Timecode(fps, subframes) => {
+                1.0 / (f64::from(fps.as_f32()) * f64::from(subframes.max(1)))
+            }
+        };
+        self.since.1 + ticks * per_tick
+    }
+
+    /// `microseconds` a beat from `tick` on
+    fn set_tempo(&mut self, tick: u64, microseconds: u32) {
+        self.since = (tick, self.seconds(tick));
+        self.tempo = f64::from(microseconds.max(1)) * 1e-6;
+    }
+}
@@ -0,0 +1,349 @@
+use crate::dsp::{self, Engine, State, PARTIALS};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use dsp_common::tuning::{A4_MIDI, NOTE_NAMES};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// turns the glide the other way
+const REVERSE: Key = Key::Space;
+
+/// points drawn per turn of the spiral
+const SPIRAL_POINTS: usize = 96;
+/// of an octave, the bright stretch of spiral behind each partial
+const TRAIL: f32 = 0.15;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        reverse,
+    }
+}
+
+fn engine(config: &Config, params: &Params) -> (Engine, UiEnd<(), State>) {
+    let (ui_bus, audio_bus) = bus::bus(1, 4);
+    let mut engine = Engine::new(audio_bus, params.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("shepard", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the glide without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("shepard", &config::path("shepard"));
+    let params = shell::load_params("shepard", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the glide on the audio device without a window
+pub fn headless() {
+    let (config, _) = shell::load_config("shepard", &config::path("shepard"));
+    let params = shell::load_params("shepard", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params);
+    render::headless("shepard", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<(), State>,
+    /// the glide as of the last buffer played
+    state: State,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("shepard");
+    let (config, _) = shell::load_config("shepard", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("shepard", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("shepard", &config, &params);
+    let serial = serial::Sensors::from_config("shepard", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("shepard", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "shepard", &config, stream.stats());
+    let shell =
+        Shell::new("shepard", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        state: State::default(),
+        stream,
+        errors,
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == REVERSE {
+        reverse(model);
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn reverse(model: &mut Model) {
+    let direction = model.params.get(dsp::DIRECTION);
+    model.params.set(dsp::DIRECTION, -direction);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let mut reversed = false;
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("reverse")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.reverse, ui)
+    {
+        reversed = true;
+    }
+    if reversed {
+        reverse(model);
+    }
+}
+
+/// `turns` of the way round clockwise from the top, `radius` out
+fn around(center: Point2, radius: f32, turns: f32) -> Point2 {
+    let angle = (0.25 - turns) * TAU;
+    center + vec2(angle.cos(), angle.sin()) * radius
+}
+
+/// `octave`s out along the spiral, a turn to each
+fn spiral(center: Point2, inner: f32, outer: f32, octave: f32) -> Point2 {
+    let radius = inner + (outer - inner) * octave / PARTIALS as f32;
+    around(center, radius, octave)
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let centre = model.params.get(dsp::CENTRE);
+    let shift = model.state.shift;
+    let [r, g, b] = palette.line;
+
+    // the pitch helix seen from above, low in the middle, a turn an octave
+    let spectrum = Rect::from_x_y_w_h(
+        area.x(),
+        area.bottom() + area.h() * 0.1,
+        area.w(),
+        area.h() * 0.2,
+    );
+    let center = pt2(area.x(), area.y() + area.h() * 0.1);
+    let outer = (area.h() * 0.38).min(area.w() * 0.5);
+    let inner = outer * 0.1;
+    let turns = (0..=PARTIALS * SPIRAL_POINTS).map(|i| {
+        let octave = i as f32 / SPIRAL_POINTS as f32;
+        let alpha = 0.1 + dsp::envelope(octave, centre) * 0.3;
+        (spiral(center, inner, outer, octave), rgba(r, g, b, alpha))
+    });
+    draw.polyline().weight(1.0).points_colored(turns);
+
+    // every partial on its way round and out, as loud as it's drawn, all of
+    // them at the same angle as octaves apart they're the same note
+    let [ar, ag, ab] = palette.accent(0);
+    for partial in 0..PARTIALS {
+        let octave = partial as f32 + shift;
+        let amp = dsp::envelope(octave, centre);
+        let trail = (0..=16).map(|i| {
+            let back = octave - TRAIL * (1.0 - i as f32 / 16.0);
+            spiral(center, inner, outer, back.max(0.0))
+        });
+        draw.polyline()
+            .weight(1.0 + amp * 4.0)
+            .points(trail)
+            .color(rgba(ar, ag, ab, amp));
+        draw.ellipse()
+            .xy(spiral(center, inner, outer, octave))
+            .radius(2.0 + amp * 8.0)
+            .color(rgba(ar, ag, ab, 0.2 + amp * 0.8));
+
+        // and in the spectrum below, under the envelope
+        let x = spectrum.left() + spectrum.w() * octave / PARTIALS as f32;
+        draw.line()
+            .start(pt2(x, spectrum.bottom()))
+            .end(pt2(x, spectrum.bottom() + spectrum.h() * amp))
+            .weight(3.0)
+            .color(rgba(ar, ag, ab, 0.8));
+    }
+    let curve = (0..=PARTIALS * SPIRAL_POINTS).map(|i| {
+        let octave = i as f32 / SPIRAL_POINTS as f32;
+        let x = spectrum.left() + spectrum.w() * octave / PARTIALS as f32;
+        pt2(
+            x,
+            spectrum.bottom() + spectrum.h() * dsp::envelope(octave, centre),
+        )
+    });
+    draw.polyline()
+        .weight(1.0)
+        .points(curve)
+        .color(rgba(r, g, b, 0.5));
+
+    // the pitch class heard, counting up from the A the partials start on
+    let semitones = (shift * 12.0).round() as usize;
+    let note = NOTE_NAMES[(A4_MIDI as usize + semitones) % 12];
+    draw.text(&format!("{}\n{:+.2} oct", note, model.state.travelled))
+        .xy(center)
+        .font_size(14)
+        .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
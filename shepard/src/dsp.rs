@@ -0,0 +1,169 @@
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::Wavetable;
+use std::f32::consts::TAU;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// octaves the partials span, one partial to each
+pub const PARTIALS: usize = 9;
+/// where the lowest partial starts, it fades in from silence here
+pub const LOW: f32 = 27.5;
+/// per partial at full volume, about half of them are loud at once
+const GAIN: f32 = 0.2;
+const TABLE_SIZE: usize = 4096;
+
+pub const RATE: usize = 0;
+pub const DIRECTION: usize = 1;
+pub const STEPS: usize = 2;
+pub const CENTRE: usize = 3;
+pub const VOLUME: usize = 4;
+
+/// how fast and which way it glides, whether it glides or steps, where the
+/// loudest partials sit, then how loud it plays
+pub static PARAMS: [ParamSpec; 5] = [
+    // octaves a second
+    ParamSpec::new("rate", 0.01, 0.5, 0.05).curve(Curve::Exponential),
+    // down at -1, standing still at 0
+    ParamSpec::new("direction", -1.0, 1.0, 1.0),
+    // a Risset glide below a half, a Shepard scale in semitones above
+    ParamSpec::new("steps", 0.0, 1.0, 0.0),
+    // in octaves up from the lowest partial
+    ParamSpec::new("centre", 2.0, PARTIALS as f32 - 2.0, PARTIALS as f32 / 2.0),
+    ParamSpec::new("volume", 0.0, 1.0, 0.5),
+];
+
+/// How loud a partial `octave` octaves above `LOW` is, a raised cosine over
+/// the span peaking at `centre` and silent at both ends.
+pub fn envelope(octave: f32, centre: f32) -> f32 {
+    let span = PARTIALS as f32;
+    if octave <= 0.0 || octave >= span {
+        return 0.0;
+    }
+    // bent so `centre` lands where the plain cosine peaks
+    let x = if octave < centre {
+        0.5 * octave / centre
+    } else {
+        0.5 + 0.5 * (octave - centre) / (span - centre)
+    };
+    0.5 - 0.5 * (TAU * x).cos()
+}
+
+/// Where the glide is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// from 0 to 1 through the octave, what's heard, after stepping
+    pub shift: f32,
+    /// octaves travelled since the start, negative going down
+    pub travelled: f64,
+}
+
+/// Octave spaced sines sliding together under a fixed spectral envelope.
+///
+/// As the top partial fades out the bottom one fades in an octave below
+/// where the next would be, so the glide never arrives anywhere.
+pub struct Engine {
+    bus: AudioEnd<(), State>,
+    params: Params,
+    /// octaves travelled, its fraction is the shift
+    travelled: f64,
+    phases: [f32; PARTIALS],
+    amps: [f32; PARTIALS],
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<(), State>, params: Params) -> Self {
+        Self {
+            bus,
+            params,
+            travelled: 0.0,
+            phases: [0.0; PARTIALS],
+            amps: [0.0; PARTIALS],
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// the shift heard, snapped to semitones when stepping
+    fn shift(&self) -> f32 {
+        let shift = self.travelled.rem_euclid(1.0) as f32;
+        if self.params.get(STEPS) > 0.5 {
+            (shift * 12.0).floor() / 12.0
+        } else {
+            shift
+        }
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+
+        let frames = out.len() / channels;
+        let sample_time = 1.0 / sample_rate as f32;
+        let before = self.travelled.floor();
+        let speed = self.params.get(RATE) * self.params.get(DIRECTION);
+        self.travelled += (speed * sample_time * frames as f32) as f64;
+        // each partial takes over the phase of the one it replaces, so
+        // wrapping round the octave is seamless
+        let wrapped = (self.travelled.floor() - before) as i64;
+        if wrapped > 0 {
+            self.phases.rotate_right(1);
+            self.amps.rotate_right(1);
+        } else if wrapped < 0 {
+            self.phases.rotate_left(1);
+            self.amps.rotate_left(1);
+        }
+
+        // mono into the first channel, copied to the rest after
+        let shift = self.shift();
+        let centre = self.params.get(CENTRE);
+        let gain = self.params.get(VOLUME) * GAIN;
+        for partial in 0..PARTIALS {
+            let octave = partial as f32 + shift;
+            let target = envelope(octave, centre);
+            let mut amp = self.amps[partial];
+            if amp == 0.0 && target == 0.0 {
+                continue;
+            }
+            let step = (target - amp) / frames as f32;
+            let increment = LOW * 2f32.powf(octave) * sample_time;
+            let phase = &mut self.phases[partial];
+            for frame in out.chunks_exact_mut(channels) {
+                frame[0] += self.sine.at(*phase) * amp * gain;
+                *phase = (*phase + increment).fract();
+                amp += step;
+            }
+            self.amps[partial] = target;
+        }
+        for frame in out.chunks_exact_mut(channels) {
+            let sample = frame[0];
+            for out in frame[1..].iter_mut() {
+                *out = sample;
+            }
+        }
+
+        self.bus.publish(State {
+            shift,
+            travelled: self.travelled,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
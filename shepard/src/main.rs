@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("shepard");
+    match &args.render {
+        Some(request) => shepard::render(request),
+        None if args.headless => shepard::headless(),
+        None => shepard::run(),
+    }
+}
@@ -0,0 +1,518 @@
+use crate::dsp::{self, Command, Engine, State};
+use crate::sample::Sample;
+use crate::shuffle::{Arrangement, MAX_SLICES};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::tasks::{self, Pending, Tasks};
+use app_common::theme::{self, Themed};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// a bar of guitar at 120bpm, shared with yfes, unless the config names
+/// another loop
+static LOOP: Asset = Asset::new("gtr.wav", include_bytes!("../../yfes/res/gtr.wav"));
+
+/// back to the first step with the loop in order
+const RESTART: Key = Key::Space;
+
+/// peaks drawn across the whole loop
+const OVERVIEW: usize = 1024;
+/// how quickly a block slides to its step, per second
+const SLIDE: f32 = 8.0;
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        restart,
+    }
+}
+
+/// `path`, or the built in loop
+fn load_sample(path: Option<&Path>) -> Result<Sample, startup::Error> {
+    let (path, bytes) = match path {
+        Some(path) => (path.to_path_buf(), fs::read(path).map(Cow::Owned)),
+        None => (LOOP.source(), LOOP.bytes()),
+    };
+    let failed = |reason: String| startup::Error::Sample {
+        path: path.clone(),
+        reason,
+    };
+    Sample::decode(&bytes.map_err(|e| failed(e.to_string()))?).map_err(failed)
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    sample: Arc<Sample>,
+    seed: u64,
+) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), sample, seed);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("shuffler", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the headless engine on the configured loop, silent if it can't be read
+fn headless_engine(config: &Config, seed: u64) -> Engine {
+    let sample = load_sample(config.sample_path.as_deref()).unwrap_or_else(|e| {
+        eprintln!("shuffler: {}", e);
+        Sample::default()
+    });
+    let (engine, _bus) = engine(config, &load_params(config), Arc::new(sample), seed);
+    engine
+}
+
+/// the loop shuffling without a window or audio device
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("shuffler", &config::path("shuffler"));
+    let seed = shell::seed(session_seed);
+    let mut engine = headless_engine(&config, seed);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the loop shuffling on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("shuffler", &config::path("shuffler"));
+    let seed = shell::seed(session_seed);
+    render::headless(
+        "shuffler",
+        headless_engine(&config, seed),
+        stream_config(&config),
+    );
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    bus: UiEnd<Command, State>,
+    /// the arrangement as of the last buffer played
+    state: State,
+    /// the loop the engine plays
+    sample: Arc<Sample>,
+    /// the loop before the last one dropped in, kept so the audio thread
+    /// never frees it
+    _previous: Option<Arc<Sample>>,
+    peaks: Vec<f32>,
+    /// where each step's block is drawn, in steps, sliding from the slice
+    /// it took towards its own place
+    slots: [f32; MAX_SLICES],
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// loops being decoded, off the window's thread so it keeps drawing
+    tasks: Tasks,
+    /// the last loop asked for, until it's swapped in
+    loading: Option<Pending<Result<Loaded, startup::Error>>>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("shuffler");
+    let (config, session_seed) = shell::load_config("shuffler", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("shuffler", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("shuffler", &config, &params);
+    let serial = serial::Sensors::from_config("shuffler", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("shuffler", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    let sample = Arc::new(
+        load_sample(config.sample_path.as_deref()).unwrap_or_else(|e| {
+            errors.push(e);
+            Sample::default()
+        }),
+    );
+    let (engine, bus) = engine(&config, &params, sample.clone(), seed);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "shuffler", &config, stream.stats());
+    let shell = Shell::new("shuffler", config, &config_path, "phosphor", stream_config)
+        .with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        bus,
+        state: State::default(),
+        peaks: sample.peaks(OVERVIEW),
+        sample,
+        _previous: None,
+        slots: slots(),
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        tasks: Tasks::new("shuffler", tasks::THREADS),
+        loading: None,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// every block in its own place
+fn slots() -> [f32; MAX_SLICES] {
+    let mut slots = [0.0; MAX_SLICES];
+    for (step, slot) in slots.iter_mut().enumerate() {
+        *slot = step as f32;
+    }
+    slots
+}
+
+/// A loop decoded as a task, with its overview.
+struct Loaded {
+    sample: Sample,
+    peaks: Vec<f32>,
+    path: Option<PathBuf>,
+}
+
+/// decodes the loop at `path`, or the built in one, as a task, dropping
+/// any asked for before that hasn't come in yet
+fn load_loop(model: &mut Model, path: Option<&Path>) {
+    let path = path.map(Path::to_path_buf);
+    let pending = model.tasks.spawn("loading loop", move |progress| {
+        let sample = load_sample(path.as_deref())?;
+        progress.set(0.8);
+        let peaks = sample.peaks(OVERVIEW);
+        Ok(Loaded {
+            sample,
+            peaks,
+            path,
+        })
+    });
+    model.loading = Some(pending);
+}
+
+/// swaps in the loop once it's decoded and remembers it in the config
+fn swap_loop(model: &mut Model) {
+    let loaded = match model.loading.as_ref().and_then(Pending::take) {
+        Some(Ok(loaded)) => loaded,
+        Some(Err(e)) => {
+            eprintln!("shuffler: {}", e);
+            model.loading = None;
+            return;
+        }
+        None => return,
+    };
+    model.loading = None;
+    let sample = Arc::new(loaded.sample);
+    model.peaks = loaded.peaks;
+    model.slots = slots();
+    model._previous = Some(std::mem::replace(&mut model.sample, sample.clone()));
+    model.stream.send(move |engine| engine.set_sample(sample));
+    model.shell.config.sample_path = loaded.path;
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(DroppedFile(path)),
+            ..
+        } => {
+            load_loop(model, Some(&path));
+            return;
+        }
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if key == RESTART {
+        let _ = model.bus.send(Command::Restart);
+    }
+    model.hud.key_pressed(key);
+    model
+        .shell
+        .key_pressed(app, key, &model.stream, Some(model.seed));
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// blocks that took a new slice jump to where that slice lives, then all of
+/// them slide home
+fn shuffle_blocks(model: &mut Model, previous: &Arrangement, dt: f32) {
+    let arrangement = &model.state.arrangement;
+    if arrangement.len() != previous.len() {
+        model.slots = slots();
+    } else {
+        for (step, (now, before)) in arrangement
+            .slices()
+            .iter()
+            .zip(previous.slices())
+            .enumerate()
+        {
+            if now != before {
+                model.slots[step] = now.source as f32;
+            }
+        }
+    }
+    let slide = 1.0 - (-dt * SLIDE).exp();
+    for (step, slot) in model.slots.iter_mut().enumerate() {
+        *slot += (step as f32 - *slot) * slide;
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.tasks.poll();
+    swap_loop(model);
+    let previous = model.state.arrangement;
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    shuffle_blocks(model, &previous, update.since_last.as_secs_f32());
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        let sample_path = config.sample_path.clone();
+        let reload = sample_path != model.shell.config.sample_path;
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+        if reload {
+            load_loop(model, sample_path.as_deref());
+        }
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    for _click in widget::Button::new()
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label("restart")
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.restart, ui)
+    {
+        let _ = model.bus.send(Command::Restart);
+    }
+    model.tasks.panel(palette, ui);
+}
+
+/// `peaks` as mirrored vertical lines across `rect`
+fn waveform(draw: &Draw, rect: Rect, peaks: &[f32], color: Rgb) {
+    let step = rect.w() / peaks.len().max(1) as f32;
+    for (i, peak) in peaks.iter().enumerate() {
+        let x = rect.left() + (i as f32 + 0.5) * step;
+        let h = peak.min(1.0) * rect.h() * 0.5;
+        draw.line()
+            .start(pt2(x, rect.y() - h))
+            .end(pt2(x, rect.y() + h))
+            .weight(step.max(1.0))
+            .color(color);
+    }
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let State {
+        arrangement,
+        step: playing,
+        progress,
+    } = model.state;
+    let len = arrangement.len();
+    let width = area.w() / len as f32;
+    let [r, g, b] = palette.line;
+    let accent = theme::color(palette.accent(0));
+    // peaks of the loop per slice
+    let per_slice = model.peaks.len() / len;
+
+    // the steps as played, a block per step
+    let height = area.h() * 0.4;
+    let row = area.y() + area.h() * 0.2;
+    for (step, slice) in arrangement.slices().iter().enumerate() {
+        let slot = model.slots[step];
+        // blocks on the move lift over the others
+        let lift = (slot - step as f32).abs().min(2.0) * height * 0.15;
+        let block = Rect::from_x_y_w_h(
+            area.left() + (slot + 0.5) * width,
+            row + lift,
+            width * 0.9,
+            height,
+        );
+        let alpha = if step == playing { 0.4 } else { 0.12 };
+        draw.rect()
+            .xy(block.xy())
+            .wh(block.wh())
+            .color(rgba(r, g, b, alpha));
+
+        let start = slice.source * per_slice;
+        let source = &model.peaks[start..start + per_slice];
+        // a stutter holds the start of the slice that many times over
+        let piece = &source[..(per_slice / slice.repeats).max(1).min(per_slice)];
+        let mut peaks = Vec::with_capacity(piece.len() * slice.repeats);
+        for _ in 0..slice.repeats {
+            if slice.reverse {
+                peaks.extend(piece.iter().rev());
+            } else {
+                peaks.extend(piece);
+            }
+        }
+        waveform(draw, block.pad(2.0), &peaks, accent);
+        draw.text(&(slice.source + 1).to_string())
+            .xy(pt2(block.x(), block.top() + 12.0))
+            .font_size(12)
+            .color(theme::color(palette.line));
+    }
+    let x = area.left() + (playing as f32 + progress) * width;
+    draw.line()
+        .start(pt2(x, row - height * 0.5))
+        .end(pt2(x, row + height * 0.5))
+        .weight(2.0)
+        .color(theme::color(palette.line));
+
+    // the loop as it is, the slice playing now lit
+    let strip = Rect::from_x_y_w_h(
+        area.x(),
+        area.bottom() + area.h() * 0.15,
+        area.w(),
+        area.h() * 0.25,
+    );
+    let source = arrangement.slices()[playing.min(len - 1)].source;
+    draw.rect()
+        .x_y(strip.left() + (source as f32 + 0.5) * width, strip.y())
+        .w_h(width, strip.h())
+        .color(rgba(r, g, b, 0.25));
+    waveform(draw, strip, &model.peaks, accent);
+    for slice in 1..len {
+        let x = strip.left() + slice as f32 * width;
+        draw.line()
+            .start(pt2(x, strip.bottom()))
+            .end(pt2(x, strip.top()))
+            .weight(1.0)
+            .color(rgba(r, g, b, 0.3));
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,175 @@
+use crate::sample::Sample;
+use crate::shuffle::{Arrangement, Odds, MAX_SLICES};
+use app_common::bus::AudioEnd;
+use app_common::param::{ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::random::Rng;
+use std::sync::Arc;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// seconds each piece fades in and out over, so cuts don't click
+const FADE: f64 = 0.002;
+
+pub const SLICES: usize = 0;
+pub const SHUFFLE: usize = 1;
+pub const REVERSE: usize = 2;
+pub const STUTTER: usize = 3;
+pub const REPEATS: usize = 4;
+pub const VOLUME: usize = 5;
+
+/// the grid, the odds of each change, then how loud it plays
+pub static PARAMS: [ParamSpec; 6] = [
+    ParamSpec::new("slices", 2.0, MAX_SLICES as f32, 16.0),
+    ParamSpec::new("shuffle", 0.0, 1.0, 0.35),
+    ParamSpec::new("reverse", 0.0, 1.0, 0.15),
+    ParamSpec::new("stutter", 0.0, 1.0, 0.2),
+    ParamSpec::new("repeats", 2.0, 8.0, 4.0),
+    ParamSpec::new("volume", 0.0, 1.0, 0.8),
+];
+
+pub enum Command {
+    /// back to the first step, the loop in order
+    Restart,
+}
+
+/// What the window draws, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    pub arrangement: Arrangement,
+    pub step: usize,
+    /// from 0 to 1 through the step
+    pub progress: f32,
+}
+
+/// Plays the loop a step at a time, choosing each step's slice as it
+/// starts.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    sample: Arc<Sample>,
+    rng: Rng,
+    arrangement: Arrangement,
+    step: usize,
+    /// sample frames into the step
+    phase: f64,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(
+        bus: AudioEnd<Command, State>,
+        params: Params,
+        sample: Arc<Sample>,
+        seed: u64,
+    ) -> Self {
+        let slices = params.get(SLICES).round() as usize;
+        Self {
+            bus,
+            params,
+            sample,
+            rng: Rng::new(seed),
+            arrangement: Arrangement::straight(slices),
+            step: 0,
+            phase: 0.0,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// starts over on another loop, the caller keeps a reference to the old
+    /// one so it isn't freed on the audio thread
+    pub fn set_sample(&mut self, sample: Arc<Sample>) {
+        self.sample = sample;
+        self.restart();
+    }
+
+    fn restart(&mut self) {
+        self.arrangement = Arrangement::straight(self.params.get(SLICES).round() as usize);
+        self.step = 0;
+        self.phase = 0.0;
+    }
+
+    /// on to the next step and what it plays, the grid changes size here
+    /// rather than mid-step
+    fn advance(&mut self) {
+        let len = (self.params.get(SLICES).round() as usize).clamp(1, MAX_SLICES);
+        if len != self.arrangement.len() {
+            self.arrangement = Arrangement::straight(len);
+        }
+        self.step = (self.step + 1) % len;
+        let odds = Odds {
+            shuffle: self.params.get(SHUFFLE),
+            reverse: self.params.get(REVERSE),
+            stutter: self.params.get(STUTTER),
+            max_repeats: self.params.get(REPEATS).round() as usize,
+        };
+        let slice = odds.choose(&mut self.rng, self.step, len);
+        self.arrangement.set(self.step, slice);
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        let mut restart = false;
+        for command in self.bus.commands() {
+            match command {
+                Command::Restart => restart = true,
+            }
+        }
+        if restart {
+            self.restart();
+        }
+
+        let gain = self.params.get(VOLUME);
+        let rate = self.sample.sample_rate as f64 / sample_rate as f64;
+        let fade = FADE * self.sample.sample_rate as f64;
+        let mut slice_len = self.sample.len() as f64 / self.arrangement.len() as f64;
+        if slice_len >= 1.0 {
+            for frame in out.chunks_exact_mut(channels) {
+                let slice = self.arrangement.slices()[self.step];
+                let piece = slice_len / slice.repeats as f64;
+                let local = self.phase % piece;
+                let offset = if slice.reverse { piece - local } else { local };
+                let level = (local / fade).min((piece - local) / fade).min(1.0) as f32 * gain;
+                let [left, right] = self.sample.at(slice.source as f64 * slice_len + offset);
+                match frame {
+                    [mono] => *mono = (left + right) * 0.5 * level,
+                    [l, r, ..] => {
+                        *l = left * level;
+                        *r = right * level;
+                    }
+                    [] => {}
+                }
+
+                self.phase += rate;
+                if self.phase >= slice_len {
+                    self.phase -= slice_len;
+                    self.advance();
+                    slice_len = self.sample.len() as f64 / self.arrangement.len() as f64;
+                }
+            }
+        }
+
+        self.bus.publish(State {
+            arrangement: self.arrangement,
+            step: self.step,
+            progress: (self.phase / slice_len.max(1.0)) as f32,
+        });
+        self.limiter.process_interleaved(out, channels);
+    }
+}
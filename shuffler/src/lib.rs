@@ -0,0 +1,6 @@
+mod app;
+mod dsp;
+mod sample;
+mod shuffle;
+
+pub use app::{headless, render, run};
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("shuffler");
+    match &args.render {
+        Some(request) => shuffler::render(request),
+        None if args.headless => shuffler::headless(),
+        None => shuffler::run(),
+    }
+}
@@ -0,0 +1,83 @@
+//! The loop being shuffled, decoded whole into stereo frames.
+
+use std::io::Cursor;
+
+#[derive(Default)]
+pub struct Sample {
+    frames: Vec<[f32; 2]>,
+    pub sample_rate: u32,
+}
+
+impl Sample {
+    /// a WAV file of any bit depth, mono is spread to both sides and
+    /// channels past the second are dropped
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let spec = reader.spec();
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .into_samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| e.to_string())?
+            }
+        };
+        let channels = spec.channels as usize;
+        if channels == 0 {
+            return Err(String::from("no channels"));
+        }
+        let frames = samples
+            .chunks_exact(channels)
+            .map(|frame| match frame {
+                [mono] => [*mono, *mono],
+                [left, right, ..] => [*left, *right],
+                [] => [0.0, 0.0],
+            })
+            .collect();
+        Ok(Self {
+            frames,
+            sample_rate: spec.sample_rate,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// between frames, silent outside the sample
+    pub fn at(&self, position: f64) -> [f32; 2] {
+        if position < 0.0 {
+            return [0.0, 0.0];
+        }
+        let index = position as usize;
+        let w = (position - index as f64) as f32;
+        let frame = |i: usize| self.frames.get(i).copied().unwrap_or([0.0, 0.0]);
+        let (a, b) = (frame(index), frame(index + 1));
+        [a[0] + (b[0] - a[0]) * w, a[1] + (b[1] - a[1]) * w]
+    }
+
+    /// the loudest sample in each of `count` equal spans, for drawing
+    pub fn peaks(&self, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| {
+                let start = i * self.len() / count;
+                let end = ((i + 1) * self.len() / count)
+                    .max(start + 1)
+                    .min(self.len());
+                self.frames[start.min(end)..end]
+                    .iter()
+                    .fold(0.0f32, |peak, [l, r]| peak.max(l.abs()).max(r.abs()))
+            })
+            .collect()
+    }
+}
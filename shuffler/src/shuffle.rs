@@ -0,0 +1,106 @@
+//! What plays at each step of the grid.
+//!
+//! The loop is cut into equal slices and played step by step. Each time a
+//! step comes round it may take any slice instead of its own, play it
+//! backwards, or stutter, repeating the start of the slice to fill the
+//! step.
+
+use dsp_common::random::Rng;
+
+pub const MAX_SLICES: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slice {
+    /// which slice of the loop plays
+    pub source: usize,
+    pub reverse: bool,
+    /// the step holds this many copies of the start of the slice, 1 plays
+    /// it whole
+    pub repeats: usize,
+}
+
+impl Slice {
+    /// the slice the step would play in the untouched loop
+    pub fn straight(step: usize) -> Self {
+        Self {
+            source: step,
+            reverse: false,
+            repeats: 1,
+        }
+    }
+}
+
+/// Chances out of 1 of each change at each step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Odds {
+    pub shuffle: f32,
+    pub reverse: f32,
+    pub stutter: f32,
+    /// a stutter repeats from 2 up to this many times
+    pub max_repeats: usize,
+}
+
+impl Odds {
+    /// what step `step` of `len` plays this time round
+    pub fn choose(&self, rng: &mut Rng, step: usize, len: usize) -> Slice {
+        let source = if rng.chance(self.shuffle) {
+            rng.below(len)
+        } else {
+            step
+        };
+        let repeats = if rng.chance(self.stutter) {
+            2 + rng.below(self.max_repeats.max(2) - 1)
+        } else {
+            1
+        };
+        Slice {
+            source,
+            reverse: rng.chance(self.reverse),
+            repeats,
+        }
+    }
+}
+
+/// The slice each step last played, kept inline so it can cross the audio
+/// bus.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arrangement {
+    slices: [Slice; MAX_SLICES],
+    len: usize,
+}
+
+impl Arrangement {
+    /// every step on its own slice
+    pub fn straight(len: usize) -> Self {
+        let mut slices = [Slice::straight(0); MAX_SLICES];
+        for (step, slice) in slices.iter_mut().enumerate() {
+            *slice = Slice::straight(step);
+        }
+        Self {
+            slices,
+            len: len.clamp(1, MAX_SLICES),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn slices(&self) -> &[Slice] {
+        &self.slices[..self.len]
+    }
+
+    pub fn set(&mut self, step: usize, slice: Slice) {
+        self.slices[step] = slice;
+    }
+}
+
+impl Default for Arrangement {
+    fn default() -> Self {
+        Self::straight(1)
+    }
+}
@@ -0,0 +1,758 @@
+use crate::dsp::{self, Command, Engine, State};
+use crate::pattern::{Cell, Note, Song, CHANNELS, INSTRUMENTS, PATTERNS, ROWS};
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// played until there's a song of the user's to load
+const DEMO: &str = include_str!("../res/demo.txt");
+/// a bar of guitar, shared with yfes, for the grains to read
+static TABLE: Asset = Asset::new("gtr.wav", include_bytes!("../../yfes/res/gtr.wav"));
+
+const INSTRUMENT_NAMES: [&str; INSTRUMENTS as usize] = ["sine", "hat", "grains"];
+
+/// plays from the slot being edited, or stops
+const PLAY: Key = Key::Space;
+const PREVIOUS_SLOT: Key = Key::Home;
+const NEXT_SLOT: Key = Key::End;
+const PREVIOUS_PATTERN: Key = Key::LBracket;
+const NEXT_PATTERN: Key = Key::RBracket;
+/// a slot after the one being edited, playing the same pattern
+const INSERT_SLOT: Key = Key::Insert;
+const REMOVE_SLOT: Key = Key::Back;
+const OCTAVE_DOWN: Key = Key::Minus;
+const OCTAVE_UP: Key = Key::Equals;
+/// empties the column under the cursor
+const CLEAR: Key = Key::Delete;
+const NOTE_OFF: Key = Key::Key1;
+/// rows PageUp and PageDown jump
+const PAGE: isize = 16;
+
+/// two octaves, the lower on the bottom row of letters, over the hud,
+/// capture and theme keys while the cursor is on a note
+const PIANO: [Key; 25] = [
+    Key::Z,
+    Key::S,
+    Key::X,
+    Key::D,
+    Key::C,
+    Key::V,
+    Key::G,
+    Key::B,
+    Key::H,
+    Key::N,
+    Key::J,
+    Key::M,
+    Key::Q,
+    Key::Key2,
+    Key::W,
+    Key::Key3,
+    Key::E,
+    Key::R,
+    Key::Key5,
+    Key::T,
+    Key::Key6,
+    Key::Y,
+    Key::Key7,
+    Key::U,
+    Key::I,
+];
+const HEX: [Key; 16] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+];
+
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+const LINE: f32 = 18.0;
+const ROW_NUMBER_WIDTH: f32 = 36.0;
+const CHANNEL_WIDTH: f32 = 120.0;
+/// where each column starts in a channel, and how wide it is
+const COLUMNS: [(f32, f32); 3] = [(4.0, 36.0), (44.0, 14.0), (62.0, 36.0)];
+
+widget_ids! {
+    struct Ids {
+        play,
+    }
+}
+
+/// beside the config, saved on exit
+fn song_path(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("song.txt")
+}
+
+/// the song last saved at `path`, or the demo if there isn't one
+fn load_song(path: &Path) -> Result<Box<Song>, startup::Error> {
+    let failed = |reason: String| startup::Error::Sample {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => DEMO.to_string(),
+        Err(e) => return Err(failed(e.to_string())),
+    };
+    Song::parse(&text).map(Box::new).map_err(failed)
+}
+
+/// the built in loop mixed to mono, kept for the rest of the program
+fn load_table() -> Result<&'static [f32], startup::Error> {
+    let failed = |reason: String| startup::Error::Sample {
+        path: TABLE.source(),
+        reason,
+    };
+    let bytes = TABLE.bytes().map_err(|e| failed(e.to_string()))?;
+    let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(|e| failed(e.to_string()))?;
+    let channels = reader.spec().channels.max(1) as usize;
+    let samples: Vec<f32> = reader
+        .into_samples::<i16>()
+        .map(|s| s.map(|s| s as f32 / 32_768.0))
+        .collect::<Result<_, _>>()
+        .map_err(|e| failed(e.to_string()))?;
+    let mono = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok(granular::leak_table(mono))
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    song: Box<Song>,
+    table: &'static [f32],
+) -> (Engine, UiEnd<Command, State>) {
+    // a whole pattern pasted in is a command a cell
+    let (ui_bus, audio_bus) = bus::bus(1024, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), song, table);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("tracker", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the saved song and the table, or why either couldn't be read
+fn load(config_path: &Path) -> (Box<Song>, &'static [f32]) {
+    let loaded = load_song(&song_path(config_path)).and_then(|song| Ok((song, load_table()?)));
+    loaded.unwrap_or_else(|e| startup::fatal("tracker", e))
+}
+
+/// the saved song from the top, without a window or audio device
+pub fn render(request: &Request) {
+    let config_path = config::path("tracker");
+    let (config, _) = shell::load_config("tracker", &config_path);
+    let (song, table) = load(&config_path);
+    let params = shell::load_params("tracker", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, song, table);
+    engine.play(0);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the saved song on the audio device without a window
+pub fn headless() {
+    let config_path = config::path("tracker");
+    let (config, _) = shell::load_config("tracker", &config_path);
+    let (song, table) = load(&config_path);
+    let params = shell::load_params("tracker", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, song, table);
+    engine.play(0);
+    render::headless("tracker", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Note,
+    Instrument,
+    Effect,
+}
+
+const ORDER: [Column; 3] = [Column::Note, Column::Instrument, Column::Effect];
+
+/// Where the next key edits.
+#[derive(Clone, Copy, Debug)]
+struct Position {
+    slot: usize,
+    row: usize,
+    channel: usize,
+    column: Column,
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<Command, State>,
+    /// where the song is as of the last buffer played
+    state: State,
+    /// as edited, the engine is sent every change
+    song: Box<Song>,
+    /// `None` if the saved song couldn't be read, so it isn't written over
+    song_path: Option<PathBuf>,
+    cursor: Position,
+    /// of the lower row of the piano keys
+    octave: u8,
+    /// put in with every note
+    instrument: u8,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("tracker");
+    let (config, _) = shell::load_config("tracker", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("tracker", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("tracker", &config, &params);
+    let serial = serial::Sensors::from_config("tracker", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("tracker", startup::Error::Ui(format!("{:?}", e))));
+    let mut errors = Vec::new();
+    let path = song_path(&config_path);
+    let (song, song_path) = match load_song(&path) {
+        Ok(song) => (song, Some(path)),
+        Err(e) => {
+            errors.push(e);
+            (Box::new(Song::parse(DEMO).unwrap_or_default()), None)
+        }
+    };
+    let table = load_table().unwrap_or_else(|e| {
+        errors.push(e);
+        &[]
+    });
+    let (mut engine, bus) = engine(&config, &params, song.clone(), table);
+    // something to hear straight away, space stops it
+    engine.play(0);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    errors.extend(stream.rebuild().err().map(Into::into));
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "tracker", &config, stream.stats());
+    let shell =
+        Shell::new("tracker", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        state: State::default(),
+        song,
+        song_path,
+        cursor: Position {
+            slot: 0,
+            row: 0,
+            channel: 0,
+            column: Column::Note,
+        },
+        octave: 4,
+        instrument: dsp::SINE,
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// the pattern the cursor is in
+fn pattern(model: &Model) -> usize {
+    model.song.order.pattern(model.cursor.slot)
+}
+
+fn cell(model: &Model) -> Cell {
+    let Position { row, channel, .. } = model.cursor;
+    model.song.patterns[pattern(model)][row][channel]
+}
+
+/// changes the cell under the cursor here and in the engine
+fn set_cell(model: &mut Model, cell: Cell) {
+    let pattern = pattern(model);
+    let Position { row, channel, .. } = model.cursor;
+    model.song.patterns[pattern][row][channel] = cell;
+    let _ = model.bus.send(Command::Cell {
+        pattern,
+        row,
+        channel,
+        cell,
+    });
+}
+
+fn send_order(model: &mut Model) {
+    let _ = model.bus.send(Command::Order(model.song.order));
+}
+
+fn toggle_play(model: &mut Model) {
+    let command = if model.state.playing {
+        Command::Stop
+    } else {
+        Command::Play {
+            slot: model.cursor.slot,
+        }
+    };
+    let _ = model.bus.send(command);
+}
+
+fn move_rows(model: &mut Model, rows: isize) {
+    model.cursor.row = (model.cursor.row as isize + rows).rem_euclid(ROWS as isize) as usize;
+}
+
+/// a column at a time, on into the next channel and round
+fn move_columns(model: &mut Model, columns: isize) {
+    let at = model.cursor.channel * ORDER.len()
+        + ORDER
+            .iter()
+            .position(|c| *c == model.cursor.column)
+            .unwrap_or(0);
+    let at = (at as isize + columns).rem_euclid((CHANNELS * ORDER.len()) as isize) as usize;
+    model.cursor.channel = at / ORDER.len();
+    model.cursor.column = ORDER[at % ORDER.len()];
+}
+
+fn move_slot(model: &mut Model, slots: isize) {
+    let len = model.song.order.len() as isize;
+    model.cursor.slot = (model.cursor.slot as isize + slots).rem_euclid(len) as usize;
+}
+
+fn step_pattern(model: &mut Model, step: isize) {
+    let pattern = (pattern(model) as isize + step).rem_euclid(PATTERNS as isize) as usize;
+    model.song.order.set_pattern(model.cursor.slot, pattern);
+    send_order(model);
+}
+
+/// what a key puts in the column under the cursor, true if it did
+fn enter(model: &mut Model, key: Key) -> bool {
+    let mut cell = cell(model);
+    let hex = HEX.iter().position(|k| *k == key).map(|digit| digit as u8);
+    match model.cursor.column {
+        Column::Note => {
+            let note = if key == NOTE_OFF {
+                Note::Off
+            } else {
+                match PIANO.iter().position(|k| *k == key) {
+                    Some(offset) => {
+                        let key = (model.octave + 1) * 12 + offset as u8;
+                        Note::Key(key.clamp(Note::LOWEST, Note::HIGHEST))
+                    }
+                    None => return false,
+                }
+            };
+            cell.note = Some(note);
+            if note != Note::Off {
+                cell.instrument = Some(model.instrument);
+            }
+            let channel = model.cursor.channel;
+            set_cell(model, cell);
+            let _ = model.bus.send(Command::Preview { channel, cell });
+            move_rows(model, 1);
+        }
+        Column::Instrument => match hex {
+            Some(instrument) if instrument < INSTRUMENTS => {
+                cell.instrument = Some(instrument);
+                model.instrument = instrument;
+                set_cell(model, cell);
+                move_rows(model, 1);
+            }
+            _ => return false,
+        },
+        // shifted in a digit at a time, the oldest falling off the left
+        Column::Effect => match hex {
+            Some(digit) => {
+                cell.effect = (cell.effect << 4 | u16::from(digit)) & 0xfff;
+                set_cell(model, cell);
+            }
+            None => return false,
+        },
+    }
+    true
+}
+
+fn clear(model: &mut Model) {
+    let mut cell = cell(model);
+    match model.cursor.column {
+        Column::Note => cell.note = None,
+        Column::Instrument => cell.instrument = None,
+        Column::Effect => cell.effect = 0,
+    }
+    set_cell(model, cell);
+}
+
+/// true if the key was the editor's
+fn edit_key_pressed(model: &mut Model, key: Key) -> bool {
+    match key {
+        Key::Up => move_rows(model, -1),
+        Key::Down => move_rows(model, 1),
+        Key::PageUp => move_rows(model, -PAGE),
+        Key::PageDown => move_rows(model, PAGE),
+        Key::Left => move_columns(model, -1),
+        Key::Right => move_columns(model, 1),
+        Key::Tab => move_columns(model, ORDER.len() as isize),
+        PREVIOUS_SLOT => move_slot(model, -1),
+        NEXT_SLOT => move_slot(model, 1),
+        PREVIOUS_PATTERN => step_pattern(model, -1),
+        NEXT_PATTERN => step_pattern(model, 1),
+        INSERT_SLOT => {
+            if model.song.order.insert(model.cursor.slot) {
+                model.cursor.slot += 1;
+                send_order(model);
+            }
+        }
+        REMOVE_SLOT => {
+            if model.song.order.remove(model.cursor.slot) {
+                model.cursor.slot = model.cursor.slot.min(model.song.order.len() - 1);
+                send_order(model);
+            }
+        }
+        OCTAVE_DOWN => model.octave = model.octave.saturating_sub(1),
+        OCTAVE_UP => model.octave = (model.octave + 1).min(8),
+        PLAY => toggle_play(model),
+        CLEAR => clear(model),
+        _ => return enter(model, key),
+    }
+    true
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    if edit_key_pressed(model, key) {
+        return;
+    }
+    model.hud.key_pressed(key);
+    model.shell.key_pressed(app, key, &model.stream, None);
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+    if let Some(path) = &model.song_path {
+        if let Err(e) = fs::write(path, model.song.to_string()) {
+            eprintln!("tracker: cannot save {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let label = if model.state.playing { "stop" } else { "play" };
+    let mut toggled = false;
+    for _value in widget::Toggle::new(model.state.playing)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.play, ui)
+    {
+        toggled = true;
+    }
+    if toggled {
+        toggle_play(model);
+    }
+}
+
+/// `text` left aligned from `left`, centred on `y`
+fn text(draw: &Draw, text: &str, left: f32, y: f32, width: f32, color: Rgba) {
+    draw.text(text)
+        .x_y(left + width / 2.0, y)
+        .w_h(width, LINE)
+        .left_justify()
+        .font_size(13)
+        .color(color);
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let [r, g, b] = palette.line;
+    let faint = rgba(r, g, b, 0.45);
+    let plain = rgba(r, g, b, 0.9);
+    let [ar, ag, ab] = palette.accent(0);
+    let [pr, pg, pb] = palette.accent(1);
+    let state = &model.state;
+    let cursor = model.cursor;
+
+    // the order along the top, the slot being edited boxed and the one
+    // playing underlined
+    let mut y = area.top() - LINE / 2.0;
+    text(draw, "order", area.left(), y, 50.0, faint);
+    let per_line = ((area.w() - 50.0) / 28.0).max(1.0) as usize;
+    let first = cursor.slot / per_line * per_line;
+    for (i, slot) in model
+        .song
+        .order
+        .slots()
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(per_line)
+    {
+        let x = area.left() + 50.0 + (i - first) as f32 * 28.0;
+        if i == cursor.slot {
+            draw.rect()
+                .x_y(x + 12.0, y)
+                .w_h(24.0, LINE)
+                .color(rgba(ar, ag, ab, 0.4));
+        }
+        if state.playing && i == state.slot {
+            draw.line()
+                .start(pt2(x, y - LINE / 2.0))
+                .end(pt2(x + 24.0, y - LINE / 2.0))
+                .weight(2.0)
+                .color(rgba(pr, pg, pb, 1.0));
+        }
+        text(draw, &format!("{:02X}", slot), x + 4.0, y, 24.0, plain);
+    }
+
+    // a header for each channel, with how loud it is
+    y -= LINE * 1.5;
+    let left = area.left() + ROW_NUMBER_WIDTH;
+    let pattern = pattern(model);
+    text(
+        draw,
+        &format!("{:02X}", pattern),
+        area.left(),
+        y,
+        ROW_NUMBER_WIDTH,
+        plain,
+    );
+    for (channel, level) in state.levels.iter().enumerate() {
+        let x = left + channel as f32 * CHANNEL_WIDTH;
+        let width = (CHANNEL_WIDTH - 8.0) * level.min(1.0);
+        draw.rect()
+            .x_y(x + 4.0 + width / 2.0, y)
+            .w_h(width, LINE - 4.0)
+            .color(rgba(pr, pg, pb, 0.5));
+        text(
+            draw,
+            &format!("channel {}", channel + 1),
+            x + 4.0,
+            y,
+            CHANNEL_WIDTH,
+            plain,
+        );
+    }
+
+    // the rows with the cursor's in the middle, every beat brighter
+    let top = y - LINE * 1.5;
+    let visible = ((top - area.bottom() - LINE * 2.0) / LINE).max(1.0) as isize;
+    let first = cursor.row as isize - visible / 2;
+    let playing_here = state.playing && model.song.order.pattern(state.slot) == pattern;
+    for line in 0..visible {
+        let row = first + line;
+        if row < 0 || row >= ROWS as isize {
+            continue;
+        }
+        let row = row as usize;
+        let y = top - line as f32 * LINE;
+        let width = CHANNEL_WIDTH * CHANNELS as f32 + ROW_NUMBER_WIDTH;
+        if row == cursor.row {
+            draw.rect()
+                .x_y(area.left() + width / 2.0, y)
+                .w_h(width, LINE)
+                .color(rgba(r, g, b, 0.12));
+        }
+        if playing_here && row == state.row {
+            draw.rect()
+                .x_y(area.left() + width / 2.0, y)
+                .w_h(width, LINE)
+                .color(rgba(pr, pg, pb, 0.25));
+        }
+        let color = if row % 4 == 0 { plain } else { faint };
+        text(
+            draw,
+            &format!("{:02X}", row),
+            area.left(),
+            y,
+            ROW_NUMBER_WIDTH,
+            color,
+        );
+        for (channel, cell) in model.song.patterns[pattern][row].iter().enumerate() {
+            let x = left + channel as f32 * CHANNEL_WIDTH;
+            for ((column, (offset, w)), shown) in
+                ORDER.iter().zip(COLUMNS.iter()).zip(cell.columns().iter())
+            {
+                if row == cursor.row && channel == cursor.channel && *column == cursor.column {
+                    draw.rect()
+                        .x_y(x + offset + w / 2.0 - 2.0, y)
+                        .w_h(*w, LINE)
+                        .color(rgba(ar, ag, ab, 0.5));
+                }
+                text(draw, shown, x + offset, y, *w, color);
+            }
+        }
+    }
+
+    let footer = format!(
+        "octave {}   instrument {} {}   {}",
+        model.octave,
+        model.instrument,
+        INSTRUMENT_NAMES[model.instrument as usize],
+        if state.playing { "playing" } else { "stopped" },
+    );
+    text(
+        draw,
+        &footer,
+        area.left(),
+        area.bottom() + LINE / 2.0,
+        area.w(),
+        faint,
+    );
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
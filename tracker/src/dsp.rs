@@ -0,0 +1,448 @@
+use crate::oscillators::{self, Oscillators};
+use crate::pattern::{Cell, Effect, Note, Order, Song, CHANNELS, ROWS};
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::env::{Envelope, Retrigger, Shape};
+use dsp_common::filter::Biquad;
+use dsp_common::limiter::Limiter;
+use dsp_common::noise::White;
+use dsp_common::pan;
+use dsp_common::tuning::midi_to_freq;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// frames rendered between ticks, a row starts within about a millisecond
+/// of when it's due
+pub const BLOCK: usize = 64;
+/// steps of an arpeggio in a row
+const TICKS: usize = 6;
+const ROWS_PER_BEAT: f32 = 4.0;
+
+pub const SINE: u8 = 0;
+pub const HAT: u8 = 1;
+pub const GRAINS: u8 = 2;
+
+/// what each instrument is played at, a channel at full volume
+const SINE_GAIN: f32 = 0.25;
+const HAT_GAIN: f32 = 0.3;
+const GRAINS_GAIN: f32 = 0.8;
+const SINE_SHAPE: Shape = Shape::adsr(0.005, 0.2, 0.7, 0.15);
+const HAT_SHAPE: Shape = Shape::ar(0.001, 0.06);
+/// the hat's noise is filtered this many semitones above the note
+const HAT_OFFSET: f32 = 36.0;
+/// blocks between the grains of a grain voice
+const GRAIN_INTERVAL: usize = 12;
+
+pub const TEMPO: usize = 0;
+pub const GRAIN_LENGTH: usize = 1;
+pub const VOLUME: usize = 2;
+
+/// how fast the song plays until an `F` effect says otherwise, how long
+/// the grain voices last, then how loud it plays
+pub static PARAMS: [ParamSpec; 3] = [
+    ParamSpec::new("tempo", 40.0, 240.0, 125.0).unit("bpm"),
+    ParamSpec::new("grain length", 0.1, 4.0, 1.0)
+        .curve(Curve::Exponential)
+        .unit("s"),
+    ParamSpec::new("volume", 0.0, 1.0, 0.7),
+];
+
+pub enum Command {
+    /// what the editor changed
+    Cell {
+        pattern: usize,
+        row: usize,
+        channel: usize,
+        cell: Cell,
+    },
+    Order(Order),
+    /// from the top of `slot`
+    Play {
+        slot: usize,
+    },
+    Stop,
+    /// a cell's note on its instrument straight away, to hear it entered
+    Preview {
+        channel: usize,
+        cell: Cell,
+    },
+}
+
+/// Where the song is, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    pub playing: bool,
+    pub slot: usize,
+    pub row: usize,
+    /// how loud each channel is, before the volume
+    pub levels: [f32; CHANNELS],
+}
+
+/// What one column of the pattern plays with.
+#[derive(Clone, Debug)]
+struct Channel {
+    instrument: u8,
+    /// before any arpeggio
+    key: u8,
+    arpeggio: (u8, u8),
+    volume: f32,
+    /// from 0 to 1, left to right
+    pan: f32,
+    sine: Envelope,
+    hat: Envelope,
+    noise: White,
+    filter: Biquad,
+}
+
+impl Channel {
+    fn new(index: usize, sample_rate: u32) -> Self {
+        let mut sine = Envelope::new(SINE_SHAPE);
+        // a note over a sounding one rises from where it is
+        sine.set_retrigger(Retrigger::Legato);
+        Self {
+            instrument: SINE,
+            key: 60,
+            arpeggio: (0, 0),
+            volume: 1.0,
+            pan: 0.5,
+            sine,
+            hat: Envelope::new(HAT_SHAPE),
+            noise: White::new(index as u64),
+            filter: Biquad::highpass(8_000.0, sample_rate as f32),
+        }
+    }
+
+    /// as the song starts, leaving anything sounding to fade
+    fn reset(&mut self) {
+        self.instrument = SINE;
+        self.arpeggio = (0, 0);
+        self.volume = 1.0;
+        self.pan = 0.5;
+    }
+}
+
+/// Plays a song's patterns on sines, noise hats and grains, a channel to a
+/// column.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    player: Player,
+    limiter: Limiter,
+}
+
+/// Everything but the bus, so commands can be handled as they're read.
+struct Player {
+    params: Params,
+    song: Box<Song>,
+    channels: Vec<Channel>,
+    oscillators: Box<dyn Oscillators>,
+    blocks: [[f32; BLOCK]; CHANNELS],
+    granular: granular::Engine,
+    grains: [f32; BLOCK * 2],
+    playing: bool,
+    slot: usize,
+    row: usize,
+    tick: usize,
+    /// frames until the next tick
+    until_tick: f64,
+    /// from an `F` effect, in place of the param
+    tempo: Option<f32>,
+    /// the row of the next slot a `D` effect goes on to after this row
+    jump: Option<usize>,
+    sample_rate: u32,
+}
+
+impl Engine {
+    /// `table` is what the grains read, stopped until `play`
+    pub fn new(
+        bus: AudioEnd<Command, State>,
+        params: Params,
+        song: Box<Song>,
+        table: &'static [f32],
+    ) -> Self {
+        Self {
+            bus,
+            player: Player::new(params, song, table),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// from the top of `slot`
+    pub fn play(&mut self, slot: usize) {
+        self.player.play(slot);
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.player.sample_rate {
+            self.player.set_sample_rate(sample_rate);
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        for command in self.bus.commands() {
+            self.player.command(command);
+        }
+        self.player.render(out, channels);
+        self.bus.publish(self.player.state());
+        self.limiter.process_interleaved(out, channels);
+    }
+}
+
+impl Player {
+    fn new(params: Params, song: Box<Song>, table: &'static [f32]) -> Self {
+        let mut granular = granular::Engine::new(table, SAMPLE_RATE as f32);
+        granular.set_params(granular::Params {
+            // the patterns start the voices
+            trigger_interval: None,
+            grain_interval: GRAIN_INTERVAL,
+            voice_shape: Shape::trapezoid(0.05, 0.6),
+            ..granular::Params::default()
+        });
+        Self {
+            params,
+            song,
+            channels: (0..CHANNELS)
+                .map(|i| Channel::new(i, SAMPLE_RATE as u32))
+                .collect(),
+            oscillators: oscillators::new(),
+            blocks: [[0.0; BLOCK]; CHANNELS],
+            granular,
+            grains: [0.0; BLOCK * 2],
+            playing: false,
+            slot: 0,
+            row: 0,
+            tick: 0,
+            until_tick: 0.0,
+            tempo: None,
+            jump: None,
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    fn play(&mut self, slot: usize) {
+        self.stop();
+        self.playing = true;
+        self.slot = slot.min(self.song.order.len() - 1);
+        self.row = 0;
+        self.tick = 0;
+        self.until_tick = 0.0;
+        self.tempo = None;
+        self.jump = None;
+        for channel in self.channels.iter_mut() {
+            channel.reset();
+        }
+    }
+
+    fn stop(&mut self) {
+        self.playing = false;
+        for channel in self.channels.iter_mut() {
+            channel.sine.gate_off();
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.granular.set_sample_rate(sample_rate as f32);
+        for channel in self.channels.iter_mut() {
+            let cutoff = channel.filter.cutoff().min(sample_rate as f32 * 0.45);
+            channel.filter = Biquad::highpass(cutoff, sample_rate as f32);
+        }
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Cell {
+                pattern,
+                row,
+                channel,
+                cell,
+            } => self.song.patterns[pattern][row][channel] = cell,
+            Command::Order(order) => {
+                self.song.order = order;
+                self.slot = self.slot.min(order.len() - 1);
+            }
+            Command::Play { slot } => self.play(slot),
+            Command::Stop => self.stop(),
+            Command::Preview { channel, cell } => {
+                if let Some(instrument) = cell.instrument {
+                    self.channels[channel].instrument = instrument;
+                }
+                if let Some(Note::Key(key)) = cell.note {
+                    self.note_on(channel, key);
+                    // nothing comes along to let go of it
+                    self.channels[channel].sine.set_shape(SINE_SHAPE.one_shot());
+                }
+            }
+        }
+    }
+
+    fn note_on(&mut self, index: usize, key: u8) {
+        let rate = 1.0 / self.sample_rate as f32;
+        let grain_length = self.params.get(GRAIN_LENGTH);
+        let channel = &mut self.channels[index];
+        channel.key = key;
+        if channel.instrument != SINE {
+            channel.sine.gate_off();
+        }
+        match channel.instrument {
+            SINE => {
+                self.oscillators
+                    .set_freq(index, midi_to_freq(f32::from(key)));
+                channel.sine.set_shape(SINE_SHAPE);
+                channel.sine.gate_on(rate);
+            }
+            HAT => {
+                let cutoff = midi_to_freq(f32::from(key) + HAT_OFFSET);
+                channel
+                    .filter
+                    .set_cutoff(cutoff.min(self.sample_rate as f32 * 0.45));
+                channel.hat.gate_on(rate);
+            }
+            _ => {
+                self.granular.play(f32::from(key), grain_length);
+            }
+        }
+    }
+
+    /// the row's cells, on the first tick of it
+    fn play_row(&mut self) {
+        let pattern = self.song.order.pattern(self.slot);
+        let row = self.song.patterns[pattern][self.row];
+        for (index, cell) in row.iter().enumerate() {
+            let channel = &mut self.channels[index];
+            if let Some(instrument) = cell.instrument {
+                channel.instrument = instrument;
+            }
+            channel.arpeggio = (0, 0);
+            match cell.effect() {
+                Some(Effect::Arpeggio(x, y)) => channel.arpeggio = (x, y),
+                Some(Effect::Pan(pan)) => channel.pan = pan,
+                Some(Effect::Volume(volume)) => channel.volume = volume,
+                Some(Effect::Break(row)) => self.jump = Some(row),
+                Some(Effect::Tempo(bpm)) => self.tempo = Some(bpm),
+                None => {}
+            }
+            match cell.note {
+                Some(Note::Key(key)) => self.note_on(index, key),
+                Some(Note::Off) => self.channels[index].sine.gate_off(),
+                None => {}
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.tick == 0 {
+            self.play_row();
+        }
+        for (index, channel) in self.channels.iter().enumerate() {
+            if channel.instrument != SINE || channel.arpeggio == (0, 0) {
+                continue;
+            }
+            let (x, y) = channel.arpeggio;
+            let offset = [0, x, y][self.tick % 3];
+            self.oscillators
+                .set_freq(index, midi_to_freq(f32::from(channel.key + offset)));
+        }
+        self.tick += 1;
+        if self.tick == TICKS {
+            self.tick = 0;
+            let next = match self.jump.take() {
+                Some(row) => {
+                    self.slot += 1;
+                    row
+                }
+                None if self.row + 1 == ROWS => {
+                    self.slot += 1;
+                    0
+                }
+                None => self.row + 1,
+            };
+            self.row = next;
+            self.slot %= self.song.order.len();
+        }
+    }
+
+    /// plays the ticks due in the next `frames`, all at the start of them
+    fn advance(&mut self, frames: usize) {
+        if !self.playing {
+            return;
+        }
+        let bpm = self.tempo.unwrap_or_else(|| self.params.get(TEMPO));
+        let tick = self.sample_rate as f64 * 60.0 / (bpm * ROWS_PER_BEAT) as f64 / TICKS as f64;
+        while self.until_tick < frames as f64 {
+            self.tick();
+            self.until_tick += tick;
+        }
+        self.until_tick -= frames as f64;
+    }
+
+    fn state(&self) -> State {
+        let grains = self
+            .granular
+            .voices()
+            .iter()
+            .map(granular::Voice::level)
+            .fold(0.0, f32::max);
+        let mut levels = [0.0; CHANNELS];
+        for (level, channel) in levels.iter_mut().zip(self.channels.iter()) {
+            let playing = match channel.instrument {
+                SINE => channel.sine.value(),
+                HAT => channel.hat.value(),
+                _ => grains,
+            };
+            *level = playing * channel.volume;
+        }
+        State {
+            playing: self.playing,
+            slot: self.slot,
+            row: self.row,
+            levels,
+        }
+    }
+
+    fn render(&mut self, out: &mut [f32], channels: usize) {
+        let volume = self.params.get(VOLUME);
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let frames = chunk.len() / channels;
+            self.advance(frames);
+            self.oscillators
+                .render(&mut self.blocks, frames, self.sample_rate);
+            let grains = &mut self.grains[..frames * 2];
+            grains.iter_mut().for_each(|sample| *sample = 0.0);
+            self.granular.process(grains, 2);
+
+            for (i, frame) in chunk.chunks_exact_mut(channels).enumerate() {
+                let mut pair = (grains[i * 2] * GRAINS_GAIN, grains[i * 2 + 1] * GRAINS_GAIN);
+                for (channel, block) in self.channels.iter_mut().zip(self.blocks.iter()) {
+                    let mut sample = block[i] * channel.sine.step() * SINE_GAIN;
+                    if channel.hat.is_active() {
+                        let noise = channel.filter.process(channel.noise.sample());
+                        sample += noise * channel.hat.step() * HAT_GAIN;
+                    }
+                    let (left, right) = pan::equal_power(sample * channel.volume, channel.pan);
+                    pair.0 += left;
+                    pair.1 += right;
+                }
+                match frame {
+                    [mono] => *mono = (pair.0 + pair.1) * volume,
+                    [l, r, rest @ ..] => {
+                        *l = pair.0 * volume;
+                        *r = pair.1 * volume;
+                        for out in rest.iter_mut() {
+                            *out = 0.0;
+                        }
+                    }
+                    [] => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("tracker");
+    match &args.render {
+        Some(request) => tracker::render(request),
+        None if args.headless => tracker::headless(),
+        None => tracker::run(),
+    }
+}
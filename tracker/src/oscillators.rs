@@ -0,0 +1,155 @@
+//! The sines the first instrument plays, one per channel, as a rume graph
+//! like lissa's or, without the `audio` feature, wavetable sines.
+
+use crate::dsp::BLOCK;
+use crate::pattern::CHANNELS;
+#[cfg(not(feature = "audio"))]
+use dsp_common::Wavetable;
+#[cfg(feature = "audio")]
+use rume::{Processor, Renderable};
+
+/// Each channel's raw sine, the envelopes and mix are applied on top.
+pub trait Oscillators: Send {
+    /// from the next `render` on
+    fn set_freq(&mut self, channel: usize, freq: f32);
+
+    /// the first `frames` samples of each channel's block, `frames` at most
+    /// `BLOCK`
+    fn render(&mut self, out: &mut [[f32; BLOCK]; CHANNELS], frames: usize, sample_rate: u32);
+}
+
+/// the oscillators this build has
+pub fn new() -> Box<dyn Oscillators> {
+    #[cfg(feature = "audio")]
+    {
+        Box::new(Graph::new())
+    }
+    #[cfg(not(feature = "audio"))]
+    {
+        Box::new(Sines::new())
+    }
+}
+
+#[cfg(feature = "audio")]
+struct Graph {
+    graph: rume::SignalChain,
+    freqs: Vec<rume::InputStreamProducer>,
+    /// the last frequency sent to each channel, only changes are queued
+    sent: [f32; CHANNELS],
+    outputs: Vec<rume::OutputStreamConsumer>,
+}
+
+#[cfg(feature = "audio")]
+impl Graph {
+    fn new() -> Self {
+        let (freq_0_prod, freq_0_con) = rume::input!(FREQ_0_ENDPOINT);
+        let (freq_1_prod, freq_1_con) = rume::input!(FREQ_1_ENDPOINT);
+        let (freq_2_prod, freq_2_con) = rume::input!(FREQ_2_ENDPOINT);
+        let (freq_3_prod, freq_3_con) = rume::input!(FREQ_3_ENDPOINT);
+        let (out_0_prod, out_0_con) = rume::output!(OUT_0_ENDPOINT);
+        let (out_1_prod, out_1_con) = rume::output!(OUT_1_ENDPOINT);
+        let (out_2_prod, out_2_con) = rume::output!(OUT_2_ENDPOINT);
+        let (out_3_prod, out_3_con) = rume::output!(OUT_3_ENDPOINT);
+
+        let graph = rume::graph! {
+            endpoints: {
+                freq_0: rume::InputEndpoint::new(freq_0_con),
+                freq_1: rume::InputEndpoint::new(freq_1_con),
+                freq_2: rume::InputEndpoint::new(freq_2_con),
+                freq_3: rume::InputEndpoint::new(freq_3_con),
+                out_0: rume::OutputEndpoint::new(out_0_prod),
+                out_1: rume::OutputEndpoint::new(out_1_prod),
+                out_2: rume::OutputEndpoint::new(out_2_prod),
+                out_3: rume::OutputEndpoint::new(out_3_prod),
+            },
+            processors: {
+                sine_0: rume::Sine::default(),
+                sine_1: rume::Sine::default(),
+                sine_2: rume::Sine::default(),
+                sine_3: rume::Sine::default(),
+                // full scale, the envelopes set each channel's level
+                amp: rume::Value::new(1.0),
+            },
+            connections: {
+                freq_0.output   -> sine_0.input.0,
+                freq_1.output   -> sine_1.input.0,
+                freq_2.output   -> sine_2.input.0,
+                freq_3.output   -> sine_3.input.0,
+                amp.output      -> sine_0.input.1,
+                amp.output      -> sine_1.input.1,
+                amp.output      -> sine_2.input.1,
+                amp.output      -> sine_3.input.1,
+                sine_0.output   -> out_0.input,
+                sine_1.output   -> out_1.input,
+                sine_2.output   -> out_2.input,
+                sine_3.output   -> out_3.input,
+            }
+        };
+
+        Self {
+            graph,
+            freqs: vec![freq_0_prod, freq_1_prod, freq_2_prod, freq_3_prod],
+            sent: [0.0; CHANNELS],
+            outputs: vec![],
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Oscillators for Graph {
+    fn set_freq(&mut self, channel: usize, freq: f32) {
+        if self.sent[channel] != freq {
+            self.sent[channel] = freq;
+            self.freqs[channel].enqueue(freq).unwrap();
+        }
+    }
+
+    fn render(&mut self, out: &mut [[f32; BLOCK]; CHANNELS], frames: usize, sample_rate: u32) {
+        self.graph.prepare(sample_rate.into());
+        self.graph.render(frames);
+        for (block, output) in out.iter_mut().zip(self.outputs.iter_mut()) {
+            for sample in block[..frames].iter_mut() {
+                *sample = output.dequeue().unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+const TABLE_SIZE: usize = 4096;
+
+#[cfg(not(feature = "audio"))]
+struct Sines {
+    sine: Wavetable,
+    freqs: [f32; CHANNELS],
+    phases: [f32; CHANNELS],
+}
+
+#[cfg(not(feature = "audio"))]
+impl Sines {
+    fn new() -> Self {
+        Self {
+            sine: Wavetable::sine(TABLE_SIZE),
+            freqs: [0.0; CHANNELS],
+            phases: [0.0; CHANNELS],
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl Oscillators for Sines {
+    fn set_freq(&mut self, channel: usize, freq: f32) {
+        self.freqs[channel] = freq;
+    }
+
+    fn render(&mut self, out: &mut [[f32; BLOCK]; CHANNELS], frames: usize, sample_rate: u32) {
+        for (channel, block) in out.iter_mut().enumerate() {
+            let increment = self.freqs[channel] / sample_rate as f32;
+            let phase = &mut self.phases[channel];
+            for sample in block[..frames].iter_mut() {
+                *sample = self.sine.at(*phase);
+                *phase = (*phase + increment).fract();
+            }
+        }
+    }
+}
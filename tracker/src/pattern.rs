@@ -0,0 +1,350 @@
+//! Patterns of notes and effects, the order they're chained in, and the
+//! text they're saved as.
+//!
+//! A song is saved as an `order` line then each pattern with anything in
+//! it, a line per row and a cell per channel between bars, the way it's
+//! drawn:
+//!
+//! ```text
+//! order 0 0 1
+//! pattern 0
+//! C-3 0 C30 | --- . ... | ...
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const CHANNELS: usize = 4;
+pub const ROWS: usize = 64;
+pub const PATTERNS: usize = 16;
+/// slots in the order, patterns played one after the other
+pub const SLOTS: usize = 64;
+/// the sines, the hat then the grains
+pub const INSTRUMENTS: u8 = 3;
+
+const NAMES: [&str; 12] = [
+    "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Note {
+    /// a midi key, C-0 is 12 and B-9 the highest written
+    Key(u8),
+    /// lets go of what the channel is playing
+    Off,
+}
+
+impl Note {
+    /// C-0, the lowest key a note column can hold
+    pub const LOWEST: u8 = 12;
+    /// B-9
+    pub const HIGHEST: u8 = 131;
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Note::Key(key) => write!(
+                f,
+                "{}{}",
+                NAMES[key as usize % 12],
+                (key / 12).saturating_sub(1)
+            ),
+            Note::Off => write!(f, "==="),
+        }
+    }
+}
+
+impl FromStr for Note {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "===" {
+            return Ok(Note::Off);
+        }
+        let bad = || format!("{:?} is not a note", s);
+        let (name, octave) = (s.get(..2).ok_or_else(bad)?, s.get(2..).ok_or_else(bad)?);
+        let pitch = NAMES.iter().position(|n| *n == name).ok_or_else(bad)?;
+        let octave: u8 = octave.parse().map_err(|_| bad())?;
+        if octave > 9 {
+            return Err(bad());
+        }
+        Ok(Note::Key((octave + 1) * 12 + pitch as u8))
+    }
+}
+
+/// What an effect column asks for, after the three hex digits of the
+/// command and its value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// `0xy`, the note then `x` then `y` semitones above it, a tick each
+    Arpeggio(u8, u8),
+    /// `8xx`, 00 hard left, 80 the centre and FF hard right
+    Pan(f32),
+    /// `Cxx`, 00 silent to 40 full
+    Volume(f32),
+    /// `Dxx`, on to the next slot at row `xx` after this row
+    Break(usize),
+    /// `Fxx`, beats a minute from 20 up, until the song stops
+    Tempo(f32),
+}
+
+/// One channel's row.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub note: Option<Note>,
+    /// keeps whatever the channel played last when empty
+    pub instrument: Option<u8>,
+    /// three hex digits, the command then its value, 0 for none
+    pub effect: u16,
+}
+
+impl Cell {
+    pub fn is_empty(&self) -> bool {
+        *self == Cell::default()
+    }
+
+    pub fn effect(&self) -> Option<Effect> {
+        let value = (self.effect & 0xff) as u8;
+        match self.effect >> 8 {
+            0 if value != 0 => Some(Effect::Arpeggio(value >> 4, value & 0xf)),
+            0x8 => Some(Effect::Pan(f32::from(value) / 255.0)),
+            0xc => Some(Effect::Volume(f32::from(value.min(0x40)) / 64.0)),
+            0xd => Some(Effect::Break(usize::from(value) % ROWS)),
+            0xf if value >= 0x20 => Some(Effect::Tempo(f32::from(value))),
+            _ => None,
+        }
+    }
+
+    /// the note, instrument and effect columns as drawn and saved
+    pub fn columns(&self) -> [String; 3] {
+        [
+            self.note
+                .map_or_else(|| "---".to_string(), |note| note.to_string()),
+            self.instrument
+                .map_or_else(|| ".".to_string(), |i| format!("{:X}", i)),
+            match self.effect {
+                0 => "...".to_string(),
+                effect => format!("{:03X}", effect),
+            },
+        ]
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [note, instrument, effect] = self.columns();
+        write!(f, "{} {} {}", note, instrument, effect)
+    }
+}
+
+impl FromStr for Cell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = s.split_whitespace();
+        let mut next = || {
+            columns
+                .next()
+                .ok_or_else(|| format!("{:?} is not a cell", s))
+        };
+        let note = match next()? {
+            "---" => None,
+            note => Some(note.parse()?),
+        };
+        let instrument = match next()? {
+            "." => None,
+            i => match u8::from_str_radix(i, 16) {
+                Ok(i) if i < INSTRUMENTS => Some(i),
+                _ => return Err(format!("{:?} is not an instrument", i)),
+            },
+        };
+        let effect = match next()? {
+            "..." => 0,
+            e => match u16::from_str_radix(e, 16) {
+                Ok(effect) if e.len() == 3 => effect,
+                _ => return Err(format!("{:?} is not an effect", e)),
+            },
+        };
+        Ok(Self {
+            note,
+            instrument,
+            effect,
+        })
+    }
+}
+
+pub type Pattern = [[Cell; CHANNELS]; ROWS];
+
+/// Which pattern each slot plays, at least one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Order {
+    slots: [u8; SLOTS],
+    len: usize,
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Self {
+            slots: [0; SLOTS],
+            len: 1,
+        }
+    }
+}
+
+impl Order {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// never, there's always a slot to play
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn slots(&self) -> &[u8] {
+        &self.slots[..self.len]
+    }
+
+    /// the pattern `slot` plays
+    pub fn pattern(&self, slot: usize) -> usize {
+        usize::from(self.slots[slot.min(self.len - 1)])
+    }
+
+    pub fn set_pattern(&mut self, slot: usize, pattern: usize) {
+        if slot < self.len {
+            self.slots[slot] = pattern.min(PATTERNS - 1) as u8;
+        }
+    }
+
+    /// a slot after `slot` playing the same pattern, false when full
+    pub fn insert(&mut self, slot: usize) -> bool {
+        if self.len == SLOTS || slot >= self.len {
+            return false;
+        }
+        self.slots.copy_within(slot..self.len, slot + 1);
+        self.len += 1;
+        true
+    }
+
+    /// false if it's the only one
+    pub fn remove(&mut self, slot: usize) -> bool {
+        if self.len == 1 || slot >= self.len {
+            return false;
+        }
+        self.slots.copy_within(slot + 1..self.len, slot);
+        self.len -= 1;
+        true
+    }
+
+    fn push(&mut self, pattern: usize) -> Result<(), String> {
+        if pattern >= PATTERNS {
+            return Err(format!("there is no pattern {}", pattern));
+        }
+        if self.len == SLOTS {
+            return Err(format!("more than {} slots", SLOTS));
+        }
+        self.slots[self.len] = pattern as u8;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+/// Every pattern and the order they play in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Song {
+    pub patterns: [Pattern; PATTERNS],
+    pub order: Order,
+}
+
+impl Default for Song {
+    fn default() -> Self {
+        Self {
+            patterns: [[[Cell::default(); CHANNELS]; ROWS]; PATTERNS],
+            order: Order::default(),
+        }
+    }
+}
+
+impl Song {
+    /// from the text `fmt::Display` writes, saying which line is wrong
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut song = Song::default();
+        let mut order = None;
+        let mut pattern = None;
+        let mut row = 0;
+        for (number, line) in text.lines().enumerate() {
+            let at = |e: String| format!("line {}: {}", number + 1, e);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(slots) = line.strip_prefix("order") {
+                let mut parsed = Order {
+                    len: 0,
+                    ..Order::default()
+                };
+                for slot in slots.split_whitespace() {
+                    let slot = slot
+                        .parse()
+                        .map_err(|_| at(format!("{:?} is not a pattern", slot)))?;
+                    parsed.push(slot).map_err(at)?;
+                }
+                order = Some(parsed);
+            } else if let Some(index) = line.strip_prefix("pattern") {
+                let index = index.trim();
+                match index.parse::<usize>() {
+                    Ok(index) if index < PATTERNS => pattern = Some(index),
+                    _ => return Err(at(format!("there is no pattern {}", index))),
+                }
+                row = 0;
+            } else {
+                let pattern = pattern.ok_or_else(|| at("a row before any pattern".to_string()))?;
+                if row == ROWS {
+                    return Err(at(format!("more than {} rows", ROWS)));
+                }
+                let cells: Vec<&str> = line.split('|').collect();
+                if cells.len() != CHANNELS {
+                    return Err(at(format!("{} channels, not {}", cells.len(), CHANNELS)));
+                }
+                for (channel, cell) in cells.iter().enumerate() {
+                    song.patterns[pattern][row][channel] = cell.parse().map_err(at)?;
+                }
+                row += 1;
+            }
+        }
+        song.order = match order {
+            Some(order) if order.len > 0 => order,
+            _ => return Err("no order to play the patterns in".to_string()),
+        };
+        Ok(song)
+    }
+
+    fn is_used(&self, pattern: usize) -> bool {
+        self.patterns[pattern]
+            .iter()
+            .flatten()
+            .any(|cell| !cell.is_empty())
+    }
+}
+
+impl fmt::Display for Song {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "order")?;
+        for slot in self.order.slots() {
+            write!(f, " {}", slot)?;
+        }
+        writeln!(f)?;
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            if !self.is_used(i) {
+                continue;
+            }
+            writeln!(f, "pattern {}", i)?;
+            for row in pattern.iter() {
+                let cells: Vec<String> = row.iter().map(Cell::to_string).collect();
+                writeln!(f, "{}", cells.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+}
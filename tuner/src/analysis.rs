@@ -0,0 +1,102 @@
+//! From mic samples to the nearest note and how far off it is.
+//!
+//! The input is kept as a rolling window as long as YIN needs for the
+//! lowest string, the pitch found in it is then placed against the notes
+//! of the chosen temperament rather than equal temperament, so an in-tune
+//! reading means in tune for that tuning.
+
+use dsp_common::pitch::{Pitch, Yin};
+use dsp_common::tuning::{Temperament, A4_MIDI};
+
+/// below a bass guitar's low E
+pub const MIN_FREQ: f32 = 40.0;
+/// around the top of a flute
+pub const MAX_FREQ: f32 = 2000.0;
+/// quieter than this is room noise, not something to tune
+const GATE: f32 = 0.003;
+
+/// Which notes count as in tune.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    /// A4 in Hz
+    pub reference: f32,
+    pub temperament: Temperament,
+    /// pitch class of the temperament's tonic, 0 is C
+    pub root: i32,
+}
+
+impl Tuning {
+    pub fn freq(&self, note: i32) -> f32 {
+        self.temperament.freq(note, self.root, self.reference)
+    }
+
+    /// the note `pitch` is closest to and how far from it
+    pub fn read(&self, pitch: Pitch) -> Reading {
+        let guess = (A4_MIDI + 12.0 * (pitch.freq / self.reference).log2()).round() as i32;
+        // a temperament can move a note far enough for a neighbour to be closer
+        let (note, cents) = (guess - 1..=guess + 1)
+            .map(|note| (note, 1200.0 * (pitch.freq / self.freq(note)).log2()))
+            .min_by(|(_, a), (_, b)| a.abs().partial_cmp(&b.abs()).unwrap())
+            .unwrap_or((guess, 0.0));
+        Reading {
+            freq: pitch.freq,
+            note,
+            cents,
+            clarity: pitch.clarity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    pub freq: f32,
+    /// MIDI note number
+    pub note: i32,
+    /// sharp when positive
+    pub cents: f32,
+    pub clarity: f32,
+}
+
+/// Keeps the newest input and finds its pitch.
+pub struct Analyzer {
+    yin: Yin,
+    window: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Analyzer {
+    pub fn new(sample_rate: u32) -> Self {
+        let yin = Yin::new(MIN_FREQ, MAX_FREQ, sample_rate as f32);
+        Self {
+            window: Vec::with_capacity(2 * yin.len()),
+            yin,
+            sample_rate,
+        }
+    }
+
+    /// starts over when the input changed rate
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            *self = Self::new(sample_rate);
+        }
+    }
+
+    /// mono samples, oldest first
+    pub fn push(&mut self, samples: &[f32]) {
+        self.window.extend_from_slice(samples);
+        let len = self.yin.len();
+        if self.window.len() > len {
+            self.window.drain(..self.window.len() - len);
+        }
+    }
+
+    /// `None` while quiet or unpitched
+    pub fn detect(&mut self) -> Option<Pitch> {
+        let len = self.window.len().max(1) as f32;
+        let rms = (self.window.iter().map(|s| s * s).sum::<f32>() / len).sqrt();
+        if rms < GATE {
+            return None;
+        }
+        self.yin.detect(&self.window, self.sample_rate as f32)
+    }
+}
@@ -0,0 +1,480 @@
+use crate::analysis::{Analyzer, Reading, Tuning};
+use crate::dsp::{self, Command, Engine};
+use crate::strobe::Strobe;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::input::{self, Input, InputConfig, InputReader};
+use app_common::kiosk::{self, Kiosk};
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::Request;
+use app_common::serial;
+use app_common::shell::{self, SetupKey, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme::{self, Themed};
+use dsp_common::tuning::{note_name, Temperament, A4_MIDI, NOTE_NAMES};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::time::Duration;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// the reference tone on or off
+const TONE: Key = Key::Space;
+
+/// mono frames read from the input at a time
+const READ_FRAMES: usize = 1024;
+/// how often the headless tuner listens
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// the readout holds the last note this long after the sound stops
+const HOLD: f32 = 0.5;
+/// of the way to each new reading the cents readout moves, the strobe
+/// itself is never smoothed
+const SMOOTHING: f32 = 0.25;
+/// of the window's shorter side
+const SCALE: f32 = 0.4;
+
+widget_ids! {
+    struct Ids {
+        tone,
+    }
+}
+
+fn engine(config: &Config, params: &Params) -> (Engine, UiEnd<Command, ()>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 1);
+    let mut engine = Engine::new(audio_bus, params.clone());
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("tuner", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the first channel of the configured input device
+fn open_input(config: &Config) -> Result<(Input, InputReader), input::Error> {
+    Input::start(&InputConfig {
+        host: config.audio_host.clone(),
+        device: config.input_device.clone(),
+        channels: vec![0],
+        ..InputConfig::default()
+    })
+}
+
+fn tuning(params: &Params) -> Tuning {
+    let temperament = params.get(dsp::TEMPERAMENT).round() as usize;
+    Tuning {
+        reference: params.get(dsp::REFERENCE),
+        temperament: Temperament::ALL[temperament.min(Temperament::ALL.len() - 1)],
+        root: params.get(dsp::ROOT).round() as i32,
+    }
+}
+
+/// the reference A without a window or audio device
+pub fn render(request: &Request) {
+    let (config, _) = shell::load_config("tuner", &config::path("tuner"));
+    let params = shell::load_params("tuner", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params);
+    engine.play(tuning(&params).freq(A4_MIDI as i32));
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// a line on stdout whenever the note or the cents off change, no tone
+pub fn headless() {
+    let (config, _) = shell::load_config("tuner", &config::path("tuner"));
+    let params = shell::load_params("tuner", &config, &dsp::PARAMS);
+    let (input, mut reader) = match open_input(&config) {
+        Ok(input) => input,
+        Err(e) => startup::fatal("tuner", startup::Error::Input(e.to_string())),
+    };
+    println!("tuner: listening on {}", input.device());
+    let mut analyzer = Analyzer::new(reader.sample_rate());
+    let mut buffer = vec![0.0; READ_FRAMES];
+    let mut shown = None;
+    loop {
+        analyzer.set_sample_rate(reader.sample_rate());
+        loop {
+            let frames = reader.read(&mut buffer);
+            if frames == 0 {
+                break;
+            }
+            analyzer.push(&buffer[..frames]);
+        }
+        let reading = analyzer.detect().map(|pitch| tuning(&params).read(pitch));
+        let line = reading.map(|r| (r.note, r.cents.round() as i32));
+        if line != shown {
+            if let Some(r) = reading {
+                println!(
+                    "{:<4} {:+5.1} cents {:8.2} Hz",
+                    note_name(r.note),
+                    r.cents,
+                    r.freq
+                );
+            }
+            shown = line;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    bus: UiEnd<Command, ()>,
+    /// dropping it stops listening
+    input: Option<Input>,
+    reader: Option<InputReader>,
+    buffer: Vec<f32>,
+    analyzer: Analyzer,
+    /// the last note heard, held for `HOLD`
+    reading: Option<Reading>,
+    /// the readout's cents, smoothed
+    cents: f32,
+    /// seconds since anything was heard
+    silence: f32,
+    strobe: Strobe,
+    /// the note the reference tone plays, and the frequency last sent
+    tone: Option<(i32, f32)>,
+    stream: Supervisor<Engine>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("tuner");
+    let (config, _) = shell::load_config("tuner", &config_path);
+    config.build_window(app, view);
+    let params = shell::load_params("tuner", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("tuner", &config, &params);
+    let serial = serial::Sensors::from_config("tuner", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("tuner", startup::Error::Ui(format!("{:?}", e))));
+    let (engine, bus) = engine(&config, &params);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let mut errors: Vec<startup::Error> =
+        stream.rebuild().err().map(Into::into).into_iter().collect();
+    let (input, reader) = match open_input(&config) {
+        Ok((input, reader)) => (Some(input), Some(reader)),
+        Err(e) => {
+            errors.push(startup::Error::Input(e.to_string()));
+            (None, None)
+        }
+    };
+    let analyzer = Analyzer::new(
+        reader
+            .as_ref()
+            .map_or(dsp::SAMPLE_RATE as u32, |r| r.sample_rate()),
+    );
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "tuner", &config, stream.stats());
+    let shell =
+        Shell::new("tuner", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        bus,
+        input,
+        reader,
+        buffer: vec![0.0; READ_FRAMES],
+        analyzer,
+        reading: None,
+        cents: 0.0,
+        silence: 0.0,
+        strobe: Strobe::default(),
+        tone: None,
+        stream,
+        errors: ErrorScreen::new(errors),
+        hud,
+        kiosk,
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+/// listens on the input the config names now, keeping the old one if the
+/// new one won't open
+fn reopen_input(model: &mut Model, config: &Config) {
+    match open_input(config) {
+        Ok((input, reader)) => {
+            model.input = Some(input);
+            model.reader = Some(reader);
+        }
+        Err(e) => eprintln!("tuner: {}", e),
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if setup_key_pressed(model, key) {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        if key == TONE {
+            toggle_tone(model);
+        }
+        model.hud.key_pressed(key);
+        model.shell.key_pressed(app, key, &model.stream, None);
+    }
+}
+
+/// plays the note last heard, or A when nothing has been, until toggled
+/// off, it doesn't follow the input or it would end up tuning to itself
+fn toggle_tone(model: &mut Model) {
+    model.tone = match model.tone {
+        Some(_) => {
+            let _ = model.bus.send(Command::Tone(None));
+            None
+        }
+        None => {
+            let note = model.reading.map_or(A4_MIDI as i32, |r| r.note);
+            // sent by `update`
+            Some((note, 0.0))
+        }
+    };
+}
+
+/// true while the setup screen takes the keys
+fn setup_key_pressed(model: &mut Model, key: Key) -> bool {
+    let outcome = model.shell.setup_key_pressed(key, &mut model.stream);
+    if let SetupKey::Applied(previous) = &outcome {
+        if previous.input_device != model.shell.config.input_device || model.input.is_none() {
+            let config = model.shell.config.clone();
+            reopen_input(model, &config);
+        }
+    }
+    outcome.taken()
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// drains the input and moves the readout and the strobe on by `dt`
+fn listen(model: &mut Model, dt: f32) {
+    let tuning = tuning(&model.params);
+    let mut heard = None;
+    if let Some(reader) = &mut model.reader {
+        model.analyzer.set_sample_rate(reader.sample_rate());
+        loop {
+            let frames = reader.read(&mut model.buffer);
+            if frames == 0 {
+                break;
+            }
+            model.analyzer.push(&model.buffer[..frames]);
+        }
+        heard = model.analyzer.detect().map(|pitch| tuning.read(pitch));
+    }
+    match heard {
+        Some(reading) => {
+            // a new note jumps straight to its cents
+            let same = model.reading.map_or(false, |r| r.note == reading.note);
+            model.cents = if same {
+                model.cents + (reading.cents - model.cents) * SMOOTHING
+            } else {
+                reading.cents
+            };
+            model.reading = Some(reading);
+            model.silence = 0.0;
+        }
+        None => {
+            model.silence += dt;
+            if model.silence > HOLD {
+                model.reading = None;
+            }
+        }
+    }
+    model.strobe.advance(heard.map(|r| r.cents), dt);
+
+    // follows the reference and temperament as they are changed
+    if let Some((note, sent)) = &mut model.tone {
+        let freq = tuning.freq(*note);
+        if freq != *sent && model.bus.send(Command::Tone(Some(freq))).is_ok() {
+            *sent = freq;
+        }
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    listen(model, update.since_last.as_secs_f32());
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.input_device != model.shell.config.input_device
+            || config.audio_host != model.shell.config.audio_host
+        {
+            reopen_input(model, &config);
+        }
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+
+    let playing = model.tone.is_some();
+    let label = match model.tone {
+        Some((note, _)) => format!("tone: {}", note_name(note)),
+        None => String::from("tone"),
+    };
+    let mut toggled = false;
+    for _value in widget::Toggle::new(playing)
+        .w_h(200.0, 30.0)
+        .down(20.0)
+        .label(&label)
+        .label_font_size(15)
+        .themed(palette)
+        .border(0.0)
+        .set(model.ids.tone, ui)
+    {
+        toggled = true;
+    }
+    if toggled {
+        toggle_tone(model);
+    }
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let rect = app.window_rect();
+    let radius = rect.w().min(rect.h()) * SCALE;
+    let alpha = if model.reading.is_some() { 1.0 } else { 0.25 };
+    model
+        .strobe
+        .draw(draw, pt2(0.0, 0.0), radius, palette.accent(0), alpha);
+
+    let text = theme::color(palette.line);
+    let (note, detail) = match model.reading {
+        Some(reading) => (
+            note_name(reading.note),
+            format!("{:+.1} cents  {:.2} Hz", model.cents, reading.freq),
+        ),
+        None => (String::from("-"), String::new()),
+    };
+    draw.text(&note)
+        .x_y(0.0, 10.0)
+        .w_h(radius, 60.0)
+        .font_size(48)
+        .color(text);
+    draw.text(&detail)
+        .x_y(0.0, -30.0)
+        .w_h(radius, 20.0)
+        .font_size(14)
+        .color(text);
+
+    let tuning = tuning(&model.params);
+    let caption = format!(
+        "{} on {}, A4 = {:.1} Hz",
+        tuning.temperament.name,
+        NOTE_NAMES[tuning.root.rem_euclid(12) as usize],
+        tuning.reference
+    );
+    draw.text(&caption)
+        .x_y(0.0, rect.bottom() + 20.0)
+        .w_h(rect.w(), 20.0)
+        .font_size(14)
+        .color(text);
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
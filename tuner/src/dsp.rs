@@ -0,0 +1,106 @@
+use app_common::bus::AudioEnd;
+use app_common::param::{ParamSpec, Params};
+use app_common::render::Render;
+use dsp_common::limiter::Limiter;
+use dsp_common::Wavetable;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+/// seconds for the tone to fade in or out, so switching it isn't a click
+const FADE: f32 = 0.02;
+const TABLE_SIZE: usize = 1024;
+
+pub const REFERENCE: usize = 0;
+pub const TEMPERAMENT: usize = 1;
+pub const ROOT: usize = 2;
+pub const TONE: usize = 3;
+
+/// what counts as in tune, then how loud the reference tone is
+pub static PARAMS: [ParamSpec; 4] = [
+    ParamSpec::new("reference", 415.0, 466.0, 440.0).unit("Hz"),
+    // `Temperament::ALL` and a pitch class from C
+    ParamSpec::new("temperament", 0.0, 4.0, 0.0),
+    ParamSpec::new("root", 0.0, 11.0, 0.0),
+    ParamSpec::new("tone", 0.0, 1.0, 0.3),
+];
+
+pub enum Command {
+    /// the note to play in Hz, `None` to fade out
+    Tone(Option<f32>),
+}
+
+/// A sine at the note being tuned to, for tuning by ear.
+pub struct Engine {
+    bus: AudioEnd<Command, ()>,
+    params: Params,
+    freq: f32,
+    playing: bool,
+    gain: f32,
+    phase: f32,
+    sine: Wavetable,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(bus: AudioEnd<Command, ()>, params: Params) -> Self {
+        Self {
+            bus,
+            params,
+            freq: 440.0,
+            playing: false,
+            gain: 0.0,
+            phase: 0.0,
+            sine: Wavetable::sine(TABLE_SIZE),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    /// for rendering without a window, where nothing sends commands
+    pub fn play(&mut self, freq: f32) {
+        self.freq = freq;
+        self.playing = true;
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        for command in self.bus.commands() {
+            match command {
+                Command::Tone(Some(freq)) => self.play(freq),
+                Command::Tone(None) => self.playing = false,
+            }
+        }
+
+        let sample_time = 1.0 / sample_rate as f32;
+        let step = self.freq * sample_time;
+        let target = if self.playing {
+            self.params.get(TONE)
+        } else {
+            0.0
+        };
+        let fade = sample_time / FADE;
+        for frame in out.chunks_exact_mut(channels) {
+            self.gain += (target - self.gain).clamp(-fade, fade);
+            let sample = self.sine.at(self.phase) * self.gain;
+            self.phase = (self.phase + step).fract();
+            for out in frame.iter_mut() {
+                *out = sample;
+            }
+        }
+        self.limiter.process_interleaved(out, channels);
+    }
+}
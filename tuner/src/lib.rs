@@ -0,0 +1,6 @@
+mod analysis;
+mod app;
+mod dsp;
+mod strobe;
+
+pub use app::{headless, render, run};
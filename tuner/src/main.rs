@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("tuner");
+    match &args.render {
+        Some(request) => tuner::render(request),
+        None if args.headless => tuner::headless(),
+        None => tuner::run(),
+    }
+}
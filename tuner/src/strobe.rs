@@ -0,0 +1,65 @@
+//! The strobe wheel.
+//!
+//! A mechanical strobe tuner spins a striped disc at the note's speed and
+//! lights it with the instrument, so the stripes stand still when the two
+//! agree and creep one way or the other when they don't. Here the creep is
+//! computed from the cents off instead: segments pass at the same rate on
+//! every ring, so the inner rings, with fewer and wider segments, turn
+//! visibly further for the same drift.
+
+use app_common::theme::Rgb;
+use nannou::prelude::*;
+
+pub const RINGS: usize = 4;
+/// on the innermost ring, each ring out has twice as many
+const SEGMENTS: usize = 8;
+/// segments passing per second for every cent off, a semitone away is
+/// a blur and a cent is a slow creep
+const SEGMENTS_PER_CENT: f32 = 0.5;
+/// of the radius, the hole in the middle holds the readout
+const HOLE: f32 = 0.4;
+/// points along each edge of a segment
+const ARC_POINTS: usize = 8;
+
+#[derive(Default)]
+pub struct Strobe {
+    /// segments passed since the start, sharp turns clockwise
+    passed: f32,
+}
+
+impl Strobe {
+    /// moves the wheel on by `dt` seconds at `cents` off, it stands still
+    /// when nothing is heard
+    pub fn advance(&mut self, cents: Option<f32>, dt: f32) {
+        if let Some(cents) = cents {
+            // wrapped at a whole turn of every ring
+            let period = (SEGMENTS << (RINGS - 1)) as f32;
+            self.passed = (self.passed + cents * SEGMENTS_PER_CENT * dt).rem_euclid(period);
+        }
+    }
+
+    /// every other segment of each ring in `color`, `alpha` dims the wheel
+    /// while it has nothing to show
+    pub fn draw(&self, draw: &Draw, center: Point2, radius: f32, color: Rgb, alpha: f32) {
+        let color = rgba(color[0], color[1], color[2], alpha);
+        let width = radius * (1.0 - HOLE) / RINGS as f32;
+        for ring in 0..RINGS {
+            let inner = radius * HOLE + ring as f32 * width;
+            let outer = inner + width * 0.85;
+            let segments = SEGMENTS << ring;
+            let size = TAU / segments as f32;
+            let turned = -self.passed * size;
+            for segment in (0..segments).step_by(2) {
+                let start = turned + segment as f32 * size;
+                let along = |r: f32, i: usize| {
+                    let angle = start + size * i as f32 / (ARC_POINTS - 1) as f32;
+                    center + vec2(angle.cos(), angle.sin()) * r
+                };
+                let points = (0..ARC_POINTS)
+                    .map(|i| along(outer, i))
+                    .chain((0..ARC_POINTS).rev().map(|i| along(inner, i)));
+                draw.polygon().points(points).color(color);
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::dsp::{self, Command, Engine, State, BEATS_PER_BAR, BPM, PITCH_BITS};
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::config::{self, Config};
+use app_common::diagnostics::Hud;
+use app_common::kiosk::{self, Kiosk};
+use app_common::link::{Link, LinkClock};
+use app_common::midi::MidiOutput;
+use app_common::oscquery;
+use app_common::param::{self, Params};
+use app_common::render::{self, Request};
+use app_common::serial;
+use app_common::shell::{self, Shell};
+use app_common::startup::{self, ErrorScreen};
+use app_common::theme;
+use app_common::timecode::Chase;
+use app_common::transport::Transport;
+use dsp_common::tuning;
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+
+const JACK_PORTS: [&str; dsp::NUM_CHANNELS] = ["left", "right"];
+
+/// the next bit in is a one, whatever the lock says
+const WRITE_ONE: Key = Key::Up;
+/// and a zero
+const WRITE_ZERO: Key = Key::Down;
+/// every bit drawn again
+const RANDOMIZE: Key = Key::N;
+const CLEAR: Key = Key::C;
+
+/// room on the left for the controls
+const CONTROLS_WIDTH: f32 = 240.0;
+const MARGIN: f32 = 20.0;
+
+widget_ids! {
+    struct Ids {
+        link,
+        transport,
+        tempo,
+    }
+}
+
+fn engine(
+    config: &Config,
+    params: &Params,
+    transport: &Transport,
+    link: Option<LinkClock>,
+    seed: u64,
+) -> (Engine, UiEnd<Command, State>) {
+    let (ui_bus, audio_bus) = bus::bus(8, 4);
+    let mut engine = Engine::new(audio_bus, params.clone(), transport.clock(link), seed);
+    engine.set_limiter_bypass(config.bypass_limiter);
+    (engine, ui_bus)
+}
+
+fn stream_config(config: &Config) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(dsp::NUM_CHANNELS),
+        jack: config.jack_client("turing", &JACK_PORTS),
+        ..StreamConfig::default()
+    })
+}
+
+/// the loop from the seed without a window or audio device
+pub fn render(request: &Request) {
+    let (config, session_seed) = shell::load_config("turing", &config::path("turing"));
+    let seed = shell::seed(session_seed);
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let params = shell::load_params("turing", &config, &dsp::PARAMS);
+    let (mut engine, _bus) = engine(&config, &params, &transport, None, seed);
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        dsp::NUM_CHANNELS,
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the loop on the audio device without a window
+pub fn headless() {
+    let (config, session_seed) = shell::load_config("turing", &config::path("turing"));
+    let seed = shell::seed(session_seed);
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let params = shell::load_params("turing", &config, &dsp::PARAMS);
+    let (engine, _bus) = engine(&config, &params, &transport, None, seed);
+    render::headless("turing", engine, stream_config(&config));
+}
+
+pub fn run() {
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    params: Params,
+    seed: u64,
+    link: Link,
+    transport: Transport,
+    /// moves the transport after show control, when `timecode` is set
+    timecode: Option<Chase>,
+    bus: UiEnd<Command, State>,
+    /// the register as of the last buffer played
+    state: State,
+    stream: Supervisor<Engine>,
+    /// the notes for an external synth when `midi_out` is set
+    midi_out: Option<MidiOutput>,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn open_midi_out(config: &Config) -> Option<MidiOutput> {
+    let output = MidiOutput::open("turing", config.midi_out.clone()?);
+    output.map_err(|e| eprintln!("turing: {}", e)).ok()
+}
+
+fn open_timecode(config: &Config) -> Option<Chase> {
+    let chase = Chase::open("turing", config.timecode.as_ref()?);
+    chase.map_err(|e| eprintln!("turing: {}", e)).ok()
+}
+
+fn model(app: &App) -> Model {
+    let config_path = config::path("turing");
+    let (config, session_seed) = shell::load_config("turing", &config_path);
+    let seed = shell::seed(session_seed);
+    config.build_window(app, view);
+    let params = shell::load_params("turing", &config, &dsp::PARAMS);
+    let oscquery = oscquery::Service::from_config("turing", &config, &params);
+    let serial = serial::Sensors::from_config("turing", &config, &params);
+
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("turing", startup::Error::Ui(format!("{:?}", e))));
+    let link = Link::new(BPM, BEATS_PER_BAR as f64);
+    let transport = Transport::new(BPM, BEATS_PER_BAR);
+    let (engine, bus) = engine(&config, &params, &transport, Some(link.clock()), seed);
+    let mut stream = Supervisor::idle(engine, stream_config(&config));
+    let errors = ErrorScreen::new(stream.rebuild().err().map(Into::into).into_iter().collect());
+    let hud = Hud::new(stream.stats());
+    let kiosk = kiosk::open(app, "turing", &config, stream.stats());
+    let shell =
+        Shell::new("turing", config, &config_path, "phosphor", stream_config).with_params(&params);
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        seed,
+        link,
+        transport,
+        bus,
+        state: State::default(),
+        stream,
+        midi_out: open_midi_out(&shell.config),
+        errors,
+        hud,
+        kiosk,
+        timecode: open_timecode(&shell.config),
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    let key = match event {
+        Event::WindowEvent {
+            simple: Some(KeyPressed(key)),
+            ..
+        } => key,
+        _ => return,
+    };
+    if model
+        .shell
+        .setup_key_pressed(key, &mut model.stream)
+        .taken()
+    {
+        return;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.key_pressed(key, &mut model.stream) {
+            model.errors = None;
+        }
+        return;
+    }
+    if model
+        .stream
+        .insert_mut()
+        .map_or(false, |insert| insert.key_pressed(key))
+    {
+        return;
+    }
+    let command = match key {
+        WRITE_ONE => Some(Command::Write(true)),
+        WRITE_ZERO => Some(Command::Write(false)),
+        RANDOMIZE => Some(Command::Randomize),
+        CLEAR => Some(Command::Clear),
+        _ => None,
+    };
+    if let Some(command) = command {
+        let _ = model.bus.send(command);
+        return;
+    }
+    model.transport.key_pressed(key);
+    model.hud.key_pressed(key);
+    model
+        .shell
+        .key_pressed(app, key, &model.stream, Some(model.seed));
+}
+
+fn exit(app: &App, mut model: Model) {
+    model.shell.exit(app, &model.stream);
+}
+
+/// a note on for each step whose bit is set, off once the voice lets go
+fn send_midi(output: &mut MidiOutput, state: &State, steps: u64) {
+    if state.steps != steps && state.gate {
+        output.play(&[state.note]);
+    } else if !state.held {
+        output.release_all();
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    if let Some(state) = model.bus.latest() {
+        if let Some(output) = &mut model.midi_out {
+            output.poll();
+            send_midi(output, &state, model.state.steps);
+        }
+        model.state = state;
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(app, model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(timecode) = &mut model.timecode {
+        timecode.poll(&model.transport);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.set_limiter_bypass(bypass));
+        }
+        config::reopen(
+            &model.shell.config.midi_out,
+            &config.midi_out,
+            &mut model.midi_out,
+            || open_midi_out(&config),
+        );
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        config::reopen(
+            &model.shell.config.timecode,
+            &config.timecode,
+            &mut model.timecode,
+            || open_timecode(&config),
+        );
+        model.shell.config = config;
+    }
+
+    let ui = &mut model.ui.set_widgets();
+    let palette = model.shell.themes.current();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+    model.link.panel(model.ids.link, palette, ui);
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+}
+
+/// `turns` of the way round clockwise from the top, `radius` out
+fn around(center: Point2, radius: f32, turns: f32) -> Point2 {
+    let angle = (0.25 - turns) * TAU;
+    center + vec2(angle.cos(), angle.sin()) * radius
+}
+
+/// everything but the UI, shared by the window and screenshots
+fn scene(app: &App, model: &Model, draw: &Draw) {
+    let palette = model.shell.themes.current();
+    draw.background().color(theme::color(palette.background));
+
+    let area = app.window_rect().pad_left(CONTROLS_WIDTH).pad(MARGIN);
+    let center = area.xy();
+    let radius = area.w().min(area.h()) * 0.35;
+    let length = dsp::length(&model.params);
+    let lock = model.params.get(dsp::LOCK);
+    let register = model.state.register;
+
+    // the newest bit at the top, each step turns the loop a notch clockwise
+    let size = (radius * PI / length as f32 * 0.4).min(radius * 0.15);
+    for i in 0..length {
+        let lit = (register >> i) & 1 == 1;
+        // the bits the pitch is read from in the first colour
+        let [r, g, b] = palette.accent(if i < PITCH_BITS { 0 } else { 1 });
+        let xy = around(center, radius, i as f32 / length as f32);
+        let glow = if i == 0 { model.state.level } else { 0.0 };
+        if lit {
+            draw.ellipse()
+                .xy(xy)
+                .radius(size * (1.0 + 0.3 * glow))
+                .color(rgba(r, g, b, 0.7 + 0.3 * glow));
+        } else {
+            draw.ellipse()
+                .xy(xy)
+                .radius(size)
+                .no_fill()
+                .stroke_weight(1.5)
+                .stroke(rgba(r, g, b, 0.4));
+        }
+    }
+
+    // the bit about to come back round, ringed more faintly the more it's
+    // locked
+    let [r, g, b] = palette.line;
+    draw.ellipse()
+        .xy(around(center, radius, (length - 1) as f32 / length as f32))
+        .radius(size * 1.5)
+        .no_fill()
+        .stroke_weight(2.0)
+        .stroke(rgba(r, g, b, 0.2 + 0.8 * (1.0 - lock)));
+
+    let note = if model.state.steps == 0 {
+        "--".to_string()
+    } else {
+        tuning::note_name(i32::from(model.state.note))
+    };
+    let scale = dsp::scale(&model.params).name;
+    draw.text(&format!("{}\n{}\nlock {:.0}%", note, scale, lock * 100.0))
+        .xy(center)
+        .font_size(16)
+        .color(rgba(r, g, b, 0.8));
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    scene(app, model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,423 @@
+use app_common::bus::AudioEnd;
+use app_common::param::{ParamSpec, Params};
+use app_common::render::Render;
+use app_common::transport::TransportClock;
+use dsp_common::env::{Envelope, Shape};
+use dsp_common::filter::Biquad;
+use dsp_common::limiter::Limiter;
+use dsp_common::random::Rng;
+use dsp_common::tuning::{midi_to_freq, Scale};
+use dsp_common::Wavetable;
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
+pub const SAMPLE_RATE: usize = 48_000;
+pub const NUM_CHANNELS: usize = 2;
+pub const BUFFER_SIZE: usize = 512;
+
+pub const BEATS_PER_BAR: u32 = 4;
+pub const BPM: f64 = 110.0;
+/// bits in the register, the longest loop
+pub const BITS: usize = 16;
+/// the lowest bits, read as a number, set the pitch
+pub const PITCH_BITS: usize = 8;
+
+pub const SCALES: [Scale; 5] = [
+    Scale::MINOR_PENTATONIC,
+    Scale::MAJOR_PENTATONIC,
+    Scale::MINOR,
+    Scale::MAJOR,
+    Scale::CHROMATIC,
+];
+
+const SHAPE: Shape = Shape::adsr(0.003, 0.2, 0.5, 0.15);
+const TABLE_SIZE: usize = 4096;
+/// the filter opens this many times the note's frequency at the start of a
+/// note and closes to the note itself
+const BRIGHTNESS: f32 = 8.0;
+const GAIN: f32 = 0.3;
+
+pub const LOCK: usize = 0;
+pub const LENGTH: usize = 1;
+pub const DIVISION: usize = 2;
+pub const RANGE: usize = 3;
+pub const ROOT: usize = 4;
+pub const SCALE: usize = 5;
+pub const GATE: usize = 6;
+pub const VOLUME: usize = 7;
+
+/// how likely a bit comes round unchanged, the loop's length in steps and
+/// how many steps a beat, then the notes they're read as, how long each
+/// one holds for a step and how loud they play
+pub static PARAMS: [ParamSpec; 8] = [
+    ParamSpec::new("lock", 0.0, 1.0, 0.8),
+    ParamSpec::new("length", 2.0, BITS as f32, 8.0).unit("steps"),
+    ParamSpec::new("division", 1.0, 4.0, 2.0),
+    ParamSpec::new("range", 1.0, 4.0, 2.0).unit("oct"),
+    ParamSpec::new("root", 36.0, 72.0, 48.0),
+    ParamSpec::new("scale", 0.0, (SCALES.len() - 1) as f32, 0.0),
+    ParamSpec::new("gate", 0.05, 1.0, 0.5),
+    ParamSpec::new("volume", 0.0, 1.0, 0.7),
+];
+
+/// the loop's length in steps
+pub fn length(params: &Params) -> usize {
+    (params.get(LENGTH).round() as usize).clamp(2, BITS)
+}
+
+pub fn scale(params: &Params) -> &'static Scale {
+    &SCALES[(params.get(SCALE).round() as usize).min(SCALES.len() - 1)]
+}
+
+/// the midi note `register` plays, its lowest bits read from none at the
+/// root to all of them `range` octaves above, then onto the scale
+pub fn note(params: &Params, register: u16) -> u8 {
+    let top = ((1 << PITCH_BITS) - 1) as f32;
+    let value = f32::from(register & ((1 << PITCH_BITS) - 1)) / top;
+    let root = params.get(ROOT).round();
+    let note = root + value * params.get(RANGE) * 12.0;
+    scale(params).quantize(root, note).round().clamp(0.0, 127.0) as u8
+}
+
+pub enum Command {
+    /// the next bit in, whatever the lock says
+    Write(bool),
+    /// every bit drawn again
+    Randomize,
+    Clear,
+}
+
+/// What the register did, published after every buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct State {
+    /// the newest bit lowest
+    pub register: u16,
+    /// steps since the start, the UI follows the changes
+    pub steps: u64,
+    /// the newest bit was set, the note plays
+    pub gate: bool,
+    pub note: u8,
+    /// the note's gate is still open
+    pub held: bool,
+    /// the voice's envelope
+    pub level: f32,
+}
+
+/// The loop of bits, the newest lowest.
+struct Register {
+    bits: u16,
+    rng: Rng,
+    /// a bit to write in place of the next one
+    write: Option<bool>,
+    steps: u64,
+}
+
+impl Register {
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Write(bit) => self.write = Some(bit),
+            Command::Randomize => self.bits = self.rng.next_u32() as u16,
+            Command::Clear => self.bits = 0,
+        }
+    }
+
+    /// the bit leaving a loop of `length` comes back in as the newest,
+    /// flipped unless `lock` holds it, and is returned
+    fn step(&mut self, length: usize, lock: f32) -> bool {
+        let leaving = (self.bits >> (length - 1)) & 1 == 1;
+        let flip = self.rng.chance(1.0 - lock);
+        let bit = self.write.take().unwrap_or(leaving != flip);
+        self.bits = self.bits << 1 | u16::from(bit);
+        self.steps += 1;
+        bit
+    }
+}
+
+/// A Music Thing Turing Machine: a loop of random bits going round, each
+/// flipped on its way back in unless locked, read out as notes and gates
+/// for a plucked voice.
+pub struct Engine {
+    bus: AudioEnd<Command, State>,
+    params: Params,
+    transport: TransportClock,
+    register: Register,
+    gate: bool,
+    note: u8,
+    /// frames between the last two steps, what the gate is a fraction of
+    interval: usize,
+    since_step: usize,
+    voice: Voice,
+    limiter: Limiter,
+    sample_rate: u32,
+}
+
+impl Engine {
+    pub fn new(
+        bus: AudioEnd<Command, State>,
+        params: Params,
+        transport: TransportClock,
+        seed: u64,
+    ) -> Self {
+        let mut rng = Rng::new(seed);
+        Self {
+            bus,
+            params,
+            transport,
+            register: Register {
+                bits: rng.next_u32() as u16,
+                rng,
+                write: None,
+                steps: 0,
+            },
+            gate: false,
+            note: 0,
+            interval: SAMPLE_RATE / 4,
+            since_step: 0,
+            voice: Voice::new(),
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            sample_rate: SAMPLE_RATE as u32,
+        }
+    }
+
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
+    }
+
+    fn step(&mut self) {
+        if self.since_step > 0 {
+            self.interval = self.since_step;
+        }
+        self.since_step = 0;
+        self.gate = self
+            .register
+            .step(length(&self.params), self.params.get(LOCK));
+        if self.gate {
+            self.note = note(&self.params, self.register.bits);
+            let hold = self.interval as f32 * self.params.get(GATE);
+            self.voice
+                .play(midi_to_freq(f32::from(self.note)), hold as usize);
+        }
+    }
+
+    /// the voice over `out`, counting the frames since the last step
+    fn process(&mut self, out: &mut [f32], channels: usize, gain: f32) {
+        self.since_step += out.len() / channels;
+        self.voice.process(out, channels, gain);
+    }
+
+    fn state(&self) -> State {
+        State {
+            register: self.register.bits,
+            steps: self.register.steps,
+            gate: self.gate,
+            note: self.note,
+            held: self.voice.hold > 0,
+            level: self.voice.envelope.value(),
+        }
+    }
+}
+
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.voice.set_sample_rate(sample_rate);
+            self.limiter.set_sample_rate(sample_rate as f32);
+        }
+        for command in self.bus.commands() {
+            self.register.command(command);
+        }
+
+        let frames = out.len() / channels;
+        let ticks = self.transport.advance(frames, sample_rate);
+        let division = self.params.get(DIVISION).round().max(1.0) as i64;
+        let gain = self.params.get(VOLUME) * GAIN;
+        let mut done = 0;
+        // split at each step so its note starts on its frame
+        let steps = ticks.every(1.0 / division as f64, BEATS_PER_BAR as i64 * division);
+        for tick in steps {
+            self.process(
+                &mut out[done * channels..tick.frame * channels],
+                channels,
+                gain,
+            );
+            done = tick.frame;
+            self.step();
+        }
+        self.process(&mut out[done * channels..], channels, gain);
+
+        self.bus.publish(self.state());
+        self.limiter.process_interleaved(out, channels);
+    }
+}
+
+/// A sine with its octave through a lowpass that closes as it decays, held
+/// for a number of frames.
+struct Voice {
+    sine: Wavetable,
+    envelope: Envelope,
+    filter: Biquad,
+    phase: f32,
+    increment: f32,
+    freq: f32,
+    /// frames until the gate closes
+    hold: usize,
+    rate: f32,
+}
+
+impl Voice {
+    fn new() -> Self {
+        Self {
+            sine: Wavetable::sine(TABLE_SIZE),
+            envelope: Envelope::new(SHAPE),
+            filter: Biquad::lowpass(1_000.0, SAMPLE_RATE as f32),
+            phase: 0.0,
+            increment: 0.0,
+            freq: 0.0,
+            hold: 0,
+            rate: 1.0 / SAMPLE_RATE as f32,
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.rate = 1.0 / sample_rate as f32;
+        self.increment = self.freq * self.rate;
+        self.filter = Biquad::lowpass(self.filter.cutoff(), sample_rate as f32);
+    }
+
+    /// held for `hold` frames, then released
+    fn play(&mut self, freq: f32, hold: usize) {
+        self.freq = freq;
+        self.increment = freq * self.rate;
+        self.hold = hold.max(1);
+        self.envelope.gate_on(self.rate);
+    }
+
+    /// adds the voice to every channel of `out`
+    fn process(&mut self, out: &mut [f32], channels: usize, gain: f32) {
+        if !self.envelope.is_active() {
+            return;
+        }
+        let nyquist = 0.45 / self.rate;
+        for frame in out.chunks_exact_mut(channels) {
+            if self.hold > 0 {
+                self.hold -= 1;
+                if self.hold == 0 {
+                    self.envelope.gate_off();
+                }
+            }
+            let level = self.envelope.step();
+            let cutoff = self.freq * (1.0 + BRIGHTNESS * level);
+            self.filter.set_cutoff(cutoff.min(nyquist));
+            let tone = self.sine.at(self.phase) + 0.5 * self.sine.at((2.0 * self.phase).fract());
+            let sample = self.filter.process(tone) * level * gain;
+            for out in frame.iter_mut() {
+                *out += sample;
+            }
+            self.phase = (self.phase + self.increment).fract();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the bits stepped out of `register`, oldest first
+    fn run(register: &mut Register, steps: usize, length: usize, lock: f32) -> Vec<bool> {
+        (0..steps).map(|_| register.step(length, lock)).collect()
+    }
+
+    #[test]
+    fn a_locked_loop_repeats_every_length_steps() {
+        for &length in &[2, 5, 8, BITS] {
+            let mut register = Register {
+                bits: 0b1011_0010_1110_0101,
+                rng: Rng::new(1),
+                write: None,
+                steps: 0,
+            };
+            let first = run(&mut register, length, length, 1.0);
+            for _ in 0..4 {
+                assert_eq!(run(&mut register, length, length, 1.0), first);
+            }
+            let mask = (1u32 << length) - 1;
+            assert_eq!(
+                u32::from(register.bits) & mask,
+                0b1011_0010_1110_0101 & mask
+            );
+        }
+    }
+
+    #[test]
+    fn an_unlocked_loop_flips_every_bit() {
+        let mut register = Register {
+            bits: 0b0110_1001,
+            rng: Rng::new(1),
+            write: None,
+            steps: 0,
+        };
+        let first = run(&mut register, 8, 8, 0.0);
+        let second = run(&mut register, 8, 8, 0.0);
+        assert!(first.iter().zip(&second).all(|(a, b)| a != b));
+        assert_eq!(register.bits & 0xFF, 0b0110_1001);
+        assert_eq!(register.steps, 16);
+    }
+
+    #[test]
+    fn lock_sets_how_often_bits_flip() {
+        let mut register = Register {
+            bits: 0,
+            rng: Rng::new(1),
+            write: None,
+            steps: 0,
+        };
+        let mut flips = 0;
+        for _ in 0..10_000 {
+            let leaving = (register.bits >> 7) & 1 == 1;
+            flips += usize::from(register.step(8, 0.75) != leaving);
+        }
+        assert!((flips as f32 / 10_000.0 - 0.25).abs() < 0.02, "{}", flips);
+    }
+
+    #[test]
+    fn commands_write_clear_and_redraw() {
+        let mut register = Register {
+            bits: 0,
+            rng: Rng::new(1),
+            write: None,
+            steps: 0,
+        };
+        register.command(Command::Write(true));
+        assert_eq!(run(&mut register, 3, 4, 1.0), [true, false, false]);
+        // only the once, then it comes round locked
+        assert_eq!(run(&mut register, 4, 4, 1.0), [false, true, false, false]);
+        register.command(Command::Randomize);
+        assert_ne!(register.bits, 0);
+        register.command(Command::Clear);
+        assert_eq!(register.bits, 0);
+    }
+
+    #[test]
+    fn the_lowest_bits_pick_the_note() {
+        let params = Params::new(&PARAMS);
+        params.set(ROOT, 48.0);
+        params.set(RANGE, 2.0);
+        params.set(SCALE, 4.0);
+        assert_eq!(note(&params, 0), 48);
+        assert_eq!(note(&params, 0xFF), 72);
+        // the bits above the pitch bits don't count
+        assert_eq!(note(&params, 0xFF00), 48);
+        assert_eq!(note(&params, 0x1280), note(&params, 0x80));
+
+        // a semitone above the root isn't in the minor pentatonic
+        params.set(RANGE, 1.0);
+        params.set(SCALE, 0.0);
+        let semitone = (255.0 / 12.0f32).ceil() as u16;
+        assert_eq!(note(&params, semitone), 48);
+        for register in 0..=0xFF {
+            let degree = (note(&params, register) - 48) % 12;
+            assert!([0, 3, 5, 7, 10].contains(&degree));
+        }
+    }
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let args = app_common::cli::init("turing");
+    match &args.render {
+        Some(request) => turing::render(request),
+        None if args.headless => turing::headless(),
+        None => turing::run(),
+    }
+}
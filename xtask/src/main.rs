@@ -0,0 +1,4 @@
+/// cargo xtask bundle lissa-plugin --release
+fn main() -> nih_plug_xtask::Result<()> {
+    nih_plug_xtask::main()
+}
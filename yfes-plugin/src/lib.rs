@@ -0,0 +1,283 @@
+//! The yfes granular engine as a CLAP/VST3 plugin.
+//!
+//! The engine runs on fixed blocks, like in the app, so its intervals keep
+//! the same meaning whatever buffer size the host uses. Notes retrigger a
+//! voice with the chord rooted on the played note.
+
+use dsp_common::denormal::DenormalGuard;
+use dsp_common::env::Shape;
+use granular::{Engine, Params as EngineParams, NUM_VOICES};
+use nih_plug::prelude::*;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+const BLOCK_SIZE: usize = 2048;
+const NUM_CHANNELS: usize = 2;
+
+/// semitones above the played note, the app's C4 G4 D5 G5 voicing
+const CHORD: [f32; NUM_VOICES] = [0.0, 7.0, 14.0, 19.0];
+
+static SAMPLE: &[u8] = include_bytes!("../../yfes/res/old.wav");
+
+fn load_table() -> &'static [f32] {
+    let reader = hound::WavReader::new(std::io::Cursor::new(SAMPLE)).unwrap();
+    granular::leak_table(
+        reader
+            .into_samples::<i16>()
+            .map(|x| x.unwrap() as f32 / i16::MAX as f32)
+            .collect(),
+    )
+}
+
+#[derive(Params)]
+struct YfesParams {
+    #[id = "interval"]
+    interval: FloatParam,
+    #[id = "grains"]
+    grain_interval: IntParam,
+    #[id = "slope"]
+    slope: FloatParam,
+    #[id = "length"]
+    length: FloatParam,
+    #[id = "attack"]
+    attack: FloatParam,
+    #[id = "release"]
+    release: FloatParam,
+    #[id = "curve"]
+    curve: FloatParam,
+    #[id = "free"]
+    free_running: BoolParam,
+    #[id = "gain"]
+    gain: FloatParam,
+}
+
+impl Default for YfesParams {
+    fn default() -> Self {
+        Self {
+            interval: FloatParam::new(
+                "Trigger Interval",
+                3.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" s"),
+            grain_interval: IntParam::new(
+                "Grain Interval",
+                4,
+                IntRange::Linear { min: 1, max: 16 },
+            )
+            .with_unit(" blocks"),
+            slope: FloatParam::new(
+                "Grain Slope",
+                8.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 32.0,
+                },
+            ),
+            length: FloatParam::new(
+                "Voice Length",
+                24.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 60.0,
+                },
+            )
+            .with_unit(" s"),
+            attack: FloatParam::new(
+                "Voice Attack",
+                25.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" %"),
+            release: FloatParam::new(
+                "Voice Release",
+                25.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" %"),
+            curve: FloatParam::new(
+                "Envelope Curve",
+                0.0,
+                FloatRange::Linear {
+                    min: -8.0,
+                    max: 8.0,
+                },
+            ),
+            free_running: BoolParam::new("Free Running", true),
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-60.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-60.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+struct Yfes {
+    params: Arc<YfesParams>,
+    table: &'static [f32],
+    engine: Engine,
+    block: Vec<f32>,
+    read: usize,
+    notes: [f32; NUM_VOICES],
+}
+
+impl Default for Yfes {
+    fn default() -> Self {
+        let table = load_table();
+        Self {
+            params: Arc::new(YfesParams::default()),
+            table,
+            engine: Engine::new(table, 44_100.0),
+            block: vec![0.0; BLOCK_SIZE * NUM_CHANNELS],
+            read: BLOCK_SIZE,
+            notes: EngineParams::default().notes,
+        }
+    }
+}
+
+impl Yfes {
+    fn engine_params(&self) -> EngineParams {
+        let blocks_per_second = self.engine.sample_rate() / BLOCK_SIZE as f32;
+        let free_running = self.params.free_running.value();
+        EngineParams {
+            trigger_interval: if free_running {
+                Some((self.params.interval.value() * blocks_per_second).max(1.0) as usize)
+            } else {
+                None
+            },
+            grain_interval: self.params.grain_interval.value() as usize,
+            grain_slope: self.params.slope.value(),
+            grain_curve: self.params.curve.value(),
+            voice_shape: Shape::trapezoid(
+                self.params.attack.value() / 100.0,
+                self.params.release.value() / 100.0,
+            )
+            .curve(self.params.curve.value()),
+            voice_length: (self.params.length.value() / 6.0, self.params.length.value()),
+            notes: self.notes,
+            ..EngineParams::default()
+        }
+    }
+
+    fn render_block(&mut self) {
+        self.engine.set_params(self.engine_params());
+        self.block.iter_mut().for_each(|sample| *sample = 0.0);
+        self.engine.process(&mut self.block, NUM_CHANNELS);
+        while self.engine.poll_event().is_some() {}
+        self.read = 0;
+    }
+}
+
+impl Plugin for Yfes {
+    const NAME: &'static str = "Yfes";
+    const VENDOR: &'static str = "Nico Chatzi";
+    const URL: &'static str = "https://github.com/nicochatzi/nannou-apps";
+    const EMAIL: &'static str = "nico.chatzigianis@focusrite.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(NUM_CHANNELS as u32),
+        ..AudioIOLayout::const_default()
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.engine = Engine::new(self.table, buffer_config.sample_rate);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.read = BLOCK_SIZE;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let _denormals = DenormalGuard::new();
+        let mut next_event = context.next_event();
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
+                }
+                if let NoteEvent::NoteOn { note, .. } = event {
+                    let root = note as f32;
+                    self.notes = CHORD.map(|interval| root + interval);
+                    self.engine.set_params(self.engine_params());
+                    self.engine.trigger();
+                }
+                next_event = context.next_event();
+            }
+
+            if self.read == BLOCK_SIZE {
+                self.render_block();
+            }
+
+            let gain = self.params.gain.smoothed.next();
+            let frame = &self.block[self.read * NUM_CHANNELS..][..NUM_CHANNELS];
+            for (sample, rendered) in channel_samples.into_iter().zip(frame.iter()) {
+                *sample = rendered * gain;
+            }
+            self.read += 1;
+        }
+
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for Yfes {
+    const CLAP_ID: &'static str = "com.nicochatzi.yfes";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Granular drones from a sample");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for Yfes {
+    const VST3_CLASS_ID: [u8; 16] = *b"NannouYfesGrains";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(Yfes);
+nih_export_vst3!(Yfes);
@@ -0,0 +1,160 @@
+//! Filled circles drawn in a single instanced call.
+//!
+//! One circle mesh is uploaded once, each circle is an instance of it with
+//! its own centre, radius and colour, so hundreds of grains cost one draw.
+//! The shaders' GLSL sits beside the SPIR-V built from it.
+
+use nannou::prelude::*;
+use nannou::wgpu::{self, BufferInitDescriptor, DeviceExt};
+use std::mem;
+
+/// triangles around the rim, smooth at the sizes grains are drawn
+const SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+/// A circle as the shader sees it, in normalized device coordinates.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct Instance {
+    center: [f32; 2],
+    /// across and up, which differ unless the window is square
+    radius: [f32; 2],
+    /// linear, not premultiplied
+    color: [f32; 4],
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    // SAFETY: only called with the `repr(C)` vertex types above, plain f32s
+    // without padding
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) }
+}
+
+/// a triangle list fanning out from the centre
+fn mesh() -> Vec<Vertex> {
+    let at = |i: usize| {
+        let theta = TAU * i as f32 / SEGMENTS as f32;
+        Vertex {
+            position: [theta.cos(), theta.sin()],
+        }
+    };
+    (0..SEGMENTS)
+        .flat_map(|i| {
+            let centre = Vertex {
+                position: [0.0, 0.0],
+            };
+            vec![centre, at(i), at(i + 1)]
+        })
+        .collect()
+}
+
+/// Circles drawn over a frame in one go, for the window they were made
+/// with.
+pub struct Circles {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    mesh: wgpu::Buffer,
+    vertices: u32,
+    instances: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl Circles {
+    /// room for `capacity` circles a frame, more are left out
+    pub fn new(window: &Window, capacity: usize) -> Self {
+        let device = window.swap_chain_device();
+        let vertex =
+            wgpu::shader_from_spirv_bytes(device, include_bytes!("shaders/circle.vert.spv"));
+        let fragment =
+            wgpu::shader_from_spirv_bytes(device, include_bytes!("shaders/circle.frag.spv"));
+
+        let mesh = mesh();
+        let vertices = mesh.len() as u32;
+        let mesh = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("circle mesh"),
+            contents: as_bytes(&mesh),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let instances = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("circle instances"),
+            contents: as_bytes(&vec![Instance::default(); capacity]),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let layout = wgpu::BindGroupLayoutBuilder::new().build(device);
+        let bind_group = wgpu::BindGroupBuilder::new().build(device, &layout);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("circles"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vertex)
+            .fragment_shader(&fragment)
+            .color_format(Frame::TEXTURE_FORMAT)
+            .color_blend(wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            })
+            .add_vertex_buffer::<Vertex>(&wgpu::vertex_attr_array![0 => Float2])
+            .add_instance_buffer::<Instance>(
+                &wgpu::vertex_attr_array![1 => Float2, 2 => Float2, 3 => Float4],
+            )
+            .sample_count(window.msaa_samples())
+            .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            mesh,
+            vertices,
+            instances,
+            capacity,
+        }
+    }
+
+    /// Draws `circles`, each a centre and radius in window points and a
+    /// colour, over what's already in the frame.
+    pub fn draw<I>(&self, window: &Window, frame: &Frame, circles: I)
+    where
+        I: IntoIterator<Item = (Point2, f32, Rgba8)>,
+    {
+        // points to normalized device coordinates, y already points up
+        let win = window.rect();
+        let scale = [2.0 / win.w(), 2.0 / win.h()];
+        let instances: Vec<Instance> = circles
+            .into_iter()
+            .take(self.capacity)
+            .map(|(center, radius, color)| {
+                let color: LinSrgba = color.into_format::<f32, f32>().into_linear();
+                Instance {
+                    center: [center.x * scale[0], center.y * scale[1]],
+                    radius: [radius * scale[0], radius * scale[1]],
+                    color: [color.red, color.green, color.blue, color.alpha],
+                }
+            })
+            .collect();
+        if instances.is_empty() {
+            return;
+        }
+        window
+            .swap_chain_queue()
+            .write_buffer(&self.instances, 0, as_bytes(&instances));
+
+        let mut encoder = frame.command_encoder();
+        let mut pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(frame.texture_view(), |color| {
+                color.load_op(wgpu::LoadOp::Load)
+            })
+            .begin(&mut encoder);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.mesh.slice(..));
+        pass.set_vertex_buffer(1, self.instances.slice(..));
+        pass.draw(0..self.vertices, 0..instances.len() as u32);
+    }
+}
@@ -1,289 +1,182 @@
-use heapless::{consts, spsc};
-use nannou_audio::Buffer;
-use rand::{thread_rng, Rng};
-
+use app_common::bus::AudioEnd;
+use app_common::param::{Curve, ParamSpec, Params};
+use app_common::render::Render;
+use app_common::scope::ScopeInput;
+use app_common::transport::TransportClock;
+use dsp_common::convolution::Reverb;
+use dsp_common::env::Shape;
+use dsp_common::limiter::Limiter;
+use dsp_common::meter::{MeterWriter, StereoMeter};
+use dsp_common::pan::{Encoding, Layout};
+use granular::Params as EngineParams;
+
+pub use granular::{Voice, Voices, NUM_GRAINS, NUM_VOICES};
+
+/// asked of the stream unless the config says otherwise, the engine follows
+/// whatever rate it runs at
 pub const SAMPLE_RATE: usize = 44_100;
 pub const NUM_CHANNELS: usize = 2;
 pub const BUFFER_SIZE: usize = 2048;
-pub const NUM_GRAINS: usize = 8;
-pub const NUM_VOICES: usize = 4;
-
-pub type Consumer = spsc::Consumer<'static, Voices, consts::U16>;
-pub type Producer = spsc::Producer<'static, Voices, consts::U16>;
-pub type Queue = spsc::Queue<Voices, consts::U16>;
 
-#[derive(Clone, Copy, Debug)]
-pub struct Grain {
-    pub active: bool,
-    pub volume: f32,
-    pub pan: f32,
-    pub lut: rume::Lut<'static>,
-    pub slice: &'static [f32],
-    env_position: f32,
-    env_increment: f32,
-}
+pub const SNAPSHOT_CAPACITY: usize = 16;
+
+pub const ATTACK: usize = 0;
+pub const RELEASE: usize = 1;
+pub const CURVE: usize = 2;
+pub const GRAIN_SLOPE: usize = 3;
+pub const GRAIN_INTERVAL: usize = 4;
+pub const REVERB_MIX: usize = 5;
+pub const PRE_DELAY: usize = 6;
+pub const TUNE: usize = 7;
+
+/// the voice envelope's edges as fractions of its length, its curve is the
+/// grains' too, then how often each voice starts a grain, then the reverb
+/// when an impulse response is loaded, and the chords moved from where
+/// granular has them
+pub static PARAMS: [ParamSpec; 8] = [
+    ParamSpec::new("attack", 0.0, 0.5, 0.25),
+    ParamSpec::new("release", 0.0, 0.5, 0.25),
+    ParamSpec::new("curve", -8.0, 8.0, 0.0),
+    ParamSpec::new("grain slope", 2.0, 32.0, 8.0).curve(Curve::Exponential),
+    ParamSpec::new("grain interval", 1.0, 16.0, 4.0).unit("buffers"),
+    ParamSpec::new("reverb mix", 0.0, 1.0, 0.3),
+    ParamSpec::new("pre-delay", 0.0, 250.0, 20.0).unit("ms"),
+    ParamSpec::new("tune", -12.0, 12.0, 0.0).unit("st"),
+];
 
-unsafe impl Send for Grain {}
-unsafe impl Sync for Grain {}
-
-fn random_slice(table: &[f32]) -> &[f32] {
-    let mut rng = thread_rng();
-    let table_len = table.len() as f32;
-    let start = (rng.gen_range(0.0..0.4) * table_len) as usize;
-    let length = (rng.gen_range(0.8..1.0) * table_len) as usize;
-    let end = (start + length).min(table.len());
-    &table[start..end]
+pub struct Engine {
+    granular: granular::Engine,
+    bus: AudioEnd<(), Voices>,
+    limiter: Limiter,
+    meter: StereoMeter,
+    meter_out: MeterWriter,
+    scope_out: ScopeInput,
+    /// a voice starts on every bar
+    transport: TransportClock,
+    /// read at buffer rate, shapes apply from the next voice or grain
+    params: Params,
+    sample_rate: u32,
+    /// on the master bus before the limiter, when an impulse response is set
+    reverb: Option<Reverb>,
 }
 
-impl Grain {
-    fn new(table: &'static [f32], pitch: Option<f32>) -> Self {
-        let mut grain = Grain::generate(table, pitch.unwrap_or(220.0));
-        grain.active = false;
-        grain
-    }
-
-    fn generate(table: &'static [f32], pitch: f32) -> Self {
-        let mut rng = thread_rng();
-        let slice = random_slice(table);
-        let lut_increment = pitch * rume::convert::pitch::from_midi(60.0) / SAMPLE_RATE as f32;
-        let env_increment = lut_increment / slice.len() as f32;
-
+impl Engine {
+    pub fn new(
+        table: &'static [f32],
+        bus: AudioEnd<(), Voices>,
+        meter_out: MeterWriter,
+        scope_out: ScopeInput,
+        transport: TransportClock,
+        params: Params,
+    ) -> Self {
         Self {
-            active: true,
-            env_increment,
-            slice,
-            lut: {
-                let mut lut = rume::Lut::new(&slice);
-                lut.phasor.inc(lut_increment);
-                lut
-            },
-            volume: rng.gen_range(0.0f32..1.0f32).powf(0.3),
-            pan: rng.gen_range(0.0..1.0),
-            env_position: 0.0,
+            granular: granular::Engine::new(table, SAMPLE_RATE as f32),
+            bus,
+            limiter: Limiter::new(SAMPLE_RATE as f32),
+            meter: StereoMeter::new(SAMPLE_RATE as f32),
+            meter_out,
+            scope_out,
+            transport,
+            params,
+            sample_rate: SAMPLE_RATE as u32,
+            reverb: None,
         }
     }
 
-    pub fn advance(&mut self) -> (f32, f32) {
-        if !self.active {
-            return (0.0, 0.0);
+    /// made with the stream's audio channels, `None` is dry
+    pub fn set_reverb(&mut self, mut reverb: Option<Reverb>) {
+        if let Some(reverb) = &mut reverb {
+            // only allocates when it was made for another rate
+            reverb.set_sample_rate(self.sample_rate);
         }
-
-        let vol = self.env() * self.volume;
-        let sample = self.lut.step();
-        self.pan(sample * vol)
+        self.reverb = reverb;
     }
 
-    fn pan(&self, sample: f32) -> (f32, f32) {
-        (sample * (1.0 - self.pan), sample * self.pan)
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        self.limiter.set_bypass(bypass);
     }
 
-    fn env(&mut self) -> f32 {
-        let env = if self.env_position > 0.5 {
-            ((1.0 - self.env_position) * 8.0).min(1.0)
-        } else {
-            (self.env_position * 8.0).min(1.0)
-        };
-
-        self.env_position += self.env_increment;
-
-        if self.env_position >= 1.0 {
-            self.env_position = 0.0;
-            self.active = false;
-        }
-
-        env
+    /// the speakers the voices pan round, before the stream opens with as
+    /// many channels
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.granular.set_layout(layout);
     }
-}
-
-#[derive(Clone, Copy, Debug)]
-pub struct Grains {
-    pub grains: [Grain; NUM_GRAINS],
-    table: &'static [f32],
-}
 
-impl Grains {
-    fn new(table: &'static [f32]) -> Self {
-        Self {
-            grains: [Grain::new(table, None); NUM_GRAINS],
-            table,
-        }
+    /// see `granular::Engine::set_encoding`
+    pub fn set_encoding(&mut self, encoding: Option<Encoding>, elevations: [f32; NUM_VOICES]) {
+        self.granular.set_encoding(encoding, elevations);
     }
 
-    fn activate(&mut self, pitch: f32) -> Result<(), ()> {
-        for grain in self.grains.iter_mut() {
-            if !grain.active {
-                *grain = Grain::generate(self.table, pitch);
-                return Ok(());
-            }
-        }
-        Err(())
+    /// voices split across threads, see `granular::Engine::set_threads`
+    pub fn set_threads(&mut self, threads: usize) {
+        self.granular.set_threads(threads);
     }
 
-    fn advance(&mut self) -> (f32, f32) {
-        const INV_NUM_GRAINS: f32 = 1.0 / NUM_GRAINS as f32;
-        self.grains
-            .iter_mut()
-            .fold((0.0, 0.0), |(left, right), grain| {
-                let (l, r) = grain.advance();
-                (left + l * INV_NUM_GRAINS, right + r * INV_NUM_GRAINS)
-            })
-    }
-}
-
-#[derive(Clone, Copy, Debug)]
-pub struct Voice {
-    pub grains: Grains,
-
-    length: usize,
-    env_increment: f32,
-    env_position: f32,
-
-    pub active: bool,
-    pitch: f32,
-
-    buffers_since_last_trigger: usize,
-    buffers_between_triggers: usize,
-}
-
-impl Voice {
-    pub fn new(table: &'static [f32]) -> Self {
-        Self {
-            grains: Grains::new(table),
-            length: 0,
-            env_increment: 0.0,
-            env_position: 0.0,
-            active: false,
-            pitch: 440.0,
-            buffers_since_last_trigger: 0,
-            buffers_between_triggers: 4,
+    /// when the stream comes back at another rate
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.granular.set_sample_rate(sample_rate as f32);
+        self.limiter.set_sample_rate(sample_rate as f32);
+        self.meter.set_sample_rate(sample_rate as f32);
+        if let Some(reverb) = &mut self.reverb {
+            reverb.set_sample_rate(sample_rate);
         }
     }
 
-    fn trigger_grain(&mut self) {
-        let _ = self.grains.activate(self.pitch).is_err();
-    }
-
-    pub fn update_grains(&mut self) {
-        if self.buffers_since_last_trigger >= self.buffers_between_triggers {
-            self.trigger_grain();
-            self.buffers_since_last_trigger = 0;
-        }
-        self.buffers_since_last_trigger += 1;
-    }
-
-    /// called at frame rate
-    fn advance(&mut self) -> (f32, f32) {
-        let env = self.env();
-        let (l, r) = self.grains.advance();
-        (l * env, r * env)
-    }
-
-    fn env(&mut self) -> f32 {
-        let env = if self.env_position > 0.5 {
-            ((1.0 - self.env_position) * 4.0).min(1.0)
-        } else {
-            (self.env_position * 4.0).min(1.0)
+    /// called at buffer rate
+    fn update(&mut self) {
+        let curve = self.params.get(CURVE);
+        let tune = self.params.get(TUNE);
+        let params = EngineParams {
+            notes: EngineParams::default().notes.map(|note| note + tune),
+            // the transport triggers instead
+            trigger_interval: None,
+            grain_interval: self.params.get(GRAIN_INTERVAL).round().max(1.0) as usize,
+            grain_slope: self.params.get(GRAIN_SLOPE),
+            grain_curve: curve,
+            voice_shape: Shape::trapezoid(self.params.get(ATTACK), self.params.get(RELEASE))
+                .curve(curve),
+            ..*self.granular.params()
         };
-
-        self.env_position += self.env_increment;
-
-        if self.env_position >= 1.0 {
-            self.env_position = 0.0;
-            self.active = false;
+        self.granular.set_params(params);
+        if let Some(reverb) = &mut self.reverb {
+            reverb.set_mix(self.params.get(REVERB_MIX));
+            reverb.set_pre_delay(self.params.get(PRE_DELAY) / 1000.0);
         }
-
-        env
     }
-
-    pub fn activate(&mut self, length: usize, pitch: f32) {
-        self.length = length;
-        self.env_increment = 1.0 / length as f32;
-        self.env_position = 0.0;
-        self.pitch = pitch;
-        self.active = true;
-    }
-
-    pub fn process(&mut self, buffer: &mut Buffer) {
-        for frame in buffer.frames_mut() {
-            let (left, right) = self.advance();
-            let mut frame_iter = frame.iter_mut();
-            if let Some(left_out) = frame_iter.next() {
-                *left_out += left;
-            }
-            if let Some(right_out) = frame_iter.next() {
-                *right_out += right;
-            }
-        }
-    }
-}
-
-pub type Voices = [Voice; NUM_VOICES];
-
-pub struct Engine {
-    pub voices: Voices,
-    producer: Producer,
-    buffers_since_last_trigger: usize,
-    buffers_between_triggers: usize,
 }
 
-impl Engine {
-    pub fn new(table: &'static [f32], producer: Producer) -> Self {
-        Self {
-            voices: [Voice::new(table); NUM_VOICES],
-            producer,
-            buffers_since_last_trigger: 0,
-            buffers_between_triggers: 64,
+impl Render for Engine {
+    fn render(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.set_sample_rate(sample_rate);
         }
-    }
+        self.update();
 
-    fn trigger(&mut self) {
-        use rume::convert::pitch;
-        let mut rng = thread_rng();
-        let root = [-12.0, -12.0, 0.0, 0.0, 0.0, 7.0][rng.gen_range(0..=5)];
-        let freqs = [
-            pitch::from_midi(root + 60.0), // C4
-            // pitch::from_midi(63.0), // D#4
-            pitch::from_midi(root + 67.0), // G4
-            // pitch::from_midi(70.0), // A#4
-            // pitch::from_midi(72.0), // C5
-            pitch::from_midi(root + 74.0), // D5
-            pitch::from_midi(root + 79.0), // G5
-        ];
-        let mut inactive_voice_indices: Vec<usize> = Vec::new();
-        for (i, voice) in self.voices.iter_mut().enumerate() {
-            if !voice.active {
-                inactive_voice_indices.push(i);
+        // split at each bar so the voice starts on its frame
+        let mut done = 0;
+        let frames = out.len() / channels;
+        for tick in self.transport.advance(frames, sample_rate) {
+            if !tick.bar {
+                continue;
             }
-        }
-        if !inactive_voice_indices.is_empty() {
-            let i = inactive_voice_indices[rng.gen_range(0..inactive_voice_indices.len())];
-            let length = thread_rng().gen_range(4..24) * SAMPLE_RATE;
-            self.voices[i].activate(length, freqs[i]);
-        }
-    }
-
-    /// called at buffer rate
-    fn update(&mut self) {
-        if self.buffers_since_last_trigger >= self.buffers_between_triggers {
-            self.trigger();
-            self.buffers_since_last_trigger = 0;
-        }
-        self.buffers_since_last_trigger += 1;
-
-        for voice in self.voices.iter_mut() {
-            if voice.active {
-                voice.update_grains();
+            if tick.frame > done {
+                self.granular
+                    .process(&mut out[done * channels..tick.frame * channels], channels);
+                done = tick.frame;
             }
+            self.granular.trigger();
         }
-    }
+        self.granular.process(&mut out[done * channels..], channels);
+        while self.granular.poll_event().is_some() {}
+        self.bus.publish(*self.granular.voices());
 
-    pub fn process(&mut self, buffer: &mut Buffer) {
-        self.update();
-        for voice in self.voices.iter_mut() {
-            if voice.active {
-                voice.process(buffer);
-            }
+        if let Some(reverb) = &mut self.reverb {
+            reverb.process_interleaved(out, channels);
         }
-        let _ = self.producer.enqueue(self.voices);
+        self.limiter.process_interleaved(out, channels);
+        self.meter.process_interleaved(out, channels);
+        self.meter_out.write_all(&self.meter.readings());
+        self.scope_out.write(out);
     }
 }
@@ -0,0 +1,826 @@
+#![allow(dead_code)]
+
+use app_common::assets::Asset;
+use app_common::audio::{StreamConfig, Supervisor};
+use app_common::bus::{self, UiEnd};
+use app_common::cli;
+use app_common::config::{self, Config};
+use app_common::cv::{self, CvOutput, CvSignal, CvTargets, WithCv};
+use app_common::diagnostics::Hud;
+use app_common::dmx::DmxOutput;
+use app_common::kiosk::{self, Kiosk};
+use app_common::link::Link;
+use app_common::macros::{self, Macro};
+use app_common::midi::{self, MidiOutput};
+use app_common::oscquery;
+use app_common::output::OutputWindow;
+use app_common::param::{self, ParamPreset, Params};
+use app_common::render::{self, Render, Request};
+use app_common::scope::{self, ScopeReader};
+use app_common::serial;
+use app_common::session;
+use app_common::share::{self, FrameShare};
+use app_common::shell::{self, Shell};
+use app_common::speakers::{self, Spatial};
+use app_common::startup::{self, ErrorScreen};
+use app_common::tasks::{self, Pending, Tasks};
+use app_common::theme::{self, Themed};
+use app_common::timecode::Chase;
+use app_common::transport::{Transport, TransportClock};
+use app_common::widget::{Scope, StereoMeter};
+use circles::Circles;
+use dsp_common::convolution::{Impulse, Reverb};
+use dsp_common::meter::MeterReader;
+use dsp_common::pitch::{self, Pitch};
+use dsp_common::tuning::{self, A4_FREQ, A4_MIDI};
+use nannou::prelude::*;
+use nannou::ui::prelude::*;
+use std::borrow::Cow;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+mod circles;
+mod dsp;
+
+pub use dsp::{Voice, Voices, NUM_GRAINS, NUM_VOICES};
+
+/// mod wheel, follows the grain density
+const MIDI_DENSITY: u8 = 1;
+
+const CV_DENSITY: usize = 0;
+const CV_VOICES: usize = 1;
+
+/// how many grains are playing, then each voice's envelope
+static CV_SIGNALS: [CvSignal; 1 + dsp::NUM_VOICES] = [
+    CvSignal::new("density", 0.02),
+    CvSignal::new("voice_1", 0.005),
+    CvSignal::new("voice_2", 0.005),
+    CvSignal::new("voice_3", 0.005),
+    CvSignal::new("voice_4", 0.005),
+];
+
+/// `res/old.wav` unless the config names another sample
+static SAMPLE: Asset = Asset::new("old.wav", include_bytes!("../res/old.wav"));
+
+fn load_samples() -> Result<Vec<f32>, startup::Error> {
+    let (path, bytes) = match Config::load(&config::path("yfes")).sample_path {
+        Some(path) => {
+            let bytes = fs::read(&path).map(Cow::Owned);
+            (path, bytes)
+        }
+        None => (SAMPLE.source(), SAMPLE.bytes()),
+    };
+    let failed = |reason: String| startup::Error::Sample {
+        path: path.clone(),
+        reason,
+    };
+    hound::WavReader::new(Cursor::new(bytes.map_err(|e| failed(e.to_string()))?))
+        .map_err(|e| failed(e.to_string()))?
+        .into_samples::<i16>()
+        .map(|x| {
+            x.map(|s| s as f32 / 32_768.0)
+                .map_err(|e| failed(e.to_string()))
+        })
+        .collect()
+}
+
+/// a WAV of any depth and channel count
+fn load_impulse(path: &Path) -> Result<Impulse, String> {
+    let reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
+    Impulse::new(&samples, spec.channels as usize, spec.sample_rate)
+        .ok_or_else(|| String::from("no samples"))
+}
+
+/// the configured impulse response over `channels`, dry without one or
+/// when it can't be read
+fn open_reverb(config: &Config, channels: usize) -> Option<Reverb> {
+    let path = config.impulse_path.as_ref()?;
+    let impulse = load_impulse(path)
+        .map_err(|e| eprintln!("yfes: impulse response {}: {}", path.display(), e))
+        .ok()?;
+    let sample_rate = config.sample_rate.unwrap_or(dsp::SAMPLE_RATE as u32);
+    Some(Reverb::new(impulse, channels, sample_rate))
+}
+
+/// the range the sample's pitch is looked for in
+const PITCH_RANGE: (f32, f32) = (50.0, 2_000.0);
+
+/// the sample's pitch, `None` when nothing in it is clear enough to tune to
+fn analyse(samples: &[f32]) -> Option<Pitch> {
+    // the rate the grains read it at, whatever the stream's
+    pitch::dominant(
+        samples,
+        PITCH_RANGE.0,
+        PITCH_RANGE.1,
+        dsp::SAMPLE_RATE as f32,
+    )
+}
+
+/// The tune that puts each voice's grains on the note granular writes
+/// for it, moved by as little as can be. A grain reads the sample faster
+/// the higher its note, so what it sounds is the sample's own pitch plus
+/// that note and a fixed offset, only the semitones and cents of which
+/// can be lined up.
+fn tune_to(pitch: &Pitch) -> f32 {
+    let speed = 12.0 * (A4_FREQ * tuning::midi_to_freq(60.0) / dsp::SAMPLE_RATE as f32).log2();
+    let tune = A4_MIDI - tuning::freq_to_midi(pitch.freq) - speed;
+    (tune + 6.0).rem_euclid(12.0) - 6.0
+}
+
+lazy_static::lazy_static! {
+    static ref LOADED: Result<Vec<f32>, startup::Error> = load_samples();
+    /// silent when the sample could not be read, see `LOADED` for why
+    pub static ref SAMPLES: &'static [f32] = LOADED.as_deref().unwrap_or(&[]);
+}
+
+pub fn run() {
+    // wav::to_file();
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .exit(exit)
+        .run();
+}
+
+/// the config with `--session` installed and the flags over it, before
+/// `LOADED` reads the sample path
+fn load_config(config_path: &Path) -> Config {
+    shell::load_config("yfes", config_path).0
+}
+
+/// the saved parameters and macros with `--preset`'s over them, presets
+/// without macros keep the saved ones
+fn load_params(config: &Config) -> (Params, Vec<Macro>) {
+    let params = Params::new(&dsp::PARAMS);
+    config.params.apply(&params);
+    let mut macros = config.macros.clone();
+    if let Some(name) = &cli::args().preset {
+        match ParamPreset::load("yfes", name) {
+            Ok(preset) => {
+                preset.params.apply(&params);
+                if !preset.macros.is_empty() {
+                    macros = preset.macros;
+                }
+            }
+            Err(e) => eprintln!("yfes: {}", e),
+        }
+    }
+    (params, macros)
+}
+
+/// free-running, nothing reads the voices back
+fn headless_engine(config: &Config) -> dsp::Engine {
+    let link = Link::new(120.0, 4.0);
+    let transport = Transport::new(120.0, 4);
+    let (engine, _bus) = free_engine(config, transport.clock(Some(link.clock())));
+    engine
+}
+
+/// The engine as a headless run plays it, from the app's saved config in
+/// stereo and on `transport`, for hosts playing it beside other engines.
+/// The voices come back after every buffer.
+pub fn embedded(transport: &Transport) -> (impl Render + Send, UiEnd<(), Voices>) {
+    let config = Config {
+        speakers: None,
+        ..Config::load(&config::default_path("yfes"))
+    };
+    free_engine(&config, transport.clock(None))
+}
+
+/// `headless_engine` on any clock, and the voices it publishes
+fn free_engine(config: &Config, clock: TransportClock) -> (dsp::Engine, UiEnd<(), Voices>) {
+    if let Err(e) = &*LOADED {
+        eprintln!("yfes: {}", e);
+    }
+    let (ui_bus, audio_bus) = bus::bus(1, dsp::SNAPSHOT_CAPACITY);
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    let (meter_out, _meter) = dsp_common::meter::channel(dsp::NUM_CHANNELS);
+    let (scope_out, _scope) = scope::channel(spatial.channels(), dsp::SAMPLE_RATE as f32);
+    let mut engine = dsp::Engine::new(
+        &SAMPLES,
+        audio_bus,
+        meter_out,
+        scope_out,
+        clock,
+        load_params(config).0,
+    );
+    engine.set_limiter_bypass(config.bypass_limiter);
+    engine.set_layout(spatial.layout);
+    engine.set_encoding(
+        spatial.encoding,
+        speakers::elevations(config.speakers.as_ref()),
+    );
+    engine.set_reverb(open_reverb(config, spatial.channels()));
+    (engine, ui_bus)
+}
+
+/// `spatial`'s channels first, then any control voltages
+fn stream_config(config: &Config, spatial: &Spatial) -> StreamConfig {
+    config.stream_config(StreamConfig {
+        sample_rate: Some(dsp::SAMPLE_RATE as u32),
+        frames_per_buffer: Some(dsp::BUFFER_SIZE),
+        channels: Some(stream_channels(config, spatial)),
+        jack: config.jack_client("yfes", spatial.ports()),
+        ..StreamConfig::default()
+    })
+}
+
+fn stream_channels(config: &Config, spatial: &Spatial) -> usize {
+    match &config.cv {
+        Some(cv) => cv.channels(spatial.channels(), CV_SIGNALS.len()),
+        None => spatial.channels(),
+    }
+}
+
+fn open_cv(config: &Config, targets: &CvTargets, spatial: &Spatial) -> Option<CvOutput> {
+    let cv = config.cv.as_ref()?;
+    Some(CvOutput::new(targets.clone(), cv, spatial.channels()))
+}
+
+/// the engine without a window or audio device, on as many threads as
+/// `--threads` asks
+pub fn render(request: &Request) {
+    let config = load_config(&config::path("yfes"));
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    let mut engine = headless_engine(&config);
+    // threads are started every buffer, too slow for a live stream
+    if let Some(threads) = cli::args().threads {
+        engine.set_threads(threads);
+    }
+    request.run(
+        &mut engine,
+        dsp::SAMPLE_RATE as u32,
+        spatial.channels(),
+        dsp::BUFFER_SIZE,
+    );
+}
+
+/// the engine on the audio device without a window
+pub fn headless() {
+    let config = load_config(&config::path("yfes"));
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    render::headless(
+        "yfes",
+        headless_engine(&config),
+        stream_config(&config, &spatial),
+    );
+}
+
+#[derive(Clone, Copy, Default)]
+struct Grain {
+    active: bool,
+    center: Point2,
+    radius: f32,
+    color: Rgba8,
+}
+
+widget_ids! {
+    struct Ids {
+        meter,
+        scope,
+        link,
+        transport,
+        tempo,
+        tune,
+    }
+}
+
+struct Model {
+    ui: Ui,
+    ids: Ids,
+    param_ids: widget::id::List,
+    /// envelope shapes, the engine reads them directly
+    params: Params,
+    /// knobs over several of `params` at once
+    macros: Vec<Macro>,
+    macro_ids: widget::id::List,
+    meter: MeterReader,
+    /// the output, triggered
+    scope: ScopeReader,
+    grains: Vec<Grain>,
+    /// the grains in the window, all in one draw
+    circles: Circles,
+    bus: UiEnd<(), dsp::Voices>,
+    voices: dsp::Voices,
+    link: Link,
+    /// a voice starts on every bar
+    transport: Transport,
+    /// moves the transport after show control, when `timecode` is set
+    timecode: Option<Chase>,
+    stream: Supervisor<WithCv<dsp::Engine>>,
+    /// grain density and voice envelopes for modular synths
+    cv: CvTargets,
+    /// the channels the stream opened with, others wait for a restart
+    spatial: Spatial,
+    /// shown instead of the scene until resolved or dismissed
+    errors: Option<ErrorScreen>,
+    hud: Hud,
+    /// fullscreen and watched over for gallery runs, see `kiosk`
+    kiosk: Option<Kiosk>,
+    tasks: Tasks,
+    /// the sample's pitch while it's looked for
+    analysis: Option<Pending<Option<Pitch>>>,
+    /// and once found, offered to tune the chords to
+    sample_pitch: Option<Pitch>,
+    /// level `i` follows how many of voice `i`'s grains are playing
+    dmx: Option<DmxOutput>,
+    /// a note per active voice at its pitch, the density as a controller
+    midi_out: Option<MidiOutput>,
+    /// what each voice holds on `midi_out`
+    midi_notes: [Option<u8>; dsp::NUM_VOICES],
+    /// the scene to Syphon, Spout or NDI, see `share::open`
+    share: Option<FrameShare>,
+    /// projection window, opened at startup when configured
+    output: Option<OutputWindow>,
+    /// the parameters for OSC controllers to find, when `oscquery` is set
+    oscquery: oscquery::Service,
+    /// sensors on a board setting the parameters, when `serial` is set
+    serial: serial::Sensors,
+    shell: Shell,
+}
+
+fn open_dmx(config: &Config) -> Option<DmxOutput> {
+    let dmx = DmxOutput::open("yfes", config.dmx.clone()?);
+    dmx.map_err(|e| eprintln!("yfes: {}", e)).ok()
+}
+
+fn open_midi_out(config: &Config) -> Option<MidiOutput> {
+    let output = MidiOutput::open("yfes", config.midi_out.clone()?);
+    output.map_err(|e| eprintln!("yfes: {}", e)).ok()
+}
+
+fn open_output(app: &App, main: WindowId, config: &Config) -> Option<OutputWindow> {
+    let output = OutputWindow::open(app, main, config.output.as_ref()?, output_view);
+    output.map_err(|e| eprintln!("yfes: {}", e)).ok()
+}
+
+fn open_timecode(config: &Config) -> Option<Chase> {
+    let chase = Chase::open("yfes", config.timecode.as_ref()?);
+    chase.map_err(|e| eprintln!("yfes: {}", e)).ok()
+}
+
+fn model(app: &App) -> Model {
+    app.set_loop_mode(LoopMode::rate_fps(
+        dsp::SAMPLE_RATE as f64 / dsp::BUFFER_SIZE as f64,
+    ));
+
+    let config_path = config::path("yfes");
+    let config = load_config(&config_path);
+    let main = config.build_window(app, view);
+
+    let (ui_bus, audio_bus) = bus::bus(1, dsp::SNAPSHOT_CAPACITY);
+
+    let spatial = speakers::spatial(config.speakers.as_ref());
+    let (meter_out, meter) = dsp_common::meter::channel(dsp::NUM_CHANNELS);
+    let (scope_out, scope) = scope::channel(spatial.channels(), dsp::SAMPLE_RATE as f32);
+    let mut ui = app
+        .new_ui()
+        .build()
+        .unwrap_or_else(|e| startup::fatal("yfes", startup::Error::Ui(format!("{:?}", e))));
+    let link = Link::new(120.0, 4.0);
+    let transport = Transport::new(120.0, 4);
+    let (params, macros) = load_params(&config);
+    let mut engine = dsp::Engine::new(
+        &SAMPLES,
+        audio_bus,
+        meter_out,
+        scope_out,
+        transport.clock(Some(link.clock())),
+        params.clone(),
+    );
+    engine.set_limiter_bypass(config.bypass_limiter);
+    engine.set_layout(spatial.layout);
+    engine.set_encoding(
+        spatial.encoding,
+        speakers::elevations(config.speakers.as_ref()),
+    );
+    engine.set_reverb(open_reverb(&config, spatial.channels()));
+
+    let cv = CvTargets::new(&CV_SIGNALS);
+    let engine = WithCv::new(engine, spatial.channels(), open_cv(&config, &cv, &spatial));
+    let mut stream = Supervisor::idle(engine, stream_config(&config, &spatial));
+    let errors = LOADED
+        .as_ref()
+        .err()
+        .cloned()
+        .into_iter()
+        .chain(stream.rebuild().err().map(Into::into))
+        .collect();
+    let oscquery = oscquery::Service::from_config("yfes", &config, &params);
+    let serial = serial::Sensors::from_config("yfes", &config, &params);
+    let mut tasks = Tasks::new("yfes", tasks::THREADS);
+    let analysis = tasks.spawn("finding the sample's pitch", |_| analyse(&SAMPLES));
+
+    // a new layout only carries on with the same ports, so the stream keeps
+    // the channels it opened with
+    let shell = Shell::new("yfes", config, &config_path, "pastel", move |config| {
+        stream_config(config, &spatial)
+    })
+    .with_params(&params);
+
+    // Initialise the state that we want to live on the audio thread.
+
+    Model {
+        ids: Ids::new(ui.widget_id_generator()),
+        ui,
+        param_ids: widget::id::List::new(),
+        params,
+        macros,
+        macro_ids: widget::id::List::new(),
+        meter,
+        scope,
+        bus: ui_bus,
+        grains: vec![Grain::default(); NUM_GRAINS * dsp::NUM_VOICES],
+        circles: Circles::new(&app.window(main).unwrap(), NUM_GRAINS * dsp::NUM_VOICES),
+        voices: [dsp::Voice::new(&SAMPLES); dsp::NUM_VOICES],
+        link,
+        transport,
+        hud: Hud::new(stream.stats()),
+        kiosk: kiosk::open(app, "yfes", &shell.config, stream.stats()),
+        tasks,
+        analysis: Some(analysis),
+        sample_pitch: None,
+        stream,
+        cv,
+        spatial,
+        errors: ErrorScreen::new(errors),
+        dmx: open_dmx(&shell.config),
+        midi_out: open_midi_out(&shell.config),
+        midi_notes: [None; dsp::NUM_VOICES],
+        share: share::open("yfes", &shell.config),
+        output: open_output(app, main, &shell.config),
+        timecode: open_timecode(&shell.config),
+        oscquery,
+        serial,
+        shell,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: Event) {
+    if let Event::WindowEvent {
+        simple: Some(KeyPressed(key)),
+        ..
+    } = event
+    {
+        if model
+            .shell
+            .setup_key_pressed(key, &mut model.stream)
+            .taken()
+        {
+            return;
+        }
+        if let Some(screen) = &mut model.errors {
+            if screen.key_pressed(key, &mut model.stream) {
+                model.errors = None;
+            }
+            return;
+        }
+        if model
+            .stream
+            .insert_mut()
+            .map_or(false, |insert| insert.key_pressed(key))
+        {
+            return;
+        }
+        model.hud.key_pressed(key);
+        if key == session::SAVE || key == session::LOAD {
+            // sessions bundle the macros with the rest of the config
+            model.shell.config.macros = model.macros.clone();
+        }
+        let loaded = model.shell.key_pressed(app, key, &model.stream, None);
+        // samples are read once, at startup
+        if let Some(session) = loaded {
+            if session.config.sample_path != model.shell.config.sample_path {
+                println!("yfes: restart to load the session's sample");
+            }
+        }
+        model.transport.key_pressed(key);
+    }
+}
+
+fn exit(app: &App, mut model: Model) {
+    if let Some(share) = &mut model.share {
+        share.finish(app);
+    }
+    model.shell.config.macros = model.macros.clone();
+    model.shell.exit(app, &model.stream);
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    const RESOLUTION: usize = dsp::BUFFER_SIZE;
+    const INV_RESOLUTION: f32 = 1.0 / RESOLUTION as f32;
+
+    let win = app.window_rect();
+
+    if let Some(kiosk) = &model.kiosk {
+        kiosk.update();
+    }
+    model.stream.poll();
+    model.tasks.poll();
+    if let Some(pitch) = model.analysis.as_ref().and_then(Pending::take) {
+        model.analysis = None;
+        model.sample_pitch = pitch;
+    }
+    if let Some(screen) = &mut model.errors {
+        if screen.update(&model.stream) {
+            model.errors = None;
+        }
+    }
+    model.shell.capture.update(app);
+    model.hud.update(update.since_last);
+    let stats = model.bus.stats();
+    model.hud.queue(
+        "voices",
+        stats.events_queued,
+        stats.events_capacity,
+        stats.events_dropped,
+    );
+    if let Some(draw) = model.shell.screenshots.begin() {
+        scene(model, &draw);
+        model.shell.screenshots.end(app, &draw);
+    }
+    if let Some(draw) = model.share.as_ref().and_then(|share| share.begin(app)) {
+        scene(model, &draw);
+        if let Some(share) = &mut model.share {
+            share.end(app, &draw);
+        }
+    }
+    if let Some(timecode) = &mut model.timecode {
+        timecode.poll(&model.transport);
+    }
+    if let Some(config) = model.shell.reload(app, &mut model.stream) {
+        if config.macros != model.shell.config.macros {
+            // the parameters stay where they are until a knob turns
+            model.macros = config.macros.clone();
+        }
+        if config.bypass_limiter != model.shell.config.bypass_limiter {
+            let bypass = config.bypass_limiter;
+            model
+                .stream
+                .send(move |engine| engine.engine_mut().set_limiter_bypass(bypass));
+        }
+        if config.cv != model.shell.config.cv {
+            // the stream first, the signals go to the new one
+            let _ = model
+                .stream
+                .set_channels(Some(stream_channels(&config, &model.spatial)));
+            let output = open_cv(&config, &model.cv, &model.spatial);
+            model.stream.send(move |engine| engine.set_output(output));
+        }
+        if config.speakers != model.shell.config.speakers {
+            let spatial = speakers::spatial(config.speakers.as_ref());
+            // the same channels under the same names carry on without one
+            if spatial.ports() == model.spatial.ports() {
+                let elevations = speakers::elevations(config.speakers.as_ref());
+                model.stream.send(move |engine| {
+                    let engine = engine.engine_mut();
+                    engine.set_layout(spatial.layout);
+                    engine.set_encoding(spatial.encoding, elevations);
+                });
+                model.spatial = spatial;
+            } else {
+                eprintln!("yfes: the new speaker layout takes effect on restart");
+            }
+        }
+        if config.impulse_path != model.shell.config.impulse_path {
+            // loaded and planned here, the audio thread only swaps it in
+            let reverb = open_reverb(&config, model.spatial.channels());
+            model
+                .stream
+                .send(move |engine| engine.engine_mut().set_reverb(reverb));
+        }
+        if config.dmx != model.shell.config.dmx {
+            model.dmx = open_dmx(&config);
+        }
+        if config.midi_out != model.shell.config.midi_out {
+            // releases what the old port holds
+            model.midi_out = None;
+            model.midi_notes = [None; dsp::NUM_VOICES];
+            model.midi_out = open_midi_out(&config);
+        }
+        config::reopen(
+            &share::settings(&model.shell.config),
+            &share::settings(&config),
+            &mut model.share,
+            || share::open("yfes", &config),
+        );
+        model.oscquery.reload(&config, &model.params);
+        model.serial.reload(&config, &model.params);
+        config::reopen(
+            &model.shell.config.timecode,
+            &config.timecode,
+            &mut model.timecode,
+            || open_timecode(&config),
+        );
+        model.shell.config = config;
+    }
+
+    let palette = model.shell.themes.current();
+    let ui = &mut model.ui.set_widgets();
+    param::sliders(&model.params, &mut model.param_ids, palette, ui);
+    model.link.panel(model.ids.link, palette, ui);
+    model
+        .transport
+        .panel(model.ids.transport, model.ids.tempo, palette, ui);
+    macros::knobs(
+        &mut model.macros,
+        &model.params,
+        &mut model.macro_ids,
+        palette,
+        ui,
+    );
+    if let Some(pitch) = &model.sample_pitch {
+        let name = tuning::note_name(tuning::freq_to_midi(pitch.freq).round() as i32);
+        for _click in widget::Button::new()
+            .w_h(200.0, 30.0)
+            .down(20.0)
+            .label(&format!("tune to sample ({})", name))
+            .label_font_size(15)
+            .themed(palette)
+            .border(0.0)
+            .set(model.ids.tune, ui)
+        {
+            model.params.set(dsp::TUNE, tune_to(pitch));
+        }
+    }
+    model.tasks.panel(palette, ui);
+    StereoMeter::new([model.meter.read(0), model.meter.read(1)])
+        .with_style(palette.meter_style())
+        .w_h(30.0, 200.0)
+        .top_right_with_margin(20.0)
+        .set(model.ids.meter, ui);
+
+    model.scope.update();
+    if let Some(settings) = Scope::new(model.scope.samples(), *model.scope.settings())
+        .with_style(palette.scope_style())
+        .w_h(300.0, 160.0)
+        .bottom_right_with_margin(20.0)
+        .set(model.ids.scope, ui)
+    {
+        model.scope.set_settings(settings);
+    }
+
+    if let Some(voices) = model.bus.latest() {
+        let active: usize = voices
+            .iter()
+            .filter(|voice| voice.active)
+            .map(|voice| voice.grains.active())
+            .sum();
+        let density = active as f32 / (NUM_GRAINS * dsp::NUM_VOICES) as f32;
+        model.cv.set(CV_DENSITY, density * cv::UNIPOLAR);
+        for (i, voice) in voices.iter().enumerate() {
+            model.cv.set(CV_VOICES + i, voice.level() * cv::UNIPOLAR);
+        }
+        if let Some(output) = &mut model.midi_out {
+            output.poll();
+            output.control_change(MIDI_DENSITY, density);
+            for (voice, held) in voices.iter().zip(model.midi_notes.iter_mut()) {
+                let note = if voice.active {
+                    midi::note(voice.pitch)
+                } else {
+                    None
+                };
+                if note != *held {
+                    if let Some(previous) = held.take() {
+                        output.note_off(previous);
+                    }
+                    if let Some(note) = note {
+                        output.note_on(note);
+                    }
+                    *held = note;
+                }
+            }
+        }
+        if let Some(dmx) = &mut model.dmx {
+            for (i, voice) in voices.iter().enumerate() {
+                let grains = if voice.active {
+                    voice.grains.active()
+                } else {
+                    0
+                };
+                dmx.set_level(i, grains as f32 / NUM_GRAINS as f32);
+            }
+        }
+        for (i, voice) in voices.clone().iter_mut().enumerate() {
+            for (j, grain) in voice.grains.grains.iter_mut().enumerate() {
+                let drawn = &mut model.grains[i * NUM_GRAINS + j];
+
+                drawn.active = grain.active && voice.active;
+                if !drawn.active {
+                    continue;
+                }
+
+                let x = win.w() * 0.40 * ((grain.pan * 2.0) - 1.0);
+                let y = match i {
+                    0 => win.h() * -0.30,
+                    1 => win.h() * -0.10,
+                    2 => win.h() * 0.10,
+                    3 => win.h() * 0.30,
+                    _ => win.h() * 0.0,
+                };
+
+                // what the grain plays over the next buffer, sets its size
+                let mut rms = 0.0;
+                let mut peak = 0.0f32;
+                for _ in (0..RESOLUTION).step_by(8) {
+                    let sample = grain.advance();
+                    let sample = sample.0 + sample.1;
+                    rms += sample.pow(2);
+                    peak = peak.max(sample);
+                }
+                let vol = grain.volume * 100.0;
+                drawn.center = pt2(x, y);
+                drawn.radius = map_range(peak.min(1.0), 0.0, 1.0, vol * 0.75, vol);
+
+                drawn.color = {
+                    let [r, g, b] = palette.accent(i).map(|c| (c * 255.0) as u8);
+                    rgba8(r, g, b, ((rms * INV_RESOLUTION).sqrt() * 1024.0) as u8)
+                };
+            }
+        }
+    }
+
+    if let Some(dmx) = &mut model.dmx {
+        if let Err(e) = dmx.update() {
+            eprintln!("yfes: {}", e);
+            model.dmx = None;
+        }
+    }
+}
+
+/// the grains playing, as centre, radius and colour
+fn circles(model: &Model) -> impl Iterator<Item = (Point2, f32, Rgba8)> + '_ {
+    model
+        .grains
+        .iter()
+        .filter(|grain| grain.active)
+        .map(|grain| (grain.center, grain.radius, grain.color))
+}
+
+fn background(model: &Model, draw: &Draw) {
+    draw.background()
+        .color(theme::color(model.shell.themes.current().background));
+}
+
+/// Everything but the UI, for screenshots, sharing and the output window.
+/// The main window draws the grains instanced instead, see `view`.
+fn scene(model: &Model, draw: &Draw) {
+    background(model, draw);
+    for (center, radius, color) in circles(model) {
+        draw.ellipse().xy(center).radius(radius).color(color);
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    if let Some(screen) = model.shell.setup() {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    if let Some(screen) = &model.errors {
+        screen.draw(&draw, app.window_rect(), model.shell.themes.current());
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+    background(model, &draw);
+    draw.to_frame(app, &frame).unwrap();
+    model
+        .circles
+        .draw(&app.main_window(), &frame, circles(model));
+    model.ui.draw_to_frame(app, &frame).unwrap();
+
+    let overlay = app.draw();
+    model
+        .hud
+        .draw(&overlay, app.window_rect(), model.shell.themes.current());
+    if let Some(insert) = model.stream.insert() {
+        insert.draw(&overlay, app.window_rect(), model.shell.themes.current());
+    }
+    overlay.to_frame(app, &frame).unwrap();
+}
+
+/// the scene alone, in the main window's coordinates
+fn output_view(app: &App, model: &Model, frame: Frame) {
+    if let Some(output) = &model.output {
+        let draw = output.draw(app);
+        scene(model, &draw);
+        draw.to_frame(app, &frame).unwrap();
+    }
+}
@@ -0,0 +1,16 @@
+#version 450
+
+// the unit circle, the same for every instance
+layout(location = 0) in vec2 position;
+
+// per grain, in normalized device coordinates
+layout(location = 1) in vec2 center;
+layout(location = 2) in vec2 radius;
+layout(location = 3) in vec4 color;
+
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = vec4(center + position * radius, 0.0, 1.0);
+}